@@ -0,0 +1,162 @@
+//! External match audit log API endpoints
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ---------
+// | Paths |
+// ---------
+
+/// The path to query the external match audit log
+///
+/// GET /v0/audit-log/external-matches
+pub const EXTERNAL_MATCH_AUDIT_LOG_PATH: &str = "/v0/audit-log/external-matches";
+/// The path to export the external match audit log as CSV
+///
+/// GET /v0/audit-log/external-matches/csv
+pub const EXTERNAL_MATCH_AUDIT_LOG_CSV_PATH: &str = "/v0/audit-log/external-matches/csv";
+
+// --------------------------
+// | Request/Response Types |
+// --------------------------
+
+/// The filters for an audit log query, passed as query parameters
+///
+/// All filters are optional; an unset filter matches every entry
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuditLogQuery {
+    /// Only return entries for the given API key
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// Only return entries whose base asset matches, given as a mint address
+    /// or a ticker
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// Only return entries of the given type
+    #[serde(default)]
+    pub event_type: Option<AuditEventType>,
+    /// Only return entries recorded at or after this time
+    ///
+    /// In milliseconds since epoch
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    /// Only return entries recorded before this time
+    ///
+    /// In milliseconds since epoch
+    #[serde(default)]
+    pub end_time: Option<u64>,
+    /// The maximum number of entries to return
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+/// A response containing audit log entries, newest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogResponse {
+    /// The matching entries
+    pub entries: Vec<AuditLogEntry>,
+}
+
+// -------------
+// | API Types |
+// -------------
+
+/// The stage of an external match that an audit log entry records
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    /// A quote was sent to the client
+    Quote,
+    /// A match bundle was assembled for the client
+    Assemble,
+    /// A match bundle settled on-chain
+    Settlement,
+}
+
+impl AuditEventType {
+    /// The string representation of the event type, as stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::Quote => "quote",
+            AuditEventType::Assemble => "assemble",
+            AuditEventType::Settlement => "settlement",
+        }
+    }
+}
+
+impl fmt::Display for AuditEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditEventType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quote" => Ok(AuditEventType::Quote),
+            "assemble" => Ok(AuditEventType::Assemble),
+            "settlement" => Ok(AuditEventType::Settlement),
+            _ => Err(format!("invalid audit event type: {s}")),
+        }
+    }
+}
+
+/// An entry in the external match audit log
+///
+/// Amounts are from the external party's perspective, in base units of the
+/// respective mint
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    /// The entry id
+    pub id: i64,
+    /// The ID of the chain the match was made on
+    pub chain_id: u64,
+    /// The stage of the external match the entry records
+    pub event_type: AuditEventType,
+    /// The API key that made the request, if known
+    pub api_key_id: Option<Uuid>,
+    /// The description of the API key that made the request
+    pub key_description: String,
+    /// The id of the request
+    pub request_id: String,
+    /// The id of the bundle, for assemble and settlement entries
+    pub bundle_id: Option<String>,
+    /// The base mint of the match
+    pub base_mint: String,
+    /// The mint sent by the external party
+    pub input_mint: String,
+    /// The mint received by the external party
+    pub output_mint: String,
+    /// The amount sent by the external party
+    ///
+    /// For assemble entries this is the maximum send amount of the bundle
+    pub input_amount: u128,
+    /// The amount received by the external party
+    ///
+    /// For assemble entries this is the maximum receive amount of the bundle
+    pub output_amount: u128,
+    /// The price of the match in quote units per base unit, decimal corrected
+    pub price: Option<f64>,
+    /// The time at which the price was sampled
+    ///
+    /// In milliseconds since epoch
+    pub price_timestamp: Option<u64>,
+    /// Whether the match was gas sponsored
+    pub is_sponsored: bool,
+    /// The gas sponsorship refund amount, if sponsored
+    pub refund_amount: Option<u128>,
+    /// The settlement transaction hash, for settlement entries
+    pub tx_hash: Option<String>,
+    /// The time between the price sample and assembly, in milliseconds
+    pub assembly_delay_ms: Option<u64>,
+    /// The time between the price sample and settlement, in milliseconds
+    pub settlement_delay_ms: Option<u64>,
+    /// The time at which the entry was recorded
+    ///
+    /// In milliseconds since epoch
+    pub created_at: u64,
+}
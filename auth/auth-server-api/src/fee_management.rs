@@ -52,10 +52,119 @@ pub struct GetAllFeesResponse {
     pub default_fees: Vec<AssetDefaultFeeEntry>,
 }
 
+/// A request to set a fee tier, replacing any tier on the same asset and
+/// volume threshold
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetFeeTierRequest {
+    /// The tier to set
+    #[serde(flatten)]
+    pub tier: FeeTier,
+}
+
+/// A request to remove a fee tier
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoveFeeTierRequest {
+    /// The asset ticker of the tier, or `None` for a tier on all assets
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// The volume threshold of the tier
+    pub min_volume_usd: f64,
+}
+
+/// A request to add a promotional fee
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddPromotionalFeeRequest {
+    /// The API key the promotion applies to, or `None` for all keys
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// The asset ticker the promotion applies to, or `None` for all assets
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// The promotional fee rate as a floating point value
+    pub fee: f32,
+    /// The time at which the promotion starts
+    ///
+    /// In seconds since epoch
+    pub starts_at: u64,
+    /// The time at which the promotion ends
+    ///
+    /// In seconds since epoch
+    pub ends_at: u64,
+    /// A description of the promotion
+    pub description: String,
+}
+
+/// The response to adding a promotional fee
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddPromotionalFeeResponse {
+    /// The ID of the new promotion
+    pub id: i32,
+}
+
+/// A request to remove a promotional fee
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemovePromotionalFeeRequest {
+    /// The ID of the promotion
+    pub id: i32,
+}
+
+/// Response containing the volume-based fee schedule
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetFeeScheduleResponse {
+    /// All fee tiers
+    pub tiers: Vec<FeeTier>,
+    /// All promotional fees, including those that have ended
+    pub promotions: Vec<PromotionalFee>,
+}
+
 // -------------
 // | API Types |
 // -------------
 
+/// A fee tier, applied to keys whose trailing 30-day settled volume is at
+/// least the tier's threshold
+///
+/// A key is charged the fee of the highest tier it qualifies for, preferring
+/// tiers on the traded asset over tiers on all assets. Per-user overrides take
+/// precedence over tiers, and tiers take precedence over asset default fees
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeeTier {
+    /// The asset ticker the tier applies to, or `None` for all assets
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// The minimum trailing 30-day settled volume to qualify for the tier, in
+    /// USD
+    pub min_volume_usd: f64,
+    /// The fee rate as a floating point value
+    pub fee: f32,
+}
+
+/// A time-boxed promotional fee
+///
+/// While active, a promotion caps the fee charged to the keys and assets it
+/// applies to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PromotionalFee {
+    /// The ID of the promotion
+    pub id: i32,
+    /// The API key the promotion applies to, or `None` for all keys
+    pub api_key_id: Option<Uuid>,
+    /// The asset ticker the promotion applies to, or `None` for all assets
+    pub asset: Option<String>,
+    /// The promotional fee rate as a floating point value
+    pub fee: f32,
+    /// The time at which the promotion starts
+    ///
+    /// In seconds since epoch
+    pub starts_at: u64,
+    /// The time at which the promotion ends
+    ///
+    /// In seconds since epoch
+    pub ends_at: u64,
+    /// A description of the promotion
+    pub description: String,
+}
+
 /// A user-specific fee override entry
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserFeeEntry {
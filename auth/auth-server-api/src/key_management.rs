@@ -1,8 +1,85 @@
 //! Key management API endpoints
 
+use std::{fmt, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// A set of endpoints an API key may be permitted to call
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    /// Fetching external match quotes
+    Quote,
+    /// Assembling external match bundles
+    Assemble,
+    /// The RFQ-T levels and quote endpoints
+    Rfqt,
+    /// The OKX Market Maker pricing and quote endpoints
+    Okx,
+    /// Market, market depth, and exchange metadata endpoints
+    Markets,
+}
+
+impl ApiKeyScope {
+    /// The string representation of the scope, as stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Quote => "quote",
+            ApiKeyScope::Assemble => "assemble",
+            ApiKeyScope::Rfqt => "rfqt",
+            ApiKeyScope::Okx => "okx",
+            ApiKeyScope::Markets => "markets",
+        }
+    }
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quote" => Ok(ApiKeyScope::Quote),
+            "assemble" => Ok(ApiKeyScope::Assemble),
+            "rfqt" => Ok(ApiKeyScope::Rfqt),
+            "okx" => Ok(ApiKeyScope::Okx),
+            "markets" => Ok(ApiKeyScope::Markets),
+            _ => Err(format!("invalid API key scope: {s}")),
+        }
+    }
+}
+
+/// The permissions attached to an API key
+///
+/// Each restriction is optional; an unset restriction permits everything.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyPermissions {
+    /// The scopes the key may call, or all scopes if unset
+    #[serde(default)]
+    pub scopes: Option<Vec<ApiKeyScope>>,
+    /// The base mints the key may trade or query, or all mints if unset
+    #[serde(default)]
+    pub allowed_mints: Option<Vec<String>>,
+    /// The base mints the key may not trade or query
+    #[serde(default)]
+    pub denied_mints: Option<Vec<String>>,
+    /// The time after which the key is no longer valid
+    ///
+    /// In seconds since epoch
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// The IP addresses or CIDR ranges from which the key may be used, or
+    /// any address if unset
+    #[serde(default)]
+    pub allowed_ips: Option<Vec<String>>,
+}
+
 /// An API key entry
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiKey {
@@ -18,6 +95,17 @@ pub struct ApiKey {
     ///
     /// In seconds since epoch
     pub created_at: u64,
+    /// The permissions attached to the API key
+    #[serde(default)]
+    pub permissions: ApiKeyPermissions,
+    /// The generation of the key's current secret
+    ///
+    /// Starts at zero and is incremented each time the secret is rotated
+    #[serde(default)]
+    pub secret_generation: u32,
+    /// The URL to which the key's webhook notifications are delivered, if set
+    #[serde(default)]
+    pub webhook_url: Option<String>,
 }
 
 /// A response containing all API keys
@@ -6,11 +6,19 @@
 #![deny(clippy::needless_pass_by_ref_mut)]
 #![feature(trivial_bounds)]
 
+pub mod audit_log;
 pub mod fee_management;
 pub mod key_management;
+pub mod quote_analytics;
 pub mod rfqt;
+pub mod rfqt_ladders;
+pub mod sponsorship_policies;
+pub mod usage;
+pub mod volume_limits;
+pub mod webhooks;
 
 use alloy_primitives::{Address, U256, ruint::FromUintError};
+use key_management::ApiKeyPermissions;
 use renegade_external_api::types::{ApiSignedQuote, BoundedExternalMatchApiBundle};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -36,6 +44,16 @@ pub const DEACTIVATE_API_KEY_PATH: &str = "/api-keys/{id}/deactivate";
 /// POST /api-keys/{id}/rate-limit
 pub const SET_RATE_LIMIT_PATH: &str = "/api-keys/{id}/rate-limit";
 
+/// The path to set the permissions of an API key
+///
+/// POST /api-keys/{id}/permissions
+pub const SET_API_KEY_PERMISSIONS_PATH: &str = "/api-keys/{id}/permissions";
+
+/// The path to rotate the secret of an API key
+///
+/// POST /api-keys/{id}/rotate-secret
+pub const ROTATE_API_KEY_SECRET_PATH: &str = "/api-keys/{id}/rotate-secret";
+
 /// A request to create a new API key
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateApiKeyRequest {
@@ -47,6 +65,44 @@ pub struct CreateApiKeyRequest {
     pub secret: String,
     /// A description of the API key's purpose
     pub description: String,
+    /// The permissions attached to the API key, unrestricted by default
+    #[serde(default)]
+    pub permissions: ApiKeyPermissions,
+}
+
+/// A request to set the permissions of an API key
+///
+/// The given permissions replace the key's existing permissions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetApiKeyPermissionsRequest {
+    /// The permissions to attach to the API key
+    pub permissions: ApiKeyPermissions,
+}
+
+/// A request to rotate the secret of an API key
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RotateApiKeySecretRequest {
+    /// How long the previous secret remains valid after rotation, in seconds
+    ///
+    /// Defaults to the server's configured grace period. A value of zero
+    /// invalidates the previous secret immediately
+    #[serde(default)]
+    pub grace_period_secs: Option<u64>,
+}
+
+/// The response to an API key secret rotation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateApiKeySecretResponse {
+    /// The new API key secret
+    ///
+    /// Encoded as a base64 string
+    pub secret: String,
+    /// The generation of the new secret
+    pub generation: u32,
+    /// The time at which the previous secret stops being accepted
+    ///
+    /// In seconds since epoch
+    pub previous_secret_expires_at: u64,
 }
 
 /// A request to set a rate limit for an API key
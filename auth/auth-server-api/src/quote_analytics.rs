@@ -0,0 +1,95 @@
+//! Quote lifecycle analytics API endpoints
+//!
+//! Every quote, assembly, and settlement is correlated into a single lifecycle
+//! entry; quotes and assemblies are linked by the quote's signature, and
+//! assemblies and settlements by bundle ID. The endpoints here aggregate those
+//! entries into a per-key, per-market funnel
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ---------
+// | Paths |
+// ---------
+
+/// The path to query quote funnel stats
+///
+/// GET /v0/analytics/quote-funnel
+pub const QUOTE_FUNNEL_PATH: &str = "/v0/analytics/quote-funnel";
+
+// --------------------------
+// | Request/Response Types |
+// --------------------------
+
+/// The filters for a quote funnel query, passed as query parameters
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QuoteFunnelQuery {
+    /// Only return stats for the given API key
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// Only return stats for markets whose base asset matches, given as a mint
+    /// address or a ticker
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// Only include lifecycles started at or after this time
+    ///
+    /// In milliseconds since epoch, defaults to one day before the end time
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    /// Only include lifecycles started before this time
+    ///
+    /// In milliseconds since epoch, defaults to the current time
+    #[serde(default)]
+    pub end_time: Option<u64>,
+}
+
+/// A response containing quote funnel stats per key and market
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuoteFunnelResponse {
+    /// The start of the range included in the response, in milliseconds since
+    /// epoch
+    pub start_time: u64,
+    /// The end of the range included in the response, in milliseconds since
+    /// epoch
+    pub end_time: u64,
+    /// The stats for each key and market with activity in the range
+    pub markets: Vec<QuoteFunnelStats>,
+}
+
+// -------------
+// | API Types |
+// -------------
+
+/// Quote funnel stats for a single key and market
+///
+/// Price moves are in basis points from the external party's perspective; a
+/// positive move is in the external party's favor
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuoteFunnelStats {
+    /// The API key id
+    pub api_key_id: Uuid,
+    /// The description of the API key
+    pub key_description: String,
+    /// The ID of the chain the market is on
+    pub chain_id: u64,
+    /// The mint of the market's base asset
+    pub base_mint: String,
+    /// The number of quotes sent
+    pub quote_count: u64,
+    /// The number of quotes that were assembled into a bundle
+    pub quotes_assembled: u64,
+    /// The number of bundles assembled, including direct order assemblies
+    pub assembled_count: u64,
+    /// The number of assembled bundles that settled
+    pub settled_count: u64,
+    /// The fraction of quotes that were assembled
+    pub assembly_rate: Option<f64>,
+    /// The fraction of assembled bundles that settled
+    pub settlement_rate: Option<f64>,
+    /// The mean move of the reference price at settlement away from the quoted
+    /// price, over settled quotes
+    pub avg_quote_to_settlement_bps: Option<f64>,
+    /// The mean move of the reference price after settlement away from the
+    /// settled price, measured at the markout horizon
+    pub avg_markout_bps: Option<f64>,
+}
@@ -0,0 +1,119 @@
+//! RFQT price ladder API endpoints
+//!
+//! A ladder splits each side of a pair's depth into size buckets and quotes
+//! each bucket at a progressively wider price, so that aggregators can route
+//! larger orders at a price reflecting their size. A ladder may be set per API
+//! key, or as the default for all keys
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ---------
+// | Paths |
+// ---------
+
+/// The path to list all configured RFQT ladders
+///
+/// GET /v0/rfqt-ladders
+pub const GET_RFQT_LADDERS_PATH: &str = "/v0/rfqt-ladders";
+/// The path to set an RFQT ladder
+///
+/// POST /v0/rfqt-ladders/set
+pub const SET_RFQT_LADDER_PATH: &str = "/v0/rfqt-ladders/set";
+/// The path to remove an RFQT ladder
+///
+/// POST /v0/rfqt-ladders/remove
+pub const REMOVE_RFQT_LADDER_PATH: &str = "/v0/rfqt-ladders/remove";
+
+// --------------------------
+// | Request/Response Types |
+// --------------------------
+
+/// A request to set an RFQT ladder, replacing any ladder on the same key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetRfqtLadderRequest {
+    /// The ladder to set
+    #[serde(flatten)]
+    pub ladder: RfqtLadder,
+}
+
+/// A request to remove an RFQT ladder
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoveRfqtLadderRequest {
+    /// The API key the ladder applies to, or `None` for the default ladder
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+}
+
+/// A response containing all configured RFQT ladders
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetRfqtLaddersResponse {
+    /// The ladders
+    pub ladders: Vec<RfqtLadder>,
+}
+
+// -------------
+// | API Types |
+// -------------
+
+/// The shape of the price ladder quoted on `GET /rfqt/v3/levels`
+///
+/// Each side's depth is split at the given cumulative size fractions. The
+/// bucket ending at fraction `f` is quoted `base_spread_bps + impact_bps *
+/// f^impact_exponent` away from the midpoint, and the whole ladder is then
+/// shifted by `skew_bps`
+///
+/// The ladder is an advertisement only: firm quotes on `POST /rfqt/v3/quote`
+/// are priced by the relayer and are neither widened nor skewed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RfqtLadder {
+    /// The API key the ladder applies to, or `None` for the default ladder
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// The cumulative fractions of each side's depth at which buckets end
+    ///
+    /// Strictly ascending, each in (0, 1]
+    pub size_fractions: Vec<f64>,
+    /// The spread applied to every bucket, in basis points
+    pub base_spread_bps: f64,
+    /// The additional spread applied to a bucket ending at the full depth, in
+    /// basis points
+    pub impact_bps: f64,
+    /// The exponent of the impact curve, 1 for a linear curve
+    #[serde(default = "default_impact_exponent")]
+    pub impact_exponent: f64,
+    /// The shift applied to both sides of the ladder, in basis points
+    ///
+    /// A positive skew raises both bid and ask prices. Its magnitude may not
+    /// exceed `base_spread_bps`, so that no bucket is quoted better than the
+    /// midpoint a firm quote is priced at
+    #[serde(default)]
+    pub skew_bps: f64,
+}
+
+impl Default for RfqtLadder {
+    /// A single level at the midpoint covering the full depth
+    fn default() -> Self {
+        Self {
+            api_key_id: None,
+            size_fractions: vec![1.],
+            base_spread_bps: 0.,
+            impact_bps: 0.,
+            impact_exponent: default_impact_exponent(),
+            skew_bps: 0.,
+        }
+    }
+}
+
+impl RfqtLadder {
+    /// The offset of a bucket's price from the midpoint before skew, in basis
+    /// points
+    pub fn widening_bps(&self, size_fraction: f64) -> f64 {
+        self.base_spread_bps + self.impact_bps * size_fraction.powf(self.impact_exponent)
+    }
+}
+
+/// The default impact exponent, a linear impact curve
+fn default_impact_exponent() -> f64 {
+    1.
+}
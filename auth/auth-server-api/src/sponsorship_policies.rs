@@ -0,0 +1,107 @@
+//! Gas sponsorship policy API endpoints
+//!
+//! Sponsorship policies control gas sponsorship per API key, per base asset,
+//! or per key and asset. A policy only overrides the fields it sets; unset
+//! fields fall through to less specific policies and then to the server's
+//! defaults
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ---------
+// | Paths |
+// ---------
+
+/// The path to list all configured sponsorship policies
+///
+/// GET /v0/sponsorship-policies
+pub const GET_SPONSORSHIP_POLICIES_PATH: &str = "/v0/sponsorship-policies";
+/// The path to set a sponsorship policy
+///
+/// POST /v0/sponsorship-policies/set
+pub const SET_SPONSORSHIP_POLICY_PATH: &str = "/v0/sponsorship-policies/set";
+/// The path to remove a sponsorship policy
+///
+/// POST /v0/sponsorship-policies/remove
+pub const REMOVE_SPONSORSHIP_POLICY_PATH: &str = "/v0/sponsorship-policies/remove";
+
+// --------------------------
+// | Request/Response Types |
+// --------------------------
+
+/// A request to set a sponsorship policy, replacing any policy on the same
+/// scope
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetSponsorshipPolicyRequest {
+    /// The policy to set
+    #[serde(flatten)]
+    pub policy: SponsorshipPolicy,
+}
+
+/// A request to remove a sponsorship policy
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoveSponsorshipPolicyRequest {
+    /// The API key the policy applies to, or `None` for all keys
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// The ticker of the base asset the policy applies to, or `None` for all
+    /// assets
+    #[serde(default)]
+    pub asset: Option<String>,
+}
+
+/// A response containing all configured sponsorship policies
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetSponsorshipPoliciesResponse {
+    /// The policies
+    pub policies: Vec<SponsorshipPolicy>,
+}
+
+// -------------
+// | API Types |
+// -------------
+
+/// A gas sponsorship policy
+///
+/// A policy must name an API key, an asset, or both. Sponsorship is disabled
+/// if any applicable policy disables it. Otherwise each limit is taken from
+/// the most specific policy that sets it: the key and asset, then the key,
+/// then the asset
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SponsorshipPolicy {
+    /// The API key the policy applies to, or `None` for all keys
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// The ticker of the base asset the policy applies to, or `None` for all
+    /// assets
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// Whether gas sponsorship is enabled
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// The maximum refund per bundle, in USD
+    ///
+    /// Refunds above the maximum are capped rather than dropped
+    #[serde(default)]
+    pub max_refund_usd: Option<f64>,
+    /// The maximum value of gas sponsored per day, in USD
+    ///
+    /// A key's budget replaces the server's default per-key budget. Asset
+    /// budgets are shared by all keys trading the asset
+    #[serde(default)]
+    pub daily_budget_usd: Option<f64>,
+    /// The minimum order size for which gas is sponsored, in USD
+    #[serde(default)]
+    pub min_order_size_usd: Option<f64>,
+    /// The tickers of the assets in which refunds may be paid, or `None` for
+    /// any asset
+    ///
+    /// Native ETH refunds are paid in `ETH`
+    #[serde(default)]
+    pub allowed_refund_assets: Option<Vec<String>>,
+}
+
+/// Sponsorship is enabled unless a policy disables it
+fn default_enabled() -> bool {
+    true
+}
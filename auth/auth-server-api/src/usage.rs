@@ -0,0 +1,105 @@
+//! Per-API-key usage API endpoints
+//!
+//! Usage is aggregated per key by UTC day, and is intended for billing and
+//! ranking integrators
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ---------
+// | Paths |
+// ---------
+
+/// The path to query per-key usage
+///
+/// GET /v0/usage
+pub const API_KEY_USAGE_PATH: &str = "/v0/usage";
+
+// --------------------------
+// | Request/Response Types |
+// --------------------------
+
+/// The filters for a usage query, passed as query parameters
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UsageQuery {
+    /// Only return usage for the given API key
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// The first day to include, inclusive, formatted as `YYYY-MM-DD`
+    ///
+    /// Defaults to the first day of the current month
+    #[serde(default)]
+    pub start_date: Option<String>,
+    /// The last day to include, inclusive, formatted as `YYYY-MM-DD`
+    ///
+    /// Defaults to the current day
+    #[serde(default)]
+    pub end_date: Option<String>,
+}
+
+/// A response containing per-key usage, ranked by settled volume
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageResponse {
+    /// The first day included in the response, formatted as `YYYY-MM-DD`
+    pub start_date: String,
+    /// The last day included in the response, formatted as `YYYY-MM-DD`
+    pub end_date: String,
+    /// The usage of each key with activity in the range
+    pub keys: Vec<ApiKeyUsage>,
+}
+
+// -------------
+// | API Types |
+// -------------
+
+/// The usage of a single API key over a date range
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ApiKeyUsage {
+    /// The API key id
+    pub api_key_id: Uuid,
+    /// The description of the API key
+    pub description: String,
+    /// The usage summed over the date range
+    pub totals: UsageCounters,
+    /// The usage on each day with activity, oldest first
+    pub daily: Vec<DailyUsage>,
+}
+
+/// The usage of an API key on a single day
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DailyUsage {
+    /// The UTC day, formatted as `YYYY-MM-DD`
+    pub date: String,
+    /// The usage on the day
+    #[serde(flatten)]
+    pub usage: UsageCounters,
+}
+
+/// Aggregated usage counters
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageCounters {
+    /// The number of quotes served
+    pub quote_count: u64,
+    /// The number of bundles assembled
+    pub bundle_count: u64,
+    /// The number of bundles settled on-chain
+    pub settlement_count: u64,
+    /// The settled volume, in USD
+    pub settled_volume_usd: f64,
+    /// The relayer and protocol fees paid on settled volume, in USD
+    pub fees_paid_usd: f64,
+    /// The value of gas sponsored on settled bundles, in USD
+    pub gas_sponsored_usd: f64,
+}
+
+impl UsageCounters {
+    /// Add another set of counters into this one
+    pub fn accumulate(&mut self, other: &UsageCounters) {
+        self.quote_count += other.quote_count;
+        self.bundle_count += other.bundle_count;
+        self.settlement_count += other.settlement_count;
+        self.settled_volume_usd += other.settled_volume_usd;
+        self.fees_paid_usd += other.fees_paid_usd;
+        self.gas_sponsored_usd += other.gas_sponsored_usd;
+    }
+}
@@ -0,0 +1,128 @@
+//! Volume limit API endpoints
+//!
+//! Volume limits cap the notional USD volume assembled over a sliding window,
+//! either per API key (optionally per asset), or globally per asset across all
+//! keys. A limit applies across all served chains unless it names a chain
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ---------
+// | Paths |
+// ---------
+
+/// The path to list all configured volume limits
+///
+/// GET /v0/volume-limits
+pub const GET_VOLUME_LIMITS_PATH: &str = "/v0/volume-limits";
+/// The path to set a volume limit
+///
+/// POST /v0/volume-limits/set
+pub const SET_VOLUME_LIMIT_PATH: &str = "/v0/volume-limits/set";
+/// The path to remove a volume limit
+///
+/// POST /v0/volume-limits/remove
+pub const REMOVE_VOLUME_LIMIT_PATH: &str = "/v0/volume-limits/remove";
+
+// --------------------------
+// | Request/Response Types |
+// --------------------------
+
+/// A request to set a volume limit, replacing any limit on the same scope and
+/// window
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetVolumeLimitRequest {
+    /// The limit to set
+    #[serde(flatten)]
+    pub limit: VolumeLimit,
+}
+
+/// A request to remove a volume limit
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoveVolumeLimitRequest {
+    /// The API key the limit applies to, or `None` for a global limit
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// The ticker of the base asset the limit applies to, or `None` for all
+    /// assets
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// The ID of the chain the limit applies to, or `None` for all chains
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    /// The window of the limit
+    pub window: VolumeLimitWindow,
+}
+
+/// A response containing all configured volume limits
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetVolumeLimitsResponse {
+    /// The limits
+    pub limits: Vec<VolumeLimit>,
+}
+
+// -------------
+// | API Types |
+// -------------
+
+/// A cap on the notional volume assembled over a sliding window
+///
+/// A limit must name an API key, an asset, or both. A limit naming only an
+/// asset is a global cap on that asset across all keys
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VolumeLimit {
+    /// The API key the limit applies to, or `None` for a global limit
+    #[serde(default)]
+    pub api_key_id: Option<Uuid>,
+    /// The ticker of the base asset the limit applies to, or `None` for all
+    /// assets
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// The ID of the chain the limit applies to, or `None` for all chains
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    /// The window over which volume is measured
+    pub window: VolumeLimitWindow,
+    /// The maximum notional volume over the window, in USD
+    pub max_volume_usd: f64,
+}
+
+/// The window over which a volume limit is measured
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeLimitWindow {
+    /// A sliding one hour window
+    Hour,
+    /// A sliding one day window
+    Day,
+}
+
+impl VolumeLimitWindow {
+    /// The string representation of the window, as stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VolumeLimitWindow::Hour => "hour",
+            VolumeLimitWindow::Day => "day",
+        }
+    }
+
+    /// The length of the window, in milliseconds
+    pub fn duration_ms(&self) -> u64 {
+        match self {
+            VolumeLimitWindow::Hour => 60 * 60 * 1000,
+            VolumeLimitWindow::Day => 24 * 60 * 60 * 1000,
+        }
+    }
+}
+
+impl std::str::FromStr for VolumeLimitWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hour" => Ok(VolumeLimitWindow::Hour),
+            "day" => Ok(VolumeLimitWindow::Day),
+            _ => Err(format!("invalid volume limit window: {s}")),
+        }
+    }
+}
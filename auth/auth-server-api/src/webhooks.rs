@@ -0,0 +1,206 @@
+//! Partner webhook API types
+//!
+//! Webhook notifications are POSTed as JSON to the URL configured on an API
+//! key. Each request carries a timestamp header and a signature header; the
+//! signature is the base64 encoded HMAC-SHA256, keyed by the API key's current
+//! secret, of the string `{timestamp}.{body}`
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// ---------
+// | Paths |
+// ---------
+
+/// The path to set the webhook URL of an API key
+///
+/// POST /api-keys/{id}/webhook
+pub const SET_WEBHOOK_PATH: &str = "/api-keys/{id}/webhook";
+/// The path to list the webhook deliveries of an API key
+///
+/// GET /api-keys/{id}/webhook-deliveries
+pub const WEBHOOK_DELIVERIES_PATH: &str = "/api-keys/{id}/webhook-deliveries";
+
+// -----------
+// | Headers |
+// -----------
+
+/// The header containing the time at which a webhook request was signed
+///
+/// In milliseconds since epoch
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "X-Renegade-Webhook-Timestamp";
+/// The header containing the signature of a webhook request
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Renegade-Webhook-Signature";
+
+// --------------------------
+// | Request/Response Types |
+// --------------------------
+
+/// A request to set the webhook URL of an API key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetWebhookRequest {
+    /// The URL to deliver notifications to, or `None` to disable webhooks
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// The query parameters for listing webhook deliveries
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WebhookDeliveriesQuery {
+    /// The maximum number of deliveries to return
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+/// A response containing webhook deliveries, newest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDeliveriesResponse {
+    /// The deliveries
+    pub deliveries: Vec<WebhookDelivery>,
+}
+
+// -------------
+// | API Types |
+// -------------
+
+/// The body of a webhook notification
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// The id of the delivery, unique per notification and stable across
+    /// retries
+    pub delivery_id: i64,
+    /// The API key the notification is for
+    pub api_key_id: Uuid,
+    /// The event being notified
+    pub event: WebhookEvent,
+}
+
+/// An event notified via webhook
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// An assembled bundle settled on-chain
+    BundleSettled {
+        /// The id of the bundle
+        bundle_id: String,
+        /// The id of the request that assembled the bundle
+        request_id: String,
+        /// The settlement transaction hash
+        tx_hash: String,
+        /// The mint sent by the external party
+        input_mint: String,
+        /// The mint received by the external party
+        output_mint: String,
+        /// The amount sent by the external party
+        input_amount: u128,
+        /// The amount received by the external party, before any refund
+        output_amount: u128,
+        /// The time of settlement, in milliseconds since epoch
+        settled_at: u64,
+    },
+    /// A gas sponsorship refund was paid out when a bundle settled
+    GasRefund {
+        /// The id of the bundle
+        bundle_id: String,
+        /// The settlement transaction hash
+        tx_hash: String,
+        /// The mint of the refund, or the native asset address if the refund
+        /// was paid in native ETH
+        refund_mint: String,
+        /// Whether the refund was paid in native ETH
+        refund_native_eth: bool,
+        /// The amount refunded
+        refund_amount: u128,
+    },
+    /// An assembled bundle passed its deadline without settling
+    BundleExpired {
+        /// The id of the bundle
+        bundle_id: String,
+        /// The id of the request that assembled the bundle
+        request_id: String,
+        /// The deadline of the bundle, in seconds since epoch
+        deadline: u64,
+    },
+}
+
+impl WebhookEvent {
+    /// The name of the event type, as used in the payload's `type` field
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            WebhookEvent::BundleSettled { .. } => "bundle_settled",
+            WebhookEvent::GasRefund { .. } => "gas_refund",
+            WebhookEvent::BundleExpired { .. } => "bundle_expired",
+        }
+    }
+
+    /// The id of the bundle the event concerns
+    pub fn bundle_id(&self) -> &str {
+        match self {
+            WebhookEvent::BundleSettled { bundle_id, .. }
+            | WebhookEvent::GasRefund { bundle_id, .. }
+            | WebhookEvent::BundleExpired { bundle_id, .. } => bundle_id,
+        }
+    }
+}
+
+/// The state of a webhook delivery
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookDeliveryStatus {
+    /// The delivery is being attempted
+    Pending,
+    /// The receiver acknowledged the delivery with a 2xx response
+    Delivered,
+    /// Every delivery attempt failed
+    Failed,
+}
+
+impl WebhookDeliveryStatus {
+    /// The string representation of the status, as stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookDeliveryStatus::Pending => "pending",
+            WebhookDeliveryStatus::Delivered => "delivered",
+            WebhookDeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for WebhookDeliveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(WebhookDeliveryStatus::Pending),
+            "delivered" => Ok(WebhookDeliveryStatus::Delivered),
+            "failed" => Ok(WebhookDeliveryStatus::Failed),
+            _ => Err(format!("invalid webhook delivery status: {s}")),
+        }
+    }
+}
+
+/// An entry in the webhook delivery log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    /// The id of the delivery
+    pub id: i64,
+    /// The type of the event delivered
+    pub event_type: String,
+    /// The id of the bundle the event concerns
+    pub bundle_id: String,
+    /// The URL the delivery was sent to
+    pub url: String,
+    /// The state of the delivery
+    pub status: WebhookDeliveryStatus,
+    /// The number of delivery attempts made
+    pub attempts: u32,
+    /// The HTTP status code of the most recent attempt, if a response was
+    /// received
+    pub last_status_code: Option<u16>,
+    /// The error of the most recent attempt, if it failed
+    pub last_error: Option<String>,
+    /// The time at which the delivery was created, in milliseconds since epoch
+    pub created_at: u64,
+    /// The time of the most recent attempt, in milliseconds since epoch
+    pub updated_at: u64,
+}
@@ -0,0 +1,7 @@
+-- Drop the API key permission columns
+ALTER TABLE api_keys
+    DROP COLUMN scopes,
+    DROP COLUMN allowed_mints,
+    DROP COLUMN denied_mints,
+    DROP COLUMN expires_at,
+    DROP COLUMN allowed_ips;
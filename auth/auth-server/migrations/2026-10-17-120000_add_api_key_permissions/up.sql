@@ -0,0 +1,7 @@
+-- Add the API key permission columns; a NULL restriction permits everything
+ALTER TABLE api_keys
+    ADD COLUMN scopes TEXT[],
+    ADD COLUMN allowed_mints TEXT[],
+    ADD COLUMN denied_mints TEXT[],
+    ADD COLUMN expires_at TIMESTAMP,
+    ADD COLUMN allowed_ips TEXT[];
@@ -0,0 +1,5 @@
+-- Drop the api_key_secrets table
+DROP TABLE IF EXISTS api_key_secrets;
+
+-- Drop the secret generation column
+ALTER TABLE api_keys DROP COLUMN secret_generation;
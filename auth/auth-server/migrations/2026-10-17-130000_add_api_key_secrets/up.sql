@@ -0,0 +1,12 @@
+-- Track the generation of each API key's current secret
+ALTER TABLE api_keys ADD COLUMN secret_generation INTEGER NOT NULL DEFAULT 0;
+
+-- Previous API key secrets, accepted until they expire
+CREATE TABLE api_key_secrets (
+    api_key_id UUID NOT NULL,
+    generation INTEGER NOT NULL,
+    encrypted_key VARCHAR NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (api_key_id, generation),
+    FOREIGN KEY (api_key_id) REFERENCES api_keys(id) ON DELETE CASCADE
+);
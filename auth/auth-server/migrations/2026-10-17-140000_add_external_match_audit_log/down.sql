@@ -0,0 +1,2 @@
+-- Drop the external match audit log
+DROP TABLE IF EXISTS external_match_audit_log;
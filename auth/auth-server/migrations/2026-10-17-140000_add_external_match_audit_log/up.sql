@@ -0,0 +1,29 @@
+-- Create the external match audit log, one row per quote, assembly, and settlement
+-- on any served chain
+CREATE TABLE external_match_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    chain_id BIGINT NOT NULL,
+    event_type VARCHAR NOT NULL,
+    api_key_id UUID,
+    key_description VARCHAR NOT NULL,
+    request_id VARCHAR NOT NULL,
+    bundle_id VARCHAR,
+    base_mint VARCHAR NOT NULL,
+    input_mint VARCHAR NOT NULL,
+    output_mint VARCHAR NOT NULL,
+    input_amount NUMERIC(78, 0) NOT NULL,
+    output_amount NUMERIC(78, 0) NOT NULL,
+    price DOUBLE PRECISION,
+    price_timestamp TIMESTAMP,
+    is_sponsored BOOLEAN NOT NULL,
+    refund_amount NUMERIC(78, 0),
+    tx_hash VARCHAR,
+    assembly_delay_ms BIGINT,
+    settlement_delay_ms BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Index the filters supported by the audit log query endpoint
+CREATE INDEX external_match_audit_log_created_at_idx ON external_match_audit_log (created_at);
+CREATE INDEX external_match_audit_log_key_idx ON external_match_audit_log (api_key_id, created_at);
+CREATE INDEX external_match_audit_log_base_mint_idx ON external_match_audit_log (base_mint, created_at);
@@ -0,0 +1,2 @@
+-- Drop the per-key usage table
+DROP TABLE IF EXISTS api_key_usage;
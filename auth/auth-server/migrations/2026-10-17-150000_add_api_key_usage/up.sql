@@ -0,0 +1,17 @@
+-- Aggregate per-key usage by chain and UTC day, for billing and ranking
+-- integrators
+CREATE TABLE api_key_usage (
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    chain_id BIGINT NOT NULL,
+    day DATE NOT NULL,
+    quote_count BIGINT NOT NULL DEFAULT 0,
+    bundle_count BIGINT NOT NULL DEFAULT 0,
+    settlement_count BIGINT NOT NULL DEFAULT 0,
+    settled_volume_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    fees_paid_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    gas_sponsored_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    PRIMARY KEY (api_key_id, chain_id, day)
+);
+
+-- Usage is queried by date range across all keys
+CREATE INDEX api_key_usage_day_idx ON api_key_usage (day);
@@ -0,0 +1,5 @@
+-- Drop the webhook_deliveries table
+DROP TABLE IF EXISTS webhook_deliveries;
+
+-- Drop the webhook URL column
+ALTER TABLE api_keys DROP COLUMN webhook_url;
@@ -0,0 +1,22 @@
+-- The URL to which an API key's webhook notifications are delivered
+ALTER TABLE api_keys ADD COLUMN webhook_url VARCHAR;
+
+-- A log of webhook deliveries, one row per notification
+CREATE TABLE webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    api_key_id UUID NOT NULL,
+    event_type VARCHAR NOT NULL,
+    bundle_id VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_status_code INTEGER,
+    last_error VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (api_key_id) REFERENCES api_keys(id) ON DELETE CASCADE
+);
+
+-- Deliveries are listed per key, newest first
+CREATE INDEX webhook_deliveries_key_idx ON webhook_deliveries (api_key_id, created_at);
@@ -0,0 +1,2 @@
+-- Drop the volume limits table
+DROP TABLE IF EXISTS volume_limits;
@@ -0,0 +1,20 @@
+-- Caps on notional USD volume over a sliding window. A limit with no API key
+-- is a global cap on an asset, a limit with no asset applies to all of a key's
+-- assets, and a limit with no chain applies across all chains
+CREATE TABLE volume_limits (
+    id SERIAL PRIMARY KEY,
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE CASCADE,
+    asset VARCHAR,
+    chain_id BIGINT,
+    time_window VARCHAR NOT NULL,
+    max_volume_usd DOUBLE PRECISION NOT NULL,
+    CHECK (api_key_id IS NOT NULL OR asset IS NOT NULL)
+);
+
+-- At most one limit per scope and window
+CREATE UNIQUE INDEX volume_limits_scope_idx ON volume_limits (
+    COALESCE(api_key_id, '00000000-0000-0000-0000-000000000000'::UUID),
+    COALESCE(asset, ''),
+    COALESCE(chain_id, -1),
+    time_window
+);
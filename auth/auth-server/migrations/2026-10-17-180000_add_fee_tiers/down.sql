@@ -0,0 +1,3 @@
+-- Drop the fee tier and promotional fee tables
+DROP TABLE IF EXISTS promotional_fees;
+DROP TABLE IF EXISTS fee_tiers;
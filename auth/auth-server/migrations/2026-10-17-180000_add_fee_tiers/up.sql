@@ -0,0 +1,27 @@
+-- Fee tiers keyed on a key's trailing 30-day settled volume. A tier with no
+-- asset applies to all assets
+CREATE TABLE fee_tiers (
+    id SERIAL PRIMARY KEY,
+    asset VARCHAR,
+    min_volume_usd DOUBLE PRECISION NOT NULL,
+    fee FLOAT4 NOT NULL
+);
+
+-- At most one tier per asset and volume threshold
+CREATE UNIQUE INDEX fee_tiers_threshold_idx ON fee_tiers (COALESCE(asset, ''), min_volume_usd);
+
+-- Time-boxed promotional fees. A promotion with no API key applies to all keys,
+-- and a promotion with no asset applies to all assets
+CREATE TABLE promotional_fees (
+    id SERIAL PRIMARY KEY,
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE CASCADE,
+    asset VARCHAR,
+    fee FLOAT4 NOT NULL,
+    starts_at TIMESTAMP NOT NULL,
+    ends_at TIMESTAMP NOT NULL,
+    description VARCHAR NOT NULL,
+    CHECK (ends_at > starts_at)
+);
+
+-- Active promotions are looked up by time range
+CREATE INDEX promotional_fees_ends_at_idx ON promotional_fees (ends_at);
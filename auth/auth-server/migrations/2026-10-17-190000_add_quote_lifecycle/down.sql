@@ -0,0 +1,2 @@
+-- Drop the quote lifecycle table
+DROP TABLE IF EXISTS quote_lifecycle;
@@ -0,0 +1,23 @@
+-- Correlate each quote with its assembly and settlement. Entries are keyed by
+-- the quote's ID, or by the assembly request's ID for direct order assemblies
+CREATE TABLE quote_lifecycle (
+    id UUID PRIMARY KEY,
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    chain_id BIGINT NOT NULL,
+    base_mint VARCHAR NOT NULL,
+    side VARCHAR NOT NULL,
+    quoted_price DOUBLE PRECISION,
+    quoted_at TIMESTAMP,
+    bundle_id VARCHAR,
+    assembled_at TIMESTAMP,
+    settled_price DOUBLE PRECISION,
+    settled_at TIMESTAMP,
+    quote_to_settlement_bps DOUBLE PRECISION,
+    markout_bps DOUBLE PRECISION,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (chain_id, bundle_id)
+);
+
+-- Funnel stats are aggregated by key and market over a time range
+CREATE INDEX quote_lifecycle_created_at_idx ON quote_lifecycle (created_at);
+CREATE INDEX quote_lifecycle_key_idx ON quote_lifecycle (api_key_id, created_at);
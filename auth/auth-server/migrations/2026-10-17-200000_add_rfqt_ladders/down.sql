@@ -0,0 +1,2 @@
+-- Drop the RFQT ladders table
+DROP TABLE IF EXISTS rfqt_ladders;
@@ -0,0 +1,16 @@
+-- Price ladder shapes quoted on the RFQT levels endpoint. A ladder with no API
+-- key is the default for all keys
+CREATE TABLE rfqt_ladders (
+    id SERIAL PRIMARY KEY,
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE CASCADE,
+    size_fractions DOUBLE PRECISION[] NOT NULL,
+    base_spread_bps DOUBLE PRECISION NOT NULL,
+    impact_bps DOUBLE PRECISION NOT NULL,
+    impact_exponent DOUBLE PRECISION NOT NULL,
+    skew_bps DOUBLE PRECISION NOT NULL
+);
+
+-- At most one ladder per key, and one default ladder
+CREATE UNIQUE INDEX rfqt_ladders_key_idx ON rfqt_ladders (
+    COALESCE(api_key_id, '00000000-0000-0000-0000-000000000000'::UUID)
+);
@@ -0,0 +1,2 @@
+-- Drop the sponsorship policies table
+DROP TABLE IF EXISTS sponsorship_policies;
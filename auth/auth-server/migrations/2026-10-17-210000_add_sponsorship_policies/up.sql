@@ -0,0 +1,20 @@
+-- Gas sponsorship policies. A policy with no API key applies to all keys
+-- trading its asset, and a policy with no asset applies to all of a key's
+-- assets. Unset limits fall through to less specific policies
+CREATE TABLE sponsorship_policies (
+    id SERIAL PRIMARY KEY,
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE CASCADE,
+    asset VARCHAR,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    max_refund_usd DOUBLE PRECISION,
+    daily_budget_usd DOUBLE PRECISION,
+    min_order_size_usd DOUBLE PRECISION,
+    allowed_refund_assets TEXT[],
+    CHECK (api_key_id IS NOT NULL OR asset IS NOT NULL)
+);
+
+-- At most one policy per scope
+CREATE UNIQUE INDEX sponsorship_policies_scope_idx ON sponsorship_policies (
+    COALESCE(api_key_id, '00000000-0000-0000-0000-000000000000'::UUID),
+    COALESCE(asset, '')
+);
@@ -0,0 +1,5 @@
+-- Drop the retry schedule index
+DROP INDEX IF EXISTS webhook_deliveries_next_attempt_idx;
+
+-- Drop the retry schedule column
+ALTER TABLE webhook_deliveries DROP COLUMN next_attempt_at;
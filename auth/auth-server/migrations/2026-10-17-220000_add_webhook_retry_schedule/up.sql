@@ -0,0 +1,11 @@
+-- The time at which a pending delivery is next attempted, NULL once the
+-- delivery is delivered or has failed. Persisting the schedule lets retries
+-- survive a restart
+ALTER TABLE webhook_deliveries ADD COLUMN next_attempt_at TIMESTAMP;
+
+-- Resume deliveries left pending before the schedule was persisted
+UPDATE webhook_deliveries SET next_attempt_at = NOW() WHERE status = 'pending';
+
+-- Due deliveries are polled by their scheduled time
+CREATE INDEX webhook_deliveries_next_attempt_idx ON webhook_deliveries (next_attempt_at)
+    WHERE next_attempt_at IS NOT NULL;
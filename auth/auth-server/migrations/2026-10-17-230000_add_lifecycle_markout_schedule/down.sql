@@ -0,0 +1,6 @@
+-- Drop the markout schedule index
+DROP INDEX IF EXISTS quote_lifecycle_markout_due_idx;
+
+-- Drop the markout schedule columns
+ALTER TABLE quote_lifecycle DROP COLUMN markout_attempts;
+ALTER TABLE quote_lifecycle DROP COLUMN markout_due_at;
@@ -0,0 +1,16 @@
+-- The time at which a settled lifecycle's markout is due, NULL once the
+-- markout is recorded. Persisting the schedule lets markouts survive a restart
+ALTER TABLE quote_lifecycle ADD COLUMN markout_due_at TIMESTAMP;
+
+-- The number of times a lifecycle's markout has been claimed. A markout is
+-- abandoned once its attempts are exhausted
+ALTER TABLE quote_lifecycle ADD COLUMN markout_attempts INT4 NOT NULL DEFAULT 0;
+
+-- Resume markouts left pending within the horizon before the schedule was
+-- persisted
+UPDATE quote_lifecycle SET markout_due_at = settled_at + INTERVAL '5 minutes'
+    WHERE markout_bps IS NULL AND settled_at > NOW() - INTERVAL '5 minutes';
+
+-- Due markouts are polled by their scheduled time
+CREATE INDEX quote_lifecycle_markout_due_idx ON quote_lifecycle (markout_due_at)
+    WHERE markout_due_at IS NOT NULL;
@@ -0,0 +1,147 @@
+//! A bundle store held in process memory
+//!
+//! Bundles are lost on restart and are only visible to the replica that
+//! assembled them
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+
+use super::{BundleContext, BundleId, BundleStorage};
+use crate::error::AuthServerError;
+
+/// A bundle store backed by an in-memory map
+pub(super) struct InMemoryBundleStore {
+    /// The mapping from bundle ID to bundle context and its expiry
+    by_id: DashMap<BundleId, (BundleContext, Instant)>,
+    /// The duration for which a bundle is retained
+    ttl: Duration,
+}
+
+impl InMemoryBundleStore {
+    /// Constructor
+    pub fn new(ttl: Duration) -> Self {
+        Self { by_id: DashMap::new(), ttl }
+    }
+
+    /// Remove all expired bundles from the store
+    fn prune_expired(&self) {
+        let now = Instant::now();
+        self.by_id.retain(|_, (_, expiry)| *expiry > now);
+    }
+}
+
+#[async_trait]
+impl BundleStorage for InMemoryBundleStore {
+    async fn write(&self, ctx: &BundleContext) -> Result<(), AuthServerError> {
+        self.prune_expired();
+        let expiry = Instant::now() + self.ttl;
+        self.by_id.insert(ctx.bundle_id, (ctx.clone(), expiry));
+        Ok(())
+    }
+
+    async fn read(&self, bundle_id: &BundleId) -> Result<Option<BundleContext>, AuthServerError> {
+        let now = Instant::now();
+        let entry = self.by_id.get(bundle_id);
+        Ok(entry.filter(|entry| entry.1 > now).map(|entry| entry.0.clone()))
+    }
+
+    async fn take(&self, bundle_id: &BundleId) -> Result<Option<BundleContext>, AuthServerError> {
+        let now = Instant::now();
+        let entry = self.by_id.remove(bundle_id);
+        Ok(entry.filter(|(_, (_, expiry))| *expiry > now).map(|(_, (ctx, _))| ctx))
+    }
+
+    async fn take_expired(&self, before: u64) -> Result<Vec<BundleContext>, AuthServerError> {
+        let now = Instant::now();
+        let expired_ids: Vec<BundleId> = self
+            .by_id
+            .iter()
+            .filter(|entry| entry.0.deadline != 0 && entry.0.deadline < before)
+            .map(|entry| *entry.key())
+            .collect();
+
+        // Remove each bundle individually so that a concurrent `take` of the
+        // same bundle returns it at most once
+        let expired = expired_ids
+            .iter()
+            .filter_map(|id| self.by_id.remove(id))
+            .filter(|(_, (_, expiry))| *expiry > now)
+            .map(|(_, (ctx, _))| ctx)
+            .collect();
+        Ok(expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::U256;
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// Build a bundle context with the given ID and deadline
+    fn bundle_with_deadline(id: u64, deadline: u64) -> BundleContext {
+        BundleContext {
+            key_id: Uuid::new_v4(),
+            key_description: "test".to_string(),
+            bundle_id: U256::from(id),
+            request_id: "req".to_string(),
+            sdk_version: "unknown".to_string(),
+            gas_sponsorship_info: None,
+            is_sponsored: false,
+            price_timestamp: 0,
+            assembled_timestamp: None,
+            fee_rate: 0.,
+            deadline,
+            volume_consumption: None,
+        }
+    }
+
+    /// Build a bundle context with the given ID and no deadline
+    fn bundle(id: u64) -> BundleContext {
+        bundle_with_deadline(id, 0 /* deadline */)
+    }
+
+    #[tokio::test]
+    async fn take_removes_bundle() {
+        let store = InMemoryBundleStore::new(Duration::from_secs(60));
+        let ctx = bundle(1);
+        store.write(&ctx).await.unwrap();
+
+        assert!(store.read(&ctx.bundle_id).await.unwrap().is_some());
+        assert!(store.take(&ctx.bundle_id).await.unwrap().is_some());
+        assert!(store.take(&ctx.bundle_id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn expired_bundles_are_not_returned() {
+        let store = InMemoryBundleStore::new(Duration::ZERO);
+        let ctx = bundle(1);
+        store.write(&ctx).await.unwrap();
+
+        assert!(store.read(&ctx.bundle_id).await.unwrap().is_none());
+        assert!(store.take(&ctx.bundle_id).await.unwrap().is_none());
+
+        // Expired bundles are pruned on the next write
+        store.write(&bundle(2)).await.unwrap();
+        assert!(!store.by_id.contains_key(&ctx.bundle_id));
+    }
+
+    #[tokio::test]
+    async fn take_expired_returns_bundles_past_deadline() {
+        let store = InMemoryBundleStore::new(Duration::from_secs(60));
+        store.write(&bundle_with_deadline(1, 100)).await.unwrap();
+        store.write(&bundle_with_deadline(2, 200)).await.unwrap();
+        store.write(&bundle(3)).await.unwrap();
+
+        let expired = store.take_expired(150).await.unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].bundle_id, U256::from(1));
+
+        // Expired bundles are removed, bundles without a deadline are kept
+        assert_eq!(store.take_expired(u64::MAX).await.unwrap().len(), 1);
+        assert!(store.read(&U256::from(3)).await.unwrap().is_some());
+    }
+}
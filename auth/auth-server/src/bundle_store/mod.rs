@@ -1,10 +1,27 @@
 //! Defines the bundle store and associated types
+//!
+//! The store is pluggable: bundles may be held in process memory, or in Redis
+//! so that they survive restarts and are visible to every auth server replica.
+//! Each served chain has its own store, since bundles settle on the chain they
+//! were assembled on
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use alloy_primitives::U256;
+use async_trait::async_trait;
 use auth_server_api::GasSponsorshipInfo;
-use dashmap::DashMap;
+use clap::ValueEnum;
+use redis::aio::ConnectionManager as RedisConnection;
+use renegade_types_core::Chain;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{error::AuthServerError, server::rate_limiter::VolumeConsumption};
+
+use self::{in_memory::InMemoryBundleStore, redis_store::RedisBundleStore};
+
+mod in_memory;
+mod redis_store;
 
 /// The bundle ID type
 pub type BundleId = U256;
@@ -14,8 +31,10 @@ pub type BundleId = U256;
 // ------------------
 
 /// Context of an external match bundle
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct BundleContext {
+    /// The ID of the API key that requested the bundle
+    pub key_id: Uuid,
     /// The key description that settled the bundle
     pub key_description: String,
     /// The bundle ID
@@ -33,37 +52,147 @@ pub(crate) struct BundleContext {
     pub price_timestamp: u64,
     /// The timestamp of the assembly of the bundle in milliseconds
     pub assembled_timestamp: Option<u64>,
+    /// The total fee rate charged on the external party's receive amount,
+    /// including both the relayer and protocol fees
+    #[serde(default)]
+    pub fee_rate: f64,
+    /// The deadline after which the bundle can no longer settle, in seconds
+    /// since epoch, or zero if the bundle has no deadline
+    #[serde(default)]
+    pub deadline: u64,
+    /// The volume the bundle consumed against the key's volume limits, if any
+    /// applied, refunded if the bundle expires without settling
+    #[serde(default)]
+    pub volume_consumption: Option<VolumeConsumption>,
+}
+
+// -----------
+// | Storage |
+// -----------
+
+/// The backend used to store bundle contexts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BundleStoreBackend {
+    /// Store bundles in process memory, bundles are lost on restart
+    Memory,
+    /// Store bundles in Redis, shared across restarts and replicas
+    Redis,
+}
+
+/// A storage backend for bundle contexts
+///
+/// Bundles expire after a backend-configured TTL so that bundles which never
+/// settle do not accumulate
+#[async_trait]
+pub(crate) trait BundleStorage: Send + Sync {
+    /// Write a bundle to the store
+    async fn write(&self, ctx: &BundleContext) -> Result<(), AuthServerError>;
+
+    /// Read a bundle from the store by its ID
+    async fn read(&self, bundle_id: &BundleId) -> Result<Option<BundleContext>, AuthServerError>;
+
+    /// Atomically read and remove a bundle from the store
+    ///
+    /// At most one caller receives the bundle, so that a settlement is only
+    /// attributed once when several replicas observe it
+    async fn take(&self, bundle_id: &BundleId) -> Result<Option<BundleContext>, AuthServerError>;
+
+    /// Atomically remove and return all bundles whose deadline is before the
+    /// given time, in seconds since epoch
+    ///
+    /// As with `take`, each expired bundle is returned to at most one caller
+    async fn take_expired(&self, before: u64) -> Result<Vec<BundleContext>, AuthServerError>;
 }
 
 // ---------
 // | Store |
 // ---------
 
-/// A thread-safe store for tracking bundle contexts by ID and nullifier.
+/// A thread-safe store for tracking bundle contexts by ID
 #[derive(Clone)]
 pub struct BundleStore {
-    /// The mapping from bundle ID to bundle context
-    by_id: Arc<DashMap<BundleId, BundleContext>>,
+    /// The underlying storage backend
+    inner: Arc<dyn BundleStorage>,
 }
 
 impl BundleStore {
-    /// Create a new bundle store
-    pub fn new() -> Self {
-        Self { by_id: Arc::new(DashMap::new()) }
+    /// Create a new bundle store held in process memory
+    pub fn new_in_memory(ttl: Duration) -> Self {
+        Self { inner: Arc::new(InMemoryBundleStore::new(ttl)) }
+    }
+
+    /// Create a new bundle store backed by Redis, holding the bundles of the
+    /// given chain
+    pub fn new_redis(conn: RedisConnection, ttl: Duration, chain: Chain) -> Self {
+        Self { inner: Arc::new(RedisBundleStore::new(conn, ttl, chain)) }
+    }
+
+    /// Create a new bundle store for the given chain using the given backend
+    pub fn new(
+        backend: BundleStoreBackend,
+        conn: RedisConnection,
+        ttl: Duration,
+        chain: Chain,
+    ) -> Self {
+        match backend {
+            BundleStoreBackend::Memory => Self::new_in_memory(ttl),
+            BundleStoreBackend::Redis => Self::new_redis(conn, ttl, chain),
+        }
     }
 
     /// Write a bundle to the store
-    pub fn write(&self, ctx: BundleContext) {
-        self.by_id.insert(ctx.bundle_id, ctx);
+    pub(crate) async fn write(&self, ctx: &BundleContext) -> Result<(), AuthServerError> {
+        self.inner.write(ctx).await
     }
 
     /// Read a bundle from the store by its ID
-    pub fn read(&self, bundle_id: &BundleId) -> Option<BundleContext> {
-        self.by_id.get(bundle_id).map(|ptr| ptr.value().clone())
+    pub(crate) async fn read(
+        &self,
+        bundle_id: &BundleId,
+    ) -> Result<Option<BundleContext>, AuthServerError> {
+        self.inner.read(bundle_id).await
+    }
+
+    /// Atomically read and remove a bundle from the store
+    pub(crate) async fn take(
+        &self,
+        bundle_id: &BundleId,
+    ) -> Result<Option<BundleContext>, AuthServerError> {
+        self.inner.take(bundle_id).await
+    }
+
+    /// Remove and return all bundles whose deadline is before the given time,
+    /// in seconds since epoch
+    pub(crate) async fn take_expired(
+        &self,
+        before: u64,
+    ) -> Result<Vec<BundleContext>, AuthServerError> {
+        self.inner.take_expired(before).await
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_stored_before_volume_consumption_deserialize() {
+        let stored = serde_json::json!({
+            "key_id": Uuid::new_v4(),
+            "key_description": "test",
+            "bundle_id": U256::from(1),
+            "request_id": "req",
+            "sdk_version": "unknown",
+            "gas_sponsorship_info": null,
+            "is_sponsored": false,
+            "price_timestamp": 0,
+            "assembled_timestamp": null,
+            "fee_rate": 0.,
+            "deadline": 100,
+        });
 
-    /// Cleanup (remove) the bundle with the given ID
-    pub fn remove_bundle(&self, bundle_id: &BundleId) {
-        self.by_id.remove(bundle_id);
+        let ctx: BundleContext = serde_json::from_value(stored).unwrap();
+        assert_eq!(ctx.deadline, 100);
+        assert_eq!(ctx.volume_consumption, None);
     }
 }
@@ -0,0 +1,148 @@
+//! A bundle store backed by Redis
+//!
+//! Bundles are stored as JSON under a per-bundle key with a TTL, so they
+//! survive restarts and are shared between auth server replicas. Bundles with a
+//! deadline are additionally indexed in a sorted set scored by deadline, and
+//! retained until the expiry watcher has had time to take them.
+//!
+//! Each chain's bundles are stored under a prefix holding the chain ID, so
+//! that a chain's settlement listener and expiry watcher only see the bundles
+//! assembled on that chain. Bundles written under the unprefixed keys of
+//! earlier versions are not read, and lapse with their TTL
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::{AsyncCommands, aio::ConnectionManager as RedisConnection};
+
+use renegade_types_core::Chain;
+use renegade_util::get_current_time_millis;
+
+use super::{BundleContext, BundleId, BundleStorage};
+use crate::{error::AuthServerError, server::chains::chain_to_chain_id};
+
+/// The key prefix for bundle contexts in Redis
+const BUNDLE_STORE_KEY_PREFIX: &str = "bundle_store";
+/// The key, under a chain's prefix, of the sorted set indexing bundle keys by
+/// deadline
+const BUNDLE_DEADLINES_KEY: &str = "deadlines";
+/// The time past its deadline for which a bundle is retained, so that the
+/// expiry watcher takes it after its grace period rather than it lapsing
+const DEADLINE_RETENTION_MARGIN: Duration = Duration::from_secs(10 * 60); // 10 minutes
+
+/// A bundle store backed by Redis
+pub(super) struct RedisBundleStore {
+    /// The Redis connection manager
+    redis: RedisConnection,
+    /// The duration for which a bundle is retained
+    ttl: Duration,
+    /// The prefix of the store's keys, scoped to its chain
+    key_prefix: String,
+}
+
+impl RedisBundleStore {
+    /// Constructor
+    pub fn new(redis: RedisConnection, ttl: Duration, chain: Chain) -> Self {
+        let key_prefix = format!("{BUNDLE_STORE_KEY_PREFIX}:{}", chain_to_chain_id(chain));
+        Self { redis, ttl, key_prefix }
+    }
+
+    // -----------
+    // | Helpers |
+    // -----------
+
+    /// Get a handle to the Redis connection
+    fn redis(&self) -> RedisConnection {
+        self.redis.clone()
+    }
+
+    /// Get the Redis key for the given bundle
+    fn bundle_key(&self, bundle_id: &BundleId) -> String {
+        format!("{}:{bundle_id:#x}", self.key_prefix)
+    }
+
+    /// Get the Redis key of the deadline index
+    fn deadlines_key(&self) -> String {
+        format!("{}:{BUNDLE_DEADLINES_KEY}", self.key_prefix)
+    }
+
+    /// Get the TTL in seconds of a bundle with the given deadline
+    ///
+    /// Bundles are retained for at least the store's TTL, and bundles with a
+    /// deadline for at least the margin past it
+    fn bundle_ttl_secs(&self, deadline: u64) -> u64 {
+        let mut ttl_secs = self.ttl.as_secs();
+        if deadline != 0 {
+            let now_secs = get_current_time_millis() / 1000;
+            let until_deadline = deadline.saturating_sub(now_secs);
+            ttl_secs = ttl_secs.max(until_deadline + DEADLINE_RETENTION_MARGIN.as_secs());
+        }
+
+        // Redis rejects a zero expiry, so retain bundles for at least a second
+        ttl_secs.max(1)
+    }
+
+    /// Deserialize a bundle context read from Redis
+    fn parse_bundle(value: Option<String>) -> Result<Option<BundleContext>, AuthServerError> {
+        value
+            .map(|value| serde_json::from_str(&value).map_err(AuthServerError::bundle_store))
+            .transpose()
+    }
+}
+
+#[async_trait]
+impl BundleStorage for RedisBundleStore {
+    async fn write(&self, ctx: &BundleContext) -> Result<(), AuthServerError> {
+        let key = self.bundle_key(&ctx.bundle_id);
+        let value = serde_json::to_string(ctx).map_err(AuthServerError::bundle_store)?;
+        let ttl_secs = self.bundle_ttl_secs(ctx.deadline);
+
+        // Write the bundle and its deadline in one transaction, so that a
+        // bundle with a deadline is never stored unindexed
+        let mut pipe = redis::pipe();
+        pipe.atomic().set_ex(&key, value, ttl_secs).ignore();
+        if ctx.deadline != 0 {
+            pipe.zadd(self.deadlines_key(), &key, ctx.deadline).ignore();
+        }
+        let _: () = pipe.query_async(&mut self.redis()).await?;
+        Ok(())
+    }
+
+    async fn read(&self, bundle_id: &BundleId) -> Result<Option<BundleContext>, AuthServerError> {
+        let value: Option<String> = self.redis().get(self.bundle_key(bundle_id)).await?;
+        Self::parse_bundle(value)
+    }
+
+    async fn take(&self, bundle_id: &BundleId) -> Result<Option<BundleContext>, AuthServerError> {
+        // `GETDEL` is atomic, so only one replica takes a given bundle
+        let value: Option<String> = self.redis().get_del(self.bundle_key(bundle_id)).await?;
+        Self::parse_bundle(value)
+    }
+
+    async fn take_expired(&self, before: u64) -> Result<Vec<BundleContext>, AuthServerError> {
+        // The `(` prefix makes the upper bound exclusive
+        let max_score = format!("({before}");
+        let keys: Vec<String> =
+            self.redis().zrangebyscore(self.deadlines_key(), 0, max_score).await?;
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Each bundle is removed from the index in the same transaction as it
+        // is taken. Bundles already taken at settlement or by another replica
+        // are gone, in which case `GETDEL` returns nothing
+        let mut expired = Vec::new();
+        for key in keys.iter() {
+            let (value,): (Option<String>,) = redis::pipe()
+                .atomic()
+                .get_del(key)
+                .zrem(self.deadlines_key(), key)
+                .ignore()
+                .query_async(&mut self.redis())
+                .await?;
+            expired.extend(Self::parse_bundle(value)?);
+        }
+
+        Ok(expired)
+    }
+}
@@ -8,8 +8,10 @@ use crate::{
     log_task,
     logger::{Outcome, Task},
     server::{
-        gas_estimation::gas_cost_sampler::GasCostSampler, rate_limiter::AuthServerRateLimiter,
+        db::DbPool, gas_estimation::gas_cost_sampler::GasCostSampler,
+        rate_limiter::AuthServerRateLimiter,
     },
+    webhooks::WebhookDispatcher,
 };
 use alloy::{
     providers::{DynProvider, Provider, ProviderBuilder, WsConnect},
@@ -53,6 +55,10 @@ pub struct OnChainEventListenerConfig {
     pub(crate) gas_cost_sampler: Arc<GasCostSampler>,
     /// A darkpool client for listening to events
     pub(crate) darkpool_client: DarkpoolClient,
+    /// The database pool, used to record settlements in the audit log
+    pub(crate) db_pool: DbPool,
+    /// The dispatcher for partner webhooks on settlement
+    pub(crate) webhooks: WebhookDispatcher,
 }
 
 /// The worker responsible for listening for on-chain events, translating them
@@ -89,6 +95,10 @@ pub struct OnChainEventListenerExecutor {
     pub(crate) gas_cost_sampler: Arc<GasCostSampler>,
     /// A darkpool client for listening to events
     pub(crate) darkpool_client: DarkpoolClient,
+    /// The database pool, used to record settlements in the audit log
+    pub(crate) db_pool: DbPool,
+    /// The dispatcher for partner webhooks on settlement
+    pub(crate) webhooks: WebhookDispatcher,
 }
 
 impl OnChainEventListenerExecutor {
@@ -103,6 +113,8 @@ impl OnChainEventListenerExecutor {
             price_reporter_client: config.price_reporter_client,
             gas_cost_sampler: config.gas_cost_sampler,
             darkpool_client: config.darkpool_client,
+            db_pool: config.db_pool,
+            webhooks: config.webhooks,
         }
     }
 
@@ -173,6 +185,9 @@ impl OnChainEventListenerExecutor {
             "starting on-chain event listener"
         );
 
+        // Record the markouts of settled lifecycles as they come due
+        self.spawn_markout_worker();
+
         // Begin the watch loop
         let res = self.watch_nonces().await.unwrap_err();
         log_task!(
@@ -1,8 +1,22 @@
 //! Helpers for executing subroutines in the on-chain event listener
+use std::time::{Duration, SystemTime};
+
 use crate::chain_events::utils::GPv2Settlement;
 use crate::log_task;
 use crate::logger::{Outcome, Task};
-use crate::server::helpers::pick_base_and_quote_mints;
+use crate::server::chains::token_on_chain;
+use crate::server::db::{
+    models::{ApiKeyUsage, NewAuditEvent, QuoteLifecycle, millis_to_system_time},
+    queries::{
+        abandon_lifecycle_markout, claim_due_lifecycle_markouts, get_lifecycle_by_bundle,
+        insert_audit_event, record_api_key_usage, record_lifecycle_markout,
+        record_lifecycle_settlement,
+    },
+};
+use crate::server::gas_sponsorship::policies::{
+    asset_budget_scope, key_asset_budget_scope, key_budget_scope,
+};
+use crate::server::helpers::{base_asset_ticker, pick_base_and_quote_mints, price_move_bps};
 use crate::telemetry::helpers::calculate_quote_per_base_price;
 use crate::telemetry::labels::EXTERNAL_MATCH_SPREAD_COST;
 use crate::{bundle_store::BundleContext, chain_events::listener::OnChainEventListenerExecutor};
@@ -27,9 +41,10 @@ use alloy::providers::Provider;
 use alloy::rpc::types::TransactionReceipt;
 use alloy_primitives::{TxHash, U256};
 use alloy_sol_types::SolEvent;
-use auth_server_api::GasSponsorshipInfo;
+use auth_server_api::{GasSponsorshipInfo, audit_log::AuditEventType, webhooks::WebhookEvent};
 use bigdecimal::{BigDecimal, ToPrimitive};
 use renegade_circuit_types::Amount;
+use renegade_constants::NATIVE_ASSET_ADDRESS;
 use renegade_darkpool_types::bounded_match_result::BoundedMatchResult;
 use renegade_external_api::types::ApiBoundedMatchResult;
 use renegade_solidity_abi::v2::IDarkpoolV2::SponsoredExternalMatch;
@@ -53,6 +68,24 @@ const REFUND_ASSET_TICKER_ERROR_MSG: &str = "failed to get refund asset ticker";
 /// The threshold on spread cost at which we log a warning
 const HIGH_SPREAD_COST_THRESHOLD_USD: f64 = 20.; // $20 USD
 
+/// The time after settlement at which a settled price is marked out against
+/// the reference price
+const MARKOUT_HORIZON: Duration = Duration::from_secs(5 * 60); // 5 minutes
+/// The interval at which due markouts are polled
+const MARKOUT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// The maximum number of markouts recorded per poll
+const MAX_MARKOUTS_PER_POLL: i64 = 100;
+/// The time for which a claimed markout is hidden from other replicas before
+/// it is retried
+const MARKOUT_LEASE: Duration = Duration::from_secs(60);
+/// The time past its horizon after which a markout is abandoned rather than
+/// recorded, as the reference price no longer reflects the horizon
+const MARKOUT_LATENESS_TOLERANCE: Duration = Duration::from_secs(3 * 60); // 3 minutes
+/// The maximum number of attempts made to record a markout
+const MAX_MARKOUT_ATTEMPTS: i32 = 3;
+/// The error message emitted when a lifecycle due for markout has not settled
+const ERR_MARKOUT_UNSETTLED: &str = "lifecycle due for markout has no settled price";
+
 impl OnChainEventListenerExecutor {
     /// Process an external match for settlement metrics
     ///
@@ -67,8 +100,9 @@ impl OnChainEventListenerExecutor {
         receipt: &TransactionReceipt,
         settlement_time: u64,
     ) -> Result<(), AuthServerError> {
-        // Look up the bundle context
-        let bundle_ctx = match self.bundle_store.read(&nonce) {
+        // Take the bundle context, removing it from the store so that the
+        // settlement is only attributed once across replicas
+        let bundle_ctx = match self.bundle_store.take(&nonce).await? {
             Some(ctx) => ctx,
             None => return Ok(()), // No bundle context found for this nonce
         };
@@ -81,6 +115,66 @@ impl OnChainEventListenerExecutor {
         let (actual_input, actual_output) =
             compute_external_amounts(match_result, actual_external_input);
 
+        // Record the settlement in the audit log
+        if let Err(e) = self
+            .record_settlement_audit_event(
+                &bundle_ctx,
+                &api_match,
+                actual_input,
+                actual_output,
+                tx,
+                settlement_time,
+            )
+            .await
+        {
+            log_task!(
+                Task::AuditLog,
+                Outcome::Failed,
+                subject = "settlement",
+                error = %e,
+                "error recording settlement in audit log"
+            );
+        }
+
+        // Count the settlement against the key's usage
+        if let Err(e) =
+            self.record_settlement_usage(&bundle_ctx, &api_match, actual_input, actual_output).await
+        {
+            log_task!(
+                Task::Usage,
+                Outcome::Failed,
+                subject = "settlement",
+                error = %e,
+                "error recording settlement usage"
+            );
+        }
+
+        // Record the settlement on the bundle's quote lifecycle
+        if let Err(e) =
+            self.record_settlement_lifecycle(&bundle_ctx, &api_match, settlement_time).await
+        {
+            log_task!(
+                Task::QuoteLifecycle,
+                Outcome::Failed,
+                subject = "settlement",
+                error = %e,
+                "error recording settlement lifecycle"
+            );
+        }
+
+        // Notify the partner of the settlement
+        let event = WebhookEvent::BundleSettled {
+            bundle_id: bundle_ctx.bundle_id.to_string(),
+            request_id: bundle_ctx.request_id.clone(),
+            tx_hash: format!("{tx:#x}"),
+            input_mint: address_to_hex_string(&api_match.input_mint),
+            output_mint: address_to_hex_string(&api_match.output_mint),
+            input_amount: actual_input,
+            output_amount: actual_output,
+            settled_at: settlement_time,
+        };
+        self.webhooks.notify(bundle_ctx.key_id, event);
+
         // Record external match spread cost
         self.record_external_match_spread_cost(
             tx,
@@ -112,9 +206,6 @@ impl OnChainEventListenerExecutor {
             .await?;
         }
 
-        // Cleanup the bundle context
-        self.bundle_store.remove_bundle(&bundle_ctx.bundle_id);
-
         // Record price sample to assembly delay
         self.record_assembly_delay(&bundle_ctx);
 
@@ -126,6 +217,205 @@ impl OnChainEventListenerExecutor {
 
         Ok(())
     }
+
+    /// Record a settled match in the external match audit log
+    ///
+    /// Amounts are the actual amounts settled from the external party's
+    /// perspective
+    async fn record_settlement_audit_event(
+        &self,
+        ctx: &BundleContext,
+        match_result: &ApiBoundedMatchResult,
+        actual_input: Amount,
+        actual_output: Amount,
+        tx: TxHash,
+        settlement_time: u64,
+    ) -> Result<(), AuthServerError> {
+        let input_mint = match_result.input_mint;
+        let output_mint = match_result.output_mint;
+        let (base_mint, _) = pick_base_and_quote_mints(input_mint, output_mint, self.chain)?;
+        let price = calculate_quote_per_base_price(match_result, self.chain)?;
+
+        let price_timestamp = ctx.price_timestamp;
+        let assembly_delay = ctx.assembled_timestamp.map(|ts| ts.saturating_sub(price_timestamp));
+        let settlement_delay = settlement_time.saturating_sub(price_timestamp);
+        let refund_amount = ctx.gas_sponsorship_info.as_ref().map(|(info, _)| info.refund_amount);
+        let event = NewAuditEvent::new(
+            self.chain,
+            AuditEventType::Settlement,
+            Some(ctx.key_id),
+            ctx.key_description.clone(),
+            ctx.request_id.clone(),
+            address_to_hex_string(&base_mint),
+            address_to_hex_string(&input_mint),
+            address_to_hex_string(&output_mint),
+            actual_input,
+            actual_output,
+        )
+        .with_bundle_id(ctx.bundle_id.to_string())
+        .with_price(price, price_timestamp)
+        .with_refund_amount(refund_amount)
+        .with_tx_hash(format!("{tx:#x}"))
+        .with_assembly_delay_ms(assembly_delay)
+        .with_settlement_delay_ms(settlement_delay);
+
+        insert_audit_event(&self.db_pool, &event).await
+    }
+
+    /// Count a settled match against the requesting key's usage for the day
+    ///
+    /// Volume is valued at the quote amount, and fees are approximated by
+    /// applying the bundle's fee rate to that volume
+    async fn record_settlement_usage(
+        &self,
+        ctx: &BundleContext,
+        match_result: &ApiBoundedMatchResult,
+        actual_input: Amount,
+        actual_output: Amount,
+    ) -> Result<(), AuthServerError> {
+        let (_, quote_mint) = pick_base_and_quote_mints(
+            match_result.input_mint,
+            match_result.output_mint,
+            self.chain,
+        )?;
+        let quote_amount =
+            if quote_mint == match_result.input_mint { actual_input } else { actual_output };
+
+        let volume_usd = token_on_chain(&quote_mint, self.chain).convert_to_decimal(quote_amount);
+        let fees_usd = volume_usd * ctx.fee_rate;
+        let usage = ApiKeyUsage::settlement(ctx.key_id, self.chain, volume_usd, fees_usd);
+        record_api_key_usage(&self.db_pool, &usage).await
+    }
+
+    /// Record a settled bundle on its quote lifecycle, and schedule a markout
+    /// of the settled price
+    ///
+    /// The reference price is sampled here, so this should be called as close
+    /// to the time of settlement as possible. The markout is persisted with
+    /// the settlement and recorded by the markout worker once it is due
+    async fn record_settlement_lifecycle(
+        &self,
+        ctx: &BundleContext,
+        match_result: &ApiBoundedMatchResult,
+        settlement_time: u64,
+    ) -> Result<(), AuthServerError> {
+        let lifecycle =
+            match get_lifecycle_by_bundle(&self.db_pool, self.chain, &ctx.bundle_id.to_string())
+                .await?
+            {
+                Some(lifecycle) => lifecycle,
+                None => return Ok(()), // The assembly was not recorded
+            };
+
+        let (base_mint, _) = pick_base_and_quote_mints(
+            match_result.input_mint,
+            match_result.output_mint,
+            self.chain,
+        )?;
+        let base_mint = address_to_hex_string(&base_mint);
+        let settled_price = calculate_quote_per_base_price(match_result, self.chain)?;
+        let reference_price = self.price_reporter_client.get_price(&base_mint, self.chain).await?;
+
+        let quote_to_settlement_bps = lifecycle.quoted_price.map(|quoted_price| {
+            price_move_bps(lifecycle.external_buys(), quoted_price, reference_price)
+        });
+        let settled_at = millis_to_system_time(settlement_time);
+        record_lifecycle_settlement(
+            &self.db_pool,
+            lifecycle.id,
+            settled_price,
+            settled_at,
+            quote_to_settlement_bps,
+            settled_at + MARKOUT_HORIZON,
+        )
+        .await
+    }
+
+    /// Periodically record the markouts of lifecycles settled on the chain
+    /// once they are due
+    pub fn spawn_markout_worker(&self) {
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MARKOUT_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self_clone.record_due_markouts().await {
+                    log_task!(
+                        Task::QuoteLifecycle,
+                        Outcome::Failed,
+                        subject = "markout",
+                        error = %e,
+                        "failed to record due markouts"
+                    );
+                }
+            }
+        });
+    }
+
+    /// Record the markout of every lifecycle settled on the chain whose markout
+    /// is due
+    ///
+    /// Due markouts are claimed for a lease, so each is recorded once across
+    /// replicas, and a markout that fails is retried once its lease expires.
+    /// A markout is abandoned, leaving it unset, once it is later than the
+    /// tolerance or its attempts are exhausted
+    async fn record_due_markouts(&self) -> Result<(), AuthServerError> {
+        let due = claim_due_lifecycle_markouts(
+            &self.db_pool,
+            self.chain,
+            MAX_MARKOUTS_PER_POLL,
+            MARKOUT_LEASE,
+        )
+        .await?;
+
+        for lifecycle in due {
+            if markout_is_late(&lifecycle, SystemTime::now()) {
+                log_task!(
+                    Task::QuoteLifecycle,
+                    Outcome::Skipped,
+                    subject = "markout",
+                    lifecycle = %lifecycle.id,
+                    "markout is past its lateness tolerance, abandoning"
+                );
+                abandon_lifecycle_markout(&self.db_pool, lifecycle.id).await?;
+                continue;
+            }
+
+            if let Err(e) = self.record_markout(&lifecycle).await {
+                // The claim counted as an attempt, so the stored count lags by one
+                let attempt = lifecycle.markout_attempts + 1;
+                log_task!(
+                    Task::QuoteLifecycle,
+                    Outcome::Failed,
+                    subject = "markout",
+                    lifecycle = %lifecycle.id,
+                    attempt = attempt,
+                    error = %e,
+                    "error recording markout"
+                );
+
+                if attempt >= MAX_MARKOUT_ATTEMPTS {
+                    abandon_lifecycle_markout(&self.db_pool, lifecycle.id).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mark out a lifecycle's settled price against the current reference
+    /// price
+    async fn record_markout(&self, lifecycle: &QuoteLifecycle) -> Result<(), AuthServerError> {
+        let settled_price = lifecycle
+            .settled_price
+            .ok_or_else(|| AuthServerError::custom(ERR_MARKOUT_UNSETTLED))?;
+        let markout_price =
+            self.price_reporter_client.get_price(&lifecycle.base_mint, self.chain).await?;
+
+        let markout_bps = price_move_bps(lifecycle.external_buys(), settled_price, markout_price);
+        record_lifecycle_markout(&self.db_pool, lifecycle.id, markout_bps).await
+    }
+
     /// Record settlement metrics for a bundle
     ///
     /// Metrics are recorded from the external party's perspective
@@ -145,7 +435,8 @@ impl OnChainEventListenerExecutor {
         let output_mint = match_result.output_mint;
 
         // Derive base/quote from input/output (base is non-USDC)
-        let (base_mint, quote_mint) = pick_base_and_quote_mints(input_mint, output_mint)?;
+        let (base_mint, quote_mint) =
+            pick_base_and_quote_mints(input_mint, output_mint, self.chain)?;
 
         // Compute base/quote amounts from external party's input/output
         let (base_amount, quote_amount) = if base_mint == match_result.output_mint {
@@ -161,16 +452,18 @@ impl OnChainEventListenerExecutor {
             base_amount,
             EXTERNAL_MATCH_SETTLED_BASE_VOLUME,
             &labels,
+            self.chain,
         );
 
-        labels = extend_labels_with_base_asset(&base_mint, labels);
-        labels = extend_labels_with_side(input_mint, output_mint, labels)?;
+        labels = extend_labels_with_base_asset(&base_mint, labels, self.chain);
+        labels = extend_labels_with_side(input_mint, output_mint, labels, self.chain)?;
 
         record_volume_with_tags(
             &address_to_hex_string(&quote_mint),
             quote_amount,
             EXTERNAL_MATCH_SETTLED_QUOTE_VOLUME,
             &labels,
+            self.chain,
         );
 
         Ok(())
@@ -202,8 +495,11 @@ impl OnChainEventListenerExecutor {
     ) -> Result<(), AuthServerError> {
         // Derive base/quote from input/output (base is non-USDC)
         // Note: input/output are from external party's perspective
-        let (base_mint, quote_mint) =
-            pick_base_and_quote_mints(match_result.input_mint, match_result.output_mint)?;
+        let (base_mint, quote_mint) = pick_base_and_quote_mints(
+            match_result.input_mint,
+            match_result.output_mint,
+            self.chain,
+        )?;
 
         // Sample reference price for the base token (in decimal-corrected quote/base
         // units)
@@ -213,7 +509,7 @@ impl OnChainEventListenerExecutor {
             .await?;
 
         // Compute the decimal-corrected match price using the helper
-        let match_price = calculate_quote_per_base_price(match_result)?;
+        let match_price = calculate_quote_per_base_price(match_result, self.chain)?;
 
         // Determine side from internal party's perspective
         // External's input is internal's output, so:
@@ -234,7 +530,7 @@ impl OnChainEventListenerExecutor {
         // perspective)
         // - When internal buys base, they pay quote (which is external's output)
         // - When internal sells base, they receive quote (which is external's input)
-        let quote_token = Token::from_alloy_address(&quote_mint);
+        let quote_token = token_on_chain(&quote_mint, self.chain);
         let quote_amount = if internal_buys { actual_output } else { actual_input };
         let quote_amount_decimal = quote_token.convert_to_decimal(quote_amount);
 
@@ -253,7 +549,7 @@ impl OnChainEventListenerExecutor {
         }
 
         let side_tag_value = if internal_buys { "buy" } else { "sell" };
-        let base_token = Token::from_alloy_address(&base_mint);
+        let base_token = token_on_chain(&base_mint, self.chain);
         let asset_tag_value = base_token.get_ticker().unwrap_or(base_token.get_addr());
 
         let labels = vec![
@@ -291,9 +587,9 @@ impl OnChainEventListenerExecutor {
     ) -> Result<(), AuthServerError> {
         // Refund is in the external party's output token (what they receive)
         let refund_asset = if gas_sponsorship_info.refund_native_eth {
-            Token::from_ticker(WETH_TICKER)
+            Token::from_ticker_on_chain(WETH_TICKER, self.chain)
         } else {
-            Token::from_alloy_address(&match_result.output_mint)
+            token_on_chain(&match_result.output_mint, self.chain)
         };
 
         let nominal_price =
@@ -303,6 +599,15 @@ impl OnChainEventListenerExecutor {
         // against the case of insufficient funds in the gas sponsor, resulting in a
         // fallback to an unsponsored match
         let actual_refund_amount = self.get_actual_refund_amount(receipt, nonce);
+        if !actual_refund_amount.is_zero() {
+            self.notify_gas_refund(
+                ctx,
+                receipt,
+                gas_sponsorship_info,
+                &refund_asset,
+                actual_refund_amount,
+            )?;
+        }
 
         let nominal_amount: BigDecimal = actual_refund_amount.into();
 
@@ -312,7 +617,28 @@ impl OnChainEventListenerExecutor {
             "failed to convert gas sponsorship value to f64",
         ))?;
 
-        self.rate_limiter.record_gas_sponsorship(&ctx.key_description, value).await?;
+        // Charge the key's budget, along with the key-asset and asset budgets
+        let asset =
+            base_asset_ticker(match_result.input_mint, match_result.output_mint, self.chain)?;
+        let budget_scopes = [
+            key_budget_scope(ctx.key_id),
+            key_asset_budget_scope(ctx.key_id, &asset),
+            asset_budget_scope(&asset),
+        ];
+        self.rate_limiter.record_gas_sponsorship(&budget_scopes, value).await?;
+
+        // Count the sponsored value against the key's usage
+        let usage = ApiKeyUsage::gas_sponsorship(ctx.key_id, self.chain, value);
+        if let Err(e) = record_api_key_usage(&self.db_pool, &usage).await {
+            log_task!(
+                Task::Usage,
+                Outcome::Failed,
+                subject = "gas-sponsorship",
+                error = %e,
+                "error recording gas sponsorship usage"
+            );
+        }
+
         self.record_gas_sponsorship_metrics(
             value,
             gas_sponsorship_info.refund_native_eth,
@@ -327,6 +653,33 @@ impl OnChainEventListenerExecutor {
         Ok(())
     }
 
+    /// Notify the partner of a gas sponsorship refund paid on settlement
+    fn notify_gas_refund(
+        &self,
+        ctx: &BundleContext,
+        receipt: &TransactionReceipt,
+        gas_sponsorship_info: &GasSponsorshipInfo,
+        refund_asset: &Token,
+        refund_amount: U256,
+    ) -> Result<(), AuthServerError> {
+        let refund_native_eth = gas_sponsorship_info.refund_native_eth;
+        let refund_mint = if refund_native_eth {
+            NATIVE_ASSET_ADDRESS.to_lowercase()
+        } else {
+            refund_asset.get_addr()
+        };
+
+        let event = WebhookEvent::GasRefund {
+            bundle_id: ctx.bundle_id.to_string(),
+            tx_hash: format!("{:#x}", receipt.transaction_hash),
+            refund_mint,
+            refund_native_eth,
+            refund_amount: refund_amount.try_into().map_err(AuthServerError::gas_sponsorship)?,
+        };
+        self.webhooks.notify(ctx.key_id, event);
+        Ok(())
+    }
+
     /// Record the dollar value of sponsored gas for a settled match
     #[allow(clippy::too_many_arguments)]
     async fn record_gas_sponsorship_metrics(
@@ -511,3 +864,13 @@ fn compute_external_amounts(
     let obligation = match_result.to_external_obligation(actual_external_input);
     (obligation.amount_in, obligation.amount_out)
 }
+
+/// Whether a lifecycle's markout is later than the tolerance past its horizon
+///
+/// Lateness is measured from the settlement, as the schedule is moved by each
+/// claim
+fn markout_is_late(lifecycle: &QuoteLifecycle, now: SystemTime) -> bool {
+    let Some(settled_at) = lifecycle.settled_at else { return false };
+    let deadline = settled_at + MARKOUT_HORIZON + MARKOUT_LATENESS_TOLERANCE;
+    now > deadline
+}
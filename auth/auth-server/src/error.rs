@@ -74,6 +74,12 @@ impl AuthServerError {
         Self::BadRequest(msg.to_string())
     }
 
+    /// Create a new bundle store error
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn bundle_store<T: ToString>(msg: T) -> Self {
+        Self::BundleStore(msg.to_string())
+    }
+
     /// Create a new custom error
     #[allow(clippy::needless_pass_by_value)]
     pub fn custom<T: ToString>(msg: T) -> Self {
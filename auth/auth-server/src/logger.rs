@@ -52,12 +52,28 @@ pub enum Task {
     /// Handling GET /rfqt/v3/levels: parse query, fetch market depths,
     /// transform to levels.
     RfqtLevels,
+    /// Handling GET /okx/v1/pricing: fetch market depths, transform to OKX
+    /// pricing levels.
+    OkxPricing,
+    /// Handling POST /okx/v1/quote: assemble a direct order, transform the
+    /// bundle into an OKX firm quote.
+    OkxQuote,
+    /// API key authentication and secret rotation.
+    ApiKeyAuth,
     /// Gas-sponsorship updates applied to quotes and match bundles.
     GasSponsorship,
     /// Quote / bundle / execution-cost rate limiters.
     RateLimit,
     /// Telemetry / metric-recording side paths.
     Telemetry,
+    /// Recording and querying the external-match audit log.
+    AuditLog,
+    /// Per-key usage accounting for billing.
+    Usage,
+    /// Delivering partner webhook notifications.
+    Webhooks,
+    /// Tracking quotes through assembly and settlement.
+    QuoteLifecycle,
     /// Database connection-pool lifecycle.
     Db,
 }
@@ -75,9 +91,16 @@ impl Task {
             Task::ExternalMatchAssemble => "external-match-assemble",
             Task::RfqtQuote => "rfqt-quote",
             Task::RfqtLevels => "rfqt-levels",
+            Task::OkxPricing => "okx-pricing",
+            Task::OkxQuote => "okx-quote",
+            Task::ApiKeyAuth => "api-key-auth",
             Task::GasSponsorship => "gas-sponsorship",
             Task::RateLimit => "rate-limit",
             Task::Telemetry => "telemetry",
+            Task::AuditLog => "audit-log",
+            Task::Usage => "usage",
+            Task::Webhooks => "webhooks",
+            Task::QuoteLifecycle => "quote-lifecycle",
             Task::Db => "db",
         }
     }
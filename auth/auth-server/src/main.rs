@@ -22,8 +22,14 @@ pub mod http_utils;
 mod logger;
 mod server;
 mod telemetry;
+mod webhooks;
 
 use auth_server_api::API_KEYS_PATH;
+use auth_server_api::audit_log::AuditLogQuery;
+use auth_server_api::quote_analytics::QuoteFunnelQuery;
+use auth_server_api::usage::UsageQuery;
+use auth_server_api::webhooks::WebhookDeliveriesQuery;
+use bundle_store::BundleStoreBackend;
 use clap::Parser;
 use renegade_system_clock::SystemClock;
 use renegade_types_core::Chain;
@@ -42,6 +48,7 @@ use warp::{
 };
 
 use server::Server;
+use server::chains::{CHAIN_ID_HEADER, ChainServers};
 
 use crate::error::AuthServerError;
 
@@ -69,7 +76,7 @@ pub struct Cli {
     /// requests
     #[arg(long, env = "MANAGEMENT_KEY")]
     pub management_key: String,
-    /// The URL of the relayer
+    /// The URL of the relayer settling to the default chain
     #[arg(long, env = "RELAYER_URL")]
     pub relayer_url: String,
     /// The admin key for the relayer
@@ -78,16 +85,44 @@ pub struct Cli {
     /// The port to run the server on
     #[arg(long, env = "PORT", default_value = "3000")]
     pub port: u16,
-    /// The chain that the relayer settles to
+    /// The default chain, served when a request does not select a chain
+    ///
+    /// The chain-scoped flags below (relayer, RPC, contracts and gas sponsor)
+    /// configure this chain
     #[arg(long, env = "CHAIN_ID")]
     pub chain_id: Chain,
+    /// The path to a JSON file listing the configs of chains served in
+    /// addition to the default chain
+    #[arg(long, env = "ADDITIONAL_CHAINS_CONFIG")]
+    pub additional_chains_config: Option<String>,
     /// The bundle rate limit in bundles per minute
     #[arg(long, env = "BUNDLE_RATE_LIMIT", default_value = "200")]
     pub bundle_rate_limit: u64,
     /// The quote rate limit in quotes per minute
     #[arg(long, env = "QUOTE_RATE_LIMIT", default_value = "500")]
     pub quote_rate_limit: u64,
-    /// The path to the file containing token remaps for the given chain
+    /// The default notional volume each key may assemble over a sliding hour,
+    /// in USD, unless the key has its own limit. Unlimited if unset
+    #[arg(long, env = "DEFAULT_HOURLY_VOLUME_LIMIT_USD")]
+    pub default_hourly_volume_limit_usd: Option<f64>,
+    /// The default notional volume each key may assemble over a sliding day, in
+    /// USD, unless the key has its own limit. Unlimited if unset
+    #[arg(long, env = "DEFAULT_DAILY_VOLUME_LIMIT_USD")]
+    pub default_daily_volume_limit_usd: Option<f64>,
+    /// How long an API secret remains valid after it is rotated out, in
+    /// seconds, unless overridden on the rotation request
+    #[arg(long, env = "API_SECRET_ROTATION_GRACE_PERIOD_SECS", default_value = "86400")]
+    pub api_secret_rotation_grace_period_secs: u64,
+    /// The number of trusted proxies in front of the server, each of which
+    /// appends the address it received a request from to `X-Forwarded-For`
+    ///
+    /// The client IP checked against API key allowlists is the entry the
+    /// outermost trusted proxy appended, so entries a client prepends are
+    /// ignored. If zero, or a request passed through fewer proxies, the client
+    /// IP is unknown and keys with an IP allowlist are rejected
+    #[arg(long, env = "TRUSTED_PROXY_HOPS", default_value = "1")]
+    pub trusted_proxy_hops: usize,
+    /// The path to the file containing token remaps for the default chain
     ///
     /// See https://github.com/renegade-fi/token-mappings for more information on the format of this file
     #[arg(long, env = "TOKEN_REMAP_FILE")]
@@ -107,12 +142,22 @@ pub struct Cli {
     /// The URL of the price reporter
     #[arg(long, env = "PRICE_REPORTER_URL")]
     pub price_reporter_url: String,
+    /// The URLs of fallback price reporters, in order of preference
+    #[arg(long, env = "PRICE_REPORTER_FALLBACK_URLS", value_delimiter = ',')]
+    pub price_reporter_fallback_urls: Vec<String>,
     /// The URL of the Redis cluster
     #[arg(long, env = "REDIS_URL", default_value = "redis://localhost:6379")]
     pub redis_url: String,
     /// The URL of the execution cost Redis cluster
     #[arg(long, env = "EXECUTION_COST_REDIS_URL", default_value = "redis://localhost:6379")]
     pub execution_cost_redis_url: String,
+    /// The backend used to store assembled bundles awaiting settlement
+    #[arg(long, env = "BUNDLE_STORE_BACKEND", value_enum, default_value = "redis")]
+    pub bundle_store_backend: BundleStoreBackend,
+    /// How long an assembled bundle is retained while awaiting settlement, in
+    /// seconds
+    #[arg(long, env = "BUNDLE_STORE_TTL_SECS", default_value = "3600")]
+    pub bundle_store_ttl_secs: u64,
 
     // -------------------
     // | Gas Sponsorship |
@@ -123,10 +168,12 @@ pub struct Cli {
     /// The auth private key used for gas sponsorship, encoded as a hex string
     #[clap(long, env = "GAS_SPONSOR_AUTH_KEY")]
     gas_sponsor_auth_key: String,
-    /// The maximum dollar value of gas sponsorship funds per day
+    /// The maximum dollar value of gas sponsorship funds per key per day,
+    /// unless overridden by the key's sponsorship policy
     #[arg(long, env = "MAX_GAS_SPONSORSHIP_VALUE", default_value = "25.0")]
     max_gas_sponsorship_value: f64,
-    /// The minimum quote amount for which gas sponsorship is allowed, in USD
+    /// The minimum quote amount for which gas sponsorship is allowed, in USD,
+    /// unless overridden by a sponsorship policy
     #[arg(long, env = "MIN_SPONSORED_ORDER_QUOTE_AMOUNT", default_value = "10.0")]
     min_sponsored_order_quote_amount: f64,
 
@@ -213,10 +260,11 @@ async fn main() -> Result<(), AuthServerError> {
 
     let system_clock = SystemClock::new().await;
 
-    // Create the server
-    let (server_inner, chain_listener_cancellation_token) =
+    // Create the servers for each chain, management routes are served by the
+    // default chain's server
+    let (servers, chain_listener_cancellation_token) =
         Server::setup(args, &system_clock).await.expect("Failed to create server");
-    let server = Arc::new(server_inner);
+    let server = servers.default_server();
 
     // --- Management Routes --- //
 
@@ -304,6 +352,202 @@ async fn main() -> Result<(), AuthServerError> {
             server.set_rate_limit(id, path, headers, body).await
         });
 
+    // Set the permissions of an API key
+    let set_api_key_permissions = warp::path(API_KEYS_PATH)
+        .and(warp::path::param::<Uuid>())
+        .and(warp::path("permissions"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|id, path, headers, body, server: Arc<Server>| async move {
+            server.set_api_key_permissions(id, path, headers, body).await
+        });
+
+    // Rotate the secret of an API key
+    let rotate_api_key_secret = warp::path(API_KEYS_PATH)
+        .and(warp::path::param::<Uuid>())
+        .and(warp::path("rotate-secret"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|id, path, headers, body, server: Arc<Server>| async move {
+            server.rotate_api_key_secret(id, path, headers, body).await
+        });
+
+    // Set the webhook URL of an API key
+    let set_webhook = warp::path(API_KEYS_PATH)
+        .and(warp::path::param::<Uuid>())
+        .and(warp::path("webhook"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|id, path, headers, body, server: Arc<Server>| async move {
+            server.set_webhook(id, path, headers, body).await
+        });
+
+    // List the webhook deliveries of an API key
+    let get_webhook_deliveries = warp::path(API_KEYS_PATH)
+        .and(warp::path::param::<Uuid>())
+        .and(warp::path("webhook-deliveries"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::query::<WebhookDeliveriesQuery>())
+        .and(with_server(server.clone()))
+        .and_then(|id, path, headers, query, server: Arc<Server>| async move {
+            server.get_webhook_deliveries(id, path, headers, query).await
+        });
+
+    // Query the external match audit log
+    let get_audit_log = warp::path!("v0" / "audit-log" / "external-matches")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::query::<AuditLogQuery>())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, query, server: Arc<Server>| async move {
+            server.get_audit_log(path, headers, query).await
+        });
+
+    // Export the external match audit log as CSV
+    let export_audit_log_csv = warp::path!("v0" / "audit-log" / "external-matches" / "csv")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::query::<AuditLogQuery>())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, query, server: Arc<Server>| async move {
+            server.export_audit_log_csv(path, headers, query).await
+        });
+
+    // Get per-key usage
+    let get_usage = warp::path!("v0" / "usage")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::query::<UsageQuery>())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, query, server: Arc<Server>| async move {
+            server.get_usage(path, headers, query).await
+        });
+
+    // Get quote funnel stats
+    let get_quote_funnel = warp::path!("v0" / "analytics" / "quote-funnel")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::query::<QuoteFunnelQuery>())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, query, server: Arc<Server>| async move {
+            server.get_quote_funnel(path, headers, query).await
+        });
+
+    // Get all volume limits
+    let get_volume_limits = warp::path!("v0" / "volume-limits")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, server: Arc<Server>| async move {
+            server.get_volume_limits(path, headers).await
+        });
+
+    // Set a volume limit
+    let set_volume_limit = warp::path!("v0" / "volume-limits" / "set")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.set_volume_limit(path, headers, body).await
+        });
+
+    // Remove a volume limit
+    let remove_volume_limit = warp::path!("v0" / "volume-limits" / "remove")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.remove_volume_limit(path, headers, body).await
+        });
+
+    // Get all RFQT ladders
+    let get_rfqt_ladders = warp::path!("v0" / "rfqt-ladders")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, server: Arc<Server>| async move {
+            server.get_rfqt_ladders(path, headers).await
+        });
+
+    // Set an RFQT ladder
+    let set_rfqt_ladder = warp::path!("v0" / "rfqt-ladders" / "set")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.set_rfqt_ladder(path, headers, body).await
+        });
+
+    // Remove an RFQT ladder
+    let remove_rfqt_ladder = warp::path!("v0" / "rfqt-ladders" / "remove")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.remove_rfqt_ladder(path, headers, body).await
+        });
+
+    // Get all sponsorship policies
+    let get_sponsorship_policies = warp::path!("v0" / "sponsorship-policies")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, server: Arc<Server>| async move {
+            server.get_sponsorship_policies(path, headers).await
+        });
+
+    // Set a sponsorship policy
+    let set_sponsorship_policy = warp::path!("v0" / "sponsorship-policies" / "set")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.set_sponsorship_policy(path, headers, body).await
+        });
+
+    // Remove a sponsorship policy
+    let remove_sponsorship_policy = warp::path!("v0" / "sponsorship-policies" / "remove")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.remove_sponsorship_policy(path, headers, body).await
+        });
+
     // Get all user fees
     let get_all_user_fees = warp::path!("v0" / "fees" / "get-per-user-fees")
         .and(warp::get())
@@ -358,6 +602,60 @@ async fn main() -> Result<(), AuthServerError> {
             server.remove_user_fee_override(path, headers, body).await
         });
 
+    // Get the volume-based fee schedule
+    let get_fee_schedule = warp::path!("v0" / "fees" / "get-fee-schedule")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, server: Arc<Server>| async move {
+            server.get_fee_schedule(path, headers).await
+        });
+
+    // Set a volume-based fee tier
+    let set_fee_tier = warp::path!("v0" / "fees" / "set-fee-tier")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.set_fee_tier(path, headers, body).await
+        });
+
+    // Remove a volume-based fee tier
+    let remove_fee_tier = warp::path!("v0" / "fees" / "remove-fee-tier")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.remove_fee_tier(path, headers, body).await
+        });
+
+    // Add a time-boxed promotional fee
+    let add_promotional_fee = warp::path!("v0" / "fees" / "add-promotional-fee")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.add_promotional_fee(path, headers, body).await
+        });
+
+    // Remove a promotional fee
+    let remove_promotional_fee = warp::path!("v0" / "fees" / "remove-promotional-fee")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_server(server.clone()))
+        .and_then(|path, headers, body, server: Arc<Server>| async move {
+            server.remove_promotional_fee(path, headers, body).await
+        });
+
     // --- Proxied Routes --- //
 
     let external_quote_path = warp::path("v2")
@@ -368,7 +666,7 @@ async fn main() -> Result<(), AuthServerError> {
         .and(warp::header::headers_cloned())
         .and(warp::body::bytes())
         .and(with_query_string())
-        .and(with_server(server.clone()))
+        .and(with_chain_server(servers.clone()))
         .and_then(|path, headers, body, query_str, server: Arc<Server>| async move {
             server.handle_quote_request(path, headers, body, query_str).await
         });
@@ -381,7 +679,7 @@ async fn main() -> Result<(), AuthServerError> {
         .and(warp::header::headers_cloned())
         .and(warp::body::bytes())
         .and(with_query_string())
-        .and(with_server(server.clone()))
+        .and(with_chain_server(servers.clone()))
         .and_then(|path, headers, body, query_str, server: Arc<Server>| async move {
             server.handle_assemble_match_bundle_request(path, headers, body, query_str).await
         });
@@ -392,7 +690,7 @@ async fn main() -> Result<(), AuthServerError> {
         .and(warp::get())
         .and(warp::path::full())
         .and(warp::header::headers_cloned())
-        .and(with_server(server.clone()))
+        .and(with_chain_server(servers.clone()))
         .and_then(|path, headers, server: Arc<Server>| async move {
             server.handle_all_markets_request(path, headers).await
         });
@@ -404,7 +702,7 @@ async fn main() -> Result<(), AuthServerError> {
         .and(warp::path::end())
         .and(warp::path::full())
         .and(warp::header::headers_cloned())
-        .and(with_server(server.clone()))
+        .and(with_chain_server(servers.clone()))
         .and_then(|mint, path, headers, server: Arc<Server>| async move {
             server.handle_market_depth_by_mint_request(mint, path, headers).await
         });
@@ -415,7 +713,7 @@ async fn main() -> Result<(), AuthServerError> {
         .and(warp::path::end())
         .and(warp::path::full())
         .and(warp::header::headers_cloned())
-        .and(with_server(server.clone()))
+        .and(with_chain_server(servers.clone()))
         .and_then(|path, headers, server: Arc<Server>| async move {
             server.handle_all_markets_depth_request(path, headers).await
         });
@@ -426,7 +724,7 @@ async fn main() -> Result<(), AuthServerError> {
         .and(warp::get())
         .and(warp::path::full())
         .and(warp::header::headers_cloned())
-        .and(with_server(server.clone()))
+        .and(with_chain_server(servers.clone()))
         .and_then(|path, headers, server: Arc<Server>| async move {
             server.handle_exchange_metadata_request(path, headers).await
         });
@@ -435,7 +733,7 @@ async fn main() -> Result<(), AuthServerError> {
         .and(warp::path::full())
         .and(warp::header::headers_cloned())
         .and(with_query_string())
-        .and(with_server(server.clone()))
+        .and(with_chain_server(servers.clone()))
         .and_then(|path, headers, query_str, server: Arc<Server>| async move {
             server.handle_rfqt_levels_request(path, headers, query_str).await
         });
@@ -446,11 +744,32 @@ async fn main() -> Result<(), AuthServerError> {
         .and(warp::header::headers_cloned())
         .and(warp::body::bytes())
         .and(with_query_string())
-        .and(with_server(server.clone()))
+        .and(with_chain_server(servers.clone()))
         .and_then(|path, headers, body, query_str, server: Arc<Server>| async move {
             server.handle_rfqt_quote_request(path, headers, body, query_str).await
         });
 
+    let okx_pricing_path = warp::path!("okx" / "v1" / "pricing")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(with_query_string())
+        .and(with_chain_server(servers.clone()))
+        .and_then(|path, headers, query_str, server: Arc<Server>| async move {
+            server.handle_okx_pricing_request(path, headers, query_str).await
+        });
+
+    let okx_quote_path = warp::path!("okx" / "v1" / "quote")
+        .and(warp::post())
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .and(with_query_string())
+        .and(with_chain_server(servers.clone()))
+        .and_then(|path, headers, body, query_str, server: Arc<Server>| async move {
+            server.handle_okx_quote_request(path, headers, body, query_str).await
+        });
+
     // Bind the server and listen
     log_task!(
         Task::ServiceLifecycle,
@@ -466,6 +785,23 @@ async fn main() -> Result<(), AuthServerError> {
         .or(whitelist_api_key)
         .or(remove_whitelist_entry)
         .or(set_rate_limit)
+        .or(set_api_key_permissions)
+        .or(rotate_api_key_secret)
+        .or(set_webhook)
+        .or(get_webhook_deliveries)
+        .or(get_audit_log)
+        .or(export_audit_log_csv)
+        .or(get_usage)
+        .or(get_quote_funnel)
+        .or(get_volume_limits)
+        .or(set_volume_limit)
+        .or(remove_volume_limit)
+        .or(get_rfqt_ladders)
+        .or(set_rfqt_ladder)
+        .or(remove_rfqt_ladder)
+        .or(get_sponsorship_policies)
+        .or(set_sponsorship_policy)
+        .or(remove_sponsorship_policy)
         .or(add_api_key)
         .or(get_all_keys)
         .or(get_all_user_fees)
@@ -473,12 +809,19 @@ async fn main() -> Result<(), AuthServerError> {
         .or(set_user_fee_override)
         .or(remove_asset_default_fee)
         .or(remove_user_fee_override)
+        .or(get_fee_schedule)
+        .or(set_fee_tier)
+        .or(remove_fee_tier)
+        .or(add_promotional_fee)
+        .or(remove_promotional_fee)
         .or(all_markets)
         .or(market_depth_by_mint)
         .or(all_markets_depth)
         .or(exchange_metadata_path)
         .or(rfqt_levels_path)
         .or(rfqt_quote_path)
+        .or(okx_pricing_path)
+        .or(okx_quote_path)
         .boxed()
         .with(with_tracing())
         .recover(handle_rejection);
@@ -500,6 +843,17 @@ fn with_server(
     warp::any().map(move || server.clone())
 }
 
+/// Helper function to pass the server for the chain selected by a request's
+/// chain ID header to filters
+fn with_chain_server(
+    servers: ChainServers,
+) -> impl Filter<Extract = (Arc<Server>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>(CHAIN_ID_HEADER).and_then(move |chain_id: Option<String>| {
+        let servers = servers.clone();
+        async move { servers.select(chain_id.as_deref()).map_err(warp::reject::custom) }
+    })
+}
+
 /// Helper function to parse the raw query string, returning an empty string
 /// instead of rejecting in the case that no query string is present
 fn with_query_string() -> impl Filter<Extract = (String,), Error = std::convert::Infallible> + Clone
@@ -1,16 +1,38 @@
 //! Handles API authentication
 
-use auth_server_api::RENEGADE_API_KEY_HEADER;
+use std::{
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use auth_server_api::{
+    RENEGADE_API_KEY_HEADER,
+    key_management::{ApiKeyPermissions, ApiKeyScope},
+};
 use http::HeaderMap;
 use renegade_external_api::auth::validate_expiring_auth;
 use renegade_types_core::HmacKey;
-use tracing::instrument;
+use tracing::{Span, field, instrument};
 use uuid::Uuid;
 use warp::filters::path::FullPath;
 
-use crate::{ApiError, error::AuthServerError, http_utils::request_response::convert_headers};
+use crate::{
+    ApiError,
+    error::AuthServerError,
+    http_utils::request_response::convert_headers,
+    log_task,
+    logger::{Outcome, Task},
+    telemetry::helpers::record_api_key_auth,
+};
+
+use super::{
+    Server,
+    db::models::{ApiKey, ApiKeySecret},
+    helpers::aes_decrypt,
+};
 
-use super::{Server, helpers::aes_decrypt};
+/// The header carrying the client IP chain when behind a proxy
+const X_FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
 
 impl Server {
     /// Authorize a management request
@@ -27,15 +49,23 @@ impl Server {
 
     /// Authorize a request
     ///
+    /// Checks the request's HMAC, then that the key's permissions grant the
+    /// given scope and admit the request, i.e. that the key has not expired
+    /// and the client IP is allowlisted
+    ///
+    /// The generation of the secret that authenticated the request is recorded
+    /// on the request span and in metrics
+    ///
     /// Returns the description for the API key, i.e. a human readable name for
     /// the entity that is making the request, and the API key id
-    #[instrument(skip_all)]
+    #[instrument(skip_all, fields(secret_generation = field::Empty))]
     pub(crate) async fn authorize_request(
         &self,
         path: &str,
         query_str: &str,
         headers: &HeaderMap,
         body: &[u8],
+        scope: ApiKeyScope,
     ) -> Result<(String, Uuid), AuthServerError> {
         let auth_path =
             if query_str.is_empty() { path } else { &format!("{}?{}", path, query_str) };
@@ -47,36 +77,90 @@ impl Server {
             .and_then(|s| Uuid::parse_str(&s).ok()) // Use &s to parse
             .ok_or(AuthServerError::unauthorized("Invalid or missing Renegade API key"))?;
 
-        let key_description = self.check_api_key_auth(api_key, auth_path, headers, body).await?;
-        Ok((key_description, api_key))
+        let (entry, generation) =
+            self.check_api_key_auth(api_key, auth_path, headers, body).await?;
+        let client_ip = client_ip(headers, self.trusted_proxy_hops);
+        check_key_permissions(&entry.permissions(), scope, client_ip, now_secs())?;
+
+        // Report the secret generation that authenticated the request
+        Span::current().record("secret_generation", generation);
+        let is_previous_secret = generation != entry.secret_generation;
+        if is_previous_secret {
+            log_task!(
+                Task::ApiKeyAuth,
+                Outcome::Ok,
+                subject = %api_key,
+                key_description = %entry.description,
+                secret_generation = generation,
+                current_generation = entry.secret_generation,
+                "request authenticated with a previous API secret"
+            );
+        }
+        record_api_key_auth(entry.description.clone(), generation, is_previous_secret);
+
+        Ok((entry.description, api_key))
+    }
+
+    /// Check that the given API key may access the market for the given mint
+    pub(crate) async fn authorize_mint(
+        &self,
+        key_id: Uuid,
+        mint: &str,
+    ) -> Result<(), AuthServerError> {
+        let permissions = self.get_api_key_entry(key_id).await?.permissions();
+        if !mint_allowed(&permissions, mint) {
+            return Err(AuthServerError::unauthorized(format!(
+                "API key is not permitted to access market {mint}"
+            )));
+        }
+
+        Ok(())
     }
 
     /// Check that a request is authorized with a given API key and an HMAC of
-    /// the request using the API secret
+    /// the request using one of the key's secrets
     ///
-    /// Returns the description for the API key, i.e. a human readable name for
-    /// the entity that is making the request
+    /// The current secret is tried first, then any previous secrets still
+    /// within their rotation grace period, newest first
+    ///
+    /// Returns the database entry for the API key and the generation of the
+    /// secret that authenticated the request
     async fn check_api_key_auth(
         &self,
         api_key: Uuid,
         path: &str,
         headers: &HeaderMap,
         body: &[u8],
-    ) -> Result<String, AuthServerError> {
-        let (api_secret, description) = self.get_api_secret(api_key).await?;
-        let key = HmacKey::from_base64_string(&api_secret).map_err(AuthServerError::serde)?;
-
+    ) -> Result<(ApiKey, i32), AuthServerError> {
+        let (api_secret, entry) = self.get_api_secret(api_key).await?;
         let auth_headers = convert_headers(headers);
-        validate_expiring_auth(path, &auth_headers, body, &key)
-            .map_err(AuthServerError::unauthorized)?;
-        Ok(description)
+        let validate = |secret: &str| -> Result<(), AuthServerError> {
+            let key = HmacKey::from_base64_string(secret).map_err(AuthServerError::serde)?;
+            validate_expiring_auth(path, &auth_headers, body, &key)
+                .map_err(AuthServerError::unauthorized)
+        };
+
+        // Only fetch the previous secrets if the current secret fails
+        if validate(&api_secret).is_ok() {
+            let generation = entry.secret_generation;
+            return Ok((entry, generation));
+        }
+
+        let previous = self.get_previous_secrets(api_key).await?;
+        let generation = authenticating_generation(
+            (&api_secret, entry.secret_generation),
+            &previous,
+            SystemTime::now(),
+            |secret| aes_decrypt(&secret.encrypted_key, &self.encryption_key),
+            validate,
+        )?;
+        Ok((entry, generation))
     }
 
     /// Get the API secret for a given API key
     ///
-    /// Also returns the description for the API key, i.e. a human readable name
-    /// for the entity that is making the request
-    async fn get_api_secret(&self, api_key: Uuid) -> Result<(String, String), AuthServerError> {
+    /// Also returns the database entry for the API key
+    async fn get_api_secret(&self, api_key: Uuid) -> Result<(String, ApiKey), AuthServerError> {
         // Fetch the API key entry then decrypt the API secret
         let entry = self.get_api_key_entry(api_key).await?;
         let decrypted = aes_decrypt(&entry.encrypted_key, &self.encryption_key)?;
@@ -84,6 +168,293 @@ impl Server {
             return Err(AuthServerError::ApiKeyInactive);
         }
 
-        Ok((decrypted, entry.description))
+        Ok((decrypted, entry))
+    }
+}
+
+// ------------------
+// | Secret Checking |
+// ------------------
+
+/// Get the generation of the secret that authenticates a request
+///
+/// The current secret, given with its generation, is tried first, then the
+/// previous secrets still within their grace period at `now`, newest first.
+/// Returns the current secret's error if no secret authenticates the request
+fn authenticating_generation(
+    (current_secret, current_generation): (&str, i32),
+    previous: &[ApiKeySecret],
+    now: SystemTime,
+    decrypt: impl Fn(&ApiKeySecret) -> Result<String, AuthServerError>,
+    validate: impl Fn(&str) -> Result<(), AuthServerError>,
+) -> Result<i32, AuthServerError> {
+    let err = match validate(current_secret) {
+        Ok(()) => return Ok(current_generation),
+        Err(e) => e,
+    };
+
+    for secret in previous.iter().filter(|secret| secret.is_valid_at(now)) {
+        if validate(&decrypt(secret)?).is_ok() {
+            return Ok(secret.generation);
+        }
+    }
+
+    Err(err)
+}
+
+// -----------------------
+// | Permission Checking |
+// -----------------------
+
+/// Check that a key's permissions admit a request
+///
+/// Unset restrictions admit all requests
+fn check_key_permissions(
+    permissions: &ApiKeyPermissions,
+    scope: ApiKeyScope,
+    client_ip: Option<IpAddr>,
+    now_secs: u64,
+) -> Result<(), AuthServerError> {
+    if permissions.expires_at.is_some_and(|expires_at| expires_at <= now_secs) {
+        return Err(AuthServerError::unauthorized("API key expired"));
+    }
+
+    if let Some(scopes) = &permissions.scopes
+        && !scopes.contains(&scope)
+    {
+        return Err(AuthServerError::unauthorized(format!("API key lacks the `{scope}` scope")));
+    }
+
+    if let Some(allowed_ips) = &permissions.allowed_ips {
+        let allowed =
+            client_ip.is_some_and(|ip| allowed_ips.iter().any(|rule| ip_matches_rule(ip, rule)));
+        if !allowed {
+            return Err(AuthServerError::unauthorized("Client IP not allowed for API key"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a key's permissions admit the given mint
+///
+/// The deny list takes precedence over the allow list
+pub(crate) fn mint_allowed(permissions: &ApiKeyPermissions, mint: &str) -> bool {
+    let contains = |mints: &Vec<String>| mints.iter().any(|m| m.eq_ignore_ascii_case(mint));
+    if permissions.denied_mints.as_ref().is_some_and(contains) {
+        return false;
+    }
+
+    permissions.allowed_mints.as_ref().is_none_or(contains)
+}
+
+/// Parse an IP allowlist entry, either a single address or a CIDR block
+///
+/// Returns the network address and prefix length
+pub(crate) fn parse_ip_rule(rule: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix) = match rule.split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+        None => (rule.parse::<IpAddr>().ok()?, None),
+    };
+
+    let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max_prefix);
+    (prefix <= max_prefix).then_some((addr, prefix))
+}
+
+/// Whether the given IP falls within an IP allowlist entry
+fn ip_matches_rule(ip: IpAddr, rule: &str) -> bool {
+    let Some((network, prefix)) = parse_ip_rule(rule) else {
+        return false;
+    };
+
+    match (network, ip.to_canonical()) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            prefix_match(u32::from(network).into(), u32::from(ip).into(), prefix, 32)
+        },
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            prefix_match(network.into(), ip.into(), prefix, 128)
+        },
+        _ => false,
+    }
+}
+
+/// Whether the leading `prefix` bits of two `bits`-wide addresses match
+fn prefix_match(network: u128, ip: u128, prefix: u8, bits: u8) -> bool {
+    if prefix == 0 {
+        return true;
+    }
+
+    let shift = u32::from(bits - prefix);
+    (network >> shift) == (ip >> shift)
+}
+
+/// Get the client IP of a request behind the given number of trusted proxies
+///
+/// Each proxy appends the address it received the request from to
+/// `X-Forwarded-For`, so the client IP is the entry appended by the outermost
+/// trusted proxy, `trusted_hops` from the right. Entries to its left were
+/// supplied by the client and are not trusted. Repeated headers are treated as
+/// a single comma-separated list
+fn client_ip(headers: &HeaderMap, trusted_hops: usize) -> Option<IpAddr> {
+    if trusted_hops == 0 {
+        return None;
+    }
+
+    let hops = headers
+        .get_all(X_FORWARDED_FOR_HEADER)
+        .iter()
+        .map(|h| h.to_str().ok())
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .flat_map(|s| s.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+
+    let idx = hops.len().checked_sub(trusted_hops)?;
+    hops[idx].parse().ok()
+}
+
+/// The current unix timestamp in seconds
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// A mint used across tests
+    const WETH_ADDR: &str = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1";
+
+    #[test]
+    fn unrestricted_permissions_admit_all() {
+        let permissions = ApiKeyPermissions::default();
+        assert!(check_key_permissions(&permissions, ApiKeyScope::Rfqt, None, 0).is_ok());
+        assert!(mint_allowed(&permissions, WETH_ADDR));
+    }
+
+    #[test]
+    fn scopes_and_expiry_are_enforced() {
+        let permissions = ApiKeyPermissions {
+            scopes: Some(vec![ApiKeyScope::Quote]),
+            expires_at: Some(100),
+            ..Default::default()
+        };
+
+        assert!(check_key_permissions(&permissions, ApiKeyScope::Quote, None, 99).is_ok());
+        assert!(check_key_permissions(&permissions, ApiKeyScope::Assemble, None, 99).is_err());
+        assert!(check_key_permissions(&permissions, ApiKeyScope::Quote, None, 100).is_err());
+    }
+
+    #[test]
+    fn ip_allowlist_matches_addresses_and_cidrs() {
+        let permissions = ApiKeyPermissions {
+            allowed_ips: Some(vec!["10.0.0.0/8".to_string(), "2001:db8::1".to_string()]),
+            ..Default::default()
+        };
+        let check = |ip: Option<&str>| {
+            let ip = ip.map(|ip| ip.parse().unwrap());
+            check_key_permissions(&permissions, ApiKeyScope::Quote, ip, 0).is_ok()
+        };
+
+        assert!(check(Some("10.1.2.3")));
+        assert!(check(Some("::ffff:10.1.2.3")));
+        assert!(check(Some("2001:db8::1")));
+        assert!(!check(Some("11.0.0.1")));
+        assert!(!check(Some("2001:db8::2")));
+        assert!(!check(None));
+        assert!(parse_ip_rule("10.0.0.0/33").is_none());
+    }
+
+    #[test]
+    fn mint_lists_compare_case_insensitively() {
+        let permissions = ApiKeyPermissions {
+            allowed_mints: Some(vec![WETH_ADDR.to_uppercase()]),
+            ..Default::default()
+        };
+        assert!(mint_allowed(&permissions, WETH_ADDR));
+        assert!(!mint_allowed(&permissions, "0x1111111111111111111111111111111111111111"));
+
+        let permissions = ApiKeyPermissions {
+            denied_mints: Some(vec![WETH_ADDR.to_string()]),
+            ..Default::default()
+        };
+        assert!(!mint_allowed(&permissions, WETH_ADDR));
+    }
+
+    /// Build a previous secret, stored unencrypted, expiring at the given time
+    fn previous_secret(secret: &str, generation: i32, expires_at: SystemTime) -> ApiKeySecret {
+        ApiKeySecret {
+            api_key_id: Uuid::nil(),
+            generation,
+            encrypted_key: secret.to_string(),
+            expires_at,
+        }
+    }
+
+    /// Get the generation of the secret among the given ones that signed a
+    /// request, at the given time
+    fn signed_generation(
+        signer: &str,
+        current: (&str, i32),
+        previous: &[ApiKeySecret],
+        now: SystemTime,
+    ) -> Option<i32> {
+        let decrypt = |secret: &ApiKeySecret| Ok(secret.encrypted_key.clone());
+        let validate = |secret: &str| {
+            (secret == signer).then_some(()).ok_or(AuthServerError::unauthorized("bad signature"))
+        };
+        authenticating_generation(current, previous, now, decrypt, validate).ok()
+    }
+
+    #[test]
+    fn rotated_secrets_are_accepted_within_grace_period() {
+        let now = SystemTime::now();
+        let grace_end = now + Duration::from_secs(60);
+        let previous = [previous_secret("gen-2", 2, grace_end), previous_secret("gen-1", 1, now)];
+        let current = ("gen-3", 3);
+
+        // The current secret authenticates as the current generation
+        assert_eq!(signed_generation("gen-3", current, &previous, now), Some(3));
+
+        // The last rotated out secret falls back within its grace period, the
+        // one before it has expired
+        assert_eq!(signed_generation("gen-2", current, &previous, now), Some(2));
+        assert_eq!(signed_generation("gen-1", current, &previous, now), None);
+        assert_eq!(signed_generation("unknown", current, &previous, now), None);
+
+        // Once the grace period ends, only the current secret is accepted
+        assert_eq!(signed_generation("gen-2", current, &previous, grace_end), None);
+        assert_eq!(signed_generation("gen-3", current, &previous, grace_end), Some(3));
+    }
+
+    #[test]
+    fn spoofed_forwarded_for_is_rejected() {
+        // The client claims to be an allowlisted address, and the single
+        // trusted proxy appends the address it actually connected from
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR_HEADER, "10.0.0.1, 3.3.3.3".parse().unwrap());
+        assert_eq!(client_ip(&headers, 1), Some("3.3.3.3".parse().unwrap()));
+
+        let permissions = ApiKeyPermissions {
+            allowed_ips: Some(vec!["10.0.0.0/8".to_string()]),
+            ..Default::default()
+        };
+        let ip = client_ip(&headers, 1);
+        assert!(check_key_permissions(&permissions, ApiKeyScope::Quote, ip, 0).is_err());
+
+        // Behind two trusted proxies, the outer proxy's entry is the client
+        headers.insert(X_FORWARDED_FOR_HEADER, "10.0.0.1, 3.3.3.3, 4.4.4.4".parse().unwrap());
+        assert_eq!(client_ip(&headers, 2), Some("3.3.3.3".parse().unwrap()));
+
+        // Without the trusted proxies' entries, or without trusted proxies,
+        // the client IP is unknown
+        headers.insert(X_FORWARDED_FOR_HEADER, "10.0.0.1".parse().unwrap());
+        assert_eq!(client_ip(&headers, 2), None);
+        assert_eq!(client_ip(&headers, 0), None);
+        assert_eq!(client_ip(&HeaderMap::new(), 1), None);
     }
 }
@@ -0,0 +1,345 @@
+//! Handlers for the external match audit log
+//!
+//! Quotes, assemblies, and settlements are recorded off the hot path after the
+//! response has been sent; the management endpoints here query the log
+
+use auth_server_api::audit_log::{AuditEventType, AuditLogEntry, AuditLogQuery, AuditLogResponse};
+use bytes::Bytes;
+use http::{
+    HeaderMap, Response, StatusCode,
+    header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+};
+use renegade_util::hex::address_to_hex_string;
+use tracing::instrument;
+use warp::{filters::path::FullPath, reject::Rejection, reply::Json};
+
+use super::Server;
+use crate::error::AuthServerError;
+use crate::server::api_handlers::external_match::{
+    BytesResponse, SponsoredExternalMatchResponseCtx, SponsoredQuoteResponseCtx,
+};
+use crate::server::chains::ServedTokens;
+use crate::server::db::models::NewAuditEvent;
+use crate::server::helpers::pick_base_and_quote_mints;
+use crate::telemetry::helpers::calculate_quote_per_base_price;
+
+/// The number of entries returned by an audit log query if no limit is given
+const DEFAULT_AUDIT_LOG_LIMIT: u32 = 1_000;
+/// The maximum number of entries returned by an audit log query
+const MAX_AUDIT_LOG_LIMIT: u32 = 50_000;
+/// The filename suggested for a CSV export of the audit log
+const AUDIT_LOG_CSV_FILENAME: &str = "external-match-audit-log.csv";
+
+/// The error message emitted when an asset filter is not a known ticker
+const ERR_UNKNOWN_ASSET: &str = "Unknown asset";
+/// The error message emitted when an assembled bundle has no context
+const ERR_NO_BUNDLE_CONTEXT: &str = "No bundle context found for assembled bundle";
+
+/// The header row of a CSV export of the audit log
+const CSV_HEADER: [&str; 20] = [
+    "id",
+    "chain_id",
+    "event_type",
+    "api_key_id",
+    "key_description",
+    "request_id",
+    "bundle_id",
+    "base_mint",
+    "input_mint",
+    "output_mint",
+    "input_amount",
+    "output_amount",
+    "price",
+    "price_timestamp",
+    "is_sponsored",
+    "refund_amount",
+    "tx_hash",
+    "assembly_delay_ms",
+    "settlement_delay_ms",
+    "created_at",
+];
+
+impl Server {
+    // --- Management Endpoints --- //
+
+    /// Query the external match audit log
+    #[instrument(skip_all)]
+    pub async fn get_audit_log(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        query: AuditLogQuery,
+    ) -> Result<Json, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+        let entries = self.query_audit_log(query).await?;
+        Ok(warp::reply::json(&AuditLogResponse { entries }))
+    }
+
+    /// Export the external match audit log as CSV
+    #[instrument(skip_all)]
+    pub async fn export_audit_log_csv(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        query: AuditLogQuery,
+    ) -> Result<BytesResponse, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+        let entries = self.query_audit_log(query).await?;
+
+        let disposition = format!("attachment; filename=\"{AUDIT_LOG_CSV_FILENAME}\"");
+        let resp = Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/csv")
+            .header(CONTENT_DISPOSITION, disposition)
+            .body(Bytes::from(audit_log_to_csv(&entries)))
+            .map_err(AuthServerError::custom)?;
+        Ok(resp)
+    }
+
+    /// Fetch the audit log entries matching a query, newest first
+    async fn query_audit_log(
+        &self,
+        query: AuditLogQuery,
+    ) -> Result<Vec<AuditLogEntry>, AuthServerError> {
+        let base_mints = query
+            .asset
+            .as_deref()
+            .map(|asset| resolve_asset_mints(asset, &self.served_tokens))
+            .transpose()?;
+        let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LOG_LIMIT).min(MAX_AUDIT_LOG_LIMIT);
+
+        let events =
+            self.get_audit_events_query(&query, base_mints.as_deref(), limit as i64).await?;
+        events
+            .into_iter()
+            .map(|event| AuditLogEntry::try_from(event).map_err(AuthServerError::custom))
+            .collect()
+    }
+
+    // --- Recording --- //
+
+    /// Record a quote sent to a client in the audit log
+    pub(crate) async fn record_quote_audit_event(
+        &self,
+        ctx: &SponsoredQuoteResponseCtx,
+    ) -> Result<(), AuthServerError> {
+        let resp = ctx.response();
+        let quote = &resp.signed_quote.quote;
+        let match_result = &quote.match_result;
+        let (base_mint, _) = pick_base_and_quote_mints(
+            match_result.input_mint,
+            match_result.output_mint,
+            self.chain,
+        )?;
+
+        let refund_amount = resp.gas_sponsorship_info.map(|info| info.refund_amount);
+        let event = NewAuditEvent::new(
+            self.chain,
+            AuditEventType::Quote,
+            Some(ctx.key_id),
+            ctx.user(),
+            ctx.request_id.to_string(),
+            address_to_hex_string(&base_mint),
+            address_to_hex_string(&match_result.input_mint),
+            address_to_hex_string(&match_result.output_mint),
+            match_result.input_amount,
+            match_result.output_amount,
+        )
+        .with_price(quote.price.price, quote.price.timestamp)
+        .with_refund_amount(refund_amount);
+
+        self.insert_audit_event_query(&event).await
+    }
+
+    /// Record an assembled match bundle in the audit log
+    ///
+    /// Expects the bundle context to have already been written to the bundle
+    /// store, from which the price and assembly timestamps are taken
+    pub(crate) async fn record_assembly_audit_event(
+        &self,
+        ctx: &SponsoredExternalMatchResponseCtx,
+    ) -> Result<(), AuthServerError> {
+        let bundle_id = ctx
+            .sponsorship_nonce()
+            .ok_or_else(|| AuthServerError::custom(ERR_NO_BUNDLE_CONTEXT))?;
+        let bundle_ctx = self
+            .bundle_store
+            .read(&bundle_id)
+            .await?
+            .ok_or_else(|| AuthServerError::custom(ERR_NO_BUNDLE_CONTEXT))?;
+
+        let resp = ctx.response();
+        let match_bundle = &resp.match_bundle;
+        let match_result = &match_bundle.match_result;
+        let (base_mint, _) = pick_base_and_quote_mints(
+            match_result.input_mint,
+            match_result.output_mint,
+            self.chain,
+        )?;
+        let price = calculate_quote_per_base_price(match_result, self.chain)?;
+
+        let price_timestamp = bundle_ctx.price_timestamp;
+        let assembly_delay =
+            bundle_ctx.assembled_timestamp.map(|ts| ts.saturating_sub(price_timestamp));
+        let refund_amount = resp.gas_sponsorship_info.map(|info| info.refund_amount);
+        let event = NewAuditEvent::new(
+            self.chain,
+            AuditEventType::Assemble,
+            Some(ctx.key_id),
+            ctx.user(),
+            ctx.request_id.to_string(),
+            address_to_hex_string(&base_mint),
+            address_to_hex_string(&match_result.input_mint),
+            address_to_hex_string(&match_result.output_mint),
+            match_bundle.max_send.amount,
+            match_bundle.max_receive.amount,
+        )
+        .with_bundle_id(bundle_ctx.bundle_id.to_string())
+        .with_price(price, price_timestamp)
+        .with_refund_amount(refund_amount)
+        .with_assembly_delay_ms(assembly_delay);
+
+        self.insert_audit_event_query(&event).await
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Resolve an asset filter to the lowercase mints it matches
+///
+/// Filters prefixed with `0x` are taken as mint addresses, anything else is
+/// looked up as a ticker and matches the ticker's mint on every served chain
+pub(super) fn resolve_asset_mints(
+    asset: &str,
+    tokens: &ServedTokens,
+) -> Result<Vec<String>, AuthServerError> {
+    if asset.starts_with("0x") {
+        return Ok(vec![asset.to_lowercase()]);
+    }
+
+    let mints = tokens.mints(&asset.to_uppercase());
+    if mints.is_empty() {
+        return Err(AuthServerError::bad_request(format!("{ERR_UNKNOWN_ASSET}: {asset}")));
+    }
+    Ok(mints)
+}
+
+/// Serialize audit log entries as CSV, including a header row
+fn audit_log_to_csv(entries: &[AuditLogEntry]) -> String {
+    let mut csv = CSV_HEADER.join(",");
+    csv.push('\n');
+
+    for entry in entries {
+        let fields = [
+            entry.id.to_string(),
+            entry.chain_id.to_string(),
+            entry.event_type.to_string(),
+            display_opt(&entry.api_key_id),
+            entry.key_description.clone(),
+            entry.request_id.clone(),
+            display_opt(&entry.bundle_id),
+            entry.base_mint.clone(),
+            entry.input_mint.clone(),
+            entry.output_mint.clone(),
+            entry.input_amount.to_string(),
+            entry.output_amount.to_string(),
+            display_opt(&entry.price),
+            display_opt(&entry.price_timestamp),
+            entry.is_sponsored.to_string(),
+            display_opt(&entry.refund_amount),
+            display_opt(&entry.tx_hash),
+            display_opt(&entry.assembly_delay_ms),
+            display_opt(&entry.settlement_delay_ms),
+            entry.created_at.to_string(),
+        ];
+
+        let row: Vec<String> = fields.iter().map(|field| csv_escape(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Display an optional value, using an empty string for `None`
+fn display_opt<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+/// Escape a CSV field, quoting it if it contains a delimiter, quote, or newline
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use renegade_types_core::Chain;
+
+    use super::*;
+
+    #[test]
+    fn csv_escape_quotes_special_characters() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_rows_match_header() {
+        let entry = AuditLogEntry {
+            id: 1,
+            chain_id: 42161,
+            event_type: AuditEventType::Settlement,
+            api_key_id: None,
+            key_description: "desk, inc".to_string(),
+            request_id: "req".to_string(),
+            bundle_id: Some("42".to_string()),
+            base_mint: "0xbase".to_string(),
+            input_mint: "0xbase".to_string(),
+            output_mint: "0xquote".to_string(),
+            input_amount: 100,
+            output_amount: 200,
+            price: Some(2.),
+            price_timestamp: Some(10),
+            is_sponsored: false,
+            refund_amount: None,
+            tx_hash: Some("0xabc".to_string()),
+            assembly_delay_ms: Some(5),
+            settlement_delay_ms: Some(20),
+            created_at: 30,
+        };
+
+        let csv = audit_log_to_csv(&[entry]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].split(',').count(), CSV_HEADER.len());
+        assert_eq!(
+            lines[1],
+            "1,42161,settlement,,\"desk, inc\",req,42,0xbase,0xbase,0xquote,100,200,2,10,false,,0xabc,5,20,30"
+        );
+    }
+
+    #[test]
+    fn asset_filters_resolve_to_mints() {
+        let mint = "0xAF88D065E77C8CC2239327C5EDB3A432268E5831";
+        let tokens = ServedTokens::new(HashMap::from([
+            (Chain::ArbitrumOne, HashMap::from([("USDC".to_string(), "0xarb".to_string())])),
+            (Chain::BaseMainnet, HashMap::from([("USDC".to_string(), "0xbase".to_string())])),
+        ]));
+        assert_eq!(resolve_asset_mints(mint, &tokens).unwrap(), vec![mint.to_lowercase()]);
+
+        let mut usdc_mints = resolve_asset_mints("usdc", &tokens).unwrap();
+        usdc_mints.sort();
+        assert_eq!(usdc_mints, vec!["0xarb".to_string(), "0xbase".to_string()]);
+        assert!(resolve_asset_mints("DOGE", &tokens).is_err());
+    }
+}
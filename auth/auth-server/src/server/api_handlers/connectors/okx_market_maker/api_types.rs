@@ -1,5 +1,6 @@
 //! API types for the OKX Market Maker API
 
+use alloy_primitives::{Address, Bytes};
 use serde::{Deserialize, Serialize};
 
 // -----------
@@ -54,3 +55,72 @@ pub struct LevelDataEntry {
     /// - Second element: Exchange rate (takerTokenRate) as a decimal string
     pub levels: Vec<(String, String)>,
 }
+
+// ---------
+// | Quote |
+// ---------
+
+/// A firm quote request from the OKX Market Maker API
+///
+/// Amounts are in base units of their token
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OkxQuoteRequest {
+    /// Chain identifier
+    pub chain_index: u64,
+    /// Address of the token input by the taker
+    pub taker_token_address: Address,
+    /// Address of the token output by the maker
+    pub maker_token_address: Address,
+    /// The amount of the taker token to sell
+    pub taker_token_amount: String,
+    /// The address which receives the maker token and submits the settlement
+    /// transaction
+    pub taker_address: Address,
+}
+
+/// A firm quote response to the OKX Market Maker API
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OkxQuoteResponse {
+    /// Response code ("0" indicates success)
+    pub code: String,
+    /// Response message
+    pub msg: String,
+    /// Response data
+    pub data: OkxQuoteData,
+}
+
+/// Data payload in a firm quote response
+///
+/// Amounts are in base units of their token
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OkxQuoteData {
+    /// Chain identifier
+    pub chain_index: String,
+    /// Address of the token input by the taker
+    pub taker_token_address: String,
+    /// The amount of the taker token sold
+    pub taker_token_amount: String,
+    /// Address of the token output by the maker
+    pub maker_token_address: String,
+    /// The amount of the maker token bought
+    pub maker_token_amount: String,
+    /// The settlement transaction to submit
+    pub settlement: OkxSettlementTx,
+    /// The time after which the quote may no longer settle, in seconds since
+    /// the epoch
+    pub deadline: String,
+}
+
+/// A settlement transaction for a firm quote
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OkxSettlementTx {
+    /// The address of the settlement contract
+    pub to: String,
+    /// The calldata of the settlement transaction
+    pub calldata: Bytes,
+    /// The native asset value to attach to the transaction, in wei
+    pub value: String,
+}
@@ -1,7 +1,38 @@
 //! Helpers for the OKX Market Maker API
 
-use super::api_types::OkxPricingQueryParams;
-use crate::error::AuthServerError;
+use alloy_primitives::{TxKind, U256};
+use renegade_external_api::{
+    http::{
+        external_match::{
+            AssembleExternalMatchRequest, ExternalMatchAssemblyType, ExternalMatchingEngineOptions,
+        },
+        market::GetMarketDepthsResponse,
+    },
+    types::{BoundedExternalMatchApiBundle, ExternalOrder},
+};
+use renegade_types_core::{Chain, Token};
+use renegade_util::hex::address_to_hex_string;
+
+use super::api_types::{
+    LevelDataEntry, OkxPricingData, OkxPricingQueryParams, OkxPricingResponse, OkxQuoteData,
+    OkxQuoteRequest, OkxQuoteResponse, OkxSettlementTx,
+};
+use crate::{
+    error::AuthServerError,
+    server::{
+        api_handlers::connectors::rfqt::helpers::validate_chain_id,
+        chains::{chain_to_chain_id, usdc_on_chain},
+    },
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The response code indicating success
+const SUCCESS_CODE: &str = "0";
+/// The response message indicating success
+const SUCCESS_MSG: &str = "Success";
 
 /// Parse a chain ID from a query string
 pub fn parse_chain_id(query_str: &str) -> Result<u64, AuthServerError> {
@@ -16,3 +47,186 @@ pub fn parse_chain_id(query_str: &str) -> Result<u64, AuthServerError> {
         .chain_index
         .ok_or_else(|| AuthServerError::bad_request("Missing chainIndex query parameter"))
 }
+
+/// Parse and validate the chain ID of a pricing request against the server's
+/// chain
+pub fn parse_pricing_chain_id(query_str: &str, chain: Chain) -> Result<(), AuthServerError> {
+    let chain_id = parse_chain_id(query_str)?;
+    validate_chain_id(chain_id, chain)
+}
+
+/// Transform v2 market depths into an OKX pricing response
+///
+/// Level amounts are expressed in decimal units of the taker token, and rates
+/// in units of maker token per unit of taker token.
+pub fn transform_depth_to_pricing(
+    chain: Chain,
+    depth_response: GetMarketDepthsResponse,
+) -> OkxPricingResponse {
+    let usdc = usdc_on_chain(chain).get_addr();
+    let mut entries = Vec::new();
+
+    for market_depth in depth_response.market_depths {
+        let base_addr = address_to_hex_string(&market_depth.market.base.address);
+        let base_token = Token::from_addr_on_chain(&base_addr, chain);
+        let price = market_depth.market.price.price;
+
+        // Buy side
+        // Maker buys the base token with the quote token, so the taker token is the
+        // base token
+        if market_depth.buy.total_quantity > 0 {
+            let amount = base_token.convert_to_decimal(market_depth.buy.total_quantity);
+            entries.push(LevelDataEntry {
+                taker_token_address: base_addr.clone(),
+                maker_token_address: usdc.clone(),
+                levels: vec![(amount.to_string(), price.to_string())],
+            });
+        }
+
+        // Sell side
+        // Maker sells the base token for the quote token, so the taker token is the
+        // quote token. Renegade prices are in quote / base, so the rate is inverted
+        // and the base depth is converted into quote units
+        if market_depth.sell.total_quantity > 0 && price > 0. {
+            let base_amount = base_token.convert_to_decimal(market_depth.sell.total_quantity);
+            let quote_amount = base_amount * price;
+            entries.push(LevelDataEntry {
+                taker_token_address: usdc.clone(),
+                maker_token_address: base_addr,
+                levels: vec![(quote_amount.to_string(), (1.0 / price).to_string())],
+            });
+        }
+    }
+
+    OkxPricingResponse {
+        code: SUCCESS_CODE.to_string(),
+        msg: SUCCESS_MSG.to_string(),
+        data: OkxPricingData {
+            chain_index: chain_to_chain_id(chain).to_string(),
+            level_data: entries,
+        },
+    }
+}
+
+/// Create a direct-order assemble request from an OKX firm quote request
+///
+/// The quote is all-or-nothing: the full taker amount must be filled. The
+/// receiver is set to the taker so the settlement calldata embeds the
+/// correct counterparty.
+pub fn create_okx_assemble_request(
+    req: &OkxQuoteRequest,
+    chain: Chain,
+) -> Result<AssembleExternalMatchRequest, AuthServerError> {
+    validate_chain_id(req.chain_index, chain)?;
+    let input_amount = req
+        .taker_token_amount
+        .parse()
+        .map_err(|_| AuthServerError::bad_request("Invalid takerTokenAmount"))?;
+    if input_amount == 0 {
+        return Err(AuthServerError::bad_request("takerTokenAmount must be non-zero"));
+    }
+
+    let external_order = ExternalOrder {
+        input_mint: req.taker_token_address,
+        output_mint: req.maker_token_address,
+        input_amount,
+        output_amount: 0,
+        use_exact_output_amount: false,
+        min_fill_size: input_amount,
+    };
+
+    Ok(AssembleExternalMatchRequest {
+        do_gas_estimation: false,
+        receiver_address: Some(req.taker_address),
+        order: ExternalMatchAssemblyType::DirectOrder { external_order },
+        options: ExternalMatchingEngineOptions::default(),
+    })
+}
+
+/// Transform a v2 bounded match bundle into an OKX firm quote response
+pub fn transform_match_bundle_to_okx_quote(
+    bundle: &BoundedExternalMatchApiBundle,
+    chain: Chain,
+) -> Result<OkxQuoteResponse, AuthServerError> {
+    let to = match &bundle.settlement_tx.to {
+        Some(TxKind::Call(addr)) => format!("{addr:#x}"),
+        _ => {
+            return Err(AuthServerError::serde("Missing settlement contract address"));
+        },
+    };
+    let calldata = bundle
+        .settlement_tx
+        .input
+        .input()
+        .cloned()
+        .ok_or_else(|| AuthServerError::serde("Missing settlement transaction input"))?;
+    let value = bundle.settlement_tx.value.unwrap_or(U256::ZERO);
+
+    // v2 orients amounts from the external party's (taker's) perspective
+    Ok(OkxQuoteResponse {
+        code: SUCCESS_CODE.to_string(),
+        msg: SUCCESS_MSG.to_string(),
+        data: OkxQuoteData {
+            chain_index: chain_to_chain_id(chain).to_string(),
+            taker_token_address: address_to_hex_string(&bundle.match_result.input_mint),
+            taker_token_amount: bundle.max_send.amount.to_string(),
+            maker_token_address: address_to_hex_string(&bundle.match_result.output_mint),
+            maker_token_amount: bundle.max_receive.amount.to_string(),
+            settlement: OkxSettlementTx { to, calldata, value: value.to_string() },
+            deadline: bundle.deadline.to_string(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// WETH address used across fixtures (real Arbitrum One WETH)
+    const WETH_ADDR: &str = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1";
+    /// USDC address used across fixtures (real Arbitrum One USDC)
+    const USDC_ADDR: &str = "0xaf88d065e77c8cc2239327c5edb3a432268e5831";
+    /// Generic taker address
+    const TAKER_ADDR: &str = "0x1111111111111111111111111111111111111111";
+
+    /// Build a firm quote request selling the given amount of USDC for WETH
+    fn quote_request(chain_index: u64, amount: &str) -> OkxQuoteRequest {
+        OkxQuoteRequest {
+            chain_index,
+            taker_token_address: USDC_ADDR.parse().unwrap(),
+            maker_token_address: WETH_ADDR.parse().unwrap(),
+            taker_token_amount: amount.to_string(),
+            taker_address: TAKER_ADDR.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn parse_chain_id_requires_chain_index() {
+        assert_eq!(parse_chain_id("chainIndex=42161").unwrap(), 42161);
+        assert!(parse_chain_id("").is_err());
+        assert!(parse_chain_id("other=1").is_err());
+        assert!(parse_pricing_chain_id("chainIndex=1", Chain::ArbitrumOne).is_err());
+    }
+
+    #[test]
+    fn assemble_request_is_all_or_nothing_direct_order() {
+        let req = create_okx_assemble_request(&quote_request(42161, "1000000"), Chain::ArbitrumOne)
+            .unwrap();
+        assert_eq!(req.receiver_address, Some(TAKER_ADDR.parse().unwrap()));
+
+        let ExternalMatchAssemblyType::DirectOrder { external_order } = req.order else {
+            panic!("expected a direct order");
+        };
+        assert_eq!(external_order.input_amount, 1_000_000);
+        assert_eq!(external_order.min_fill_size, 1_000_000);
+        assert!(!external_order.use_exact_output_amount);
+    }
+
+    #[test]
+    fn assemble_request_rejects_invalid_requests() {
+        let chain = Chain::ArbitrumOne;
+        assert!(create_okx_assemble_request(&quote_request(1, "1000000"), chain).is_err());
+        assert!(create_okx_assemble_request(&quote_request(42161, "0"), chain).is_err());
+        assert!(create_okx_assemble_request(&quote_request(42161, "1.5"), chain).is_err());
+    }
+}
@@ -5,3 +5,4 @@
 pub mod api_types;
 mod helpers;
 pub mod pricing;
+pub mod quote;
@@ -0,0 +1,126 @@
+//! Handlers for the firm quote endpoint
+
+use auth_server_api::key_management::ApiKeyScope;
+use bytes::Bytes;
+use http::HeaderMap;
+use renegade_external_api::http::external_match::{
+    ASSEMBLE_MATCH_BUNDLE_ROUTE, AssembleExternalMatchRequest, ExternalMatchResponse,
+};
+use tracing::instrument;
+use uuid::Uuid;
+use warp::reject::Rejection;
+
+use crate::{
+    error::AuthServerError,
+    http_utils::{
+        request_response::overwrite_response_body, stringify_formatter::json_deserialize,
+    },
+    log_task,
+    logger::{Outcome, Task},
+    server::{
+        Server,
+        api_handlers::{
+            connectors::okx_market_maker::{
+                api_types::OkxQuoteRequest,
+                helpers::{create_okx_assemble_request, transform_match_bundle_to_okx_quote},
+            },
+            external_match::{BytesResponse, RequestContext, ResponseContext},
+            get_sdk_version,
+        },
+    },
+};
+
+impl Server {
+    /// Handle the OKX firm quote endpoint (`POST /okx/v1/quote`)
+    ///
+    /// The quote is assembled as a direct order through the v2 assembly
+    /// pipeline, so it is subject to the same rate limits, routing, and gas
+    /// sponsorship as other external matches. The response carries the
+    /// settlement transaction for the taker to submit.
+    pub async fn handle_okx_quote_request(
+        &self,
+        path: warp::path::FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+        query_str: String,
+    ) -> Result<BytesResponse, Rejection> {
+        log_task!(
+            Task::OkxQuote,
+            Outcome::Started,
+            subject = "request",
+            chain = %self.chain,
+            "POST /okx/v1/quote"
+        );
+
+        let mut ctx = self.okx_quote_pre_request(path, headers, body, query_str).await?;
+        self.assembly_pre_request(&mut ctx).await?;
+        let (raw_resp, resp_ctx) = self.forward_request::<_, ExternalMatchResponse>(ctx).await?;
+        let res = self.assembly_post_request(raw_resp, resp_ctx.clone()).await?;
+        let res = self.okx_quote_post_request(res, &resp_ctx)?;
+
+        log_task!(
+            Task::OkxQuote,
+            Outcome::Ok,
+            subject = "request",
+            chain = %self.chain,
+            status = resp_ctx.status().as_u16(),
+            "POST /okx/v1/quote completed"
+        );
+        Ok(res)
+    }
+
+    /// Build the assemble request context for an OKX firm quote request
+    ///
+    /// Authorizes using the original request body (for HMAC validation), then
+    /// validates the transformed order and applies the per-key relayer fee.
+    #[instrument(skip_all)]
+    async fn okx_quote_pre_request(
+        &self,
+        path: warp::path::FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+        query_str: String,
+    ) -> Result<RequestContext<AssembleExternalMatchRequest>, AuthServerError> {
+        let path = path.as_str().to_string();
+        let (key_desc, key_id) =
+            self.authorize_request(&path, &query_str, &headers, &body, ApiKeyScope::Okx).await?;
+        let sdk_version = get_sdk_version(&headers);
+
+        let okx_request: OkxQuoteRequest = json_deserialize(&body, false /* stringify */)?;
+        let assemble_request = create_okx_assemble_request(&okx_request, self.chain)?;
+        self.validate_request_body(&assemble_request)?;
+        self.authorize_order_mints(key_id, &assemble_request).await?;
+
+        let mut ctx = RequestContext {
+            path: ASSEMBLE_MATCH_BUNDLE_ROUTE.to_string(),
+            query_str,
+            sdk_version,
+            headers,
+            user: key_desc,
+            key_id,
+            chain: self.chain,
+            body: assemble_request,
+            sponsorship_info: None,
+            request_id: Uuid::new_v4(),
+        };
+        self.set_relayer_fee(&mut ctx).await?;
+        Ok(ctx)
+    }
+
+    /// Overwrite the relayer response body with the OKX firm quote response
+    /// shape
+    fn okx_quote_post_request(
+        &self,
+        mut resp: BytesResponse,
+        ctx: &ResponseContext<AssembleExternalMatchRequest, ExternalMatchResponse>,
+    ) -> Result<BytesResponse, AuthServerError> {
+        if !ctx.is_success() {
+            return Ok(resp);
+        }
+
+        let match_bundle = ctx.response().match_bundle;
+        let okx_response = transform_match_bundle_to_okx_quote(&match_bundle, self.chain)?;
+        overwrite_response_body(&mut resp, okx_response, false /* stringify */)?;
+        Ok(resp)
+    }
+}
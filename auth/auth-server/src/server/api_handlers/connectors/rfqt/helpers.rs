@@ -8,6 +8,7 @@ use auth_server_api::rfqt::{
     Consideration, Level, OrderDetails, RfqtLevelsQueryParams, RfqtLevelsResponse,
     RfqtQuoteRequest, RfqtQuoteResponse, TokenAmount, TokenPairLevels,
 };
+use auth_server_api::rfqt_ladders::RfqtLadder;
 use renegade_circuit_types::Amount;
 use renegade_external_api::{
     http::{
@@ -22,7 +23,13 @@ use renegade_external_api::{
 use renegade_types_core::{Chain, Token};
 use renegade_util::{get_current_time_millis, hex::address_to_hex_string};
 
-use crate::{error::AuthServerError, server::api_handlers::external_match::RequestContext};
+use crate::{
+    error::AuthServerError,
+    server::{
+        api_handlers::external_match::RequestContext,
+        chains::{chain_to_chain_id, usdc_on_chain},
+    },
+};
 
 // -------------
 // | Constants |
@@ -40,6 +47,9 @@ const DEADLINE_OFFSET_SECONDS: u64 = 70;
 /// empty string.
 const SIGNATURE: &str = "0x0";
 
+/// The number of basis points in one
+pub(crate) const BPS_PER_ONE: f64 = 10_000.;
+
 /// Check if the query string indicates malleable calldata should be used
 /// Returns true by default (malleable calldata enabled), false only when
 /// explicitly disabled
@@ -70,7 +80,10 @@ pub fn parse_levels_query_params(
 }
 
 /// Validate that the provided chain ID matches the server's configured chain
-fn validate_chain_id(provided_chain_id: u64, server_chain: Chain) -> Result<(), AuthServerError> {
+pub(crate) fn validate_chain_id(
+    provided_chain_id: u64,
+    server_chain: Chain,
+) -> Result<(), AuthServerError> {
     let server_chain_id = chain_to_chain_id(server_chain);
     if provided_chain_id != server_chain_id {
         return Err(AuthServerError::bad_request(format!(
@@ -80,19 +93,6 @@ fn validate_chain_id(provided_chain_id: u64, server_chain: Chain) -> Result<(),
     Ok(())
 }
 
-/// Convert a Chain enum to its numeric chain ID
-fn chain_to_chain_id(chain: Chain) -> u64 {
-    match chain {
-        Chain::ArbitrumOne => 42161,
-        Chain::ArbitrumSepolia => 421614,
-        Chain::BaseMainnet => 8453,
-        Chain::BaseSepolia => 84532,
-        Chain::EthereumMainnet => 1,
-        Chain::EthereumSepolia => 11155111,
-        Chain::Devnet => 0,
-    }
-}
-
 /// Parse the upstream `/v2/markets/depth` response, or classify the failure.
 ///
 /// Non-2xx responses and malformed JSON are surfaced as `Custom` (→ HTTP 500)
@@ -115,31 +115,39 @@ pub fn parse_market_depths_response(
 /// Transform v2 market-depths data into the RFQT levels response shape.
 ///
 /// v2 `GetMarketDepthsResponse` carries a `MarketInfo` per pair, which already
-/// includes a timestamped price, so no per-mint price fan-out is needed.
-pub fn transform_depth_to_levels(depth_response: GetMarketDepthsResponse) -> RfqtLevelsResponse {
+/// includes a timestamped price, so no per-mint price fan-out is needed. Each
+/// side's depth is split into levels according to the given ladder.
+pub fn transform_depth_to_levels(
+    chain: Chain,
+    depth_response: GetMarketDepthsResponse,
+    ladder: &RfqtLadder,
+) -> RfqtLevelsResponse {
     let mut pairs = HashMap::new();
-    let usdc_addr = Token::usdc().get_addr();
+    let usdc_addr = usdc_on_chain(chain).get_addr();
 
     for market_depth in depth_response.market_depths {
         let base_addr = address_to_hex_string(&market_depth.market.base.address);
         let pair_key = format!("{base_addr}/{usdc_addr}");
-        let base_token = Token::from_addr(&base_addr);
+        let base_token = Token::from_addr_on_chain(&base_addr, chain);
         let price = market_depth.market.price.price;
 
-        let mut bids = Vec::new();
-        let mut asks = Vec::new();
-
         // Buy depth -> bids (taker buying base)
-        if market_depth.buy.total_quantity > 0 {
-            let amount_decimal = base_token.convert_to_decimal(market_depth.buy.total_quantity);
-            bids.push(Level { price: price.to_string(), amount: amount_decimal.to_string() });
-        }
+        let bids = ladder_levels(
+            price,
+            market_depth.buy.total_quantity,
+            &base_token,
+            ladder,
+            false, // is_ask
+        );
 
         // Sell depth -> asks (taker selling base)
-        if market_depth.sell.total_quantity > 0 {
-            let amount_decimal = base_token.convert_to_decimal(market_depth.sell.total_quantity);
-            asks.push(Level { price: price.to_string(), amount: amount_decimal.to_string() });
-        }
+        let asks = ladder_levels(
+            price,
+            market_depth.sell.total_quantity,
+            &base_token,
+            ladder,
+            true, // is_ask
+        );
 
         pairs.insert(pair_key, TokenPairLevels { bids, asks });
     }
@@ -147,6 +155,50 @@ pub fn transform_depth_to_levels(depth_response: GetMarketDepthsResponse) -> Rfq
     RfqtLevelsResponse { pairs }
 }
 
+/// Split one side's depth into the ladder's levels
+///
+/// Each level holds the depth between the previous size fraction and its own,
+/// quoted away from the midpoint by the ladder's widening at its own size
+/// fraction. Bids are widened downwards and asks upwards, after which both are
+/// shifted by the ladder's skew
+///
+/// These prices are indicative only; a firm quote is matched by the relayer at
+/// the midpoint less the key's fee, regardless of the level it falls in. The
+/// skew is validated not to exceed the base spread, so no level is quoted
+/// better than the midpoint
+fn ladder_levels(
+    price: f64,
+    total_quantity: Amount,
+    base_token: &Token,
+    ladder: &RfqtLadder,
+    is_ask: bool,
+) -> Vec<Level> {
+    let mut levels = Vec::with_capacity(ladder.size_fractions.len());
+    let mut prev_cumulative: Amount = 0;
+    for &fraction in ladder.size_fractions.iter() {
+        let cumulative = if fraction >= 1. {
+            total_quantity
+        } else {
+            ((total_quantity as f64 * fraction) as Amount).min(total_quantity)
+        };
+
+        let amount = cumulative.saturating_sub(prev_cumulative);
+        prev_cumulative = prev_cumulative.max(cumulative);
+        if amount == 0 {
+            continue;
+        }
+
+        let side_sign = if is_ask { 1. } else { -1. };
+        let offset_bps = side_sign * ladder.widening_bps(fraction) + ladder.skew_bps;
+        let level_price = price * (1. + offset_bps / BPS_PER_ONE);
+
+        let amount_decimal = base_token.convert_to_decimal(amount);
+        levels.push(Level { price: level_price.to_string(), amount: amount_decimal.to_string() });
+    }
+
+    levels
+}
+
 /// Create an external quote request from an RFQT quote request
 pub fn create_quote_request(
     req: &RfqtQuoteRequest,
@@ -183,6 +235,7 @@ pub fn transform_quote_to_assemble_malleable_ctx(
         body: assemble_request,
         request_id: req_ctx.request_id,
         key_id: req_ctx.key_id,
+        chain: req_ctx.chain,
         sponsorship_info: None,
     })
 }
@@ -491,17 +544,6 @@ mod tests {
         assert!(!should_use_malleable_calldata("other=1&malleableCalldata=false"));
     }
 
-    #[test]
-    fn chain_to_chain_id_covers_all_variants() {
-        assert_eq!(chain_to_chain_id(Chain::ArbitrumOne), 42161);
-        assert_eq!(chain_to_chain_id(Chain::ArbitrumSepolia), 421614);
-        assert_eq!(chain_to_chain_id(Chain::BaseMainnet), 8453);
-        assert_eq!(chain_to_chain_id(Chain::BaseSepolia), 84532);
-        assert_eq!(chain_to_chain_id(Chain::EthereumMainnet), 1);
-        assert_eq!(chain_to_chain_id(Chain::EthereumSepolia), 11155111);
-        assert_eq!(chain_to_chain_id(Chain::Devnet), 0);
-    }
-
     #[test]
     fn parse_levels_query_params_empty_returns_default() {
         let p = parse_levels_query_params("", Chain::ArbitrumOne).unwrap();
@@ -676,6 +718,7 @@ mod tests {
             query_str: "q".to_string(),
             user: "u".to_string(),
             key_id: uuid::Uuid::nil(),
+            chain: Chain::ArbitrumOne,
             sdk_version: "sdk".to_string(),
             headers: http::HeaderMap::new(),
             body: create_quote_request(&mock_rfqt_buy_request()).unwrap(),
@@ -767,7 +810,11 @@ mod tests {
     #[test]
     fn empty_depth_response_produces_empty_levels() {
         setup_token_remap();
-        let resp = transform_depth_to_levels(GetMarketDepthsResponse { market_depths: vec![] });
+        let resp = transform_depth_to_levels(
+            Chain::ArbitrumOne,
+            GetMarketDepthsResponse { market_depths: vec![] },
+            &RfqtLadder::default(),
+        );
         assert!(resp.pairs.is_empty());
     }
 
@@ -798,8 +845,11 @@ mod tests {
             sell: DepthSide { total_quantity: 500_000_000_000_000, total_quantity_usd: 1.0 },
         };
 
-        let resp =
-            transform_depth_to_levels(GetMarketDepthsResponse { market_depths: vec![depth] });
+        let resp = transform_depth_to_levels(
+            Chain::ArbitrumOne,
+            GetMarketDepthsResponse { market_depths: vec![depth] },
+            &RfqtLadder::default(),
+        );
 
         let expected_key = format!("{WETH_ADDR}/{USDC_ADDR}");
         let levels = resp.pairs.get(&expected_key).expect("pair key present");
@@ -836,13 +886,84 @@ mod tests {
             sell: DepthSide { total_quantity: 500_000_000_000_000, total_quantity_usd: 1.0 },
         };
 
-        let resp =
-            transform_depth_to_levels(GetMarketDepthsResponse { market_depths: vec![depth] });
+        let resp = transform_depth_to_levels(
+            Chain::ArbitrumOne,
+            GetMarketDepthsResponse { market_depths: vec![depth] },
+            &RfqtLadder::default(),
+        );
         let levels = resp.pairs.values().next().unwrap();
         assert!(levels.bids.is_empty());
         assert_eq!(levels.asks.len(), 1);
     }
 
+    #[test]
+    fn ladder_splits_depth_into_widening_levels() {
+        setup_token_remap();
+
+        use renegade_external_api::types::market::{DepthSide, MarketDepth, MarketInfo};
+        use renegade_external_api::types::{ApiTimestampedPrice, ApiToken};
+
+        let market = MarketInfo {
+            base: ApiToken { address: addr(WETH_ADDR), symbol: "WETH".to_string() },
+            quote: ApiToken { address: addr(USDC_ADDR), symbol: USDC_TICKER.to_string() },
+            price: ApiTimestampedPrice { price: 2000.0, timestamp: 0 },
+            internal_match_fee_rates: FeeTakeRate {
+                relayer_fee_rate: FixedPoint::zero(),
+                protocol_fee_rate: FixedPoint::zero(),
+            },
+            external_match_fee_rates: FeeTakeRate {
+                relayer_fee_rate: FixedPoint::zero(),
+                protocol_fee_rate: FixedPoint::zero(),
+            },
+        };
+        // 1 WETH on each side.
+        let depth = MarketDepth {
+            market,
+            buy: DepthSide {
+                total_quantity: 1_000_000_000_000_000_000,
+                total_quantity_usd: 2000.0,
+            },
+            sell: DepthSide {
+                total_quantity: 1_000_000_000_000_000_000,
+                total_quantity_usd: 2000.0,
+            },
+        };
+
+        // 25% of the depth at 20bps, the remainder at 50bps, shifted up by 5bps
+        let ladder = RfqtLadder {
+            size_fractions: vec![0.25, 1.],
+            base_spread_bps: 10.,
+            impact_bps: 40.,
+            skew_bps: 5.,
+            ..Default::default()
+        };
+        let resp = transform_depth_to_levels(
+            Chain::ArbitrumOne,
+            GetMarketDepthsResponse { market_depths: vec![depth] },
+            &ladder,
+        );
+        let levels = resp.pairs.values().next().unwrap();
+
+        let parse = |levels: &[Level]| -> Vec<(f64, f64)> {
+            levels.iter().map(|l| (l.price.parse().unwrap(), l.amount.parse().unwrap())).collect()
+        };
+        let assert_close = |a: f64, b: f64| assert!((a - b).abs() < 1e-9, "{a} != {b}");
+
+        let bids = parse(&levels.bids);
+        assert_eq!(bids.len(), 2);
+        assert_close(bids[0].0, 1997.);
+        assert_close(bids[0].1, 0.25);
+        assert_close(bids[1].0, 1991.);
+        assert_close(bids[1].1, 0.75);
+
+        let asks = parse(&levels.asks);
+        assert_eq!(asks.len(), 2);
+        assert_close(asks[0].0, 2005.);
+        assert_close(asks[0].1, 0.25);
+        assert_close(asks[1].0, 2011.);
+        assert_close(asks[1].1, 0.75);
+    }
+
     #[test]
     fn parse_market_depths_response_upstream_non_success_maps_to_500() {
         use crate::ApiError;
@@ -1,5 +1,7 @@
 //! RFQT Levels endpoint handler
 
+use auth_server_api::key_management::ApiKeyScope;
+use auth_server_api::rfqt_ladders::RfqtLadder;
 use bytes::Bytes;
 use http::{HeaderMap, Method};
 use renegade_external_api::http::market::GET_MARKETS_DEPTH_ROUTE;
@@ -32,8 +34,15 @@ impl Server {
 
         // Authorize request (path + query)
         let path_str = path.as_str();
-        let (_key_desc, _key_id) =
-            self.authorize_request(path_str, &query_str, &headers, &[] /* body */).await?;
+        let (_key_desc, key_id) = self
+            .authorize_request(
+                path_str,
+                &query_str,
+                &headers,
+                &[], // body
+                ApiKeyScope::Rfqt,
+            )
+            .await?;
 
         // Parse query params with validation
         let _params = parse_levels_query_params(&query_str, self.chain)?;
@@ -63,7 +72,15 @@ impl Server {
                 );
                 err
             })?;
-        let body = transform_depth_to_levels(depth_response);
+
+        // Split the depth into the ladder configured for the key, falling back to
+        // a single level at the midpoint
+        let ladder = self
+            .get_applicable_rfqt_ladder(key_id)
+            .await?
+            .map(RfqtLadder::from)
+            .unwrap_or_default();
+        let body = transform_depth_to_levels(self.chain, depth_response, &ladder);
 
         log_task!(
             Task::RfqtLevels,
@@ -71,6 +88,7 @@ impl Server {
             subject = "request",
             chain = %self.chain,
             pairs = body.pairs.len(),
+            ladder_levels = ladder.size_fractions.len(),
             "GET /rfqt/v3/levels returned {} pairs",
             body.pairs.len()
         );
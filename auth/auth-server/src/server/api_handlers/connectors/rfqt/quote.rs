@@ -11,7 +11,9 @@ use renegade_external_api::http::external_match::{
     ExternalQuoteRequest, ExternalQuoteResponse, GET_EXTERNAL_MATCH_QUOTE_ROUTE,
 };
 
-use auth_server_api::{SponsoredQuoteResponse, rfqt::RfqtQuoteRequest};
+use auth_server_api::{
+    SponsoredQuoteResponse, key_management::ApiKeyScope, rfqt::RfqtQuoteRequest,
+};
 
 use crate::error::AuthServerError;
 use crate::http_utils::request_response::overwrite_response_body;
@@ -29,6 +31,10 @@ use crate::server::api_handlers::get_sdk_version;
 
 impl Server {
     /// Handle the RFQT Quote endpoint (`POST /rfqt/v3/quote`).
+    ///
+    /// The key's RFQT ladder is not applied here; the ladder only shapes the
+    /// levels advertised on `GET /rfqt/v3/levels`, and the firm quote is the
+    /// relayer's match at the midpoint less the key's fee.
     pub async fn handle_rfqt_quote_request(
         &self,
         path: warp::path::FullPath,
@@ -99,7 +105,8 @@ impl Server {
         query_str: String,
     ) -> Result<(RequestContextVariant, RfqtQuoteRequest), AuthServerError> {
         let path = path.as_str().to_string();
-        let (key_desc, key_id) = self.authorize_request(&path, &query_str, &headers, &body).await?;
+        let (key_desc, key_id) =
+            self.authorize_request(&path, &query_str, &headers, &body, ApiKeyScope::Rfqt).await?;
         let sdk_version = get_sdk_version(&headers);
 
         let rfq_request: RfqtQuoteRequest = json_deserialize(&body, false /* stringify */)?;
@@ -107,6 +114,7 @@ impl Server {
         let ctx = if should_use_malleable_calldata(&query_str) {
             let external_quote_request = create_quote_request(&rfq_request)?;
             self.validate_request_body(&external_quote_request)?;
+            self.authorize_order_mints(key_id, &external_quote_request).await?;
 
             let mut ctx = RequestContext {
                 path: GET_EXTERNAL_MATCH_QUOTE_ROUTE.to_string(),
@@ -115,6 +123,7 @@ impl Server {
                 headers: headers.clone(),
                 user: key_desc.clone(),
                 key_id,
+                chain: self.chain,
                 body: external_quote_request,
                 sponsorship_info: None,
                 request_id: Uuid::new_v4(),
@@ -124,6 +133,7 @@ impl Server {
         } else {
             let assemble_request = create_direct_match_request(&rfq_request)?;
             self.validate_request_body(&assemble_request)?;
+            self.authorize_order_mints(key_id, &assemble_request).await?;
 
             let mut ctx = RequestContext {
                 path: ASSEMBLE_MATCH_BUNDLE_ROUTE.to_string(),
@@ -132,6 +142,7 @@ impl Server {
                 headers: headers.clone(),
                 user: key_desc.clone(),
                 key_id,
+                chain: self.chain,
                 body: assemble_request,
                 sponsorship_info: None,
                 request_id: Uuid::new_v4(),
@@ -187,7 +198,7 @@ impl Server {
         let (assemble_raw_resp, assemble_resp_ctx) =
             self.forward_request::<_, ExternalMatchResponse>(assemble_req_ctx).await?;
         let assemble_res =
-            self.assembly_post_request(assemble_raw_resp, assemble_resp_ctx.clone())?;
+            self.assembly_post_request(assemble_raw_resp, assemble_resp_ctx.clone()).await?;
 
         // 7. Overwrite the response body with the RFQT-shaped response.
         let assemble_res =
@@ -204,7 +215,7 @@ impl Server {
     ) -> Result<BytesResponse, Rejection> {
         self.assembly_pre_request(&mut ctx).await?;
         let (raw_resp, resp_ctx) = self.forward_request::<_, ExternalMatchResponse>(ctx).await?;
-        let res = self.assembly_post_request(raw_resp, resp_ctx.clone())?;
+        let res = self.assembly_post_request(raw_resp, resp_ctx.clone()).await?;
         let res = self.rfqt_post_request_direct(&req, res, &resp_ctx)?;
         Ok(res)
     }
@@ -1,5 +1,6 @@
 //! Exchange metadata endpoint handler
 
+use auth_server_api::key_management::ApiKeyScope;
 use bytes::Bytes;
 use http::{HeaderMap, Method};
 use renegade_external_api::{
@@ -22,7 +23,14 @@ impl Server {
     ) -> Result<Json, Rejection> {
         // Authorize the request
         let path_str = path.as_str();
-        self.authorize_request(path_str, "" /* query_str */, &headers, &[] /* body */).await?;
+        self.authorize_request(
+            path_str,
+            "", // query_str
+            &headers,
+            &[], // body
+            ApiKeyScope::Markets,
+        )
+        .await?;
 
         // Proxy the request to the relayer
         let resp = self
@@ -1,13 +1,13 @@
 //! Match bundle assembly endpoint handler
 
-use auth_server_api::{GasSponsorshipInfo, SponsoredMatchResponse};
+use auth_server_api::{GasSponsorshipInfo, SponsoredMatchResponse, key_management::ApiKeyScope};
 use bytes::Bytes;
 use renegade_constants::GLOBAL_MATCHING_POOL;
 use renegade_external_api::http::external_match::{
     AssembleExternalMatchRequest, ExternalMatchAssemblyType, ExternalMatchResponse,
 };
 use renegade_external_api::types::{ApiSignedQuote, ExternalOrder};
-use renegade_types_core::Token;
+use renegade_types_core::{Chain, Token};
 use renegade_util::get_current_time_millis;
 use tracing::instrument;
 use warp::reject::Rejection;
@@ -17,15 +17,19 @@ use crate::http_utils::request_response::overwrite_response_body;
 use crate::log_task;
 use crate::logger::{Outcome, Task};
 use crate::server::api_handlers::external_match::BytesResponse;
+use crate::server::chains::token_on_chain;
+use crate::server::db::models::ApiKeyUsage;
 use crate::server::gas_sponsorship::refund_calculation::{
     apply_gas_sponsorship_to_exact_output_amount, remove_gas_sponsorship_from_quote,
     requires_exact_output_amount_update,
 };
-use crate::server::helpers::generate_quote_uuid;
+use crate::server::helpers::{generate_quote_uuid, pick_base_and_quote_mints};
+use crate::server::rate_limiter::VolumeConsumption;
 use crate::server::{
     Server,
     api_handlers::external_match::{ExternalMatchRequestType, RequestContext, ResponseContext},
 };
+use crate::telemetry::helpers::get_default_quote_amount;
 
 // -----------------
 // | Context Types |
@@ -35,19 +39,23 @@ use crate::server::{
 type AssembleMatchRequestCtx = RequestContext<AssembleExternalMatchRequest>;
 
 impl ExternalMatchRequestType for AssembleExternalMatchRequest {
-    fn input_token(&self) -> Token {
+    fn input_token(&self, chain: Chain) -> Token {
         let input_mint = &self.order.get_external_order_ref().input_mint;
-        Token::from_alloy_address(input_mint)
+        token_on_chain(input_mint, chain)
     }
 
-    fn output_token(&self) -> Token {
+    fn output_token(&self, chain: Chain) -> Token {
         let output_mint = &self.order.get_external_order_ref().output_mint;
-        Token::from_alloy_address(output_mint)
+        token_on_chain(output_mint, chain)
     }
 
     fn set_fee(&mut self, fee: f64) {
         self.options.relayer_fee_rate = Some(fee);
     }
+
+    fn scope() -> ApiKeyScope {
+        ApiKeyScope::Assemble
+    }
 }
 
 /// The response context for an external match response
@@ -68,6 +76,8 @@ impl SponsoredExternalMatchResponseCtx {
             path: ctx.path,
             query_str: ctx.query_str,
             user: ctx.user,
+            key_id: ctx.key_id,
+            chain: ctx.chain,
             sdk_version: ctx.sdk_version,
             headers: ctx.headers,
             request: ctx.request,
@@ -101,7 +111,7 @@ impl Server {
         let (raw_resp, ctx) = self.forward_request(ctx).await?;
 
         // 3. Run the post-request subroutines
-        let res = self.assembly_post_request(raw_resp, ctx)?;
+        let res = self.assembly_post_request(raw_resp, ctx).await?;
         Ok(res)
     }
 
@@ -120,6 +130,15 @@ impl Server {
         if self.consume_bundle_rate_limit_token(key, user).await.is_err() {
             return Err(AuthServerError::no_match_found());
         };
+
+        // Check the order's volume, returning the bundle token if it would
+        // exceed a volume limit. The volume is consumed once the bundle is
+        // assembled, so a failed assembly consumes nothing
+        let order = ctx.body.order.get_external_order_ref();
+        if self.check_volume_rate_limit(key, user, order).await.is_err() {
+            self.rate_limiter.add_bundle_token(user).await?;
+            return Err(AuthServerError::no_match_found());
+        }
         self.route_assembly_req(ctx).await?;
 
         // Apply gas sponsorship to the assembly request
@@ -130,7 +149,7 @@ impl Server {
 
     /// Run the post-request subroutines for the assembly endpoint
     #[instrument(skip_all, fields(success = ctx.is_success(), status = ctx.status().as_u16()))]
-    pub(crate) fn assembly_post_request(
+    pub(crate) async fn assembly_post_request(
         &self,
         mut resp: BytesResponse,
         ctx: ExternalMatchResponseCtx,
@@ -145,15 +164,55 @@ impl Server {
         let should_stringify = ctx.should_stringify_body();
         overwrite_response_body(&mut resp, sponsored_resp.clone(), should_stringify)?;
 
+        // Consume the matched volume against the key's volume limits. The bundle
+        // has already been assembled, so a failure is logged rather than
+        // returned
+        let volume_consumption = match self.consume_bundle_volume(&ctx).await {
+            Ok(consumption) => consumption,
+            Err(e) => {
+                log_task!(
+                    Task::RateLimit,
+                    Outcome::Failed,
+                    subject = "volume-consume",
+                    key_description = %ctx.user(),
+                    error = %e,
+                    "error consuming matched volume"
+                );
+                None
+            },
+        };
+
         // Record metrics
         let ctx = SponsoredExternalMatchResponseCtx::from_external_match_response_ctx(
             sponsored_resp,
             ctx,
         );
-        self.record_assembly_metrics(ctx);
+        self.record_assembly_metrics(ctx, volume_consumption);
         Ok(resp)
     }
 
+    /// Consume the matched volume of an assembled bundle against the key's
+    /// volume limits
+    ///
+    /// The matched base amount is valued at the bundle's price, i.e. as the
+    /// bundle's quote amount
+    async fn consume_bundle_volume(
+        &self,
+        ctx: &ExternalMatchResponseCtx,
+    ) -> Result<Option<VolumeConsumption>, AuthServerError> {
+        let match_bundle = ctx.response().match_bundle;
+        let match_result = &match_bundle.match_result;
+        let (base_mint, quote_mint) = pick_base_and_quote_mints(
+            match_result.input_mint,
+            match_result.output_mint,
+            self.chain,
+        )?;
+
+        let quote_amount = get_default_quote_amount(&match_bundle, self.chain)?;
+        let volume_usd = token_on_chain(&quote_mint, self.chain).convert_to_decimal(quote_amount);
+        self.consume_matched_volume(ctx.key_id, base_mint, volume_usd).await
+    }
+
     /// Route the assembly request to the correct matching pool
     ///
     /// If execution costs limits have been exceeded by the quoters, we route
@@ -162,7 +221,7 @@ impl Server {
         &self,
         ctx: &mut AssembleMatchRequestCtx,
     ) -> Result<(), AuthServerError> {
-        let ticker = ctx.body.base_ticker()?;
+        let ticker = ctx.body.base_ticker(self.chain)?;
         let should_route_to_global = self.should_route_to_global(ctx.key_id(), &ticker).await?;
         if should_route_to_global {
             log_task!(
@@ -299,10 +358,16 @@ impl Server {
     // -----------
 
     /// Record metrics for the assembly endpoint
-    fn record_assembly_metrics(&self, ctx: SponsoredExternalMatchResponseCtx) {
+    fn record_assembly_metrics(
+        &self,
+        ctx: SponsoredExternalMatchResponseCtx,
+        volume_consumption: Option<VolumeConsumption>,
+    ) {
         let server_clone = self.clone();
         tokio::spawn(async move {
-            if let Err(e) = server_clone.record_assembly_metrics_helper(&ctx) {
+            if let Err(e) =
+                server_clone.record_assembly_metrics_helper(&ctx, volume_consumption).await
+            {
                 log_task!(
                     Task::Telemetry,
                     Outcome::Partial,
@@ -311,34 +376,73 @@ impl Server {
                     "error handling assemble metrics"
                 );
             }
+
+            // Record the assembly in the audit log
+            if let Err(e) = server_clone.record_assembly_audit_event(&ctx).await {
+                log_task!(
+                    Task::AuditLog,
+                    Outcome::Failed,
+                    subject = "assemble",
+                    error = %e,
+                    "error recording assembly in audit log"
+                );
+            }
+
+            // Count the bundle against the key's usage
+            server_clone.usage_recorder.record(ApiKeyUsage::bundle(ctx.key_id, server_clone.chain));
+
+            // Record the assembly on the quote's lifecycle
+            if let Err(e) = server_clone.record_assembly_lifecycle(&ctx).await {
+                log_task!(
+                    Task::QuoteLifecycle,
+                    Outcome::Failed,
+                    subject = "assemble",
+                    error = %e,
+                    "error recording assembly lifecycle"
+                );
+            }
         });
     }
 
     /// A helper function to record metrics for the assembly endpoint
-    fn record_assembly_metrics_helper(
+    async fn record_assembly_metrics_helper(
         &self,
         ctx: &SponsoredExternalMatchResponseCtx,
+        volume_consumption: Option<VolumeConsumption>,
     ) -> Result<(), AuthServerError> {
         match &ctx.request().order {
             ExternalMatchAssemblyType::QuotedOrder { signed_quote, updated_order } => {
-                self.record_quoted_order_metrics(ctx, signed_quote, updated_order)
+                self.record_quoted_order_metrics(
+                    ctx,
+                    signed_quote,
+                    updated_order,
+                    volume_consumption,
+                )
+                .await
             },
             ExternalMatchAssemblyType::DirectOrder { external_order } => {
-                self.record_direct_order_metrics(ctx, external_order)
+                self.record_direct_order_metrics(ctx, external_order, volume_consumption).await
             },
         }
     }
 
     /// Record metrics for a quoted order assembly
-    fn record_quoted_order_metrics(
+    async fn record_quoted_order_metrics(
         &self,
         ctx: &SponsoredExternalMatchResponseCtx,
         signed_quote: &ApiSignedQuote,
         updated_order: &Option<ExternalOrder>,
+        volume_consumption: Option<VolumeConsumption>,
     ) -> Result<(), AuthServerError> {
         let price_timestamp = signed_quote.quote.price.timestamp;
         let assembled_timestamp = get_current_time_millis();
-        self.write_bundle_context(price_timestamp, Some(assembled_timestamp), ctx)?;
+        self.write_bundle_context(
+            price_timestamp,
+            Some(assembled_timestamp),
+            ctx,
+            volume_consumption,
+        )
+        .await?;
 
         let order = if let Some(updated_order) = updated_order {
             log_updated_order(ctx, signed_quote, updated_order);
@@ -351,13 +455,14 @@ impl Server {
     }
 
     /// Record metrics for a direct order assembly
-    fn record_direct_order_metrics(
+    async fn record_direct_order_metrics(
         &self,
         ctx: &SponsoredExternalMatchResponseCtx,
         external_order: &ExternalOrder,
+        volume_consumption: Option<VolumeConsumption>,
     ) -> Result<(), AuthServerError> {
         let price_timestamp = get_current_time_millis();
-        self.write_bundle_context(price_timestamp, None, ctx)?;
+        self.write_bundle_context(price_timestamp, None, ctx, volume_consumption).await?;
         self.handle_bundle_response(external_order, ctx)
     }
 }
@@ -4,10 +4,10 @@ mod match_bundle;
 mod quote;
 
 use alloy_primitives::U256;
-use auth_server_api::GasSponsorshipInfo;
+use auth_server_api::{GasSponsorshipInfo, key_management::ApiKeyScope};
 use bytes::Bytes;
 use http::{HeaderMap, Method, Response, StatusCode};
-use renegade_types_core::Token;
+use renegade_types_core::{Chain, Token};
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 use uuid::Uuid;
@@ -17,10 +17,15 @@ use crate::{
     http_utils::{
         request_response::should_stringify_numbers, stringify_formatter::json_deserialize,
     },
-    server::{Server, helpers::pick_base_and_quote_mints},
+    server::{
+        Server,
+        chains::{token_on_chain, usdc_on_chain},
+        helpers::pick_base_and_quote_mints,
+    },
     telemetry::helpers::record_relayer_request_500,
 };
 pub use match_bundle::SponsoredExternalMatchResponseCtx;
+pub use quote::SponsoredQuoteResponseCtx;
 
 use super::{get_sdk_version, log_unsuccessful_relayer_request};
 
@@ -48,6 +53,8 @@ pub struct RequestContext<Req: Serialize + for<'de> Deserialize<'de>> {
     pub user: String,
     /// The API key id
     pub key_id: Uuid,
+    /// The chain that the request is served on
+    pub chain: Chain,
     /// The version of the SDK used to make the request
     pub sdk_version: String,
     /// The headers of the request
@@ -100,20 +107,21 @@ impl<Req: Serialize + for<'de> Deserialize<'de>> RequestContext<Req> {
 /// A trait used to define access patterns on different request types
 #[allow(unused)]
 pub trait ExternalMatchRequestType: Serialize + for<'de> Deserialize<'de> {
-    /// Get the input token for the request
-    fn input_token(&self) -> Token;
+    /// Get the input token for the request on the given chain
+    fn input_token(&self, chain: Chain) -> Token;
 
-    /// Get the output token for the request
-    fn output_token(&self) -> Token;
+    /// Get the output token for the request on the given chain
+    fn output_token(&self, chain: Chain) -> Token;
 
-    /// Get the base ticker for the request
-    fn base_ticker(&self) -> Result<String, AuthServerError> {
+    /// Get the base ticker for the request on the given chain
+    fn base_ticker(&self, chain: Chain) -> Result<String, AuthServerError> {
         let (base_mint, quote_mint) = pick_base_and_quote_mints(
-            self.input_token().get_alloy_address(),
-            self.output_token().get_alloy_address(),
+            self.input_token(chain).get_alloy_address(),
+            self.output_token(chain).get_alloy_address(),
+            chain,
         )?;
 
-        let base_token = Token::from_alloy_address(&base_mint);
+        let base_token = token_on_chain(&base_mint, chain);
         base_token.get_ticker().ok_or_else(|| {
             let base_addr = base_token.get_addr();
             AuthServerError::bad_request(format!("Invalid base token: {base_addr}"))
@@ -122,6 +130,9 @@ pub trait ExternalMatchRequestType: Serialize + for<'de> Deserialize<'de> {
 
     /// Set the fee for the request
     fn set_fee(&mut self, fee: f64);
+
+    /// The API key scope required to make the request
+    fn scope() -> ApiKeyScope;
 }
 
 // --- Response Context --- //
@@ -138,6 +149,10 @@ pub struct ResponseContext<
     pub query_str: String,
     /// Derived from the API key
     pub user: String,
+    /// The API key id
+    pub key_id: Uuid,
+    /// The chain that the request is served on
+    pub chain: Chain,
     /// The version of the SDK used to make the request
     pub sdk_version: String,
     /// The headers of the request
@@ -176,6 +191,8 @@ impl<
             path: request.path,
             query_str: request.query_str,
             user: request.user,
+            key_id: request.key_id,
+            chain: request.chain,
             sdk_version: request.sdk_version,
             headers: request.headers,
             request: request.body,
@@ -250,7 +267,7 @@ impl Server {
         Req: ExternalMatchRequestType,
     {
         let user_id = ctx.key_id();
-        let ticker = ctx.body.base_ticker()?;
+        let ticker = ctx.body.base_ticker(self.chain)?;
         let user_fee = self.get_user_fee(user_id, ticker).await?;
         ctx.body_mut().set_fee(user_fee);
 
@@ -272,13 +289,15 @@ impl Server {
     {
         // Authorize the request
         let path = path.as_str().to_string();
-        let (key_desc, key_id) = self.authorize_request(&path, &query_str, &headers, &body).await?;
+        let (key_desc, key_id) =
+            self.authorize_request(&path, &query_str, &headers, &body, Req::scope()).await?;
         let sdk_version = get_sdk_version(&headers);
 
         // Deserialize the request body, then build the context
         let should_stringify = should_stringify_numbers(&headers);
         let body: Req = json_deserialize(&body, should_stringify)?;
         self.validate_request_body(&body)?;
+        self.authorize_order_mints(key_id, &body).await?;
 
         let mut ctx = RequestContext {
             path,
@@ -287,6 +306,7 @@ impl Server {
             headers,
             user: key_desc,
             key_id,
+            chain: self.chain,
             body,
             sponsorship_info: None,
             request_id: Uuid::new_v4(),
@@ -303,8 +323,8 @@ impl Server {
         Req: ExternalMatchRequestType,
     {
         // Check that the input and output tokens are valid
-        let input_token = body.input_token();
-        let output_token = body.output_token();
+        let input_token = body.input_token(self.chain);
+        let output_token = body.output_token(self.chain);
 
         let input_valid = input_token.is_named() || input_token.is_native_asset();
         if !input_valid {
@@ -321,13 +341,32 @@ impl Server {
         }
 
         // Check that either the input or output token is USDC
-        if input_token != Token::usdc() && output_token != Token::usdc() {
+        let usdc = usdc_on_chain(self.chain);
+        if input_token != usdc && output_token != usdc {
             return Err(AuthServerError::bad_request("Either input or output token must be USDC"));
         }
 
         Ok(())
     }
 
+    /// Check that the given API key may trade the base token of an order
+    pub(crate) async fn authorize_order_mints<Req>(
+        &self,
+        key_id: Uuid,
+        body: &Req,
+    ) -> Result<(), AuthServerError>
+    where
+        Req: ExternalMatchRequestType,
+    {
+        let (base_mint, _) = pick_base_and_quote_mints(
+            body.input_token(self.chain).get_alloy_address(),
+            body.output_token(self.chain).get_alloy_address(),
+            self.chain,
+        )?;
+        let base_addr = token_on_chain(&base_mint, self.chain).get_addr();
+        self.authorize_mint(key_id, &base_addr).await
+    }
+
     /// Forward a request context to the relayer's admin API, returning the
     /// associated response context
     ///
@@ -1,14 +1,15 @@
 //! Quote endpoint handler
 
+use crate::server::db::models::ApiKeyUsage;
 use crate::server::gas_sponsorship::CachedSponsorshipInfo;
-use auth_server_api::{GasSponsorshipInfo, SponsoredQuoteResponse};
+use auth_server_api::{GasSponsorshipInfo, SponsoredQuoteResponse, key_management::ApiKeyScope};
 use bytes::Bytes;
 use http::StatusCode;
 use price_reporter_client::error::PriceReporterClientError;
 use renegade_circuit_types::fixed_point::FixedPoint;
 use renegade_constants::{DEFAULT_EXTERNAL_MATCH_RELAYER_FEE, GLOBAL_MATCHING_POOL};
 use renegade_external_api::http::external_match::{ExternalQuoteRequest, ExternalQuoteResponse};
-use renegade_types_core::Token;
+use renegade_types_core::{Chain, Token};
 use renegade_util::hex::address_to_hex_string;
 use tracing::instrument;
 use warp::reject::Rejection;
@@ -24,6 +25,7 @@ use crate::{
             external_match::{BytesResponse, ExternalMatchRequestType, pick_base_and_quote_mints},
             get_base_and_quote_amount_with_price,
         },
+        chains::token_on_chain,
     },
     telemetry::{
         QUOTE_FILL_RATIO_IGNORE_THRESHOLD,
@@ -45,17 +47,21 @@ use super::{RequestContext, ResponseContext};
 type QuoteRequestCtx = RequestContext<ExternalQuoteRequest>;
 
 impl ExternalMatchRequestType for ExternalQuoteRequest {
-    fn input_token(&self) -> Token {
-        Token::from_alloy_address(&self.external_order.input_mint)
+    fn input_token(&self, chain: Chain) -> Token {
+        token_on_chain(&self.external_order.input_mint, chain)
     }
 
-    fn output_token(&self) -> Token {
-        Token::from_alloy_address(&self.external_order.output_mint)
+    fn output_token(&self, chain: Chain) -> Token {
+        token_on_chain(&self.external_order.output_mint, chain)
     }
 
     fn set_fee(&mut self, fee: f64) {
         self.options.relayer_fee_rate = Some(fee);
     }
+
+    fn scope() -> ApiKeyScope {
+        ApiKeyScope::Quote
+    }
 }
 
 /// The response context for a quote response
@@ -73,6 +79,8 @@ impl SponsoredQuoteResponseCtx {
             path: ctx.path,
             query_str: ctx.query_str,
             user: ctx.user,
+            key_id: ctx.key_id,
+            chain: ctx.chain,
             sdk_version: ctx.sdk_version,
             headers: ctx.headers,
             request: ctx.request,
@@ -121,16 +129,18 @@ impl Server {
         &self,
         ctx: &mut QuoteRequestCtx,
     ) -> Result<(), AuthServerError> {
-        // Check the quote and bundle rate limits in parallel
-        // We return no content if either rate limit is exceeded
+        // Check the quote, bundle, and volume rate limits in parallel
+        // We return no content if any rate limit is exceeded
         let key = ctx.key_id();
         let user = ctx.user();
-        let (quote_res, bundle_res) = tokio::join!(
+        let order = &ctx.body.external_order;
+        let (quote_res, bundle_res, volume_res) = tokio::join!(
             self.consume_quote_rate_limit_token(key, &user),
             self.peek_bundle_rate_limit(key, &user),
+            self.check_volume_rate_limit(key, &user, order),
         );
 
-        if quote_res.is_err() || bundle_res.is_err() {
+        if quote_res.is_err() || bundle_res.is_err() || volume_res.is_err() {
             return Err(AuthServerError::no_match_found());
         };
         self.route_quote_req(ctx).await?;
@@ -180,7 +190,7 @@ impl Server {
     /// If execution costs limits have been exceeded by the bot server, we route
     /// to the global pool to take pressure off the quoters
     async fn route_quote_req(&self, ctx: &mut QuoteRequestCtx) -> Result<(), AuthServerError> {
-        let ticker = ctx.body.base_ticker()?;
+        let ticker = ctx.body.base_ticker(self.chain)?;
         let should_route_to_global = self.should_route_to_global(ctx.key_id(), &ticker).await?;
         if should_route_to_global {
             log_task!(
@@ -282,6 +292,31 @@ impl Server {
                     "error handling quote metrics"
                 );
             }
+
+            // Record the quote in the audit log
+            if let Err(e) = server_clone.record_quote_audit_event(&ctx).await {
+                log_task!(
+                    Task::AuditLog,
+                    Outcome::Failed,
+                    subject = "quote",
+                    error = %e,
+                    "error recording quote in audit log"
+                );
+            }
+
+            // Count the quote against the key's usage
+            server_clone.usage_recorder.record(ApiKeyUsage::quote(ctx.key_id, server_clone.chain));
+
+            // Start the quote's lifecycle
+            if let Err(e) = server_clone.record_quote_lifecycle(&ctx).await {
+                log_task!(
+                    Task::QuoteLifecycle,
+                    Outcome::Failed,
+                    subject = "quote",
+                    error = %e,
+                    "error recording quote lifecycle"
+                );
+            }
         });
     }
 
@@ -302,12 +337,17 @@ impl Server {
         let relayer_fee = FixedPoint::from_f64_round_down(DEFAULT_EXTERNAL_MATCH_RELAYER_FEE);
 
         // Calculate requested and matched quote amounts
-        let (_, requested_quote_amount) =
-            get_base_and_quote_amount_with_price(&req.external_order, relayer_fee, price)?;
+        let (_, requested_quote_amount) = get_base_and_quote_amount_with_price(
+            &req.external_order,
+            relayer_fee,
+            price,
+            ctx.chain,
+        )?;
 
         let input_mint = resp.signed_quote.quote.match_result.input_mint;
         let output_mint = resp.signed_quote.quote.match_result.output_mint;
-        let (base_mint, quote_mint) = pick_base_and_quote_mints(input_mint, output_mint)?;
+        let (base_mint, quote_mint) =
+            pick_base_and_quote_mints(input_mint, output_mint, ctx.chain)?;
 
         let quote_is_input = quote_mint == input_mint;
         let matched_quote_amount = if quote_is_input {
@@ -329,6 +369,7 @@ impl Server {
             &address_to_hex_string(&base_mint),
             EXTERNAL_MATCH_QUOTE_REQUEST_COUNT,
             &labels,
+            ctx.chain,
         );
 
         Ok(())
@@ -357,9 +398,10 @@ impl Server {
     ) -> Result<(), AuthServerError> {
         let req = ctx.request();
         let order = &req.external_order;
-        let (base_mint, _) = pick_base_and_quote_mints(order.input_mint, order.output_mint)?;
+        let (base_mint, _) =
+            pick_base_and_quote_mints(order.input_mint, order.output_mint, ctx.chain)?;
 
-        record_quote_not_found(ctx.user(), &address_to_hex_string(&base_mint));
+        record_quote_not_found(ctx.user(), &address_to_hex_string(&base_mint), ctx.chain);
 
         // Record a zero fill ratio
         let quote_amt = match self
@@ -405,7 +447,7 @@ fn log_quote(ctx: &SponsoredQuoteResponseCtx) -> Result<(), AuthServerError> {
     let key_desc = &ctx.user();
     let match_result = signed_quote.quote.match_result;
     let (base_mint, _) =
-        pick_base_and_quote_mints(match_result.input_mint, match_result.output_mint)?;
+        pick_base_and_quote_mints(match_result.input_mint, match_result.output_mint, ctx.chain)?;
 
     let is_buy = base_mint == match_result.output_mint;
     let is_sponsored = gas_sponsorship_info.is_some();
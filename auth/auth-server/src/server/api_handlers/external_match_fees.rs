@@ -2,13 +2,14 @@
 
 use bytes::Bytes;
 use http::HeaderMap;
-use renegade_types_core::Token;
 use tracing::instrument;
 use warp::{filters::path::FullPath, reject::Rejection, reply::Json};
 
 use auth_server_api::fee_management::{
-    AssetDefaultFeeEntry, GetAllFeesResponse, RemoveAssetDefaultFeeRequest, RemoveUserFeeRequest,
-    SetAssetDefaultFeeRequest, SetUserFeeRequest, UserAssetFeeEntry,
+    AddPromotionalFeeRequest, AddPromotionalFeeResponse, AssetDefaultFeeEntry, FeeTier,
+    GetAllFeesResponse, GetFeeScheduleResponse, PromotionalFee, RemoveAssetDefaultFeeRequest,
+    RemoveFeeTierRequest, RemovePromotionalFeeRequest, RemoveUserFeeRequest,
+    SetAssetDefaultFeeRequest, SetFeeTierRequest, SetUserFeeRequest, UserAssetFeeEntry,
 };
 
 use crate::{
@@ -16,7 +17,8 @@ use crate::{
     http_utils::request_response::empty_json_reply,
     server::{
         Server,
-        db::models::{NewAssetDefaultFee, NewUserFee},
+        chains::ServedTokens,
+        db::models::{NewAssetDefaultFee, NewFeeTier, NewPromotionalFee, NewUserFee},
     },
 };
 
@@ -33,6 +35,10 @@ const MAX_FEE: f32 = 0.01; // 1%
 const INVALID_FEE_MSG: &str = "Fee must be between 0.0 and 0.01";
 /// The error message to return if a ticker is invalid
 const INVALID_TICKER_MSG: &str = "Ticker must be 3 uppercase letters";
+/// The error message to return if a fee tier's volume threshold is invalid
+const INVALID_MIN_VOLUME_MSG: &str = "min_volume_usd must be a non-negative number";
+/// The error message to return if a promotion's time range is invalid
+const INVALID_PROMOTION_RANGE_MSG: &str = "Promotion must end after it starts";
 
 // -----------
 // | Helpers |
@@ -44,9 +50,23 @@ pub fn validate_fee(fee: f32) -> Result<(), ApiError> {
     valid_fee.then_some(()).ok_or(ApiError::bad_request(INVALID_FEE_MSG))
 }
 
-/// Validate an asset's ticker
-pub fn validate_ticker(ticker: &str) -> Result<(), ApiError> {
-    Token::maybe_from_ticker(ticker).ok_or(ApiError::bad_request(INVALID_TICKER_MSG)).map(|_| ())
+/// Validate an asset's ticker, which may name a token on any served chain
+pub fn validate_ticker(ticker: &str, tokens: &ServedTokens) -> Result<(), ApiError> {
+    tokens
+        .is_listed(ticker, None /* chain */)
+        .then_some(())
+        .ok_or(ApiError::bad_request(INVALID_TICKER_MSG))
+}
+
+/// Validate a fee tier's volume threshold
+pub fn validate_min_volume(min_volume_usd: f64) -> Result<(), ApiError> {
+    let valid = min_volume_usd.is_finite() && min_volume_usd >= 0.;
+    valid.then_some(()).ok_or(ApiError::bad_request(INVALID_MIN_VOLUME_MSG))
+}
+
+/// Validate a promotion's time range
+pub fn validate_promotion_range(starts_at: u64, ends_at: u64) -> Result<(), ApiError> {
+    (ends_at > starts_at).then_some(()).ok_or(ApiError::bad_request(INVALID_PROMOTION_RANGE_MSG))
 }
 
 // --------------------
@@ -76,6 +96,25 @@ impl Server {
         Ok(warp::reply::json(&response))
     }
 
+    /// Get the volume-based fee schedule; all fee tiers and promotions
+    #[instrument(skip_all)]
+    pub async fn get_fee_schedule(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+    ) -> Result<Json, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+
+        let tiers = self.get_all_fee_tiers().await?;
+        let promotions = self.get_all_promotional_fees().await?;
+
+        let response = GetFeeScheduleResponse {
+            tiers: tiers.into_iter().map(FeeTier::from).collect(),
+            promotions: promotions.into_iter().map(PromotionalFee::from).collect(),
+        };
+        Ok(warp::reply::json(&response))
+    }
+
     // --- Setters --- //
 
     /// Set the default fee for a given asset
@@ -93,7 +132,7 @@ impl Server {
         let req: SetAssetDefaultFeeRequest =
             serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
         validate_fee(req.fee)?;
-        validate_ticker(&req.asset)?;
+        validate_ticker(&req.asset, &self.served_tokens)?;
 
         // Create the new default fee entry and upsert it in the database
         let new_default_fee = NewAssetDefaultFee::new(req.asset, req.fee);
@@ -116,7 +155,7 @@ impl Server {
         // Parse the request body and validate it
         let req: RemoveAssetDefaultFeeRequest =
             serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
-        validate_ticker(&req.asset)?;
+        validate_ticker(&req.asset, &self.served_tokens)?;
 
         self.remove_asset_default_fee_query(req.asset).await?;
         Ok(empty_json_reply())
@@ -137,7 +176,7 @@ impl Server {
         let req: SetUserFeeRequest =
             serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
         validate_fee(req.fee)?;
-        validate_ticker(&req.asset)?;
+        validate_ticker(&req.asset, &self.served_tokens)?;
 
         // Create the new user fee entry, upsert it in the database
         let new_user_fee = NewUserFee::new(req.user_id, req.asset, req.fee);
@@ -160,9 +199,98 @@ impl Server {
         // Parse the request body and remove the user fee override
         let req: RemoveUserFeeRequest =
             serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
-        validate_ticker(&req.asset)?;
+        validate_ticker(&req.asset, &self.served_tokens)?;
 
         self.remove_user_fee_query(req.user_id, req.asset).await?;
         Ok(empty_json_reply())
     }
+
+    // --- Fee Schedule --- //
+
+    /// Set a fee tier, replacing any tier on the same asset and threshold
+    #[instrument(skip_all)]
+    pub async fn set_fee_tier(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Parse the request body and validate it
+        let SetFeeTierRequest { tier } =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        validate_fee(tier.fee)?;
+        validate_min_volume(tier.min_volume_usd)?;
+        if let Some(asset) = &tier.asset {
+            validate_ticker(asset, &self.served_tokens)?;
+        }
+
+        self.set_fee_tier_query(NewFeeTier::from(&tier)).await?;
+        Ok(empty_json_reply())
+    }
+
+    /// Remove a fee tier
+    #[instrument(skip_all)]
+    pub async fn remove_fee_tier(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Parse the request body and remove the tier
+        let req: RemoveFeeTierRequest =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        if let Some(asset) = &req.asset {
+            validate_ticker(asset, &self.served_tokens)?;
+        }
+
+        self.remove_fee_tier_query(req.asset, req.min_volume_usd).await?;
+        Ok(empty_json_reply())
+    }
+
+    /// Add a time-boxed promotional fee
+    #[instrument(skip_all)]
+    pub async fn add_promotional_fee(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Parse the request body and validate it
+        let req: AddPromotionalFeeRequest =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        validate_fee(req.fee)?;
+        validate_promotion_range(req.starts_at, req.ends_at)?;
+        if let Some(asset) = &req.asset {
+            validate_ticker(asset, &self.served_tokens)?;
+        }
+
+        let id = self.add_promotional_fee_query(NewPromotionalFee::from(req)).await?;
+        Ok(warp::reply::json(&AddPromotionalFeeResponse { id }))
+    }
+
+    /// Remove a promotional fee
+    #[instrument(skip_all)]
+    pub async fn remove_promotional_fee(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        let req: RemovePromotionalFeeRequest =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        self.remove_promotional_fee_query(req.id).await?;
+        Ok(empty_json_reply())
+    }
 }
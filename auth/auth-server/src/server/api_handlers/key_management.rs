@@ -1,16 +1,27 @@
 //! Handles key management requests
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use crate::{
+    error::AuthServerError,
     http_utils::request_response::empty_json_reply,
+    log_task,
+    logger::{Outcome, Task},
+    server::api_auth::parse_ip_rule,
     server::db::models::{NewApiKey, NewRateLimit, RateLimitMethod},
     server::helpers::aes_encrypt,
 };
+use alloy_primitives::Address;
 use auth_server_api::{
-    CreateApiKeyRequest, SetRateLimitRequest,
-    key_management::{AllKeysResponse, ApiKey as UserFacingApiKey},
+    CreateApiKeyRequest, RotateApiKeySecretRequest, RotateApiKeySecretResponse,
+    SetApiKeyPermissionsRequest, SetRateLimitRequest,
+    key_management::{AllKeysResponse, ApiKey as UserFacingApiKey, ApiKeyPermissions},
+    webhooks::{SetWebhookRequest, WebhookDeliveriesQuery, WebhookDeliveriesResponse},
 };
 use bytes::Bytes;
 use http::HeaderMap;
+use renegade_types_core::HmacKey;
+use reqwest::Url;
 use tracing::instrument;
 use uuid::Uuid;
 use warp::{filters::path::FullPath, reject::Rejection, reply::Json};
@@ -19,6 +30,11 @@ use crate::ApiError;
 
 use super::Server;
 
+/// The number of webhook deliveries returned if no limit is given
+const DEFAULT_WEBHOOK_DELIVERIES_LIMIT: u32 = 100;
+/// The maximum number of webhook deliveries returned
+const MAX_WEBHOOK_DELIVERIES_LIMIT: u32 = 1_000;
+
 impl Server {
     // --- Getters --- //
 
@@ -38,6 +54,31 @@ impl Server {
         Ok(warp::reply::json(&reply_body))
     }
 
+    /// Get the most recent webhook deliveries for an API key
+    #[instrument(skip_all)]
+    pub async fn get_webhook_deliveries(
+        &self,
+        key_id: Uuid,
+        path: FullPath,
+        headers: HeaderMap,
+        query: WebhookDeliveriesQuery,
+    ) -> Result<Json, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_WEBHOOK_DELIVERIES_LIMIT)
+            .min(MAX_WEBHOOK_DELIVERIES_LIMIT);
+        let deliveries = self
+            .get_webhook_deliveries_query(key_id, limit as i64)
+            .await?
+            .into_iter()
+            .map(|delivery| delivery.try_into().map_err(AuthServerError::custom))
+            .collect::<Result<_, _>>()?;
+
+        Ok(warp::reply::json(&WebhookDeliveriesResponse { deliveries }))
+    }
+
     // --- Setters --- //
 
     /// Add a new API key to the database
@@ -55,9 +96,11 @@ impl Server {
         let req: CreateApiKeyRequest =
             serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
 
+        validate_permissions(&req.permissions)?;
+
         // Add the key to the database
         let encrypted_secret = aes_encrypt(&req.secret, &self.encryption_key)?;
-        let new_key = NewApiKey::new(req.id, encrypted_secret, req.description);
+        let new_key = NewApiKey::new(req.id, encrypted_secret, req.description, &req.permissions);
         self.add_key_query(new_key).await.map_err(ApiError::internal)?;
 
         Ok(empty_json_reply())
@@ -123,6 +166,116 @@ impl Server {
         Ok(empty_json_reply())
     }
 
+    /// Rotate the secret of an API key
+    ///
+    /// Issues a new secret for the key. The previous secret remains valid for
+    /// the requested grace period, or the server's default if unspecified, so
+    /// that the key holder can roll the new secret out without downtime.
+    ///
+    /// Other servers pick up the rotation once their cached key expires, so
+    /// they may accept the previous secret for up to the API key cache TTL
+    /// even if the grace period is shorter.
+    #[instrument(skip_all)]
+    pub async fn rotate_api_key_secret(
+        &self,
+        key_id: Uuid,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Deserialize the request, an empty body selects the defaults
+        let req: RotateApiKeySecretRequest = if body.is_empty() {
+            RotateApiKeySecretRequest::default()
+        } else {
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?
+        };
+        let grace_period = req
+            .grace_period_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.secret_rotation_grace_period);
+        let previous_expires_at = SystemTime::now() + grace_period;
+
+        // Issue the new secret and retire the current one
+        let secret = HmacKey::random().to_base64_string();
+        let encrypted_secret = aes_encrypt(&secret, &self.encryption_key)?;
+        let generation =
+            self.rotate_secret_query(key_id, encrypted_secret, previous_expires_at).await?;
+        self.cache.clear_key(key_id);
+        self.cache.clear_previous_secrets(key_id);
+
+        let previous_secret_expires_at =
+            previous_expires_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        log_task!(
+            Task::ApiKeyAuth,
+            Outcome::Ok,
+            subject = %key_id,
+            secret_generation = generation,
+            previous_secret_expires_at = previous_secret_expires_at,
+            "rotated API key secret"
+        );
+
+        let resp = RotateApiKeySecretResponse {
+            secret,
+            generation: generation as u32,
+            previous_secret_expires_at,
+        };
+        Ok(warp::reply::json(&resp))
+    }
+
+    /// Replace the permissions of an API key
+    ///
+    /// Unset fields in the request remove the corresponding restriction
+    #[instrument(skip_all)]
+    pub async fn set_api_key_permissions(
+        &self,
+        key_id: Uuid,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Deserialize and validate the request
+        let req: SetApiKeyPermissionsRequest =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        validate_permissions(&req.permissions)?;
+
+        self.set_api_key_permissions_query(key_id, &req.permissions).await?;
+        self.cache.clear_key(key_id);
+
+        Ok(empty_json_reply())
+    }
+
+    /// Set the webhook URL of an API key, or disable webhooks if no URL is
+    /// given
+    #[instrument(skip_all)]
+    pub async fn set_webhook(
+        &self,
+        key_id: Uuid,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Deserialize and validate the request
+        let req: SetWebhookRequest =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        if let Some(url) = &req.url {
+            validate_webhook_url(url)?;
+        }
+
+        self.set_webhook_url_query(key_id, req.url).await?;
+        self.cache.clear_key(key_id);
+
+        Ok(empty_json_reply())
+    }
+
     /// Set a rate limit for an API key
     ///
     /// This sets the maximum requests per minute for a given API key and method
@@ -153,3 +306,34 @@ impl Server {
         Ok(empty_json_reply())
     }
 }
+
+/// Validate the permissions attached to an API key
+fn validate_permissions(permissions: &ApiKeyPermissions) -> Result<(), AuthServerError> {
+    let mints = permissions.allowed_mints.iter().chain(permissions.denied_mints.iter()).flatten();
+    for mint in mints {
+        if mint.parse::<Address>().is_err() {
+            return Err(AuthServerError::bad_request(format!("Invalid mint: {mint}")));
+        }
+    }
+
+    for rule in permissions.allowed_ips.iter().flatten() {
+        if parse_ip_rule(rule).is_none() {
+            return Err(AuthServerError::bad_request(format!(
+                "Invalid IP allowlist entry: {rule}"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate a webhook URL, which must be an absolute HTTP(S) URL
+fn validate_webhook_url(url: &str) -> Result<(), AuthServerError> {
+    let parsed = Url::parse(url)
+        .map_err(|e| AuthServerError::bad_request(format!("Invalid webhook URL: {e}")))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(AuthServerError::bad_request("Webhook URL must use http or https"));
+    }
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 //! Market endpoint handlers
 
+use auth_server_api::key_management::{ApiKeyPermissions, ApiKeyScope};
 use bytes::Bytes;
 use futures_util::future;
 use http::{HeaderMap, Method, StatusCode};
@@ -11,6 +12,7 @@ use renegade_external_api::{
     },
     types::market::{MarketDepth, MarketInfo},
 };
+use renegade_util::hex::address_to_hex_string;
 use tokio::task::{JoinHandle, JoinSet};
 use tracing::instrument;
 use uuid::Uuid;
@@ -20,7 +22,7 @@ use super::{Server, log_unsuccessful_relayer_request};
 use crate::{
     error::AuthServerError,
     http_utils::request_response::{overwrite_response_body, should_stringify_numbers},
-    server::api_handlers::external_match::BytesResponse,
+    server::{api_auth::mint_allowed, api_handlers::external_match::BytesResponse},
     telemetry::helpers::record_relayer_request_500,
 };
 
@@ -36,8 +38,15 @@ impl Server {
         // Authorize the request
         let path_str = path.as_str();
         let (key_desc, key_id) = self
-            .authorize_request(path_str, "" /* query_str */, &headers, &[] /* body */)
+            .authorize_request(
+                path_str,
+                "", // query_str
+                &headers,
+                &[], // body
+                ApiKeyScope::Markets,
+            )
             .await?;
+        self.authorize_mint(key_id, &mint).await?;
 
         // Check if stringification is requested
         let should_stringify = should_stringify_numbers(&headers);
@@ -73,7 +82,13 @@ impl Server {
         // Authorize the request
         let path_str = path.as_str();
         let (key_desc, key_id) = self
-            .authorize_request(path_str, "" /* query_str */, &headers, &[] /* body */)
+            .authorize_request(
+                path_str,
+                "", // query_str
+                &headers,
+                &[], // body
+                ApiKeyScope::Markets,
+            )
             .await?;
 
         // Check if stringification is requested before headers are moved
@@ -92,6 +107,10 @@ impl Server {
         let mut body: GetMarketDepthsResponse =
             serde_json::from_slice(resp.body()).map_err(AuthServerError::serde)?;
 
+        // Hide markets the key is not permitted to access
+        let permissions = self.get_api_key_entry(key_id).await?.permissions();
+        body.market_depths.retain(|depth| market_allowed(&permissions, &depth.market));
+
         // Update all markets' external match relayer fee rates concurrently
         let mut futures = Vec::<JoinHandle<Result<MarketDepth, AuthServerError>>>::new();
         for market_depth in body.market_depths.iter().cloned() {
@@ -131,7 +150,13 @@ impl Server {
         // Authorize the request
         let path_str = path.as_str();
         let (key_desc, key_id) = self
-            .authorize_request(path_str, "" /* query_str */, &headers, &[] /* body */)
+            .authorize_request(
+                path_str,
+                "", // query_str
+                &headers,
+                &[], // body
+                ApiKeyScope::Markets,
+            )
             .await?;
 
         // Check if stringification is requested before headers are moved
@@ -149,6 +174,10 @@ impl Server {
         let mut body: GetMarketsResponse =
             serde_json::from_slice(resp.body()).map_err(AuthServerError::serde)?;
 
+        // Hide markets the key is not permitted to access
+        let permissions = self.get_api_key_entry(key_id).await?.permissions();
+        body.markets.retain(|market| market_allowed(&permissions, market));
+
         // Update all markets' external match relayer fee rates concurrently
         let mut join_set = JoinSet::new();
         for market_info in body.markets.iter().cloned() {
@@ -214,3 +243,8 @@ impl Server {
         Ok(())
     }
 }
+
+/// Whether a key's permissions admit the given market
+fn market_allowed(permissions: &ApiKeyPermissions, market: &MarketInfo) -> bool {
+    mint_allowed(permissions, &address_to_hex_string(&market.base.address))
+}
@@ -3,13 +3,19 @@
 //! At a high level the server must first authenticate the request, then forward
 //! it to the relayer with admin authentication
 
+mod audit_log;
 mod connectors;
 mod exchange_metadata;
 mod external_match;
 mod external_match_fees;
 mod key_management;
 mod markets;
+mod quote_lifecycle;
+mod rfqt_ladders;
 mod settlement;
+mod sponsorship_policies;
+mod usage;
+mod volume_limits;
 
 use auth_server_api::{GasSponsorshipInfo, GasSponsorshipQueryParams, SponsoredMatchResponse};
 use bytes::Bytes;
@@ -125,7 +131,7 @@ impl Server {
         // Generate gas sponsorship info
         let user = ctx.user();
         let gas_sponsorship_info =
-            self.generate_sponsorship_info(&user, order, &query_params).await?;
+            self.generate_sponsorship_info(ctx.key_id(), &user, order, &query_params).await?;
 
         // Subtract the refund amount from the exact output amount requested in the
         // order, so that the relayer produces a smaller quote which will
@@ -170,7 +176,7 @@ impl Server {
         ];
 
         // Record metrics
-        record_external_match_metrics(order, &match_bundle, &labels)?;
+        record_external_match_metrics(order, &match_bundle, &labels, ctx.chain)?;
         Ok(())
     }
 
@@ -183,14 +189,16 @@ impl Server {
         order: &ExternalOrder,
         relayer_fee: FixedPoint,
     ) -> Result<Amount, AuthServerError> {
-        let (base_mint, _) = pick_base_and_quote_mints(order.input_mint, order.output_mint)?;
+        let (base_mint, _) =
+            pick_base_and_quote_mints(order.input_mint, order.output_mint, self.chain)?;
 
         let price = self
             .price_reporter_client
             .get_price(&address_to_hex_string(&base_mint), self.chain)
             .await?;
 
-        let (_, quote_amount) = get_base_and_quote_amount_with_price(order, relayer_fee, price)?;
+        let (_, quote_amount) =
+            get_base_and_quote_amount_with_price(order, relayer_fee, price, self.chain)?;
         Ok(quote_amount)
     }
 }
@@ -206,6 +214,7 @@ pub(crate) fn get_base_and_quote_amount_with_price(
     order: &ExternalOrder,
     relayer_fee: FixedPoint,
     price: f64,
+    chain: Chain,
 ) -> Result<(Amount, Amount), AuthServerError> {
     let price_fp = FixedPoint::from_f64_round_down(price);
     if price_fp == FixedPoint::zero() {
@@ -214,7 +223,8 @@ pub(crate) fn get_base_and_quote_amount_with_price(
         ));
     }
 
-    let (base_mint, quote_mint) = pick_base_and_quote_mints(order.input_mint, order.output_mint)?;
+    let (base_mint, quote_mint) =
+        pick_base_and_quote_mints(order.input_mint, order.output_mint, chain)?;
 
     let base_input_set = base_mint == order.input_mint && order.input_amount != 0;
     let base_output_set = base_mint == order.output_mint && order.output_amount != 0;
@@ -226,7 +236,7 @@ pub(crate) fn get_base_and_quote_amount_with_price(
         let quote_amount = scalar_to_u128(&implied_quote_amount.floor());
         Ok((base_amount, quote_amount))
     } else if base_output_set {
-        let base_amount = fee_adjusted_output_amount(order, relayer_fee)?;
+        let base_amount = fee_adjusted_output_amount(order, relayer_fee, chain)?;
         let implied_quote_amount = price_fp * base_amount;
         let quote_amount = scalar_to_u128(&implied_quote_amount.floor());
         Ok((base_amount, quote_amount))
@@ -236,7 +246,7 @@ pub(crate) fn get_base_and_quote_amount_with_price(
         let base_amount = scalar_to_u128(&implied_base_amount);
         Ok((base_amount, quote_amount))
     } else {
-        let quote_amount = fee_adjusted_output_amount(order, relayer_fee)?;
+        let quote_amount = fee_adjusted_output_amount(order, relayer_fee, chain)?;
         let implied_base_amount = price_fp.floor_div_int(quote_amount);
         let base_amount = scalar_to_u128(&implied_base_amount);
         Ok((base_amount, quote_amount))
@@ -248,13 +258,15 @@ pub(crate) fn get_base_and_quote_amount_with_price(
 fn fee_adjusted_output_amount(
     order: &ExternalOrder,
     relayer_fee: FixedPoint,
+    chain: Chain,
 ) -> Result<Amount, AuthServerError> {
     let output_amount = order.output_amount;
     if !order.use_exact_output_amount {
         return Ok(output_amount);
     }
 
-    let (base_mint, quote_mint) = pick_base_and_quote_mints(order.input_mint, order.output_mint)?;
+    let (base_mint, quote_mint) =
+        pick_base_and_quote_mints(order.input_mint, order.output_mint, chain)?;
 
     let protocol_fee = get_protocol_fee(&base_mint, &quote_mint);
     let total_fee = protocol_fee + relayer_fee;
@@ -305,11 +317,11 @@ fn log_bundle(
     let is_sponsored = gas_sponsorship_info.is_some();
 
     // Get the decimal-corrected price
-    let price = calculate_quote_per_base_price(&match_bundle.match_result)?;
+    let price = calculate_quote_per_base_price(&match_bundle.match_result, ctx.chain)?;
 
     let match_result = &match_bundle.match_result;
     let (base_mint, _) =
-        pick_base_and_quote_mints(match_result.input_mint, match_result.output_mint)?;
+        pick_base_and_quote_mints(match_result.input_mint, match_result.output_mint, ctx.chain)?;
     let is_buy = base_mint == match_result.output_mint;
     let min_recv = &match_bundle.min_receive;
     let max_recv = &match_bundle.max_receive;
@@ -318,14 +330,14 @@ fn log_bundle(
 
     let relayer_fee = FixedPoint::from_f64_round_down(DEFAULT_EXTERNAL_MATCH_RELAYER_FEE);
     let (requested_base_amount, requested_quote_amount) =
-        get_base_and_quote_amount_with_price(order, relayer_fee, price)?;
+        get_base_and_quote_amount_with_price(order, relayer_fee, price, ctx.chain)?;
 
     // Get the base fill ratio
-    let response_base_amount = get_default_base_amount(&match_bundle)?;
+    let response_base_amount = get_default_base_amount(&match_bundle, ctx.chain)?;
     let base_fill_ratio = response_base_amount as f64 / requested_base_amount as f64;
 
     // Get the quote fill ratio
-    let response_quote_amount = get_default_quote_amount(&match_bundle)?;
+    let response_quote_amount = get_default_quote_amount(&match_bundle, ctx.chain)?;
     let quote_fill_ratio = response_quote_amount as f64 / requested_quote_amount as f64;
 
     // Get the gas sponsorship info
@@ -0,0 +1,133 @@
+//! Tracks quotes through assembly and settlement
+//!
+//! Quotes and assemblies are recorded here off the hot path; settlements and
+//! markouts are recorded by the on-chain event listener. The management
+//! endpoint aggregates the lifecycles into per-key, per-market funnel stats
+
+use auth_server_api::quote_analytics::{QuoteFunnelQuery, QuoteFunnelResponse, QuoteFunnelStats};
+use bytes::Bytes;
+use http::HeaderMap;
+use renegade_external_api::http::external_match::ExternalMatchAssemblyType;
+use renegade_util::{get_current_time_millis, hex::address_to_hex_string};
+use tracing::instrument;
+use warp::{filters::path::FullPath, reject::Rejection, reply::Json};
+
+use super::Server;
+use super::audit_log::resolve_asset_mints;
+use crate::error::AuthServerError;
+use crate::server::api_handlers::external_match::{
+    SponsoredExternalMatchResponseCtx, SponsoredQuoteResponseCtx,
+};
+use crate::server::db::models::{NewQuoteLifecycle, millis_to_system_time};
+use crate::server::helpers::{generate_quote_uuid, pick_base_and_quote_mints};
+
+/// The default length of a quote funnel query's time range, in milliseconds
+const DEFAULT_FUNNEL_RANGE_MS: u64 = 24 * 60 * 60 * 1000; // 1 day
+
+/// The error message emitted when a funnel query's range is empty
+const ERR_EMPTY_RANGE: &str = "start_time must be before end_time";
+/// The error message emitted when an assembled bundle has no bundle ID
+const ERR_NO_BUNDLE_ID: &str = "No bundle ID found for assembled bundle";
+
+impl Server {
+    // --- Management Endpoints --- //
+
+    /// Get quote funnel stats per key and market
+    #[instrument(skip_all)]
+    pub async fn get_quote_funnel(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        query: QuoteFunnelQuery,
+    ) -> Result<Json, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+
+        let end_time = query.end_time.unwrap_or_else(get_current_time_millis);
+        let start_time =
+            query.start_time.unwrap_or_else(|| end_time.saturating_sub(DEFAULT_FUNNEL_RANGE_MS));
+        if start_time >= end_time {
+            return Err(AuthServerError::bad_request(ERR_EMPTY_RANGE).into());
+        }
+        let base_mints = query
+            .asset
+            .as_deref()
+            .map(|asset| resolve_asset_mints(asset, &self.served_tokens))
+            .transpose()?;
+
+        let markets = self
+            .get_quote_funnel_query(
+                query.api_key_id,
+                base_mints,
+                millis_to_system_time(start_time),
+                millis_to_system_time(end_time),
+            )
+            .await?
+            .into_iter()
+            .map(QuoteFunnelStats::from)
+            .collect();
+        Ok(warp::reply::json(&QuoteFunnelResponse { start_time, end_time, markets }))
+    }
+
+    // --- Recording --- //
+
+    /// Record a quote sent to a client as the start of a lifecycle
+    ///
+    /// The lifecycle is keyed by the quote's ID so that an assembly of the
+    /// quote can be correlated with it
+    pub(crate) async fn record_quote_lifecycle(
+        &self,
+        ctx: &SponsoredQuoteResponseCtx,
+    ) -> Result<(), AuthServerError> {
+        let signed_quote = &ctx.response().signed_quote;
+        let match_result = &signed_quote.quote.match_result;
+        let (base_mint, _) = pick_base_and_quote_mints(
+            match_result.input_mint,
+            match_result.output_mint,
+            self.chain,
+        )?;
+
+        let entry = NewQuoteLifecycle::quote(
+            generate_quote_uuid(signed_quote),
+            ctx.key_id,
+            self.chain,
+            address_to_hex_string(&base_mint),
+            base_mint == match_result.output_mint, // external_buys
+            signed_quote.quote.price.price,
+        );
+        self.record_quote_lifecycle_query(&entry).await
+    }
+
+    /// Record an assembled bundle on its lifecycle
+    ///
+    /// Assemblies of a quote are recorded on the quote's lifecycle; direct
+    /// order assemblies start a lifecycle keyed by the request ID
+    pub(crate) async fn record_assembly_lifecycle(
+        &self,
+        ctx: &SponsoredExternalMatchResponseCtx,
+    ) -> Result<(), AuthServerError> {
+        let bundle_id =
+            ctx.sponsorship_nonce().ok_or_else(|| AuthServerError::custom(ERR_NO_BUNDLE_ID))?;
+        let id = match &ctx.request().order {
+            ExternalMatchAssemblyType::QuotedOrder { signed_quote, .. } => {
+                generate_quote_uuid(signed_quote)
+            },
+            ExternalMatchAssemblyType::DirectOrder { .. } => ctx.request_id,
+        };
+
+        let match_result = &ctx.response().match_bundle.match_result;
+        let (base_mint, _) = pick_base_and_quote_mints(
+            match_result.input_mint,
+            match_result.output_mint,
+            self.chain,
+        )?;
+        let entry = NewQuoteLifecycle::assembly(
+            id,
+            ctx.key_id,
+            self.chain,
+            address_to_hex_string(&base_mint),
+            base_mint == match_result.output_mint, // external_buys
+            bundle_id.to_string(),
+        );
+        self.record_assembly_lifecycle_query(&entry).await
+    }
+}
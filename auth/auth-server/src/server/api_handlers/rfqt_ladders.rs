@@ -0,0 +1,216 @@
+//! Handlers for RFQT price ladders
+
+use auth_server_api::rfqt_ladders::{
+    GetRfqtLaddersResponse, RemoveRfqtLadderRequest, RfqtLadder, SetRfqtLadderRequest,
+};
+use bytes::Bytes;
+use http::HeaderMap;
+use tracing::instrument;
+use warp::{filters::path::FullPath, reject::Rejection, reply::Json};
+
+use super::Server;
+use crate::ApiError;
+use crate::error::AuthServerError;
+use crate::http_utils::request_response::empty_json_reply;
+use crate::server::api_handlers::connectors::rfqt::helpers::BPS_PER_ONE;
+use crate::server::db::models::NewRfqtLadder;
+
+/// The maximum number of levels quoted on each side of a ladder
+const MAX_LADDER_LEVELS: usize = 20;
+
+/// The error message emitted when a ladder has no levels or too many levels
+const ERR_INVALID_LEVEL_COUNT: &str = "A ladder must have between 1 and 20 size fractions";
+/// The error message emitted when a ladder's size fractions are invalid
+const ERR_INVALID_SIZE_FRACTIONS: &str =
+    "size_fractions must be strictly ascending and each in (0, 1]";
+/// The error message emitted when a ladder's spreads are invalid
+const ERR_INVALID_SPREAD: &str = "base_spread_bps and impact_bps must be non-negative numbers";
+/// The error message emitted when a ladder's impact exponent is invalid
+const ERR_INVALID_IMPACT_EXPONENT: &str = "impact_exponent must be a positive number";
+/// The error message emitted when a ladder's skew is invalid
+const ERR_INVALID_SKEW: &str = "skew_bps must be a number";
+/// The error message emitted when a ladder's skew would quote a level better
+/// than the midpoint
+const ERR_SKEW_CROSSES_MIDPOINT: &str = "|skew_bps| must not exceed base_spread_bps";
+/// The error message emitted when a ladder would quote a non-positive price
+const ERR_NON_POSITIVE_PRICE: &str =
+    "base_spread_bps + impact_bps + |skew_bps| must be less than 10000";
+
+impl Server {
+    /// Get all configured RFQT ladders
+    #[instrument(skip_all)]
+    pub async fn get_rfqt_ladders(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+    ) -> Result<Json, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+
+        let ladders =
+            self.get_all_rfqt_ladders().await?.into_iter().map(RfqtLadder::from).collect();
+        Ok(warp::reply::json(&GetRfqtLaddersResponse { ladders }))
+    }
+
+    /// Set an RFQT ladder, replacing any ladder on the same key
+    #[instrument(skip_all)]
+    pub async fn set_rfqt_ladder(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Deserialize and validate the request
+        let SetRfqtLadderRequest { ladder } =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        validate_rfqt_ladder(&ladder)?;
+
+        self.set_rfqt_ladder_query(NewRfqtLadder::from(&ladder)).await?;
+        Ok(empty_json_reply())
+    }
+
+    /// Remove an RFQT ladder
+    #[instrument(skip_all)]
+    pub async fn remove_rfqt_ladder(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        let req: RemoveRfqtLadderRequest =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        self.remove_rfqt_ladder_query(req.api_key_id).await?;
+        Ok(empty_json_reply())
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Validate an RFQT ladder
+fn validate_rfqt_ladder(ladder: &RfqtLadder) -> Result<(), AuthServerError> {
+    let n_levels = ladder.size_fractions.len();
+    if n_levels == 0 || n_levels > MAX_LADDER_LEVELS {
+        return Err(AuthServerError::bad_request(ERR_INVALID_LEVEL_COUNT));
+    }
+
+    let mut prev = 0.;
+    for &fraction in ladder.size_fractions.iter() {
+        // Written to reject NaN
+        if !(fraction > prev && fraction <= 1.) {
+            return Err(AuthServerError::bad_request(ERR_INVALID_SIZE_FRACTIONS));
+        }
+        prev = fraction;
+    }
+
+    let valid_spread = |bps: f64| bps.is_finite() && bps >= 0.;
+    if !valid_spread(ladder.base_spread_bps) || !valid_spread(ladder.impact_bps) {
+        return Err(AuthServerError::bad_request(ERR_INVALID_SPREAD));
+    }
+
+    if !ladder.impact_exponent.is_finite() || ladder.impact_exponent <= 0. {
+        return Err(AuthServerError::bad_request(ERR_INVALID_IMPACT_EXPONENT));
+    }
+
+    if !ladder.skew_bps.is_finite() {
+        return Err(AuthServerError::bad_request(ERR_INVALID_SKEW));
+    }
+
+    // Firm quotes are matched at the midpoint less the key's fee, so no level
+    // may advertise a price better than the midpoint
+    if ladder.skew_bps.abs() > ladder.base_spread_bps {
+        return Err(AuthServerError::bad_request(ERR_SKEW_CROSSES_MIDPOINT));
+    }
+
+    // The widest bid is quoted at the full depth, and must stay above zero
+    let max_offset_bps = ladder.widening_bps(1.) + ladder.skew_bps.abs();
+    if max_offset_bps >= BPS_PER_ONE {
+        return Err(AuthServerError::bad_request(ERR_NON_POSITIVE_PRICE));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_ladder_is_valid() {
+        assert!(validate_rfqt_ladder(&RfqtLadder::default()).is_ok());
+    }
+
+    #[test]
+    fn ladder_size_fractions_must_be_ascending_in_unit_interval() {
+        let mut ladder = RfqtLadder { size_fractions: vec![], ..Default::default() };
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+
+        ladder.size_fractions = vec![0.25, 0.5, 1.];
+        assert!(validate_rfqt_ladder(&ladder).is_ok());
+
+        ladder.size_fractions = vec![0.5, 0.5];
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+
+        ladder.size_fractions = vec![0., 1.];
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+
+        ladder.size_fractions = vec![0.5, 1.5];
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+
+        ladder.size_fractions = vec![f64::NAN];
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+    }
+
+    #[test]
+    fn ladder_prices_must_stay_positive() {
+        let mut ladder = RfqtLadder {
+            size_fractions: vec![0.5, 1.],
+            base_spread_bps: 5.,
+            impact_bps: 20.,
+            skew_bps: -2.,
+            ..Default::default()
+        };
+        assert!(validate_rfqt_ladder(&ladder).is_ok());
+
+        ladder.impact_bps = 9_995.;
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+
+        ladder.impact_bps = -1.;
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+
+        ladder.impact_bps = 20.;
+        ladder.impact_exponent = 0.;
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+    }
+
+    #[test]
+    fn ladder_skew_must_not_cross_midpoint() {
+        let mut ladder = RfqtLadder {
+            size_fractions: vec![0.5, 1.],
+            base_spread_bps: 5.,
+            impact_bps: 20.,
+            skew_bps: 5.,
+            ..Default::default()
+        };
+        assert!(validate_rfqt_ladder(&ladder).is_ok());
+
+        // The first bid would be quoted above the midpoint
+        ladder.skew_bps = 6.;
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+
+        // The first ask would be quoted below the midpoint
+        ladder.skew_bps = -6.;
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+
+        // A ladder without a base spread cannot be skewed
+        ladder.base_spread_bps = 0.;
+        ladder.skew_bps = 1.;
+        assert!(validate_rfqt_ladder(&ladder).is_err());
+    }
+}
@@ -5,6 +5,7 @@ use crate::bundle_store::BundleContext;
 use crate::bundle_store::BundleId;
 use crate::error::AuthServerError;
 use crate::server::api_handlers::external_match::SponsoredExternalMatchResponseCtx;
+use crate::server::rate_limiter::VolumeConsumption;
 
 /// The error message emitted when a nonce cannot be found
 const ERR_NO_NONCE: &str = "No sponsorship nonce found";
@@ -12,12 +13,17 @@ const ERR_NO_NONCE: &str = "No sponsorship nonce found";
 impl Server {
     /// Write the bundle context to the store, handling gas sponsorship if
     /// necessary
+    ///
+    /// If the write fails, the bundle's consumed volume is refunded, as the
+    /// bundle can no longer be refunded when it expires
+    ///
     /// Returns the bundle ID
-    pub fn write_bundle_context(
+    pub async fn write_bundle_context(
         &self,
         price_timestamp: u64,
         assembled_timestamp: Option<u64>,
         ctx: &SponsoredExternalMatchResponseCtx,
+        volume_consumption: Option<VolumeConsumption>,
     ) -> Result<BundleId, AuthServerError> {
         // We use the gas sponsorship nonce as the bundle ID. This is a per-bundle
         // unique identifier that we can use to attribute settlement
@@ -28,7 +34,10 @@ impl Server {
         // Create bundle context
         let gas_sponsorship_info = ctx.sponsorship_info_with_nonce();
         let is_sponsored = gas_sponsorship_info.is_some();
+        let fee_rates = ctx.response().match_bundle.fee_rates;
+        let fee_rate = fee_rates.relayer_fee_rate.to_f64() + fee_rates.protocol_fee_rate.to_f64();
         let bundle_ctx = BundleContext {
+            key_id: ctx.key_id,
             key_description: ctx.user(),
             bundle_id,
             request_id: ctx.request_id.to_string(),
@@ -37,10 +46,18 @@ impl Server {
             is_sponsored,
             price_timestamp,
             assembled_timestamp,
+            fee_rate,
+            deadline: ctx.response().match_bundle.deadline,
+            volume_consumption,
         };
 
         // Write to bundle store
-        self.bundle_store.write(bundle_ctx);
+        if let Err(e) = self.bundle_store.write(&bundle_ctx).await {
+            if let Some(consumption) = &bundle_ctx.volume_consumption {
+                self.rate_limiter.refund_volume(consumption).await?;
+            }
+            return Err(e);
+        }
         Ok(bundle_id)
     }
 }
@@ -0,0 +1,169 @@
+//! Handlers for gas sponsorship policies
+
+use auth_server_api::sponsorship_policies::{
+    GetSponsorshipPoliciesResponse, RemoveSponsorshipPolicyRequest, SetSponsorshipPolicyRequest,
+    SponsorshipPolicy,
+};
+use bytes::Bytes;
+use http::HeaderMap;
+use tracing::instrument;
+use warp::{filters::path::FullPath, reject::Rejection, reply::Json};
+
+use super::Server;
+use super::volume_limits::normalize_asset;
+use crate::ApiError;
+use crate::error::AuthServerError;
+use crate::http_utils::request_response::empty_json_reply;
+use crate::server::chains::ServedTokens;
+use crate::server::db::models::NewSponsorshipPolicy;
+use crate::server::gas_sponsorship::policies::NATIVE_ETH_REFUND_ASSET;
+
+/// The error message emitted when a policy names neither a key nor an asset
+const ERR_UNSCOPED_POLICY: &str = "A sponsorship policy must name an API key, an asset, or both";
+/// The error message emitted when a policy's USD limits are invalid
+const ERR_INVALID_USD_LIMIT: &str =
+    "max_refund_usd, daily_budget_usd, and min_order_size_usd must be non-negative numbers";
+
+impl Server {
+    /// Get all configured sponsorship policies
+    #[instrument(skip_all)]
+    pub async fn get_sponsorship_policies(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+    ) -> Result<Json, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+
+        let policies = self
+            .get_all_sponsorship_policies()
+            .await?
+            .into_iter()
+            .map(SponsorshipPolicy::from)
+            .collect();
+        Ok(warp::reply::json(&GetSponsorshipPoliciesResponse { policies }))
+    }
+
+    /// Set a sponsorship policy, replacing any policy on the same scope
+    #[instrument(skip_all)]
+    pub async fn set_sponsorship_policy(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Deserialize and validate the request
+        let SetSponsorshipPolicyRequest { mut policy } =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        let tokens = &self.served_tokens;
+        policy.asset = policy
+            .asset
+            .as_deref()
+            .map(|asset| normalize_asset(asset, tokens, None /* chain */))
+            .transpose()?;
+        policy.allowed_refund_assets = policy
+            .allowed_refund_assets
+            .map(|assets| {
+                assets
+                    .iter()
+                    .map(|asset| normalize_refund_asset(asset, tokens))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        validate_sponsorship_policy(&policy)?;
+
+        self.set_sponsorship_policy_query(NewSponsorshipPolicy::from(&policy)).await?;
+        Ok(empty_json_reply())
+    }
+
+    /// Remove a sponsorship policy
+    #[instrument(skip_all)]
+    pub async fn remove_sponsorship_policy(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        let req: RemoveSponsorshipPolicyRequest =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        let asset = req
+            .asset
+            .as_deref()
+            .map(|asset| normalize_asset(asset, &self.served_tokens, None /* chain */))
+            .transpose()?;
+
+        self.remove_sponsorship_policy_query(req.api_key_id, asset).await?;
+        Ok(empty_json_reply())
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Normalize a refund asset ticker, checking that it is native ETH or a known
+/// token on a served chain
+fn normalize_refund_asset(asset: &str, tokens: &ServedTokens) -> Result<String, AuthServerError> {
+    if asset.eq_ignore_ascii_case(NATIVE_ETH_REFUND_ASSET) {
+        return Ok(NATIVE_ETH_REFUND_ASSET.to_string());
+    }
+
+    normalize_asset(asset, tokens, None /* chain */)
+}
+
+/// Validate a sponsorship policy
+fn validate_sponsorship_policy(policy: &SponsorshipPolicy) -> Result<(), AuthServerError> {
+    if policy.api_key_id.is_none() && policy.asset.is_none() {
+        return Err(AuthServerError::bad_request(ERR_UNSCOPED_POLICY));
+    }
+
+    let usd_limits = [policy.max_refund_usd, policy.daily_budget_usd, policy.min_order_size_usd];
+    if usd_limits.into_iter().flatten().any(|limit| !limit.is_finite() || limit < 0.) {
+        return Err(AuthServerError::bad_request(ERR_INVALID_USD_LIMIT));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn sponsorship_policies_must_be_scoped_and_non_negative() {
+        let mut policy = SponsorshipPolicy {
+            api_key_id: None,
+            asset: None,
+            enabled: true,
+            max_refund_usd: Some(1.),
+            daily_budget_usd: None,
+            min_order_size_usd: None,
+            allowed_refund_assets: None,
+        };
+        assert!(validate_sponsorship_policy(&policy).is_err());
+
+        policy.api_key_id = Some(Uuid::new_v4());
+        assert!(validate_sponsorship_policy(&policy).is_ok());
+
+        policy.daily_budget_usd = Some(-1.);
+        assert!(validate_sponsorship_policy(&policy).is_err());
+
+        policy.daily_budget_usd = None;
+        policy.min_order_size_usd = Some(f64::INFINITY);
+        assert!(validate_sponsorship_policy(&policy).is_err());
+    }
+
+    #[test]
+    fn native_eth_is_an_allowed_refund_asset() {
+        let tokens = ServedTokens::default();
+        assert_eq!(normalize_refund_asset("eth", &tokens).unwrap(), NATIVE_ETH_REFUND_ASSET);
+        assert!(normalize_refund_asset("WETH", &tokens).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Handlers for per-key usage
+//!
+//! Usage counters are incremented alongside the metrics for quotes, bundles,
+//! and settlements; the management endpoint here aggregates them for billing
+
+use auth_server_api::usage::{
+    ApiKeyUsage as UserFacingApiKeyUsage, DailyUsage, UsageCounters, UsageQuery, UsageResponse,
+};
+use bytes::Bytes;
+use chrono::{Datelike, NaiveDate, Utc};
+use http::HeaderMap;
+use tracing::instrument;
+use warp::{filters::path::FullPath, reject::Rejection, reply::Json};
+
+use super::Server;
+use crate::error::AuthServerError;
+use crate::server::db::models::ApiKeyUsage;
+
+/// The format of dates in usage queries and responses
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The error message emitted when a usage date cannot be parsed
+const ERR_INVALID_DATE: &str = "Invalid date, expected YYYY-MM-DD";
+/// The error message emitted when a usage date range is inverted
+const ERR_INVERTED_RANGE: &str = "start_date must not be after end_date";
+
+impl Server {
+    /// Get the usage of each API key over a date range, ranked by settled
+    /// volume
+    #[instrument(skip_all)]
+    pub async fn get_usage(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        query: UsageQuery,
+    ) -> Result<Json, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+
+        let today = Utc::now().date_naive();
+        let (start_date, end_date) = parse_date_range(&query, today)?;
+        let rows = self.get_usage_query(query.api_key_id, start_date, end_date).await?;
+
+        let resp = UsageResponse {
+            start_date: start_date.to_string(),
+            end_date: end_date.to_string(),
+            keys: aggregate_usage(rows),
+        };
+        Ok(warp::reply::json(&resp))
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Parse the inclusive date range of a usage query
+///
+/// Defaults to the current month to date
+fn parse_date_range(
+    query: &UsageQuery,
+    today: NaiveDate,
+) -> Result<(NaiveDate, NaiveDate), AuthServerError> {
+    let parse = |date: &str| {
+        NaiveDate::parse_from_str(date, DATE_FORMAT)
+            .map_err(|_| AuthServerError::bad_request(format!("{ERR_INVALID_DATE}: {date}")))
+    };
+
+    let start_of_month = today.with_day(1).unwrap_or(today);
+    let start_date = query.start_date.as_deref().map(parse).transpose()?.unwrap_or(start_of_month);
+    let end_date = query.end_date.as_deref().map(parse).transpose()?.unwrap_or(today);
+    if start_date > end_date {
+        return Err(AuthServerError::bad_request(ERR_INVERTED_RANGE));
+    }
+
+    Ok((start_date, end_date))
+}
+
+/// Aggregate daily usage rows into per-key usage, ranked by settled volume
+///
+/// Usage is summed across chains. Expects the rows to be ordered by key, then
+/// by day
+fn aggregate_usage(rows: Vec<(ApiKeyUsage, String)>) -> Vec<UserFacingApiKeyUsage> {
+    let mut keys: Vec<UserFacingApiKeyUsage> = Vec::new();
+    for (row, description) in rows {
+        let usage = UsageCounters::from(&row);
+        let date = row.day.to_string();
+        let daily = DailyUsage { date: date.clone(), usage: usage.clone() };
+
+        match keys.last_mut() {
+            Some(key) if key.api_key_id == row.api_key_id => {
+                key.totals.accumulate(&usage);
+                match key.daily.last_mut() {
+                    Some(day) if day.date == date => day.usage.accumulate(&usage),
+                    _ => key.daily.push(daily),
+                }
+            },
+            _ => keys.push(UserFacingApiKeyUsage {
+                api_key_id: row.api_key_id,
+                description,
+                totals: usage,
+                daily: vec![daily],
+            }),
+        }
+    }
+
+    keys.sort_by(|a, b| b.totals.settled_volume_usd.total_cmp(&a.totals.settled_volume_usd));
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// Build a usage row for the given key and day of October 2026
+    fn row(api_key_id: Uuid, day: u32, settled_volume_usd: f64) -> (ApiKeyUsage, String) {
+        let usage = ApiKeyUsage {
+            api_key_id,
+            chain_id: 42161,
+            day: NaiveDate::from_ymd_opt(2026, 10, day).unwrap(),
+            quote_count: 10,
+            bundle_count: 2,
+            settlement_count: 1,
+            settled_volume_usd,
+            fees_paid_usd: settled_volume_usd / 1000.,
+            gas_sponsored_usd: 0.,
+        };
+        (usage, api_key_id.to_string())
+    }
+
+    #[test]
+    fn date_range_defaults_to_month_to_date() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let (start, end) = parse_date_range(&UsageQuery::default(), today).unwrap();
+        assert_eq!(start.to_string(), "2026-10-01");
+        assert_eq!(end, today);
+
+        let query = UsageQuery {
+            start_date: Some("2026-09-01".to_string()),
+            end_date: Some("2026-09-30".to_string()),
+            ..Default::default()
+        };
+        let (start, end) = parse_date_range(&query, today).unwrap();
+        assert_eq!(
+            (start.to_string(), end.to_string()),
+            ("2026-09-01".into(), "2026-09-30".into())
+        );
+    }
+
+    #[test]
+    fn date_range_rejects_invalid_ranges() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let bad_date =
+            UsageQuery { start_date: Some("10/01/2026".to_string()), ..Default::default() };
+        assert!(parse_date_range(&bad_date, today).is_err());
+
+        let inverted =
+            UsageQuery { start_date: Some("2026-10-18".to_string()), ..Default::default() };
+        assert!(parse_date_range(&inverted, today).is_err());
+    }
+
+    #[test]
+    fn usage_is_summed_per_key_and_ranked_by_volume() {
+        let (small, large) = (Uuid::new_v4(), Uuid::new_v4());
+        let rows = vec![row(small, 1, 100.), row(small, 2, 200.), row(large, 1, 1000.)];
+
+        let keys = aggregate_usage(rows);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].api_key_id, large);
+        assert_eq!(keys[1].api_key_id, small);
+        assert_eq!(keys[1].daily.len(), 2);
+        assert_eq!(keys[1].totals.quote_count, 20);
+        assert_eq!(keys[1].totals.settled_volume_usd, 300.);
+    }
+
+    #[test]
+    fn usage_on_several_chains_is_summed_per_day() {
+        let key = Uuid::new_v4();
+        let (arbitrum, mut base) = (row(key, 1, 100.), row(key, 1, 50.));
+        base.0.chain_id = 8453;
+
+        let keys = aggregate_usage(vec![arbitrum, base, row(key, 2, 10.)]);
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].daily.len(), 2);
+        assert_eq!(keys[0].daily[0].usage.settled_volume_usd, 150.);
+        assert_eq!(keys[0].daily[0].usage.quote_count, 20);
+        assert_eq!(keys[0].totals.settled_volume_usd, 160.);
+    }
+}
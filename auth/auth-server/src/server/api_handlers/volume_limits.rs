@@ -0,0 +1,158 @@
+//! Handlers for notional volume limits
+
+use auth_server_api::volume_limits::{
+    GetVolumeLimitsResponse, RemoveVolumeLimitRequest, SetVolumeLimitRequest, VolumeLimit,
+};
+use bytes::Bytes;
+use http::HeaderMap;
+use renegade_types_core::Chain;
+use tracing::instrument;
+use warp::{filters::path::FullPath, reject::Rejection, reply::Json};
+
+use super::Server;
+use crate::ApiError;
+use crate::error::AuthServerError;
+use crate::http_utils::request_response::empty_json_reply;
+use crate::server::chains::ServedTokens;
+use crate::server::db::models::NewVolumeLimit;
+
+/// The error message emitted when a volume limit names neither a key nor an
+/// asset
+const ERR_UNSCOPED_LIMIT: &str = "A volume limit must name an API key, an asset, or both";
+/// The error message emitted when a volume limit's maximum is invalid
+const ERR_INVALID_MAX_VOLUME: &str = "max_volume_usd must be a non-negative number";
+/// The error message emitted when a volume limit's asset is not a known ticker
+const ERR_UNKNOWN_ASSET: &str = "Unknown asset";
+
+impl Server {
+    /// Get all configured volume limits
+    #[instrument(skip_all)]
+    pub async fn get_volume_limits(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+    ) -> Result<Json, Rejection> {
+        self.authorize_management_request(&path, &headers, &Bytes::new() /* body */)?;
+
+        let limits = self
+            .get_all_volume_limits()
+            .await?
+            .into_iter()
+            .map(|limit| VolumeLimit::try_from(limit).map_err(AuthServerError::custom))
+            .collect::<Result<_, _>>()?;
+        Ok(warp::reply::json(&GetVolumeLimitsResponse { limits }))
+    }
+
+    /// Set a volume limit, replacing any limit on the same scope and window
+    #[instrument(skip_all)]
+    pub async fn set_volume_limit(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        // Deserialize and validate the request
+        let SetVolumeLimitRequest { mut limit } =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        let chain = limit.chain_id.map(|id| self.served_tokens.chain(id)).transpose()?;
+        limit.asset = limit
+            .asset
+            .as_deref()
+            .map(|asset| normalize_asset(asset, &self.served_tokens, chain))
+            .transpose()?;
+        validate_volume_limit(&limit)?;
+
+        self.set_volume_limit_query(NewVolumeLimit::from(&limit)).await?;
+        Ok(empty_json_reply())
+    }
+
+    /// Remove a volume limit
+    #[instrument(skip_all)]
+    pub async fn remove_volume_limit(
+        &self,
+        path: FullPath,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<Json, Rejection> {
+        // Check management auth on the request
+        self.authorize_management_request(&path, &headers, &body)?;
+
+        let req: RemoveVolumeLimitRequest =
+            serde_json::from_slice(&body).map_err(ApiError::bad_request)?;
+        let asset = req
+            .asset
+            .as_deref()
+            .map(|asset| normalize_asset(asset, &self.served_tokens, None /* chain */))
+            .transpose()?;
+
+        let chain_id = req.chain_id.map(|id| id as i64);
+        self.remove_volume_limit_query(req.api_key_id, asset, chain_id, req.window.as_str())
+            .await?;
+        Ok(empty_json_reply())
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Normalize an asset ticker, checking that it is listed on the given chain,
+/// or on any served chain if none is given
+pub(super) fn normalize_asset(
+    asset: &str,
+    tokens: &ServedTokens,
+    chain: Option<Chain>,
+) -> Result<String, AuthServerError> {
+    let ticker = asset.to_uppercase();
+    if !tokens.is_listed(&ticker, chain) {
+        return Err(AuthServerError::bad_request(format!("{ERR_UNKNOWN_ASSET}: {asset}")));
+    }
+
+    Ok(ticker)
+}
+
+/// Validate a volume limit
+fn validate_volume_limit(limit: &VolumeLimit) -> Result<(), AuthServerError> {
+    if limit.api_key_id.is_none() && limit.asset.is_none() {
+        return Err(AuthServerError::bad_request(ERR_UNSCOPED_LIMIT));
+    }
+
+    if !limit.max_volume_usd.is_finite() || limit.max_volume_usd < 0. {
+        return Err(AuthServerError::bad_request(ERR_INVALID_MAX_VOLUME));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use auth_server_api::volume_limits::VolumeLimitWindow;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn volume_limits_must_be_scoped_and_non_negative() {
+        let mut limit = VolumeLimit {
+            api_key_id: None,
+            asset: None,
+            chain_id: None,
+            window: VolumeLimitWindow::Day,
+            max_volume_usd: 1_000_000.,
+        };
+        assert!(validate_volume_limit(&limit).is_err());
+
+        limit.asset = Some("WETH".to_string());
+        assert!(validate_volume_limit(&limit).is_ok());
+
+        limit.api_key_id = Some(Uuid::new_v4());
+        limit.max_volume_usd = -1.;
+        assert!(validate_volume_limit(&limit).is_err());
+
+        limit.max_volume_usd = f64::NAN;
+        assert!(validate_volume_limit(&limit).is_err());
+    }
+}
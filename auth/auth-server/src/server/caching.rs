@@ -1,41 +1,175 @@
 //! Caching helpers for the auth server
 
+use std::{
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
 use dashmap::DashMap;
+use renegade_types_core::Chain;
 use uuid::Uuid;
 
-use super::db::models::{ApiKey, RateLimitMethod};
+use super::db::models::{
+    ApiKey, ApiKeySecret, RateLimitMethod, RfqtLadder, SponsorshipPolicy, VolumeLimit,
+};
+
+/// The maximum time for which an API key and its previous secrets are cached
+const MAX_API_KEY_CACHE_TTL: Duration = Duration::from_secs(30);
 
 /// The API key cache type
-pub type ApiKeyCache = DashMap<Uuid, ApiKey>;
+///
+/// Maps from an API key id to the key
+pub type ApiKeyCache = TtlCache<Uuid, ApiKey>;
+/// The previous API key secrets cache type
+///
+/// Maps from an API key id to the key's previous secrets, including expired
+/// secrets that have not yet been pruned
+pub type PreviousSecretsCache = TtlCache<Uuid, Vec<ApiKeySecret>>;
+/// The time for which a user fee is cached
+///
+/// Fees depend on trailing volume and on promotions starting and ending, so
+/// they are refreshed periodically rather than only on management updates
+const USER_FEE_CACHE_TTL: Duration = Duration::from_secs(60);
+
 /// The user fee cache type
 ///
 /// Maps from (user_id, asset) to the fee rate for that asset
-pub type UserFeeCache = DashMap<(Uuid, String), f64>;
+pub type UserFeeCache = TtlCache<(Uuid, String), f64>;
 /// The rate limit cache type
 ///
 /// Maps from (api_key_id, method) to the rate limit (if configured)
 /// Stores `None` to indicate no custom rate limit is configured (negative
 /// cache)
 pub type RateLimitCache = DashMap<(Uuid, RateLimitMethod), Option<u32>>;
+/// The time for which the volume limits of a key are cached
+const VOLUME_LIMIT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The volume limit cache type
+///
+/// Maps from (api_key_id, asset, chain) to the volume limits that apply to the
+/// key trading the asset on the chain, including global limits on the asset
+pub type VolumeLimitCache = TtlCache<(Uuid, String, Chain), Vec<VolumeLimit>>;
+/// The time for which the RFQT ladder of a key is cached
+const RFQT_LADDER_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The RFQT ladder cache type
+///
+/// Maps from an API key id to the ladder that applies to the key, either the
+/// key's own ladder or the default ladder. Stores `None` to indicate that no
+/// ladder is configured (negative cache)
+pub type RfqtLadderCache = TtlCache<Uuid, Option<RfqtLadder>>;
+/// The time for which the sponsorship policies of a key are cached
+const SPONSORSHIP_POLICY_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The sponsorship policy cache type
+///
+/// Maps from (api_key_id, asset) to the sponsorship policies that apply to the
+/// key trading the asset, including policies on the asset for all keys
+pub type SponsorshipPolicyCache = TtlCache<(Uuid, String), Vec<SponsorshipPolicy>>;
+
+// -------------
+// | TTL Cache |
+// -------------
+
+/// A concurrent map whose entries expire a fixed time after they are cached
+///
+/// Management updates clear the cache of the server handling the request
+/// only, so the TTL bounds how long other servers serve a stale entry before
+/// picking up the change
+#[derive(Clone)]
+pub struct TtlCache<K: Eq + Hash, V> {
+    /// The time for which an entry is served after it is cached
+    ttl: Duration,
+    /// The cached entries and the times at which they were cached
+    entries: DashMap<K, (V, Instant)>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    /// Create an empty cache with the given TTL
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: DashMap::new() }
+    }
+
+    /// The time for which an entry is served after it is cached
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Get the entry for a key, ignoring entries older than the TTL
+    pub fn get(&self, key: &K) -> Option<V> {
+        let ptr = self.entries.get(key)?;
+        let (value, cached_at) = ptr.value();
+        (cached_at.elapsed() < self.ttl).then(|| value.clone())
+    }
+
+    /// Cache an entry for a key
+    pub fn insert(&self, key: K, value: V) {
+        self.entries.insert(key, (value, Instant::now()));
+    }
+
+    /// Update the entry for a key in place, if one is cached, without
+    /// extending its TTL
+    pub fn update(&self, key: &K, f: impl FnOnce(&mut V)) {
+        if let Some(mut entry) = self.entries.get_mut(key) {
+            f(&mut entry.value_mut().0);
+        }
+    }
+
+    /// Clear the entry for a key
+    pub fn remove(&self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    /// Clear the entries whose keys do not satisfy the predicate
+    pub fn retain(&self, mut f: impl FnMut(&K) -> bool) {
+        self.entries.retain(|key, _| f(key));
+    }
+
+    /// Clear all entries
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+// ----------------
+// | Server Cache |
+// ----------------
 
 /// The Server's data cache
 #[derive(Clone)]
 pub struct ServerCache {
     /// The API key cache
     pub api_key_cache: ApiKeyCache,
+    /// The previous API key secrets cache
+    pub previous_secrets_cache: PreviousSecretsCache,
     /// The user fee cache
     pub user_fee_cache: UserFeeCache,
     /// The rate limit cache
     pub rate_limit_cache: RateLimitCache,
+    /// The volume limit cache
+    pub volume_limit_cache: VolumeLimitCache,
+    /// The RFQT ladder cache
+    pub rfqt_ladder_cache: RfqtLadderCache,
+    /// The sponsorship policy cache
+    pub sponsorship_policy_cache: SponsorshipPolicyCache,
 }
 
 impl ServerCache {
     /// Constructor
-    pub fn new() -> Self {
+    ///
+    /// API keys are cached for no longer than the given secret rotation grace
+    /// period, so that a secret rotated out on another server is not accepted
+    /// as current past its grace period
+    pub fn new(secret_rotation_grace_period: Duration) -> Self {
+        let api_key_cache_ttl = secret_rotation_grace_period.min(MAX_API_KEY_CACHE_TTL);
         Self {
-            api_key_cache: DashMap::new(),
-            user_fee_cache: DashMap::new(),
+            api_key_cache: TtlCache::new(api_key_cache_ttl),
+            previous_secrets_cache: TtlCache::new(api_key_cache_ttl),
+            user_fee_cache: TtlCache::new(USER_FEE_CACHE_TTL),
             rate_limit_cache: DashMap::new(),
+            volume_limit_cache: TtlCache::new(VOLUME_LIMIT_CACHE_TTL),
+            rfqt_ladder_cache: TtlCache::new(RFQT_LADDER_CACHE_TTL),
+            sponsorship_policy_cache: TtlCache::new(SPONSORSHIP_POLICY_CACHE_TTL),
         }
     }
 
@@ -43,19 +177,21 @@ impl ServerCache {
 
     /// Check the cache for an API key
     pub fn get_api_key(&self, id: Uuid) -> Option<ApiKey> {
-        self.api_key_cache.get(&id).map(|ptr| ptr.value().clone())
+        self.api_key_cache.get(&id)
     }
 
     /// Cache an API key
+    ///
+    /// The key's previous secrets are cleared, as the key may have been
+    /// rotated since they were cached
     pub fn cache_api_key(&self, api_key: ApiKey) {
+        self.clear_previous_secrets(api_key.id);
         self.api_key_cache.insert(api_key.id, api_key);
     }
 
     /// Mark a cached API key as expired
     pub fn mark_key_expired(&self, id: Uuid) {
-        if let Some(mut key) = self.api_key_cache.get_mut(&id) {
-            key.value_mut().is_active = false;
-        }
+        self.api_key_cache.update(&id, |key| key.is_active = false);
     }
 
     /// Clear the cache entry for a given API key
@@ -63,11 +199,26 @@ impl ServerCache {
         self.api_key_cache.remove(&id);
     }
 
+    /// Check the cache for the previous secrets of an API key
+    pub fn get_previous_secrets(&self, id: Uuid) -> Option<Vec<ApiKeySecret>> {
+        self.previous_secrets_cache.get(&id)
+    }
+
+    /// Cache the previous secrets of an API key
+    pub fn cache_previous_secrets(&self, id: Uuid, secrets: Vec<ApiKeySecret>) {
+        self.previous_secrets_cache.insert(id, secrets);
+    }
+
+    /// Clear the cached previous secrets of an API key
+    pub fn clear_previous_secrets(&self, id: Uuid) {
+        self.previous_secrets_cache.remove(&id);
+    }
+
     // --- User Fee Cache --- //
 
     /// Check the cache for a user fee
     pub fn get_user_fee(&self, user_id: Uuid, asset: String) -> Option<f64> {
-        self.user_fee_cache.get(&(user_id, asset)).map(|ptr| *ptr.value())
+        self.user_fee_cache.get(&(user_id, asset))
     }
 
     /// Cache a user fee
@@ -82,7 +233,12 @@ impl ServerCache {
 
     /// Clear the cache entries for a given asset
     pub fn clear_asset_entries(&self, asset: &str) {
-        self.user_fee_cache.retain(|(_, asset_name), _| asset_name != asset);
+        self.user_fee_cache.retain(|(_, asset_name)| asset_name != asset);
+    }
+
+    /// Clear all user fee cache entries
+    pub fn clear_user_fees(&self) {
+        self.user_fee_cache.clear();
     }
 
     // --- Rate Limit Cache --- //
@@ -114,4 +270,161 @@ impl ServerCache {
     pub fn clear_rate_limit(&self, api_key_id: Uuid, method: RateLimitMethod) {
         self.rate_limit_cache.remove(&(api_key_id, method));
     }
+
+    // --- Volume Limit Cache --- //
+
+    /// Check the cache for the volume limits that apply to a key trading an
+    /// asset on a chain
+    pub fn get_volume_limits(
+        &self,
+        api_key_id: Uuid,
+        asset: &str,
+        chain: Chain,
+    ) -> Option<Vec<VolumeLimit>> {
+        self.volume_limit_cache.get(&(api_key_id, asset.to_string(), chain))
+    }
+
+    /// Cache the volume limits that apply to a key trading an asset on a chain
+    pub fn cache_volume_limits(
+        &self,
+        api_key_id: Uuid,
+        asset: String,
+        chain: Chain,
+        limits: Vec<VolumeLimit>,
+    ) {
+        self.volume_limit_cache.insert((api_key_id, asset, chain), limits);
+    }
+
+    /// Clear all cached volume limits
+    ///
+    /// Global limits apply to every key, so any change invalidates the whole
+    /// cache
+    pub fn clear_volume_limits(&self) {
+        self.volume_limit_cache.clear();
+    }
+
+    // --- RFQT Ladder Cache --- //
+
+    /// Check the cache for the ladder that applies to a key
+    ///
+    /// Returns:
+    /// - `None` if the ladder is not in the cache or has expired
+    /// - `Some(None)` if cached but no ladder is configured
+    /// - `Some(Some(ladder))` if a ladder is configured
+    pub fn get_rfqt_ladder(&self, api_key_id: Uuid) -> Option<Option<RfqtLadder>> {
+        self.rfqt_ladder_cache.get(&api_key_id)
+    }
+
+    /// Cache the ladder that applies to a key
+    pub fn cache_rfqt_ladder(&self, api_key_id: Uuid, ladder: Option<RfqtLadder>) {
+        self.rfqt_ladder_cache.insert(api_key_id, ladder);
+    }
+
+    /// Clear all cached RFQT ladders
+    ///
+    /// The default ladder applies to every key, so any change invalidates the
+    /// whole cache
+    pub fn clear_rfqt_ladders(&self) {
+        self.rfqt_ladder_cache.clear();
+    }
+
+    // --- Sponsorship Policy Cache --- //
+
+    /// Check the cache for the sponsorship policies that apply to a key trading
+    /// an asset
+    pub fn get_sponsorship_policies(
+        &self,
+        api_key_id: Uuid,
+        asset: &str,
+    ) -> Option<Vec<SponsorshipPolicy>> {
+        self.sponsorship_policy_cache.get(&(api_key_id, asset.to_string()))
+    }
+
+    /// Cache the sponsorship policies that apply to a key trading an asset
+    pub fn cache_sponsorship_policies(
+        &self,
+        api_key_id: Uuid,
+        asset: String,
+        policies: Vec<SponsorshipPolicy>,
+    ) {
+        self.sponsorship_policy_cache.insert((api_key_id, asset), policies);
+    }
+
+    /// Clear all cached sponsorship policies
+    ///
+    /// Asset policies apply to every key, so any change invalidates the whole
+    /// cache
+    pub fn clear_sponsorship_policies(&self) {
+        self.sponsorship_policy_cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    /// Build an active API key at the given secret generation
+    fn api_key(id: Uuid, secret_generation: i32) -> ApiKey {
+        ApiKey {
+            id,
+            encrypted_key: format!("gen-{secret_generation}"),
+            description: "key".to_string(),
+            created_at: SystemTime::now(),
+            is_active: true,
+            rate_limit_whitelisted: false,
+            scopes: None,
+            allowed_mints: None,
+            denied_mints: None,
+            expires_at: None,
+            allowed_ips: None,
+            secret_generation,
+            webhook_url: None,
+        }
+    }
+
+    #[test]
+    fn api_key_ttl_is_bounded_by_grace_period() {
+        let id = Uuid::new_v4();
+        let cache = ServerCache::new(Duration::from_secs(86_400));
+        assert_eq!(cache.api_key_cache.ttl(), MAX_API_KEY_CACHE_TTL);
+        cache.cache_api_key(api_key(id, 1));
+        assert!(cache.get_api_key(id).is_some());
+
+        // With no grace period, a rotated out secret must never be served from
+        // the cache
+        let cache = ServerCache::new(Duration::ZERO);
+        cache.cache_api_key(api_key(id, 1));
+        cache.cache_previous_secrets(id, Vec::new());
+        assert!(cache.get_api_key(id).is_none());
+        assert!(cache.get_previous_secrets(id).is_none());
+    }
+
+    #[test]
+    fn reloading_a_rotated_key_clears_its_previous_secrets() {
+        let id = Uuid::new_v4();
+        let cache = ServerCache::new(Duration::from_secs(60));
+        cache.cache_api_key(api_key(id, 1));
+        cache.cache_previous_secrets(id, Vec::new());
+        assert_eq!(cache.get_previous_secrets(id).map(|s| s.len()), Some(0));
+
+        // The key was rotated elsewhere, so the previous secrets cached for the
+        // old generation are missing the secret rotated out
+        cache.cache_api_key(api_key(id, 2));
+        assert_eq!(cache.get_api_key(id).map(|key| key.secret_generation), Some(2));
+        assert!(cache.get_previous_secrets(id).is_none());
+    }
+
+    #[test]
+    fn expired_entries_are_not_served() {
+        let cache = TtlCache::new(Duration::from_secs(60));
+        cache.insert(1, "fresh");
+        assert_eq!(cache.get(&1), Some("fresh"));
+
+        // An entry changed on another server is picked up once the entry expires
+        let cache = TtlCache::new(Duration::ZERO);
+        cache.insert(1, "stale");
+        assert_eq!(cache.get(&1), None);
+    }
 }
@@ -0,0 +1,291 @@
+//! Chain-scoped server state
+//!
+//! A single server process may serve several chains. Each chain has its own
+//! relayer, gas sponsor, gas cost sampler and on-chain event listener, while
+//! the database, caches, and rate limiters are shared between chains.
+//!
+//! Proxied requests select a chain with the chain ID header, falling back to
+//! the default chain when the header is absent. Management requests are not
+//! chain-scoped and are served by the default chain's server, so assets named
+//! on management requests are validated against the tokens of all served
+//! chains rather than the default chain's token list
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use alloy_primitives::Address;
+use renegade_types_core::{Chain, Token, USDC_TICKER, read_token_remaps};
+use renegade_util::hex::address_to_hex_string;
+use serde::Deserialize;
+
+use super::Server;
+use crate::error::AuthServerError;
+
+/// The header used to select the chain that a request is served on, given as
+/// a numeric chain ID
+pub const CHAIN_ID_HEADER: &str = "x-renegade-chain-id";
+
+/// All chains that the server may be configured to serve
+const SUPPORTED_CHAINS: [Chain; 7] = [
+    Chain::ArbitrumOne,
+    Chain::ArbitrumSepolia,
+    Chain::BaseMainnet,
+    Chain::BaseSepolia,
+    Chain::EthereumMainnet,
+    Chain::EthereumSepolia,
+    Chain::Devnet,
+];
+
+/// The error message emitted when the chain ID header is not a number
+const ERR_INVALID_CHAIN_ID: &str = "Invalid chain ID header";
+/// The error message emitted when a request selects a chain that is not served
+const ERR_UNSERVED_CHAIN: &str = "Chain not served";
+
+// -----------------
+// | Chain Configs |
+// -----------------
+
+/// The configuration of a single served chain
+///
+/// The default chain is configured from the CLI; additional chains are read
+/// from a JSON file holding a list of these configs
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChainConfig {
+    /// The numeric ID of the chain
+    pub chain_id: u64,
+    /// The URL of the relayer settling to the chain
+    pub relayer_url: String,
+    /// The RPC url of the chain
+    pub rpc_url: String,
+    /// The RPC websocket address to dial for on-chain events, if any
+    #[serde(default)]
+    pub eth_websocket_url: Option<String>,
+    /// The address of the darkpool contract
+    pub darkpool_address: String,
+    /// The address of the permit2 contract
+    pub permit2_address: String,
+    /// The address of the gas sponsor contract
+    pub gas_sponsor_address: String,
+    /// The auth private key used for gas sponsorship, as a hex string
+    pub gas_sponsor_auth_key: String,
+    /// The path to the token remap file for the chain, if not the default
+    #[serde(default)]
+    pub token_remap_file: Option<String>,
+}
+
+impl ChainConfig {
+    /// The chain that the config is for
+    pub fn chain(&self) -> Result<Chain, AuthServerError> {
+        chain_from_id(self.chain_id).ok_or_else(|| {
+            AuthServerError::setup(format!("Unsupported chain ID: {}", self.chain_id))
+        })
+    }
+}
+
+/// Read the configs of additional chains from a JSON file
+pub fn read_chain_configs(path: &str) -> Result<Vec<ChainConfig>, AuthServerError> {
+    let contents = std::fs::read_to_string(path).map_err(AuthServerError::setup)?;
+    serde_json::from_str(&contents).map_err(AuthServerError::setup)
+}
+
+// -----------------
+// | Chain Routing |
+// -----------------
+
+/// The chain-scoped servers, indexed by chain
+#[derive(Clone)]
+pub struct ChainServers {
+    /// The chain served when a request does not select one
+    default_chain: Chain,
+    /// The server for each served chain
+    servers: Arc<HashMap<Chain, Arc<Server>>>,
+}
+
+impl ChainServers {
+    /// Constructor
+    ///
+    /// The default chain must be one of the served chains
+    pub fn new(default_chain: Chain, servers: HashMap<Chain, Arc<Server>>) -> Self {
+        assert!(servers.contains_key(&default_chain), "default chain must be served");
+        Self { default_chain, servers: Arc::new(servers) }
+    }
+
+    /// The server for the default chain
+    pub fn default_server(&self) -> Arc<Server> {
+        self.servers[&self.default_chain].clone()
+    }
+
+    /// Select the server for a request from its chain ID header, if any
+    pub fn select(&self, chain_id_header: Option<&str>) -> Result<Arc<Server>, AuthServerError> {
+        let chain = select_chain(self.default_chain, chain_id_header, |chain| {
+            self.servers.contains_key(&chain)
+        })?;
+        Ok(self.servers[&chain].clone())
+    }
+}
+
+/// Select the chain a request is served on from its chain ID header, if any
+fn select_chain(
+    default_chain: Chain,
+    chain_id_header: Option<&str>,
+    is_served: impl Fn(Chain) -> bool,
+) -> Result<Chain, AuthServerError> {
+    let Some(header) = chain_id_header else {
+        return Ok(default_chain);
+    };
+
+    let chain_id: u64 =
+        header.trim().parse().map_err(|_| AuthServerError::bad_request(ERR_INVALID_CHAIN_ID))?;
+    served_chain_from_id(chain_id, is_served)
+}
+
+/// Get the served chain with the given numeric chain ID
+fn served_chain_from_id(
+    chain_id: u64,
+    is_served: impl Fn(Chain) -> bool,
+) -> Result<Chain, AuthServerError> {
+    chain_from_id(chain_id)
+        .filter(|chain| is_served(*chain))
+        .ok_or_else(|| AuthServerError::bad_request(format!("{ERR_UNSERVED_CHAIN}: {chain_id}")))
+}
+
+// -----------------
+// | Served Tokens |
+// -----------------
+
+/// The tokens listed on each served chain, indexed by ticker
+///
+/// The global token mapping only resolves tickers on the default chain, so
+/// requests that are not chain-scoped resolve tickers against this instead
+#[derive(Clone, Debug, Default)]
+pub struct ServedTokens {
+    /// The lowercase mint of each listed ticker, per served chain
+    mints: Arc<HashMap<Chain, HashMap<String, String>>>,
+}
+
+impl ServedTokens {
+    /// Constructor
+    pub fn new(mints: HashMap<Chain, HashMap<String, String>>) -> Self {
+        Self { mints: Arc::new(mints) }
+    }
+
+    /// Get the served chain with the given numeric chain ID
+    pub fn chain(&self, chain_id: u64) -> Result<Chain, AuthServerError> {
+        served_chain_from_id(chain_id, |chain| self.mints.contains_key(&chain))
+    }
+
+    /// Whether the ticker is listed on the given chain, or on any served chain
+    /// if none is given
+    pub fn is_listed(&self, ticker: &str, chain: Option<Chain>) -> bool {
+        match chain {
+            Some(chain) => self.mints.get(&chain).is_some_and(|mints| mints.contains_key(ticker)),
+            None => self.mints.values().any(|mints| mints.contains_key(ticker)),
+        }
+    }
+
+    /// The lowercase mints of the ticker on every served chain listing it
+    pub fn mints(&self, ticker: &str) -> Vec<String> {
+        self.mints.values().filter_map(|mints| mints.get(ticker)).cloned().collect()
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Convert a Chain enum to its numeric chain ID
+pub fn chain_to_chain_id(chain: Chain) -> u64 {
+    match chain {
+        Chain::ArbitrumOne => 42161,
+        Chain::ArbitrumSepolia => 421614,
+        Chain::BaseMainnet => 8453,
+        Chain::BaseSepolia => 84532,
+        Chain::EthereumMainnet => 1,
+        Chain::EthereumSepolia => 11155111,
+        Chain::Devnet => 0,
+    }
+}
+
+/// Get the chain with the given numeric chain ID
+pub fn chain_from_id(chain_id: u64) -> Option<Chain> {
+    SUPPORTED_CHAINS.into_iter().find(|chain| chain_to_chain_id(*chain) == chain_id)
+}
+
+/// Get the token at the given address on the given chain
+pub fn token_on_chain(addr: &Address, chain: Chain) -> Token {
+    Token::from_addr_on_chain(&address_to_hex_string(addr), chain)
+}
+
+/// Get USDC on the given chain
+pub fn usdc_on_chain(chain: Chain) -> Token {
+    Token::from_ticker_on_chain(USDC_TICKER, chain)
+}
+
+/// Get all tokens in the given chain's token mapping
+///
+/// Unlike `get_all_tokens`, this does not read the default chain's mapping,
+/// so the default chain need not be switched to list another chain's tokens
+pub fn tokens_on_chain(chain: Chain) -> Vec<Token> {
+    let remaps = read_token_remaps();
+    let Some(remap) = remaps.get(&chain) else {
+        return Vec::new();
+    };
+
+    remap.iter().map(|(addr, _)| Token::from_addr_on_chain(addr, chain)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_to_chain_id_covers_all_variants() {
+        assert_eq!(chain_to_chain_id(Chain::ArbitrumOne), 42161);
+        assert_eq!(chain_to_chain_id(Chain::ArbitrumSepolia), 421614);
+        assert_eq!(chain_to_chain_id(Chain::BaseMainnet), 8453);
+        assert_eq!(chain_to_chain_id(Chain::BaseSepolia), 84532);
+        assert_eq!(chain_to_chain_id(Chain::EthereumMainnet), 1);
+        assert_eq!(chain_to_chain_id(Chain::EthereumSepolia), 11155111);
+        assert_eq!(chain_to_chain_id(Chain::Devnet), 0);
+    }
+
+    #[test]
+    fn chain_is_selected_by_header() {
+        let served = |chain| matches!(chain, Chain::ArbitrumOne | Chain::BaseMainnet);
+        let select = |header| select_chain(Chain::ArbitrumOne, header, served);
+
+        assert_eq!(select(None).unwrap(), Chain::ArbitrumOne);
+        assert_eq!(select(Some("8453")).unwrap(), Chain::BaseMainnet);
+        assert_eq!(select(Some(" 42161 ")).unwrap(), Chain::ArbitrumOne);
+
+        // Malformed, unknown, and supported but unserved chains are rejected
+        for header in ["base", "10", "1"] {
+            assert!(matches!(select(Some(header)), Err(AuthServerError::BadRequest(_))));
+        }
+    }
+
+    #[test]
+    fn tickers_resolve_across_served_chains() {
+        let mints = |mint: &str| HashMap::from([("WETH".to_string(), mint.to_string())]);
+        let tokens = ServedTokens::new(HashMap::from([
+            (Chain::ArbitrumOne, mints("0xarb")),
+            (Chain::BaseMainnet, HashMap::new()),
+        ]));
+
+        assert!(tokens.is_listed("WETH", None));
+        assert!(tokens.is_listed("WETH", Some(Chain::ArbitrumOne)));
+        assert!(!tokens.is_listed("WETH", Some(Chain::BaseMainnet)));
+        assert!(!tokens.is_listed("DOGE", None));
+        assert_eq!(tokens.mints("WETH"), vec!["0xarb".to_string()]);
+        assert_eq!(tokens.chain(8453).unwrap(), Chain::BaseMainnet);
+        assert!(tokens.chain(1).is_err());
+    }
+
+    #[test]
+    fn chain_ids_round_trip() {
+        for chain in SUPPORTED_CHAINS {
+            assert_eq!(chain_from_id(chain_to_chain_id(chain)), Some(chain));
+        }
+        assert_eq!(chain_from_id(10), None);
+    }
+}
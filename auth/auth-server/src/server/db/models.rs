@@ -2,16 +2,34 @@
 #![allow(missing_docs, clippy::missing_docs_in_private_items)]
 #![allow(trivial_bounds)]
 
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use auth_server_api::{
-    fee_management::{AssetDefaultFeeEntry, UserAssetFeeEntry},
-    key_management::ApiKey as UserFacingApiKey,
+    audit_log::{AuditEventType, AuditLogEntry},
+    fee_management::{
+        AddPromotionalFeeRequest, AssetDefaultFeeEntry, FeeTier as UserFacingFeeTier,
+        PromotionalFee as UserFacingPromotionalFee, UserAssetFeeEntry,
+    },
+    key_management::{ApiKey as UserFacingApiKey, ApiKeyPermissions},
+    quote_analytics::QuoteFunnelStats,
+    rfqt_ladders::RfqtLadder as UserFacingRfqtLadder,
+    sponsorship_policies::SponsorshipPolicy as UserFacingSponsorshipPolicy,
+    usage::UsageCounters,
+    volume_limits::VolumeLimit as UserFacingVolumeLimit,
+    webhooks::{WebhookDelivery as UserFacingWebhookDelivery, WebhookDeliveryStatus},
 };
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::{NaiveDate, Utc};
 use diesel::prelude::*;
+use renegade_types_core::Chain;
 use uuid::Uuid;
 
-use crate::server::db::schema::{api_keys, asset_default_fees, rate_limits, user_fees};
+use crate::server::chains::chain_to_chain_id;
+use crate::server::db::schema::{
+    api_key_secrets, api_key_usage, api_keys, asset_default_fees, external_match_audit_log,
+    fee_tiers, promotional_fees, quote_lifecycle, rate_limits, rfqt_ladders, sponsorship_policies,
+    user_fees, volume_limits, webhook_deliveries,
+};
 
 #[derive(Queryable, Selectable, Clone)]
 #[diesel(table_name = api_keys)]
@@ -24,17 +42,49 @@ pub struct ApiKey {
     pub created_at: SystemTime,
     pub is_active: bool,
     pub rate_limit_whitelisted: bool,
+    pub scopes: Option<Vec<Option<String>>>,
+    pub allowed_mints: Option<Vec<Option<String>>>,
+    pub denied_mints: Option<Vec<Option<String>>>,
+    pub expires_at: Option<SystemTime>,
+    pub allowed_ips: Option<Vec<Option<String>>>,
+    pub secret_generation: i32,
+    pub webhook_url: Option<String>,
+}
+
+impl ApiKey {
+    /// Get the permissions attached to the key
+    ///
+    /// Scopes not recognized by this version of the server are dropped
+    pub fn permissions(&self) -> ApiKeyPermissions {
+        let scopes = from_db_list(self.scopes.clone())
+            .map(|scopes| scopes.iter().filter_map(|scope| scope.parse().ok()).collect());
+        let expires_at = self
+            .expires_at
+            .map(|t| t.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs());
+
+        ApiKeyPermissions {
+            scopes,
+            allowed_mints: from_db_list(self.allowed_mints.clone()),
+            denied_mints: from_db_list(self.denied_mints.clone()),
+            expires_at,
+            allowed_ips: from_db_list(self.allowed_ips.clone()),
+        }
+    }
 }
 
 impl From<ApiKey> for UserFacingApiKey {
     fn from(key: ApiKey) -> Self {
         let created_at = key.created_at.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        let permissions = key.permissions();
         Self {
             id: key.id,
             description: key.description,
             is_active: key.is_active,
             rate_limit_whitelisted: key.rate_limit_whitelisted,
             created_at: created_at.as_secs(),
+            permissions,
+            secret_generation: key.secret_generation as u32,
+            webhook_url: key.webhook_url,
         }
     }
 }
@@ -45,12 +95,38 @@ pub struct NewApiKey {
     pub id: Uuid,
     pub encrypted_key: String,
     pub description: String,
+    pub scopes: Option<Vec<Option<String>>>,
+    pub allowed_mints: Option<Vec<Option<String>>>,
+    pub denied_mints: Option<Vec<Option<String>>>,
+    pub expires_at: Option<SystemTime>,
+    pub allowed_ips: Option<Vec<Option<String>>>,
 }
 
 impl NewApiKey {
     /// Create a new API key
-    pub fn new(id: Uuid, encrypted_key: String, description: String) -> Self {
-        Self { id, encrypted_key, description }
+    pub fn new(
+        id: Uuid,
+        encrypted_key: String,
+        description: String,
+        permissions: &ApiKeyPermissions,
+    ) -> Self {
+        let ApiKeyPermissionsChangeset {
+            scopes,
+            allowed_mints,
+            denied_mints,
+            expires_at,
+            allowed_ips,
+        } = ApiKeyPermissionsChangeset::new(permissions);
+        Self {
+            id,
+            encrypted_key,
+            description,
+            scopes,
+            allowed_mints,
+            denied_mints,
+            expires_at,
+            allowed_ips,
+        }
     }
 }
 
@@ -63,10 +139,87 @@ impl From<NewApiKey> for ApiKey {
             created_at: SystemTime::now(),
             is_active: true,
             rate_limit_whitelisted: false,
+            scopes: key.scopes,
+            allowed_mints: key.allowed_mints,
+            denied_mints: key.denied_mints,
+            expires_at: key.expires_at,
+            allowed_ips: key.allowed_ips,
+            secret_generation: 0,
+            webhook_url: None,
+        }
+    }
+}
+
+/// A previous secret of an API key, accepted until it expires
+#[derive(Queryable, Selectable, Insertable, Clone)]
+#[diesel(table_name = api_key_secrets)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ApiKeySecret {
+    pub api_key_id: Uuid,
+    pub generation: i32,
+    pub encrypted_key: String,
+    pub expires_at: SystemTime,
+}
+
+impl ApiKeySecret {
+    /// Whether the secret is still accepted at the given time
+    pub fn is_valid_at(&self, time: SystemTime) -> bool {
+        self.expires_at > time
+    }
+}
+
+/// An update replacing the permissions of an API key
+///
+/// Unset restrictions are written as NULL rather than skipped, so that
+/// removing a restriction clears it
+#[derive(AsChangeset)]
+#[diesel(table_name = api_keys)]
+#[diesel(treat_none_as_null = true)]
+pub struct ApiKeyPermissionsChangeset {
+    pub scopes: Option<Vec<Option<String>>>,
+    pub allowed_mints: Option<Vec<Option<String>>>,
+    pub denied_mints: Option<Vec<Option<String>>>,
+    pub expires_at: Option<SystemTime>,
+    pub allowed_ips: Option<Vec<Option<String>>>,
+}
+
+impl ApiKeyPermissionsChangeset {
+    /// Create the changeset for the given permissions
+    ///
+    /// Mints are stored lowercased so that they compare equal regardless of
+    /// their checksum casing
+    pub fn new(permissions: &ApiKeyPermissions) -> Self {
+        let scopes = permissions
+            .scopes
+            .as_ref()
+            .map(|scopes| scopes.iter().map(|scope| scope.as_str().to_string()).collect());
+        let lowercase = |mints: &Option<Vec<String>>| {
+            mints.as_ref().map(|mints| mints.iter().map(|mint| mint.to_lowercase()).collect())
+        };
+
+        Self {
+            scopes: to_db_list(scopes),
+            allowed_mints: to_db_list(lowercase(&permissions.allowed_mints)),
+            denied_mints: to_db_list(lowercase(&permissions.denied_mints)),
+            expires_at: permissions
+                .expires_at
+                .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            allowed_ips: to_db_list(permissions.allowed_ips.clone()),
         }
     }
 }
 
+/// Convert a list into its nullable-element database representation
+fn to_db_list(list: Option<Vec<String>>) -> Option<Vec<Option<String>>> {
+    list.map(|list| list.into_iter().map(Some).collect())
+}
+
+/// Convert a nullable-element database list into a list, dropping null
+/// elements
+fn from_db_list(list: Option<Vec<Option<String>>>) -> Option<Vec<String>> {
+    list.map(|list| list.into_iter().flatten().collect())
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = user_fees)]
 pub struct NewUserFee {
@@ -145,6 +298,88 @@ pub struct FeeResult {
     pub fee: f32,
 }
 
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = fee_tiers)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct FeeTier {
+    pub asset: Option<String>,
+    pub min_volume_usd: f64,
+    pub fee: f32,
+}
+
+impl From<FeeTier> for UserFacingFeeTier {
+    fn from(tier: FeeTier) -> Self {
+        Self { asset: tier.asset, min_volume_usd: tier.min_volume_usd, fee: tier.fee }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = fee_tiers)]
+pub struct NewFeeTier {
+    pub asset: Option<String>,
+    pub min_volume_usd: f64,
+    pub fee: f32,
+}
+
+impl From<&UserFacingFeeTier> for NewFeeTier {
+    fn from(tier: &UserFacingFeeTier) -> Self {
+        Self { asset: tier.asset.clone(), min_volume_usd: tier.min_volume_usd, fee: tier.fee }
+    }
+}
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = promotional_fees)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PromotionalFee {
+    pub id: i32,
+    pub api_key_id: Option<Uuid>,
+    pub asset: Option<String>,
+    pub fee: f32,
+    pub starts_at: SystemTime,
+    pub ends_at: SystemTime,
+    pub description: String,
+}
+
+impl From<PromotionalFee> for UserFacingPromotionalFee {
+    fn from(promotion: PromotionalFee) -> Self {
+        let to_secs =
+            |t: SystemTime| t.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+        Self {
+            id: promotion.id,
+            api_key_id: promotion.api_key_id,
+            asset: promotion.asset,
+            fee: promotion.fee,
+            starts_at: to_secs(promotion.starts_at),
+            ends_at: to_secs(promotion.ends_at),
+            description: promotion.description,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = promotional_fees)]
+pub struct NewPromotionalFee {
+    pub api_key_id: Option<Uuid>,
+    pub asset: Option<String>,
+    pub fee: f32,
+    pub starts_at: SystemTime,
+    pub ends_at: SystemTime,
+    pub description: String,
+}
+
+impl From<AddPromotionalFeeRequest> for NewPromotionalFee {
+    fn from(req: AddPromotionalFeeRequest) -> Self {
+        Self {
+            api_key_id: req.api_key_id,
+            asset: req.asset,
+            fee: req.fee,
+            starts_at: SystemTime::UNIX_EPOCH + Duration::from_secs(req.starts_at),
+            ends_at: SystemTime::UNIX_EPOCH + Duration::from_secs(req.ends_at),
+            description: req.description,
+        }
+    }
+}
+
 /// Result of a rate limit query
 #[derive(diesel::QueryableByName)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -205,3 +440,667 @@ impl NewRateLimit {
         Self { api_key_id, method, requests_per_minute }
     }
 }
+
+/// An entry in the external match audit log
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = external_match_audit_log)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AuditEvent {
+    pub id: i64,
+    pub chain_id: i64,
+    pub event_type: String,
+    pub api_key_id: Option<Uuid>,
+    pub key_description: String,
+    pub request_id: String,
+    pub bundle_id: Option<String>,
+    pub base_mint: String,
+    pub input_mint: String,
+    pub output_mint: String,
+    pub input_amount: BigDecimal,
+    pub output_amount: BigDecimal,
+    pub price: Option<f64>,
+    pub price_timestamp: Option<SystemTime>,
+    pub is_sponsored: bool,
+    pub refund_amount: Option<BigDecimal>,
+    pub tx_hash: Option<String>,
+    pub assembly_delay_ms: Option<i64>,
+    pub settlement_delay_ms: Option<i64>,
+    pub created_at: SystemTime,
+}
+
+impl TryFrom<AuditEvent> for AuditLogEntry {
+    type Error = String;
+
+    fn try_from(event: AuditEvent) -> Result<Self, Self::Error> {
+        let to_amount = |amount: &BigDecimal| {
+            amount.to_u128().ok_or_else(|| format!("invalid audit log amount: {amount}"))
+        };
+
+        Ok(Self {
+            id: event.id,
+            chain_id: event.chain_id as u64,
+            event_type: event.event_type.parse()?,
+            api_key_id: event.api_key_id,
+            key_description: event.key_description,
+            request_id: event.request_id,
+            bundle_id: event.bundle_id,
+            base_mint: event.base_mint,
+            input_mint: event.input_mint,
+            output_mint: event.output_mint,
+            input_amount: to_amount(&event.input_amount)?,
+            output_amount: to_amount(&event.output_amount)?,
+            price: event.price,
+            price_timestamp: event.price_timestamp.map(system_time_to_millis),
+            is_sponsored: event.is_sponsored,
+            refund_amount: event.refund_amount.as_ref().map(to_amount).transpose()?,
+            tx_hash: event.tx_hash,
+            assembly_delay_ms: event.assembly_delay_ms.map(|ms| ms as u64),
+            settlement_delay_ms: event.settlement_delay_ms.map(|ms| ms as u64),
+            created_at: system_time_to_millis(event.created_at),
+        })
+    }
+}
+
+/// A new entry in the external match audit log
+///
+/// Amounts are from the external party's perspective
+#[derive(Insertable, Clone)]
+#[diesel(table_name = external_match_audit_log)]
+pub struct NewAuditEvent {
+    pub chain_id: i64,
+    pub event_type: String,
+    pub api_key_id: Option<Uuid>,
+    pub key_description: String,
+    pub request_id: String,
+    pub bundle_id: Option<String>,
+    pub base_mint: String,
+    pub input_mint: String,
+    pub output_mint: String,
+    pub input_amount: BigDecimal,
+    pub output_amount: BigDecimal,
+    pub price: Option<f64>,
+    pub price_timestamp: Option<SystemTime>,
+    pub is_sponsored: bool,
+    pub refund_amount: Option<BigDecimal>,
+    pub tx_hash: Option<String>,
+    pub assembly_delay_ms: Option<i64>,
+    pub settlement_delay_ms: Option<i64>,
+}
+
+impl NewAuditEvent {
+    /// Create a new audit log entry for the given match on the given chain
+    ///
+    /// Optional fields are unset and may be filled in with the builder methods
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        chain: Chain,
+        event_type: AuditEventType,
+        api_key_id: Option<Uuid>,
+        key_description: String,
+        request_id: String,
+        base_mint: String,
+        input_mint: String,
+        output_mint: String,
+        input_amount: u128,
+        output_amount: u128,
+    ) -> Self {
+        Self {
+            chain_id: chain_to_chain_id(chain) as i64,
+            event_type: event_type.as_str().to_string(),
+            api_key_id,
+            key_description,
+            request_id,
+            bundle_id: None,
+            base_mint,
+            input_mint,
+            output_mint,
+            input_amount: BigDecimal::from(input_amount),
+            output_amount: BigDecimal::from(output_amount),
+            price: None,
+            price_timestamp: None,
+            is_sponsored: false,
+            refund_amount: None,
+            tx_hash: None,
+            assembly_delay_ms: None,
+            settlement_delay_ms: None,
+        }
+    }
+
+    /// Set the bundle id
+    pub fn with_bundle_id(mut self, bundle_id: String) -> Self {
+        self.bundle_id = Some(bundle_id);
+        self
+    }
+
+    /// Set the price and the time it was sampled, in milliseconds since epoch
+    pub fn with_price(mut self, price: f64, price_timestamp_ms: u64) -> Self {
+        self.price = Some(price);
+        self.price_timestamp = Some(millis_to_system_time(price_timestamp_ms));
+        self
+    }
+
+    /// Set the gas sponsorship refund, if the match was sponsored
+    pub fn with_refund_amount(mut self, refund_amount: Option<u128>) -> Self {
+        self.is_sponsored = refund_amount.is_some();
+        self.refund_amount = refund_amount.map(BigDecimal::from);
+        self
+    }
+
+    /// Set the settlement transaction hash
+    pub fn with_tx_hash(mut self, tx_hash: String) -> Self {
+        self.tx_hash = Some(tx_hash);
+        self
+    }
+
+    /// Set the time between the price sample and assembly
+    pub fn with_assembly_delay_ms(mut self, delay_ms: Option<u64>) -> Self {
+        self.assembly_delay_ms = delay_ms.map(|ms| ms as i64);
+        self
+    }
+
+    /// Set the time between the price sample and settlement
+    pub fn with_settlement_delay_ms(mut self, delay_ms: u64) -> Self {
+        self.settlement_delay_ms = Some(delay_ms as i64);
+        self
+    }
+}
+
+/// A day of usage for an API key on a chain
+///
+/// Inserted as an increment, and summed into the existing row for the key,
+/// chain, and day if one exists
+#[derive(Queryable, Selectable, Insertable, Clone)]
+#[diesel(table_name = api_key_usage)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ApiKeyUsage {
+    pub api_key_id: Uuid,
+    pub chain_id: i64,
+    pub day: NaiveDate,
+    pub quote_count: i64,
+    pub bundle_count: i64,
+    pub settlement_count: i64,
+    pub settled_volume_usd: f64,
+    pub fees_paid_usd: f64,
+    pub gas_sponsored_usd: f64,
+}
+
+impl ApiKeyUsage {
+    /// Create an empty usage increment for the given key and chain on the
+    /// current day
+    fn today(api_key_id: Uuid, chain: Chain) -> Self {
+        Self {
+            api_key_id,
+            chain_id: chain_to_chain_id(chain) as i64,
+            day: Utc::now().date_naive(),
+            quote_count: 0,
+            bundle_count: 0,
+            settlement_count: 0,
+            settled_volume_usd: 0.,
+            fees_paid_usd: 0.,
+            gas_sponsored_usd: 0.,
+        }
+    }
+
+    /// A usage increment for a single quote
+    pub fn quote(api_key_id: Uuid, chain: Chain) -> Self {
+        Self { quote_count: 1, ..Self::today(api_key_id, chain) }
+    }
+
+    /// A usage increment for a single assembled bundle
+    pub fn bundle(api_key_id: Uuid, chain: Chain) -> Self {
+        Self { bundle_count: 1, ..Self::today(api_key_id, chain) }
+    }
+
+    /// A usage increment for a single settled bundle
+    pub fn settlement(
+        api_key_id: Uuid,
+        chain: Chain,
+        settled_volume_usd: f64,
+        fees_paid_usd: f64,
+    ) -> Self {
+        Self {
+            settlement_count: 1,
+            settled_volume_usd,
+            fees_paid_usd,
+            ..Self::today(api_key_id, chain)
+        }
+    }
+
+    /// A usage increment for gas sponsored on a settled bundle
+    pub fn gas_sponsorship(api_key_id: Uuid, chain: Chain, gas_sponsored_usd: f64) -> Self {
+        Self { gas_sponsored_usd, ..Self::today(api_key_id, chain) }
+    }
+}
+
+impl From<&ApiKeyUsage> for UsageCounters {
+    fn from(usage: &ApiKeyUsage) -> Self {
+        Self {
+            quote_count: usage.quote_count as u64,
+            bundle_count: usage.bundle_count as u64,
+            settlement_count: usage.settlement_count as u64,
+            settled_volume_usd: usage.settled_volume_usd,
+            fees_paid_usd: usage.fees_paid_usd,
+            gas_sponsored_usd: usage.gas_sponsored_usd,
+        }
+    }
+}
+
+/// An entry in the webhook delivery log
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = webhook_deliveries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub api_key_id: Uuid,
+    pub event_type: String,
+    pub bundle_id: String,
+    pub url: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+}
+
+impl TryFrom<WebhookDelivery> for UserFacingWebhookDelivery {
+    type Error = String;
+
+    fn try_from(delivery: WebhookDelivery) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: delivery.id,
+            event_type: delivery.event_type,
+            bundle_id: delivery.bundle_id,
+            url: delivery.url,
+            status: delivery.status.parse()?,
+            attempts: delivery.attempts as u32,
+            last_status_code: delivery.last_status_code.map(|code| code as u16),
+            last_error: delivery.last_error,
+            created_at: system_time_to_millis(delivery.created_at),
+            updated_at: system_time_to_millis(delivery.updated_at),
+        })
+    }
+}
+
+/// A new entry in the webhook delivery log
+///
+/// The stored payload is the serialized event; the delivered body wraps it with
+/// the delivery id once that is assigned
+#[derive(Insertable, Clone)]
+#[diesel(table_name = webhook_deliveries)]
+pub struct NewWebhookDelivery {
+    pub api_key_id: Uuid,
+    pub event_type: String,
+    pub bundle_id: String,
+    pub url: String,
+    pub payload: String,
+    pub status: String,
+    pub next_attempt_at: Option<SystemTime>,
+}
+
+impl NewWebhookDelivery {
+    /// Create a new pending delivery, first attempted at `next_attempt_at`
+    pub fn new(
+        api_key_id: Uuid,
+        event_type: &str,
+        bundle_id: &str,
+        url: String,
+        payload: String,
+        next_attempt_at: SystemTime,
+    ) -> Self {
+        Self {
+            api_key_id,
+            event_type: event_type.to_string(),
+            bundle_id: bundle_id.to_string(),
+            url,
+            payload,
+            status: WebhookDeliveryStatus::Pending.as_str().to_string(),
+            next_attempt_at: Some(next_attempt_at),
+        }
+    }
+}
+
+/// The result of a single webhook delivery attempt
+#[derive(AsChangeset)]
+#[diesel(table_name = webhook_deliveries)]
+#[diesel(treat_none_as_null = true)]
+pub struct WebhookAttemptChangeset {
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub updated_at: SystemTime,
+    pub next_attempt_at: Option<SystemTime>,
+}
+
+impl WebhookAttemptChangeset {
+    /// Record the result of the given attempt, scheduling the next attempt at
+    /// `next_attempt_at` if the delivery is still pending
+    pub fn new(
+        status: WebhookDeliveryStatus,
+        attempts: u32,
+        last_status_code: Option<u16>,
+        last_error: Option<String>,
+        next_attempt_at: Option<SystemTime>,
+    ) -> Self {
+        Self {
+            status: status.as_str().to_string(),
+            attempts: attempts as i32,
+            last_status_code: last_status_code.map(i32::from),
+            last_error,
+            updated_at: SystemTime::now(),
+            next_attempt_at,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = volume_limits)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct VolumeLimit {
+    pub api_key_id: Option<Uuid>,
+    pub asset: Option<String>,
+    pub chain_id: Option<i64>,
+    pub time_window: String,
+    pub max_volume_usd: f64,
+}
+
+impl TryFrom<VolumeLimit> for UserFacingVolumeLimit {
+    type Error = String;
+
+    fn try_from(limit: VolumeLimit) -> Result<Self, Self::Error> {
+        Ok(Self {
+            api_key_id: limit.api_key_id,
+            asset: limit.asset,
+            chain_id: limit.chain_id.map(|id| id as u64),
+            window: limit.time_window.parse()?,
+            max_volume_usd: limit.max_volume_usd,
+        })
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = volume_limits)]
+pub struct NewVolumeLimit {
+    pub api_key_id: Option<Uuid>,
+    pub asset: Option<String>,
+    pub chain_id: Option<i64>,
+    pub time_window: String,
+    pub max_volume_usd: f64,
+}
+
+impl From<&UserFacingVolumeLimit> for NewVolumeLimit {
+    fn from(limit: &UserFacingVolumeLimit) -> Self {
+        Self {
+            api_key_id: limit.api_key_id,
+            asset: limit.asset.clone(),
+            chain_id: limit.chain_id.map(|id| id as i64),
+            time_window: limit.window.as_str().to_string(),
+            max_volume_usd: limit.max_volume_usd,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = rfqt_ladders)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RfqtLadder {
+    pub api_key_id: Option<Uuid>,
+    pub size_fractions: Vec<Option<f64>>,
+    pub base_spread_bps: f64,
+    pub impact_bps: f64,
+    pub impact_exponent: f64,
+    pub skew_bps: f64,
+}
+
+impl From<RfqtLadder> for UserFacingRfqtLadder {
+    fn from(ladder: RfqtLadder) -> Self {
+        Self {
+            api_key_id: ladder.api_key_id,
+            size_fractions: ladder.size_fractions.into_iter().flatten().collect(),
+            base_spread_bps: ladder.base_spread_bps,
+            impact_bps: ladder.impact_bps,
+            impact_exponent: ladder.impact_exponent,
+            skew_bps: ladder.skew_bps,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = rfqt_ladders)]
+pub struct NewRfqtLadder {
+    pub api_key_id: Option<Uuid>,
+    pub size_fractions: Vec<Option<f64>>,
+    pub base_spread_bps: f64,
+    pub impact_bps: f64,
+    pub impact_exponent: f64,
+    pub skew_bps: f64,
+}
+
+impl From<&UserFacingRfqtLadder> for NewRfqtLadder {
+    fn from(ladder: &UserFacingRfqtLadder) -> Self {
+        Self {
+            api_key_id: ladder.api_key_id,
+            size_fractions: ladder.size_fractions.iter().copied().map(Some).collect(),
+            base_spread_bps: ladder.base_spread_bps,
+            impact_bps: ladder.impact_bps,
+            impact_exponent: ladder.impact_exponent,
+            skew_bps: ladder.skew_bps,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = sponsorship_policies)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SponsorshipPolicy {
+    pub api_key_id: Option<Uuid>,
+    pub asset: Option<String>,
+    pub enabled: bool,
+    pub max_refund_usd: Option<f64>,
+    pub daily_budget_usd: Option<f64>,
+    pub min_order_size_usd: Option<f64>,
+    pub allowed_refund_assets: Option<Vec<Option<String>>>,
+}
+
+impl From<SponsorshipPolicy> for UserFacingSponsorshipPolicy {
+    fn from(policy: SponsorshipPolicy) -> Self {
+        Self {
+            api_key_id: policy.api_key_id,
+            asset: policy.asset,
+            enabled: policy.enabled,
+            max_refund_usd: policy.max_refund_usd,
+            daily_budget_usd: policy.daily_budget_usd,
+            min_order_size_usd: policy.min_order_size_usd,
+            allowed_refund_assets: from_db_list(policy.allowed_refund_assets),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = sponsorship_policies)]
+pub struct NewSponsorshipPolicy {
+    pub api_key_id: Option<Uuid>,
+    pub asset: Option<String>,
+    pub enabled: bool,
+    pub max_refund_usd: Option<f64>,
+    pub daily_budget_usd: Option<f64>,
+    pub min_order_size_usd: Option<f64>,
+    pub allowed_refund_assets: Option<Vec<Option<String>>>,
+}
+
+impl From<&UserFacingSponsorshipPolicy> for NewSponsorshipPolicy {
+    fn from(policy: &UserFacingSponsorshipPolicy) -> Self {
+        Self {
+            api_key_id: policy.api_key_id,
+            asset: policy.asset.clone(),
+            enabled: policy.enabled,
+            max_refund_usd: policy.max_refund_usd,
+            daily_budget_usd: policy.daily_budget_usd,
+            min_order_size_usd: policy.min_order_size_usd,
+            allowed_refund_assets: to_db_list(policy.allowed_refund_assets.clone()),
+        }
+    }
+}
+
+/// The side recorded on a lifecycle entry when the external party buys the
+/// base asset
+pub const LIFECYCLE_SIDE_BUY: &str = "buy";
+/// The side recorded on a lifecycle entry when the external party sells the
+/// base asset
+pub const LIFECYCLE_SIDE_SELL: &str = "sell";
+
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name = quote_lifecycle)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct QuoteLifecycle {
+    pub id: Uuid,
+    pub api_key_id: Uuid,
+    pub chain_id: i64,
+    pub base_mint: String,
+    pub side: String,
+    pub quoted_price: Option<f64>,
+    pub quoted_at: Option<SystemTime>,
+    pub bundle_id: Option<String>,
+    pub assembled_at: Option<SystemTime>,
+    pub settled_price: Option<f64>,
+    pub settled_at: Option<SystemTime>,
+    pub quote_to_settlement_bps: Option<f64>,
+    pub markout_bps: Option<f64>,
+    pub created_at: SystemTime,
+    pub markout_due_at: Option<SystemTime>,
+    pub markout_attempts: i32,
+}
+
+impl QuoteLifecycle {
+    /// Whether the external party buys the base asset
+    pub fn external_buys(&self) -> bool {
+        self.side == LIFECYCLE_SIDE_BUY
+    }
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = quote_lifecycle)]
+pub struct NewQuoteLifecycle {
+    pub id: Uuid,
+    pub api_key_id: Uuid,
+    pub chain_id: i64,
+    pub base_mint: String,
+    pub side: String,
+    pub quoted_price: Option<f64>,
+    pub quoted_at: Option<SystemTime>,
+    pub bundle_id: Option<String>,
+    pub assembled_at: Option<SystemTime>,
+}
+
+impl NewQuoteLifecycle {
+    /// A lifecycle entry for a quote sent to a client
+    pub fn quote(
+        id: Uuid,
+        api_key_id: Uuid,
+        chain: Chain,
+        base_mint: String,
+        external_buys: bool,
+        quoted_price: f64,
+    ) -> Self {
+        Self {
+            quoted_price: Some(quoted_price),
+            quoted_at: Some(SystemTime::now()),
+            ..Self::empty(id, api_key_id, chain, base_mint, external_buys)
+        }
+    }
+
+    /// A lifecycle entry for a bundle assembled for a client
+    pub fn assembly(
+        id: Uuid,
+        api_key_id: Uuid,
+        chain: Chain,
+        base_mint: String,
+        external_buys: bool,
+        bundle_id: String,
+    ) -> Self {
+        Self {
+            bundle_id: Some(bundle_id),
+            assembled_at: Some(SystemTime::now()),
+            ..Self::empty(id, api_key_id, chain, base_mint, external_buys)
+        }
+    }
+
+    /// A lifecycle entry with no stages recorded
+    fn empty(
+        id: Uuid,
+        api_key_id: Uuid,
+        chain: Chain,
+        base_mint: String,
+        external_buys: bool,
+    ) -> Self {
+        let side = if external_buys { LIFECYCLE_SIDE_BUY } else { LIFECYCLE_SIDE_SELL };
+        Self {
+            id,
+            api_key_id,
+            chain_id: chain_to_chain_id(chain) as i64,
+            base_mint,
+            side: side.to_string(),
+            quoted_price: None,
+            quoted_at: None,
+            bundle_id: None,
+            assembled_at: None,
+        }
+    }
+}
+
+/// Result of a quote funnel query, aggregated per key and market
+#[derive(QueryableByName, Clone)]
+pub struct QuoteFunnelQueryResult {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub api_key_id: Uuid,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub key_description: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub chain_id: i64,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub base_mint: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub quote_count: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub quotes_assembled: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub assembled_count: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub settled_count: i64,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Double>)]
+    pub avg_quote_to_settlement_bps: Option<f64>,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Double>)]
+    pub avg_markout_bps: Option<f64>,
+}
+
+impl From<QuoteFunnelQueryResult> for QuoteFunnelStats {
+    fn from(res: QuoteFunnelQueryResult) -> Self {
+        let rate = |num: i64, denom: i64| (denom > 0).then(|| num as f64 / denom as f64);
+        Self {
+            api_key_id: res.api_key_id,
+            key_description: res.key_description,
+            chain_id: res.chain_id as u64,
+            base_mint: res.base_mint,
+            quote_count: res.quote_count as u64,
+            quotes_assembled: res.quotes_assembled as u64,
+            assembled_count: res.assembled_count as u64,
+            settled_count: res.settled_count as u64,
+            assembly_rate: rate(res.quotes_assembled, res.quote_count),
+            settlement_rate: rate(res.settled_count, res.assembled_count),
+            avg_quote_to_settlement_bps: res.avg_quote_to_settlement_bps,
+            avg_markout_bps: res.avg_markout_bps,
+        }
+    }
+}
+
+/// Convert a `SystemTime` to milliseconds since epoch
+pub fn system_time_to_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Convert milliseconds since epoch to a `SystemTime`
+pub fn millis_to_system_time(millis: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+}
@@ -1,24 +1,62 @@
 //! DB queries for the auth server
+//!
+//! Persisted work queues are claimed with a lease: the `claim_due_*` queries
+//! lock due rows with `FOR UPDATE SKIP LOCKED` and reschedule them `lease`
+//! into the future in the same transaction. Other replicas skip the claimed
+//! rows while they are processed, and the rows are resumed if the claiming
+//! replica exits before recording the result
 
-use diesel::{ExpressionMethods, QueryDsl};
-use diesel_async::RunQueryDsl;
+use std::time::{Duration, SystemTime};
+
+use auth_server_api::{
+    audit_log::AuditLogQuery, key_management::ApiKeyPermissions, webhooks::WebhookDeliveryStatus,
+};
+use chrono::{Days, NaiveDate, Utc};
+use diesel::{
+    ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper,
+    expression_methods::PgExpressionMethods, upsert::excluded,
+};
+use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+use renegade_types_core::Chain;
 use uuid::Uuid;
 
 use crate::{
     error::AuthServerError,
-    server::{Server, api_handlers::DEFAULT_RELAYER_FEE},
+    server::{Server, api_handlers::DEFAULT_RELAYER_FEE, chains::chain_to_chain_id},
 };
 
 use super::{
+    DbPool,
     models::{
-        ApiKey, AssetDefaultFee, FeeResult, NewApiKey, NewAssetDefaultFee, NewRateLimit,
-        NewUserFee, RateLimitMethod, RateLimitResult, UserAssetFeeQueryResult,
+        ApiKey, ApiKeyPermissionsChangeset, ApiKeySecret, ApiKeyUsage, AssetDefaultFee, AuditEvent,
+        FeeResult, FeeTier, NewApiKey, NewAssetDefaultFee, NewAuditEvent, NewFeeTier,
+        NewPromotionalFee, NewQuoteLifecycle, NewRateLimit, NewRfqtLadder, NewUserFee,
+        NewVolumeLimit, NewWebhookDelivery, PromotionalFee, QuoteFunnelQueryResult, QuoteLifecycle,
+        RateLimitMethod, RateLimitResult, RfqtLadder, UserAssetFeeQueryResult, VolumeLimit,
+        WebhookAttemptChangeset, WebhookDelivery, millis_to_system_time,
+    },
+    schema::{
+        api_key_secrets, api_key_usage, api_keys, asset_default_fees, external_match_audit_log,
+        fee_tiers, promotional_fees, quote_lifecycle, rfqt_ladders, sponsorship_policies,
+        user_fees, volume_limits, webhook_deliveries,
     },
-    schema::{api_keys, asset_default_fees, user_fees},
 };
 
 /// Error returned when a key is not found in the database
 const ERR_NO_KEY: &str = "API key not found";
+/// Error returned when a volume limit is not found in the database
+const ERR_NO_VOLUME_LIMIT: &str = "Volume limit not found";
+/// Error returned when an RFQT ladder is not found in the database
+const ERR_NO_RFQT_LADDER: &str = "RFQT ladder not found";
+/// Error returned when a sponsorship policy is not found in the database
+const ERR_NO_SPONSORSHIP_POLICY: &str = "Sponsorship policy not found";
+/// Error returned when a fee tier is not found in the database
+const ERR_NO_FEE_TIER: &str = "Fee tier not found";
+/// Error returned when a promotional fee is not found in the database
+const ERR_NO_PROMOTIONAL_FEE: &str = "Promotional fee not found";
+/// The number of days of settled volume, including today, that determine a
+/// key's fee tier
+const FEE_TIER_VOLUME_WINDOW_DAYS: u64 = 30;
 
 impl Server {
     // --- Getters --- //
@@ -61,6 +99,31 @@ impl Server {
         Ok(key)
     }
 
+    /// Get the unexpired previous secrets of an API key, newest first
+    pub async fn get_previous_secrets(
+        &self,
+        key_id: Uuid,
+    ) -> Result<Vec<ApiKeySecret>, AuthServerError> {
+        // Check the cache first
+        if let Some(secrets) = self.cache.get_previous_secrets(key_id) {
+            return Ok(secrets);
+        }
+
+        let mut conn = self.get_db_conn().await?;
+        let secrets = api_key_secrets::table
+            .filter(api_key_secrets::api_key_id.eq(key_id))
+            .filter(api_key_secrets::expires_at.gt(SystemTime::now()))
+            .order(api_key_secrets::generation.desc())
+            .load::<ApiKeySecret>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+        drop(conn); // Drop the connection to release the mutable borrow on `self`
+
+        // Cache the secrets and return
+        self.cache.cache_previous_secrets(key_id, secrets.clone());
+        Ok(secrets)
+    }
+
     // --- Setters --- //
 
     /// Add a new API key to the database
@@ -127,6 +190,135 @@ impl Server {
         Ok(())
     }
 
+    /// Rotate the secret of an API key
+    ///
+    /// The current secret is retained as a previous secret until
+    /// `previous_expires_at`, and expired previous secrets are pruned. If
+    /// `previous_expires_at` has already passed, the current secret is
+    /// discarded outright.
+    ///
+    /// Returns the generation of the new secret
+    pub async fn rotate_secret_query(
+        &self,
+        key_id: Uuid,
+        new_encrypted_key: String,
+        previous_expires_at: SystemTime,
+    ) -> Result<i32, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let res = conn
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move {
+                    // Lock the key row so that concurrent rotations serialize
+                    let key: ApiKey = api_keys::table
+                        .filter(api_keys::id.eq(key_id))
+                        .for_update()
+                        .first(conn)
+                        .await?;
+
+                    let now = SystemTime::now();
+                    diesel::delete(
+                        api_key_secrets::table
+                            .filter(api_key_secrets::api_key_id.eq(key_id))
+                            .filter(api_key_secrets::expires_at.le(now)),
+                    )
+                    .execute(conn)
+                    .await?;
+
+                    if previous_expires_at > now {
+                        let previous = ApiKeySecret {
+                            api_key_id: key_id,
+                            generation: key.secret_generation,
+                            encrypted_key: key.encrypted_key,
+                            expires_at: previous_expires_at,
+                        };
+                        diesel::insert_into(api_key_secrets::table)
+                            .values(&previous)
+                            .execute(conn)
+                            .await?;
+                    }
+
+                    let generation = key.secret_generation + 1;
+                    diesel::update(api_keys::table.filter(api_keys::id.eq(key_id)))
+                        .set((
+                            api_keys::encrypted_key.eq(new_encrypted_key),
+                            api_keys::secret_generation.eq(generation),
+                        ))
+                        .execute(conn)
+                        .await?;
+
+                    Ok(generation)
+                }
+                .scope_boxed()
+            })
+            .await;
+
+        match res {
+            Ok(generation) => Ok(generation),
+            Err(diesel::result::Error::NotFound) => Err(AuthServerError::bad_request(ERR_NO_KEY)),
+            Err(e) => Err(AuthServerError::db(e)),
+        }
+    }
+
+    /// Replace the permissions of an API key
+    pub async fn set_api_key_permissions_query(
+        &self,
+        key_id: Uuid,
+        permissions: &ApiKeyPermissions,
+    ) -> Result<(), AuthServerError> {
+        let changeset = ApiKeyPermissionsChangeset::new(permissions);
+        let mut conn = self.get_db_conn().await?;
+        let num_updates = diesel::update(api_keys::table.filter(api_keys::id.eq(key_id)))
+            .set(&changeset)
+            .execute(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+        drop(conn); // Drop the connection to release the mutable borrow on `self`
+
+        // Check that an update was made
+        if num_updates == 0 {
+            return Err(AuthServerError::bad_request(ERR_NO_KEY));
+        }
+        Ok(())
+    }
+
+    /// Set the webhook URL of an API key, or clear it if `None`
+    pub async fn set_webhook_url_query(
+        &self,
+        key_id: Uuid,
+        url: Option<String>,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let num_updates = diesel::update(api_keys::table.filter(api_keys::id.eq(key_id)))
+            .set(api_keys::webhook_url.eq(url))
+            .execute(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+        drop(conn); // Drop the connection to release the mutable borrow on `self`
+
+        // Check that an update was made
+        if num_updates == 0 {
+            return Err(AuthServerError::bad_request(ERR_NO_KEY));
+        }
+        Ok(())
+    }
+
+    /// Get the most recent webhook deliveries for an API key, newest first
+    pub async fn get_webhook_deliveries_query(
+        &self,
+        key_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        webhook_deliveries::table
+            .filter(webhook_deliveries::api_key_id.eq(key_id))
+            .order(webhook_deliveries::id.desc())
+            .limit(limit)
+            .select(WebhookDelivery::as_select())
+            .load::<WebhookDelivery>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+
     // -----------------------
     // | External Match Fees |
     // -----------------------
@@ -135,29 +327,53 @@ impl Server {
 
     /// Get the per-asset fee for a given user
     ///
-    /// If no override is set, this method returns the asset default fee. If
-    /// that value is not set the method returns zero
+    /// The fee is resolved as the user's override if set, otherwise the fee of
+    /// the highest tier the user's trailing settled volume qualifies for,
+    /// otherwise the asset default fee, and finally the default relayer fee.
+    /// Any active promotion for the user and asset caps the resolved fee
     pub async fn get_user_fee(&self, user_id: Uuid, asset: String) -> Result<f64, AuthServerError> {
         // Check the cache first
         if let Some(fee) = self.cache.get_user_fee(user_id, asset.clone()) {
             return Ok(fee);
         }
 
-        // Otherwise query the db; first for a user-specific fee, then for an
-        // asset-specific fee, and finally return zero if no fee is found
+        // Otherwise query the db; tiers prefer the asset's own tiers over tiers
+        // on all assets. `LEAST` ignores the promotional fee if none is active
         let mut conn = self.get_db_conn().await?;
         let query = "
-            SELECT COALESCE(
-                (SELECT fee FROM user_fees WHERE id = $1 AND asset = $2),
-                (SELECT fee FROM asset_default_fees WHERE asset = $2),
-                $3::float4
+            WITH trailing_volume AS (
+                SELECT COALESCE(SUM(settled_volume_usd), 0) AS volume_usd
+                FROM api_key_usage
+                WHERE api_key_id = $1 AND day >= $4
+            )
+            SELECT LEAST(
+                COALESCE(
+                    (SELECT fee FROM user_fees WHERE id = $1 AND asset = $2),
+                    (
+                        SELECT fee FROM fee_tiers, trailing_volume
+                        WHERE (asset = $2 OR asset IS NULL)
+                          AND min_volume_usd <= trailing_volume.volume_usd
+                        ORDER BY asset IS NULL, min_volume_usd DESC
+                        LIMIT 1
+                    ),
+                    (SELECT fee FROM asset_default_fees WHERE asset = $2),
+                    $3::float4
+                ),
+                (
+                    SELECT MIN(fee) FROM promotional_fees
+                    WHERE (api_key_id = $1 OR api_key_id IS NULL)
+                      AND (asset = $2 OR asset IS NULL)
+                      AND starts_at <= NOW() AND ends_at > NOW()
+                )
             ) as fee
         ";
 
+        let volume_start = fee_tier_volume_start(Utc::now().date_naive());
         let fee_res: FeeResult = diesel::sql_query(query)
             .bind::<diesel::sql_types::Uuid, _>(user_id)
             .bind::<diesel::sql_types::Text, _>(&asset)
             .bind::<diesel::sql_types::Float, _>(DEFAULT_RELAYER_FEE as f32)
+            .bind::<diesel::sql_types::Date, _>(volume_start)
             .load::<FeeResult>(&mut conn)
             .await
             .map_err(AuthServerError::db)?
@@ -188,8 +404,8 @@ impl Server {
             .await
             .map_err(AuthServerError::db)?;
 
-        // Cache the user fee
-        self.cache.cache_user_fee(new_user_fee.id, new_user_fee.asset, new_user_fee.fee as f64);
+        // Clear the cached fee, which may be capped by a promotion
+        self.cache.clear_user_fee(new_user_fee.id, new_user_fee.asset);
         Ok(())
     }
 
@@ -315,6 +531,116 @@ impl Server {
         Ok(())
     }
 
+    // --- Fee Tiers --- //
+
+    /// Get all fee tiers
+    pub async fn get_all_fee_tiers(&self) -> Result<Vec<FeeTier>, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        fee_tiers::table
+            .order((fee_tiers::asset, fee_tiers::min_volume_usd))
+            .select(FeeTier::as_select())
+            .load::<FeeTier>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+
+    /// Set a fee tier, replacing any tier on the same asset and threshold
+    pub async fn set_fee_tier_query(&self, tier: NewFeeTier) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::delete(
+                    fee_tiers::table
+                        .filter(fee_tiers::asset.is_not_distinct_from(tier.asset.clone()))
+                        .filter(fee_tiers::min_volume_usd.eq(tier.min_volume_usd)),
+                )
+                .execute(conn)
+                .await?;
+
+                diesel::insert_into(fee_tiers::table).values(&tier).execute(conn).await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(AuthServerError::db)?;
+
+        // Tiers may change the fee of any user
+        self.cache.clear_user_fees();
+        Ok(())
+    }
+
+    /// Remove a fee tier
+    pub async fn remove_fee_tier_query(
+        &self,
+        asset: Option<String>,
+        min_volume_usd: f64,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let num_deleted = diesel::delete(
+            fee_tiers::table
+                .filter(fee_tiers::asset.is_not_distinct_from(asset))
+                .filter(fee_tiers::min_volume_usd.eq(min_volume_usd)),
+        )
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+
+        if num_deleted == 0 {
+            return Err(AuthServerError::bad_request(ERR_NO_FEE_TIER));
+        }
+
+        self.cache.clear_user_fees();
+        Ok(())
+    }
+
+    // --- Promotional Fees --- //
+
+    /// Get all promotional fees, including those that have ended
+    pub async fn get_all_promotional_fees(&self) -> Result<Vec<PromotionalFee>, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        promotional_fees::table
+            .order(promotional_fees::starts_at.desc())
+            .select(PromotionalFee::as_select())
+            .load::<PromotionalFee>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+
+    /// Add a promotional fee, returning its ID
+    pub async fn add_promotional_fee_query(
+        &self,
+        promotion: NewPromotionalFee,
+    ) -> Result<i32, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let id = diesel::insert_into(promotional_fees::table)
+            .values(&promotion)
+            .returning(promotional_fees::id)
+            .get_result(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+
+        self.cache.clear_user_fees();
+        Ok(id)
+    }
+
+    /// Remove a promotional fee
+    pub async fn remove_promotional_fee_query(&self, id: i32) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let num_deleted =
+            diesel::delete(promotional_fees::table.filter(promotional_fees::id.eq(id)))
+                .execute(&mut conn)
+                .await
+                .map_err(AuthServerError::db)?;
+
+        if num_deleted == 0 {
+            return Err(AuthServerError::bad_request(ERR_NO_PROMOTIONAL_FEE));
+        }
+
+        self.cache.clear_user_fees();
+        Ok(())
+    }
+
     // ---------------
     // | Rate Limits |
     // ---------------
@@ -388,4 +714,784 @@ impl Server {
         self.cache.cache_rate_limit(api_key_id, method, Some(new_rate_limit.requests_per_minute));
         Ok(())
     }
+
+    // --- Volume Limits --- //
+
+    /// Get all configured volume limits
+    pub async fn get_all_volume_limits(&self) -> Result<Vec<VolumeLimit>, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        volume_limits::table
+            .order(volume_limits::id.asc())
+            .select(VolumeLimit::as_select())
+            .load::<VolumeLimit>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+
+    /// Get the volume limits that apply to a key trading an asset on the
+    /// server's chain
+    ///
+    /// This includes the key's limits on the asset, the key's limits across
+    /// all assets, and the global limits on the asset, each either on the
+    /// server's chain or across all chains
+    pub async fn get_applicable_volume_limits(
+        &self,
+        key_id: Uuid,
+        asset: &str,
+    ) -> Result<Vec<VolumeLimit>, AuthServerError> {
+        if let Some(cached) = self.cache.get_volume_limits(key_id, asset, self.chain) {
+            return Ok(cached);
+        }
+
+        let chain_id = chain_to_chain_id(self.chain) as i64;
+        let mut conn = self.get_db_conn().await?;
+        let limits = volume_limits::table
+            .filter(volume_limits::api_key_id.eq(key_id).or(volume_limits::api_key_id.is_null()))
+            .filter(volume_limits::asset.eq(asset).or(volume_limits::asset.is_null()))
+            .filter(volume_limits::chain_id.eq(chain_id).or(volume_limits::chain_id.is_null()))
+            .select(VolumeLimit::as_select())
+            .load::<VolumeLimit>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+        drop(conn); // Drop the connection to release the mutable borrow on `self`
+
+        self.cache.cache_volume_limits(key_id, asset.to_string(), self.chain, limits.clone());
+        Ok(limits)
+    }
+
+    /// Set a volume limit, replacing any limit on the same scope and window
+    pub async fn set_volume_limit_query(
+        &self,
+        limit: NewVolumeLimit,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::delete(
+                    volume_limits::table
+                        .filter(volume_limits::api_key_id.is_not_distinct_from(limit.api_key_id))
+                        .filter(volume_limits::asset.is_not_distinct_from(limit.asset.clone()))
+                        .filter(volume_limits::chain_id.is_not_distinct_from(limit.chain_id))
+                        .filter(volume_limits::time_window.eq(limit.time_window.clone())),
+                )
+                .execute(conn)
+                .await?;
+
+                diesel::insert_into(volume_limits::table).values(&limit).execute(conn).await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(AuthServerError::db)?;
+        drop(conn);
+
+        self.cache.clear_volume_limits();
+        Ok(())
+    }
+
+    /// Remove a volume limit
+    pub async fn remove_volume_limit_query(
+        &self,
+        api_key_id: Option<Uuid>,
+        asset: Option<String>,
+        chain_id: Option<i64>,
+        window: &str,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let num_deleted = diesel::delete(
+            volume_limits::table
+                .filter(volume_limits::api_key_id.is_not_distinct_from(api_key_id))
+                .filter(volume_limits::asset.is_not_distinct_from(asset))
+                .filter(volume_limits::chain_id.is_not_distinct_from(chain_id))
+                .filter(volume_limits::time_window.eq(window)),
+        )
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+        drop(conn);
+
+        self.cache.clear_volume_limits();
+        if num_deleted == 0 {
+            return Err(AuthServerError::bad_request(ERR_NO_VOLUME_LIMIT));
+        }
+        Ok(())
+    }
+
+    // ----------------
+    // | RFQT Ladders |
+    // ----------------
+
+    /// Get all configured RFQT ladders
+    pub async fn get_all_rfqt_ladders(&self) -> Result<Vec<RfqtLadder>, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        rfqt_ladders::table
+            .order(rfqt_ladders::id.asc())
+            .select(RfqtLadder::as_select())
+            .load::<RfqtLadder>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+
+    /// Get the RFQT ladder that applies to a key
+    ///
+    /// This is the key's own ladder if one is set, otherwise the default
+    /// ladder, if one is set
+    pub async fn get_applicable_rfqt_ladder(
+        &self,
+        key_id: Uuid,
+    ) -> Result<Option<RfqtLadder>, AuthServerError> {
+        if let Some(cached) = self.cache.get_rfqt_ladder(key_id) {
+            return Ok(cached);
+        }
+
+        let mut conn = self.get_db_conn().await?;
+        let ladders = rfqt_ladders::table
+            .filter(rfqt_ladders::api_key_id.eq(key_id).or(rfqt_ladders::api_key_id.is_null()))
+            .select(RfqtLadder::as_select())
+            .load::<RfqtLadder>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+        drop(conn); // Drop the connection to release the mutable borrow on `self`
+
+        // Prefer the key's own ladder over the default ladder
+        let ladder = ladders.into_iter().max_by_key(|ladder| ladder.api_key_id.is_some());
+        self.cache.cache_rfqt_ladder(key_id, ladder.clone());
+        Ok(ladder)
+    }
+
+    /// Set an RFQT ladder, replacing any ladder on the same key
+    pub async fn set_rfqt_ladder_query(
+        &self,
+        ladder: NewRfqtLadder,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let res = conn
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move {
+                    // Check the key exists, rather than surface a foreign key violation
+                    if let Some(key_id) = ladder.api_key_id {
+                        api_keys::table
+                            .filter(api_keys::id.eq(key_id))
+                            .select(api_keys::id)
+                            .first::<Uuid>(conn)
+                            .await?;
+                    }
+
+                    diesel::delete(
+                        rfqt_ladders::table.filter(
+                            rfqt_ladders::api_key_id.is_not_distinct_from(ladder.api_key_id),
+                        ),
+                    )
+                    .execute(conn)
+                    .await?;
+
+                    diesel::insert_into(rfqt_ladders::table).values(&ladder).execute(conn).await?;
+                    Ok(())
+                }
+                .scope_boxed()
+            })
+            .await;
+        drop(conn);
+
+        res.map_err(|e| match e {
+            diesel::result::Error::NotFound => AuthServerError::bad_request(ERR_NO_KEY),
+            e => AuthServerError::db(e),
+        })?;
+
+        self.cache.clear_rfqt_ladders();
+        Ok(())
+    }
+
+    /// Remove an RFQT ladder
+    pub async fn remove_rfqt_ladder_query(
+        &self,
+        api_key_id: Option<Uuid>,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let num_deleted = diesel::delete(
+            rfqt_ladders::table.filter(rfqt_ladders::api_key_id.is_not_distinct_from(api_key_id)),
+        )
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+        drop(conn);
+
+        self.cache.clear_rfqt_ladders();
+        if num_deleted == 0 {
+            return Err(AuthServerError::bad_request(ERR_NO_RFQT_LADDER));
+        }
+        Ok(())
+    }
+
+    // ------------------------
+    // | Sponsorship Policies |
+    // ------------------------
+
+    /// Get all configured sponsorship policies
+    pub async fn get_all_sponsorship_policies(
+        &self,
+    ) -> Result<Vec<SponsorshipPolicy>, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        sponsorship_policies::table
+            .order(sponsorship_policies::id.asc())
+            .select(SponsorshipPolicy::as_select())
+            .load::<SponsorshipPolicy>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+
+    /// Get the sponsorship policies that apply to a key trading an asset
+    ///
+    /// This includes the key's policy on the asset, the key's policy across all
+    /// assets, and the policy on the asset across all keys
+    pub async fn get_applicable_sponsorship_policies(
+        &self,
+        key_id: Uuid,
+        asset: &str,
+    ) -> Result<Vec<SponsorshipPolicy>, AuthServerError> {
+        if let Some(cached) = self.cache.get_sponsorship_policies(key_id, asset) {
+            return Ok(cached);
+        }
+
+        let mut conn = self.get_db_conn().await?;
+        let policies = sponsorship_policies::table
+            .filter(
+                sponsorship_policies::api_key_id
+                    .eq(key_id)
+                    .or(sponsorship_policies::api_key_id.is_null()),
+            )
+            .filter(sponsorship_policies::asset.eq(asset).or(sponsorship_policies::asset.is_null()))
+            .select(SponsorshipPolicy::as_select())
+            .load::<SponsorshipPolicy>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+        drop(conn); // Drop the connection to release the mutable borrow on `self`
+
+        self.cache.cache_sponsorship_policies(key_id, asset.to_string(), policies.clone());
+        Ok(policies)
+    }
+
+    /// Set a sponsorship policy, replacing any policy on the same scope
+    pub async fn set_sponsorship_policy_query(
+        &self,
+        policy: NewSponsorshipPolicy,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::delete(
+                    sponsorship_policies::table
+                        .filter(
+                            sponsorship_policies::api_key_id
+                                .is_not_distinct_from(policy.api_key_id),
+                        )
+                        .filter(
+                            sponsorship_policies::asset.is_not_distinct_from(policy.asset.clone()),
+                        ),
+                )
+                .execute(conn)
+                .await?;
+
+                diesel::insert_into(sponsorship_policies::table)
+                    .values(&policy)
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(AuthServerError::db)?;
+        drop(conn);
+
+        self.cache.clear_sponsorship_policies();
+        Ok(())
+    }
+
+    /// Remove a sponsorship policy
+    pub async fn remove_sponsorship_policy_query(
+        &self,
+        api_key_id: Option<Uuid>,
+        asset: Option<String>,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let num_deleted = diesel::delete(
+            sponsorship_policies::table
+                .filter(sponsorship_policies::api_key_id.is_not_distinct_from(api_key_id))
+                .filter(sponsorship_policies::asset.is_not_distinct_from(asset)),
+        )
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+        drop(conn);
+
+        self.cache.clear_sponsorship_policies();
+        if num_deleted == 0 {
+            return Err(AuthServerError::bad_request(ERR_NO_SPONSORSHIP_POLICY));
+        }
+        Ok(())
+    }
+
+    // -------------
+    // | Audit Log |
+    // -------------
+
+    /// Insert an entry into the external match audit log
+    pub async fn insert_audit_event_query(
+        &self,
+        event: &NewAuditEvent,
+    ) -> Result<(), AuthServerError> {
+        insert_audit_event(&self.db_pool, event).await
+    }
+
+    /// Get the external match audit log entries matching a query, newest first
+    ///
+    /// The query's asset filter is ignored in favor of the given base mints,
+    /// which must be lowercase mint addresses
+    pub async fn get_audit_events_query(
+        &self,
+        query: &AuditLogQuery,
+        base_mints: Option<&[String]>,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, AuthServerError> {
+        let mut db_query = external_match_audit_log::table.into_boxed();
+        if let Some(api_key_id) = query.api_key_id {
+            db_query = db_query.filter(external_match_audit_log::api_key_id.eq(api_key_id));
+        }
+        if let Some(base_mints) = base_mints {
+            db_query = db_query.filter(external_match_audit_log::base_mint.eq_any(base_mints));
+        }
+        if let Some(event_type) = query.event_type {
+            let event_type = event_type.as_str().to_string();
+            db_query = db_query.filter(external_match_audit_log::event_type.eq(event_type));
+        }
+        if let Some(start_time) = query.start_time {
+            let start_time = millis_to_system_time(start_time);
+            db_query = db_query.filter(external_match_audit_log::created_at.ge(start_time));
+        }
+        if let Some(end_time) = query.end_time {
+            let end_time = millis_to_system_time(end_time);
+            db_query = db_query.filter(external_match_audit_log::created_at.lt(end_time));
+        }
+
+        let mut conn = self.get_db_conn().await?;
+        db_query
+            .order(external_match_audit_log::created_at.desc())
+            .limit(limit)
+            .select(AuditEvent::as_select())
+            .load::<AuditEvent>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+
+    // ---------
+    // | Usage |
+    // ---------
+
+    /// Get the daily usage within an inclusive date range, along with the
+    /// description of each key
+    pub async fn get_usage_query(
+        &self,
+        api_key_id: Option<Uuid>,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<Vec<(ApiKeyUsage, String)>, AuthServerError> {
+        let mut db_query = api_key_usage::table
+            .inner_join(api_keys::table)
+            .filter(api_key_usage::day.ge(start_date))
+            .filter(api_key_usage::day.le(end_date))
+            .into_boxed();
+        if let Some(api_key_id) = api_key_id {
+            db_query = db_query.filter(api_key_usage::api_key_id.eq(api_key_id));
+        }
+
+        let mut conn = self.get_db_conn().await?;
+        db_query
+            .order((api_key_usage::api_key_id, api_key_usage::day, api_key_usage::chain_id))
+            .select((ApiKeyUsage::as_select(), api_keys::description))
+            .load::<(ApiKeyUsage, String)>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+
+    // -------------------
+    // | Quote Lifecycle |
+    // -------------------
+
+    /// Record a quote as the start of a lifecycle
+    pub async fn record_quote_lifecycle_query(
+        &self,
+        entry: &NewQuoteLifecycle,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        diesel::insert_into(quote_lifecycle::table)
+            .values(entry)
+            .on_conflict(quote_lifecycle::id)
+            .do_nothing()
+            .execute(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+
+        Ok(())
+    }
+
+    /// Record an assembly on a lifecycle, starting the lifecycle if its quote
+    /// was not recorded
+    ///
+    /// A quote assembled more than once is attributed to its latest bundle
+    pub async fn record_assembly_lifecycle_query(
+        &self,
+        entry: &NewQuoteLifecycle,
+    ) -> Result<(), AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        diesel::insert_into(quote_lifecycle::table)
+            .values(entry)
+            .on_conflict(quote_lifecycle::id)
+            .do_update()
+            .set((
+                quote_lifecycle::bundle_id.eq(excluded(quote_lifecycle::bundle_id)),
+                quote_lifecycle::assembled_at.eq(excluded(quote_lifecycle::assembled_at)),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(AuthServerError::db)?;
+
+        Ok(())
+    }
+
+    /// Aggregate the lifecycles started within a time range into funnel stats
+    /// per key and market, optionally only for markets on the given base mints
+    pub async fn get_quote_funnel_query(
+        &self,
+        api_key_id: Option<Uuid>,
+        base_mints: Option<Vec<String>>,
+        start_time: SystemTime,
+        end_time: SystemTime,
+    ) -> Result<Vec<QuoteFunnelQueryResult>, AuthServerError> {
+        let mut conn = self.get_db_conn().await?;
+        let query = "
+            SELECT
+                quote_lifecycle.api_key_id,
+                api_keys.description AS key_description,
+                quote_lifecycle.chain_id,
+                quote_lifecycle.base_mint,
+                COUNT(quoted_at) AS quote_count,
+                COUNT(quoted_at) FILTER (WHERE assembled_at IS NOT NULL) AS quotes_assembled,
+                COUNT(assembled_at) AS assembled_count,
+                COUNT(settled_at) AS settled_count,
+                AVG(quote_to_settlement_bps) AS avg_quote_to_settlement_bps,
+                AVG(markout_bps) AS avg_markout_bps
+            FROM quote_lifecycle
+            INNER JOIN api_keys ON api_keys.id = quote_lifecycle.api_key_id
+            WHERE quote_lifecycle.created_at >= $1
+              AND quote_lifecycle.created_at < $2
+              AND ($3::uuid IS NULL OR quote_lifecycle.api_key_id = $3)
+              AND ($4::text[] IS NULL OR quote_lifecycle.base_mint = ANY($4))
+            GROUP BY
+                quote_lifecycle.api_key_id,
+                api_keys.description,
+                quote_lifecycle.chain_id,
+                quote_lifecycle.base_mint
+            ORDER BY quote_count DESC, assembled_count DESC
+        ";
+
+        diesel::sql_query(query)
+            .bind::<diesel::sql_types::Timestamp, _>(start_time)
+            .bind::<diesel::sql_types::Timestamp, _>(end_time)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Uuid>, _>(api_key_id)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Array<diesel::sql_types::Text>>, _>(
+                base_mints,
+            )
+            .load::<QuoteFunnelQueryResult>(&mut conn)
+            .await
+            .map_err(AuthServerError::db)
+    }
+}
+
+/// Add a usage increment to the key's usage on a chain for the day
+///
+/// Increments are summed into the existing row for the key, chain, and day, so
+/// that concurrent writers from several replicas do not clobber each other
+pub async fn record_api_key_usage(
+    pool: &DbPool,
+    usage: &ApiKeyUsage,
+) -> Result<(), AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    diesel::insert_into(api_key_usage::table)
+        .values(usage)
+        .on_conflict((api_key_usage::api_key_id, api_key_usage::chain_id, api_key_usage::day))
+        .do_update()
+        .set((
+            api_key_usage::quote_count
+                .eq(api_key_usage::quote_count + excluded(api_key_usage::quote_count)),
+            api_key_usage::bundle_count
+                .eq(api_key_usage::bundle_count + excluded(api_key_usage::bundle_count)),
+            api_key_usage::settlement_count
+                .eq(api_key_usage::settlement_count + excluded(api_key_usage::settlement_count)),
+            api_key_usage::settled_volume_usd.eq(
+                api_key_usage::settled_volume_usd + excluded(api_key_usage::settled_volume_usd)
+            ),
+            api_key_usage::fees_paid_usd
+                .eq(api_key_usage::fees_paid_usd + excluded(api_key_usage::fees_paid_usd)),
+            api_key_usage::gas_sponsored_usd
+                .eq(api_key_usage::gas_sponsored_usd + excluded(api_key_usage::gas_sponsored_usd)),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+
+    Ok(())
+}
+
+/// Insert an entry into the external match audit log
+///
+/// Takes the pool directly so that components without a `Server` handle, e.g.
+/// the on-chain event listener, may record entries
+pub async fn insert_audit_event(
+    pool: &DbPool,
+    event: &NewAuditEvent,
+) -> Result<(), AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    diesel::insert_into(external_match_audit_log::table)
+        .values(event)
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+
+    Ok(())
+}
+
+// ------------
+// | Webhooks |
+// ------------
+
+/// Get the webhook URL and encrypted secret of an active API key
+///
+/// Returns `None` if the key has no webhook configured or is inactive
+pub async fn get_webhook_target(
+    pool: &DbPool,
+    key_id: Uuid,
+) -> Result<Option<(String, String)>, AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    let target: Option<(Option<String>, String)> = api_keys::table
+        .filter(api_keys::id.eq(key_id))
+        .filter(api_keys::is_active.eq(true))
+        .select((api_keys::webhook_url, api_keys::encrypted_key))
+        .first(&mut conn)
+        .await
+        .optional()
+        .map_err(AuthServerError::db)?;
+
+    Ok(target.and_then(|(url, encrypted_key)| url.map(|url| (url, encrypted_key))))
+}
+
+/// Insert a webhook delivery into the delivery log, returning its id
+pub async fn insert_webhook_delivery(
+    pool: &DbPool,
+    delivery: &NewWebhookDelivery,
+) -> Result<i64, AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    diesel::insert_into(webhook_deliveries::table)
+        .values(delivery)
+        .returning(webhook_deliveries::id)
+        .get_result(&mut conn)
+        .await
+        .map_err(AuthServerError::db)
+}
+
+/// Record the result of a webhook delivery attempt
+pub async fn update_webhook_delivery(
+    pool: &DbPool,
+    delivery_id: i64,
+    changeset: &WebhookAttemptChangeset,
+) -> Result<(), AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    diesel::update(webhook_deliveries::table.filter(webhook_deliveries::id.eq(delivery_id)))
+        .set(changeset)
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+
+    Ok(())
+}
+
+/// Claim up to `limit` pending webhook deliveries whose next attempt is due,
+/// leasing them as described in the module docs
+pub async fn claim_due_webhook_deliveries(
+    pool: &DbPool,
+    limit: i64,
+    lease: Duration,
+) -> Result<Vec<WebhookDelivery>, AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            let now = SystemTime::now();
+            let due: Vec<WebhookDelivery> = webhook_deliveries::table
+                .filter(webhook_deliveries::status.eq(WebhookDeliveryStatus::Pending.as_str()))
+                .filter(webhook_deliveries::next_attempt_at.le(now))
+                .order(webhook_deliveries::next_attempt_at.asc())
+                .limit(limit)
+                .select(WebhookDelivery::as_select())
+                .for_update()
+                .skip_locked()
+                .load(conn)
+                .await?;
+
+            let ids: Vec<i64> = due.iter().map(|delivery| delivery.id).collect();
+            diesel::update(webhook_deliveries::table.filter(webhook_deliveries::id.eq_any(ids)))
+                .set(webhook_deliveries::next_attempt_at.eq(now + lease))
+                .execute(conn)
+                .await?;
+
+            Ok(due)
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(AuthServerError::db)
+}
+
+// -------------------
+// | Quote Lifecycle |
+// -------------------
+
+/// Get the lifecycle entry of a bundle assembled on the given chain, if one
+/// was recorded
+pub async fn get_lifecycle_by_bundle(
+    pool: &DbPool,
+    chain: Chain,
+    bundle_id: &str,
+) -> Result<Option<QuoteLifecycle>, AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    quote_lifecycle::table
+        .filter(quote_lifecycle::chain_id.eq(chain_to_chain_id(chain) as i64))
+        .filter(quote_lifecycle::bundle_id.eq(bundle_id))
+        .select(QuoteLifecycle::as_select())
+        .first(&mut conn)
+        .await
+        .optional()
+        .map_err(AuthServerError::db)
+}
+
+/// Record the settlement of a lifecycle's bundle, scheduling its markout
+pub async fn record_lifecycle_settlement(
+    pool: &DbPool,
+    id: Uuid,
+    settled_price: f64,
+    settled_at: SystemTime,
+    quote_to_settlement_bps: Option<f64>,
+    markout_due_at: SystemTime,
+) -> Result<(), AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    diesel::update(quote_lifecycle::table.filter(quote_lifecycle::id.eq(id)))
+        .set((
+            quote_lifecycle::settled_price.eq(settled_price),
+            quote_lifecycle::settled_at.eq(settled_at),
+            quote_lifecycle::quote_to_settlement_bps.eq(quote_to_settlement_bps),
+            quote_lifecycle::markout_due_at.eq(markout_due_at),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+
+    Ok(())
+}
+
+/// Claim up to `limit` settled lifecycles on the given chain whose markout is
+/// due
+///
+/// Lifecycles are leased as described in the module docs, and each claim
+/// counts as an attempt at the markout
+pub async fn claim_due_lifecycle_markouts(
+    pool: &DbPool,
+    chain: Chain,
+    limit: i64,
+    lease: Duration,
+) -> Result<Vec<QuoteLifecycle>, AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            let now = SystemTime::now();
+            let due: Vec<QuoteLifecycle> = quote_lifecycle::table
+                .filter(quote_lifecycle::chain_id.eq(chain_to_chain_id(chain) as i64))
+                .filter(quote_lifecycle::settled_price.is_not_null())
+                .filter(quote_lifecycle::markout_due_at.le(now))
+                .order(quote_lifecycle::markout_due_at.asc())
+                .limit(limit)
+                .select(QuoteLifecycle::as_select())
+                .for_update()
+                .skip_locked()
+                .load(conn)
+                .await?;
+
+            let ids: Vec<Uuid> = due.iter().map(|lifecycle| lifecycle.id).collect();
+            diesel::update(quote_lifecycle::table.filter(quote_lifecycle::id.eq_any(ids)))
+                .set((
+                    quote_lifecycle::markout_due_at.eq(now + lease),
+                    quote_lifecycle::markout_attempts.eq(quote_lifecycle::markout_attempts + 1),
+                ))
+                .execute(conn)
+                .await?;
+
+            Ok(due)
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(AuthServerError::db)
+}
+
+/// Record the markout of a settled lifecycle, clearing its schedule
+pub async fn record_lifecycle_markout(
+    pool: &DbPool,
+    id: Uuid,
+    markout_bps: f64,
+) -> Result<(), AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    diesel::update(quote_lifecycle::table.filter(quote_lifecycle::id.eq(id)))
+        .set((
+            quote_lifecycle::markout_bps.eq(markout_bps),
+            quote_lifecycle::markout_due_at.eq(None::<SystemTime>),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+
+    Ok(())
+}
+
+/// Abandon the markout of a settled lifecycle, clearing its schedule and
+/// leaving its markout unset
+pub async fn abandon_lifecycle_markout(pool: &DbPool, id: Uuid) -> Result<(), AuthServerError> {
+    let mut conn = pool.get().await.map_err(AuthServerError::db)?;
+    diesel::update(quote_lifecycle::table.filter(quote_lifecycle::id.eq(id)))
+        .set(quote_lifecycle::markout_due_at.eq(None::<SystemTime>))
+        .execute(&mut conn)
+        .await
+        .map_err(AuthServerError::db)?;
+
+    Ok(())
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Get the first day of settled volume counted towards a key's fee tier
+fn fee_tier_volume_start(today: NaiveDate) -> NaiveDate {
+    today.checked_sub_days(Days::new(FEE_TIER_VOLUME_WINDOW_DAYS - 1)).unwrap_or(today)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_tier_volume_window_includes_today() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 15).unwrap();
+        let start = fee_tier_volume_start(today);
+        assert_eq!(start, NaiveDate::from_ymd_opt(2026, 2, 14).unwrap());
+        assert_eq!((today - start).num_days() + 1, FEE_TIER_VOLUME_WINDOW_DAYS as i64);
+    }
 }
@@ -14,6 +14,36 @@ diesel::table! {
         created_at -> Timestamp,
         is_active -> Bool,
         rate_limit_whitelisted -> Bool,
+        scopes -> Nullable<Array<Nullable<Text>>>,
+        allowed_mints -> Nullable<Array<Nullable<Text>>>,
+        denied_mints -> Nullable<Array<Nullable<Text>>>,
+        expires_at -> Nullable<Timestamp>,
+        allowed_ips -> Nullable<Array<Nullable<Text>>>,
+        secret_generation -> Int4,
+        webhook_url -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    api_key_secrets (api_key_id, generation) {
+        api_key_id -> Uuid,
+        generation -> Int4,
+        encrypted_key -> Varchar,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    api_key_usage (api_key_id, chain_id, day) {
+        api_key_id -> Uuid,
+        chain_id -> Int8,
+        day -> Date,
+        quote_count -> Int8,
+        bundle_count -> Int8,
+        settlement_count -> Int8,
+        settled_volume_usd -> Float8,
+        fees_paid_usd -> Float8,
+        gas_sponsored_usd -> Float8,
     }
 }
 
@@ -24,6 +54,73 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    external_match_audit_log (id) {
+        id -> Int8,
+        chain_id -> Int8,
+        event_type -> Varchar,
+        api_key_id -> Nullable<Uuid>,
+        key_description -> Varchar,
+        request_id -> Varchar,
+        bundle_id -> Nullable<Varchar>,
+        base_mint -> Varchar,
+        input_mint -> Varchar,
+        output_mint -> Varchar,
+        input_amount -> Numeric,
+        output_amount -> Numeric,
+        price -> Nullable<Float8>,
+        price_timestamp -> Nullable<Timestamp>,
+        is_sponsored -> Bool,
+        refund_amount -> Nullable<Numeric>,
+        tx_hash -> Nullable<Varchar>,
+        assembly_delay_ms -> Nullable<Int8>,
+        settlement_delay_ms -> Nullable<Int8>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    fee_tiers (id) {
+        id -> Int4,
+        asset -> Nullable<Varchar>,
+        min_volume_usd -> Float8,
+        fee -> Float4,
+    }
+}
+
+diesel::table! {
+    promotional_fees (id) {
+        id -> Int4,
+        api_key_id -> Nullable<Uuid>,
+        asset -> Nullable<Varchar>,
+        fee -> Float4,
+        starts_at -> Timestamp,
+        ends_at -> Timestamp,
+        description -> Varchar,
+    }
+}
+
+diesel::table! {
+    quote_lifecycle (id) {
+        id -> Uuid,
+        api_key_id -> Uuid,
+        chain_id -> Int8,
+        base_mint -> Varchar,
+        side -> Varchar,
+        quoted_price -> Nullable<Float8>,
+        quoted_at -> Nullable<Timestamp>,
+        bundle_id -> Nullable<Varchar>,
+        assembled_at -> Nullable<Timestamp>,
+        settled_price -> Nullable<Float8>,
+        settled_at -> Nullable<Timestamp>,
+        quote_to_settlement_bps -> Nullable<Float8>,
+        markout_bps -> Nullable<Float8>,
+        created_at -> Timestamp,
+        markout_due_at -> Nullable<Timestamp>,
+        markout_attempts -> Int4,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::RateLimitMethod;
@@ -35,6 +132,31 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    rfqt_ladders (id) {
+        id -> Int4,
+        api_key_id -> Nullable<Uuid>,
+        size_fractions -> Array<Nullable<Float8>>,
+        base_spread_bps -> Float8,
+        impact_bps -> Float8,
+        impact_exponent -> Float8,
+        skew_bps -> Float8,
+    }
+}
+
+diesel::table! {
+    sponsorship_policies (id) {
+        id -> Int4,
+        api_key_id -> Nullable<Uuid>,
+        asset -> Nullable<Varchar>,
+        enabled -> Bool,
+        max_refund_usd -> Nullable<Float8>,
+        daily_budget_usd -> Nullable<Float8>,
+        min_order_size_usd -> Nullable<Float8>,
+        allowed_refund_assets -> Nullable<Array<Nullable<Text>>>,
+    }
+}
+
 diesel::table! {
     user_fees (id, asset) {
         id -> Uuid,
@@ -43,7 +165,59 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    volume_limits (id) {
+        id -> Int4,
+        api_key_id -> Nullable<Uuid>,
+        asset -> Nullable<Varchar>,
+        chain_id -> Nullable<Int8>,
+        time_window -> Varchar,
+        max_volume_usd -> Float8,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Int8,
+        api_key_id -> Uuid,
+        event_type -> Varchar,
+        bundle_id -> Varchar,
+        url -> Varchar,
+        payload -> Text,
+        status -> Varchar,
+        attempts -> Int4,
+        last_status_code -> Nullable<Int4>,
+        last_error -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        next_attempt_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::joinable!(api_key_secrets -> api_keys (api_key_id));
+diesel::joinable!(api_key_usage -> api_keys (api_key_id));
+diesel::joinable!(promotional_fees -> api_keys (api_key_id));
+diesel::joinable!(quote_lifecycle -> api_keys (api_key_id));
 diesel::joinable!(rate_limits -> api_keys (api_key_id));
+diesel::joinable!(rfqt_ladders -> api_keys (api_key_id));
+diesel::joinable!(sponsorship_policies -> api_keys (api_key_id));
 diesel::joinable!(user_fees -> api_keys (id));
+diesel::joinable!(volume_limits -> api_keys (api_key_id));
+diesel::joinable!(webhook_deliveries -> api_keys (api_key_id));
 
-diesel::allow_tables_to_appear_in_same_query!(api_keys, asset_default_fees, rate_limits, user_fees,);
+diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    api_key_secrets,
+    api_key_usage,
+    asset_default_fees,
+    external_match_audit_log,
+    fee_tiers,
+    promotional_fees,
+    quote_lifecycle,
+    rate_limits,
+    rfqt_ladders,
+    sponsorship_policies,
+    user_fees,
+    volume_limits,
+    webhook_deliveries,
+);
@@ -9,6 +9,7 @@ use alloy::{
 };
 use rand::{RngCore, thread_rng};
 use renegade_system_clock::{SystemClock, SystemClockError};
+use renegade_types_core::Chain;
 use tokio::sync::RwLock;
 
 use crate::error::AuthServerError;
@@ -26,7 +27,9 @@ use super::{
 pub struct GasCostSampler {
     /// The latest estimate of the gas cost for an external match
     latest_estimate: Arc<RwLock<U256>>,
-    /// An Arbitrum RPC client
+    /// The chain on which gas costs are sampled
+    chain: Chain,
+    /// An RPC client for the chain
     client: DynProvider,
     /// The address of the gas sponsor contract
     gas_sponsor_address: Address,
@@ -35,12 +38,14 @@ pub struct GasCostSampler {
 impl GasCostSampler {
     /// Create a new gas cost sampler
     pub async fn new(
+        chain: Chain,
         client: DynProvider,
         gas_sponsor_address: Address,
         system_clock: &SystemClock,
     ) -> Result<Self, AuthServerError> {
         let this = Self {
             latest_estimate: Arc::new(RwLock::new(U256::ZERO)),
+            chain,
             client,
             gas_sponsor_address,
         };
@@ -53,7 +58,7 @@ impl GasCostSampler {
 
         system_clock
             .add_async_timer(
-                "gas-cost-sampler".to_string(),
+                format!("gas-cost-sampler-{chain}"),
                 GAS_COST_SAMPLING_INTERVAL,
                 move || {
                     let this_for_future = this_for_timer.clone();
@@ -84,8 +89,9 @@ impl GasCostSampler {
         let mut data = [0_u8; ESTIMATED_COMPRESSED_CALLDATA_SIZE_BYTES];
         thread_rng().fill_bytes(&mut data);
 
-        // Use the arbitrum gas oracle to estimate the L1 gas component
+        // Use the chain's gas oracle to estimate the L1 gas component
         let estimation = gas_oracles::estimate_l1_gas_component(
+            self.chain,
             self.client.clone(),
             self.gas_sponsor_address,
             data.to_vec(),
@@ -1,42 +1,16 @@
 //! Chain specific gas oracle contract methods
+//!
+//! The oracle is selected at runtime from the chain that a gas cost sampler
+//! is configured for, so that a single server may sample gas costs on several
+//! chains
+use alloy::primitives::Address;
+use alloy::providers::DynProvider;
 use alloy_primitives::U256;
+use renegade_types_core::Chain;
 
-#[cfg(feature = "arbitrum")]
 mod arbitrum;
-#[cfg(feature = "base")]
 mod base;
-
-#[cfg(feature = "arbitrum")]
-pub use arbitrum::estimate_l1_gas_component;
-#[cfg(feature = "base")]
-pub use base::estimate_l1_gas_component;
-
-#[cfg(feature = "ethereum")]
 mod ethereum;
-#[cfg(feature = "ethereum")]
-pub use ethereum::estimate_l1_gas_component;
-
-/// Fallback when no chain feature is enabled at compile time. Production
-/// builds must select a chain via `--features arbitrum|base|ethereum` (see
-/// `renegade-deploy-config.toml`). The fallback exists so workspace-level
-/// `cargo check` / `cargo clippy` / IDE analysis succeed without picking a
-/// chain. Calling it would only happen in a misconfigured build, so it
-/// returns a clear error.
-///
-/// `async` is preserved so the call-site `.await` typechecks across all
-/// feature flag combinations; `#[allow(clippy::unused_async)]` is needed
-/// because this default-feature variant has no `.await` of its own.
-#[cfg(not(any(feature = "arbitrum", feature = "base", feature = "ethereum")))]
-#[allow(clippy::unused_async)]
-pub async fn estimate_l1_gas_component(
-    _provider: alloy::providers::DynProvider,
-    _to: alloy::primitives::Address,
-    _data: Vec<u8>,
-) -> Result<GasPriceEstimation, String> {
-    Err("auth-server built without a chain feature; enable one of `arbitrum`, \
-         `base`, or `ethereum`"
-        .to_string())
-}
 
 /// Result of the gas price estimation
 pub struct GasPriceEstimation {
@@ -47,3 +21,25 @@ pub struct GasPriceEstimation {
     /// The L1 base fee estimate (per byte) in wei
     pub l1_data_fee: U256,
 }
+
+/// Estimate the L1 gas component for a transaction on the given chain
+///
+/// Devnet has no L1 data posting cost, so it is estimated as an L1
+pub async fn estimate_l1_gas_component(
+    chain: Chain,
+    provider: DynProvider,
+    to: Address,
+    data: Vec<u8>,
+) -> Result<GasPriceEstimation, String> {
+    match chain {
+        Chain::ArbitrumOne | Chain::ArbitrumSepolia => {
+            arbitrum::estimate_l1_gas_component(provider, to, data).await
+        },
+        Chain::BaseMainnet | Chain::BaseSepolia => {
+            base::estimate_l1_gas_component(provider, to, data).await
+        },
+        Chain::EthereumMainnet | Chain::EthereumSepolia | Chain::Devnet => {
+            ethereum::estimate_l1_gas_component(provider, to, data).await
+        },
+    }
+}
@@ -9,18 +9,21 @@ use auth_server_api::{
 };
 use price_reporter_client::error::PriceReporterClientError;
 
+use policies::{ResolvedSponsorshipPolicy, refund_asset_ticker, resolve_sponsorship_policy};
 use refund_calculation::{apply_gas_sponsorship_to_match_bundle, apply_gas_sponsorship_to_quote};
 use renegade_circuit_types::fixed_point::FixedPoint;
 use renegade_external_api::http::external_match::{ExternalMatchResponse, ExternalQuoteResponse};
 use renegade_external_api::types::{ApiTimestampedPriceFp, ExternalOrder};
-use renegade_types_core::Token;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use super::Server;
 use crate::error::AuthServerError;
-use crate::server::helpers::generate_quote_uuid;
+use crate::server::chains::usdc_on_chain;
+use crate::server::helpers::{base_asset_ticker, generate_quote_uuid};
 
 pub mod contract_interaction;
+pub mod policies;
 pub mod refund_calculation;
 
 // -------------
@@ -56,19 +59,31 @@ pub(crate) struct CachedSponsorshipInfo {
 /// Handle a proxied request
 impl Server {
     /// Generate gas sponsorship info for a given user's order, if permissible
-    /// according to the rate limit and query params
+    /// according to the key's sponsorship policy, rate limit, and query params
     pub(crate) async fn generate_sponsorship_info(
         &self,
+        key_id: Uuid,
         key_desc: &str,
         order: &ExternalOrder,
         query_params: &GasSponsorshipQueryParams,
     ) -> Result<GasSponsorshipInfo, AuthServerError> {
-        // Parse query params
+        // Parse query params, skipping the policy lookup if the client opted out
         let (sponsorship_disabled, refund_address, refund_native_eth) =
             query_params.get_or_default();
+        if sponsorship_disabled {
+            return Ok(GasSponsorshipInfo::zero());
+        }
+
+        // Resolve the sponsorship policy for the key and asset
+        let policy = self.get_sponsorship_policy(key_id, order).await?;
+        let refund_asset = refund_asset_ticker(order, refund_native_eth, self.chain);
+        if !policy.enabled || !policy.allows_refund_asset(&refund_asset) {
+            return Ok(GasSponsorshipInfo::zero());
+        }
 
         // Check gas sponsorship rate limit
-        let rate_limited = !self.check_gas_sponsorship_rate_limit(key_desc).await?;
+        let rate_limited =
+            !self.check_gas_sponsorship_rate_limit(key_desc, &policy.budgets).await?;
 
         let expected_quote_amount =
             match self.get_quote_amount(order, FixedPoint::zero() /* relayer_fee */).await {
@@ -79,19 +94,38 @@ impl Server {
                 Err(e) => return Err(e),
             };
 
-        let expected_quote_amount_f64 = Token::usdc().convert_to_decimal(expected_quote_amount);
-        let order_too_small = expected_quote_amount_f64 < self.min_sponsored_order_quote_amount;
+        let expected_quote_amount_f64 =
+            usdc_on_chain(self.chain).convert_to_decimal(expected_quote_amount);
+        let order_too_small = expected_quote_amount_f64 < policy.min_order_size_usd;
 
-        let sponsor_match = !(rate_limited || sponsorship_disabled || order_too_small);
+        let sponsor_match = !(rate_limited || order_too_small);
         if !sponsor_match {
             return Ok(GasSponsorshipInfo::zero());
         }
 
-        let refund_amount = self.compute_refund_amount_for_order(order, refund_native_eth).await?;
+        let refund_amount = self
+            .compute_refund_amount_for_order(order, refund_native_eth, policy.max_refund_usd)
+            .await?;
         GasSponsorshipInfo::new(refund_amount, refund_native_eth, refund_address)
             .map_err(AuthServerError::gas_sponsorship)
     }
 
+    /// Resolve the sponsorship policy that applies to a key's order
+    async fn get_sponsorship_policy(
+        &self,
+        key_id: Uuid,
+        order: &ExternalOrder,
+    ) -> Result<ResolvedSponsorshipPolicy, AuthServerError> {
+        let asset = base_asset_ticker(order.input_mint, order.output_mint, self.chain)?;
+        let configured = self.get_applicable_sponsorship_policies(key_id, &asset).await?;
+        Ok(resolve_sponsorship_policy(
+            key_id,
+            &asset,
+            &configured,
+            self.min_sponsored_order_quote_amount,
+        ))
+    }
+
     /// Construct a sponsored match response from an external match response
     pub(crate) fn construct_sponsored_match_response(
         &self,
@@ -0,0 +1,235 @@
+//! Resolution of gas sponsorship policies
+//!
+//! The policies that apply to a key trading an asset are merged into a single
+//! resolved policy. Sponsorship is disabled if any applicable policy disables
+//! it, and each limit is taken from the most specific policy that sets it,
+//! falling back to the server's defaults
+//!
+//! Daily budgets are tracked per scope. A key's budget is tracked under its
+//! ID, sharing its counter with the server's default per-key budget, while
+//! key-asset and asset budgets are tracked under their own scopes. All scopes
+//! are charged on settlement, so that a budget configured later counts the
+//! day's earlier sponsorship
+//!
+//! Key budgets were previously tracked under the key's description. Counters
+//! under the old scope are no longer read and lapse at the end of their
+//! window, so each key's budget starts afresh for the day of the upgrade
+
+use renegade_constants::NATIVE_ASSET_ADDRESS;
+use renegade_external_api::types::ExternalOrder;
+use renegade_types_core::Chain;
+use renegade_util::hex::address_to_hex_string;
+use uuid::Uuid;
+
+use crate::server::{chains::token_on_chain, db::models::SponsorshipPolicy};
+
+/// The refund asset of a native ETH refund, as named in a policy's allowed
+/// refund assets
+pub const NATIVE_ETH_REFUND_ASSET: &str = "ETH";
+
+// ---------
+// | Types |
+// ---------
+
+/// A daily sponsorship budget resolved for a single request
+#[derive(Clone, Debug, PartialEq)]
+pub struct SponsorshipBudget {
+    /// The scope the budget is tracked under
+    pub scope: String,
+    /// The maximum value sponsored per day in USD, or `None` for the server's
+    /// default budget
+    pub daily_budget_usd: Option<f64>,
+}
+
+/// The sponsorship policy resolved for a key trading an asset
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedSponsorshipPolicy {
+    /// Whether gas sponsorship is enabled
+    pub enabled: bool,
+    /// The maximum refund per bundle in USD, if any
+    pub max_refund_usd: Option<f64>,
+    /// The minimum order size for which gas is sponsored, in USD
+    pub min_order_size_usd: f64,
+    /// The tickers of the assets in which refunds may be paid, or `None` for
+    /// any asset
+    pub allowed_refund_assets: Option<Vec<String>>,
+    /// The daily budgets which must all have remaining value
+    pub budgets: Vec<SponsorshipBudget>,
+}
+
+impl ResolvedSponsorshipPolicy {
+    /// Whether refunds may be paid in the given asset
+    pub fn allows_refund_asset(&self, asset: &str) -> bool {
+        match &self.allowed_refund_assets {
+            Some(allowed) => allowed.iter().any(|a| a.eq_ignore_ascii_case(asset)),
+            None => true,
+        }
+    }
+}
+
+// --------------
+// | Resolution |
+// --------------
+
+/// Get the ticker of the asset in which an order's refund is paid
+///
+/// In-kind refunds are paid in the order's output token, falling back to its
+/// address for tokens without a ticker
+pub fn refund_asset_ticker(order: &ExternalOrder, refund_native_eth: bool, chain: Chain) -> String {
+    let output_mint = address_to_hex_string(&order.output_mint);
+    if refund_native_eth || output_mint == NATIVE_ASSET_ADDRESS.to_lowercase() {
+        return NATIVE_ETH_REFUND_ASSET.to_string();
+    }
+
+    let output_token = token_on_chain(&order.output_mint, chain);
+    output_token.get_ticker().unwrap_or(output_mint)
+}
+
+/// Get the scope that a key's budget across all assets is tracked under
+///
+/// Keyed by ID rather than description, so that keys sharing a description
+/// do not share a budget
+pub fn key_budget_scope(key_id: Uuid) -> String {
+    format!("key:{key_id}")
+}
+
+/// Get the scope that a key's budget on an asset is tracked under
+pub fn key_asset_budget_scope(key_id: Uuid, asset: &str) -> String {
+    format!("key:{key_id}:{asset}")
+}
+
+/// Get the scope that an asset's budget across all keys is tracked under
+pub fn asset_budget_scope(asset: &str) -> String {
+    format!("asset:{asset}")
+}
+
+/// Resolve the sponsorship policy for a key trading an asset from the
+/// configured policies that apply to it
+pub fn resolve_sponsorship_policy(
+    key_id: Uuid,
+    asset: &str,
+    configured: &[SponsorshipPolicy],
+    default_min_order_size_usd: f64,
+) -> ResolvedSponsorshipPolicy {
+    let find = |has_key: bool, has_asset: bool| {
+        configured.iter().find(|policy| {
+            policy.api_key_id.is_some() == has_key && policy.asset.is_some() == has_asset
+        })
+    };
+    let key_asset_policy = find(true, true);
+    let key_policy = find(true, false);
+    let asset_policy = find(false, true);
+
+    // The applicable policies, most specific first
+    let policies: Vec<&SponsorshipPolicy> =
+        [key_asset_policy, key_policy, asset_policy].into_iter().flatten().collect();
+    let first_set = |field: fn(&SponsorshipPolicy) -> Option<f64>| {
+        policies.iter().find_map(|policy| field(policy))
+    };
+
+    let enabled = policies.iter().all(|policy| policy.enabled);
+    let max_refund_usd = first_set(|policy| policy.max_refund_usd);
+    let min_order_size_usd =
+        first_set(|policy| policy.min_order_size_usd).unwrap_or(default_min_order_size_usd);
+    let allowed_refund_assets = policies.iter().find_map(|policy| {
+        policy.allowed_refund_assets.clone().map(|assets| assets.into_iter().flatten().collect())
+    });
+
+    // The key's budget always applies, the others only if configured
+    let mut budgets = vec![SponsorshipBudget {
+        scope: key_budget_scope(key_id),
+        daily_budget_usd: key_policy.and_then(|policy| policy.daily_budget_usd),
+    }];
+    if let Some(daily_budget_usd) = key_asset_policy.and_then(|policy| policy.daily_budget_usd) {
+        let scope = key_asset_budget_scope(key_id, asset);
+        budgets.push(SponsorshipBudget { scope, daily_budget_usd: Some(daily_budget_usd) });
+    }
+    if let Some(daily_budget_usd) = asset_policy.and_then(|policy| policy.daily_budget_usd) {
+        let scope = asset_budget_scope(asset);
+        budgets.push(SponsorshipBudget { scope, daily_budget_usd: Some(daily_budget_usd) });
+    }
+
+    ResolvedSponsorshipPolicy {
+        enabled,
+        max_refund_usd,
+        min_order_size_usd,
+        allowed_refund_assets,
+        budgets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a configured policy with no limits set
+    fn policy(api_key_id: Option<Uuid>, asset: Option<&str>) -> SponsorshipPolicy {
+        SponsorshipPolicy {
+            api_key_id,
+            asset: asset.map(str::to_string),
+            enabled: true,
+            max_refund_usd: None,
+            daily_budget_usd: None,
+            min_order_size_usd: None,
+            allowed_refund_assets: None,
+        }
+    }
+
+    #[test]
+    fn defaults_apply_without_policies() {
+        let key_id = Uuid::new_v4();
+        let resolved = resolve_sponsorship_policy(key_id, "WETH", &[], 10.);
+
+        assert!(resolved.enabled);
+        assert_eq!(resolved.max_refund_usd, None);
+        assert_eq!(resolved.min_order_size_usd, 10.);
+        assert!(resolved.allows_refund_asset("USDC"));
+        assert_eq!(
+            resolved.budgets,
+            vec![SponsorshipBudget { scope: key_budget_scope(key_id), daily_budget_usd: None }]
+        );
+    }
+
+    #[test]
+    fn most_specific_limit_wins_and_any_disable_wins() {
+        let key_id = Uuid::new_v4();
+
+        let mut key_asset = policy(Some(key_id), Some("WETH"));
+        key_asset.max_refund_usd = Some(1.);
+
+        let mut key = policy(Some(key_id), None);
+        key.max_refund_usd = Some(5.);
+        key.min_order_size_usd = Some(100.);
+        key.daily_budget_usd = Some(50.);
+        key.allowed_refund_assets = Some(vec![Some("ETH".to_string())]);
+
+        let mut asset = policy(None, Some("WETH"));
+        asset.min_order_size_usd = Some(1_000.);
+        asset.daily_budget_usd = Some(500.);
+
+        let configured = vec![asset.clone(), key, key_asset];
+        let resolved = resolve_sponsorship_policy(key_id, "WETH", &configured, 10.);
+
+        assert!(resolved.enabled);
+        assert_eq!(resolved.max_refund_usd, Some(1.));
+        assert_eq!(resolved.min_order_size_usd, 100.);
+        assert!(resolved.allows_refund_asset("eth"));
+        assert!(!resolved.allows_refund_asset("USDC"));
+        assert_eq!(
+            resolved.budgets,
+            vec![
+                SponsorshipBudget { scope: key_budget_scope(key_id), daily_budget_usd: Some(50.) },
+                SponsorshipBudget {
+                    scope: asset_budget_scope("WETH"),
+                    daily_budget_usd: Some(500.)
+                },
+            ]
+        );
+
+        // Disabling the asset disables sponsorship for every key
+        let mut disabled_asset = asset;
+        disabled_asset.enabled = false;
+        let resolved = resolve_sponsorship_policy(key_id, "WETH", &[disabled_asset], 10.);
+        assert!(!resolved.enabled);
+    }
+}
@@ -25,6 +25,8 @@ use crate::{error::AuthServerError, server::Server};
 /// The number of Wei in 1 ETH, as an `U256`.
 /// Concretely, this is 10^18
 const ALLOY_WEI_IN_ETHER: U256 = U256::from_limbs([1_000_000_000_000_000_000_u64, 0, 0, 0]);
+/// The number of Wei in 1 ETH, as an `f64`
+const WEI_IN_ETHER_F64: f64 = 1e18;
 
 /// The error message emitted when converting an f64 price to a `BigDecimal`
 /// fails
@@ -36,15 +38,25 @@ const ERR_PRICE_BIGDECIMAL_CONVERSION: &str = "failed to convert price to BigDec
 
 impl Server {
     /// Get the amount to refund for a given match result
+    ///
+    /// The refund covers the estimated gas cost, capped at the given USD value
+    /// if any
     pub async fn compute_refund_amount_for_order(
         &self,
         order: &ExternalOrder,
         refund_native_eth: bool,
+        max_refund_usd: Option<f64>,
     ) -> Result<U256, AuthServerError> {
         let conversion_rate =
             self.compute_conversion_rate_for_order(order, refund_native_eth).await?;
 
-        let estimated_gas_cost = self.get_gas_cost_estimate().await;
+        let mut estimated_gas_cost = self.get_gas_cost_estimate().await;
+        if let Some(max_refund_usd) = max_refund_usd {
+            let eth_price = self.price_reporter_client.get_eth_price().await?;
+            estimated_gas_cost =
+                estimated_gas_cost.min(max_gas_cost_wei(max_refund_usd, eth_price));
+        }
+
         let refund_amount = (estimated_gas_cost * conversion_rate) / ALLOY_WEI_IN_ETHER;
         Ok(refund_amount)
     }
@@ -59,7 +71,7 @@ impl Server {
         let buy_mint = address_to_hex_string(&order.output_mint);
         let native_eth_buy = buy_mint == NATIVE_ASSET_ADDRESS.to_lowercase();
 
-        let weth_addr = Token::from_ticker(WETH_TICKER).get_addr();
+        let weth_addr = Token::from_ticker_on_chain(WETH_TICKER, self.chain).get_addr();
         let weth_buy = buy_mint == weth_addr;
 
         if refund_native_eth || native_eth_buy || weth_buy {
@@ -96,6 +108,13 @@ impl Server {
 // | Helpers |
 // -----------
 
+/// The gas cost in wei worth the given USD value at the given ETH price
+///
+/// The cost saturates rather than overflowing for extreme values
+fn max_gas_cost_wei(max_value_usd: f64, eth_price: f64) -> U256 {
+    U256::from((max_value_usd / eth_price * WEI_IN_ETHER_F64) as u128)
+}
+
 /// Revert the effect of gas sponsorship from the given quote
 ///
 /// The `cached_info` contains both the gas sponsorship info and the original
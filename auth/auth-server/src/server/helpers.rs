@@ -8,10 +8,11 @@ use rand::thread_rng;
 use renegade_circuit_types::Amount;
 use renegade_external_api::types::ApiSignedQuote;
 use renegade_solidity_abi::v2::relayer_types::u256_to_u128;
-use renegade_types_core::Token;
+use renegade_types_core::{Chain, Token};
 use uuid::Uuid;
 
 use crate::error::AuthServerError;
+use crate::server::chains::{token_on_chain, usdc_on_chain};
 
 // -------------
 // | Constants |
@@ -125,13 +126,14 @@ pub fn generate_quote_uuid(signed_quote: &ApiSignedQuote) -> Uuid {
 }
 
 /// Pick the base and quote mints from the given input and output mints,
-/// expecting one of them to be USDC. Returns a tuple of (base_mint,
-/// quote_mint).
+/// expecting one of them to be USDC on the given chain. Returns a tuple of
+/// (base_mint, quote_mint).
 pub fn pick_base_and_quote_mints(
     input_mint: Address,
     output_mint: Address,
+    chain: Chain,
 ) -> Result<(Address, Address), AuthServerError> {
-    let usdc_mint = Token::usdc().get_alloy_address();
+    let usdc_mint = usdc_on_chain(chain).get_alloy_address();
 
     if input_mint == usdc_mint {
         Ok((output_mint, input_mint))
@@ -142,6 +144,28 @@ pub fn pick_base_and_quote_mints(
     }
 }
 
+/// Get the ticker of the base asset traded between the given mints, falling
+/// back to the base mint's address for tokens without a ticker
+pub fn base_asset_ticker(
+    input_mint: Address,
+    output_mint: Address,
+    chain: Chain,
+) -> Result<String, AuthServerError> {
+    let (base_mint, _) = pick_base_and_quote_mints(input_mint, output_mint, chain)?;
+    let base_token = token_on_chain(&base_mint, chain);
+    Ok(base_token.get_ticker().unwrap_or_else(|| base_token.get_addr()))
+}
+
+/// The move from one price to another in basis points, from the external
+/// party's perspective
+///
+/// A positive move is in the external party's favor; a rising price favors a
+/// buyer of the base asset, and a falling price a seller
+pub fn price_move_bps(external_buys: bool, from_price: f64, to_price: f64) -> f64 {
+    let side_factor = if external_buys { 1. } else { -1. };
+    side_factor * (to_price - from_price) / from_price * 10_000.
+}
+
 // ---------
 // | Tests |
 // ---------
@@ -165,6 +189,14 @@ mod tests {
         assert_eq!(value, decrypted);
     }
 
+    /// Tests that price moves are signed by the external party's side
+    #[test]
+    fn test_price_move_bps() {
+        assert!((price_move_bps(true, 100., 101.) - 100.).abs() < 1e-9);
+        assert!((price_move_bps(false, 100., 101.) + 100.).abs() < 1e-9);
+        assert!((price_move_bps(false, 2_000., 1_999.) - 5.).abs() < 1e-9);
+    }
+
     /// Generate an API secret
     #[test]
     fn test_generate_api_secret() {
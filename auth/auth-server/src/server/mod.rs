@@ -4,12 +4,14 @@
 mod api_auth;
 pub(crate) mod api_handlers;
 pub(crate) mod caching;
+pub(crate) mod chains;
 pub(crate) mod db;
 pub mod gas_estimation;
 pub(crate) mod gas_sponsorship;
 pub(crate) mod helpers;
 pub(crate) mod rate_limiter;
 mod setup;
+pub(crate) mod usage_recorder;
 
 use std::str::FromStr;
 use std::{sync::Arc, time::Duration};
@@ -20,6 +22,8 @@ use crate::http_utils::request_response::convert_headers;
 use crate::log_task;
 use crate::logger::{Outcome, Task};
 use crate::server::caching::ServerCache;
+use crate::server::chains::ServedTokens;
+use crate::server::usage_recorder::UsageRecorder;
 use aes_gcm::Aes128Gcm;
 use alloy::signers::k256::ecdsa::SigningKey;
 use alloy_primitives::Address;
@@ -45,13 +49,13 @@ const ADMIN_AUTH_DURATION_MS: u64 = 5_000; // 5 seconds
 /// The server struct that holds all the necessary components
 #[derive(Clone)]
 pub struct Server {
-    /// The chain for which the server is configured
+    /// The chain that the server serves requests on
     pub chain: Chain,
     /// The database connection pool
     pub db_pool: DbPool,
     /// The Redis client
     pub redis_client: ConnectionManager,
-    /// The URL of the relayer
+    /// The URL of the relayer settling to the server's chain
     pub relayer_url: String,
     /// The admin key for the relayer
     pub relayer_admin_key: HmacKey,
@@ -59,27 +63,37 @@ pub struct Server {
     pub management_key: HmacKey,
     /// The encryption key for storing API secrets
     pub encryption_key: Aes128Gcm,
-    /// The server's data cache
-    pub cache: ServerCache,
+    /// How long an API secret remains valid after it is rotated out, unless
+    /// overridden on the rotation request
+    pub secret_rotation_grace_period: Duration,
+    /// The number of trusted proxies in front of the server, from which the
+    /// client IP is read
+    pub trusted_proxy_hops: usize,
+    /// The server's data cache, shared between the servers of all chains
+    pub cache: Arc<ServerCache>,
     /// The HTTP client
     pub client: Client,
     /// The rate limiter
     pub rate_limiter: AuthServerRateLimiter,
     /// Rate at which to sample metrics (0.0 to 1.0)
     pub metrics_sampling_rate: f64,
-    /// The address of the gas sponsor address
+    /// The address of the gas sponsor contract on the server's chain
     pub gas_sponsor_address: Address,
-    /// The auth key for the gas sponsor
+    /// The auth key for the gas sponsor on the server's chain
     pub gas_sponsor_auth_key: SigningKey,
     /// The price reporter client with WebSocket streaming support
     pub price_reporter_client: PriceReporterClient,
-    /// The gas cost sampler
+    /// The gas cost sampler for the server's chain
     pub gas_cost_sampler: Arc<GasCostSampler>,
-    /// The minimum order quote amount for which gas sponsorship is allowed,
-    /// in whole units of USDC
+    /// The default minimum order quote amount for which gas sponsorship is
+    /// allowed, in whole units of USDC
     pub min_sponsored_order_quote_amount: f64,
-    /// The bundle store
+    /// The bundle store for the server's chain
     pub bundle_store: BundleStore,
+    /// The recorder buffering usage increments from the request path
+    pub usage_recorder: UsageRecorder,
+    /// The tokens listed on each served chain
+    pub served_tokens: ServedTokens,
 }
 
 // ----------------
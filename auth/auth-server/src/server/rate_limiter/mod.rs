@@ -9,7 +9,13 @@
 //!   requires active liquidity.
 //! - Gas sponsorship: This is used for sponsored match bundles. We keep track
 //!   of approximate dollar value of sponsorship when a sponsored bundle is
-//!   settled.
+//!   settled, per key and against any key-asset or asset budgets configured by
+//!   sponsorship policies.
+//! - Volume: This caps the notional USD volume a key may assemble over a
+//!   sliding hour or day, optionally per asset, along with global per-asset
+//!   caps across all keys. Quotes and assemblies check the limits, and the
+//!   matched volume of an assembled bundle is consumed against them, then
+//!   refunded if the bundle expires without settling.
 //!
 //! For the first two mechanisms, the unit which we rate limit is number of
 //! inflight bundles. Therefore, there are two ways for the token bucket to
@@ -20,8 +26,11 @@
 //! The latter is measured by waiting for nullifier spend events on-chain. This
 //! is also when we record the gas sponsorship value for sponsored bundles.
 
+use alloy_primitives::Address;
 use chrono::TimeDelta;
 use redis::aio::ConnectionManager as RedisConnection;
+use renegade_circuit_types::fixed_point::FixedPoint;
+use renegade_external_api::types::ExternalOrder;
 use tracing::instrument;
 use uuid::Uuid;
 
@@ -30,10 +39,17 @@ use crate::{
     log_task,
     logger::{Outcome, Task},
     server::{
-        db::{create_redis_client, models::RateLimitMethod},
+        chains::token_on_chain,
+        db::{
+            create_redis_client,
+            models::{RateLimitMethod, VolumeLimit},
+        },
+        gas_sponsorship::policies::SponsorshipBudget,
+        helpers::pick_base_and_quote_mints,
         rate_limiter::{
             execution_cost_rate_limiter::ExecutionCostRateLimiter,
             redis_rate_limiter::RedisRateLimiter,
+            volume_rate_limiter::{ResolvedVolumeLimit, VolumeRateLimiter},
         },
     },
 };
@@ -42,6 +58,9 @@ use super::Server;
 
 mod execution_cost_rate_limiter;
 mod redis_rate_limiter;
+mod volume_rate_limiter;
+
+pub use volume_rate_limiter::VolumeConsumption;
 
 /// The bundle rate limiter key prefix
 const BUNDLE_RATE_LIMITER_KEY_PREFIX: &str = "bundle_rate_limit";
@@ -130,26 +149,104 @@ impl Server {
         Ok(())
     }
 
-    /// Check the gas sponsorship rate limiter
+    /// Check the gas sponsorship budgets that apply to a request
     ///
-    /// Returns a boolean indicating whether or not the gas sponsorship rate
-    /// limit has been exceeded.
-    #[instrument(skip(self))]
+    /// Returns a boolean indicating whether or not every budget has remaining
+    /// value
+    #[instrument(skip(self, budgets))]
     pub async fn check_gas_sponsorship_rate_limit(
         &self,
         key_description: &str,
+        budgets: &[SponsorshipBudget],
     ) -> Result<bool, AuthServerError> {
-        if !self.rate_limiter.check_gas_sponsorship(key_description).await? {
+        for budget in budgets {
+            let scope = budget.scope.as_str();
+            if !self.rate_limiter.check_gas_sponsorship(scope, budget.daily_budget_usd).await? {
+                log_task!(
+                    Task::RateLimit,
+                    Outcome::Failed,
+                    subject = "gas-sponsorship",
+                    key_description = key_description,
+                    scope = scope,
+                    "gas sponsorship rate limit exceeded"
+                );
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Check the notional volume of an order against the volume limits that
+    /// apply to the key
+    ///
+    /// Returns an error if any limit would be exceeded
+    #[instrument(skip(self, order))]
+    pub async fn check_volume_rate_limit(
+        &self,
+        key_id: Uuid,
+        key_description: &str,
+        order: &ExternalOrder,
+    ) -> Result<(), AuthServerError> {
+        let (base_mint, quote_mint) =
+            pick_base_and_quote_mints(order.input_mint, order.output_mint, self.chain)?;
+
+        // Only price the order if a limit applies
+        let limits = self.resolve_volume_limits(key_id, &base_mint).await?;
+        if limits.is_empty() {
+            return Ok(());
+        }
+
+        let quote_amount =
+            self.get_quote_amount(order, FixedPoint::zero() /* relayer_fee */).await?;
+        let volume_usd = token_on_chain(&quote_mint, self.chain).convert_to_decimal(quote_amount);
+        if let Some(limit) = self.rate_limiter.check_volume(&limits, volume_usd).await? {
             log_task!(
                 Task::RateLimit,
                 Outcome::Failed,
-                subject = "gas-sponsorship",
+                subject = "volume",
                 key_description = key_description,
-                "gas sponsorship rate limit exceeded"
+                scope = %limit.scope,
+                window = limit.window.as_str(),
+                max_volume_usd = limit.max_volume_usd,
+                volume_usd = volume_usd,
+                "volume rate limit exceeded"
             );
-            return Ok(false);
+            return Err(AuthServerError::RateLimit);
         }
-        Ok(true)
+
+        Ok(())
+    }
+
+    /// Consume the matched volume of an assembled bundle against the volume
+    /// limits that apply to the key
+    ///
+    /// Returns the consumption, or `None` if no limit applies
+    #[instrument(skip(self))]
+    pub async fn consume_matched_volume(
+        &self,
+        key_id: Uuid,
+        base_mint: Address,
+        volume_usd: f64,
+    ) -> Result<Option<VolumeConsumption>, AuthServerError> {
+        let limits = self.resolve_volume_limits(key_id, &base_mint).await?;
+        if limits.is_empty() {
+            return Ok(None);
+        }
+
+        self.rate_limiter.consume_volume(&limits, volume_usd).await.map(Some)
+    }
+
+    /// Resolve the volume limits that apply to a key trading the given base
+    async fn resolve_volume_limits(
+        &self,
+        key_id: Uuid,
+        base_mint: &Address,
+    ) -> Result<Vec<ResolvedVolumeLimit>, AuthServerError> {
+        let base_token = token_on_chain(base_mint, self.chain);
+        let asset = base_token.get_ticker().unwrap_or_else(|| base_token.get_addr());
+        let configured = self.get_applicable_volume_limits(key_id, &asset).await?;
+        Ok(self.rate_limiter.resolve_volume_limits(key_id, &asset, &configured))
     }
 
     /// Check the execution cost rate limiter
@@ -174,6 +271,8 @@ pub struct AuthServerRateLimiter {
     gas_sponsorship_rate_limiter: RedisRateLimiter,
     /// The execution cost rate limiter
     execution_cost_rate_limiter: ExecutionCostRateLimiter,
+    /// The notional volume rate limiter
+    volume_rate_limiter: VolumeRateLimiter,
 }
 
 impl AuthServerRateLimiter {
@@ -182,6 +281,8 @@ impl AuthServerRateLimiter {
         quote_rate_limit: u64,
         bundle_rate_limit: u64,
         max_gas_sponsorship_value: f64,
+        default_hourly_volume_limit: Option<f64>,
+        default_daily_volume_limit: Option<f64>,
         auth_server_redis_url: &str,
         execution_cost_redis_url: &str,
     ) -> Result<Self, AuthServerError> {
@@ -190,15 +291,18 @@ impl AuthServerRateLimiter {
         let quote_rate_limiter = Self::new_quote_rate_limiter(quote_rate_limit, conn.clone());
         let bundle_rate_limiter = Self::new_bundle_rate_limiter(bundle_rate_limit, conn.clone());
         let gas_sponsorship_rate_limiter =
-            Self::new_gas_sponsorship_rate_limiter(max_gas_sponsorship_value, conn);
+            Self::new_gas_sponsorship_rate_limiter(max_gas_sponsorship_value, conn.clone());
         let execution_cost_rate_limiter =
             ExecutionCostRateLimiter::new(execution_cost_redis_url).await?;
+        let volume_rate_limiter =
+            VolumeRateLimiter::new(default_hourly_volume_limit, default_daily_volume_limit, conn);
 
         Ok(Self {
             quote_rate_limiter,
             bundle_rate_limiter,
             gas_sponsorship_rate_limiter,
             execution_cost_rate_limiter,
+            volume_rate_limiter,
         })
     }
 
@@ -298,28 +402,73 @@ impl AuthServerRateLimiter {
         self.bundle_rate_limiter.decrement_consumed(user_id, 1.0).await.map(|_| ())
     }
 
-    /// Check if the given user has any remaining gas sponsorship budget
-    pub async fn check_gas_sponsorship(&self, user_id: &str) -> Result<bool, AuthServerError> {
-        let exceeded = self
-            .gas_sponsorship_rate_limiter
-            .rate_limit_exceeded(user_id, None /* max_tokens */)
-            .await?;
+    /// Check if the given budget scope has any remaining gas sponsorship
+    /// budget
+    ///
+    /// The budget defaults to the server's per-key budget if not given
+    pub async fn check_gas_sponsorship(
+        &self,
+        scope: &str,
+        max_value: Option<f64>,
+    ) -> Result<bool, AuthServerError> {
+        let exceeded =
+            self.gas_sponsorship_rate_limiter.rate_limit_exceeded(scope, max_value).await?;
         Ok(!exceeded)
     }
 
-    /// Record a gas sponsorship value for a given user.
+    /// Record a gas sponsorship value against the given budget scopes.
     ///
-    /// If the user does not have any remaining gas sponsorship budget, this
-    /// method will do nothing.
+    /// Values are recorded regardless of any remaining budget
     pub async fn record_gas_sponsorship(
         &self,
-        user_id: &str,
+        scopes: &[String],
         value: f64,
     ) -> Result<(), AuthServerError> {
-        self.gas_sponsorship_rate_limiter.increment_consumed_no_check(user_id, value).await?;
+        for scope in scopes {
+            self.gas_sponsorship_rate_limiter.increment_consumed_no_check(scope, value).await?;
+        }
         Ok(())
     }
 
+    /// Resolve the volume limits that apply to a key trading an asset from the
+    /// configured limits
+    pub fn resolve_volume_limits(
+        &self,
+        key_id: Uuid,
+        asset: &str,
+        configured: &[VolumeLimit],
+    ) -> Vec<ResolvedVolumeLimit> {
+        self.volume_rate_limiter.resolve_limits(key_id, asset, configured)
+    }
+
+    /// Check the given volume against the given limits
+    ///
+    /// Returns the first limit that would be exceeded, if any
+    pub async fn check_volume(
+        &self,
+        limits: &[ResolvedVolumeLimit],
+        volume_usd: f64,
+    ) -> Result<Option<ResolvedVolumeLimit>, AuthServerError> {
+        self.volume_rate_limiter.check_volume(limits, volume_usd).await
+    }
+
+    /// Consume the given volume against every one of the given limits
+    pub async fn consume_volume(
+        &self,
+        limits: &[ResolvedVolumeLimit],
+        volume_usd: f64,
+    ) -> Result<VolumeConsumption, AuthServerError> {
+        self.volume_rate_limiter.consume_volume(limits, volume_usd).await
+    }
+
+    /// Refund volume consumed by a bundle that did not settle
+    pub async fn refund_volume(
+        &self,
+        consumption: &VolumeConsumption,
+    ) -> Result<(), AuthServerError> {
+        self.volume_rate_limiter.refund_volume(consumption).await
+    }
+
     /// Check if execution costs have been exceeded for the given ticker
     ///
     /// Returns false if the rate limit has been exceeded, otherwise true
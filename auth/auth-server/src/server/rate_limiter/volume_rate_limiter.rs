@@ -0,0 +1,416 @@
+//! A rate limiter on notional volume over sliding windows
+//!
+//! Each limit is tracked with a sliding window counter: volume is accumulated
+//! in fixed buckets the length of the window, and the volume in the trailing
+//! window is estimated as the current bucket plus the previous bucket weighted
+//! by the fraction of it still inside the window. This bounds memory to two
+//! keys per limit while avoiding the burst allowed at fixed window boundaries
+//!
+//! Volume is checked before a bundle is assembled, and consumed only once the
+//! bundle is assembled. The consumed buckets are recorded with the bundle so
+//! that its volume may be refunded if it expires without settling
+
+use auth_server_api::volume_limits::VolumeLimitWindow;
+use lazy_static::lazy_static;
+use redis::{Script, aio::ConnectionManager as RedisConnection};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{error::AuthServerError, server::db::models::VolumeLimit};
+
+/// The key prefix for volume rate limit buckets
+const VOLUME_RATE_LIMITER_KEY_PREFIX: &str = "volume_rate_limit";
+
+// -----------------
+// | Redis Scripts |
+// -----------------
+
+/// A Redis script that checks volume against a set of sliding window limits
+const CHECK_VOLUME_LIMITS_LUA: &str = r#"
+    -- Check volume against a set of sliding windows
+    --
+    -- Keys, for each limit i:
+    --  2i - 1. The current bucket of the limit
+    --  2i.     The previous bucket of the limit
+    --
+    -- Arguments:
+    --  1. The volume to check
+    --  Then, for each limit i:
+    --  2i.     The maximum volume in the window
+    --  2i + 1. The weight of the previous bucket
+    --
+    -- Returns the (1-indexed) first limit that would be exceeded, or 0
+    local amount = tonumber(ARGV[1])
+    local n = #KEYS / 2
+
+    for i = 1, n do
+        local limit   = tonumber(ARGV[2 * i])
+        local weight  = tonumber(ARGV[2 * i + 1])
+        local current = tonumber(redis.call("GET", KEYS[2 * i - 1]) or "0")
+        local prev    = tonumber(redis.call("GET", KEYS[2 * i]) or "0")
+        if prev * weight + current + amount > limit then
+            return i
+        end
+    end
+    return 0
+"#;
+
+/// A Redis script that refunds volume to the buckets it was consumed from
+const REFUND_VOLUME_LUA: &str = r#"
+    -- Refund volume to a set of buckets, saturating at zero
+    --
+    -- Keys: the buckets the volume was consumed from
+    --
+    -- Arguments:
+    --  1. The volume to refund
+    --
+    -- Buckets which have since expired are skipped, and the TTLs of the
+    -- others are preserved
+    local amount = tonumber(ARGV[1])
+    for _, key in ipairs(KEYS) do
+        local current = redis.call("GET", key)
+        if current then
+            local refunded = math.max(tonumber(current) - amount, 0)
+            redis.call("SET", key, tostring(refunded), "KEEPTTL")
+        end
+    end
+    return 0
+"#;
+
+lazy_static! {
+    static ref CHECK_VOLUME_LIMITS_SCRIPT: Script = Script::new(CHECK_VOLUME_LIMITS_LUA);
+    static ref REFUND_VOLUME_SCRIPT: Script = Script::new(REFUND_VOLUME_LUA);
+}
+
+// ---------------
+// | Limit Types |
+// ---------------
+
+/// A volume limit resolved for a single request
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedVolumeLimit {
+    /// The scope the limit is tracked under, e.g. a key and asset
+    pub scope: String,
+    /// The window over which volume is measured
+    pub window: VolumeLimitWindow,
+    /// The maximum notional volume over the window, in USD
+    pub max_volume_usd: f64,
+}
+
+impl ResolvedVolumeLimit {
+    /// Constructor
+    pub fn new(scope: String, window: VolumeLimitWindow, max_volume_usd: f64) -> Self {
+        Self { scope, window, max_volume_usd }
+    }
+}
+
+/// Volume consumed by an assembled bundle, recorded so that it may be refunded
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VolumeConsumption {
+    /// The buckets the volume was consumed from
+    pub bucket_keys: Vec<String>,
+    /// The consumed volume, in USD
+    pub volume_usd: f64,
+}
+
+// ----------------
+// | Rate Limiter |
+// ----------------
+
+/// A rate limiter on notional volume, using Redis as the underlying storage
+#[derive(Clone)]
+pub struct VolumeRateLimiter {
+    /// The default per-key hourly volume limit, in USD
+    default_hourly_limit: Option<f64>,
+    /// The default per-key daily volume limit, in USD
+    default_daily_limit: Option<f64>,
+    /// The Redis connection manager
+    redis: RedisConnection,
+}
+
+impl VolumeRateLimiter {
+    /// Constructor
+    pub fn new(
+        default_hourly_limit: Option<f64>,
+        default_daily_limit: Option<f64>,
+        redis: RedisConnection,
+    ) -> Self {
+        Self { default_hourly_limit, default_daily_limit, redis }
+    }
+
+    /// Get the default per-key limit for the given window, if configured
+    pub fn default_limit(&self, window: VolumeLimitWindow) -> Option<f64> {
+        match window {
+            VolumeLimitWindow::Hour => self.default_hourly_limit,
+            VolumeLimitWindow::Day => self.default_daily_limit,
+        }
+    }
+
+    /// Resolve the limits that apply to a key trading an asset from the
+    /// configured limits, applying the default per-key limits
+    pub fn resolve_limits(
+        &self,
+        key_id: Uuid,
+        asset: &str,
+        configured: &[VolumeLimit],
+    ) -> Vec<ResolvedVolumeLimit> {
+        resolve_volume_limits(key_id, asset, configured, |window| self.default_limit(window))
+    }
+
+    /// Check whether the given volume fits within all of the given limits
+    ///
+    /// Returns the first limit that would be exceeded, if any
+    pub async fn check_volume(
+        &self,
+        limits: &[ResolvedVolumeLimit],
+        volume_usd: f64,
+    ) -> Result<Option<ResolvedVolumeLimit>, AuthServerError> {
+        if limits.is_empty() {
+            return Ok(None);
+        }
+
+        let now_ms = Self::now_ms();
+        let mut invocation = CHECK_VOLUME_LIMITS_SCRIPT.prepare_invoke();
+        invocation.arg(volume_usd);
+        for limit in limits {
+            let window_ms = limit.window.duration_ms();
+            let (bucket, prev_weight) = sliding_window_position(now_ms, window_ms);
+            invocation
+                .key(bucket_key(limit, bucket))
+                .key(bucket_key(limit, bucket.saturating_sub(1)))
+                .arg(limit.max_volume_usd)
+                .arg(prev_weight);
+        }
+
+        let exceeded: usize = invocation.invoke_async(&mut self.redis.clone()).await?;
+        Ok(exceeded.checked_sub(1).and_then(|i| limits.get(i)).cloned())
+    }
+
+    /// Consume the given volume against every one of the given limits
+    ///
+    /// Volume is consumed even if it exceeds a limit, as it has already been
+    /// matched. Returns the consumption, to be refunded if the volume does not
+    /// settle
+    pub async fn consume_volume(
+        &self,
+        limits: &[ResolvedVolumeLimit],
+        volume_usd: f64,
+    ) -> Result<VolumeConsumption, AuthServerError> {
+        let buckets = current_buckets(limits, Self::now_ms());
+        let mut pipe = redis::pipe();
+        for (key, ttl_ms) in buckets.iter() {
+            pipe.incr(key, volume_usd).ignore().pexpire(key, *ttl_ms as i64).ignore();
+        }
+        let _: () = pipe.query_async(&mut self.redis.clone()).await?;
+
+        let bucket_keys = buckets.into_iter().map(|(key, _)| key).collect();
+        Ok(VolumeConsumption { bucket_keys, volume_usd })
+    }
+
+    /// Refund consumed volume to the buckets it was consumed from
+    pub async fn refund_volume(
+        &self,
+        consumption: &VolumeConsumption,
+    ) -> Result<(), AuthServerError> {
+        if consumption.bucket_keys.is_empty() {
+            return Ok(());
+        }
+
+        let mut invocation = REFUND_VOLUME_SCRIPT.prepare_invoke();
+        invocation.arg(consumption.volume_usd);
+        for key in consumption.bucket_keys.iter() {
+            invocation.key(key);
+        }
+
+        let _: i64 = invocation.invoke_async(&mut self.redis.clone()).await?;
+        Ok(())
+    }
+
+    // -----------
+    // | Helpers |
+    // -----------
+
+    /// Get the current time in milliseconds since epoch
+    fn now_ms() -> u64 {
+        chrono::Utc::now().timestamp_millis() as u64
+    }
+}
+
+/// Get the Redis key of a limit's bucket
+fn bucket_key(limit: &ResolvedVolumeLimit, bucket: u64) -> String {
+    let window = limit.window.as_str();
+    format!("{VOLUME_RATE_LIMITER_KEY_PREFIX}:{}:{window}#{bucket}", limit.scope)
+}
+
+/// Get the key of each limit's bucket at the given time, along with the TTL
+/// in milliseconds that keeps the bucket alive while it may be the previous
+/// bucket of a window
+fn current_buckets(limits: &[ResolvedVolumeLimit], now_ms: u64) -> Vec<(String, u64)> {
+    limits
+        .iter()
+        .map(|limit| {
+            let window_ms = limit.window.duration_ms();
+            let (bucket, _) = sliding_window_position(now_ms, window_ms);
+            (bucket_key(limit, bucket), window_ms * 2)
+        })
+        .collect()
+}
+
+/// Resolve the limits that apply to a key trading an asset
+///
+/// Limits on a single chain are tracked under a scope prefixed with the chain
+/// ID, separately from limits across all chains. The default per-key limits
+/// apply to any window for which the key has no configured limit across all
+/// assets and chains
+fn resolve_volume_limits(
+    key_id: Uuid,
+    asset: &str,
+    configured: &[VolumeLimit],
+    default_limit: impl Fn(VolumeLimitWindow) -> Option<f64>,
+) -> Vec<ResolvedVolumeLimit> {
+    let key_scope = format!("key:{key_id}");
+    let mut limits: Vec<ResolvedVolumeLimit> = configured
+        .iter()
+        .filter_map(|limit| {
+            let window = limit.time_window.parse().ok()?;
+            let scope = match (limit.api_key_id, &limit.asset) {
+                (Some(_), Some(_)) => format!("{key_scope}:{asset}"),
+                (Some(_), None) => key_scope.clone(),
+                (None, Some(_)) => format!("asset:{asset}"),
+                (None, None) => return None,
+            };
+            let scope = match limit.chain_id {
+                Some(chain_id) => format!("chain:{chain_id}:{scope}"),
+                None => scope,
+            };
+            Some(ResolvedVolumeLimit::new(scope, window, limit.max_volume_usd))
+        })
+        .collect();
+
+    for window in [VolumeLimitWindow::Hour, VolumeLimitWindow::Day] {
+        let has_key_limit =
+            limits.iter().any(|limit| limit.scope == key_scope && limit.window == window);
+        if let Some(max_volume_usd) = default_limit(window)
+            && !has_key_limit
+        {
+            limits.push(ResolvedVolumeLimit::new(key_scope.clone(), window, max_volume_usd));
+        }
+    }
+
+    limits
+}
+
+/// Get the index of the bucket containing the given time, and the weight of
+/// the previous bucket in the sliding window ending at that time
+///
+/// The weight is the fraction of the previous bucket still inside the window
+fn sliding_window_position(now_ms: u64, window_ms: u64) -> (u64, f64) {
+    let bucket = now_ms / window_ms;
+    let elapsed = now_ms % window_ms;
+    let prev_weight = 1. - (elapsed as f64 / window_ms as f64);
+    (bucket, prev_weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a configured volume limit
+    fn limit(api_key_id: Option<Uuid>, asset: Option<&str>, window: &str) -> VolumeLimit {
+        VolumeLimit {
+            api_key_id,
+            asset: asset.map(str::to_string),
+            chain_id: None,
+            time_window: window.to_string(),
+            max_volume_usd: 1_000.,
+        }
+    }
+
+    #[test]
+    fn defaults_apply_only_without_key_limit() {
+        let key_id = Uuid::new_v4();
+        let configured = vec![
+            limit(Some(key_id), None, "day"),
+            limit(Some(key_id), Some("WETH"), "hour"),
+            limit(None, Some("WETH"), "day"),
+        ];
+        let defaults = |window| match window {
+            VolumeLimitWindow::Hour => Some(10.),
+            VolumeLimitWindow::Day => Some(100.),
+        };
+        let limits = resolve_volume_limits(key_id, "WETH", &configured, defaults);
+
+        let key_scope = format!("key:{key_id}");
+        let key_asset_scope = format!("{key_scope}:WETH");
+        let scopes: Vec<(&str, VolumeLimitWindow, f64)> = limits
+            .iter()
+            .map(|limit| (limit.scope.as_str(), limit.window, limit.max_volume_usd))
+            .collect();
+        assert_eq!(
+            scopes,
+            vec![
+                (key_scope.as_str(), VolumeLimitWindow::Day, 1_000.),
+                (key_asset_scope.as_str(), VolumeLimitWindow::Hour, 1_000.),
+                ("asset:WETH", VolumeLimitWindow::Day, 1_000.),
+                (key_scope.as_str(), VolumeLimitWindow::Hour, 10.),
+            ]
+        );
+    }
+
+    #[test]
+    fn chain_limits_are_tracked_separately() {
+        let key_id = Uuid::new_v4();
+        let base_chain_limit =
+            VolumeLimit { chain_id: Some(8453), ..limit(Some(key_id), None, "day") };
+        let limits = resolve_volume_limits(key_id, "WETH", &[base_chain_limit], |_| Some(10.));
+
+        let key_scope = format!("key:{key_id}");
+        let chain_scope = format!("chain:8453:{key_scope}");
+        let scopes: Vec<(&str, VolumeLimitWindow)> =
+            limits.iter().map(|limit| (limit.scope.as_str(), limit.window)).collect();
+        assert_eq!(
+            scopes,
+            vec![
+                (chain_scope.as_str(), VolumeLimitWindow::Day),
+                (key_scope.as_str(), VolumeLimitWindow::Hour),
+                (key_scope.as_str(), VolumeLimitWindow::Day),
+            ]
+        );
+    }
+
+    #[test]
+    fn previous_bucket_weight_decays_over_window() {
+        let window_ms = VolumeLimitWindow::Hour.duration_ms();
+
+        let (bucket, weight) = sliding_window_position(10 * window_ms, window_ms);
+        assert_eq!((bucket, weight), (10, 1.));
+
+        let (bucket, weight) = sliding_window_position(10 * window_ms + window_ms / 4, window_ms);
+        assert_eq!((bucket, weight), (10, 0.75));
+
+        let (bucket, weight) = sliding_window_position(11 * window_ms - 1, window_ms);
+        assert_eq!(bucket, 10);
+        assert!(weight < 1e-6);
+    }
+
+    #[test]
+    fn consumption_targets_current_bucket_of_each_limit() {
+        let hour_ms = VolumeLimitWindow::Hour.duration_ms();
+        let day_ms = VolumeLimitWindow::Day.duration_ms();
+        let limits = vec![
+            ResolvedVolumeLimit::new("key:k".to_string(), VolumeLimitWindow::Hour, 10.),
+            ResolvedVolumeLimit::new("asset:WETH".to_string(), VolumeLimitWindow::Day, 100.),
+        ];
+
+        // One hour and a quarter into the third day
+        let now_ms = 2 * day_ms + hour_ms + hour_ms / 4;
+        let buckets = current_buckets(&limits, now_ms);
+        assert_eq!(
+            buckets,
+            vec![
+                ("volume_rate_limit:key:k:hour#49".to_string(), 2 * hour_ms),
+                ("volume_rate_limit:asset:WETH:day#2".to_string(), 2 * day_ms),
+            ]
+        );
+    }
+}
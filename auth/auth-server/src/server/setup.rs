@@ -1,22 +1,27 @@
 //! Helpers for setting up the server
 
 use super::Server;
+use super::chains::{
+    ChainConfig, ChainServers, ServedTokens, chain_to_chain_id, read_chain_configs,
+    tokens_on_chain, usdc_on_chain,
+};
 use super::db::{create_db_pool, create_redis_client};
 use super::gas_estimation::gas_cost_sampler::GasCostSampler;
 use super::rate_limiter::AuthServerRateLimiter;
 
-use std::{iter, sync::Arc, time::Duration};
+use std::{collections::HashMap, iter, sync::Arc, time::Duration};
 
 use crate::bundle_store::BundleStore;
 use crate::chain_events::listener::{OnChainEventListener, OnChainEventListenerConfig};
 use crate::server::caching::ServerCache;
+use crate::server::usage_recorder::UsageRecorder;
 use crate::telemetry::configure_telemetry_from_args;
+use crate::webhooks::WebhookDispatcher;
 use crate::{Cli, error::AuthServerError};
 use aes_gcm::{Aes128Gcm, KeyInit};
 use alloy::hex;
 use alloy::signers::k256::ecdsa::SigningKey;
 use alloy::signers::local::PrivateKeySigner;
-use alloy_primitives::Address;
 use base64::{Engine, engine::general_purpose};
 use price_reporter_client::{PriceReporterClient, PriceReporterClientConfig};
 use renegade_config::setup_token_remaps;
@@ -24,7 +29,7 @@ use renegade_constants::NATIVE_ASSET_ADDRESS;
 use renegade_darkpool_client::DarkpoolClient;
 use renegade_darkpool_client::client::DarkpoolClientConfig;
 use renegade_system_clock::SystemClock;
-use renegade_types_core::{Chain, get_all_tokens};
+use renegade_types_core::{Chain, set_default_chain};
 use renegade_types_core::{HmacKey, Token};
 use renegade_util::hex::address_from_hex_string;
 use renegade_util::on_chain::set_protocol_fee;
@@ -35,36 +40,49 @@ use tokio_util::sync::CancellationToken;
 const DEFAULT_BLOCK_POLLING_INTERVAL: Duration = Duration::from_millis(100);
 
 impl Server {
-    /// Create a new server instance
+    /// Create the servers for all configured chains
+    ///
+    /// The servers share all state that is not chain-scoped, so that caches
+    /// and rate limits apply across chains
     pub async fn setup(
         args: Cli,
         system_clock: &SystemClock,
-    ) -> Result<(Self, CancellationToken), AuthServerError> {
+    ) -> Result<(ChainServers, CancellationToken), AuthServerError> {
         configure_telemetry_from_args(&args)?;
-        setup_token_mapping(&args).await?;
-
-        // Create the darkpool client
-        let darkpool_client = create_darkpool_client(
-            &args.darkpool_address,
-            &args.permit2_address,
-            args.chain_id,
-            args.rpc_url.clone(),
-        )
-        .expect("failed to create darkpool client");
+        let chain_configs = parse_chain_configs(&args)?;
+        setup_token_mapping(&chain_configs, args.chain_id).await?;
 
-        // Set the external match fees & protocol fee
-        set_external_match_fees(&darkpool_client).await?;
+        // Create the darkpool clients, set the external match fees & protocol fee,
+        // and index the listed tokens on each chain before any chain-scoped
+        // workers start
+        let mut darkpool_clients = Vec::with_capacity(chain_configs.len());
+        let mut listed_mints = HashMap::with_capacity(chain_configs.len());
+        for (chain, config) in chain_configs.iter() {
+            let darkpool_client = create_darkpool_client(
+                &config.darkpool_address,
+                &config.permit2_address,
+                *chain,
+                config.rpc_url.clone(),
+            )
+            .expect("failed to create darkpool client");
+
+            set_external_match_fees(&darkpool_client, *chain).await?;
+            listed_mints.insert(*chain, get_listed_mints(*chain));
+            darkpool_clients.push(darkpool_client);
+        }
+        let served_tokens = ServedTokens::new(listed_mints);
 
         // Setup the DB connection pool and the Redis client
         let db_pool = create_db_pool(&args.database_url).await?;
         let redis_client = create_redis_client(&args.redis_url).await?;
-        let (encryption_key, management_key, relayer_admin_key, gas_sponsor_auth_key) =
-            parse_auth_server_keys(&args)?;
+        let (encryption_key, management_key, relayer_admin_key) = parse_auth_server_keys(&args)?;
 
         let rate_limiter = AuthServerRateLimiter::new(
             args.quote_rate_limit,
             args.bundle_rate_limit,
             args.max_gas_sponsorship_value,
+            args.default_hourly_volume_limit_usd,
+            args.default_daily_volume_limit_usd,
             &args.redis_url,
             &args.execution_cost_redis_url,
         )
@@ -72,61 +90,97 @@ impl Server {
 
         let price_reporter_client = PriceReporterClient::new(PriceReporterClientConfig {
             base_url: args.price_reporter_url.clone(),
+            fallback_urls: args.price_reporter_fallback_urls.clone(),
             ..Default::default()
         })?;
 
-        let gas_sponsor_address = parse_gas_sponsor_address(&args)?;
-        let gas_cost_sampler = Arc::new(
-            GasCostSampler::new(
-                darkpool_client.provider().clone(),
-                gas_sponsor_address,
-                system_clock,
-            )
-            .await?,
-        );
-
-        // Create the shared in-memory bundle store
-        let bundle_store = BundleStore::new();
-
-        // Start the on-chain event listener
-        let chain_listener_config = OnChainEventListenerConfig {
-            chain: args.chain_id,
-            gas_sponsor_address,
-            websocket_addr: args.eth_websocket_addr.clone(),
-            bundle_store: bundle_store.clone(),
-            rate_limiter: rate_limiter.clone(),
-            price_reporter_client: price_reporter_client.clone(),
-            gas_cost_sampler: gas_cost_sampler.clone(),
-            darkpool_client: darkpool_client.clone(),
-        };
-        let mut chain_listener = OnChainEventListener::new(chain_listener_config)
-            .expect("failed to build on-chain event listener");
-        chain_listener.start().expect("failed to start on-chain event listener");
+        // Start the webhook dispatcher and resume pending deliveries
+        let webhooks = WebhookDispatcher::new(db_pool.clone(), encryption_key.clone());
+        webhooks.spawn_retry_worker();
+
+        // Start flushing usage buffered from the request path
+        let usage_recorder = UsageRecorder::new(db_pool.clone());
+        usage_recorder.spawn_flusher();
+
+        let grace_period = Duration::from_secs(args.api_secret_rotation_grace_period_secs);
+        let cache = Arc::new(ServerCache::new(grace_period));
+        let client = Client::new();
         let chain_listener_cancellation_token = CancellationToken::new();
-        chain_listener.watch(chain_listener_cancellation_token.clone());
-
-        let server = Self {
-            chain: args.chain_id,
-            db_pool,
-            redis_client,
-            relayer_url: args.relayer_url,
-            relayer_admin_key,
-            management_key,
-            encryption_key,
-            cache: ServerCache::new(),
-            client: Client::new(),
-            rate_limiter,
-            metrics_sampling_rate: args
-                .metrics_sampling_rate
-                .unwrap_or(1.0 /* default no sampling */),
-            gas_sponsor_address,
-            gas_sponsor_auth_key,
-            price_reporter_client,
-            gas_cost_sampler,
-            min_sponsored_order_quote_amount: args.min_sponsored_order_quote_amount,
-            bundle_store,
-        };
-        Ok((server, chain_listener_cancellation_token))
+        let mut servers = HashMap::with_capacity(chain_configs.len());
+        for ((chain, config), darkpool_client) in chain_configs.into_iter().zip(darkpool_clients) {
+            let gas_sponsor_address = address_from_hex_string(&config.gas_sponsor_address)
+                .map_err(AuthServerError::setup)?;
+            let gas_sponsor_auth_key = parse_gas_sponsor_auth_key(&config.gas_sponsor_auth_key)?;
+
+            // Create the chain's bundle store, and watch for bundles that expire
+            // unsettled to notify them and refund their volume
+            let bundle_store = BundleStore::new(
+                args.bundle_store_backend,
+                redis_client.clone(),
+                Duration::from_secs(args.bundle_store_ttl_secs),
+                chain,
+            );
+            webhooks.spawn_expiry_watcher(bundle_store.clone(), rate_limiter.clone());
+
+            let gas_cost_sampler = Arc::new(
+                GasCostSampler::new(
+                    chain,
+                    darkpool_client.provider().clone(),
+                    gas_sponsor_address,
+                    system_clock,
+                )
+                .await?,
+            );
+
+            // Start the chain's on-chain event listener, any listener crashing
+            // cancels the shared token
+            let chain_listener_config = OnChainEventListenerConfig {
+                chain,
+                gas_sponsor_address,
+                websocket_addr: config.eth_websocket_url.clone(),
+                bundle_store: bundle_store.clone(),
+                rate_limiter: rate_limiter.clone(),
+                price_reporter_client: price_reporter_client.clone(),
+                gas_cost_sampler: gas_cost_sampler.clone(),
+                darkpool_client,
+                db_pool: db_pool.clone(),
+                webhooks: webhooks.clone(),
+            };
+            let mut chain_listener = OnChainEventListener::new(chain_listener_config)
+                .expect("failed to build on-chain event listener");
+            chain_listener.start().expect("failed to start on-chain event listener");
+            chain_listener.watch(chain_listener_cancellation_token.clone());
+
+            let server = Self {
+                chain,
+                db_pool: db_pool.clone(),
+                redis_client: redis_client.clone(),
+                relayer_url: config.relayer_url,
+                relayer_admin_key,
+                management_key,
+                encryption_key: encryption_key.clone(),
+                secret_rotation_grace_period: grace_period,
+                trusted_proxy_hops: args.trusted_proxy_hops,
+                cache: cache.clone(),
+                client: client.clone(),
+                rate_limiter: rate_limiter.clone(),
+                metrics_sampling_rate: args
+                    .metrics_sampling_rate
+                    .unwrap_or(1.0 /* default no sampling */),
+                gas_sponsor_address,
+                gas_sponsor_auth_key,
+                price_reporter_client: price_reporter_client.clone(),
+                gas_cost_sampler,
+                min_sponsored_order_quote_amount: args.min_sponsored_order_quote_amount,
+                bundle_store,
+                usage_recorder: usage_recorder.clone(),
+                served_tokens: served_tokens.clone(),
+            };
+            servers.insert(chain, Arc::new(server));
+        }
+
+        let servers = ChainServers::new(args.chain_id, servers);
+        Ok((servers, chain_listener_cancellation_token))
     }
 }
 
@@ -134,25 +188,71 @@ impl Server {
 // | Setup Helpers |
 // -----------------
 
-/// Setup the token mapping
-async fn setup_token_mapping(args: &Cli) -> Result<(), AuthServerError> {
-    let chain_id = args.chain_id;
-    let token_remap_file = args.token_remap_file.clone();
-    tokio::task::spawn_blocking(move || setup_token_remaps(token_remap_file, chain_id))
-        .await
-        .unwrap()
-        .map(|_loaded_tickers| ())
-        .map_err(AuthServerError::setup)
+/// Parse the configs of all served chains, the default chain first
+fn parse_chain_configs(args: &Cli) -> Result<Vec<(Chain, ChainConfig)>, AuthServerError> {
+    let default_config = ChainConfig {
+        chain_id: chain_to_chain_id(args.chain_id),
+        relayer_url: args.relayer_url.clone(),
+        rpc_url: args.rpc_url.clone(),
+        eth_websocket_url: args.eth_websocket_addr.clone(),
+        darkpool_address: args.darkpool_address.clone(),
+        permit2_address: args.permit2_address.clone(),
+        gas_sponsor_address: args.gas_sponsor_address.clone(),
+        gas_sponsor_auth_key: args.gas_sponsor_auth_key.clone(),
+        token_remap_file: args.token_remap_file.clone(),
+    };
+
+    let additional_configs = match args.additional_chains_config.as_deref() {
+        Some(path) => read_chain_configs(path)?,
+        None => Vec::new(),
+    };
+
+    let mut configs = Vec::with_capacity(additional_configs.len() + 1);
+    for config in iter::once(default_config).chain(additional_configs) {
+        let chain = config.chain()?;
+        if configs.iter().any(|(c, _)| *c == chain) {
+            return Err(AuthServerError::setup(format!("Chain configured twice: {chain}")));
+        }
+
+        configs.push((chain, config));
+    }
+
+    Ok(configs)
 }
 
-/// Set the external match fees & protocol fee
-async fn set_external_match_fees(darkpool_client: &DarkpoolClient) -> Result<(), AuthServerError> {
-    let tokens: Vec<Token> = get_all_tokens()
+/// Setup the token mapping for all served chains
+async fn setup_token_mapping(
+    chain_configs: &[(Chain, ChainConfig)],
+    default_chain: Chain,
+) -> Result<(), AuthServerError> {
+    let remaps: Vec<(Chain, Option<String>)> = chain_configs
+        .iter()
+        .map(|(chain, config)| (*chain, config.token_remap_file.clone()))
+        .collect();
+
+    tokio::task::spawn_blocking(move || {
+        for (chain, token_remap_file) in remaps {
+            setup_token_remaps(token_remap_file, chain).map_err(AuthServerError::setup)?;
+        }
+
+        set_default_chain(default_chain);
+        Ok(())
+    })
+    .await
+    .map_err(AuthServerError::setup)?
+}
+
+/// Set the external match fees & protocol fee for the given chain
+async fn set_external_match_fees(
+    darkpool_client: &DarkpoolClient,
+    chain: Chain,
+) -> Result<(), AuthServerError> {
+    let tokens: Vec<Token> = tokens_on_chain(chain)
         .into_iter()
-        .chain(iter::once(Token::from_addr(NATIVE_ASSET_ADDRESS)))
+        .chain(iter::once(Token::from_addr_on_chain(NATIVE_ASSET_ADDRESS, chain)))
         .collect();
 
-    let usdc = Token::usdc().get_alloy_address();
+    let usdc = usdc_on_chain(chain).get_alloy_address();
     for token in tokens {
         if token.get_alloy_address() == usdc {
             continue;
@@ -170,11 +270,16 @@ async fn set_external_match_fees(darkpool_client: &DarkpoolClient) -> Result<(),
     Ok(())
 }
 
-/// Parse the encryption key, management key, relayer admin key, and gas sponsor
-/// auth key
-fn parse_auth_server_keys(
-    args: &Cli,
-) -> Result<(Aes128Gcm, HmacKey, HmacKey, SigningKey), AuthServerError> {
+/// Get the lowercase mint of each ticker listed on the given chain
+fn get_listed_mints(chain: Chain) -> HashMap<String, String> {
+    tokens_on_chain(chain)
+        .into_iter()
+        .filter_map(|token| Some((token.get_ticker()?, token.get_addr().to_lowercase())))
+        .collect()
+}
+
+/// Parse the encryption key, management key, and relayer admin key
+fn parse_auth_server_keys(args: &Cli) -> Result<(Aes128Gcm, HmacKey, HmacKey), AuthServerError> {
     let encryption_key_bytes =
         general_purpose::STANDARD.decode(&args.encryption_key).map_err(AuthServerError::setup)?;
 
@@ -187,18 +292,13 @@ fn parse_auth_server_keys(
     let relayer_admin_key =
         HmacKey::from_base64_string(&args.relayer_admin_key).map_err(AuthServerError::setup)?;
 
-    let gas_sponsor_auth_key_bytes =
-        hex::decode(&args.gas_sponsor_auth_key).map_err(AuthServerError::setup)?;
-
-    let gas_sponsor_auth_key =
-        SigningKey::from_slice(&gas_sponsor_auth_key_bytes).map_err(AuthServerError::setup)?;
-
-    Ok((encryption_key, management_key, relayer_admin_key, gas_sponsor_auth_key))
+    Ok((encryption_key, management_key, relayer_admin_key))
 }
 
-/// Parse the gas sponsor address from the CLI args
-fn parse_gas_sponsor_address(args: &Cli) -> Result<Address, AuthServerError> {
-    address_from_hex_string(&args.gas_sponsor_address).map_err(AuthServerError::setup)
+/// Parse a gas sponsor auth key from its hex encoding
+fn parse_gas_sponsor_auth_key(key: &str) -> Result<SigningKey, AuthServerError> {
+    let gas_sponsor_auth_key_bytes = hex::decode(key).map_err(AuthServerError::setup)?;
+    SigningKey::from_slice(&gas_sponsor_auth_key_bytes).map_err(AuthServerError::setup)
 }
 
 /// Create a darkpool client with the provided configuration
@@ -0,0 +1,149 @@
+//! Buffers usage increments from the request path
+//!
+//! Quotes and bundles are counted in memory and flushed to the usage table
+//! periodically, so that a request never waits on a usage write and a burst of
+//! quotes costs one upsert per key per flush rather than one per quote.
+//! Increments buffered since the last flush are lost if the process exits
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::NaiveDate;
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::{
+    log_task,
+    logger::{Outcome, Task},
+    server::db::{DbPool, models::ApiKeyUsage, queries::record_api_key_usage},
+};
+
+/// The interval at which buffered usage is flushed to the database
+const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The usage increments awaiting a flush, summed per key, chain, and day
+#[derive(Default)]
+struct PendingUsage {
+    /// The summed increments, keyed by API key, chain ID, and day
+    by_key_day: DashMap<(Uuid, i64, NaiveDate), ApiKeyUsage>,
+}
+
+impl PendingUsage {
+    /// Sum an increment into the pending usage for its key, chain, and day
+    fn add(&self, usage: ApiKeyUsage) {
+        self.by_key_day
+            .entry((usage.api_key_id, usage.chain_id, usage.day))
+            .and_modify(|pending| add_usage(pending, &usage))
+            .or_insert(usage);
+    }
+
+    /// Take all pending increments, leaving the buffer empty
+    ///
+    /// Entries are removed individually so that increments recorded
+    /// concurrently are either taken or left for the next flush
+    fn take(&self) -> Vec<ApiKeyUsage> {
+        let keys: Vec<(Uuid, i64, NaiveDate)> =
+            self.by_key_day.iter().map(|entry| *entry.key()).collect();
+        keys.iter().filter_map(|key| self.by_key_day.remove(key)).map(|(_, usage)| usage).collect()
+    }
+}
+
+/// Records usage increments, flushing them to the database in the background
+#[derive(Clone)]
+pub struct UsageRecorder {
+    /// The database connection pool
+    db_pool: DbPool,
+    /// The increments awaiting a flush
+    pending: Arc<PendingUsage>,
+}
+
+impl UsageRecorder {
+    /// Constructor
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool, pending: Arc::new(PendingUsage::default()) }
+    }
+
+    /// Record a usage increment, to be written on the next flush
+    pub fn record(&self, usage: ApiKeyUsage) {
+        self.pending.add(usage);
+    }
+
+    /// Spawn a task which periodically flushes buffered usage
+    pub fn spawn_flusher(&self) {
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(USAGE_FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                self_clone.flush().await;
+            }
+        });
+    }
+
+    /// Write all buffered usage to the database
+    ///
+    /// Increments which fail to write are returned to the buffer and retried
+    /// on the next flush
+    async fn flush(&self) {
+        for usage in self.pending.take() {
+            if let Err(e) = record_api_key_usage(&self.db_pool, &usage).await {
+                log_task!(
+                    Task::Usage,
+                    Outcome::Failed,
+                    subject = %usage.api_key_id,
+                    error = %e,
+                    "error flushing usage, retrying on next flush"
+                );
+                self.pending.add(usage);
+            }
+        }
+    }
+}
+
+/// Sum a usage increment into another for the same key, chain, and day
+fn add_usage(pending: &mut ApiKeyUsage, usage: &ApiKeyUsage) {
+    pending.quote_count += usage.quote_count;
+    pending.bundle_count += usage.bundle_count;
+    pending.settlement_count += usage.settlement_count;
+    pending.settled_volume_usd += usage.settled_volume_usd;
+    pending.fees_paid_usd += usage.fees_paid_usd;
+    pending.gas_sponsored_usd += usage.gas_sponsored_usd;
+}
+
+#[cfg(test)]
+mod tests {
+    use renegade_types_core::Chain;
+
+    use super::*;
+
+    #[test]
+    fn increments_are_summed_per_key_chain_and_day() {
+        let pending = PendingUsage::default();
+        let (key1, key2) = (Uuid::new_v4(), Uuid::new_v4());
+        let chain = Chain::ArbitrumOne;
+
+        pending.add(ApiKeyUsage::quote(key1, chain));
+        pending.add(ApiKeyUsage::quote(key1, chain));
+        pending.add(ApiKeyUsage::bundle(key1, chain));
+        pending.add(ApiKeyUsage::quote(key2, chain));
+        pending.add(ApiKeyUsage::settlement(key2, Chain::BaseMainnet, 100., 0.1));
+
+        let mut taken = pending.take();
+        taken.sort_by_key(|usage| usage.quote_count);
+        assert_eq!(taken.len(), 3);
+        assert_eq!(
+            (taken[0].api_key_id, taken[0].chain_id, taken[0].settlement_count),
+            (key2, 8453, 1)
+        );
+        assert_eq!(
+            (taken[1].api_key_id, taken[1].quote_count, taken[1].bundle_count),
+            (key2, 1, 0)
+        );
+        assert_eq!(
+            (taken[2].api_key_id, taken[2].quote_count, taken[2].bundle_count),
+            (key1, 2, 1)
+        );
+
+        // The buffer is empty once taken
+        assert!(pending.take().is_empty());
+    }
+}
@@ -12,7 +12,7 @@ use renegade_darkpool_types::bounded_match_result::BoundedMatchResult;
 use renegade_external_api::types::{
     ApiBoundedMatchResult, BoundedExternalMatchApiBundle, ExternalOrder,
 };
-use renegade_types_core::Token;
+use renegade_types_core::{Chain, Token};
 use renegade_util::hex::address_to_hex_string;
 use renegade_util::metrics;
 
@@ -20,7 +20,9 @@ use crate::{
     error::AuthServerError,
     server::helpers::get_external_party_amount_in,
     server::{
-        api_handlers::get_base_and_quote_amount_with_price, helpers::pick_base_and_quote_mints,
+        api_handlers::get_base_and_quote_amount_with_price,
+        chains::{token_on_chain, usdc_on_chain},
+        helpers::pick_base_and_quote_mints,
     },
     telemetry::labels::{
         ASSET_METRIC_TAG, BASE_ASSET_METRIC_TAG, EXTERNAL_MATCH_BASE_VOLUME,
@@ -30,8 +32,9 @@ use crate::{
 };
 
 use super::labels::{
-    KEY_DESCRIPTION_METRIC_TAG, NUM_EXTERNAL_MATCH_REQUESTS, QUOTE_NOT_FOUND_COUNT,
-    REQUEST_PATH_METRIC_TAG, SIDE_TAG,
+    API_KEY_AUTH_COUNT, KEY_DESCRIPTION_METRIC_TAG, NUM_EXTERNAL_MATCH_REQUESTS,
+    PREVIOUS_SECRET_METRIC_TAG, QUOTE_NOT_FOUND_COUNT, REQUEST_PATH_METRIC_TAG,
+    SECRET_GENERATION_METRIC_TAG, SIDE_TAG,
 };
 
 /// Maximum quote volume (in decimal whole-unit terms) for which we still record
@@ -47,8 +50,8 @@ const MAX_EXTERNAL_ORDER_QUOTE_VOLUME: f64 = 10_000_000.0;
 /// the token's address.
 /// The amount is the decimal amount of the transfer, going through
 /// lossy f64 conversion via the associated number of decimals
-fn get_asset_and_volume(mint: &str, amount: u128) -> (String, f64) {
-    let token = Token::from_addr(mint);
+fn get_asset_and_volume(mint: &str, amount: u128, chain: Chain) -> (String, f64) {
+    let token = Token::from_addr_on_chain(mint, chain);
     let asset = token.get_ticker().unwrap_or(mint.to_string());
     let volume = token.convert_to_decimal(amount);
 
@@ -62,12 +65,13 @@ fn get_asset_and_volume(mint: &str, amount: u128) -> (String, f64) {
 /// difference in decimal places between quote and base tokens
 pub(crate) fn calculate_quote_per_base_price(
     match_result: &ApiBoundedMatchResult,
+    chain: Chain,
 ) -> Result<f64, AuthServerError> {
     let out_per_in_price = match_result.price_fp.to_f64();
 
     let input_mint = match_result.input_mint;
     let output_mint = match_result.output_mint;
-    let (base_mint, quote_mint) = pick_base_and_quote_mints(input_mint, output_mint)?;
+    let (base_mint, quote_mint) = pick_base_and_quote_mints(input_mint, output_mint, chain)?;
 
     let quote_per_base_price =
         if quote_mint == output_mint { out_per_in_price } else { 1.0 / out_per_in_price };
@@ -75,12 +79,12 @@ pub(crate) fn calculate_quote_per_base_price(
     let trades_native_asset =
         address_to_hex_string(&base_mint) == NATIVE_ASSET_ADDRESS.to_lowercase();
     let base_token = if trades_native_asset {
-        Token::from_ticker(NATIVE_ASSET_WRAPPER_TICKER)
+        Token::from_ticker_on_chain(NATIVE_ASSET_WRAPPER_TICKER, chain)
     } else {
-        Token::from_alloy_address(&base_mint)
+        token_on_chain(&base_mint, chain)
     };
 
-    let quote_token = Token::from_alloy_address(&quote_mint);
+    let quote_token = token_on_chain(&quote_mint, chain);
 
     let base_decimals = base_token.get_decimals().ok_or_else(|| {
         AuthServerError::Serde(format!("No decimals for {}", base_token.get_addr()))
@@ -99,8 +103,9 @@ pub(crate) fn calculate_quote_per_base_price(
 pub(crate) fn extend_labels_with_base_asset(
     base_mint: &Address,
     mut labels: Vec<(String, String)>,
+    chain: Chain,
 ) -> Vec<(String, String)> {
-    let base_token = Token::from_alloy_address(base_mint);
+    let base_token = token_on_chain(base_mint, chain);
     let base_asset = base_token.get_ticker().unwrap_or(address_to_hex_string(base_mint));
 
     labels.insert(0, (BASE_ASSET_METRIC_TAG.to_string(), base_asset));
@@ -116,8 +121,9 @@ pub(crate) fn extend_labels_with_side(
     input_mint: Address,
     output_mint: Address,
     mut labels: Vec<(String, String)>,
+    chain: Chain,
 ) -> Result<Vec<(String, String)>, AuthServerError> {
-    let (base_mint, _) = pick_base_and_quote_mints(input_mint, output_mint)?;
+    let (base_mint, _) = pick_base_and_quote_mints(input_mint, output_mint, chain)?;
 
     // External party buys if they receive the base token (base == output)
     let side_label = if base_mint == output_mint { "buy" } else { "sell" };
@@ -132,8 +138,9 @@ pub(crate) fn record_volume_with_tags(
     amount: u128,
     volume_metric_name: &'static str,
     extra_labels: &[(String, String)],
+    chain: Chain,
 ) {
-    let (asset, volume) = get_asset_and_volume(mint, amount);
+    let (asset, volume) = get_asset_and_volume(mint, amount, chain);
     let mut labels = vec![(ASSET_METRIC_TAG.to_string(), asset)];
     let extra_labels = extra_labels.iter().map(|(k, v)| (k.clone(), v.clone()));
     labels.extend(extra_labels);
@@ -148,36 +155,45 @@ fn record_external_match_request_metrics(
     order: &ExternalOrder,
     price: f64,
     labels: &[(String, String)],
+    chain: Chain,
 ) -> Result<(), AuthServerError> {
     // Record external order volume
-    let (base_mint, quote_mint) = pick_base_and_quote_mints(order.input_mint, order.output_mint)?;
-    let labels = extend_labels_with_base_asset(&base_mint, labels.to_vec());
+    let (base_mint, quote_mint) =
+        pick_base_and_quote_mints(order.input_mint, order.output_mint, chain)?;
+    let labels = extend_labels_with_base_asset(&base_mint, labels.to_vec(), chain);
 
     let relayer_fee = FixedPoint::from_f64_round_down(DEFAULT_EXTERNAL_MATCH_RELAYER_FEE);
 
     // Calculate base and quote amounts
     let (base_amount, quote_amount) =
-        get_base_and_quote_amount_with_price(order, relayer_fee, price)?;
+        get_base_and_quote_amount_with_price(order, relayer_fee, price, chain)?;
 
     // Calculate the decimal quote volume to enforce the cap.
-    let quote_token = Token::from_alloy_address(&quote_mint);
+    let quote_token = token_on_chain(&quote_mint, chain);
     let quote_volume_decimal = quote_token.convert_to_decimal(quote_amount);
     let should_record_volume = quote_volume_decimal <= MAX_EXTERNAL_ORDER_QUOTE_VOLUME;
 
     let base_mint_str = address_to_hex_string(&base_mint);
     if should_record_volume {
         // Record base/quote volumes using the original pattern.
-        record_volume_with_tags(&base_mint_str, base_amount, EXTERNAL_ORDER_BASE_VOLUME, &labels);
+        record_volume_with_tags(
+            &base_mint_str,
+            base_amount,
+            EXTERNAL_ORDER_BASE_VOLUME,
+            &labels,
+            chain,
+        );
         record_volume_with_tags(
             &address_to_hex_string(&quote_mint),
             quote_amount,
             EXTERNAL_ORDER_QUOTE_VOLUME,
             &labels,
+            chain,
         );
     }
 
     // Always record request count metric.
-    record_endpoint_metrics(&base_mint_str, NUM_EXTERNAL_MATCH_REQUESTS, &labels);
+    record_endpoint_metrics(&base_mint_str, NUM_EXTERNAL_MATCH_REQUESTS, &labels, chain);
 
     Ok(())
 }
@@ -186,27 +202,31 @@ fn record_external_match_request_metrics(
 fn record_external_match_response_metrics(
     match_bundle: &BoundedExternalMatchApiBundle,
     labels: &[(String, String)],
+    chain: Chain,
 ) -> Result<(), AuthServerError> {
     let (base_mint, quote_mint) = pick_base_and_quote_mints(
         match_bundle.match_result.input_mint,
         match_bundle.match_result.output_mint,
+        chain,
     )?;
 
-    let base_amount = get_default_base_amount(match_bundle)?;
+    let base_amount = get_default_base_amount(match_bundle, chain)?;
     record_volume_with_tags(
         &address_to_hex_string(&base_mint),
         base_amount,
         EXTERNAL_MATCH_BASE_VOLUME,
         labels,
+        chain,
     );
 
-    let quote_amount = get_default_quote_amount(match_bundle)?;
-    let labels = extend_labels_with_base_asset(&base_mint, labels.to_vec());
+    let quote_amount = get_default_quote_amount(match_bundle, chain)?;
+    let labels = extend_labels_with_base_asset(&base_mint, labels.to_vec(), chain);
     record_volume_with_tags(
         &address_to_hex_string(&quote_mint),
         quote_amount,
         EXTERNAL_MATCH_QUOTE_VOLUME,
         &labels,
+        chain,
     );
 
     Ok(())
@@ -217,8 +237,9 @@ pub(crate) fn record_endpoint_metrics(
     mint: &str,
     metric_name: &'static str,
     extra_labels: &[(String, String)],
+    chain: Chain,
 ) {
-    let (asset, _) = get_asset_and_volume(mint, 0);
+    let (asset, _) = get_asset_and_volume(mint, 0, chain);
     let mut labels = vec![(ASSET_METRIC_TAG.to_string(), asset)];
     labels.extend(extra_labels.iter().cloned());
     metrics::counter!(metric_name, &labels).increment(1);
@@ -240,12 +261,13 @@ pub(crate) fn record_external_match_metrics(
     order: &ExternalOrder,
     match_bundle: &BoundedExternalMatchApiBundle,
     labels: &[(String, String)],
+    chain: Chain,
 ) -> Result<(), AuthServerError> {
     // Get decimal-corrected quote / base price
-    let price = calculate_quote_per_base_price(&match_bundle.match_result)?;
+    let price = calculate_quote_per_base_price(&match_bundle.match_result, chain)?;
 
     // Record request metrics
-    if let Err(e) = record_external_match_request_metrics(order, price, labels) {
+    if let Err(e) = record_external_match_request_metrics(order, price, labels, chain) {
         log_task!(
             Task::Telemetry,
             Outcome::Partial,
@@ -259,9 +281,9 @@ pub(crate) fn record_external_match_metrics(
 
     // Record fill ratio metric
     let (_, requested_quote_amount) =
-        get_base_and_quote_amount_with_price(order, relayer_fee, price)?;
+        get_base_and_quote_amount_with_price(order, relayer_fee, price, chain)?;
 
-    let matched_quote_amount = get_default_quote_amount(match_bundle)?;
+    let matched_quote_amount = get_default_quote_amount(match_bundle, chain)?;
     if let Err(e) = record_fill_ratio(requested_quote_amount, matched_quote_amount, labels) {
         log_task!(
             Task::Telemetry,
@@ -273,7 +295,7 @@ pub(crate) fn record_external_match_metrics(
     }
 
     // Record response metrics
-    if let Err(e) = record_external_match_response_metrics(match_bundle, labels) {
+    if let Err(e) = record_external_match_response_metrics(match_bundle, labels, chain) {
         log_task!(
             Task::Telemetry,
             Outcome::Partial,
@@ -296,10 +318,26 @@ pub(crate) fn record_relayer_request_500(key_description: String, path: String)
     metrics::counter!(UNSUCCESSFUL_RELAYER_REQUEST_COUNT, &labels).increment(1);
 }
 
+/// Record a counter metric for a request authenticated with an API key,
+/// tagged with the generation of the secret used
+pub(crate) fn record_api_key_auth(
+    key_description: String,
+    secret_generation: i32,
+    is_previous_secret: bool,
+) {
+    let labels = vec![
+        (KEY_DESCRIPTION_METRIC_TAG.to_string(), key_description),
+        (SECRET_GENERATION_METRIC_TAG.to_string(), secret_generation.to_string()),
+        (PREVIOUS_SECRET_METRIC_TAG.to_string(), is_previous_secret.to_string()),
+    ];
+
+    metrics::counter!(API_KEY_AUTH_COUNT, &labels).increment(1);
+}
+
 /// Record a counter metric for quote requests for which the relayer could not
 /// produce a quote
-pub(crate) fn record_quote_not_found(key_description: String, base_mint: &str) {
-    let base_token = Token::from_addr(base_mint);
+pub(crate) fn record_quote_not_found(key_description: String, base_mint: &str, chain: Chain) {
+    let base_token = Token::from_addr_on_chain(base_mint, chain);
     let base_asset = base_token.get_ticker().unwrap_or(base_mint.to_string());
 
     let labels = vec![
@@ -314,13 +352,14 @@ pub(crate) fn record_quote_not_found(key_description: String, base_mint: &str) {
 /// `externalPartyAmountIn` calldata field in the match bundle
 pub(crate) fn get_default_base_amount(
     match_bundle: &BoundedExternalMatchApiBundle,
+    chain: Chain,
 ) -> Result<u128, AuthServerError> {
     let match_res = BoundedMatchResult::from(match_bundle.match_result.clone());
     let calldata = match_bundle.settlement_tx.input.input().unwrap_or_default();
     let amount_in = get_external_party_amount_in(calldata)?;
 
     // Get a settlement obligation for the external party
-    let usdc_addr = Token::usdc().get_alloy_address();
+    let usdc_addr = usdc_on_chain(chain).get_alloy_address();
     let obligation = match_res.to_external_obligation(amount_in);
     let base_amt = if obligation.input_token == usdc_addr {
         obligation.amount_out
@@ -335,13 +374,14 @@ pub(crate) fn get_default_base_amount(
 /// `externalPartyAmountIn` calldata field in the match bundle
 pub(crate) fn get_default_quote_amount(
     match_bundle: &BoundedExternalMatchApiBundle,
+    chain: Chain,
 ) -> Result<u128, AuthServerError> {
     let match_res = BoundedMatchResult::from(match_bundle.match_result.clone());
     let calldata = match_bundle.settlement_tx.input.input().unwrap_or_default();
     let amount_in = get_external_party_amount_in(calldata)?;
 
     // Get a settlement obligation for the external party
-    let usdc_addr = Token::usdc().get_alloy_address();
+    let usdc_addr = usdc_on_chain(chain).get_alloy_address();
     let obligation = match_res.to_external_obligation(amount_in);
     let quote_amt = if obligation.input_token == usdc_addr {
         obligation.amount_in
@@ -48,6 +48,9 @@ pub const UNSUCCESSFUL_RELAYER_REQUEST_COUNT: &str = "num_unsuccessful_relayer_r
 /// Metric describing the number of times a quote was not found
 pub const QUOTE_NOT_FOUND_COUNT: &str = "num_quotes_not_found";
 
+/// Metric describing the number of requests authenticated with an API key
+pub const API_KEY_AUTH_COUNT: &str = "num_api_key_authentications";
+
 /// Metric describing the cost experienced by the internal party
 /// in an external match due to the spread between the match price
 /// and the reference price at the time of settlement
@@ -73,6 +76,13 @@ pub const REQUEST_PATH_METRIC_TAG: &str = "request_path";
 /// Metric tag for the SDK version of the request
 pub const SDK_VERSION_METRIC_TAG: &str = "sdk_version";
 
+/// Metric tag for the generation of the API secret that authenticated a
+/// request
+pub const SECRET_GENERATION_METRIC_TAG: &str = "secret_generation";
+/// Metric tag indicating that a request was authenticated with a previous API
+/// secret, i.e. one in its rotation grace period
+pub const PREVIOUS_SECRET_METRIC_TAG: &str = "previous_secret";
+
 /// Metric tag for identifying the order side (buy/sell)
 pub const SIDE_TAG: &str = "side";
 
@@ -0,0 +1,406 @@
+//! Delivers partner webhook notifications
+//!
+//! Settlement, refund, and expiry events are POSTed to the webhook URL
+//! configured on the API key that assembled the bundle. Every notification is
+//! recorded in the delivery log, and failed deliveries are retried with
+//! exponential backoff. The retry schedule is persisted in the delivery log,
+//! so that retries survive a restart
+
+use std::time::{Duration, SystemTime};
+
+use aes_gcm::Aes128Gcm;
+use auth_server_api::webhooks::{
+    WEBHOOK_SIGNATURE_HEADER, WEBHOOK_TIMESTAMP_HEADER, WebhookDeliveryStatus, WebhookEvent,
+    WebhookPayload,
+};
+use base64::{Engine, engine::general_purpose};
+use renegade_types_core::HmacKey;
+use renegade_util::get_current_time_millis;
+use reqwest::{Client, header::CONTENT_TYPE};
+use uuid::Uuid;
+
+use crate::{
+    bundle_store::BundleStore,
+    error::AuthServerError,
+    log_task,
+    logger::{Outcome, Task},
+    server::{
+        db::{
+            DbPool,
+            models::{NewWebhookDelivery, WebhookAttemptChangeset, WebhookDelivery},
+            queries::{
+                claim_due_webhook_deliveries, get_webhook_target, insert_webhook_delivery,
+                update_webhook_delivery,
+            },
+        },
+        helpers::aes_decrypt,
+        rate_limiter::AuthServerRateLimiter,
+    },
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The timeout on a single webhook request
+const WEBHOOK_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// The maximum number of attempts made to deliver a notification
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+/// The delay before the first retry, doubled on each subsequent retry
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+/// The interval at which the delivery log is polled for due retries
+const RETRY_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// The maximum number of due retries claimed per poll
+const MAX_RETRIES_PER_POLL: i64 = 100;
+/// The time for which an attempt holds its delivery
+///
+/// An attempt that is not recorded within the lease, e.g. because the replica
+/// making it exited, is picked up again by the retry worker
+const DELIVERY_LEASE: Duration = Duration::from_secs(60);
+/// The interval at which the bundle store is checked for expired bundles
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// The time after a bundle's deadline before it is considered expired
+///
+/// Gives the chain listener time to observe settlements made close to the
+/// deadline
+const EXPIRY_GRACE_PERIOD_SECS: u64 = 60;
+/// The error recorded on a delivery whose key no longer has a webhook
+const ERR_WEBHOOK_REMOVED: &str = "webhook no longer configured";
+
+// --------------
+// | Dispatcher |
+// --------------
+
+/// Dispatches webhook notifications to partners in the background
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    /// The database pool, used to look up webhook URLs and record deliveries
+    db_pool: DbPool,
+    /// The encryption key for API secrets, which sign the notifications
+    encryption_key: Aes128Gcm,
+    /// The HTTP client used to deliver notifications
+    client: Client,
+}
+
+impl WebhookDispatcher {
+    /// Constructor
+    pub fn new(db_pool: DbPool, encryption_key: Aes128Gcm) -> Self {
+        Self { db_pool, encryption_key, client: Client::new() }
+    }
+
+    /// Notify an API key of an event
+    ///
+    /// The first attempt happens in the background; failed attempts are
+    /// scheduled in the delivery log and retried by the retry worker. Keys
+    /// without a webhook URL are skipped
+    pub fn notify(&self, key_id: Uuid, event: WebhookEvent) {
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = self_clone.deliver(key_id, &event).await {
+                log_task!(
+                    Task::Webhooks,
+                    Outcome::Failed,
+                    subject = event.event_type(),
+                    key_id = %key_id,
+                    bundle_id = event.bundle_id(),
+                    error = %e,
+                    "failed to deliver webhook"
+                );
+            }
+        });
+    }
+
+    /// Periodically retry the pending deliveries whose next attempt is due
+    ///
+    /// The schedule lives in the delivery log, so deliveries left pending by a
+    /// restart are resumed once due
+    pub fn spawn_retry_worker(&self) {
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RETRY_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self_clone.retry_due_deliveries().await {
+                    log_task!(
+                        Task::Webhooks,
+                        Outcome::Failed,
+                        subject = "retry",
+                        error = %e,
+                        "failed to retry due webhook deliveries"
+                    );
+                }
+            }
+        });
+    }
+
+    /// Periodically notify keys of bundles that passed their deadline without
+    /// settling, refunding the volume the bundles consumed
+    pub fn spawn_expiry_watcher(
+        &self,
+        bundle_store: BundleStore,
+        rate_limiter: AuthServerRateLimiter,
+    ) {
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EXPIRY_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) =
+                    self_clone.notify_expired_bundles(&bundle_store, &rate_limiter).await
+                {
+                    log_task!(
+                        Task::Webhooks,
+                        Outcome::Failed,
+                        subject = "bundle-expiry",
+                        error = %e,
+                        "failed to check for expired bundles"
+                    );
+                }
+            }
+        });
+    }
+
+    // -----------
+    // | Helpers |
+    // -----------
+
+    /// Notify keys of every expired bundle in the store, and refund the volume
+    /// each bundle consumed
+    ///
+    /// Expired bundles are taken from the store, so each is notified and
+    /// refunded once across replicas
+    async fn notify_expired_bundles(
+        &self,
+        bundle_store: &BundleStore,
+        rate_limiter: &AuthServerRateLimiter,
+    ) -> Result<(), AuthServerError> {
+        let now_secs = get_current_time_millis() / 1000;
+        let cutoff = now_secs.saturating_sub(EXPIRY_GRACE_PERIOD_SECS);
+        for ctx in bundle_store.take_expired(cutoff).await? {
+            if let Some(consumption) = &ctx.volume_consumption
+                && let Err(e) = rate_limiter.refund_volume(consumption).await
+            {
+                log_task!(
+                    Task::RateLimit,
+                    Outcome::Failed,
+                    subject = "volume-refund",
+                    bundle_id = %ctx.bundle_id,
+                    error = %e,
+                    "failed to refund volume of expired bundle"
+                );
+            }
+
+            let event = WebhookEvent::BundleExpired {
+                bundle_id: ctx.bundle_id.to_string(),
+                request_id: ctx.request_id,
+                deadline: ctx.deadline,
+            };
+            self.notify(ctx.key_id, event);
+        }
+
+        Ok(())
+    }
+
+    /// Record a delivery of an event to a key's webhook and make its first
+    /// attempt
+    async fn deliver(&self, key_id: Uuid, event: &WebhookEvent) -> Result<(), AuthServerError> {
+        let (url, encrypted_secret) = match get_webhook_target(&self.db_pool, key_id).await? {
+            Some(target) => target,
+            None => return Ok(()), // No webhook configured
+        };
+
+        // Record the delivery under a lease, so that the retry worker resumes
+        // it if this replica exits before the attempt is recorded
+        let event_json = serde_json::to_string(event).map_err(AuthServerError::serde)?;
+        let delivery = NewWebhookDelivery::new(
+            key_id,
+            event.event_type(),
+            event.bundle_id(),
+            url.clone(),
+            event_json,
+            SystemTime::now() + DELIVERY_LEASE,
+        );
+        let delivery_id = insert_webhook_delivery(&self.db_pool, &delivery).await?;
+
+        let attempt = 1;
+        self.attempt(delivery_id, key_id, &url, event, &encrypted_secret, attempt).await
+    }
+
+    /// Retry every pending delivery whose next attempt is due
+    async fn retry_due_deliveries(&self) -> Result<(), AuthServerError> {
+        let due = claim_due_webhook_deliveries(&self.db_pool, MAX_RETRIES_PER_POLL, DELIVERY_LEASE)
+            .await?;
+
+        for delivery in due {
+            let self_clone = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = self_clone.retry(&delivery).await {
+                    log_task!(
+                        Task::Webhooks,
+                        Outcome::Failed,
+                        subject = %delivery.event_type,
+                        delivery_id = delivery.id,
+                        error = %e,
+                        "failed to retry webhook delivery"
+                    );
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Retry a claimed delivery
+    ///
+    /// The delivery is failed outright if its key no longer has a webhook
+    async fn retry(&self, delivery: &WebhookDelivery) -> Result<(), AuthServerError> {
+        let attempt = delivery.attempts as u32 + 1;
+        let encrypted_secret = match get_webhook_target(&self.db_pool, delivery.api_key_id).await? {
+            Some((_, encrypted_secret)) => encrypted_secret,
+            None => {
+                let status = WebhookDeliveryStatus::Failed;
+                let error = Some(ERR_WEBHOOK_REMOVED.to_string());
+                let changeset = WebhookAttemptChangeset::new(status, attempt, None, error, None);
+                return update_webhook_delivery(&self.db_pool, delivery.id, &changeset).await;
+            },
+        };
+
+        let event: WebhookEvent =
+            serde_json::from_str(&delivery.payload).map_err(AuthServerError::serde)?;
+        self.attempt(
+            delivery.id,
+            delivery.api_key_id,
+            &delivery.url,
+            &event,
+            &encrypted_secret,
+            attempt,
+        )
+        .await
+    }
+
+    /// Make the given attempt at a delivery and record its result, scheduling
+    /// the next attempt if the delivery failed and attempts remain
+    async fn attempt(
+        &self,
+        delivery_id: i64,
+        key_id: Uuid,
+        url: &str,
+        event: &WebhookEvent,
+        encrypted_secret: &str,
+        attempt: u32,
+    ) -> Result<(), AuthServerError> {
+        // Sign the payload with the key's current secret
+        let payload = WebhookPayload { delivery_id, api_key_id: key_id, event: event.clone() };
+        let body = serde_json::to_vec(&payload).map_err(AuthServerError::serde)?;
+        let secret = aes_decrypt(encrypted_secret, &self.encryption_key)?;
+        let key = HmacKey::from_base64_string(&secret).map_err(AuthServerError::serde)?;
+
+        let (status_code, error) = self.attempt_delivery(url, &body, &key).await;
+        let (status, retry_in) = attempt_outcome(error.is_none(), attempt);
+        let next_attempt_at = retry_in.map(|delay| SystemTime::now() + delay);
+        let changeset =
+            WebhookAttemptChangeset::new(status, attempt, status_code, error, next_attempt_at);
+        update_webhook_delivery(&self.db_pool, delivery_id, &changeset).await?;
+
+        match status {
+            WebhookDeliveryStatus::Delivered => Ok(()),
+            WebhookDeliveryStatus::Pending => {
+                log_task!(
+                    Task::Webhooks,
+                    Outcome::Retrying,
+                    subject = event.event_type(),
+                    delivery_id = delivery_id,
+                    attempt = attempt,
+                    "webhook delivery failed, retrying"
+                );
+                Ok(())
+            },
+            WebhookDeliveryStatus::Failed => Err(AuthServerError::custom(format!(
+                "delivery {delivery_id} failed after {MAX_DELIVERY_ATTEMPTS} attempts"
+            ))),
+        }
+    }
+
+    /// Make a single attempt at delivering a signed body
+    ///
+    /// Returns the response status code, if a response was received, and an
+    /// error if the attempt failed
+    async fn attempt_delivery(
+        &self,
+        url: &str,
+        body: &[u8],
+        key: &HmacKey,
+    ) -> (Option<u16>, Option<String>) {
+        let timestamp = get_current_time_millis();
+        let signature = sign_webhook_body(key, timestamp, body);
+        let res = self
+            .client
+            .post(url)
+            .timeout(WEBHOOK_REQUEST_TIMEOUT)
+            .header(CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string())
+            .header(WEBHOOK_SIGNATURE_HEADER, signature)
+            .body(body.to_vec())
+            .send()
+            .await;
+
+        match res {
+            Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16()), None),
+            Ok(resp) => {
+                let status = resp.status();
+                (Some(status.as_u16()), Some(format!("unexpected status: {status}")))
+            },
+            Err(e) => (e.status().map(|status| status.as_u16()), Some(e.to_string())),
+        }
+    }
+}
+
+/// Get the status of a delivery after the given attempt, and the delay before
+/// its next attempt if it remains pending
+fn attempt_outcome(delivered: bool, attempt: u32) -> (WebhookDeliveryStatus, Option<Duration>) {
+    if delivered {
+        (WebhookDeliveryStatus::Delivered, None)
+    } else if attempt >= MAX_DELIVERY_ATTEMPTS {
+        (WebhookDeliveryStatus::Failed, None)
+    } else {
+        (WebhookDeliveryStatus::Pending, Some(INITIAL_RETRY_BACKOFF * 2u32.pow(attempt - 1)))
+    }
+}
+
+/// Sign a webhook body sent at the given timestamp
+///
+/// The signature is the base64 encoded HMAC of `{timestamp}.{body}`
+fn sign_webhook_body(key: &HmacKey, timestamp: u64, body: &[u8]) -> String {
+    let mut message = format!("{timestamp}.").into_bytes();
+    message.extend_from_slice(body);
+    general_purpose::STANDARD.encode(key.compute_mac(&message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let key = HmacKey::random();
+        let body = br#"{"delivery_id":1}"#;
+        let signature = sign_webhook_body(&key, 1_000, body);
+
+        let mac = general_purpose::STANDARD.decode(&signature).unwrap();
+        assert!(key.verify_mac(br#"1000.{"delivery_id":1}"#, &mac));
+        assert_ne!(signature, sign_webhook_body(&key, 1_001, body));
+        assert_ne!(signature, sign_webhook_body(&key, 1_000, b"{}"));
+    }
+
+    #[test]
+    fn failed_attempts_back_off_until_exhausted() {
+        let delays: Vec<Option<Duration>> =
+            (1..=MAX_DELIVERY_ATTEMPTS).map(|attempt| attempt_outcome(false, attempt).1).collect();
+        let secs = |secs| Some(Duration::from_secs(secs));
+        assert_eq!(delays, vec![secs(30), secs(60), secs(120), secs(240), None]);
+
+        let (status, _) = attempt_outcome(false, MAX_DELIVERY_ATTEMPTS);
+        assert_eq!(status, WebhookDeliveryStatus::Failed);
+        assert_eq!(attempt_outcome(true, 2), (WebhookDeliveryStatus::Delivered, None));
+    }
+}
@@ -88,6 +88,9 @@ pub struct Cli {
     /// The URL of the price reporter
     #[clap(long, env = "PRICE_REPORTER_URL")]
     pub price_reporter_url: String,
+    /// The URLs of fallback price reporters, in order of preference
+    #[clap(long, env = "PRICE_REPORTER_FALLBACK_URLS", value_delimiter = ',')]
+    pub price_reporter_fallback_urls: Vec<String>,
 
     // --- Chain-Specific Config --- //
 
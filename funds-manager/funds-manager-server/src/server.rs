@@ -60,6 +60,7 @@ impl Server {
 
         let price_reporter = PriceReporterClient::new(PriceReporterClientConfig {
             base_url: args.price_reporter_url.clone(),
+            fallback_urls: args.price_reporter_fallback_urls.clone(),
             ..Default::default()
         })?;
 
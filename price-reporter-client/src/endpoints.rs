@@ -0,0 +1,480 @@
+//! Failover between multiple price reporter endpoints
+//!
+//! The client may be configured with several price reporter endpoints, in
+//! order of preference. A background task health-checks every endpoint, and
+//! requests are served by the active endpoint, failing over to the next
+//! healthy one when it errors. Once a more preferred endpoint passes its health
+//! check again, the active endpoint fails back to it.
+//!
+//! Only transport errors and server errors count as endpoint failures. A client
+//! error, e.g. for an unknown mint, is the request's fault and is returned to
+//! the caller without failing over.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use metrics::{counter, gauge};
+use reqwest::{Client, Response, Url};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{
+    PRICE_ROUTE, construct_price_topic,
+    error::PriceReporterClientError,
+    price_stream::{PriceInfo, PriceMessage},
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The price reporter's websocket port
+const WS_PORT: u16 = 4000;
+/// The route for the health check endpoint
+const HEALTH_CHECK_ROUTE: &str = "/health";
+/// The query string selecting a JSON response from the price endpoint
+const JSON_FORMAT_QUERY: &str = "format=json";
+/// The interval at which endpoints are health-checked
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The metric counting switches of the active endpoint
+const ENDPOINT_FAILOVERS_METRIC: &str = "price_reporter_client_endpoint_failovers";
+/// The metric counting switches back to a more preferred endpoint
+const ENDPOINT_FAILBACKS_METRIC: &str = "price_reporter_client_endpoint_failbacks";
+/// The metric reporting the health of each endpoint
+const ENDPOINT_HEALTHY_METRIC: &str = "price_reporter_client_endpoint_healthy";
+
+// ---------
+// | Types |
+// ---------
+
+/// A single price reporter endpoint
+#[derive(Debug)]
+struct Endpoint {
+    /// The base URL of the endpoint's HTTP server
+    base_url: String,
+    /// The URL of the endpoint's websocket server
+    ws_url: String,
+    /// Whether the endpoint passed its last health check or request
+    healthy: AtomicBool,
+}
+
+/// The set of price reporter endpoints available to the client
+#[derive(Debug)]
+pub struct Endpoints {
+    /// The endpoints, in order of preference
+    endpoints: Vec<Endpoint>,
+    /// The index of the active endpoint
+    active: AtomicUsize,
+}
+
+impl Endpoints {
+    /// Create the endpoint set from the given base URLs, in order of
+    /// preference
+    pub fn new(base_urls: Vec<String>) -> Result<Self, PriceReporterClientError> {
+        if base_urls.is_empty() {
+            return Err(PriceReporterClientError::setup("No price reporter endpoints given"));
+        }
+
+        let endpoints = base_urls
+            .into_iter()
+            .map(|base_url| {
+                let ws_url = ws_url_for(&base_url)?;
+                Ok(Endpoint { base_url, ws_url, healthy: AtomicBool::new(true) })
+            })
+            .collect::<Result<Vec<_>, PriceReporterClientError>>()?;
+
+        Ok(Self { endpoints, active: AtomicUsize::new(0) })
+    }
+
+    /// The websocket URL of the endpoint at the given index
+    pub fn ws_url(&self, idx: usize) -> &str {
+        &self.endpoints[idx].ws_url
+    }
+
+    /// The indices of the endpoints to try for a request, in order: the
+    /// active endpoint, then the other healthy endpoints in order of
+    /// preference, then the unhealthy ones
+    pub fn candidates(&self) -> Vec<usize> {
+        let active = self.active.load(Ordering::Relaxed);
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = (0..self.endpoints.len())
+            .filter(|idx| *idx != active)
+            .partition(|idx| self.is_healthy(*idx));
+
+        std::iter::once(active).chain(healthy).chain(unhealthy).collect()
+    }
+
+    /// The index of the endpoint to which the websocket should connect after
+    /// the given number of consecutive failed connection attempts
+    ///
+    /// This is the first healthy candidate, or if no endpoint is healthy, the
+    /// candidates in turn.
+    pub fn ws_candidate(&self, failed_attempts: usize) -> usize {
+        let candidates = self.candidates();
+        candidates
+            .iter()
+            .copied()
+            .find(|idx| self.is_healthy(*idx))
+            .unwrap_or(candidates[failed_attempts % candidates.len()])
+    }
+
+    /// The index of the most preferred healthy endpoint, if any
+    pub fn preferred(&self) -> Option<usize> {
+        (0..self.endpoints.len()).find(|idx| self.is_healthy(*idx))
+    }
+
+    /// Whether a healthy endpoint is preferred over the one at the given
+    /// index, so that a connection to it should fail back
+    pub fn should_fail_back(&self, idx: usize) -> bool {
+        self.preferred().is_some_and(|preferred| preferred < idx)
+    }
+
+    /// Fail back to the most preferred healthy endpoint, if it is preferred
+    /// over the active endpoint
+    pub fn fail_back(&self) {
+        let Some(preferred) = self.preferred() else { return };
+        let prev = self.active.fetch_min(preferred, Ordering::Relaxed);
+        if preferred < prev {
+            let (from, to) = (&self.endpoints[prev].base_url, &self.endpoints[preferred].base_url);
+            info!("Price reporter endpoint failed back from {from} to {to}");
+            counter!(ENDPOINT_FAILBACKS_METRIC, "from" => from.clone(), "to" => to.clone())
+                .increment(1);
+        }
+    }
+
+    /// Whether the endpoint at the given index is healthy
+    fn is_healthy(&self, idx: usize) -> bool {
+        self.endpoints[idx].healthy.load(Ordering::Relaxed)
+    }
+
+    /// Record a successful request to the endpoint at the given index, making
+    /// it the active endpoint
+    pub fn mark_success(&self, idx: usize) {
+        self.set_health(idx, true);
+
+        let prev = self.active.swap(idx, Ordering::Relaxed);
+        if prev != idx {
+            let (from, to) = (&self.endpoints[prev].base_url, &self.endpoints[idx].base_url);
+            info!("Price reporter endpoint failed over from {from} to {to}");
+            counter!(ENDPOINT_FAILOVERS_METRIC, "from" => from.clone(), "to" => to.clone())
+                .increment(1);
+        }
+    }
+
+    /// Record a failed request to the endpoint at the given index
+    pub fn mark_failure(&self, idx: usize) {
+        self.set_health(idx, false);
+    }
+
+    /// Set the health of the endpoint at the given index
+    fn set_health(&self, idx: usize, healthy: bool) {
+        let endpoint = &self.endpoints[idx];
+        let was_healthy = endpoint.healthy.swap(healthy, Ordering::Relaxed);
+        if was_healthy && !healthy {
+            warn!("Price reporter endpoint {} is unhealthy", endpoint.base_url);
+        }
+
+        let value = if healthy { 1.0 } else { 0.0 };
+        gauge!(ENDPOINT_HEALTHY_METRIC, "endpoint" => endpoint.base_url.clone()).set(value);
+    }
+}
+
+/// An HTTP client for the price reporter which fails over between endpoints
+#[derive(Debug, Clone)]
+pub struct EndpointHttpClient {
+    /// The shared HTTP client used for issuing requests
+    client: Client,
+    /// The endpoints to which requests are sent
+    endpoints: Arc<Endpoints>,
+}
+
+impl EndpointHttpClient {
+    /// Create a new HTTP client over the given endpoints
+    pub fn new(client: Client, endpoints: Arc<Endpoints>) -> Self {
+        Self { client, endpoints }
+    }
+
+    /// The endpoints to which requests are sent
+    pub fn endpoints(&self) -> &Arc<Endpoints> {
+        &self.endpoints
+    }
+
+    /// Get the price of a token along with its provenance and age
+    pub async fn get_price_info(&self, mint: &str) -> Result<PriceInfo, PriceReporterClientError> {
+        let topic = construct_price_topic(mint);
+        let path = format!("{PRICE_ROUTE}/{topic}?{JSON_FORMAT_QUERY}");
+        let response = self.get(&path).await?;
+
+        let message: PriceMessage =
+            response.json().await.map_err(PriceReporterClientError::parsing)?;
+        Ok(message.into())
+    }
+
+    /// Check that some endpoint is reachable, by its health check
+    pub async fn check_reachable(&self) -> Result<(), PriceReporterClientError> {
+        self.get(HEALTH_CHECK_ROUTE).await.map(|_| ())
+    }
+
+    /// Get the price of a token as plain text
+    pub async fn get_price(&self, mint: &str) -> Result<f64, PriceReporterClientError> {
+        let topic = construct_price_topic(mint);
+        let response = self.get(&format!("{PRICE_ROUTE}/{topic}")).await?;
+
+        let res_text = response.text().await.map_err(PriceReporterClientError::parsing)?;
+        res_text.parse().map_err(PriceReporterClientError::parsing)
+    }
+
+    /// Send a GET request for the given path, failing over between endpoints
+    /// until one responds without a server error
+    ///
+    /// Returns the last endpoint's error if every endpoint fails.
+    pub async fn get(&self, path: &str) -> Result<Response, PriceReporterClientError> {
+        let mut last_err = None;
+        for idx in self.endpoints.candidates() {
+            let url = format!("{}{path}", self.endpoints.endpoints[idx].base_url);
+            let err = match self.client.get(&url).send().await {
+                Ok(response) if response.status().is_server_error() => status_error(response).await,
+                Ok(response) => {
+                    self.endpoints.mark_success(idx);
+                    return error_for_status(response).await;
+                },
+                Err(e) => PriceReporterClientError::http(e),
+            };
+
+            warn!("Price reporter request to {url} failed: {err}");
+            self.endpoints.mark_failure(idx);
+            last_err = Some(err);
+        }
+
+        Err(last_err.expect("endpoint set is non-empty"))
+    }
+
+    /// Sends a basic GET request
+    async fn send_get_request(&self, url: &str) -> Result<Response, PriceReporterClientError> {
+        let response = self.client.get(url).send().await.map_err(PriceReporterClientError::http)?;
+        error_for_status(response).await
+    }
+
+    /// Spawn a task which periodically health-checks every endpoint
+    pub fn spawn_health_checks(&self) -> JoinHandle<()> {
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                self_clone.check_health().await;
+            }
+        })
+    }
+
+    /// Health-check every endpoint, failing back to the most preferred healthy
+    /// endpoint
+    async fn check_health(&self) {
+        for (idx, endpoint) in self.endpoints.endpoints.iter().enumerate() {
+            let url = format!("{}{HEALTH_CHECK_ROUTE}", endpoint.base_url);
+            let healthy = self.send_get_request(&url).await.is_ok();
+            self.endpoints.set_health(idx, healthy);
+        }
+
+        self.endpoints.fail_back();
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Return the response if it succeeded, or an error carrying its status and
+/// body otherwise
+async fn error_for_status(response: Response) -> Result<Response, PriceReporterClientError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    Err(status_error(response).await)
+}
+
+/// Build an error from an unsuccessful response's status and body
+async fn status_error(response: Response) -> PriceReporterClientError {
+    let status = response.status();
+    match response.text().await {
+        Ok(message) => PriceReporterClientError::http(format!("Status {}: {}", status, message)),
+        Err(e) => PriceReporterClientError::parsing(e),
+    }
+}
+
+/// Get the websocket URL of the price reporter with the given base URL
+fn ws_url_for(base_url: &str) -> Result<String, PriceReporterClientError> {
+    let mut ws_url: Url = base_url.parse().map_err(PriceReporterClientError::parsing)?;
+
+    if ws_url.scheme() == "https" {
+        ws_url
+            .set_scheme("wss")
+            .map_err(|_| PriceReporterClientError::setup("Error setting websocket scheme"))?;
+    } else if ws_url.scheme() == "http" {
+        ws_url
+            .set_scheme("ws")
+            .map_err(|_| PriceReporterClientError::setup("Error setting websocket scheme"))?;
+    }
+
+    ws_url
+        .set_port(Some(WS_PORT))
+        .map_err(|_| PriceReporterClientError::setup("Error setting websocket port"))?;
+
+    Ok(ws_url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Spawn a local HTTP server which answers every request with its name,
+    /// or with a 503 while `up` is false, returning its base URL
+    async fn spawn_reporter(name: &'static str, up: Arc<AtomicBool>) -> String {
+        spawn_server(name, move || if up.load(Ordering::Relaxed) { "200 OK" } else { "503 Down" })
+            .await
+    }
+
+    /// Spawn a local HTTP server which answers every request with its name and
+    /// the given status, returning its base URL
+    async fn spawn_server(
+        name: &'static str,
+        status: impl Fn() -> &'static str + Clone + Send + 'static,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let status = status.clone();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = socket.read(&mut buf).await;
+                    let status = status();
+                    let response = format!(
+                        "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{name}",
+                        name.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        format!("http://{addr}")
+    }
+
+    /// Build an endpoint set over the given number of local endpoints
+    fn endpoints(n: usize) -> Endpoints {
+        let urls = (0..n).map(|i| format!("http://reporter-{i}.local")).collect();
+        Endpoints::new(urls).unwrap()
+    }
+
+    #[test]
+    fn candidates_prefer_active_then_healthy() {
+        let endpoints = endpoints(3);
+        assert_eq!(endpoints.candidates(), vec![0, 1, 2]);
+
+        endpoints.mark_failure(0);
+        endpoints.mark_failure(1);
+        assert_eq!(endpoints.candidates(), vec![0, 2, 1]);
+
+        endpoints.mark_success(2);
+        assert_eq!(endpoints.candidates(), vec![2, 0, 1]);
+
+        // With no healthy endpoint, websocket connections rotate through them
+        endpoints.mark_failure(2);
+        assert_eq!(endpoints.ws_candidate(0), 2);
+        assert_eq!(endpoints.ws_candidate(1), 0);
+        assert_eq!(endpoints.ws_candidate(2), 1);
+    }
+
+    #[test]
+    fn fails_back_to_preferred_healthy_endpoint() {
+        let endpoints = endpoints(3);
+        endpoints.mark_failure(0);
+        endpoints.mark_failure(1);
+        endpoints.mark_success(2);
+        assert!(!endpoints.should_fail_back(2));
+
+        // A recovered endpoint which is less preferred than the active one is
+        // not failed back to
+        endpoints.set_health(1, true);
+        assert!(endpoints.should_fail_back(2));
+        endpoints.fail_back();
+        assert_eq!(endpoints.candidates(), vec![1, 2, 0]);
+
+        endpoints.set_health(0, true);
+        endpoints.fail_back();
+        assert_eq!(endpoints.candidates(), vec![0, 1, 2]);
+        assert!(!endpoints.should_fail_back(0));
+    }
+
+    #[tokio::test]
+    async fn primary_failure_fails_over_then_back_on_recovery() {
+        let (primary_up, secondary_up) =
+            (Arc::new(AtomicBool::new(true)), Arc::new(AtomicBool::new(true)));
+        let urls = vec![
+            spawn_reporter("primary", primary_up.clone()).await,
+            spawn_reporter("secondary", secondary_up).await,
+        ];
+        let http = EndpointHttpClient::new(Client::new(), Arc::new(Endpoints::new(urls).unwrap()));
+        let served_by = |http: EndpointHttpClient| async move {
+            http.get(PRICE_ROUTE).await.unwrap().text().await.unwrap()
+        };
+
+        assert_eq!(served_by(http.clone()).await, "primary");
+
+        // The primary fails, so requests fail over to the secondary, which
+        // stays active while the primary is down
+        primary_up.store(false, Ordering::Relaxed);
+        assert_eq!(served_by(http.clone()).await, "secondary");
+        http.check_health().await;
+        assert_eq!(served_by(http.clone()).await, "secondary");
+
+        // The primary recovers and passes its health check, so requests fail
+        // back to it
+        primary_up.store(true, Ordering::Relaxed);
+        assert_eq!(served_by(http.clone()).await, "secondary");
+        http.check_health().await;
+        assert_eq!(served_by(http.clone()).await, "primary");
+    }
+
+    #[tokio::test]
+    async fn client_error_is_returned_without_failing_over() {
+        let urls = vec![
+            spawn_server("primary", || "404 Not Found").await,
+            spawn_reporter("secondary", Arc::new(AtomicBool::new(true))).await,
+        ];
+        let http = EndpointHttpClient::new(Client::new(), Arc::new(Endpoints::new(urls).unwrap()));
+
+        let err = http.get(PRICE_ROUTE).await.unwrap_err();
+        assert!(err.to_string().contains("404"));
+
+        // The primary answered, so it stays active and healthy
+        assert_eq!(http.endpoints.candidates(), vec![0, 1]);
+        assert!(http.endpoints.is_healthy(0));
+    }
+
+    #[test]
+    fn ws_urls_use_websocket_scheme_and_port() {
+        let endpoints = Endpoints::new(vec![
+            "https://reporter.example.com".to_string(),
+            "http://localhost:3000".to_string(),
+        ])
+        .unwrap();
+
+        assert_eq!(endpoints.ws_url(0), "wss://reporter.example.com:4000/");
+        assert_eq!(endpoints.ws_url(1), "ws://localhost:4000/");
+        assert!(Endpoints::new(Vec::new()).is_err());
+    }
+}
@@ -29,6 +29,10 @@ pub enum PriceReporterClientError {
     #[error("No price stream for {0}")]
     StreamMissing(String),
 
+    /// The available price is older than the caller's maximum age
+    #[error("Stale price: {0}")]
+    StalePrice(String),
+
     /// Custom error
     #[error("Custom error: {0}")]
     Custom(String),
@@ -71,6 +75,12 @@ impl PriceReporterClientError {
         Self::StreamMissing(mint.to_string())
     }
 
+    /// Create a new stale price error
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn stale_price<T: ToString>(msg: T) -> Self {
+        Self::StalePrice(msg.to_string())
+    }
+
     /// Create a new custom error
     #[allow(clippy::needless_pass_by_value)]
     pub fn custom<T: ToString>(msg: T) -> Self {
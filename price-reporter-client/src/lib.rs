@@ -10,22 +10,26 @@
 
 use std::time::Duration;
 
+use std::sync::Arc;
+
 use bigdecimal::{BigDecimal, FromPrimitive, num_bigint::BigInt};
+use endpoints::{EndpointHttpClient, Endpoints};
 use error::PriceReporterClientError;
-use price_stream::MultiPriceStream;
+use price_stream::{MultiPriceStream, TaskGuard};
 use renegade_types_core::{Chain, Exchange, Token, USD_TICKER, USDC_TICKER, get_all_tokens};
-use reqwest::{Client, Response, Url};
+use reqwest::Client;
 use tracing::warn;
 
+mod endpoints;
 pub mod error;
 mod price_stream;
 
+pub use price_stream::PriceInfo;
+
 // -------------
 // | Constants |
 // -------------
 
-/// The price reporter's websocket port
-const WS_PORT: u16 = 4000;
 /// The route for the price endpoint
 pub const PRICE_ROUTE: &str = "/price";
 /// Default timeout for requests to the price reporter
@@ -34,6 +38,9 @@ const DEFAULT_TIMEOUT_SECS: u64 = 5;
 /// Must be strictly less than the price-reporter server's
 /// `header_read_timeout` (see `price-reporter/src/http_server.rs`).
 const POOL_IDLE_TIMEOUT_SECS: u64 = 20;
+/// The default interval at which prices are polled over HTTP while the
+/// websocket is disconnected
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
 /// The ticker for the WETH token
 const WETH_TICKER: &str = "WETH";
@@ -59,11 +66,22 @@ const ERR_PRICE_BIGDECIMAL_CONVERSION: &str = "failed to convert price to BigDec
 pub struct PriceReporterClientConfig {
     /// The base URL of the price reporter
     pub base_url: String,
+    /// The base URLs of additional price reporters to fail over to, in order
+    /// of preference. The client fails back to a more preferred price reporter
+    /// once it passes its health check again.
+    pub fallback_urls: Vec<String>,
+    /// The interval at which requested prices are polled over HTTP while the
+    /// websocket is disconnected from every price reporter.
+    ///
+    /// Defaults to `DEFAULT_POLL_INTERVAL`.
+    pub poll_interval: Option<Duration>,
     /// Whether to disable streaming of prices
     pub disable_price_stream: bool,
     /// Whether to allow the price stream to become stale.
     ///
-    /// If `false`, the process will exit when staleness is detected.
+    /// If `false`, the process will exit when staleness is detected. Callers
+    /// needing a freshness bound on individual prices should use
+    /// `PriceReporterClient::get_price_with_max_age` instead.
     pub allow_stale_price_stream: bool,
 }
 
@@ -73,15 +91,19 @@ pub struct PriceReporterClientConfig {
 
 /// A client for the price reporter that supports both HTTP requests
 /// and websocket streaming for real-time price updates
+///
+/// The client may be given several price reporter endpoints, between which it
+/// fails over as they become unhealthy.
 #[derive(Debug, Clone)]
 pub struct PriceReporterClient {
-    /// The base URL of the price reporter
-    base_url: String,
-    /// The shared HTTP client used for issuing requests to the price reporter
-    http_client: Client,
+    /// The HTTP client used for issuing requests to the price reporters
+    http: EndpointHttpClient,
     /// The multi-price stream for real-time token price updates,
     /// if the client was constructed with price streaming enabled
     multi_price_stream: Option<MultiPriceStream>,
+    /// Guard that aborts the endpoint health checks when all clones of the
+    /// client are dropped, if there are several endpoints to check
+    _health_check_guard: Option<Arc<TaskGuard>>,
 }
 
 impl PriceReporterClient {
@@ -89,8 +111,13 @@ impl PriceReporterClient {
     /// If `exit_on_stale` is true, the process will exit if the price stream
     /// becomes stale.
     pub fn new(config: PriceReporterClientConfig) -> Result<Self, PriceReporterClientError> {
-        let PriceReporterClientConfig { base_url, disable_price_stream, allow_stale_price_stream } =
-            config;
+        let PriceReporterClientConfig {
+            base_url,
+            fallback_urls,
+            poll_interval,
+            disable_price_stream,
+            allow_stale_price_stream,
+        } = config;
 
         // Build a shared HTTP client with a sensible default timeout.
         // `pool_idle_timeout` must stay below the price-reporter server's
@@ -102,41 +129,34 @@ impl PriceReporterClient {
             .build()
             .map_err(PriceReporterClientError::http)?;
 
-        if disable_price_stream {
-            return Ok(Self { base_url, http_client, multi_price_stream: None });
-        }
+        let base_urls = std::iter::once(base_url).chain(fallback_urls).collect::<Vec<_>>();
+        let has_fallbacks = base_urls.len() > 1;
+        let endpoints = Arc::new(Endpoints::new(base_urls)?);
+        let http = EndpointHttpClient::new(http_client, endpoints);
 
-        let mut ws_url: Url = base_url.parse().map_err(PriceReporterClientError::parsing)?;
+        // Health-check the endpoints in the background so that requests skip
+        // unhealthy ones, if there is more than one to choose from
+        let _health_check_guard =
+            has_fallbacks.then(|| Arc::new(TaskGuard(vec![http.spawn_health_checks()])));
 
-        if ws_url.scheme() == "https" {
-            ws_url
-                .set_scheme("wss")
-                .map_err(|_| PriceReporterClientError::setup("Error setting websocket scheme"))?;
-        } else if ws_url.scheme() == "http" {
-            ws_url
-                .set_scheme("ws")
-                .map_err(|_| PriceReporterClientError::setup("Error setting websocket scheme"))?;
+        if disable_price_stream {
+            return Ok(Self { http, multi_price_stream: None, _health_check_guard });
         }
 
-        ws_url
-            .set_port(Some(WS_PORT))
-            .map_err(|_| PriceReporterClientError::setup("Error setting websocket port"))?;
-
         let mints = get_all_tokens()
             .into_iter()
             .filter(|t| !UNIT_PRICE_TICKERS.contains(&t.get_ticker().unwrap_or_default().as_str()))
             .map(|t| t.get_addr())
             .collect();
 
-        Ok(Self {
-            base_url,
-            http_client,
-            multi_price_stream: Some(MultiPriceStream::new(
-                ws_url.to_string(),
-                mints,
-                !allow_stale_price_stream,
-            )),
-        })
+        let multi_price_stream = MultiPriceStream::new(
+            http.clone(),
+            mints,
+            !allow_stale_price_stream,
+            poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL),
+        );
+
+        Ok(Self { http, multi_price_stream: Some(multi_price_stream), _health_check_guard })
     }
 
     /// Signal the background WebSocket price stream (if any) to shut down
@@ -205,8 +225,9 @@ impl PriceReporterClient {
 
     /// Fetch the current price of a token from the price reporter.
     ///
-    /// We first try reading the state of the price stream,
-    /// and fall back to an HTTP request if the stream is not connected.
+    /// We first try reading the state of the price stream. While the stream is
+    /// disconnected, a recently polled price is served from its state, and we
+    /// fall back to an HTTP request otherwise.
     pub async fn get_price(
         &self,
         mint: &str,
@@ -220,44 +241,62 @@ impl PriceReporterClient {
             return Ok(1.0);
         }
 
-        let multi_price_stream = self.multi_price_stream.as_ref();
-        let ws_is_connected = multi_price_stream.is_some_and(|stream| stream.is_connected());
+        if let Some(stream) = self.multi_price_stream.as_ref() {
+            if stream.is_connected() {
+                return stream.get_price(&mint).await;
+            }
 
-        if ws_is_connected {
-            return multi_price_stream.unwrap().get_price(&mint).await;
+            if let Some(price) = stream.get_polled_price(&mint).await {
+                return Ok(price);
+            }
         }
 
         warn!("Price stream is not connected, fetching price via HTTP");
         self.get_price_http(&mint).await
     }
 
-    /// Get the price of a token from the price reporter via HTTP
-    pub async fn get_price_http(&self, mint: &str) -> Result<f64, PriceReporterClientError> {
-        let price_topic = construct_price_topic(mint);
-
-        let url = format!("{}{}/{}", self.base_url, PRICE_ROUTE, price_topic);
-        let response = self.send_get_request(&url).await?;
-
-        let res_text = response.text().await.map_err(PriceReporterClientError::parsing)?;
+    /// Fetch the current price of a token from the price reporter, erroring if
+    /// the freshest available price is older than `max_age`.
+    ///
+    /// We first try reading the state of the price stream, which holds the
+    /// polled prices while it is disconnected, and fall back to an HTTP request
+    /// if its price is missing or too old.
+    pub async fn get_price_with_max_age(
+        &self,
+        mint: &str,
+        chain: Chain,
+        max_age: Duration,
+    ) -> Result<f64, PriceReporterClientError> {
+        let mint = mint.to_lowercase();
+        let token = Token::from_addr_on_chain(&mint, chain);
+        if let Some(ticker) = token.get_ticker()
+            && UNIT_PRICE_TICKERS.contains(&ticker.as_str())
+        {
+            return Ok(1.0);
+        }
 
-        let price: f64 = res_text.parse().map_err(PriceReporterClientError::parsing)?;
+        if let Some(stream) = self.multi_price_stream.as_ref() {
+            match stream.get_price_with_max_age(&mint, max_age).await {
+                Ok(price) => return Ok(price),
+                Err(e) => warn!("Streamed price unavailable ({e}), fetching price via HTTP"),
+            }
+        }
 
-        Ok(price)
+        self.get_price_info_http(&mint).await?.price_within(&mint, max_age)
     }
 
-    /// Sends a basic GET request
-    async fn send_get_request(&self, url: &str) -> Result<Response, PriceReporterClientError> {
-        let response =
-            self.http_client.get(url).send().await.map_err(PriceReporterClientError::http)?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let message = response.text().await.map_err(PriceReporterClientError::parsing)?;
-
-            return Err(PriceReporterClientError::http(format!("Status {}: {}", status, message)));
-        }
+    /// Get the price of a token along with its provenance and age from the
+    /// price reporter via HTTP
+    pub async fn get_price_info_http(
+        &self,
+        mint: &str,
+    ) -> Result<PriceInfo, PriceReporterClientError> {
+        self.http.get_price_info(mint).await
+    }
 
-        Ok(response)
+    /// Get the price of a token from the price reporter via HTTP
+    pub async fn get_price_http(&self, mint: &str) -> Result<f64, PriceReporterClientError> {
+        self.http.get_price(mint).await
     }
 }
 
@@ -1,19 +1,20 @@
 //! The shared stream of the ETH price
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use atomic_float::AtomicF64;
 use futures_util::{
     SinkExt, StreamExt,
+    future::join_all,
     stream::{SplitSink, SplitStream},
 };
+use metrics::{counter, gauge};
 use renegade_api::websocket::WebsocketMessage;
 use serde::Deserialize;
 use tokio::{net::TcpStream, sync::RwLock, task::JoinHandle};
@@ -21,7 +22,12 @@ use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungsten
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, warn};
 
-use super::{construct_price_topic, error::PriceReporterClientError, get_base_mint_from_topic};
+use super::{
+    construct_price_topic,
+    endpoints::{EndpointHttpClient, Endpoints},
+    error::PriceReporterClientError,
+    get_base_mint_from_topic,
+};
 
 // -------------
 // | Constants |
@@ -35,6 +41,20 @@ pub const CONN_RETRY_DELAY_MS: u64 = 2_000; // 2 seconds
 /// for *any* assets.
 pub const STALENESS_TIMEOUT_MS: u64 = 60_000; // 1 minute
 
+/// The interval at which a websocket connected to a fallback endpoint checks
+/// whether a more preferred endpoint has recovered
+const FAILBACK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The number of poll intervals within which a polled price must have arrived
+/// to be served while the websocket is disconnected
+const POLLED_PRICE_MAX_INTERVALS: u32 = 2;
+
+/// The metric reporting whether the websocket is connected
+const WS_CONNECTED_METRIC: &str = "price_reporter_client_ws_connected";
+/// The metric counting prices polled over HTTP while the websocket is
+/// disconnected
+const POLLED_PRICES_METRIC: &str = "price_reporter_client_polled_prices";
+
 // ---------
 // | Types |
 // ---------
@@ -46,16 +66,97 @@ type WsWriteStream = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Messa
 type WsReadStream = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
 
 /// A type alias for a synchronized map from token mints to their latest prices
-type SyncPricesMap = RwLock<HashMap<String, AtomicF64>>;
+type SyncPricesMap = RwLock<HashMap<String, StreamedPrice>>;
 
 /// A message that is sent by the price reporter to the client indicating
 /// a price udpate for the given topic
+///
+/// The metadata fields are optional so that messages from price reporters
+/// which do not publish them still parse.
 #[derive(Deserialize)]
 pub struct PriceMessage {
     /// The topic for which the price update is being sent
     pub topic: String,
     /// The new price
     pub price: f64,
+    /// The exchange from which the price was sourced
+    #[serde(default)]
+    pub source: Option<String>,
+    /// The time at which the exchange reported the price, in milliseconds
+    /// since the epoch
+    #[serde(default)]
+    pub exchange_timestamp: Option<u64>,
+    /// The time at which the price reporter received the price, in
+    /// milliseconds since the epoch
+    #[serde(default)]
+    pub received_at: Option<u64>,
+    /// The age of the price when the message was sent, in milliseconds
+    #[serde(default)]
+    pub age_ms: Option<u64>,
+}
+
+/// The latest price of a token, along with its provenance and age
+#[derive(Clone, Debug, PartialEq)]
+pub struct PriceInfo {
+    /// The price
+    pub price: f64,
+    /// The exchange from which the price was sourced, if reported
+    pub source: Option<String>,
+    /// The time at which the exchange reported the price, in milliseconds
+    /// since the epoch, if reported
+    pub exchange_timestamp: Option<u64>,
+    /// The time at which the price reporter received the price, in
+    /// milliseconds since the epoch, if reported
+    pub received_at: Option<u64>,
+    /// The age of the price, if reported
+    ///
+    /// Measured as the age reported by the price reporter plus the time since
+    /// the client received the message, so it is unaffected by clock skew
+    /// between the client and the price reporter. A price of unknown age is
+    /// treated as stale.
+    pub age: Option<Duration>,
+}
+
+impl From<PriceMessage> for PriceInfo {
+    fn from(message: PriceMessage) -> Self {
+        Self {
+            price: message.price,
+            source: message.source,
+            exchange_timestamp: message.exchange_timestamp,
+            received_at: message.received_at,
+            age: message.age_ms.map(Duration::from_millis),
+        }
+    }
+}
+
+impl PriceInfo {
+    /// Return the price if it is no older than the given maximum age
+    pub fn price_within(
+        &self,
+        mint: &str,
+        max_age: Duration,
+    ) -> Result<f64, PriceReporterClientError> {
+        match self.age {
+            Some(age) if age <= max_age => Ok(self.price),
+            Some(age) => Err(PriceReporterClientError::stale_price(format!(
+                "price for {mint} is {}ms old, exceeding the maximum of {}ms",
+                age.as_millis(),
+                max_age.as_millis()
+            ))),
+            None => Err(PriceReporterClientError::stale_price(format!(
+                "price for {mint} has no reported age"
+            ))),
+        }
+    }
+}
+
+/// A price held by the stream, along with the local time at which it arrived
+#[derive(Clone, Debug)]
+struct StreamedPrice {
+    /// The price as of its arrival
+    info: PriceInfo,
+    /// The local time at which the price arrived
+    arrived_at: Instant,
 }
 
 /// The thread-safe state of the multi-price stream
@@ -63,6 +164,9 @@ pub struct PriceMessage {
 pub struct MultiPriceStreamState {
     /// The latest prices for the tokens managed by the price stream
     pub prices: SyncPricesMap,
+    /// The mints whose prices have been requested from the stream, which are
+    /// polled over HTTP while the websocket is disconnected
+    pub requested_mints: RwLock<HashSet<String>>,
     /// Whether the websocket is currently connected
     pub is_connected: AtomicBool,
     /// Whether to exit the process when the price stream becomes stale
@@ -76,6 +180,7 @@ impl MultiPriceStreamState {
     pub fn new(exit_on_stale: bool) -> Self {
         Self {
             prices: SyncPricesMap::new(HashMap::new()),
+            requested_mints: RwLock::new(HashSet::new()),
             is_connected: AtomicBool::new(false),
             exit_on_stale,
             staleness_timer: RwLock::new(None),
@@ -83,20 +188,45 @@ impl MultiPriceStreamState {
     }
 
     /// Update the price of a token
-    async fn update_price(&self, mint: String, price: f64) {
-        self.prices
-            .write()
-            .await
-            .entry(mint)
-            .or_insert(AtomicF64::new(price))
-            .store(price, Ordering::Relaxed);
+    async fn update_price(&self, mint: String, info: PriceInfo) {
+        let streamed_price = StreamedPrice { info, arrived_at: Instant::now() };
+        self.prices.write().await.insert(mint, streamed_price);
 
         self.restart_staleness_timer().await;
     }
 
+    /// Record that the price of a token has been requested
+    async fn record_request(&self, mint: &str) {
+        if self.requested_mints.read().await.contains(mint) {
+            return;
+        }
+
+        self.requested_mints.write().await.insert(mint.to_string());
+    }
+
+    /// The latest price of a token, if it arrived within the given age
+    async fn price_arrived_within(&self, mint: &str, max_arrival_age: Duration) -> Option<f64> {
+        let prices = self.prices.read().await;
+        prices.get(mint).filter(|p| p.arrived_at.elapsed() < max_arrival_age).map(|p| p.info.price)
+    }
+
+    /// The requested mints among the given ones whose prices did not arrive
+    /// within the given interval, and so must be polled
+    async fn mints_to_poll(&self, mints: &[String], interval: Duration) -> Vec<String> {
+        let requested = self.requested_mints.read().await;
+        let prices = self.prices.read().await;
+        mints
+            .iter()
+            .filter(|mint| requested.contains(*mint))
+            .filter(|mint| prices.get(*mint).is_none_or(|p| p.arrived_at.elapsed() >= interval))
+            .cloned()
+            .collect()
+    }
+
     /// Set the connection status
     fn set_connected(&self, connected: bool) {
         self.is_connected.store(connected, Ordering::Relaxed);
+        gauge!(WS_CONNECTED_METRIC).set(if connected { 1.0 } else { 0.0 });
     }
 
     /// Restart the staleness timer
@@ -132,14 +262,14 @@ impl MultiPriceStreamState {
     }
 }
 
-/// A guard that aborts a spawned task when the last reference is dropped.
+/// A guard that aborts spawned tasks when the last reference is dropped.
 /// Wrapped in `Arc` so that `MultiPriceStream` can remain `Clone` — the
-/// task is aborted only when every clone has been dropped.
+/// tasks are aborted only when every clone has been dropped.
 ///
-/// In normal operation the task is shut down gracefully via a
+/// In normal operation the tasks are shut down gracefully via a
 /// [`CancellationToken`]; the abort here is a safety net for the case
 /// where the stream is dropped without an explicit `shutdown()` call.
-struct TaskGuard(JoinHandle<()>);
+pub(crate) struct TaskGuard(pub(crate) Vec<JoinHandle<()>>);
 
 impl std::fmt::Debug for TaskGuard {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -149,7 +279,9 @@ impl std::fmt::Debug for TaskGuard {
 
 impl Drop for TaskGuard {
     fn drop(&mut self) {
-        self.0.abort();
+        for handle in &self.0 {
+            handle.abort();
+        }
     }
 }
 
@@ -160,11 +292,15 @@ pub struct MultiPriceStream {
     /// The inner state of the multi-price stream, made shareable via an `Arc`
     /// so that it can be updated by the websocket thread
     inner: Arc<MultiPriceStreamState>,
-    /// Token used to signal the background task to shut down gracefully,
-    /// giving it a chance to send WebSocket Close frames before exiting
+    /// The interval at which requested prices are polled over HTTP while the
+    /// websocket is disconnected
+    poll_interval: Duration,
+    /// Token used to signal the background tasks to shut down gracefully,
+    /// giving them a chance to send WebSocket Close frames before exiting
     cancel: CancellationToken,
-    /// Guard that aborts the background websocket task when all clones of
-    /// this stream are dropped (safety net if `shutdown()` was not called)
+    /// Guard that aborts the background websocket and polling tasks when all
+    /// clones of this stream are dropped (safety net if `shutdown()` was not
+    /// called)
     _task_guard: Arc<TaskGuard>,
 }
 
@@ -175,26 +311,75 @@ pub struct MultiPriceStream {
 impl MultiPriceStream {
     /// Create a new multi-price stream, starting the subscription to the price
     /// topics
-    pub fn new(ws_url: String, mints: Vec<String>, exit_on_stale: bool) -> Self {
+    ///
+    /// While the websocket is disconnected from every endpoint, the prices
+    /// that have been requested are polled over HTTP at the given interval
+    /// instead.
+    pub fn new(
+        http: EndpointHttpClient,
+        mints: Vec<String>,
+        exit_on_stale: bool,
+        poll_interval: Duration,
+    ) -> Self {
         let cancel = CancellationToken::new();
         let inner = Arc::new(MultiPriceStreamState::new(exit_on_stale));
-        let inner_clone = inner.clone();
-        let cancel_clone = cancel.clone();
-
-        let handle = tokio::spawn(async move {
-            Self::run_websocket_loop(inner_clone, ws_url, mints, cancel_clone).await;
-        });
 
-        Self { inner, cancel, _task_guard: Arc::new(TaskGuard(handle)) }
+        let ws_handle = tokio::spawn(Self::run_websocket_loop(
+            inner.clone(),
+            http.endpoints().clone(),
+            mints.clone(),
+            cancel.clone(),
+        ));
+        let poll_handle = tokio::spawn(Self::run_poll_loop(
+            inner.clone(),
+            http,
+            mints,
+            poll_interval,
+            cancel.clone(),
+        ));
+
+        let _task_guard = Arc::new(TaskGuard(vec![ws_handle, poll_handle]));
+        Self { inner, poll_interval, cancel, _task_guard }
     }
 
     /// Get the current state of the price stream
     pub async fn get_price(&self, mint: &str) -> Result<f64, PriceReporterClientError> {
+        Ok(self.get_price_info(mint).await?.price)
+    }
+
+    /// Get the latest price of a token along with its provenance and current
+    /// age
+    pub async fn get_price_info(&self, mint: &str) -> Result<PriceInfo, PriceReporterClientError> {
+        self.inner.record_request(mint).await;
         let prices = self.inner.prices.read().await;
 
-        let price = prices.get(mint).ok_or(PriceReporterClientError::stream_missing(mint))?;
+        let streamed_price =
+            prices.get(mint).ok_or(PriceReporterClientError::stream_missing(mint))?;
 
-        Ok(price.load(Ordering::Relaxed))
+        let mut info = streamed_price.info.clone();
+        info.age = info.age.map(|age| age + streamed_price.arrived_at.elapsed());
+        Ok(info)
+    }
+
+    /// Get the latest price of a token, erroring if it is older than the given
+    /// maximum age
+    pub async fn get_price_with_max_age(
+        &self,
+        mint: &str,
+        max_age: Duration,
+    ) -> Result<f64, PriceReporterClientError> {
+        self.get_price_info(mint).await?.price_within(mint, max_age)
+    }
+
+    /// Get the latest polled price of a token while the websocket is
+    /// disconnected, if it arrived recently enough to be served
+    ///
+    /// The request is recorded either way, so that a mint first requested
+    /// during an outage is polled from then on.
+    pub async fn get_polled_price(&self, mint: &str) -> Option<f64> {
+        self.inner.record_request(mint).await;
+        let max_arrival_age = self.poll_interval * POLLED_PRICE_MAX_INTERVALS;
+        self.inner.price_arrived_within(mint, max_arrival_age).await
     }
 
     /// Get the connection status of the price stream
@@ -223,18 +408,35 @@ impl MultiPriceStream {
     /// attempt to send a Close frame before dropping.
     async fn run_websocket_loop(
         state: Arc<MultiPriceStreamState>,
-        ws_url: String,
+        endpoints: Arc<Endpoints>,
         mints: Vec<String>,
         cancel: CancellationToken,
     ) {
+        let mut failed_attempts = 0;
         loop {
             if cancel.is_cancelled() {
                 break;
             }
 
-            if let Err(e) =
-                Self::stream_prices(state.clone(), &ws_url, &mints, cancel.clone()).await
-            {
+            // Connect to the preferred healthy endpoint, failing over to the
+            // others in turn while connections fail
+            let idx = endpoints.ws_candidate(failed_attempts);
+            let ws_url = endpoints.ws_url(idx);
+            let res = match connect_and_subscribe(ws_url, &mints).await {
+                Ok((write, read)) => {
+                    endpoints.mark_success(idx);
+                    failed_attempts = 0;
+                    let failback = Self::wait_for_failback(endpoints.clone(), idx);
+                    Self::stream_prices(state.clone(), write, read, failback, cancel.clone()).await
+                },
+                Err(e) => {
+                    endpoints.mark_failure(idx);
+                    failed_attempts += 1;
+                    Err(e)
+                },
+            };
+
+            if let Err(e) = res {
                 // Transient — typically a TCP reset on an idle WS. The
                 // surrounding loop reconnects unconditionally and the
                 // adjacent `Reconnecting to price reporter…` warn already
@@ -252,21 +454,40 @@ impl MultiPriceStream {
         }
     }
 
-    /// Subscribe to the price topics, handle price updates, and send a
-    /// graceful Close frame before returning.
+    /// Wait until a healthy endpoint is preferred over the one at the given
+    /// index, so that the websocket should reconnect to it
+    async fn wait_for_failback(endpoints: Arc<Endpoints>, idx: usize) {
+        let mut interval = tokio::time::interval(FAILBACK_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if endpoints.should_fail_back(idx) {
+                return;
+            }
+        }
+    }
+
+    /// Handle price updates on a subscribed connection until it drops or the
+    /// given failback future resolves, and send a graceful Close frame before
+    /// returning.
     ///
     /// The write half of the WebSocket is kept alive for the duration of
     /// the connection so that a proper Close frame can be sent on exit.
     async fn stream_prices(
         state: Arc<MultiPriceStreamState>,
-        ws_url: &str,
-        mints: &[String],
+        mut write: WsWriteStream,
+        read: WsReadStream,
+        failback: impl Future<Output = ()>,
         cancel: CancellationToken,
     ) -> Result<(), PriceReporterClientError> {
-        let (mut write, read) = connect_and_subscribe(ws_url, mints).await?;
         state.set_connected(true);
 
-        let result = Self::handle_price_updates(read, state, cancel).await;
+        let result = tokio::select! {
+            res = Self::handle_price_updates(read, state, cancel) => res,
+            _ = failback => {
+                warn!("Preferred price reporter endpoint recovered, failing back");
+                Ok(())
+            },
+        };
 
         // Send a graceful WebSocket Close frame before dropping the
         // connection. Errors are ignored — the peer may already be gone.
@@ -276,6 +497,59 @@ impl MultiPriceStream {
         result
     }
 
+    /// Poll the prices of the requested tokens over HTTP while the websocket
+    /// is disconnected, until `cancel` fires
+    ///
+    /// Only the prices that have been requested and did not arrive within the
+    /// last interval are polled, and they are served from the stream state
+    /// until the websocket reconnects. If there are none, the endpoints are
+    /// checked instead so that a reachable price reporter keeps the stream
+    /// from going stale.
+    async fn run_poll_loop(
+        state: Arc<MultiPriceStreamState>,
+        http: EndpointHttpClient,
+        mints: Vec<String>,
+        poll_interval: Duration,
+        cancel: CancellationToken,
+    ) {
+        let mut interval = tokio::time::interval(poll_interval);
+        loop {
+            tokio::select! {
+                biased;
+
+                _ = cancel.cancelled() => break,
+                _ = interval.tick() => {},
+            }
+
+            if state.is_connected.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            let to_poll = state.mints_to_poll(&mints, poll_interval).await;
+            if to_poll.is_empty() {
+                match http.check_reachable().await {
+                    Ok(()) => state.restart_staleness_timer().await,
+                    Err(e) => debug!("No price reporter endpoint is reachable: {e}"),
+                }
+                continue;
+            }
+
+            let results = join_all(to_poll.iter().map(|mint| http.get_price_info(mint))).await;
+            for (mint, res) in to_poll.iter().zip(results) {
+                match res {
+                    Ok(info) => {
+                        counter!(POLLED_PRICES_METRIC, "result" => "ok").increment(1);
+                        state.update_price(mint.clone(), info).await;
+                    },
+                    Err(e) => {
+                        counter!(POLLED_PRICES_METRIC, "result" => "error").increment(1);
+                        debug!("Failed to poll price for {mint}: {e}");
+                    },
+                }
+            }
+        }
+    }
+
     /// Handle price updates from the price reporter, updating the state with
     /// the latest prices. Exits when the connection drops or `cancel` fires.
     async fn handle_price_updates(
@@ -300,7 +574,7 @@ impl MultiPriceStream {
                             {
                                 let mint =
                                     get_base_mint_from_topic(&price_message.topic)?;
-                                state.update_price(mint, price_message.price).await;
+                                state.update_price(mint, price_message.into()).await;
                             } else {
                                 debug!("Received invalid price message: {text}");
                             }
@@ -362,3 +636,62 @@ async fn ws_connect(
     let (ws_write, ws_read) = ws_conn.split();
     Ok((ws_write, ws_read))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a price message with the given reported age
+    fn price_message(age_ms: Option<u64>) -> PriceMessage {
+        PriceMessage {
+            topic: "binance-WETH-USDT".to_string(),
+            price: 100.,
+            source: None,
+            exchange_timestamp: None,
+            received_at: None,
+            age_ms,
+        }
+    }
+
+    #[tokio::test]
+    async fn polls_only_requested_mints_without_recent_prices() {
+        let state = MultiPriceStreamState::new(false /* exit_on_stale */);
+        let mints = vec!["WETH".to_string(), "WBTC".to_string(), "ARB".to_string()];
+        let interval = Duration::from_secs(5);
+        assert!(state.mints_to_poll(&mints, interval).await.is_empty());
+
+        state.record_request("WETH").await;
+        state.record_request("WBTC").await;
+        assert_eq!(state.mints_to_poll(&mints, interval).await, vec!["WETH", "WBTC"]);
+
+        // A price that arrived within the interval is not polled again
+        state.update_price("WETH".to_string(), price_message(Some(0)).into()).await;
+        assert_eq!(state.mints_to_poll(&mints, interval).await, vec!["WBTC"]);
+        assert_eq!(state.mints_to_poll(&mints, Duration::ZERO).await, vec!["WETH", "WBTC"]);
+    }
+
+    #[tokio::test]
+    async fn polled_price_is_served_only_while_recent() {
+        let state = MultiPriceStreamState::new(false /* exit_on_stale */);
+        let max_arrival_age = Duration::from_secs(10);
+        assert_eq!(state.price_arrived_within("WETH", max_arrival_age).await, None);
+
+        state.update_price("WETH".to_string(), price_message(Some(0)).into()).await;
+        assert_eq!(state.price_arrived_within("WETH", max_arrival_age).await, Some(100.));
+        assert_eq!(state.price_arrived_within("WETH", Duration::ZERO).await, None);
+    }
+
+    #[test]
+    fn price_of_unknown_age_is_stale() {
+        let max_age = Duration::from_secs(1);
+
+        let fresh = PriceInfo::from(price_message(Some(500)));
+        assert_eq!(fresh.price_within("WETH", max_age).unwrap(), 100.);
+
+        let old = PriceInfo::from(price_message(Some(1_500)));
+        assert!(old.price_within("WETH", max_age).is_err());
+
+        let unknown = PriceInfo::from(price_message(None));
+        assert!(unknown.price_within("WETH", max_age).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! API-key authentication and rate limiting for price consumers
+//!
+//! When an API key file is configured, the websocket server and the HTTP
+//! price routes require a known key, passed in the `X-Api-Key` header. Keys
+//! are held and looked up by their SHA-256 digest, so the time taken to reject
+//! a key does not reveal how much of a known key it matches. Each key may
+//! carry its own subscription limit and request rate limit, both of which are
+//! shared across the key's connections.
+//!
+//! The key file is a JSON array of entries of the form
+//! `{"key": "...", "name": "partner-a", "max_subscriptions": 20,
+//! "requests_per_minute": 600}`, where the limits are optional.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::errors::ServerError;
+
+// -------------
+// | Constants |
+// -------------
+
+/// The header carrying a client's API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// The window over which request rate limits are expressed
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+// ---------
+// | Types |
+// ---------
+
+/// The SHA-256 digest of an API key
+type KeyDigest = [u8; 32];
+
+/// A single entry of the API key file
+#[derive(Clone, Debug, Deserialize)]
+pub struct ApiKeyEntry {
+    /// The API key
+    pub key: String,
+    /// The name of the client holding the key, used in logs
+    pub name: String,
+    /// The maximum number of topics the client may subscribe to across its
+    /// websocket connections, overriding the server default
+    #[serde(default)]
+    pub max_subscriptions: Option<usize>,
+    /// The maximum number of requests the client may make per minute, across
+    /// HTTP requests and websocket messages
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+}
+
+/// An authenticated client
+#[derive(Clone, Debug)]
+pub struct ApiClient {
+    /// The name of the client
+    pub name: String,
+    /// The client's websocket subscription limit, if it overrides the default
+    pub max_subscriptions: Option<usize>,
+    /// The client's request rate limiter, shared across its connections
+    rate_limiter: Option<Arc<Mutex<RateLimiter>>>,
+    /// The number of topics the client is subscribed to, across its
+    /// connections
+    subscriptions: Arc<AtomicUsize>,
+}
+
+impl ApiClient {
+    /// Consume a request from the client's rate limit, erroring if the limit
+    /// is exhausted
+    pub fn check_rate_limit(&self) -> Result<(), ServerError> {
+        let Some(rate_limiter) = &self.rate_limiter else {
+            return Ok(());
+        };
+
+        let mut rate_limiter = rate_limiter.lock().expect("rate limiter lock poisoned");
+        if !rate_limiter.try_acquire(Instant::now()) {
+            return Err(ServerError::RateLimited(self.name.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// The websocket subscription limit for the client, given the server
+    /// default. `None` means the client is uncapped
+    pub fn subscription_limit(&self, default: Option<usize>) -> Option<usize> {
+        self.max_subscriptions.or(default)
+    }
+
+    /// Take one of the client's subscriptions, erroring if the client is
+    /// already subscribed to `limit` topics across its connections
+    ///
+    /// Subscriptions are counted even when uncapped, so that releases stay
+    /// balanced.
+    pub fn acquire_subscription(&self, limit: Option<usize>) -> Result<(), ServerError> {
+        let limit = limit.unwrap_or(usize::MAX);
+        self.subscriptions
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < limit).then_some(n + 1))
+            .map(|_| ())
+            .map_err(|_| ServerError::TooManySubscriptions(limit))
+    }
+
+    /// Release `n` of the client's subscriptions
+    pub fn release_subscriptions(&self, n: usize) {
+        self.subscriptions.fetch_sub(n, Ordering::AcqRel);
+    }
+}
+
+/// The set of API keys accepted by the server
+#[derive(Clone, Debug, Default)]
+pub struct ApiKeys {
+    /// The authenticated clients, indexed by the digest of their key
+    clients: Arc<HashMap<KeyDigest, ApiClient>>,
+}
+
+impl ApiKeys {
+    /// Build the key set from the entries of a key file
+    pub fn new(entries: Vec<ApiKeyEntry>) -> Self {
+        let clients = entries
+            .into_iter()
+            .map(|entry| {
+                let rate_limiter = entry
+                    .requests_per_minute
+                    .map(|limit| Arc::new(Mutex::new(RateLimiter::new(limit, RATE_LIMIT_WINDOW))));
+                let client = ApiClient {
+                    name: entry.name,
+                    max_subscriptions: entry.max_subscriptions,
+                    rate_limiter,
+                    subscriptions: Arc::default(),
+                };
+                (key_digest(&entry.key), client)
+            })
+            .collect();
+
+        Self { clients: Arc::new(clients) }
+    }
+
+    /// Read the key set from the given JSON file
+    pub fn from_file(path: &str) -> Result<Self, ServerError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ServerError::InvalidRequest(format!("reading {path}: {e}")))?;
+        let entries: Vec<ApiKeyEntry> = serde_json::from_str(&contents)
+            .map_err(|e| ServerError::Serde(format!("parsing {path}: {e}")))?;
+
+        Ok(Self::new(entries))
+    }
+
+    /// Authenticate a client by its API key
+    pub fn authenticate(&self, key: Option<&str>) -> Result<ApiClient, ServerError> {
+        let key = key.ok_or_else(|| ServerError::Unauthorized("missing API key".to_string()))?;
+        self.clients
+            .get(&key_digest(key))
+            .cloned()
+            .ok_or_else(|| ServerError::Unauthorized("unknown API key".to_string()))
+    }
+}
+
+// ----------------
+// | Rate Limiter |
+// ----------------
+
+/// A token bucket rate limiter
+#[derive(Debug)]
+struct RateLimiter {
+    /// The maximum number of tokens in the bucket
+    capacity: f64,
+    /// The number of tokens refilled per second
+    refill_per_sec: f64,
+    /// The number of tokens currently in the bucket
+    tokens: f64,
+    /// The time at which the bucket was last refilled
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a full bucket allowing `limit` requests per `window`
+    fn new(limit: u32, window: Duration) -> Self {
+        let capacity = f64::from(limit);
+        Self {
+            capacity,
+            refill_per_sec: capacity / window.as_secs_f64(),
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token from the bucket at the given time, returning whether one
+    /// was available
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens < 1. {
+            return false;
+        }
+
+        self.tokens -= 1.;
+        true
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Get the digest by which an API key is held
+fn key_digest(key: &str) -> KeyDigest {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limiter_refills_over_window() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let start = limiter.last_refill;
+
+        assert!(limiter.try_acquire(start));
+        assert!(limiter.try_acquire(start));
+        assert!(!limiter.try_acquire(start));
+
+        // One token is refilled every 30 seconds
+        assert!(!limiter.try_acquire(start + Duration::from_secs(15)));
+        assert!(limiter.try_acquire(start + Duration::from_secs(30)));
+        assert!(!limiter.try_acquire(start + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn authenticate_known_keys_only() {
+        let keys = ApiKeys::new(vec![ApiKeyEntry {
+            key: "secret".to_string(),
+            name: "partner".to_string(),
+            max_subscriptions: Some(5),
+            requests_per_minute: Some(1),
+        }]);
+
+        let client = keys.authenticate(Some("secret")).unwrap();
+        assert_eq!(client.name, "partner");
+        assert_eq!(client.subscription_limit(Some(100)), Some(5));
+        assert!(client.check_rate_limit().is_ok());
+        assert!(matches!(client.check_rate_limit(), Err(ServerError::RateLimited(_))));
+
+        assert!(matches!(keys.authenticate(Some("other")), Err(ServerError::Unauthorized(_))));
+        assert!(matches!(keys.authenticate(None), Err(ServerError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn subscriptions_are_shared_across_connections() {
+        let keys = ApiKeys::new(vec![ApiKeyEntry {
+            key: "secret".to_string(),
+            name: "partner".to_string(),
+            max_subscriptions: Some(2),
+            requests_per_minute: None,
+        }]);
+
+        // Each connection authenticates separately
+        let conn1 = keys.authenticate(Some("secret")).unwrap();
+        let conn2 = keys.authenticate(Some("secret")).unwrap();
+        let limit = conn1.subscription_limit(None);
+
+        assert!(conn1.acquire_subscription(limit).is_ok());
+        assert!(conn2.acquire_subscription(limit).is_ok());
+        assert!(matches!(
+            conn1.acquire_subscription(limit),
+            Err(ServerError::TooManySubscriptions(2))
+        ));
+
+        // Closing a connection frees its subscriptions for the others
+        conn2.release_subscriptions(1);
+        assert!(conn1.acquire_subscription(limit).is_ok());
+    }
+}
@@ -1,11 +1,23 @@
 //! Defines the command-line arguments & parsing helpers for the price reporter
 
+use std::time::Duration;
+
+use alloy::primitives::Address;
 use clap::Parser;
 use renegade_types_core::{Chain, Exchange, HmacKey};
 use renegade_util::telemetry::{configure_telemetry_with_metrics_config, metrics::MetricsConfig};
 
 use crate::{
-    errors::ServerError, exchanges::ExchangeConnectionsConfig, utils::PriceReporterConfig,
+    api_keys::ApiKeys,
+    composite::{CompositeConfig, CompositeMethod},
+    errors::ServerError,
+    exchanges::{
+        ExchangeConnectionsConfig,
+        replay::{FrameRecorder, ReplayConfig},
+    },
+    failover::{FallbackEntry, build_fallback_exchanges},
+    price_history::PriceHistoryConfig,
+    utils::PriceReporterConfig,
 };
 
 /// The prefix to apply to all metrics emitted by the price reporter
@@ -26,11 +38,23 @@ pub struct Cli {
     /// The websocket port
     #[clap(long, default_value = "4000", env = "WS_PORT")]
     pub ws_port: u16,
+    /// The maximum number of topics a websocket client may subscribe to,
+    /// counted across its connections for API key clients and per
+    /// connection otherwise. If unset, subscriptions are uncapped except by
+    /// per-key limits
+    #[clap(long, env = "MAX_WS_SUBSCRIPTIONS")]
+    pub max_ws_subscriptions: Option<usize>,
     /// The admin key, as a base64-encoded string.
     ///
     /// If not provided, the admin API will be disabled.
     #[clap(long, env = "ADMIN_KEY")]
     pub admin_key: Option<String>,
+    /// The path to a JSON file of API keys accepted from price consumers.
+    ///
+    /// If provided, the websocket server and HTTP price routes require a
+    /// known key. If not provided, they are open to all clients.
+    #[clap(long, env = "API_KEYS_PATH")]
+    pub api_keys_path: Option<String>,
 
     // --- Environment --- //
     /// The path to the token remap file.
@@ -38,6 +62,13 @@ pub struct Cli {
     /// If not provided, the remap will be fetched from Github.
     #[clap(long, env = "TOKEN_REMAP_PATH")]
     pub token_remap_path: Option<String>,
+    /// The interval, in seconds, at which to poll the token remap for changes.
+    ///
+    /// A remap file is reloaded when its modification time changes; a remap
+    /// fetched from Github is re-fetched on every poll. If not provided, the
+    /// remap is only reloaded through the admin API.
+    #[clap(long, env = "TOKEN_REMAP_POLL_INTERVAL_SECS")]
+    pub token_remap_poll_interval_secs: Option<u64>,
     /// The chains to use for token remappings, as a comma-separated list.
     #[clap(long, env = "CHAIN_ID", default_value = "devnet", value_delimiter = ',', num_args = 1..)]
     pub chains: Vec<Chain>,
@@ -52,9 +83,79 @@ pub struct Cli {
     /// The Ethereum RPC node websocket address
     #[clap(long, env = "ETH_WS_ADDR")]
     pub eth_ws_addr: Option<String>,
+    /// The address of the Uniswap V3 factory to look up pools from
+    ///
+    /// Defaults to the canonical deployment, may be overridden to point at a
+    /// factory deployed on a local node
+    #[clap(long, env = "UNISWAP_V3_FACTORY")]
+    pub uniswap_v3_factory: Option<Address>,
     /// The exchanges to disable price reporting for, as a comma-separated list.
     #[clap(long, env = "DISABLED_EXCHANGES", default_value = "uniswapv3", value_delimiter = ',', num_args = 1..)]
     pub disabled_exchanges: Vec<Exchange>,
+    /// The fallback exchanges for canonical price streams, as a
+    /// comma-separated list of `<TICKER>=<exchange>[:<exchange>...]` entries.
+    ///
+    /// When a ticker's canonical exchange stream exhausts its retries, the
+    /// stream fails over to the first healthy fallback in order, and fails
+    /// back once the canonical exchange recovers. Prices from a fallback are
+    /// converted into the stream's quote, and the USDC conversion streams of
+    /// canonical pairs fail over with them.
+    #[clap(long, env = "FALLBACK_EXCHANGES", value_delimiter = ',', num_args = 1..)]
+    pub fallback_exchanges: Vec<FallbackEntry>,
+
+    // --- Frame Recording --- //
+    /// The directory to which the raw websocket frames of every price
+    /// connection are recorded, one file per pair
+    #[clap(long, env = "RECORD_FRAMES_DIR", conflicts_with = "replay_frames_dir")]
+    pub record_frames_dir: Option<String>,
+    /// The directory from which recorded websocket frames are replayed
+    #[clap(long, env = "REPLAY_FRAMES_DIR", requires = "replay_pairs")]
+    pub replay_frames_dir: Option<String>,
+    /// The pairs to replay from recorded frames, as topics of the form
+    /// `<exchange>-<BASE>-<QUOTE>`, e.g. `binance-WETH-USDT`.
+    ///
+    /// These pairs are served by the replay pseudo-exchange in place of their
+    /// live exchanges, while all other pairs connect to the live exchanges.
+    #[clap(
+        long,
+        env = "REPLAY_PAIRS",
+        value_delimiter = ',',
+        num_args = 1..,
+        requires = "replay_frames_dir"
+    )]
+    pub replay_pairs: Vec<String>,
+    /// The factor by which to accelerate the recorded timing of replayed
+    /// frames, or zero to replay frames without delay
+    #[clap(long, env = "REPLAY_SPEED", default_value = "1.0")]
+    pub replay_speed: f64,
+
+    // --- Composite Prices --- //
+    /// The maximum deviation of an exchange's price from the cross-exchange
+    /// median, in basis points, before it is excluded from composite prices
+    #[clap(long, env = "COMPOSITE_MAX_DEVIATION_BPS", default_value = "100")]
+    pub composite_max_deviation_bps: u32,
+    /// The minimum number of in-band exchange prices required to publish a
+    /// composite price
+    #[clap(long, env = "COMPOSITE_MIN_SOURCES", default_value = "1")]
+    pub composite_min_sources: usize,
+    /// The method used to aggregate in-band exchange prices into a composite
+    /// price
+    #[clap(long, env = "COMPOSITE_METHOD", value_enum, default_value = "median")]
+    pub composite_method: CompositeMethod,
+
+    // --- Price History --- //
+    /// Whether or not to record the history of published prices
+    #[clap(long, env = "RECORD_PRICE_HISTORY")]
+    pub record_price_history: bool,
+    /// The number of ticks of price history to retain in memory per topic
+    #[clap(long, env = "PRICE_HISTORY_CAPACITY", default_value = "100000")]
+    pub price_history_capacity: usize,
+    /// The path of a file to which every recorded tick is appended as a JSON
+    /// line
+    ///
+    /// The file is periodically compacted to the ticks retained in memory.
+    #[clap(long, env = "PRICE_HISTORY_SINK_PATH")]
+    pub price_history_sink_path: Option<String>,
 
     // --- Telemetry --- //
     /// Whether or not to enable Datadog-formatted logs
@@ -80,13 +181,58 @@ impl Cli {
             ws_port: self.ws_port,
             admin_key,
             token_remap_path: self.token_remap_path.clone(),
+            token_remap_poll_interval: self.token_remap_poll_interval_secs.map(Duration::from_secs),
             chains: self.chains.clone(),
             exchange_conn_config: ExchangeConnectionsConfig {
                 coinbase_key_name: self.coinbase_api_key.clone(),
                 coinbase_key_secret: self.coinbase_api_secret.clone(),
                 eth_websocket_addr: self.eth_ws_addr.clone(),
+                uniswap_v3_factory: self.uniswap_v3_factory,
+                fallback_exchanges: build_fallback_exchanges(
+                    &self.fallback_exchanges,
+                    &self.disabled_exchanges,
+                ),
+                frame_recorder: self
+                    .record_frames_dir
+                    .as_deref()
+                    .map(FrameRecorder::new)
+                    .transpose()?,
+                frame_replay: self
+                    .replay_frames_dir
+                    .as_deref()
+                    .map(|dir| ReplayConfig::new(dir, self.replay_speed, &self.replay_pairs)),
             },
             disabled_exchanges: self.disabled_exchanges.clone(),
+            composite_config: self.parse_composite_config(),
+            price_history_config: self.parse_price_history_config(),
+            max_ws_subscriptions: self.max_ws_subscriptions,
+            api_keys: self.api_keys_path.as_deref().map(ApiKeys::from_file).transpose()?,
+        })
+    }
+
+    /// Parse the CLI arguments into a `CompositeConfig`
+    fn parse_composite_config(&self) -> CompositeConfig {
+        // Composite prices are sourced from every enabled exchange, excluding
+        // `Renegade` which is itself an alias for the canonical exchange
+        let source_exchanges = Exchange::all()
+            .into_iter()
+            .filter(|e| *e != Exchange::Renegade && !self.disabled_exchanges.contains(e))
+            .collect();
+
+        CompositeConfig {
+            source_exchanges,
+            max_deviation_bps: self.composite_max_deviation_bps,
+            min_sources: self.composite_min_sources,
+            method: self.composite_method,
+        }
+    }
+
+    /// Parse the CLI arguments into a `PriceHistoryConfig`, if price history
+    /// is enabled
+    fn parse_price_history_config(&self) -> Option<PriceHistoryConfig> {
+        self.record_price_history.then(|| PriceHistoryConfig {
+            capacity: self.price_history_capacity,
+            sink_path: self.price_history_sink_path.clone(),
         })
     }
 
@@ -0,0 +1,384 @@
+//! Composite price sources
+//!
+//! A composite price aggregates a pair's price across every enabled exchange
+//! that lists it, rejecting sources that deviate too far from the
+//! cross-exchange median. This protects consumers from a single bad exchange
+//! feed moving the price directly.
+//!
+//! The volume-weighted method weights each in-band source by the size resting
+//! in the top levels of its order book, as published on the source's depth
+//! feed.
+//!
+//! Composite topics take the form `composite-<base mint>[-<quote mint>]`. The
+//! quote must be USDC, and defaults to USDC if omitted, mirroring the format
+//! of `Renegade` topics.
+
+use std::{collections::HashMap, time::Duration};
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use renegade_types_core::{Exchange, Price, Token, USDC_TICKER};
+
+use crate::{
+    errors::ServerError,
+    exchanges::ExchangeConnectionsConfig,
+    get_supported_exchanges,
+    utils::{BPS_PER_ONE, PairInfo, PriceUpdate, get_token_and_chain, resolve_tokens_and_chain},
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The prefix identifying a composite price topic
+pub const COMPOSITE_TOPIC_PREFIX: &str = "composite";
+/// The maximum age of a source's last price before it is excluded from the
+/// composite price.
+///
+/// Age is measured from the time the price was received from the exchange.
+/// Heartbeat replays keep that receive time, so a stalled exchange ages out
+/// even while its stream keeps replaying the cached price.
+pub const COMPOSITE_SOURCE_MAX_AGE: Duration = Duration::from_secs(30);
+
+// ---------
+// | Types |
+// ---------
+
+/// The method used to aggregate in-band source prices into a composite price
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CompositeMethod {
+    /// The median of the in-band source prices
+    #[default]
+    Median,
+    /// The arithmetic mean of the in-band source prices
+    Mean,
+    /// The mean of the in-band source prices, weighted by the size resting in
+    /// the top levels of each source's order book
+    ///
+    /// Sources without a depth feed are excluded from the weighting, and the
+    /// median is used if no in-band source has a depth feed.
+    VolumeWeighted,
+}
+
+/// The configuration options for composite price streams
+#[derive(Clone, Debug)]
+pub struct CompositeConfig {
+    /// The exchanges from which composite prices may be sourced
+    pub source_exchanges: Vec<Exchange>,
+    /// The maximum deviation of a source price from the cross-exchange
+    /// median, in basis points, before the source is rejected
+    pub max_deviation_bps: u32,
+    /// The minimum number of in-band sources required to publish a price
+    pub min_sources: usize,
+    /// The method used to aggregate in-band source prices
+    pub method: CompositeMethod,
+}
+
+/// The result of aggregating a set of source prices
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompositePrice {
+    /// The aggregated price, if enough sources were in-band
+    pub price: Option<Price>,
+    /// The sources rejected as outliers
+    pub rejected: Vec<Exchange>,
+}
+
+/// The pair for which a composite price is computed
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CompositePair {
+    /// The base token
+    pub base: Token,
+    /// The quote token, always USDC
+    pub quote: Token,
+}
+
+impl CompositePair {
+    /// Parse a composite pair from a given topic
+    pub fn from_topic(topic: &str) -> Result<Self, ServerError> {
+        let parts: Vec<&str> = topic.split('-').collect();
+        if parts[0] != COMPOSITE_TOPIC_PREFIX {
+            return Err(ServerError::InvalidPairInfo(format!("not a composite topic `{topic}`")));
+        }
+
+        let (base, quote) = match parts.len() {
+            // The quote may be omitted, in which case we use USDC
+            2 => {
+                let (base, chain) = get_token_and_chain(parts[1]).ok_or_else(|| {
+                    ServerError::InvalidPairInfo(format!("invalid token `{}`", parts[1]))
+                })?;
+                (base, Token::from_ticker_on_chain(USDC_TICKER, chain))
+            },
+            3 => {
+                let (base, quote, _chain) = resolve_tokens_and_chain(parts[1], parts[2])
+                    .ok_or_else(|| {
+                        ServerError::InvalidPairInfo(format!(
+                            "invalid token pair `{}`-`{}`",
+                            parts[1], parts[2]
+                        ))
+                    })?;
+                (base, quote)
+            },
+            _ => {
+                return Err(ServerError::InvalidPairInfo(format!(
+                    "invalid composite topic `{topic}`"
+                )));
+            },
+        };
+
+        if quote.get_ticker().as_deref() != Some(USDC_TICKER) {
+            return Err(ServerError::InvalidPairInfo(format!(
+                "composite quote must be USDC, got {quote}"
+            )));
+        }
+
+        Ok(Self { base, quote })
+    }
+
+    /// Get the topic name for the composite pair
+    pub fn to_topic(&self) -> String {
+        format!("{COMPOSITE_TOPIC_PREFIX}-{}-{}", self.base, self.quote)
+    }
+
+    /// Get the pairs from which the composite price is sourced, one per
+    /// enabled exchange listing the base token
+    pub fn source_pairs(
+        &self,
+        composite_config: &CompositeConfig,
+        config: &ExchangeConnectionsConfig,
+    ) -> Vec<PairInfo> {
+        let supported_exchanges = get_supported_exchanges(&self.base, config);
+        let base_mint = self.base.get_addr();
+
+        composite_config
+            .source_exchanges
+            .iter()
+            .filter(|exchange| supported_exchanges.contains(exchange))
+            .filter_map(|exchange| PairInfo::new_default_stable(*exchange, &base_mint).ok())
+            .collect_vec()
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Whether the given topic refers to a composite price
+pub fn is_composite_topic(topic: &str) -> bool {
+    topic.split('-').next() == Some(COMPOSITE_TOPIC_PREFIX)
+}
+
+/// Drop every source whose latest price was received more than
+/// `COMPOSITE_SOURCE_MAX_AGE` before `now`, in milliseconds since the epoch
+pub fn retain_fresh_sources(latest_prices: &mut HashMap<Exchange, PriceUpdate>, now: u64) {
+    let max_age = COMPOSITE_SOURCE_MAX_AGE.as_millis() as u64;
+    latest_prices.retain(|_, update| update.age_ms(now) < max_age);
+}
+
+/// Aggregate the latest price from each source into a composite price.
+///
+/// Non-finite and non-positive prices are ignored. Sources whose price
+/// deviates from the median of all sources by more than the configured band
+/// are rejected, and the remaining in-band sources are aggregated using the
+/// configured method. No price is produced if fewer than `min_sources`
+/// sources remain.
+///
+/// `volumes` holds the size resting in the top of each source's book, and is
+/// only used by the volume-weighted method.
+pub fn aggregate_prices(
+    sources: &[(Exchange, Price)],
+    volumes: &HashMap<Exchange, f64>,
+    config: &CompositeConfig,
+) -> CompositePrice {
+    let valid_sources =
+        sources.iter().copied().filter(|(_, price)| price.is_finite() && *price > 0.).collect_vec();
+
+    let all_prices = valid_sources.iter().map(|(_, price)| *price).collect_vec();
+    let reference = match median(&all_prices) {
+        Some(median) => median,
+        None => return CompositePrice { price: None, rejected: Vec::new() },
+    };
+
+    let band = reference * config.max_deviation_bps as f64 / BPS_PER_ONE;
+    let (in_band, rejected): (Vec<_>, Vec<_>) =
+        valid_sources.into_iter().partition(|(_, price)| (price - reference).abs() <= band);
+
+    let rejected = rejected.into_iter().map(|(exchange, _)| exchange).collect_vec();
+    if in_band.is_empty() || in_band.len() < config.min_sources {
+        return CompositePrice { price: None, rejected };
+    }
+
+    let in_band_prices = in_band.iter().map(|(_, price)| *price).collect_vec();
+    let price = match config.method {
+        CompositeMethod::Median => median(&in_band_prices),
+        CompositeMethod::Mean => {
+            Some(in_band_prices.iter().sum::<f64>() / in_band_prices.len() as f64)
+        },
+        CompositeMethod::VolumeWeighted => {
+            volume_weighted_mean(&in_band, volumes).or_else(|| median(&in_band_prices))
+        },
+    };
+
+    CompositePrice { price, rejected }
+}
+
+/// Compute the mean of the given source prices weighted by each source's
+/// volume, returning `None` if no source has a positive volume
+fn volume_weighted_mean(
+    sources: &[(Exchange, Price)],
+    volumes: &HashMap<Exchange, f64>,
+) -> Option<Price> {
+    let weighted = sources
+        .iter()
+        .filter_map(|(exchange, price)| {
+            let volume = volumes.get(exchange).copied()?;
+            (volume.is_finite() && volume > 0.).then_some((*price, volume))
+        })
+        .collect_vec();
+
+    let total_volume = weighted.iter().map(|(_, volume)| volume).sum::<f64>();
+    if total_volume <= 0. {
+        return None;
+    }
+
+    Some(weighted.iter().map(|(price, volume)| price * volume).sum::<f64>() / total_volume)
+}
+
+/// Compute the median of a set of prices, returning `None` if the set is
+/// empty
+fn median(prices: &[Price]) -> Option<Price> {
+    if prices.is_empty() {
+        return None;
+    }
+
+    let sorted = prices.iter().copied().sorted_by(|a, b| a.total_cmp(b)).collect_vec();
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        Some((sorted[mid - 1] + sorted[mid]) / 2.)
+    } else {
+        Some(sorted[mid])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a composite config with the given band and quorum
+    fn config(
+        max_deviation_bps: u32,
+        min_sources: usize,
+        method: CompositeMethod,
+    ) -> CompositeConfig {
+        CompositeConfig { source_exchanges: Vec::new(), max_deviation_bps, min_sources, method }
+    }
+
+    #[test]
+    fn median_of_agreeing_sources() {
+        let sources = [(Exchange::Binance, 100.), (Exchange::Okx, 101.), (Exchange::Kraken, 102.)];
+        let res =
+            aggregate_prices(&sources, &HashMap::new(), &config(500, 1, CompositeMethod::Median));
+
+        assert_eq!(res.price, Some(101.));
+        assert!(res.rejected.is_empty());
+    }
+
+    #[test]
+    fn mean_of_agreeing_sources() {
+        let sources = [(Exchange::Binance, 100.), (Exchange::Okx, 102.)];
+        let res =
+            aggregate_prices(&sources, &HashMap::new(), &config(500, 1, CompositeMethod::Mean));
+
+        assert_eq!(res.price, Some(101.));
+    }
+
+    #[test]
+    fn volume_weighted_mean_of_agreeing_sources() {
+        let sources = [(Exchange::Binance, 100.), (Exchange::Okx, 104.), (Exchange::Kraken, 102.)];
+        let volumes = HashMap::from([(Exchange::Binance, 3.), (Exchange::Okx, 1.)]);
+        let res =
+            aggregate_prices(&sources, &volumes, &config(500, 1, CompositeMethod::VolumeWeighted));
+
+        // Kraken has no depth feed, so it is excluded from the weighting
+        assert_eq!(res.price, Some(101.));
+        assert!(res.rejected.is_empty());
+    }
+
+    #[test]
+    fn volume_weighted_falls_back_to_median_without_volumes() {
+        let sources = [(Exchange::Binance, 100.), (Exchange::Okx, 101.), (Exchange::Kraken, 105.)];
+        let volumes = HashMap::from([(Exchange::Binance, 0.)]);
+        let res =
+            aggregate_prices(&sources, &volumes, &config(500, 1, CompositeMethod::VolumeWeighted));
+
+        assert_eq!(res.price, Some(101.));
+    }
+
+    /// A single bad feed must not move the composite price
+    #[test]
+    fn outlier_is_rejected() {
+        let sources = [
+            (Exchange::Binance, 100.),
+            (Exchange::Coinbase, 100.5),
+            (Exchange::Kraken, 99.5),
+            (Exchange::Okx, 50.),
+        ];
+        let res =
+            aggregate_prices(&sources, &HashMap::new(), &config(100, 1, CompositeMethod::Mean));
+
+        assert_eq!(res.price, Some(100.));
+        assert_eq!(res.rejected, vec![Exchange::Okx]);
+    }
+
+    #[test]
+    fn quorum_not_met_returns_none() {
+        let sources = [(Exchange::Binance, 100.), (Exchange::Okx, 150.)];
+        let res =
+            aggregate_prices(&sources, &HashMap::new(), &config(100, 1, CompositeMethod::Median));
+
+        assert_eq!(res.price, None);
+        assert_eq!(res.rejected.len(), 2);
+
+        let sources = [(Exchange::Binance, 100.)];
+        let res =
+            aggregate_prices(&sources, &HashMap::new(), &config(100, 2, CompositeMethod::Median));
+        assert_eq!(res.price, None);
+    }
+
+    #[test]
+    fn invalid_prices_are_ignored() {
+        let sources = [
+            (Exchange::Binance, 100.),
+            (Exchange::Coinbase, 0.),
+            (Exchange::Kraken, f64::NAN),
+            (Exchange::Okx, f64::INFINITY),
+        ];
+        let res =
+            aggregate_prices(&sources, &HashMap::new(), &config(100, 1, CompositeMethod::Median));
+
+        assert_eq!(res.price, Some(100.));
+        assert!(res.rejected.is_empty());
+    }
+
+    /// A stalled source whose stream keeps replaying its cached price must
+    /// still age out of the composite
+    #[test]
+    fn replayed_stale_source_is_excluded() {
+        // The heartbeat replays the stalled source's cached update well past
+        // the max age
+        let now = 1_000 + COMPOSITE_SOURCE_MAX_AGE.as_millis() as u64;
+        let stalled = PriceUpdate::new_composite(100., 1_000);
+        let fresh = PriceUpdate::new_composite(101., now);
+        let mut latest_prices =
+            HashMap::from([(Exchange::Binance, stalled), (Exchange::Okx, fresh)]);
+        retain_fresh_sources(&mut latest_prices, now);
+
+        assert_eq!(latest_prices.len(), 1);
+        assert!(latest_prices.contains_key(&Exchange::Okx));
+    }
+
+    #[test]
+    fn no_sources_returns_none() {
+        let res = aggregate_prices(&[], &HashMap::new(), &config(100, 1, CompositeMethod::Median));
+        assert_eq!(res, CompositePrice { price: None, rejected: Vec::new() });
+    }
+}
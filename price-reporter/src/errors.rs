@@ -48,6 +48,22 @@ pub enum ServerError {
     /// An error indicating that the price stream is closed
     #[error("Price stream closed")]
     PriceStreamClosed,
+    /// An error recording or querying price history
+    #[error("Error in price history: {0}")]
+    PriceHistory(String),
+    /// An error indicating that price history is disabled
+    #[error("Price history is disabled")]
+    PriceHistoryDisabled,
+    /// An error indicating that a request was malformed
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    /// An error indicating that a websocket client has reached its
+    /// subscription limit
+    #[error("Subscription limit reached: at most {0} topics")]
+    TooManySubscriptions(usize),
+    /// An error indicating that a client has exceeded its request rate limit
+    #[error("Rate limit exceeded for client {0}")]
+    RateLimited(String),
 }
 
 impl ServerError {
@@ -55,7 +71,9 @@ impl ServerError {
     pub fn invalid_pair_info(pair_info: &PairInfo) -> Self {
         Self::InvalidPairInfo(format!(
             "{}:{}:{}",
-            pair_info.exchange, pair_info.base, pair_info.quote
+            pair_info.source(),
+            pair_info.base,
+            pair_info.quote
         ))
     }
 
@@ -7,7 +7,7 @@ use std::{
 
 use async_trait::async_trait;
 use futures_util::{Stream, StreamExt};
-use renegade_types_core::{Exchange, Price, PriceReport, Token};
+use renegade_types_core::{Exchange, PriceReport, Token};
 use renegade_util::{err_str, get_current_time_millis};
 use serde_json::Value;
 use tungstenite::Message;
@@ -17,8 +17,16 @@ use crate::{
     PairInfo,
     exchanges::{
         ExchangeConnectionsConfig,
-        connection::{BoxedPriceReader, BoxedWsWriter, InitializablePriceStream, PriceStreamType},
+        connection::{
+            BoxedPriceReader, BoxedWsWriter, ExchangePrice, InitializablePriceStream,
+            PriceStreamType,
+        },
+        depth::{
+            BoxedDepthReader, DEPTH_SNAPSHOT_LEVELS, DepthConnection, DepthSnapshot,
+            DepthStreamType,
+        },
         error::ExchangeConnectionError,
+        replay::maybe_record,
         util::{
             exchange_lists_pair_tokens, get_base_exchange_ticker, get_quote_exchange_ticker,
             safe_midpoint,
@@ -29,7 +37,8 @@ use crate::{
 };
 
 use super::connection::{
-    ExchangeConnection, parse_json_field, parse_json_from_message, ws_connect, ws_ping,
+    ExchangeConnection, parse_json_field, parse_json_from_message, parse_json_levels, ws_connect,
+    ws_ping,
 };
 
 // -------------
@@ -51,6 +60,13 @@ const BINANCE_BID_PRICE_WS: &str = "b";
 /// The name of the best offer field in a websocket message
 const BINANCE_OFFER_PRICE_WS: &str = "a";
 
+/// The partial book depth stream to subscribe to for depth snapshots
+const BINANCE_DEPTH_STREAM: &str = "depth20@100ms";
+/// The name of the bids field in a partial book depth message
+const BINANCE_DEPTH_BIDS: &str = "bids";
+/// The name of the asks field in a partial book depth message
+const BINANCE_DEPTH_ASKS: &str = "asks";
+
 // --------------
 // | Connection |
 // --------------
@@ -70,6 +86,15 @@ impl BinanceConnection {
     fn websocket_url(
         base_token: Token,
         quote_token: Token,
+    ) -> Result<Url, ExchangeConnectionError> {
+        Self::stream_url(base_token, quote_token, "bookTicker")
+    }
+
+    /// Construct the websocket url for the given stream on the asset pair
+    fn stream_url(
+        base_token: Token,
+        quote_token: Token,
+        stream: &str,
     ) -> Result<Url, ExchangeConnectionError> {
         let base_ticker =
             get_base_exchange_ticker(base_token.clone(), quote_token.clone(), Exchange::Binance)?;
@@ -77,7 +102,7 @@ impl BinanceConnection {
         let quote_ticker = get_quote_exchange_ticker(base_token, quote_token, Exchange::Binance)?;
 
         let url = Url::parse(&format!(
-            "{BINANCE_WS_BASE_URL}/{}{}@bookTicker",
+            "{BINANCE_WS_BASE_URL}/{}{}@{stream}",
             base_ticker.to_lowercase(),
             quote_ticker.to_lowercase()
         ))
@@ -133,10 +158,10 @@ impl BinanceConnection {
     }
 
     /// Parse a price report from an incoming message
-    fn midpoint_from_ws_message(
+    pub(crate) fn midpoint_from_ws_message(
         message: Message,
         pair_info: &PairInfo,
-    ) -> Result<Option<Price>, ExchangeConnectionError> {
+    ) -> Result<Option<ExchangePrice>, ExchangeConnectionError> {
         // Deserialize the message into a JSON object
         if let Some(json_blob) = parse_json_from_message(message, pair_info)? {
             // Raw numbers are ignored
@@ -150,7 +175,7 @@ impl BinanceConnection {
             // A non-finite or non-positive bid/offer would corrupt the midpoint
             // (see incident 2026-05-08 cbBTC). Drop the message rather than emit
             // a bad price; the stream resumes on the next valid update.
-            Ok(safe_midpoint(best_bid, best_offer))
+            Ok(safe_midpoint(best_bid, best_offer).map(ExchangePrice::from))
         } else {
             Ok(None)
         }
@@ -170,7 +195,7 @@ impl Stream for BinanceConnection {
 impl ExchangeConnection for BinanceConnection {
     async fn connect(
         pair_info: PairInfo,
-        _config: &ExchangeConnectionsConfig,
+        config: &ExchangeConnectionsConfig,
     ) -> Result<Self, ExchangeConnectionError>
     where
         Self: Sized,
@@ -185,6 +210,7 @@ impl ExchangeConnection for BinanceConnection {
         // Connect to the websocket
         let url = Self::websocket_url(base_token, quote_token)?;
         let (write, read) = ws_connect(url).await?;
+        let read = maybe_record(read, &pair_info, config);
 
         // Map the stream to process midpoint prices
         let mapped_stream = read.filter_map(move |message| {
@@ -255,3 +281,85 @@ impl ExchangeConnection for BinanceConnection {
         Ok(response.status().is_success())
     }
 }
+
+// --------------------
+// | Depth Connection |
+// --------------------
+
+/// The connection handle for Binance order book depth data
+pub struct BinanceDepthConnection {
+    /// The underlying depth stream
+    depth_stream: BoxedDepthReader,
+    /// The underlying write stream of the websocket
+    write_stream: BoxedWsWriter,
+}
+
+impl BinanceDepthConnection {
+    /// Parse a depth snapshot from a partial book depth message
+    ///
+    /// Binance publishes the full top of the book on every message, so no
+    /// local book is maintained.
+    fn depth_from_ws_message(
+        message: Message,
+        pair_info: &PairInfo,
+    ) -> Result<Option<DepthSnapshot>, ExchangeConnectionError> {
+        let json_blob = match parse_json_from_message(message, pair_info)? {
+            // Raw numbers are ignored
+            Some(Value::Number(_)) | None => return Ok(None),
+            Some(json_blob) => json_blob,
+        };
+
+        let bids = parse_json_levels(&json_blob[BINANCE_DEPTH_BIDS])?;
+        let asks = parse_json_levels(&json_blob[BINANCE_DEPTH_ASKS])?;
+        Ok(DepthSnapshot::from_levels(&bids, &asks, DEPTH_SNAPSHOT_LEVELS))
+    }
+}
+
+impl Stream for BinanceDepthConnection {
+    type Item = DepthStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.depth_stream.as_mut().poll_next_unpin(cx)
+    }
+}
+
+#[async_trait]
+impl DepthConnection for BinanceDepthConnection {
+    async fn connect(
+        pair_info: PairInfo,
+        _config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError> {
+        let url = BinanceConnection::stream_url(
+            pair_info.base_token(),
+            pair_info.quote_token(),
+            BINANCE_DEPTH_STREAM,
+        )?;
+        let (write, read) = ws_connect(url).await?;
+
+        let mapped_stream = read.filter_map(move |message| {
+            let pair_info = pair_info.clone();
+            async move {
+                match message.map(|message| Self::depth_from_ws_message(message, &pair_info)) {
+                    Ok(mapped_res) => mapped_res.transpose(),
+                    Err(e) => {
+                        log_task!(
+                            Task::ExchangeConnection,
+                            Outcome::Failed,
+                            exchange = "binance",
+                            error = %e,
+                            "error reading message from depth websocket"
+                        );
+                        Some(Err(ExchangeConnectionError::ConnectionHangup(e.to_string())))
+                    },
+                }
+            }
+        });
+
+        Ok(Self { depth_stream: Box::new(Box::pin(mapped_stream)), write_stream: Box::new(write) })
+    }
+
+    async fn send_keepalive(&mut self) -> Result<(), ExchangeConnectionError> {
+        ws_ping(&mut self.write_stream).await
+    }
+}
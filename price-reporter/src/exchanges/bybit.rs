@@ -0,0 +1,288 @@
+//! Defines the logic for connecting to Bybit spot markets
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures_util::{SinkExt, Stream, StreamExt};
+use renegade_types_core::Token;
+use renegade_util::err_str;
+use serde_json::{Value, json};
+use tungstenite::Message;
+use url::Url;
+
+use crate::{
+    exchanges::{
+        ExchangeConnectionsConfig,
+        connection::{
+            BoxedPriceReader, BoxedWsWriter, ExchangePrice, InitializablePriceStream,
+            PriceStreamType,
+        },
+        error::ExchangeConnectionError,
+        replay::maybe_record,
+        util::safe_midpoint,
+        venue::Venue,
+    },
+    log_task,
+    logger::{Outcome, Task},
+    utils::PairInfo,
+};
+
+use super::connection::{
+    ExchangeConnection, parse_json_field_array, parse_json_from_message, ws_connect,
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The websocket endpoint for Bybit spot market data
+const BYBIT_WS_URL: &str = "wss://stream.bybit.com/v5/public/spot";
+/// The base URL for the Bybit REST API
+const BYBIT_REST_BASE_URL: &str = "https://api.bybit.com/v5";
+
+/// The text of a Bybit ping message
+const BYBIT_PING_MESSAGE: &str = r#"{"op":"ping"}"#;
+/// The prefix of the top-of-book order book topic
+const BYBIT_TOP_OF_BOOK_TOPIC: &str = "orderbook.1";
+
+/// The field name for the topic on a Bybit websocket message, absent on
+/// subscription acks and pongs
+const BYBIT_TOPIC: &str = "topic";
+/// The field name for the exchange timestamp on a Bybit websocket message, in
+/// milliseconds since the epoch
+const BYBIT_TIMESTAMP: &str = "ts";
+/// The field name for the data on a Bybit websocket message
+const BYBIT_DATA: &str = "data";
+/// The field name for bids on a Bybit order book message
+const BYBIT_BIDS: &str = "b";
+/// The field name for asks on a Bybit order book message
+const BYBIT_ASKS: &str = "a";
+/// The index of the best bid or ask in a Bybit order book message
+const FIRST_ENTRY: usize = 0;
+/// The index of the price in a bid or ask level
+const BYBIT_PRICE: usize = 0;
+
+/// The field name for the return code on a Bybit REST response
+const BYBIT_RET_CODE: &str = "retCode";
+/// The field name for the result on a Bybit REST response
+const BYBIT_RESULT: &str = "result";
+/// The field name for the list of instruments on an instruments response
+const BYBIT_LIST: &str = "list";
+
+// --------------
+// | Connection |
+// --------------
+
+/// The connection handle for Bybit spot price data
+pub struct BybitConnection {
+    /// The underlying price stream
+    price_stream: BoxedPriceReader,
+    /// The underlying write stream of the websocket
+    write_stream: BoxedWsWriter,
+}
+
+impl BybitConnection {
+    /// Get the Bybit symbol of the given pair, e.g. `ETHUSDT`
+    fn symbol(base_token: &Token, quote_token: &Token) -> Result<String, ExchangeConnectionError> {
+        let base_ticker = Venue::Bybit.ticker(base_token);
+        let quote_ticker = Venue::Bybit.ticker(quote_token);
+        match (base_ticker, quote_ticker) {
+            (Some(base), Some(quote)) => Ok(format!("{base}{quote}")),
+            _ => Err(ExchangeConnectionError::UnsupportedPair(
+                base_token.clone(),
+                quote_token.clone(),
+                Venue::Bybit.ticker_exchange(),
+            )),
+        }
+    }
+
+    /// Construct the message subscribing to the top of the pair's book
+    fn subscribe_message(pair_info: &PairInfo) -> Result<String, ExchangeConnectionError> {
+        let symbol = Self::symbol(&pair_info.base_token(), &pair_info.quote_token())?;
+        Ok(json!({
+            "op": "subscribe",
+            "args": [format!("{BYBIT_TOP_OF_BOOK_TOPIC}.{symbol}")],
+        })
+        .to_string())
+    }
+
+    /// Parse a price from a Bybit websocket message
+    pub(crate) fn midpoint_from_ws_message(
+        message: Message,
+        pair_info: &PairInfo,
+    ) -> Result<Option<ExchangePrice>, ExchangeConnectionError> {
+        let message_json = match parse_json_from_message(message, pair_info)? {
+            Some(json) => json,
+            None => return Ok(None),
+        };
+
+        // Subscription acks and pongs carry no topic
+        if message_json[BYBIT_TOPIC].is_null() {
+            return Ok(None);
+        }
+
+        // A side may be empty if its book is empty
+        let data = &message_json[BYBIT_DATA];
+        let (best_bid, best_offer) =
+            (&data[BYBIT_BIDS][FIRST_ENTRY], &data[BYBIT_ASKS][FIRST_ENTRY]);
+        if best_bid.is_null() || best_offer.is_null() {
+            return Ok(None);
+        }
+
+        let best_bid: f64 = parse_json_field_array(BYBIT_PRICE, best_bid)?;
+        let best_offer: f64 = parse_json_field_array(BYBIT_PRICE, best_offer)?;
+        let exchange_timestamp = message_json[BYBIT_TIMESTAMP].as_u64();
+
+        // A non-finite or non-positive bid/offer would corrupt the midpoint
+        // (see incident 2026-05-08 cbBTC). Drop the message rather than emit
+        // a bad price; the stream resumes on the next valid update.
+        Ok(safe_midpoint(best_bid, best_offer)
+            .map(|price| ExchangePrice { price, exchange_timestamp }))
+    }
+}
+
+impl Stream for BybitConnection {
+    type Item = PriceStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.price_stream.as_mut().poll_next_unpin(cx)
+    }
+}
+
+#[async_trait]
+impl ExchangeConnection for BybitConnection {
+    async fn connect(
+        pair_info: PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError>
+    where
+        Self: Sized,
+    {
+        // Connect to the websocket
+        let url = Url::parse(BYBIT_WS_URL).expect("Bybit websocket url should parse");
+        let (mut write, read) = ws_connect(url).await?;
+        let read = maybe_record(read, &pair_info, config);
+
+        // Subscribe to the top of the pair's book
+        let subscribe_str = Self::subscribe_message(&pair_info)?;
+        write
+            .send(Message::Text(subscribe_str))
+            .await
+            .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))?;
+
+        // Map the stream to process midpoint prices
+        let mapped_stream = read.filter_map(move |message| {
+            let pair_info = pair_info.clone();
+            async move {
+                match message.map(|message| Self::midpoint_from_ws_message(message, &pair_info)) {
+                    // The outer `Result` comes from reading the message from the websocket
+                    // Processing the message returns a `Result<Option<..>>` which we
+                    // flip to match the stream type
+                    Ok(mapped_res) => mapped_res.transpose(),
+
+                    // Error reading from the websocket
+                    Err(e) => {
+                        log_task!(
+                            Task::ExchangeConnection,
+                            Outcome::Failed,
+                            exchange = "bybit",
+                            error = %e,
+                            "error reading message from websocket"
+                        );
+                        Some(Err(ExchangeConnectionError::ConnectionHangup(e.to_string())))
+                    },
+                }
+            }
+        });
+
+        let price_stream = InitializablePriceStream::new(Box::pin(mapped_stream));
+        Ok(Self { price_stream: Box::new(price_stream), write_stream: Box::new(write) })
+    }
+
+    async fn send_keepalive(&mut self) -> Result<(), ExchangeConnectionError> {
+        // Bybit expects an application level ping rather than a ping frame
+        self.write_stream
+            .send(Message::Text(String::from(BYBIT_PING_MESSAGE)))
+            .await
+            .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))
+    }
+
+    async fn supports_pair(
+        base_token: &Token,
+        quote_token: &Token,
+    ) -> Result<bool, ExchangeConnectionError> {
+        let symbol = match Self::symbol(base_token, quote_token) {
+            Ok(symbol) => symbol,
+            Err(_) => return Ok(false),
+        };
+
+        // Query the `instruments-info` endpoint about the pair
+        let request_url =
+            format!("{BYBIT_REST_BASE_URL}/market/instruments-info?category=spot&symbol={symbol}");
+        let response = reqwest::get(request_url)
+            .await
+            .map_err(err_str!(ExchangeConnectionError::ConnectionHangup))?;
+        let res_json: Value =
+            response.json().await.map_err(err_str!(ExchangeConnectionError::InvalidMessage))?;
+
+        if res_json[BYBIT_RET_CODE].as_i64() != Some(0) {
+            return Err(ExchangeConnectionError::InvalidMessage(res_json.to_string()));
+        }
+
+        // The list is non-empty iff Bybit lists the pair
+        match &res_json[BYBIT_RESULT][BYBIT_LIST] {
+            Value::Array(instruments) => Ok(!instruments.is_empty()),
+            _ => Err(ExchangeConnectionError::InvalidMessage(
+                "Invalid response from Bybit".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use renegade_types_core::Exchange;
+
+    use super::*;
+
+    /// The pair the test messages are parsed for
+    fn pair_info() -> PairInfo {
+        PairInfo::new(Exchange::Binance, "WETH".to_string(), "USDT".to_string(), None)
+            .with_venue(Venue::Bybit)
+    }
+
+    #[test]
+    fn parses_top_of_book() {
+        let message = Message::text(
+            json!({
+                "topic": "orderbook.1.ETHUSDT",
+                "ts": 1_700_000_000_000u64,
+                "type": "snapshot",
+                "data": { "s": "ETHUSDT", "b": [["1999", "1.5"]], "a": [["2001", "0.4"]] },
+            })
+            .to_string(),
+        );
+
+        let price = BybitConnection::midpoint_from_ws_message(message, &pair_info()).unwrap();
+        assert_eq!(price, Some(ExchangePrice::new_with_timestamp(2000., 1_700_000_000_000)));
+    }
+
+    #[test]
+    fn skips_acks_pongs_and_empty_sides() {
+        let messages = [
+            json!({ "success": true, "ret_msg": "subscribe", "op": "subscribe" }),
+            json!({ "success": true, "ret_msg": "pong", "op": "ping" }),
+            json!({ "topic": "orderbook.1.ETHUSDT", "data": { "b": [], "a": [["2001", "1"]] } }),
+        ];
+
+        for message in messages {
+            let message = Message::text(message.to_string());
+            let price = BybitConnection::midpoint_from_ws_message(message, &pair_info()).unwrap();
+            assert_eq!(price, None);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Defines the depth feed handler for a Coinbase websocket connection
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures_util::{SinkExt, Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+use tungstenite::Message;
+
+use crate::{
+    exchanges::{
+        ExchangeConnectionsConfig,
+        connection::ws_connect,
+        depth::{
+            BoxedDepthReader, DEPTH_SNAPSHOT_LEVELS, DepthConnection, DepthSnapshot,
+            DepthStreamType,
+        },
+        error::ExchangeConnectionError,
+        order_book::OrderBookData,
+    },
+    log_task,
+    logger::{Outcome, Task},
+    utils::PairInfo,
+};
+
+use super::{CoinbaseConnection, get_product_id};
+
+/// The depth feed handler for Exchange::Coinbase.
+///
+/// Replicates the level2 book in the same manner as the price connection,
+/// publishing a depth snapshot of the book after each update.
+pub struct CoinbaseDepthConnection {
+    /// The underlying stream of depth snapshots from the websocket
+    depth_stream: BoxedDepthReader,
+    /// Cancellation token to stop background tasks on drop
+    cancel_token: CancellationToken,
+}
+
+impl CoinbaseDepthConnection {
+    /// Parse a depth snapshot from a websocket message
+    fn depth_from_ws_message(
+        order_book: &OrderBookData,
+        message: Message,
+        pair_info: &PairInfo,
+        last_sequence_num: &mut i64,
+    ) -> Result<Option<DepthSnapshot>, ExchangeConnectionError> {
+        if !CoinbaseConnection::update_book_from_ws_message(
+            order_book,
+            message,
+            pair_info,
+            last_sequence_num,
+        )? {
+            return Ok(None);
+        }
+
+        Ok(order_book.depth_snapshot(DEPTH_SNAPSHOT_LEVELS))
+    }
+}
+
+impl Stream for CoinbaseDepthConnection {
+    type Item = DepthStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.depth_stream.as_mut().poll_next_unpin(cx)
+    }
+}
+
+impl Drop for CoinbaseDepthConnection {
+    fn drop(&mut self) {
+        self.cancel_token.cancel();
+    }
+}
+
+#[async_trait]
+impl DepthConnection for CoinbaseDepthConnection {
+    async fn connect(
+        pair_info: PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError> {
+        let base_token = pair_info.base_token();
+        let quote_token = pair_info.quote_token();
+
+        // Build the base websocket connection
+        let url = CoinbaseConnection::websocket_url();
+        let (mut writer, read) = ws_connect(url).await?;
+
+        let product_id = get_product_id(&base_token, &quote_token)?;
+        let authenticated_subscribe_msg =
+            CoinbaseConnection::construct_subscribe_message(&product_id, config)?;
+        let authenticated_heartbeat_msg = CoinbaseConnection::construct_heartbeat_message(config)?;
+
+        // Setup the topic and heartbeat subscriptions
+        for msg in [authenticated_subscribe_msg, authenticated_heartbeat_msg] {
+            writer
+                .send(Message::Text(msg))
+                .await
+                .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))?;
+        }
+
+        // Map the stream of Coinbase messages to one of depth snapshots
+        let order_book = OrderBookData::new();
+        let mut last_sequence_num = -1;
+        let mapped_stream = read.filter_map(move |message| {
+            let pair_info = pair_info.clone();
+            let order_book = order_book.clone();
+            async move {
+                match message {
+                    Ok(val) => Self::depth_from_ws_message(
+                        &order_book,
+                        val,
+                        &pair_info,
+                        &mut last_sequence_num,
+                    )
+                    .transpose(),
+
+                    Err(e) => {
+                        log_task!(
+                            Task::ExchangeConnection,
+                            Outcome::Failed,
+                            exchange = "coinbase",
+                            error = %e,
+                            "error reading message from depth websocket"
+                        );
+                        Some(Err(ExchangeConnectionError::ConnectionHangup(e.to_string())))
+                    },
+                }
+            }
+        });
+
+        // Periodically re-subscribe to refresh the local book from a snapshot
+        let cancel_token = CancellationToken::new();
+        CoinbaseConnection::start_resubscription_loop(
+            &product_id,
+            config,
+            Box::new(writer),
+            cancel_token.clone(),
+        );
+
+        Ok(Self { depth_stream: Box::new(Box::pin(mapped_stream)), cancel_token })
+    }
+}
@@ -10,7 +10,7 @@ use async_trait::async_trait;
 use futures_util::{SinkExt, Stream, StreamExt};
 use jsonwebtoken::{Algorithm, EncodingKey as JwtEncodingKey, Header as JwtHeader, encode};
 use rand::Rng;
-use renegade_types_core::{Exchange, Price, Token};
+use renegade_types_core::{Exchange, Token};
 use renegade_util::{err_str, get_current_time_seconds};
 use reqwest::{
     Client,
@@ -25,9 +25,13 @@ use url::Url;
 use crate::{
     exchanges::{
         ExchangeConnectionsConfig,
-        coinbase::order_book::CoinbaseOrderBookData,
-        connection::{BoxedPriceReader, BoxedWsWriter, InitializablePriceStream, PriceStreamType},
+        connection::{
+            BoxedPriceReader, BoxedWsWriter, ExchangePrice, InitializablePriceStream,
+            PriceStreamType,
+        },
         error::ExchangeConnectionError,
+        order_book::OrderBookData,
+        replay::maybe_record,
     },
     log_task,
     logger::{Outcome, Task},
@@ -41,7 +45,9 @@ use super::util::{
     exchange_lists_pair_tokens, get_base_exchange_ticker, get_quote_exchange_ticker,
 };
 
-mod order_book;
+mod depth;
+
+pub use depth::CoinbaseDepthConnection;
 
 // -------------
 // | Constants |
@@ -312,16 +318,32 @@ impl CoinbaseConnection {
     }
 
     /// Parse a midpoint price from a websocket message
-    fn midpoint_from_ws_message(
-        order_book: &CoinbaseOrderBookData,
+    pub(crate) fn midpoint_from_ws_message(
+        order_book: &OrderBookData,
         message: Message,
         pair_info: &PairInfo,
         last_sequence_num: &mut i64,
-    ) -> Result<Option<Price>, ExchangeConnectionError> {
+    ) -> Result<Option<ExchangePrice>, ExchangeConnectionError> {
+        if !Self::update_book_from_ws_message(order_book, message, pair_info, last_sequence_num)? {
+            return Ok(None);
+        }
+
+        // Compute the midpoint price
+        Ok(order_book.midpoint().map(ExchangePrice::from))
+    }
+
+    /// Apply the price level updates in a websocket message to the locally
+    /// replicated book, returning whether the message carried any updates
+    fn update_book_from_ws_message(
+        order_book: &OrderBookData,
+        message: Message,
+        pair_info: &PairInfo,
+        last_sequence_num: &mut i64,
+    ) -> Result<bool, ExchangeConnectionError> {
         // The json body of the message
         let json = match parse_json_from_message(message, pair_info)? {
             Some(json) => json,
-            None => return Ok(None),
+            None => return Ok(false),
         };
 
         // Extract the list of events and update the order book
@@ -330,7 +352,7 @@ impl CoinbaseConnection {
             coinbase_event.and_then(|event| event[COINBASE_EVENT_UPDATE].as_array());
 
         if coinbase_event.is_none() || update_events.is_none() {
-            return Ok(None);
+            return Ok(false);
         }
 
         let coinbase_event = coinbase_event.unwrap();
@@ -348,7 +370,7 @@ impl CoinbaseConnection {
         // Make updates to the locally replicated book given the price level updates
         for coinbase_event in update_events {
             let price_level: f64 = parse_json_field(COINBASE_PRICE_LEVEL, coinbase_event)?;
-            let new_quantity: f32 = parse_json_field(COINBASE_NEW_QUANTITY, coinbase_event)?;
+            let new_quantity: f64 = parse_json_field(COINBASE_NEW_QUANTITY, coinbase_event)?;
             let side: String = parse_json_field(COINBASE_SIDE, coinbase_event)?;
 
             match &side[..] {
@@ -356,14 +378,14 @@ impl CoinbaseConnection {
                     if new_quantity == 0. {
                         order_book.remove_bid(price_level);
                     } else {
-                        order_book.add_bid(price_level);
+                        order_book.add_bid(price_level, new_quantity);
                     }
                 },
                 COINBASE_OFFER => {
                     if new_quantity == 0.0 {
                         order_book.remove_offer(price_level);
                     } else {
-                        order_book.add_offer(price_level);
+                        order_book.add_offer(price_level, new_quantity);
                     }
                 },
                 _ => {
@@ -372,8 +394,7 @@ impl CoinbaseConnection {
             }
         }
 
-        // Compute the midpoint price
-        Ok(order_book.midpoint())
+        Ok(true)
     }
 
     /// Check the sequence number of a websocket message against the last-seen
@@ -442,6 +463,7 @@ impl ExchangeConnection for CoinbaseConnection {
         // Build the base websocket connection
         let url = Self::websocket_url();
         let (mut writer, read) = ws_connect(url).await?;
+        let read = maybe_record(read, &pair_info, config);
 
         let product_id = get_product_id(&base_token, &quote_token)?;
         let authenticated_subscribe_msg = Self::construct_subscribe_message(&product_id, config)?;
@@ -460,7 +482,7 @@ impl ExchangeConnection for CoinbaseConnection {
             .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))?;
 
         // Map the stream of Coinbase messages to one of midpoint prices
-        let order_book = CoinbaseOrderBookData::new();
+        let order_book = OrderBookData::new();
         let order_book_clone = order_book.clone();
         let mut last_sequence_num = -1;
         let mapped_stream = read.filter_map(move |message| {
@@ -1,7 +1,6 @@
 //! Defines abstract connection interfaces that can be streamed from
 
 use async_trait::async_trait;
-use atomic_float::AtomicF64;
 use futures::stream::StreamExt;
 use futures_util::{
     Sink, SinkExt, Stream,
@@ -12,7 +11,6 @@ use serde_json::Value;
 use std::{
     pin::Pin,
     str::FromStr,
-    sync::atomic::{AtomicBool, Ordering},
     task::{Context, Poll},
 };
 use tokio::net::TcpStream;
@@ -40,6 +38,10 @@ const PROTOCOL_VIOLATION_MSG: &str = "Protocol violation";
 const PONG_MESSAGE: &str = "pong";
 /// The message passed when a ws proxy resets
 const CLOUDFLARE_RESET_MESSAGE: &str = "CloudFlare WebSocket proxy restarting";
+/// The index of the price in an order book level
+const LEVEL_PRICE_INDEX: usize = 0;
+/// The index of the size in an order book level
+const LEVEL_SIZE_INDEX: usize = 1;
 
 // ----------------
 // | Stream Types |
@@ -51,7 +53,30 @@ pub type BoxedPriceReader = Box<dyn Stream<Item = PriceStreamType> + Unpin + Sen
 pub type BoxedWsWriter = Box<dyn Sink<Message, Error = WsError> + Unpin + Send>;
 
 /// The type that a price stream should return
-pub(crate) type PriceStreamType = Result<Price, ExchangeConnectionError>;
+pub(crate) type PriceStreamType = Result<ExchangePrice, ExchangeConnectionError>;
+
+/// A price reported by an exchange
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExchangePrice {
+    /// The midpoint price
+    pub price: Price,
+    /// The time at which the exchange reported the price, in milliseconds
+    /// since the epoch, if the exchange reports one
+    pub exchange_timestamp: Option<u64>,
+}
+
+impl ExchangePrice {
+    /// Construct a new exchange price with the given exchange timestamp
+    pub fn new_with_timestamp(price: Price, exchange_timestamp: u64) -> Self {
+        Self { price, exchange_timestamp: Some(exchange_timestamp) }
+    }
+}
+
+impl From<Price> for ExchangePrice {
+    fn from(price: Price) -> Self {
+        Self { price, exchange_timestamp: None }
+    }
+}
 
 /// A helper struct that represents a stream of midpoint prices that may
 /// be initialized at construction
@@ -60,9 +85,7 @@ pub struct InitializablePriceStream<T: Stream<Item = PriceStreamType> + Unpin> {
     /// The underlying stream
     stream: T,
     /// A buffered stream value, possibly used for initialization
-    buffered_value: AtomicF64,
-    /// Whether the buffered value has been consumed
-    buffered_value_consumed: AtomicBool,
+    buffered_value: Option<ExchangePrice>,
 }
 
 impl<T: Stream<Item = PriceStreamType> + Unpin> Stream for InitializablePriceStream<T> {
@@ -72,17 +95,8 @@ impl<T: Stream<Item = PriceStreamType> + Unpin> Stream for InitializablePriceStr
         let this = self.get_mut();
 
         // Attempt to consume the buffered value
-        if this
-            .buffered_value_consumed
-            .compare_exchange(
-                false, // current
-                true,  // new
-                Ordering::Release,
-                Ordering::Relaxed,
-            )
-            .is_ok()
-        {
-            return Poll::Ready(Some(Ok(this.buffered_value.load(Ordering::Relaxed))));
+        if let Some(value) = this.buffered_value.take() {
+            return Poll::Ready(Some(Ok(value)));
         }
 
         T::poll_next(Pin::new(&mut this.stream), cx)
@@ -92,20 +106,12 @@ impl<T: Stream<Item = PriceStreamType> + Unpin> Stream for InitializablePriceStr
 impl<T: Stream<Item = PriceStreamType> + Unpin> InitializablePriceStream<T> {
     /// Construct a new stream without an initial value
     pub fn new(stream: T) -> Self {
-        Self {
-            stream,
-            buffered_value: AtomicF64::new(0.0),
-            buffered_value_consumed: AtomicBool::new(true),
-        }
+        Self { stream, buffered_value: None }
     }
 
     /// Construct a new stream with an initial value
     pub fn new_with_initial(stream: T, initial_value: Price) -> Self {
-        Self {
-            stream,
-            buffered_value: AtomicF64::new(initial_value),
-            buffered_value_consumed: AtomicBool::new(false),
-        }
+        Self { stream, buffered_value: Some(initial_value.into()) }
     }
 }
 
@@ -183,6 +189,25 @@ pub(super) fn parse_json_field_array<T: FromStr>(
     }
 }
 
+/// Helper to parse a list of `[price, size, ...]` order book levels from a
+/// JSON response
+pub(super) fn parse_json_levels(
+    levels: &Value,
+) -> Result<Vec<(f64, f64)>, ExchangeConnectionError> {
+    let levels = levels
+        .as_array()
+        .ok_or_else(|| ExchangeConnectionError::InvalidMessage(levels.to_string()))?;
+
+    levels
+        .iter()
+        .map(|level| {
+            let price: f64 = parse_json_field_array(LEVEL_PRICE_INDEX, level)?;
+            let size: f64 = parse_json_field_array(LEVEL_SIZE_INDEX, level)?;
+            Ok((price, size))
+        })
+        .collect()
+}
+
 /// Parse an json structure from a websocket message
 pub fn parse_json_from_message(
     message: Message,
@@ -0,0 +1,219 @@
+//! Order book depth snapshots and the connection abstraction for depth feeds
+
+use async_trait::async_trait;
+use futures_util::Stream;
+use itertools::Itertools;
+use renegade_types_core::Exchange;
+use renegade_util::get_current_time_millis;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    exchanges::{
+        ExchangeConnectionsConfig, binance::BinanceDepthConnection,
+        coinbase::CoinbaseDepthConnection, error::ExchangeConnectionError,
+        kraken::KrakenDepthConnection, okx::OkxDepthConnection, util::safe_midpoint,
+    },
+    utils::PairInfo,
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The number of levels published on each side of a depth snapshot
+pub const DEPTH_SNAPSHOT_LEVELS: usize = 10;
+
+// ---------
+// | Types |
+// ---------
+
+/// A single price level in a depth snapshot
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DepthLevel {
+    /// The price of the level
+    pub price: f64,
+    /// The size resting at the level, in units of the base token
+    pub size: f64,
+    /// The total size resting at this level and all better levels
+    pub cumulative_size: f64,
+}
+
+/// A snapshot of the top levels of an exchange's order book
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DepthSnapshot {
+    /// The best bid price
+    pub best_bid: f64,
+    /// The best ask price
+    pub best_ask: f64,
+    /// The midpoint of the best bid and ask
+    pub midpoint: f64,
+    /// The spread between the best ask and best bid
+    pub spread: f64,
+    /// The bid levels, best first
+    pub bids: Vec<DepthLevel>,
+    /// The ask levels, best first
+    pub asks: Vec<DepthLevel>,
+    /// The local time at which the snapshot was taken, in milliseconds since
+    /// the epoch
+    pub timestamp: u64,
+}
+
+impl DepthSnapshot {
+    /// Build a depth snapshot from `(price, size)` levels on each side of the
+    /// book, keeping at most `levels` levels per side.
+    ///
+    /// Levels with a non-finite or non-positive price or size are dropped.
+    /// Returns `None` if either side is empty or the book is crossed, so that
+    /// the caller emits no snapshot rather than a corrupted one.
+    pub fn from_levels(bids: &[(f64, f64)], asks: &[(f64, f64)], levels: usize) -> Option<Self> {
+        let bids = bids
+            .iter()
+            .copied()
+            .filter(|level| Self::is_valid_level(*level))
+            .sorted_by(|a, b| b.0.total_cmp(&a.0))
+            .take(levels)
+            .collect_vec();
+        let asks = asks
+            .iter()
+            .copied()
+            .filter(|level| Self::is_valid_level(*level))
+            .sorted_by(|a, b| a.0.total_cmp(&b.0))
+            .take(levels)
+            .collect_vec();
+
+        let best_bid = bids.first()?.0;
+        let best_ask = asks.first()?.0;
+        if best_bid >= best_ask {
+            return None;
+        }
+        let midpoint = safe_midpoint(best_bid, best_ask)?;
+
+        Some(Self {
+            best_bid,
+            best_ask,
+            midpoint,
+            spread: best_ask - best_bid,
+            bids: Self::accumulate_levels(&bids),
+            asks: Self::accumulate_levels(&asks),
+            timestamp: get_current_time_millis(),
+        })
+    }
+
+    /// The total size resting in the snapshot's levels on both sides of the
+    /// book, in units of the base token
+    pub fn total_size(&self) -> f64 {
+        let side_size =
+            |levels: &[DepthLevel]| levels.last().map_or(0., |level| level.cumulative_size);
+        side_size(&self.bids) + side_size(&self.asks)
+    }
+
+    /// Whether the given `(price, size)` level is valid
+    fn is_valid_level((price, size): (f64, f64)) -> bool {
+        price.is_finite() && price > 0. && size.is_finite() && size > 0.
+    }
+
+    /// Convert sorted `(price, size)` levels into depth levels with
+    /// cumulative sizes
+    fn accumulate_levels(levels: &[(f64, f64)]) -> Vec<DepthLevel> {
+        let mut cumulative_size = 0.;
+        levels
+            .iter()
+            .map(|(price, size)| {
+                cumulative_size += size;
+                DepthLevel { price: *price, size: *size, cumulative_size }
+            })
+            .collect_vec()
+    }
+}
+
+// ----------------
+// | Stream Types |
+// ----------------
+
+/// The type that a depth stream should return
+pub(crate) type DepthStreamType = Result<DepthSnapshot, ExchangeConnectionError>;
+
+/// A type alias for a boxed depth reader
+pub type BoxedDepthReader = Box<dyn Stream<Item = DepthStreamType> + Unpin + Send>;
+
+// --------------------------
+// | Connection Abstraction |
+// --------------------------
+
+/// A trait representing a connection to an exchange's order book depth feed
+#[async_trait]
+pub trait DepthConnection: Stream<Item = DepthStreamType> + Unpin + Send {
+    /// Create a new depth connection to the exchange on a given asset pair
+    async fn connect(
+        pair_info: PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError>
+    where
+        Self: Sized;
+
+    /// Send a keepalive signal on the connection if necessary
+    async fn send_keepalive(&mut self) -> Result<(), ExchangeConnectionError> {
+        Ok(())
+    }
+}
+
+/// Construct a new depth connection for the given exchange
+pub async fn connect_depth(
+    pair_info: PairInfo,
+    config: &ExchangeConnectionsConfig,
+) -> Result<Box<dyn DepthConnection>, ExchangeConnectionError> {
+    let exchange = pair_info.exchange;
+    Ok(match exchange {
+        Exchange::Binance => Box::new(BinanceDepthConnection::connect(pair_info, config).await?),
+        Exchange::Coinbase => Box::new(CoinbaseDepthConnection::connect(pair_info, config).await?),
+        Exchange::Kraken => Box::new(KrakenDepthConnection::connect(pair_info, config).await?),
+        Exchange::Okx => Box::new(OkxDepthConnection::connect(pair_info, config).await?),
+        _ => return Err(ExchangeConnectionError::unsupported_exchange(exchange)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_sorts_and_accumulates_levels() {
+        let bids = [(99., 1.), (100., 2.), (98., 3.)];
+        let asks = [(102., 4.), (101., 1.)];
+        let snapshot = DepthSnapshot::from_levels(&bids, &asks, 2).unwrap();
+
+        assert_eq!(snapshot.best_bid, 100.);
+        assert_eq!(snapshot.best_ask, 101.);
+        assert_eq!(snapshot.midpoint, 100.5);
+        assert_eq!(snapshot.spread, 1.);
+
+        let bid_levels = snapshot.bids.iter().map(|l| (l.price, l.cumulative_size)).collect_vec();
+        assert_eq!(bid_levels, vec![(100., 2.), (99., 3.)]);
+        let ask_levels = snapshot.asks.iter().map(|l| (l.price, l.cumulative_size)).collect_vec();
+        assert_eq!(ask_levels, vec![(101., 1.), (102., 5.)]);
+        assert_eq!(snapshot.total_size(), 8.);
+    }
+
+    #[test]
+    fn snapshot_drops_invalid_levels() {
+        let bids = [(0., 1.), (100., 0.), (99., 1.), (f64::NAN, 1.)];
+        let asks = [(101., f64::INFINITY), (102., 1.)];
+        let snapshot = DepthSnapshot::from_levels(&bids, &asks, 10).unwrap();
+
+        assert_eq!(snapshot.best_bid, 99.);
+        assert_eq!(snapshot.best_ask, 102.);
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.asks.len(), 1);
+    }
+
+    #[test]
+    fn one_sided_book_returns_none() {
+        assert_eq!(DepthSnapshot::from_levels(&[(100., 1.)], &[], 10), None);
+        assert_eq!(DepthSnapshot::from_levels(&[], &[(100., 1.)], 10), None);
+    }
+
+    #[test]
+    fn crossed_book_returns_none() {
+        assert_eq!(DepthSnapshot::from_levels(&[(101., 1.)], &[(100., 1.)], 10), None);
+    }
+}
@@ -20,6 +20,9 @@ pub enum ExchangeConnectionError {
     /// A custom error occurred
     #[error("custom error: {0}")]
     Custom(String),
+    /// Error reading or writing a websocket frame recording
+    #[error("error reading or writing a frame recording: {0}")]
+    FrameRecording(String),
     /// An initial websocket subscription to a remote server failed.
     #[error("initial websocket subscription failed: {0}")]
     HandshakeFailure(String),
@@ -0,0 +1,298 @@
+//! Defines the logic for connecting to Hyperliquid perpetuals, priced at the
+//! mark price
+//!
+//! Hyperliquid perpetuals are margined and quoted in USDC, so only USDC pairs
+//! are supported.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures_util::{SinkExt, Stream, StreamExt};
+use renegade_types_core::{Token, USDC_TICKER};
+use renegade_util::err_str;
+use serde_json::{Value, json};
+use tungstenite::Message;
+use url::Url;
+
+use crate::{
+    exchanges::{
+        ExchangeConnectionsConfig,
+        connection::{
+            BoxedPriceReader, BoxedWsWriter, ExchangePrice, InitializablePriceStream,
+            PriceStreamType,
+        },
+        error::ExchangeConnectionError,
+        replay::maybe_record,
+        venue::Venue,
+    },
+    log_task,
+    logger::{Outcome, Task},
+    utils::PairInfo,
+};
+
+use super::connection::{
+    ExchangeConnection, parse_json_field, parse_json_from_message, ws_connect,
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The Hyperliquid websocket endpoint
+const HYPERLIQUID_WS_URL: &str = "wss://api.hyperliquid.xyz/ws";
+/// The Hyperliquid info endpoint
+const HYPERLIQUID_INFO_URL: &str = "https://api.hyperliquid.xyz/info";
+
+/// The text of a Hyperliquid ping message
+const HYPERLIQUID_PING_MESSAGE: &str = r#"{"method":"ping"}"#;
+/// The subscription type carrying an asset's mark price
+const HYPERLIQUID_ASSET_CTX: &str = "activeAssetCtx";
+
+/// The field name for the channel on a Hyperliquid websocket message
+const HYPERLIQUID_CHANNEL: &str = "channel";
+/// The field name for the data on a Hyperliquid websocket message
+const HYPERLIQUID_DATA: &str = "data";
+/// The field name for the asset context on an asset context message
+const HYPERLIQUID_CTX: &str = "ctx";
+/// The field name for the mark price on an asset context
+const HYPERLIQUID_MARK_PRICE: &str = "markPx";
+
+/// The field name for the listed perpetuals on a meta response
+const HYPERLIQUID_UNIVERSE: &str = "universe";
+/// The field name for a perpetual's coin on a meta response
+const HYPERLIQUID_NAME: &str = "name";
+/// The field name marking a delisted perpetual on a meta response
+const HYPERLIQUID_DELISTED: &str = "isDelisted";
+
+// --------------
+// | Connection |
+// --------------
+
+/// The connection handle for Hyperliquid perpetual mark prices
+pub struct HyperliquidConnection {
+    /// The underlying price stream
+    price_stream: BoxedPriceReader,
+    /// The underlying write stream of the websocket
+    write_stream: BoxedWsWriter,
+}
+
+impl HyperliquidConnection {
+    /// Get the Hyperliquid coin of the given pair, e.g. `ETH`
+    fn coin(base_token: &Token, quote_token: &Token) -> Result<String, ExchangeConnectionError> {
+        let is_usdc = quote_token.get_ticker().as_deref() == Some(USDC_TICKER);
+        match Venue::Hyperliquid.ticker(base_token) {
+            Some(coin) if is_usdc => Ok(coin),
+            _ => Err(ExchangeConnectionError::UnsupportedPair(
+                base_token.clone(),
+                quote_token.clone(),
+                Venue::Hyperliquid.ticker_exchange(),
+            )),
+        }
+    }
+
+    /// Construct the message subscribing to the pair's asset context
+    fn subscribe_message(pair_info: &PairInfo) -> Result<String, ExchangeConnectionError> {
+        let coin = Self::coin(&pair_info.base_token(), &pair_info.quote_token())?;
+        Ok(json!({
+            "method": "subscribe",
+            "subscription": { "type": HYPERLIQUID_ASSET_CTX, "coin": coin },
+        })
+        .to_string())
+    }
+
+    /// Parse a mark price from a Hyperliquid websocket message
+    pub(crate) fn mark_price_from_ws_message(
+        message: Message,
+        pair_info: &PairInfo,
+    ) -> Result<Option<ExchangePrice>, ExchangeConnectionError> {
+        let message_json = match parse_json_from_message(message, pair_info)? {
+            Some(json) => json,
+            None => return Ok(None),
+        };
+
+        // Ignore subscription responses and pongs
+        if message_json[HYPERLIQUID_CHANNEL].as_str() != Some(HYPERLIQUID_ASSET_CTX) {
+            return Ok(None);
+        }
+
+        let ctx = &message_json[HYPERLIQUID_DATA][HYPERLIQUID_CTX];
+        let mark_price: f64 = parse_json_field(HYPERLIQUID_MARK_PRICE, ctx)?;
+
+        // Drop non-finite or non-positive mark prices rather than emit a bad
+        // price; the stream resumes on the next valid update
+        if !mark_price.is_finite() || mark_price <= 0. {
+            return Ok(None);
+        }
+
+        Ok(Some(ExchangePrice::from(mark_price)))
+    }
+}
+
+impl Stream for HyperliquidConnection {
+    type Item = PriceStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.price_stream.as_mut().poll_next_unpin(cx)
+    }
+}
+
+#[async_trait]
+impl ExchangeConnection for HyperliquidConnection {
+    async fn connect(
+        pair_info: PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError>
+    where
+        Self: Sized,
+    {
+        // Connect to the websocket
+        let url = Url::parse(HYPERLIQUID_WS_URL).expect("Hyperliquid websocket url should parse");
+        let (mut write, read) = ws_connect(url).await?;
+        let read = maybe_record(read, &pair_info, config);
+
+        // Subscribe to the pair's asset context
+        let subscribe_str = Self::subscribe_message(&pair_info)?;
+        write
+            .send(Message::Text(subscribe_str))
+            .await
+            .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))?;
+
+        // Map the stream to process mark prices
+        let mapped_stream = read.filter_map(move |message| {
+            let pair_info = pair_info.clone();
+            async move {
+                match message.map(|message| Self::mark_price_from_ws_message(message, &pair_info)) {
+                    // The outer `Result` comes from reading the message from the websocket
+                    // Processing the message returns a `Result<Option<..>>` which we
+                    // flip to match the stream type
+                    Ok(mapped_res) => mapped_res.transpose(),
+
+                    // Error reading from the websocket
+                    Err(e) => {
+                        log_task!(
+                            Task::ExchangeConnection,
+                            Outcome::Failed,
+                            exchange = "hyperliquid",
+                            error = %e,
+                            "error reading message from websocket"
+                        );
+                        Some(Err(ExchangeConnectionError::ConnectionHangup(e.to_string())))
+                    },
+                }
+            }
+        });
+
+        let price_stream = InitializablePriceStream::new(Box::pin(mapped_stream));
+        Ok(Self { price_stream: Box::new(price_stream), write_stream: Box::new(write) })
+    }
+
+    async fn send_keepalive(&mut self) -> Result<(), ExchangeConnectionError> {
+        // Hyperliquid expects an application level ping rather than a ping frame
+        self.write_stream
+            .send(Message::Text(String::from(HYPERLIQUID_PING_MESSAGE)))
+            .await
+            .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))
+    }
+
+    async fn supports_pair(
+        base_token: &Token,
+        quote_token: &Token,
+    ) -> Result<bool, ExchangeConnectionError> {
+        let coin = match Self::coin(base_token, quote_token) {
+            Ok(coin) => coin,
+            Err(_) => return Ok(false),
+        };
+
+        // Query the listed perpetuals
+        let response = reqwest::Client::new()
+            .post(HYPERLIQUID_INFO_URL)
+            .json(&json!({ "type": "meta" }))
+            .send()
+            .await
+            .map_err(err_str!(ExchangeConnectionError::ConnectionHangup))?;
+        let res_json: Value =
+            response.json().await.map_err(err_str!(ExchangeConnectionError::InvalidMessage))?;
+
+        lists_perpetual(&res_json, &coin)
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Whether the given meta response lists a live perpetual on the coin
+fn lists_perpetual(meta: &Value, coin: &str) -> Result<bool, ExchangeConnectionError> {
+    let universe = meta[HYPERLIQUID_UNIVERSE].as_array().ok_or_else(|| {
+        ExchangeConnectionError::InvalidMessage("Invalid response from Hyperliquid".to_string())
+    })?;
+
+    Ok(universe.iter().any(|perp| {
+        perp[HYPERLIQUID_NAME].as_str() == Some(coin)
+            && !perp[HYPERLIQUID_DELISTED].as_bool().unwrap_or(false)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use renegade_types_core::Exchange;
+
+    use super::*;
+
+    /// The pair the test messages are parsed for
+    fn pair_info() -> PairInfo {
+        PairInfo::new(Exchange::Coinbase, "WETH".to_string(), "USDC".to_string(), None)
+            .with_venue(Venue::Hyperliquid)
+    }
+
+    #[test]
+    fn parses_mark_price() {
+        let message = Message::text(
+            json!({
+                "channel": "activeAssetCtx",
+                "data": {
+                    "coin": "ETH",
+                    "ctx": { "markPx": "2000.5", "midPx": "2000.4", "oraclePx": "2001.0" },
+                },
+            })
+            .to_string(),
+        );
+
+        let price = HyperliquidConnection::mark_price_from_ws_message(message, &pair_info());
+        assert_eq!(price.unwrap(), Some(ExchangePrice::from(2000.5)));
+    }
+
+    #[test]
+    fn skips_subscription_responses_and_pongs() {
+        let messages = [
+            json!({ "channel": "subscriptionResponse", "data": { "method": "subscribe" } }),
+            json!({ "channel": "pong" }),
+        ];
+
+        for message in messages {
+            let message = Message::text(message.to_string());
+            let price = HyperliquidConnection::mark_price_from_ws_message(message, &pair_info());
+            assert_eq!(price.unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn only_live_perpetuals_are_listed() {
+        let meta = json!({
+            "universe": [
+                { "name": "BTC", "szDecimals": 5 },
+                { "name": "ETH", "szDecimals": 4 },
+                { "name": "LUNA", "szDecimals": 1, "isDelisted": true },
+            ],
+        });
+
+        assert!(lists_perpetual(&meta, "ETH").unwrap());
+        assert!(!lists_perpetual(&meta, "LUNA").unwrap());
+        assert!(!lists_perpetual(&meta, "DOGE").unwrap());
+        assert!(lists_perpetual(&json!({}), "ETH").is_err());
+    }
+}
@@ -9,15 +9,24 @@ use std::{
 use async_trait::async_trait;
 use futures_util::{SinkExt, Stream, StreamExt};
 use lazy_static::lazy_static;
-use renegade_types_core::{Exchange, Price, Token};
+use renegade_types_core::{Exchange, Token};
 use renegade_util::err_str;
 use serde_json::{Value, json};
 use tungstenite::Message;
 use url::Url;
 
 use crate::{
-    exchanges::connection::{
-        BoxedPriceReader, BoxedWsWriter, InitializablePriceStream, PriceStreamType,
+    exchanges::{
+        connection::{
+            BoxedPriceReader, BoxedWsWriter, ExchangePrice, InitializablePriceStream,
+            PriceStreamType,
+        },
+        depth::{
+            BoxedDepthReader, DEPTH_SNAPSHOT_LEVELS, DepthConnection, DepthSnapshot,
+            DepthStreamType,
+        },
+        order_book::OrderBookData,
+        replay::maybe_record,
     },
     log_task,
     logger::{Outcome, Task},
@@ -27,7 +36,8 @@ use crate::{
 use super::{
     ExchangeConnectionsConfig,
     connection::{
-        ExchangeConnection, parse_json_field_array, parse_json_from_message, ws_connect, ws_ping,
+        ExchangeConnection, parse_json_field_array, parse_json_from_message, parse_json_levels,
+        ws_connect, ws_ping,
     },
     error::ExchangeConnectionError,
     util::{
@@ -55,9 +65,21 @@ const KRAKEN_BID_PRICE_INDEX: usize = 0;
 const KRAKEN_ASK_PRICE_INDEX: usize = 1;
 /// The timestamp of the price report from kraken
 const KRAKEN_PRICE_REPORT_TIMESTAMP_INDEX: usize = 2;
+/// The number of milliseconds in a second, used to convert Kraken's
+/// fractional-second timestamps
+const MILLIS_PER_SECOND: f64 = 1000.;
 /// The name of the error field in a Kraken API response
 const KRAKEN_ERROR: &str = "error";
 
+/// The depth of the book subscription used for depth snapshots
+const KRAKEN_BOOK_DEPTH: usize = 10;
+/// The field names of the ask levels in a Kraken book snapshot and update
+const KRAKEN_BOOK_ASK_FIELDS: [&str; 2] = ["as", "a"];
+/// The field names of the bid levels in a Kraken book snapshot and update
+const KRAKEN_BOOK_BID_FIELDS: [&str; 2] = ["bs", "b"];
+/// The field names present only on a Kraken book snapshot
+const KRAKEN_BOOK_SNAPSHOT_FIELDS: [&str; 2] = ["as", "bs"];
+
 lazy_static! {
     static ref KRAKEN_MSG_IGNORE_LIST: HashSet<String> = {
         let mut set = HashSet::new();
@@ -87,11 +109,32 @@ impl KrakenConnection {
         String::from(KRAKEN_WS_BASE_URL).parse().expect("Failed to parse Kraken websocket URL")
     }
 
+    /// Construct the subscription message for the given subscription on the
+    /// asset pair
+    fn subscribe_message(
+        pair_info: &PairInfo,
+        subscription: Value,
+    ) -> Result<String, ExchangeConnectionError> {
+        let base_token = pair_info.base_token();
+        let quote_token = pair_info.quote_token();
+        let base_ticker =
+            get_base_exchange_ticker(base_token.clone(), quote_token.clone(), Exchange::Kraken)?;
+        let quote_ticker = get_quote_exchange_ticker(base_token, quote_token, Exchange::Kraken)?;
+
+        let pair = format!("{}/{}", base_ticker, quote_ticker);
+        Ok(json!({
+            "event": "subscribe",
+            "pair": [ pair ],
+            "subscription": subscription,
+        })
+        .to_string())
+    }
+
     /// Parse a price report from a Kraken websocket message
-    fn midpoint_from_ws_message(
+    pub(crate) fn midpoint_from_ws_message(
         message: Message,
         pair_info: &PairInfo,
-    ) -> Result<Option<Price>, ExchangeConnectionError> {
+    ) -> Result<Option<ExchangePrice>, ExchangeConnectionError> {
         // Parse the message to json
         let json_blob = parse_json_from_message(message, pair_info)?;
         if json_blob.is_none() {
@@ -109,13 +152,15 @@ impl KrakenConnection {
         let price_data = &message_json[KRAKEN_PRICE_DATA_INDEX];
         let best_bid: f64 = parse_json_field_array(KRAKEN_BID_PRICE_INDEX, price_data)?;
         let best_offer: f64 = parse_json_field_array(KRAKEN_ASK_PRICE_INDEX, price_data)?;
-        let _reported_timestamp_seconds: f32 =
+        let reported_timestamp_seconds: f64 =
             parse_json_field_array(KRAKEN_PRICE_REPORT_TIMESTAMP_INDEX, price_data)?;
+        let timestamp_ms = (reported_timestamp_seconds * MILLIS_PER_SECOND) as u64;
 
         // A non-finite or non-positive bid/offer would corrupt the midpoint
         // (see incident 2026-05-08 cbBTC). Drop the message rather than emit
         // a bad price; the stream resumes on the next valid update.
-        Ok(safe_midpoint(best_bid, best_offer))
+        Ok(safe_midpoint(best_bid, best_offer)
+            .map(|price| ExchangePrice::new_with_timestamp(price, timestamp_ms)))
     }
 }
 
@@ -132,33 +177,18 @@ impl Stream for KrakenConnection {
 impl ExchangeConnection for KrakenConnection {
     async fn connect(
         pair_info: PairInfo,
-        _config: &ExchangeConnectionsConfig,
+        config: &ExchangeConnectionsConfig,
     ) -> Result<Self, ExchangeConnectionError>
     where
         Self: Sized,
     {
-        let base_token = pair_info.base_token();
-        let quote_token = pair_info.quote_token();
-
         // Connect to the websocket
         let url = Self::websocket_url();
         let (mut write, read) = ws_connect(url).await?;
+        let read = maybe_record(read, &pair_info, config);
 
         // Subscribe to the asset pair spread topic
-        let base_ticker =
-            get_base_exchange_ticker(base_token.clone(), quote_token.clone(), Exchange::Kraken)?;
-        let quote_ticker =
-            get_quote_exchange_ticker(base_token.clone(), quote_token.clone(), Exchange::Kraken)?;
-
-        let pair = format!("{}/{}", base_ticker, quote_ticker);
-        let subscribe_str = json!({
-            "event": "subscribe",
-            "pair": [ pair ],
-            "subscription": {
-                "name": "spread",
-            },
-        })
-        .to_string();
+        let subscribe_str = Self::subscribe_message(&pair_info, json!({ "name": "spread" }))?;
 
         write
             .send(Message::Text(subscribe_str))
@@ -237,3 +267,131 @@ impl ExchangeConnection for KrakenConnection {
         }
     }
 }
+
+// --------------------
+// | Depth Connection |
+// --------------------
+
+/// The depth feed handler for Exchange::Kraken
+pub struct KrakenDepthConnection {
+    /// The underlying depth stream
+    depth_stream: BoxedDepthReader,
+    /// The underlying write stream of the websocket
+    write_stream: BoxedWsWriter,
+}
+
+impl KrakenDepthConnection {
+    /// Apply a Kraken book message to the local book, returning a depth
+    /// snapshot of the updated book
+    ///
+    /// Book messages take the form `[channel_id, {..levels}, .., channel_name,
+    /// pair]`, where an update may carry its ask and bid levels in separate
+    /// objects.
+    fn depth_from_ws_message(
+        order_book: &OrderBookData,
+        message: Message,
+        pair_info: &PairInfo,
+    ) -> Result<Option<DepthSnapshot>, ExchangeConnectionError> {
+        let message_json = match parse_json_from_message(message, pair_info)? {
+            Some(json) => json,
+            None => return Ok(None),
+        };
+
+        // Status messages are objects rather than arrays
+        let level_objects = match message_json.as_array() {
+            Some(entries) => entries.iter().filter(|entry| entry.is_object()).collect::<Vec<_>>(),
+            None => return Ok(None),
+        };
+
+        // A snapshot replaces the local book entirely
+        let is_snapshot = level_objects
+            .iter()
+            .any(|obj| KRAKEN_BOOK_SNAPSHOT_FIELDS.iter().any(|field| !obj[*field].is_null()));
+        if is_snapshot {
+            order_book.clear();
+        }
+
+        for obj in level_objects {
+            for field in KRAKEN_BOOK_ASK_FIELDS.iter().filter(|field| !obj[**field].is_null()) {
+                for (price, size) in parse_json_levels(&obj[*field])? {
+                    if size == 0. {
+                        order_book.remove_offer(price)
+                    } else {
+                        order_book.add_offer(price, size)
+                    }
+                }
+            }
+            for field in KRAKEN_BOOK_BID_FIELDS.iter().filter(|field| !obj[**field].is_null()) {
+                for (price, size) in parse_json_levels(&obj[*field])? {
+                    if size == 0. {
+                        order_book.remove_bid(price)
+                    } else {
+                        order_book.add_bid(price, size)
+                    }
+                }
+            }
+        }
+
+        // Kraken does not send removals for levels pushed out of the subscribed
+        // depth, so we prune them locally
+        order_book.truncate(KRAKEN_BOOK_DEPTH);
+        Ok(order_book.depth_snapshot(DEPTH_SNAPSHOT_LEVELS))
+    }
+}
+
+impl Stream for KrakenDepthConnection {
+    type Item = DepthStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        Pin::new(&mut this.depth_stream).poll_next(cx)
+    }
+}
+
+#[async_trait]
+impl DepthConnection for KrakenDepthConnection {
+    async fn connect(
+        pair_info: PairInfo,
+        _config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError> {
+        let url = KrakenConnection::websocket_url();
+        let (mut write, read) = ws_connect(url).await?;
+
+        // Subscribe to the asset pair book topic
+        let subscription = json!({ "name": "book", "depth": KRAKEN_BOOK_DEPTH });
+        let subscribe_str = KrakenConnection::subscribe_message(&pair_info, subscription)?;
+        write
+            .send(Message::Text(subscribe_str))
+            .await
+            .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))?;
+
+        let order_book = OrderBookData::new();
+        let mapped_stream = read.filter_map(move |message| {
+            let pair_info = pair_info.clone();
+            let order_book = order_book.clone();
+            async move {
+                match message
+                    .map(|message| Self::depth_from_ws_message(&order_book, message, &pair_info))
+                {
+                    Ok(val) => val.transpose(),
+                    Err(e) => {
+                        log_task!(
+                            Task::ExchangeConnection,
+                            Outcome::Failed,
+                            exchange = "kraken",
+                            error = %e,
+                            "error reading message from depth websocket"
+                        );
+                        Some(Err(ExchangeConnectionError::ConnectionHangup(e.to_string())))
+                    },
+                }
+            }
+        });
+
+        Ok(Self { depth_stream: Box::new(Box::pin(mapped_stream)), write_stream: Box::new(write) })
+    }
+
+    async fn send_keepalive(&mut self) -> Result<(), ExchangeConnectionError> {
+        ws_ping(&mut self.write_stream).await
+    }
+}
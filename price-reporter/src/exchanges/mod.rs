@@ -1,22 +1,41 @@
 //! Exchange connection shims
 
+use alloy::primitives::Address;
 use renegade_types_core::Exchange;
 
 use crate::{
     exchanges::{
-        binance::BinanceConnection, coinbase::CoinbaseConnection, connection::ExchangeConnection,
-        error::ExchangeConnectionError, kraken::KrakenConnection, okx::OkxConnection,
+        binance::BinanceConnection,
+        bybit::BybitConnection,
+        coinbase::CoinbaseConnection,
+        connection::ExchangeConnection,
+        error::ExchangeConnectionError,
+        hyperliquid::HyperliquidConnection,
+        kraken::KrakenConnection,
+        okx::OkxConnection,
+        replay::{FrameRecorder, ReplayConfig, ReplayConnection},
+        uniswap_v3::UniswapV3Connection,
+        venue::Venue,
     },
+    failover::FallbackExchanges,
     utils::PairInfo,
 };
 
 pub(crate) mod binance;
+pub(crate) mod bybit;
 pub(crate) mod coinbase;
 pub(crate) mod connection;
+pub(crate) mod depth;
 pub(crate) mod error;
+pub(crate) mod hyperliquid;
 pub(crate) mod kraken;
 pub(crate) mod okx;
+pub(crate) mod order_book;
+pub(crate) mod quote_conversion;
+pub(crate) mod replay;
+pub(crate) mod uniswap_v3;
 pub(crate) mod util;
+pub(crate) mod venue;
 
 /// The configuration options that may be used by exchange connections
 #[derive(Clone, Debug, Default)]
@@ -27,6 +46,17 @@ pub struct ExchangeConnectionsConfig {
     pub coinbase_key_secret: Option<String>,
     /// The ethereum RPC node websocket addresses for on-chain data
     pub eth_websocket_addr: Option<String>,
+    /// The address of the Uniswap V3 factory used to look up pools, if it
+    /// differs from the canonical deployment
+    pub uniswap_v3_factory: Option<Address>,
+    /// The ordered fallback exchanges for each base ticker, used when the
+    /// ticker's canonical exchange stream fails
+    pub fallback_exchanges: FallbackExchanges,
+    /// The recorder of raw websocket frames, if recording is enabled
+    pub frame_recorder: Option<FrameRecorder>,
+    /// The replay configuration, if any price streams are replayed from
+    /// recorded frames rather than connected to live exchanges
+    pub frame_replay: Option<ReplayConfig>,
 }
 
 impl ExchangeConnectionsConfig {
@@ -39,19 +69,39 @@ impl ExchangeConnectionsConfig {
     pub fn uniswap_v3_configured(&self) -> bool {
         self.eth_websocket_addr.is_some()
     }
+
+    /// Whether or not the given pair is replayed from recorded frames
+    pub fn replays(&self, pair_info: &PairInfo) -> bool {
+        self.frame_replay.as_ref().is_some_and(|replay| replay.replays(pair_info))
+    }
 }
 
-/// Construct a new websocket connection for the given exchange
+/// Construct a new websocket connection for the given exchange or venue
 pub async fn connect_exchange(
     pair_info: PairInfo,
     config: &ExchangeConnectionsConfig,
 ) -> Result<Box<dyn ExchangeConnection>, ExchangeConnectionError> {
+    if config.replays(&pair_info) {
+        return Ok(Box::new(ReplayConnection::connect(pair_info, config).await?));
+    }
+
+    match pair_info.venue {
+        Some(Venue::Bybit) => {
+            return Ok(Box::new(BybitConnection::connect(pair_info, config).await?));
+        },
+        Some(Venue::Hyperliquid) => {
+            return Ok(Box::new(HyperliquidConnection::connect(pair_info, config).await?));
+        },
+        None => {},
+    }
+
     let exchange = pair_info.exchange;
     Ok(match exchange {
         Exchange::Binance => Box::new(BinanceConnection::connect(pair_info, config).await?),
         Exchange::Coinbase => Box::new(CoinbaseConnection::connect(pair_info, config).await?),
         Exchange::Kraken => Box::new(KrakenConnection::connect(pair_info, config).await?),
         Exchange::Okx => Box::new(OkxConnection::connect(pair_info, config).await?),
+        Exchange::UniswapV3 => Box::new(UniswapV3Connection::connect(pair_info, config).await?),
         _ => return Err(ExchangeConnectionError::unsupported_exchange(exchange)),
     })
 }
@@ -7,15 +7,24 @@ use std::{
 
 use async_trait::async_trait;
 use futures_util::{SinkExt, Stream, StreamExt};
-use renegade_types_core::{Exchange, Price, Token};
+use renegade_types_core::{Exchange, Token};
 use renegade_util::err_str;
 use serde_json::{Value, json};
 use tungstenite::Message;
 use url::Url;
 
 use crate::{
-    exchanges::connection::{
-        BoxedPriceReader, BoxedWsWriter, InitializablePriceStream, PriceStreamType,
+    exchanges::{
+        connection::{
+            BoxedPriceReader, BoxedWsWriter, ExchangePrice, InitializablePriceStream,
+            PriceStreamType,
+        },
+        depth::{
+            BoxedDepthReader, DEPTH_SNAPSHOT_LEVELS, DepthConnection, DepthSnapshot,
+            DepthStreamType,
+        },
+        order_book::OrderBookData,
+        replay::maybe_record,
     },
     log_task,
     logger::{Outcome, Task},
@@ -24,7 +33,10 @@ use crate::{
 
 use super::{
     ExchangeConnectionsConfig,
-    connection::{ExchangeConnection, parse_json_field_array, parse_json_from_message, ws_connect},
+    connection::{
+        ExchangeConnection, parse_json_field, parse_json_field_array, parse_json_from_message,
+        parse_json_levels, ws_connect,
+    },
     error::ExchangeConnectionError,
     util::{
         exchange_lists_pair_tokens, get_base_exchange_ticker, get_quote_exchange_ticker,
@@ -56,11 +68,23 @@ const OKX_DATA: &str = "data";
 const OKX_BIDS: &str = "bids";
 /// The field name for asks on an Okx bbo websocket message
 const OKX_ASKS: &str = "asks";
+/// The field name for the exchange timestamp on an Okx bbo websocket message,
+/// in milliseconds since the epoch
+///
+/// Optional, a message without a timestamp still yields a price
+const OKX_TIMESTAMP: &str = "ts";
 /// The data index to pull the first bid or ask
 const FIRST_ENTRY: usize = 0;
 /// The data index to pull the price from a bid or ask
 const OKX_PRICE: usize = 0;
 
+/// The order book channel to subscribe to for depth snapshots
+const OKX_DEPTH_CHANNEL: &str = "books";
+/// The field name for the action on an Okx order book message
+const OKX_ACTION: &str = "action";
+/// The action indicating a full order book snapshot
+const OKX_SNAPSHOT_ACTION: &str = "snapshot";
+
 // -----------------------------
 // | Connection Implementation |
 // -----------------------------
@@ -79,11 +103,34 @@ impl OkxConnection {
         String::from(OKX_WS_BASE_URL).parse().expect("Failed to parse Okx websocket URL")
     }
 
+    /// Construct the subscription message for the given channel on the asset
+    /// pair
+    fn subscribe_message(
+        pair_info: &PairInfo,
+        channel: &str,
+    ) -> Result<String, ExchangeConnectionError> {
+        let base_token = pair_info.base_token();
+        let quote_token = pair_info.quote_token();
+        let base_ticker =
+            get_base_exchange_ticker(base_token.clone(), quote_token.clone(), Exchange::Okx)?;
+        let quote_ticker = get_quote_exchange_ticker(base_token, quote_token, Exchange::Okx)?;
+
+        let pair = format!("{}-{}", base_ticker, quote_ticker);
+        Ok(json!({
+            "op": "subscribe",
+            "args": [{
+                "channel": channel,
+                "instId": pair,
+            }],
+        })
+        .to_string())
+    }
+
     /// Parse a price from an Okx websocket message
-    fn midpoint_from_ws_message(
+    pub(crate) fn midpoint_from_ws_message(
         message: Message,
         pair_info: &PairInfo,
-    ) -> Result<Option<Price>, ExchangeConnectionError> {
+    ) -> Result<Option<ExchangePrice>, ExchangeConnectionError> {
         let json_blob = parse_json_from_message(message, pair_info)?;
         if json_blob.is_none() {
             return Ok(None);
@@ -101,11 +148,13 @@ impl OkxConnection {
             parse_json_field_array(OKX_PRICE, &first_data_entry[OKX_BIDS][FIRST_ENTRY])?;
         let best_offer: f64 =
             parse_json_field_array(OKX_PRICE, &first_data_entry[OKX_ASKS][FIRST_ENTRY])?;
+        let exchange_timestamp = parse_json_field(OKX_TIMESTAMP, first_data_entry).ok();
 
         // A non-finite or non-positive bid/offer would corrupt the midpoint
         // (see incident 2026-05-08 cbBTC). Drop the message rather than emit
         // a bad price; the stream resumes on the next valid update.
-        Ok(safe_midpoint(best_bid, best_offer))
+        Ok(safe_midpoint(best_bid, best_offer)
+            .map(|price| ExchangePrice { price, exchange_timestamp }))
     }
 }
 
@@ -122,33 +171,18 @@ impl Stream for OkxConnection {
 impl ExchangeConnection for OkxConnection {
     async fn connect(
         pair_info: PairInfo,
-        _config: &ExchangeConnectionsConfig,
+        config: &ExchangeConnectionsConfig,
     ) -> Result<Self, ExchangeConnectionError>
     where
         Self: Sized,
     {
-        let base_token = pair_info.base_token();
-        let quote_token = pair_info.quote_token();
-
         // Connect to the websocket
         let url = Self::websocket_url();
         let (mut write, read) = ws_connect(url).await?;
+        let read = maybe_record(read, &pair_info, config);
 
         // Subscribe to the asset pair's bbo tick-by-tick stream
-        let base_ticker =
-            get_base_exchange_ticker(base_token.clone(), quote_token.clone(), Exchange::Okx)?;
-        let quote_ticker =
-            get_quote_exchange_ticker(base_token.clone(), quote_token.clone(), Exchange::Okx)?;
-
-        let pair = format!("{}-{}", base_ticker, quote_ticker);
-        let subscribe_str = json!({
-            "op": "subscribe",
-            "args": [{
-                "channel": "bbo-tbt",
-                "instId": pair,
-            }],
-        })
-        .to_string();
+        let subscribe_str = Self::subscribe_message(&pair_info, "bbo-tbt")?;
 
         write
             .send(Message::Text(subscribe_str))
@@ -232,3 +266,145 @@ impl ExchangeConnection for OkxConnection {
         }
     }
 }
+
+// --------------------
+// | Depth Connection |
+// --------------------
+
+/// The depth feed handler for Exchange::Okx
+pub struct OkxDepthConnection {
+    /// The underlying depth stream
+    depth_stream: BoxedDepthReader,
+    /// The underlying write stream of the websocket
+    write_stream: BoxedWsWriter,
+}
+
+impl OkxDepthConnection {
+    /// Apply an Okx order book message to the local book, returning a depth
+    /// snapshot of the updated book
+    fn depth_from_ws_message(
+        order_book: &OrderBookData,
+        message: Message,
+        pair_info: &PairInfo,
+    ) -> Result<Option<DepthSnapshot>, ExchangeConnectionError> {
+        let message_json = match parse_json_from_message(message, pair_info)? {
+            Some(json) => json,
+            None => return Ok(None),
+        };
+
+        // Ignore Okx status update messages
+        if message_json[OKX_EVENT].as_str().unwrap_or("") == OKX_SUBSCRIBE_EVENT {
+            return Ok(None);
+        }
+
+        // A snapshot replaces the local book entirely, updates are applied
+        // incrementally with a zero size indicating a removed level
+        if message_json[OKX_ACTION].as_str().unwrap_or("") == OKX_SNAPSHOT_ACTION {
+            order_book.clear();
+        }
+
+        let first_data_entry = &message_json[OKX_DATA][FIRST_ENTRY];
+        for (price, size) in parse_json_levels(&first_data_entry[OKX_BIDS])? {
+            if size == 0. { order_book.remove_bid(price) } else { order_book.add_bid(price, size) }
+        }
+        for (price, size) in parse_json_levels(&first_data_entry[OKX_ASKS])? {
+            if size == 0. {
+                order_book.remove_offer(price)
+            } else {
+                order_book.add_offer(price, size)
+            }
+        }
+
+        Ok(order_book.depth_snapshot(DEPTH_SNAPSHOT_LEVELS))
+    }
+}
+
+impl Stream for OkxDepthConnection {
+    type Item = DepthStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        Pin::new(&mut this.depth_stream).poll_next(cx)
+    }
+}
+
+#[async_trait]
+impl DepthConnection for OkxDepthConnection {
+    async fn connect(
+        pair_info: PairInfo,
+        _config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError> {
+        let url = OkxConnection::websocket_url();
+        let (mut write, read) = ws_connect(url).await?;
+
+        // Subscribe to the asset pair's order book channel
+        let subscribe_str = OkxConnection::subscribe_message(&pair_info, OKX_DEPTH_CHANNEL)?;
+        write
+            .send(Message::Text(subscribe_str))
+            .await
+            .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))?;
+
+        let order_book = OrderBookData::new();
+        let mapped_stream = read.filter_map(move |message| {
+            let pair_info = pair_info.clone();
+            let order_book = order_book.clone();
+            async move {
+                match message
+                    .map(|message| Self::depth_from_ws_message(&order_book, message, &pair_info))
+                {
+                    Ok(mapped_res) => mapped_res.transpose(),
+                    Err(e) => {
+                        log_task!(
+                            Task::ExchangeConnection,
+                            Outcome::Failed,
+                            exchange = "okx",
+                            error = %e,
+                            "error reading message from depth websocket"
+                        );
+                        Some(Err(ExchangeConnectionError::ConnectionHangup(e.to_string())))
+                    },
+                }
+            }
+        });
+
+        Ok(Self { depth_stream: Box::new(Box::pin(mapped_stream)), write_stream: Box::new(write) })
+    }
+
+    async fn send_keepalive(&mut self) -> Result<(), ExchangeConnectionError> {
+        // Okx in specific uses a text representation of the ping message
+        self.write_stream
+            .send(Message::Text(String::from(OKX_PING_MESSAGE)))
+            .await
+            .map_err(|err| ExchangeConnectionError::ConnectionHangup(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a bbo message with the given data entry fields
+    fn bbo_message(ts: Option<&str>) -> Message {
+        let mut entry = json!({ "bids": [["99", "1"]], "asks": [["101", "1"]] });
+        if let Some(ts) = ts {
+            entry["ts"] = json!(ts);
+        }
+        Message::text(json!({ "data": [entry] }).to_string())
+    }
+
+    #[test]
+    fn exchange_timestamp_is_optional() {
+        let pair_info = PairInfo::new(Exchange::Okx, "WETH".to_string(), "USDT".to_string(), None);
+
+        let price =
+            OkxConnection::midpoint_from_ws_message(bbo_message(Some("1700000000000")), &pair_info)
+                .unwrap()
+                .unwrap();
+        assert_eq!(price, ExchangePrice::new_with_timestamp(100., 1_700_000_000_000));
+
+        let price = OkxConnection::midpoint_from_ws_message(bbo_message(None), &pair_info)
+            .unwrap()
+            .unwrap();
+        assert_eq!(price, ExchangePrice::from(100.));
+    }
+}
@@ -1,33 +1,36 @@
-//! A local copy of the Coinbase order book
+//! A locally replicated exchange order book
 
 use std::sync::Arc;
 
-use crossbeam_skiplist::SkipSet;
+use crossbeam_skiplist::SkipMap;
+use itertools::Itertools;
 use ordered_float::NotNan;
 
+use crate::exchanges::depth::DepthSnapshot;
+
 // ------------------
 // | Orderbook Data |
 // ------------------
 
 /// A non-nan f64
 type NonNanF64 = NotNan<f64>;
-/// A shared skip set of price levels
-pub type OrderBookLevels = Arc<SkipSet<NonNanF64>>;
+/// A shared skip map of price levels to the size resting at each level
+pub type OrderBookLevels = Arc<SkipMap<NonNanF64, f64>>;
 
-/// The order book data stored locally by the connection
+/// The order book data stored locally by a connection
 #[derive(Clone, Default)]
-pub struct CoinbaseOrderBookData {
+pub struct OrderBookData {
     /// The bid price levels, sorted in ascending order
     bids: OrderBookLevels,
-    /// The offer price levels, sorted in ascending order  
+    /// The offer price levels, sorted in ascending order
     offers: OrderBookLevels,
 }
 
-impl CoinbaseOrderBookData {
+impl OrderBookData {
     /// Construct a new order book data
     pub fn new() -> Self {
-        let bids = Arc::new(SkipSet::new());
-        let offers = Arc::new(SkipSet::new());
+        let bids = Arc::new(SkipMap::new());
+        let offers = Arc::new(SkipMap::new());
         Self { bids, offers }
     }
 
@@ -37,12 +40,12 @@ impl CoinbaseOrderBookData {
 
     /// Get the best bid price from the current order book
     pub fn best_bid(&self) -> Option<f64> {
-        self.bids.back().map(|e| e.value().into_inner())
+        self.bids.back().map(|e| e.key().into_inner())
     }
 
     /// Get the best offer price from the current order book
     pub fn best_offer(&self) -> Option<f64> {
-        self.offers.front().map(|e| e.value().into_inner())
+        self.offers.front().map(|e| e.key().into_inner())
     }
 
     /// Get the midpoint price from the current order book
@@ -52,6 +55,26 @@ impl CoinbaseOrderBookData {
         Some((best_bid + best_offer) / 2.)
     }
 
+    /// Get a depth snapshot of the top `levels` levels on each side of the
+    /// book
+    pub fn depth_snapshot(&self, levels: usize) -> Option<DepthSnapshot> {
+        let bids = self
+            .bids
+            .iter()
+            .rev()
+            .take(levels)
+            .map(|e| (e.key().into_inner(), *e.value()))
+            .collect_vec();
+        let offers = self
+            .offers
+            .iter()
+            .take(levels)
+            .map(|e| (e.key().into_inner(), *e.value()))
+            .collect_vec();
+
+        DepthSnapshot::from_levels(&bids, &offers, levels)
+    }
+
     // ----------------------
     // | Order Book Updates |
     // ----------------------
@@ -70,34 +93,36 @@ impl CoinbaseOrderBookData {
         }
     }
 
-    /// Add a bid at the given price level.
+    /// Add a bid of the given size at the given price level, replacing any
+    /// size previously resting at the level.
     ///
     /// Non-finite and non-positive prices are silently rejected: a zero or
     /// negative bid would skew `midpoint` toward `best_offer / 2`, and a
     /// NaN/Inf bid would corrupt the book entirely. A real Coinbase feed
     /// never emits these, but partial-book / reset edge cases can.
-    pub fn add_bid(&self, price_level: f64) {
+    pub fn add_bid(&self, price_level: f64, size: f64) {
         if !price_level.is_finite() || price_level <= 0.0 {
             return;
         }
         if let Ok(price_notnan) = NotNan::new(price_level) {
-            self.bids.insert(price_notnan);
+            self.bids.insert(price_notnan, size);
         }
     }
 
-    /// Add an offer at the given price level.
+    /// Add an offer of the given size at the given price level, replacing any
+    /// size previously resting at the level.
     ///
     /// Non-finite and non-positive prices are silently rejected: a zero or
     /// negative offer becomes the new `best_offer` and pulls `midpoint` to
     /// `best_bid / 2`. This is the failure mode behind the cbBTC pricing
     /// incident on 2026-05-08 ~07:10 UTC. NaN/Inf would corrupt the book
     /// entirely.
-    pub fn add_offer(&self, price_level: f64) {
+    pub fn add_offer(&self, price_level: f64, size: f64) {
         if !price_level.is_finite() || price_level <= 0.0 {
             return;
         }
         if let Ok(price_notnan) = NotNan::new(price_level) {
-            self.offers.insert(price_notnan);
+            self.offers.insert(price_notnan, size);
         }
     }
 
@@ -106,6 +131,20 @@ impl CoinbaseOrderBookData {
         self.bids.clear();
         self.offers.clear();
     }
+
+    /// Drop all levels beyond the best `levels` on each side of the book.
+    ///
+    /// Exchanges that publish a fixed-depth book do not send deletions for
+    /// levels that fall out of the subscribed depth, so these must be pruned
+    /// locally to avoid resurfacing stale levels.
+    pub fn truncate(&self, levels: usize) {
+        while self.bids.len() > levels {
+            self.bids.pop_front();
+        }
+        while self.offers.len() > levels {
+            self.offers.pop_back();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -121,9 +160,9 @@ mod tests {
     /// be `None`.
     #[test]
     fn midpoint_ignores_zero_priced_offer() {
-        let book = CoinbaseOrderBookData::new();
-        book.add_bid(79_674.0);
-        book.add_offer(0.0);
+        let book = OrderBookData::new();
+        book.add_bid(79_674.0, 1.0);
+        book.add_offer(0.0, 1.0);
 
         assert_eq!(
             book.midpoint(),
@@ -137,9 +176,9 @@ mod tests {
     /// not produce real_offer / 2.
     #[test]
     fn midpoint_ignores_zero_priced_bid() {
-        let book = CoinbaseOrderBookData::new();
-        book.add_bid(0.0);
-        book.add_offer(79_675.0);
+        let book = OrderBookData::new();
+        book.add_bid(0.0, 1.0);
+        book.add_offer(79_675.0, 1.0);
 
         assert_eq!(book.midpoint(), None);
     }
@@ -148,10 +187,10 @@ mod tests {
     /// the new best offer (which would skew midpoint downward by ~½).
     #[test]
     fn zero_offer_does_not_displace_real_offer() {
-        let book = CoinbaseOrderBookData::new();
-        book.add_bid(79_674.0);
-        book.add_offer(79_675.0);
-        book.add_offer(0.0);
+        let book = OrderBookData::new();
+        book.add_bid(79_674.0, 1.0);
+        book.add_offer(79_675.0, 1.0);
+        book.add_offer(0.0, 1.0);
 
         assert_eq!(book.best_offer(), Some(79_675.0));
         assert_eq!(book.midpoint(), Some((79_674.0 + 79_675.0) / 2.0));
@@ -161,9 +200,9 @@ mod tests {
     /// rejected at insertion time.
     #[test]
     fn negative_prices_are_rejected() {
-        let book = CoinbaseOrderBookData::new();
-        book.add_bid(-1.0);
-        book.add_offer(-1.0);
+        let book = OrderBookData::new();
+        book.add_bid(-1.0, 1.0);
+        book.add_offer(-1.0, 1.0);
 
         assert_eq!(book.best_bid(), None);
         assert_eq!(book.best_offer(), None);
@@ -178,9 +217,9 @@ mod tests {
     #[test]
     fn non_finite_prices_are_rejected() {
         for bad in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
-            let book = CoinbaseOrderBookData::new();
-            book.add_bid(bad);
-            book.add_offer(bad);
+            let book = OrderBookData::new();
+            book.add_bid(bad, 1.0);
+            book.add_offer(bad, 1.0);
             assert_eq!(book.best_bid(), None, "bid {bad} should be rejected");
             assert_eq!(book.best_offer(), None, "offer {bad} should be rejected");
         }
@@ -190,10 +229,10 @@ mod tests {
     /// (which would push midpoint to infinity).
     #[test]
     fn inf_bid_does_not_displace_real_bid() {
-        let book = CoinbaseOrderBookData::new();
-        book.add_bid(79_674.0);
-        book.add_offer(79_675.0);
-        book.add_bid(f64::INFINITY);
+        let book = OrderBookData::new();
+        book.add_bid(79_674.0, 1.0);
+        book.add_offer(79_675.0, 1.0);
+        book.add_bid(f64::INFINITY, 1.0);
 
         assert_eq!(book.best_bid(), Some(79_674.0));
         assert_eq!(book.midpoint(), Some((79_674.0 + 79_675.0) / 2.0));
@@ -202,14 +241,48 @@ mod tests {
     /// Sanity: a normal book still computes the midpoint correctly.
     #[test]
     fn midpoint_normal_book() {
-        let book = CoinbaseOrderBookData::new();
-        book.add_bid(100.0);
-        book.add_bid(99.0);
-        book.add_offer(101.0);
-        book.add_offer(102.0);
+        let book = OrderBookData::new();
+        book.add_bid(100.0, 1.0);
+        book.add_bid(99.0, 1.0);
+        book.add_offer(101.0, 1.0);
+        book.add_offer(102.0, 1.0);
 
         assert_eq!(book.best_bid(), Some(100.0));
         assert_eq!(book.best_offer(), Some(101.0));
         assert_eq!(book.midpoint(), Some(100.5));
     }
+
+    /// Sizes are tracked per level and replaced on update, and the depth
+    /// snapshot reads the best levels first.
+    #[test]
+    fn depth_snapshot_tracks_sizes() {
+        let book = OrderBookData::new();
+        book.add_bid(100.0, 1.0);
+        book.add_bid(99.0, 2.0);
+        book.add_bid(98.0, 3.0);
+        book.add_offer(101.0, 4.0);
+        book.add_offer(101.0, 5.0);
+        book.add_offer(102.0, 6.0);
+
+        let snapshot = book.depth_snapshot(2).unwrap();
+        assert_eq!(snapshot.bids.iter().map(|l| l.price).collect::<Vec<_>>(), vec![100.0, 99.0]);
+        assert_eq!(snapshot.bids[1].cumulative_size, 3.0);
+        assert_eq!(snapshot.asks[0].size, 5.0);
+        assert_eq!(snapshot.asks[1].cumulative_size, 11.0);
+    }
+
+    /// Truncation keeps only the best levels on each side.
+    #[test]
+    fn truncate_drops_worst_levels() {
+        let book = OrderBookData::new();
+        for i in 0..5 {
+            book.add_bid(100.0 - i as f64, 1.0);
+            book.add_offer(101.0 + i as f64, 1.0);
+        }
+
+        book.truncate(2);
+        let snapshot = book.depth_snapshot(10).unwrap();
+        assert_eq!(snapshot.bids.iter().map(|l| l.price).collect::<Vec<_>>(), vec![100.0, 99.0]);
+        assert_eq!(snapshot.asks.iter().map(|l| l.price).collect::<Vec<_>>(), vec![101.0, 102.0]);
+    }
 }
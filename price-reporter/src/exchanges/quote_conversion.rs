@@ -0,0 +1,163 @@
+//! Defines a connection streaming a pair in a quote its exchange does not use
+//! as its default stable
+//!
+//! The pair is streamed in the exchange's default stable, and each price is
+//! divided by the price of the requested quote in that same stable. This lets
+//! a stream that fails over to an exchange with a different default stable
+//! keep publishing in its own quote.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
+use renegade_types_core::Token;
+
+use crate::{
+    exchanges::{
+        ExchangeConnectionsConfig, connect_exchange,
+        connection::{ExchangeConnection, ExchangePrice, PriceStreamType},
+        error::ExchangeConnectionError,
+    },
+    failover::quote_conversion_pair,
+    utils::{PairInfo, default_exchange_stable},
+};
+
+// --------------
+// | Connection |
+// --------------
+
+/// The connection handle for a pair converted into a different quote
+pub struct QuoteConversionConnection {
+    /// The connection streaming the pair in the exchange's default stable, or
+    /// `None` if the base is the default stable itself
+    base_conn: Option<Box<dyn ExchangeConnection>>,
+    /// The connection streaming the requested quote in the exchange's default
+    /// stable
+    quote_conn: Box<dyn ExchangeConnection>,
+    /// The latest price of the base in the default stable
+    base_price: Option<ExchangePrice>,
+    /// The latest price of the quote in the default stable
+    quote_price: Option<ExchangePrice>,
+}
+
+impl QuoteConversionConnection {
+    /// Get the converted price, once both legs have published a price
+    ///
+    /// The converted price is only as recent as its older leg.
+    fn converted_price(&self) -> Option<ExchangePrice> {
+        let quote_price = self.quote_price?;
+        let base_price = match &self.base_conn {
+            Some(_) => self.base_price?,
+            None => return Some(ExchangePrice { price: 1. / quote_price.price, ..quote_price }),
+        };
+
+        let exchange_timestamp = base_price.exchange_timestamp.zip(quote_price.exchange_timestamp);
+        Some(ExchangePrice {
+            price: base_price.price / quote_price.price,
+            exchange_timestamp: exchange_timestamp.map(|(base, quote)| base.min(quote)),
+        })
+    }
+
+    /// Drain the ready prices of a leg into its latest price
+    ///
+    /// Returns whether the leg published a price, or the leg's error or
+    /// closure.
+    fn poll_leg(
+        conn: &mut Box<dyn ExchangeConnection>,
+        latest: &mut Option<ExchangePrice>,
+        cx: &mut Context<'_>,
+    ) -> Result<bool, Option<ExchangeConnectionError>> {
+        let mut updated = false;
+        loop {
+            match conn.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(price))) => {
+                    *latest = Some(price);
+                    updated = true;
+                },
+                Poll::Ready(Some(Err(e))) => return Err(Some(e)),
+                Poll::Ready(None) => return Err(None),
+                Poll::Pending => return Ok(updated),
+            }
+        }
+    }
+}
+
+impl Stream for QuoteConversionConnection {
+    type Item = PriceStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // Both legs are drained until pending, so that both wake the task
+        let mut updated = match Self::poll_leg(&mut this.quote_conn, &mut this.quote_price, cx) {
+            Ok(updated) => updated,
+            Err(e) => return Poll::Ready(e.map(Err)),
+        };
+        if let Some(base_conn) = this.base_conn.as_mut() {
+            updated |= match Self::poll_leg(base_conn, &mut this.base_price, cx) {
+                Ok(updated) => updated,
+                Err(e) => return Poll::Ready(e.map(Err)),
+            };
+        }
+
+        match this.converted_price() {
+            Some(price) if updated => Poll::Ready(Some(Ok(price))),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+#[async_trait]
+impl ExchangeConnection for QuoteConversionConnection {
+    async fn connect(
+        pair_info: PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError>
+    where
+        Self: Sized,
+    {
+        let stable = default_exchange_stable(&pair_info.exchange, pair_info.chain);
+        let stable_ticker = stable.get_ticker().ok_or_else(|| {
+            ExchangeConnectionError::custom(format!("no ticker for the stable {stable}"))
+        })?;
+
+        let source = PairInfo { quote: stable_ticker, ..pair_info.clone() };
+        let quote_pair = quote_conversion_pair(&source, &pair_info.quote).ok_or_else(|| {
+            ExchangeConnectionError::custom(format!(
+                "{} is already quoted in its exchange's default stable",
+                pair_info.to_topic()
+            ))
+        })?;
+
+        let base_conn = if source.is_unit_pair() {
+            None
+        } else {
+            Some(connect_exchange(source, config).await?)
+        };
+        let quote_conn = connect_exchange(quote_pair, config).await?;
+
+        Ok(Self { base_conn, quote_conn, base_price: None, quote_price: None })
+    }
+
+    async fn send_keepalive(&mut self) -> Result<(), ExchangeConnectionError> {
+        if let Some(base_conn) = self.base_conn.as_mut() {
+            base_conn.send_keepalive().await?;
+        }
+        self.quote_conn.send_keepalive().await
+    }
+
+    /// Support is checked on each leg by the failover logic, as the legs may
+    /// be on any exchange
+    async fn supports_pair(
+        _base_token: &Token,
+        _quote_token: &Token,
+    ) -> Result<bool, ExchangeConnectionError>
+    where
+        Self: Sized,
+    {
+        Ok(true)
+    }
+}
@@ -0,0 +1,622 @@
+//! Recording and replay of raw exchange websocket frames
+//!
+//! In recording mode, every frame read by a price connection is appended to a
+//! per-pair file of JSON lines, grouped into one session per connection.
+//!
+//! Replay is selected per pair: the configured pairs are served from the
+//! replay pseudo-exchange while all other pairs connect to the live exchanges.
+//! Each connection plays back the next recorded session for the pair through
+//! the exchange's own message parser, with the original or accelerated timing.
+//! Recorded read errors are replayed as connection hangups, so that reconnect,
+//! heartbeat, and keepalive behavior can be exercised deterministically and
+//! offline.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt, stream};
+use renegade_types_core::{Exchange, Token};
+use renegade_util::get_current_time_millis;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    time::Instant,
+};
+use tungstenite::{Error as WsError, Message};
+
+use crate::{
+    exchanges::{
+        ExchangeConnectionsConfig,
+        binance::BinanceConnection,
+        bybit::BybitConnection,
+        coinbase::CoinbaseConnection,
+        connection::{BoxedPriceReader, ExchangeConnection, ExchangePrice, PriceStreamType},
+        error::ExchangeConnectionError,
+        hyperliquid::HyperliquidConnection,
+        kraken::KrakenConnection,
+        okx::OkxConnection,
+        order_book::OrderBookData,
+        venue::Venue,
+    },
+    log_task,
+    logger::{Outcome, Task},
+    utils::PairInfo,
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The extension of frame recording files
+const FRAME_FILE_EXTENSION: &str = "jsonl";
+
+// ---------
+// | Types |
+// ---------
+
+/// A websocket frame as recorded to disk
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedFrame {
+    /// A text frame
+    Text(String),
+    /// A binary frame
+    Binary(Vec<u8>),
+    /// A ping frame
+    Ping,
+    /// A pong frame
+    Pong,
+    /// A close frame
+    Close,
+    /// An error reading from the websocket, e.g. a connection reset
+    Error(String),
+}
+
+impl RecordedFrame {
+    /// Record the result of reading from a websocket, returning `None` for raw
+    /// frames, which are never surfaced by the reader
+    fn from_read(res: &Result<Message, WsError>) -> Option<Self> {
+        Some(match res {
+            Ok(Message::Text(text)) => Self::Text(text.clone()),
+            Ok(Message::Binary(data)) => Self::Binary(data.clone()),
+            Ok(Message::Ping(_)) => Self::Ping,
+            Ok(Message::Pong(_)) => Self::Pong,
+            Ok(Message::Close(_)) => Self::Close,
+            Ok(Message::Frame(_)) => return None,
+            Err(e) => Self::Error(e.to_string()),
+        })
+    }
+
+    /// Convert the recorded frame back into the result of a websocket read
+    fn into_read(self) -> Result<Message, ExchangeConnectionError> {
+        Ok(match self {
+            Self::Text(text) => Message::Text(text),
+            Self::Binary(data) => Message::Binary(data),
+            Self::Ping => Message::Ping(Vec::new()),
+            Self::Pong => Message::Pong(Vec::new()),
+            Self::Close => Message::Close(None),
+            Self::Error(e) => return Err(ExchangeConnectionError::ConnectionHangup(e)),
+        })
+    }
+}
+
+/// A single line of a frame recording file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameRecord {
+    /// The session in which the frame was read, identified by the time at
+    /// which the connection was established, in milliseconds since the epoch
+    pub session: u64,
+    /// The time since the connection was established at which the frame was
+    /// read, in milliseconds
+    pub elapsed_ms: u64,
+    /// The frame
+    pub frame: RecordedFrame,
+}
+
+/// A parser from raw websocket frames to prices, holding any state carried
+/// across frames
+type FrameParser =
+    Box<dyn FnMut(Message) -> Result<Option<ExchangePrice>, ExchangeConnectionError> + Send>;
+
+// ------------
+// | Recorder |
+// ------------
+
+/// Records the frames read by price connections to per-pair files
+#[derive(Clone, Debug)]
+pub struct FrameRecorder {
+    /// The directory in which recording files are written
+    dir: PathBuf,
+    /// The channel on which records are forwarded to the writer
+    record_tx: UnboundedSender<(PathBuf, FrameRecord)>,
+}
+
+impl FrameRecorder {
+    /// Create a new frame recorder writing to the given directory, spawning
+    /// the writer
+    pub fn new(dir: &str) -> Result<Self, ExchangeConnectionError> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| ExchangeConnectionError::FrameRecording(format!("{dir}: {e}")))?;
+
+        let (record_tx, record_rx) = unbounded_channel();
+        tokio::task::spawn_blocking(move || Self::writer_task(record_rx));
+        Ok(Self { dir: PathBuf::from(dir), record_tx })
+    }
+
+    /// Record every read from the given websocket stream as a new session for
+    /// the pair
+    pub fn record<S>(
+        &self,
+        pair_info: &PairInfo,
+        read: S,
+    ) -> impl Stream<Item = Result<Message, WsError>> + Unpin + Send + use<S>
+    where
+        S: Stream<Item = Result<Message, WsError>> + Unpin + Send,
+    {
+        let path = frame_file_path(&self.dir, pair_info);
+        let record_tx = self.record_tx.clone();
+        let session = get_current_time_millis();
+        let start = Instant::now();
+
+        read.inspect(move |res| {
+            if let Some(frame) = RecordedFrame::from_read(res) {
+                let elapsed_ms = start.elapsed().as_millis() as u64;
+                let record = FrameRecord { session, elapsed_ms, frame };
+                let _ = record_tx.send((path.clone(), record));
+            }
+        })
+    }
+
+    /// The task writing records to their files as JSON lines
+    ///
+    /// Runs on a blocking thread until every recorder is dropped.
+    fn writer_task(mut record_rx: UnboundedReceiver<(PathBuf, FrameRecord)>) {
+        let mut writers: HashMap<PathBuf, BufWriter<File>> = HashMap::new();
+        while let Some((path, record)) = record_rx.blocking_recv() {
+            if let Err(e) = Self::write_record(&mut writers, &path, &record) {
+                log_task!(
+                    Task::ExchangeConnection,
+                    Outcome::Failed,
+                    subject = %path.display(),
+                    error = %e,
+                    "failed to write recorded frame"
+                );
+            }
+
+            // Flush once the backlog is drained
+            if record_rx.is_empty() {
+                for writer in writers.values_mut() {
+                    let _ = writer.flush();
+                }
+            }
+        }
+    }
+
+    /// Append a single record to its file, opening the file if necessary
+    fn write_record(
+        writers: &mut HashMap<PathBuf, BufWriter<File>>,
+        path: &Path,
+        record: &FrameRecord,
+    ) -> std::io::Result<()> {
+        if !writers.contains_key(path) {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            writers.insert(path.to_path_buf(), BufWriter::new(file));
+        }
+
+        let writer = writers.get_mut(path).expect("writer inserted above");
+        serde_json::to_writer(&mut *writer, record)?;
+        writer.write_all(b"\n")
+    }
+}
+
+/// Record the reads from a price connection's websocket if recording is
+/// enabled
+pub(crate) fn maybe_record<S>(
+    read: S,
+    pair_info: &PairInfo,
+    config: &ExchangeConnectionsConfig,
+) -> Pin<Box<dyn Stream<Item = Result<Message, WsError>> + Send>>
+where
+    S: Stream<Item = Result<Message, WsError>> + Unpin + Send + 'static,
+{
+    match &config.frame_recorder {
+        Some(recorder) => Box::pin(recorder.record(pair_info, read)),
+        None => Box::pin(read),
+    }
+}
+
+// ----------
+// | Replay |
+// ----------
+
+/// The configuration of the replay pseudo-exchange
+#[derive(Clone, Debug)]
+pub struct ReplayConfig {
+    /// The directory from which recording files are read
+    pub dir: PathBuf,
+    /// The factor by which to accelerate the recorded timing, or zero to
+    /// replay frames without delay
+    pub speed: f64,
+    /// The topics of the pairs served from recordings, e.g.
+    /// `binance-WETH-USDT`
+    pairs: HashSet<String>,
+    /// The index of the next session to replay for each pair, shared across
+    /// connections so that reconnects replay subsequent sessions
+    cursors: Arc<Mutex<HashMap<PairInfo, usize>>>,
+}
+
+impl ReplayConfig {
+    /// Create a new replay configuration serving the given pairs from
+    /// recordings
+    pub fn new(dir: &str, speed: f64, pairs: &[String]) -> Self {
+        let pairs = pairs.iter().cloned().collect();
+        Self { dir: PathBuf::from(dir), speed, pairs, cursors: Arc::default() }
+    }
+
+    /// Whether the given pair is served from recordings
+    pub fn replays(&self, pair_info: &PairInfo) -> bool {
+        self.pairs.contains(&pair_info.to_topic())
+    }
+
+    /// Take the next recorded session for the given pair
+    fn next_session(
+        &self,
+        pair_info: &PairInfo,
+    ) -> Result<Vec<FrameRecord>, ExchangeConnectionError> {
+        let path = frame_file_path(&self.dir, pair_info);
+        let mut sessions = read_sessions(&path)?;
+
+        let mut cursors = self.cursors.lock().expect("replay cursors lock poisoned");
+        let cursor = cursors.entry(pair_info.clone()).or_default();
+        if *cursor >= sessions.len() {
+            return Err(ExchangeConnectionError::ConnectionHangup(format!(
+                "no recorded sessions remain in {}",
+                path.display()
+            )));
+        }
+
+        let session = sessions.swap_remove(*cursor);
+        *cursor += 1;
+        Ok(session)
+    }
+}
+
+/// A connection which replays the recorded frames of a pair through its
+/// exchange's message parser
+pub struct ReplayConnection {
+    /// The underlying stream of replayed prices
+    price_stream: BoxedPriceReader,
+}
+
+impl Stream for ReplayConnection {
+    type Item = PriceStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.price_stream.as_mut().poll_next_unpin(cx)
+    }
+}
+
+#[async_trait]
+impl ExchangeConnection for ReplayConnection {
+    async fn connect(
+        pair_info: PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError>
+    where
+        Self: Sized,
+    {
+        let replay = config
+            .frame_replay
+            .as_ref()
+            .filter(|replay| replay.replays(&pair_info))
+            .ok_or_else(|| ExchangeConnectionError::unsupported_exchange(pair_info.exchange))?;
+        let mut parser = frame_parser(&pair_info)?;
+        let session = replay.next_session(&pair_info)?;
+
+        log_task!(
+            Task::ExchangeConnection,
+            Outcome::Ok,
+            subject = %pair_info.to_topic(),
+            num_frames = session.len(),
+            "replaying recorded session"
+        );
+
+        let delays = replay_delays(&session, replay.speed);
+        let frames = session.into_iter().map(|record| record.frame);
+        let mapped_stream = stream::iter(delays.into_iter().zip(frames))
+            .then(|(delay, frame)| async move {
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                frame
+            })
+            .filter_map(move |frame| {
+                let res = frame.into_read().and_then(&mut parser).transpose();
+                async move { res }
+            });
+
+        Ok(Self { price_stream: Box::new(Box::pin(mapped_stream)) })
+    }
+
+    async fn supports_pair(
+        _base_token: &Token,
+        _quote_token: &Token,
+    ) -> Result<bool, ExchangeConnectionError>
+    where
+        Self: Sized,
+    {
+        Ok(true)
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Get the path of the recording file for the given pair
+fn frame_file_path(dir: &Path, pair_info: &PairInfo) -> PathBuf {
+    dir.join(format!("{}.{FRAME_FILE_EXTENSION}", pair_info.to_topic()))
+}
+
+/// Read the recorded sessions from the given file, in order
+fn read_sessions(path: &Path) -> Result<Vec<Vec<FrameRecord>>, ExchangeConnectionError> {
+    let to_err =
+        |e: String| ExchangeConnectionError::FrameRecording(format!("{}: {e}", path.display()));
+    let file = File::open(path).map_err(|e| to_err(e.to_string()))?;
+
+    let records = BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let line = line.map_err(|e| to_err(e.to_string()))?;
+            serde_json::from_str(&line).map_err(|e| to_err(e.to_string()))
+        })
+        .collect::<Result<Vec<FrameRecord>, _>>()?;
+
+    Ok(group_sessions(records))
+}
+
+/// Group consecutive records of the same session
+fn group_sessions(records: Vec<FrameRecord>) -> Vec<Vec<FrameRecord>> {
+    let mut sessions: Vec<Vec<FrameRecord>> = Vec::new();
+    for record in records {
+        match sessions.last_mut() {
+            Some(session) if session[0].session == record.session => session.push(record),
+            _ => sessions.push(vec![record]),
+        }
+    }
+
+    sessions
+}
+
+/// Compute the delay before each frame of a session, scaling the recorded
+/// timing by the given speed
+///
+/// A speed of zero replays every frame without delay.
+fn replay_delays(session: &[FrameRecord], speed: f64) -> Vec<Duration> {
+    let mut prev_elapsed_ms = 0;
+    session
+        .iter()
+        .map(|record| {
+            let gap_ms = record.elapsed_ms.saturating_sub(prev_elapsed_ms);
+            prev_elapsed_ms = record.elapsed_ms;
+            if speed > 0. {
+                Duration::from_secs_f64(gap_ms as f64 / 1000. / speed)
+            } else {
+                Duration::ZERO
+            }
+        })
+        .collect()
+}
+
+/// Build the frame parser of the given pair's exchange
+fn frame_parser(pair_info: &PairInfo) -> Result<FrameParser, ExchangeConnectionError> {
+    let pair_info = pair_info.clone();
+    match pair_info.venue {
+        Some(Venue::Bybit) => {
+            return Ok(Box::new(move |msg| {
+                BybitConnection::midpoint_from_ws_message(msg, &pair_info)
+            }));
+        },
+        Some(Venue::Hyperliquid) => {
+            return Ok(Box::new(move |msg| {
+                HyperliquidConnection::mark_price_from_ws_message(msg, &pair_info)
+            }));
+        },
+        None => {},
+    }
+
+    let parser: FrameParser = match pair_info.exchange {
+        Exchange::Binance => {
+            Box::new(move |msg| BinanceConnection::midpoint_from_ws_message(msg, &pair_info))
+        },
+        Exchange::Kraken => {
+            Box::new(move |msg| KrakenConnection::midpoint_from_ws_message(msg, &pair_info))
+        },
+        Exchange::Okx => {
+            Box::new(move |msg| OkxConnection::midpoint_from_ws_message(msg, &pair_info))
+        },
+        Exchange::Coinbase => {
+            let order_book = OrderBookData::new();
+            let mut last_sequence_num = -1;
+            Box::new(move |msg| {
+                CoinbaseConnection::midpoint_from_ws_message(
+                    &order_book,
+                    msg,
+                    &pair_info,
+                    &mut last_sequence_num,
+                )
+            })
+        },
+        exchange => return Err(ExchangeConnectionError::unsupported_exchange(exchange)),
+    };
+
+    Ok(parser)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a text frame record
+    fn text_record(session: u64, elapsed_ms: u64, text: &str) -> FrameRecord {
+        FrameRecord { session, elapsed_ms, frame: RecordedFrame::Text(text.to_string()) }
+    }
+
+    #[test]
+    fn records_roundtrip_through_json() {
+        let records = [
+            text_record(1, 0, "{}"),
+            FrameRecord { session: 1, elapsed_ms: 5, frame: RecordedFrame::Ping },
+            FrameRecord {
+                session: 1,
+                elapsed_ms: 9,
+                frame: RecordedFrame::Error("connection reset".to_string()),
+            },
+        ];
+
+        for record in records {
+            let line = serde_json::to_string(&record).unwrap();
+            assert_eq!(serde_json::from_str::<FrameRecord>(&line).unwrap(), record);
+        }
+    }
+
+    #[test]
+    fn sessions_are_grouped_in_order() {
+        let records = vec![
+            text_record(1, 0, "a"),
+            text_record(1, 10, "b"),
+            text_record(2, 0, "c"),
+            text_record(3, 0, "d"),
+            text_record(3, 5, "e"),
+        ];
+
+        let sessions = group_sessions(records);
+        let lens = sessions.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(lens, vec![2, 1, 2]);
+        assert_eq!(sessions[1][0].session, 2);
+    }
+
+    #[test]
+    fn delays_scale_with_speed() {
+        let session =
+            vec![text_record(1, 100, "a"), text_record(1, 300, "b"), text_record(1, 300, "c")];
+
+        let original = replay_delays(&session, 1.);
+        assert_eq!(
+            original,
+            vec![Duration::from_millis(100), Duration::from_millis(200), Duration::ZERO]
+        );
+
+        let accelerated = replay_delays(&session, 2.);
+        assert_eq!(accelerated[1], Duration::from_millis(100));
+
+        assert!(replay_delays(&session, 0.).iter().all(Duration::is_zero));
+    }
+
+    #[test]
+    fn recorded_errors_replay_as_hangups() {
+        let res = RecordedFrame::Error("connection reset".to_string()).into_read();
+        assert!(matches!(res, Err(ExchangeConnectionError::ConnectionHangup(_))));
+    }
+
+    // --- Fixtures --- //
+
+    /// The directory holding the recorded fixture sessions
+    const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/replay");
+
+    /// Build a pair info from tickers
+    fn pair(exchange: Exchange, base: &str, quote: &str) -> PairInfo {
+        PairInfo::new(exchange, base.to_string(), quote.to_string(), None)
+    }
+
+    /// Build a connection config replaying the given pairs from the fixtures
+    /// without delay
+    fn fixture_config(pairs: &[&PairInfo]) -> ExchangeConnectionsConfig {
+        let pairs = pairs.iter().map(|pair| pair.to_topic()).collect::<Vec<_>>();
+        let replay = ReplayConfig::new(FIXTURES_DIR, 0. /* speed */, &pairs);
+        ExchangeConnectionsConfig { frame_replay: Some(replay), ..Default::default() }
+    }
+
+    /// Replay the next recorded session of a pair, collecting its prices
+    async fn replay_session(
+        pair_info: &PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Vec<PriceStreamType> {
+        let conn = ReplayConnection::connect(pair_info.clone(), config).await.unwrap();
+        conn.collect().await
+    }
+
+    #[test]
+    fn only_configured_pairs_are_replayed() {
+        let replayed = pair(Exchange::Okx, "WETH", "USDT");
+        let live = pair(Exchange::Binance, "WETH", "USDT");
+        let config = fixture_config(&[&replayed]);
+
+        assert!(config.replays(&replayed));
+        assert!(!config.replays(&live));
+        assert!(!ExchangeConnectionsConfig::default().replays(&replayed));
+    }
+
+    #[tokio::test]
+    async fn replays_coinbase_order_book() {
+        let pair_info = pair(Exchange::Coinbase, "WETH", "USDC");
+        let config = fixture_config(&[&pair_info]);
+
+        // The snapshot is followed by a bid removal and an offer insertion,
+        // while subscription acks and heartbeats carry no updates
+        let prices = replay_session(&pair_info, &config).await;
+        let prices = prices.into_iter().map(|res| res.unwrap().price).collect::<Vec<_>>();
+        assert_eq!(prices, vec![2000.5, 2000., 1999.75]);
+    }
+
+    #[tokio::test]
+    async fn skips_okx_protocol_violations() {
+        let pair_info = pair(Exchange::Okx, "WETH", "USDT");
+        let config = fixture_config(&[&pair_info]);
+
+        let prices = replay_session(&pair_info, &config).await;
+        let prices = prices.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            prices,
+            vec![
+                ExchangePrice::new_with_timestamp(100., 1_700_000_000_000),
+                ExchangePrice::from(101.)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn cloudflare_reset_hangs_up_and_reconnects_to_next_session() {
+        let pair_info = pair(Exchange::Kraken, "WETH", "USDC");
+        let config = fixture_config(&[&pair_info]);
+
+        // The proxy restart notice is skipped, and the reset that follows it
+        // ends the first session
+        let first = replay_session(&pair_info, &config).await;
+        assert_eq!(first.len(), 2);
+        assert_eq!(
+            *first[0].as_ref().unwrap(),
+            ExchangePrice::new_with_timestamp(2000., 1_700_000_000_123)
+        );
+        assert!(matches!(first[1], Err(ExchangeConnectionError::ConnectionHangup(_))));
+
+        // The reconnect replays the second session
+        let second = replay_session(&pair_info, &config).await;
+        let prices = second.into_iter().map(|res| res.unwrap().price).collect::<Vec<_>>();
+        assert_eq!(prices, vec![2002.]);
+
+        // Once the recorded sessions are exhausted, connections fail
+        let res = ReplayConnection::connect(pair_info, &config).await;
+        assert!(matches!(res, Err(ExchangeConnectionError::ConnectionHangup(_))));
+    }
+}
@@ -0,0 +1,549 @@
+//! Defines the logic for deriving prices from Uniswap V3 pools on-chain
+//!
+//! Prices are read from a pool's `slot0` on connect and updated from the
+//! `sqrtPriceX96` of each `Swap` event emitted by the pool. Long-tail pools
+//! may go minutes without a swap, so `slot0` is also re-read periodically to
+//! keep the stream live.
+
+use std::{
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use alloy::{
+    primitives::{Address, U160, aliases::U24, hex},
+    providers::{DynProvider, Provider, ProviderBuilder, WsConnect},
+    rpc::types::{Filter, Log},
+    sol,
+    sol_types::SolEvent,
+};
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt, stream};
+use renegade_types_core::{Exchange, Price, Token};
+use renegade_util::err_str;
+use tokio::time::{Instant, interval_at};
+use tokio_stream::wrappers::IntervalStream;
+
+use crate::{
+    PairInfo,
+    exchanges::{
+        ExchangeConnectionsConfig,
+        connection::{
+            BoxedPriceReader, ExchangeConnection, ExchangePrice, InitializablePriceStream,
+            PriceStreamType,
+        },
+        error::ExchangeConnectionError,
+        util::exchange_lists_pair_tokens,
+    },
+    log_task,
+    logger::{Outcome, Task},
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The address of the Uniswap V3 factory on Ethereum mainnet and Arbitrum
+pub const DEFAULT_UNISWAP_V3_FACTORY: Address =
+    Address::new(hex!("1F98431c8aD98523631AE4a59f267346ea31F984"));
+/// The fee tiers, in hundredths of a basis point, searched for a pool
+const UNISWAP_V3_FEE_TIERS: [u32; 4] = [100, 500, 3_000, 10_000];
+/// The interval at which the pool's `slot0` is re-read in the absence of
+/// swaps
+const SLOT0_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// The number of milliseconds in a second, used to convert block timestamps
+const MILLIS_PER_SECOND: u64 = 1000;
+/// The number of fractional bits in a Q64.96 fixed point number
+const Q96_FRACTIONAL_BITS: i32 = 96;
+/// The number of bits in a limb of a `Uint`
+const LIMB_BITS: i32 = 64;
+
+// --------
+// | ABIs |
+// --------
+
+sol! {
+    #[sol(rpc)]
+    contract IUniswapV3Factory {
+        function getPool(address tokenA, address tokenB, uint24 fee) external view returns (address pool);
+    }
+
+    #[sol(rpc)]
+    contract IUniswapV3Pool {
+        event Swap(address indexed sender, address indexed recipient, int256 amount0, int256 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick);
+        function token0() external view returns (address);
+        function liquidity() external view returns (uint128);
+        function slot0() external view returns (uint160 sqrtPriceX96, int24 tick, uint16 observationIndex, uint16 observationCardinality, uint16 observationCardinalityNext, uint8 feeProtocol, bool unlocked);
+    }
+
+    #[sol(rpc)]
+    contract IERC20Metadata {
+        function decimals() external view returns (uint8);
+    }
+}
+
+// ---------
+// | Types |
+// ---------
+
+/// The static parameters of a pool needed to convert its price into a price
+/// of the base token in units of the quote token
+#[derive(Clone, Copy, Debug)]
+struct PoolInfo {
+    /// The address of the pool
+    address: Address,
+    /// Whether the base token is the pool's `token0`
+    base_is_token0: bool,
+    /// The decimals of the pool's `token0`
+    token0_decimals: u8,
+    /// The decimals of the pool's `token1`
+    token1_decimals: u8,
+}
+
+impl PoolInfo {
+    /// Convert a pool's `sqrtPriceX96` into a price of the base token
+    fn price(&self, sqrt_price_x96: U160) -> Option<Price> {
+        price_from_sqrt_price_x96(
+            sqrt_price_x96,
+            self.token0_decimals,
+            self.token1_decimals,
+            self.base_is_token0,
+        )
+    }
+}
+
+// --------------
+// | Connection |
+// --------------
+
+/// The connection handle for Uniswap V3 price data
+pub struct UniswapV3Connection {
+    /// The underlying price stream
+    price_stream: BoxedPriceReader,
+    /// The websocket RPC provider, kept alive for the lifetime of the log
+    /// subscription
+    provider: DynProvider,
+}
+
+impl UniswapV3Connection {
+    /// Connect to the configured websocket RPC node
+    async fn connect_provider(
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<DynProvider, ExchangeConnectionError> {
+        let rpc_addr = config
+            .eth_websocket_addr
+            .clone()
+            .ok_or_else(|| ExchangeConnectionError::unsupported_exchange(Exchange::UniswapV3))?;
+        let provider = ProviderBuilder::new()
+            .connect_ws(WsConnect::new(rpc_addr))
+            .await
+            .map_err(err_str!(ExchangeConnectionError::HandshakeFailure))?;
+
+        Ok(DynProvider::new(provider))
+    }
+
+    /// Check whether the factory has a pool for the given pair on the
+    /// configured RPC node
+    ///
+    /// `supports_pair` only checks the token remap, as it is not given the
+    /// node to query, so subscriptions are validated with this check instead.
+    pub async fn has_pool(
+        base_token: &Token,
+        quote_token: &Token,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<bool, ExchangeConnectionError> {
+        if !config.uniswap_v3_configured() || !Self::supports_pair(base_token, quote_token).await? {
+            return Ok(false);
+        }
+
+        let provider = Self::connect_provider(config).await?;
+        let base_addr = Self::token_address(base_token, base_token, quote_token)?;
+        let quote_addr = Self::token_address(quote_token, base_token, quote_token)?;
+        let factory = config.uniswap_v3_factory.unwrap_or(DEFAULT_UNISWAP_V3_FACTORY);
+        let pool = Self::find_pool(&provider, factory, base_addr, quote_addr).await?;
+
+        Ok(pool.is_some())
+    }
+
+    /// Get the on-chain address of a token
+    ///
+    /// The Uniswap V3 exchange ticker is used if the token remap provides one,
+    /// allowing a token to be priced from a pool on a different chain than
+    /// the one it is listed on.
+    fn token_address(
+        token: &Token,
+        base_token: &Token,
+        quote_token: &Token,
+    ) -> Result<Address, ExchangeConnectionError> {
+        let addr =
+            token.get_exchange_ticker(Exchange::UniswapV3).unwrap_or_else(|| token.get_addr());
+        Address::from_str(&addr).map_err(|_| {
+            ExchangeConnectionError::UnsupportedPair(
+                base_token.clone(),
+                quote_token.clone(),
+                Exchange::UniswapV3,
+            )
+        })
+    }
+
+    /// Find the pool with the deepest in-range liquidity for the given tokens
+    /// across all fee tiers
+    async fn find_pool(
+        provider: &DynProvider,
+        factory: Address,
+        base_addr: Address,
+        quote_addr: Address,
+    ) -> Result<Option<Address>, ExchangeConnectionError> {
+        let factory = IUniswapV3Factory::new(factory, provider.clone());
+
+        let mut best_pool: Option<(Address, u128)> = None;
+        for fee in UNISWAP_V3_FEE_TIERS {
+            let pool = factory
+                .getPool(base_addr, quote_addr, U24::from(fee))
+                .call()
+                .await
+                .map_err(err_str!(ExchangeConnectionError::ConnectionHangup))?;
+            if pool.is_zero() {
+                continue;
+            }
+
+            let liquidity = IUniswapV3Pool::new(pool, provider.clone())
+                .liquidity()
+                .call()
+                .await
+                .map_err(err_str!(ExchangeConnectionError::ConnectionHangup))?;
+            if best_pool.is_none_or(|(_, best_liquidity)| liquidity > best_liquidity) {
+                best_pool = Some((pool, liquidity));
+            }
+        }
+
+        Ok(best_pool.map(|(pool, _)| pool))
+    }
+
+    /// Fetch the static parameters of the given pool
+    async fn fetch_pool_info(
+        provider: &DynProvider,
+        pool: Address,
+        base_addr: Address,
+        quote_addr: Address,
+    ) -> Result<PoolInfo, ExchangeConnectionError> {
+        let token0 = IUniswapV3Pool::new(pool, provider.clone())
+            .token0()
+            .call()
+            .await
+            .map_err(err_str!(ExchangeConnectionError::ConnectionHangup))?;
+        let base_is_token0 = token0 == base_addr;
+        let base_decimals = Self::fetch_decimals(provider, base_addr).await?;
+        let quote_decimals = Self::fetch_decimals(provider, quote_addr).await?;
+
+        let (token0_decimals, token1_decimals) = if base_is_token0 {
+            (base_decimals, quote_decimals)
+        } else {
+            (quote_decimals, base_decimals)
+        };
+
+        Ok(PoolInfo { address: pool, base_is_token0, token0_decimals, token1_decimals })
+    }
+
+    /// Fetch the decimals of an ERC20 token
+    async fn fetch_decimals(
+        provider: &DynProvider,
+        token: Address,
+    ) -> Result<u8, ExchangeConnectionError> {
+        IERC20Metadata::new(token, provider.clone())
+            .decimals()
+            .call()
+            .await
+            .map_err(err_str!(ExchangeConnectionError::ConnectionHangup))
+    }
+
+    /// Fetch the current price of the pool from its `slot0`
+    async fn fetch_pool_price(
+        provider: &DynProvider,
+        pool: &PoolInfo,
+    ) -> Result<Option<Price>, ExchangeConnectionError> {
+        let slot0 = IUniswapV3Pool::new(pool.address, provider.clone())
+            .slot0()
+            .call()
+            .await
+            .map_err(err_str!(ExchangeConnectionError::ConnectionHangup))?;
+
+        Ok(pool.price(slot0.sqrtPriceX96))
+    }
+
+    /// Parse a price from a `Swap` event log, timestamped with the log's
+    /// block time if the node reports it
+    fn price_from_swap_log(
+        log: &Log,
+        pool: &PoolInfo,
+    ) -> Result<Option<ExchangePrice>, ExchangeConnectionError> {
+        // Logs removed by a reorg do not reflect the pool's price
+        if log.removed {
+            return Ok(None);
+        }
+
+        let swap = log
+            .log_decode::<IUniswapV3Pool::Swap>()
+            .map_err(err_str!(ExchangeConnectionError::InvalidMessage))?;
+        let price = pool.price(swap.data().sqrtPriceX96);
+        Ok(price.map(|price| ExchangePrice {
+            price,
+            exchange_timestamp: log.block_timestamp.map(|secs| secs * MILLIS_PER_SECOND),
+        }))
+    }
+}
+
+impl Stream for UniswapV3Connection {
+    type Item = PriceStreamType;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.price_stream.as_mut().poll_next_unpin(cx)
+    }
+}
+
+#[async_trait]
+impl ExchangeConnection for UniswapV3Connection {
+    async fn connect(
+        pair_info: PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<Self, ExchangeConnectionError>
+    where
+        Self: Sized,
+    {
+        let base_token = pair_info.base_token();
+        let quote_token = pair_info.quote_token();
+        let unsupported_pair = || {
+            ExchangeConnectionError::UnsupportedPair(
+                base_token.clone(),
+                quote_token.clone(),
+                Exchange::UniswapV3,
+            )
+        };
+
+        // Connect to the RPC node
+        let provider = Self::connect_provider(config).await?;
+
+        // Find the pool for the pair
+        let base_addr = Self::token_address(&base_token, &base_token, &quote_token)?;
+        let quote_addr = Self::token_address(&quote_token, &base_token, &quote_token)?;
+        let factory = config.uniswap_v3_factory.unwrap_or(DEFAULT_UNISWAP_V3_FACTORY);
+        let pool_addr = Self::find_pool(&provider, factory, base_addr, quote_addr)
+            .await?
+            .ok_or_else(unsupported_pair)?;
+        let pool = Self::fetch_pool_info(&provider, pool_addr, base_addr, quote_addr).await?;
+
+        log_task!(
+            Task::ExchangeConnection,
+            Outcome::Ok,
+            exchange = "uniswapv3",
+            subject = %pair_info.to_topic(),
+            pool = %pool.address,
+            "connected to uniswap v3 pool"
+        );
+
+        // Stream prices from the pool's swaps
+        let filter = Filter::new().address(pool.address).event(IUniswapV3Pool::Swap::SIGNATURE);
+        let swap_stream = provider
+            .subscribe_logs(&filter)
+            .await
+            .map_err(err_str!(ExchangeConnectionError::HandshakeFailure))?
+            .into_stream()
+            .filter_map(
+                move |log| async move { Self::price_from_swap_log(&log, &pool).transpose() },
+            );
+
+        // Periodically re-read `slot0` so that quiet pools still tick
+        let poll_provider = provider.clone();
+        let poll_interval = interval_at(Instant::now() + SLOT0_POLL_INTERVAL, SLOT0_POLL_INTERVAL);
+        let poll_stream = IntervalStream::new(poll_interval).filter_map(move |_| {
+            let provider = poll_provider.clone();
+            async move {
+                let price = Self::fetch_pool_price(&provider, &pool).await;
+                price.map(|price| price.map(ExchangePrice::from)).transpose()
+            }
+        });
+
+        let mapped_stream = stream::select(Box::pin(swap_stream), Box::pin(poll_stream));
+
+        // Initialize the stream with the pool's current price, if it is valid
+        let price_stream = match Self::fetch_pool_price(&provider, &pool).await? {
+            Some(price) => InitializablePriceStream::new_with_initial(mapped_stream, price),
+            None => InitializablePriceStream::new(mapped_stream),
+        };
+
+        Ok(Self { price_stream: Box::new(price_stream), provider })
+    }
+
+    async fn send_keepalive(&mut self) -> Result<(), ExchangeConnectionError> {
+        // Check that the RPC connection is still live
+        self.provider
+            .get_block_number()
+            .await
+            .map(|_| ())
+            .map_err(err_str!(ExchangeConnectionError::SendError))
+    }
+
+    /// Pool existence can only be checked against the configured RPC node,
+    /// which is done by `has_pool`
+    async fn supports_pair(
+        base_token: &Token,
+        quote_token: &Token,
+    ) -> Result<bool, ExchangeConnectionError> {
+        if !exchange_lists_pair_tokens(Exchange::UniswapV3, base_token, quote_token) {
+            return Ok(false);
+        }
+
+        let base_addr = Self::token_address(base_token, base_token, quote_token);
+        let quote_addr = Self::token_address(quote_token, base_token, quote_token);
+        Ok(base_addr.is_ok() && quote_addr.is_ok())
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Convert a pool's `sqrtPriceX96` into the price of the base token in units
+/// of the quote token, adjusting for the decimals of each token
+///
+/// The pool price is the amount of `token1` per unit of `token0`, in the
+/// tokens' smallest units. Returns `None` if the resulting price is not a
+/// finite, positive number.
+fn price_from_sqrt_price_x96(
+    sqrt_price_x96: U160,
+    token0_decimals: u8,
+    token1_decimals: u8,
+    base_is_token0: bool,
+) -> Option<Price> {
+    // Convert limb-wise to avoid losing precision on small prices
+    let limb_scale = 2f64.powi(LIMB_BITS);
+    let sqrt_price_x96 = sqrt_price_x96
+        .as_limbs()
+        .iter()
+        .rev()
+        .fold(0., |acc, limb| acc * limb_scale + *limb as f64);
+    let sqrt_price = sqrt_price_x96 / 2f64.powi(Q96_FRACTIONAL_BITS);
+
+    let decimal_adjustment = 10f64.powi(i32::from(token0_decimals) - i32::from(token1_decimals));
+    let token0_price = sqrt_price * sqrt_price * decimal_adjustment;
+    let price = if base_is_token0 { token0_price } else { 1. / token0_price };
+
+    (price.is_finite() && price > 0.).then_some(price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compute the `sqrtPriceX96` of a pool from its raw `token1` per `token0`
+    /// price
+    fn sqrt_price_x96(raw_price: f64) -> U160 {
+        let sqrt_price = raw_price.sqrt() * 2f64.powi(Q96_FRACTIONAL_BITS);
+        U160::from(sqrt_price as u128)
+    }
+
+    /// Assert that two prices are equal up to floating point error
+    fn assert_price_eq(actual: Option<Price>, expected: Price) {
+        let actual = actual.expect("expected a price");
+        assert!((actual - expected).abs() / expected < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn unit_price() {
+        let one = U160::from(1u8) << Q96_FRACTIONAL_BITS as usize;
+        assert_eq!(price_from_sqrt_price_x96(one, 18, 18, true), Some(1.));
+        assert_eq!(price_from_sqrt_price_x96(one, 18, 18, false), Some(1.));
+    }
+
+    /// A USDC (6 decimals) / WETH (18 decimals) pool, with USDC as `token0`
+    #[test]
+    fn base_is_token1() {
+        let eth_price = 3_000.;
+        let raw_price = 1e12 / eth_price;
+        let res = price_from_sqrt_price_x96(sqrt_price_x96(raw_price), 6, 18, false);
+
+        assert_price_eq(res, eth_price);
+    }
+
+    /// A WBTC (8 decimals) / USDC (6 decimals) pool, with WBTC as `token0`
+    #[test]
+    fn base_is_token0() {
+        let btc_price = 60_000.;
+        let raw_price = btc_price * 1e-2;
+        let res = price_from_sqrt_price_x96(sqrt_price_x96(raw_price), 8, 6, true);
+
+        assert_price_eq(res, btc_price);
+    }
+
+    /// A long-tail token priced far below its quote must retain precision
+    #[test]
+    fn small_price_retains_precision() {
+        let token_price = 1e-8;
+        let res = price_from_sqrt_price_x96(sqrt_price_x96(token_price), 18, 18, true);
+
+        assert_price_eq(res, token_price);
+    }
+
+    #[test]
+    fn zero_price_returns_none() {
+        assert_eq!(price_from_sqrt_price_x96(U160::ZERO, 18, 18, true), None);
+        assert_eq!(price_from_sqrt_price_x96(U160::ZERO, 18, 18, false), None);
+    }
+
+    /// The websocket address of the local anvil node used by the fork test
+    const ANVIL_WS_ADDR_ENV: &str = "ANVIL_WS_ADDR";
+    /// The default websocket address of a local anvil node
+    const DEFAULT_ANVIL_WS_ADDR: &str = "ws://127.0.0.1:8545";
+    /// WETH on Ethereum mainnet
+    const MAINNET_WETH: Address = Address::new(hex!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"));
+    /// USDC on Ethereum mainnet
+    const MAINNET_USDC: Address = Address::new(hex!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"));
+
+    /// Prices the WETH/USDC pool on a local anvil node forked from Ethereum
+    /// mainnet, e.g. one started with `anvil --fork-url <mainnet rpc>`
+    #[tokio::test]
+    #[ignore = "requires an anvil node forked from Ethereum mainnet"]
+    async fn prices_pool_on_anvil_fork() {
+        let ws_addr =
+            std::env::var(ANVIL_WS_ADDR_ENV).unwrap_or_else(|_| DEFAULT_ANVIL_WS_ADDR.to_string());
+        let config =
+            ExchangeConnectionsConfig { eth_websocket_addr: Some(ws_addr), ..Default::default() };
+        let provider = UniswapV3Connection::connect_provider(&config).await.unwrap();
+
+        // The factory has a WETH/USDC pool, but none for WETH against itself
+        let pool = UniswapV3Connection::find_pool(
+            &provider,
+            DEFAULT_UNISWAP_V3_FACTORY,
+            MAINNET_WETH,
+            MAINNET_USDC,
+        )
+        .await
+        .unwrap()
+        .expect("expected a WETH/USDC pool");
+        let missing = UniswapV3Connection::find_pool(
+            &provider,
+            DEFAULT_UNISWAP_V3_FACTORY,
+            MAINNET_WETH,
+            MAINNET_WETH,
+        )
+        .await
+        .unwrap();
+        assert_eq!(missing, None);
+
+        // The pool prices WETH in USDC within a sane range
+        let pool_info =
+            UniswapV3Connection::fetch_pool_info(&provider, pool, MAINNET_WETH, MAINNET_USDC)
+                .await
+                .unwrap();
+        assert_eq!((pool_info.token0_decimals, pool_info.token1_decimals), (6, 18));
+
+        let price = UniswapV3Connection::fetch_pool_price(&provider, &pool_info)
+            .await
+            .unwrap()
+            .expect("expected a pool price");
+        assert!((100. ..100_000.).contains(&price), "implausible WETH price {price}");
+    }
+}
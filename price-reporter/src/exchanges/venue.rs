@@ -0,0 +1,119 @@
+//! Venues served by the price reporter which have no upstream `Exchange`
+//!
+//! The `Exchange` enum and the token remap's exchange ticker mappings live in
+//! `renegade-types-core`, which has no variants for Bybit or Hyperliquid. A
+//! pair on one of these venues names the venue separately, and keeps the
+//! exchange whose ticker mappings the venue shares as its `Exchange`.
+
+use std::{fmt::Display, str::FromStr};
+
+use renegade_types_core::{Exchange, Token};
+
+// ---------
+// | Types |
+// ---------
+
+/// A venue without an upstream `Exchange` variant
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Venue {
+    /// Bybit spot
+    Bybit,
+    /// Hyperliquid perpetuals, priced at the mark price
+    Hyperliquid,
+}
+
+impl Venue {
+    /// The exchange whose ticker mappings the venue shares
+    ///
+    /// Bybit spot symbols follow Binance's, e.g. `ETHUSDT`, and Hyperliquid
+    /// coins follow Coinbase's base tickers, e.g. `ETH`.
+    pub fn ticker_exchange(&self) -> Exchange {
+        match self {
+            Venue::Bybit => Exchange::Binance,
+            Venue::Hyperliquid => Exchange::Coinbase,
+        }
+    }
+
+    /// Get the venue's ticker for the given token
+    ///
+    /// Falls back to the token's own ticker, so that tokens which are not
+    /// mapped for the ticker exchange, e.g. because they only list on the
+    /// venue, may still be priced.
+    pub fn ticker(&self, token: &Token) -> Option<String> {
+        token.get_exchange_ticker(self.ticker_exchange()).or_else(|| token.get_ticker())
+    }
+}
+
+impl Display for Venue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Venue::Bybit => "bybit",
+            Venue::Hyperliquid => "hyperliquid",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Venue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bybit" => Ok(Venue::Bybit),
+            "hyperliquid" => Ok(Venue::Hyperliquid),
+            _ => Err(format!("unknown venue `{s}`")),
+        }
+    }
+}
+
+/// The source of a price: an upstream exchange or a venue
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PriceSource {
+    /// An upstream exchange
+    Exchange(Exchange),
+    /// A venue without an upstream `Exchange` variant
+    Venue(Venue),
+}
+
+impl Display for PriceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PriceSource::Exchange(exchange) => write!(f, "{exchange}"),
+            PriceSource::Venue(venue) => write!(f, "{venue}"),
+        }
+    }
+}
+
+impl FromStr for PriceSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(venue) = Venue::from_str(s) {
+            return Ok(PriceSource::Venue(venue));
+        }
+
+        Exchange::from_str(s).map(PriceSource::Exchange).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_price_sources() {
+        assert_eq!("bybit".parse::<PriceSource>().unwrap(), PriceSource::Venue(Venue::Bybit));
+        assert_eq!(
+            "Hyperliquid".parse::<PriceSource>().unwrap(),
+            PriceSource::Venue(Venue::Hyperliquid)
+        );
+        assert_eq!(
+            "binance".parse::<PriceSource>().unwrap(),
+            PriceSource::Exchange(Exchange::Binance)
+        );
+        assert!("notavenue".parse::<PriceSource>().is_err());
+
+        // Venue names roundtrip through topics
+        assert_eq!(PriceSource::Venue(Venue::Hyperliquid).to_string(), "hyperliquid");
+    }
+}
@@ -0,0 +1,289 @@
+//! Fallback exchanges for canonical price streams
+//!
+//! A ticker may be configured with an ordered list of fallback exchanges.
+//! When the stream on the ticker's canonical exchange exhausts its retries,
+//! the stream switches to the next healthy fallback rather than closing, and
+//! switches back once the canonical exchange recovers.
+//!
+//! A stream keeps publishing in its own quote while failed over: a fallback
+//! quoted in a different stable has its prices converted back into the
+//! stream's quote through the fallback exchange's own market for that quote.
+//! The USDC conversion streams of `Renegade` topics fail over alongside the
+//! pairs they convert, using the fallbacks configured for USDC if any, and
+//! otherwise every configured fallback exchange.
+//!
+//! Fallbacks are configured as a comma-separated list of entries of the form
+//! `<TICKER>=<exchange>[:<exchange>...]`, e.g. `WETH=okx:kraken,WBTC=okx`.
+
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use itertools::Itertools;
+use renegade_types_core::{Exchange, USDC_TICKER};
+
+use crate::utils::{PairInfo, default_exchange_stable};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The interval at which the primary exchange is probed while a stream is
+/// failed over to a fallback exchange
+pub const FAILBACK_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+/// The delimiter between a ticker and its fallback exchanges
+const TICKER_DELIMITER: char = '=';
+/// The delimiter between fallback exchanges
+const EXCHANGE_DELIMITER: char = ':';
+
+// ---------
+// | Types |
+// ---------
+
+/// A map from a base ticker to its ordered list of fallback exchanges
+pub type FallbackExchanges = HashMap<String, Vec<Exchange>>;
+
+/// A single ticker's fallback configuration, as parsed from the CLI
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FallbackEntry {
+    /// The base ticker
+    pub ticker: String,
+    /// The fallback exchanges, in order of preference
+    pub exchanges: Vec<Exchange>,
+}
+
+impl FromStr for FallbackEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ticker, exchanges) = s
+            .split_once(TICKER_DELIMITER)
+            .ok_or_else(|| format!("expected `<TICKER>=<exchange>[:<exchange>...]`, got `{s}`"))?;
+
+        let ticker = ticker.trim();
+        if ticker.is_empty() {
+            return Err(format!("missing ticker in `{s}`"));
+        }
+
+        let exchanges = exchanges
+            .split(EXCHANGE_DELIMITER)
+            .map(|exchange| {
+                Exchange::from_str(exchange.trim())
+                    .map_err(|_| format!("invalid exchange `{exchange}` in `{s}`"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { ticker: ticker.to_uppercase(), exchanges })
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Build the fallback map from the parsed entries, dropping disabled
+/// exchanges
+///
+/// Later entries for the same ticker replace earlier ones.
+pub fn build_fallback_exchanges(
+    entries: &[FallbackEntry],
+    disabled_exchanges: &[Exchange],
+) -> FallbackExchanges {
+    entries
+        .iter()
+        .map(|entry| {
+            let exchanges = entry
+                .exchanges
+                .iter()
+                .copied()
+                .filter(|exchange| !disabled_exchanges.contains(exchange))
+                .unique()
+                .collect_vec();
+            (entry.ticker.clone(), exchanges)
+        })
+        .collect()
+}
+
+/// Get the pairs to which the given pair's stream may fail over, in order of
+/// preference
+///
+/// A pair quoted in its exchange's default stable is quoted in each fallback
+/// exchange's default stable instead, e.g. a Binance USDT pair fails over to
+/// Coinbase's USD pair, and is converted back into the stream's quote with
+/// `quote_conversion_pair`. Other quotes are kept as is.
+pub fn fallback_pairs(pair_info: &PairInfo, fallbacks: &FallbackExchanges) -> Vec<PairInfo> {
+    fallback_pairs_with_stables(pair_info, fallbacks, |exchange| {
+        default_exchange_stable(exchange, pair_info.chain).get_ticker()
+    })
+}
+
+/// Get the fallback pairs of the given pair, resolving each exchange's default
+/// stable ticker with the given function
+fn fallback_pairs_with_stables(
+    pair_info: &PairInfo,
+    fallbacks: &FallbackExchanges,
+    stable_ticker: impl Fn(&Exchange) -> Option<String>,
+) -> Vec<PairInfo> {
+    let quoted_in_stable =
+        stable_ticker(&pair_info.exchange).is_some_and(|stable| stable == pair_info.quote);
+
+    // A USDC conversion stream is shared by every pair on its exchange, so it
+    // may fail over to any configured fallback unless USDC has its own
+    let is_conversion_pair = quoted_in_stable && pair_info.base == USDC_TICKER;
+    let exchanges = match fallbacks.get(&pair_info.base) {
+        Some(exchanges) => exchanges.clone(),
+        None if is_conversion_pair => all_fallback_exchanges(fallbacks),
+        None => return Vec::new(),
+    };
+
+    exchanges
+        .iter()
+        .filter(|exchange| **exchange != pair_info.exchange)
+        .map(|exchange| {
+            let quote = match stable_ticker(exchange) {
+                Some(stable) if quoted_in_stable => stable,
+                _ => pair_info.quote.clone(),
+            };
+            PairInfo { exchange: *exchange, quote, ..pair_info.clone() }
+        })
+        .collect_vec()
+}
+
+/// Every configured fallback exchange, in order of the tickers they are
+/// configured for
+fn all_fallback_exchanges(fallbacks: &FallbackExchanges) -> Vec<Exchange> {
+    fallbacks
+        .iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .flat_map(|(_, exchanges)| exchanges.iter().copied())
+        .unique()
+        .collect_vec()
+}
+
+/// Get the pair pricing the given quote on the source's exchange, in the
+/// source's quote, if the source is quoted in a different unit
+///
+/// A stream failed over to the source divides the source's prices by this
+/// pair's price to keep publishing in its own quote.
+pub fn quote_conversion_pair(source: &PairInfo, quote: &str) -> Option<PairInfo> {
+    (source.quote != quote).then(|| PairInfo {
+        base: quote.to_string(),
+        quote: source.quote.clone(),
+        ..source.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fallback_entry() {
+        let entry: FallbackEntry = "weth=okx:kraken".parse().unwrap();
+        assert_eq!(entry.ticker, "WETH");
+        assert_eq!(entry.exchanges, vec![Exchange::Okx, Exchange::Kraken]);
+
+        assert!("WETH".parse::<FallbackEntry>().is_err());
+        assert!("=okx".parse::<FallbackEntry>().is_err());
+        assert!("WETH=okx:notanexchange".parse::<FallbackEntry>().is_err());
+    }
+
+    #[test]
+    fn disabled_and_duplicate_exchanges_are_dropped() {
+        let entries = [FallbackEntry {
+            ticker: "WETH".to_string(),
+            exchanges: vec![Exchange::Okx, Exchange::UniswapV3, Exchange::Okx, Exchange::Kraken],
+        }];
+        let fallbacks = build_fallback_exchanges(&entries, &[Exchange::UniswapV3]);
+
+        assert_eq!(fallbacks["WETH"], vec![Exchange::Okx, Exchange::Kraken]);
+    }
+
+    /// The default stable tickers used in tests, independent of the token
+    /// remap
+    fn stable_ticker(exchange: &Exchange) -> Option<String> {
+        match exchange {
+            Exchange::Binance | Exchange::Okx => Some("USDT".to_string()),
+            Exchange::Coinbase | Exchange::Kraken => Some("USDC".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn fallback_pairs_map_stable_quote_and_skip_primary() {
+        let fallbacks = FallbackExchanges::from([(
+            "WETH".to_string(),
+            vec![Exchange::Binance, Exchange::Okx, Exchange::Coinbase],
+        )]);
+        let primary =
+            PairInfo::new(Exchange::Binance, "WETH".to_string(), "USDT".to_string(), None);
+
+        let pairs = fallback_pairs_with_stables(&primary, &fallbacks, stable_ticker);
+        let quotes = pairs.iter().map(|pair| (pair.exchange, pair.quote.as_str())).collect_vec();
+        assert_eq!(quotes, vec![(Exchange::Okx, "USDT"), (Exchange::Coinbase, "USDC")]);
+        assert!(pairs.iter().all(|pair| pair.base == "WETH"));
+
+        let other = PairInfo::new(Exchange::Binance, "WBTC".to_string(), "USDT".to_string(), None);
+        assert!(fallback_pairs_with_stables(&other, &fallbacks, stable_ticker).is_empty());
+    }
+
+    /// A `Renegade` topic on a USDT-canonical token reads both the canonical
+    /// pair and its USDC conversion pair from Binance. Both must survive
+    /// Binance going down, and keep publishing in USDT.
+    #[test]
+    fn usdt_canonical_topic_survives_binance_outage() {
+        let fallbacks = FallbackExchanges::from([("WETH".to_string(), vec![Exchange::Coinbase])]);
+        let primary =
+            PairInfo::new(Exchange::Binance, "WETH".to_string(), "USDT".to_string(), None);
+        let conversion =
+            PairInfo::new(Exchange::Binance, "USDC".to_string(), "USDT".to_string(), None);
+
+        // The canonical pair fails over to Coinbase's USD pair, converted back
+        // into USDT through Coinbase's USDT market
+        let pairs = fallback_pairs_with_stables(&primary, &fallbacks, stable_ticker);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].exchange, pairs[0].quote.as_str()), (Exchange::Coinbase, "USDC"));
+        let to_usdt = quote_conversion_pair(&pairs[0], &primary.quote).unwrap();
+        assert_eq!(to_usdt.exchange, Exchange::Coinbase);
+        assert_eq!((to_usdt.base.as_str(), to_usdt.quote.as_str()), ("USDT", "USDC"));
+
+        // The conversion pair has no fallbacks of its own, so it inherits the
+        // configured fallbacks and becomes a unit pair converted into USDT
+        let pairs = fallback_pairs_with_stables(&conversion, &fallbacks, stable_ticker);
+        assert_eq!(pairs.len(), 1);
+        assert!(pairs[0].is_unit_pair());
+        let to_usdt = quote_conversion_pair(&pairs[0], &conversion.quote).unwrap();
+        assert_eq!((to_usdt.base.as_str(), to_usdt.quote.as_str()), ("USDT", "USDC"));
+
+        // A fallback in the stream's own quote needs no conversion
+        let okx = PairInfo { exchange: Exchange::Okx, ..primary.clone() };
+        assert!(quote_conversion_pair(&okx, &primary.quote).is_none());
+    }
+
+    #[test]
+    fn conversion_pair_prefers_usdc_fallbacks() {
+        let fallbacks = FallbackExchanges::from([
+            ("WETH".to_string(), vec![Exchange::Coinbase, Exchange::Kraken]),
+            ("WBTC".to_string(), vec![Exchange::Okx, Exchange::Coinbase]),
+        ]);
+        let conversion =
+            PairInfo::new(Exchange::Binance, "USDC".to_string(), "USDT".to_string(), None);
+
+        let pairs = fallback_pairs_with_stables(&conversion, &fallbacks, stable_ticker);
+        let exchanges = pairs.iter().map(|pair| pair.exchange).collect_vec();
+        assert_eq!(exchanges, vec![Exchange::Okx, Exchange::Coinbase, Exchange::Kraken]);
+
+        let mut fallbacks = fallbacks;
+        fallbacks.insert("USDC".to_string(), vec![Exchange::Kraken]);
+        let pairs = fallback_pairs_with_stables(&conversion, &fallbacks, stable_ticker);
+        assert_eq!(pairs.iter().map(|pair| pair.exchange).collect_vec(), vec![Exchange::Kraken]);
+    }
+
+    #[test]
+    fn fallback_pairs_keep_non_stable_quote() {
+        let fallbacks = FallbackExchanges::from([("WETH".to_string(), vec![Exchange::Coinbase])]);
+        let primary =
+            PairInfo::new(Exchange::Binance, "WETH".to_string(), "WBTC".to_string(), None);
+
+        let pairs = fallback_pairs_with_stables(&primary, &fallbacks, stable_ticker);
+        assert_eq!(pairs[0].quote, "WBTC");
+    }
+}
@@ -12,10 +12,14 @@ use hyper::{
 use hyper_util::rt::{TokioIo, TokioTimer};
 use matchit::Router;
 use renegade_util::err_str;
-use routes::{REFRESH_TOKEN_MAPPING_ROUTE, RefreshTokenMappingHandler};
+use routes::{
+    REFRESH_TOKEN_MAPPING_ROUTE, RefreshTokenMappingHandler, TOKEN_MAPPING_STATUS_ROUTE,
+    TokenMappingStatusHandler,
+};
 use tokio::net::{TcpListener, TcpStream};
 
 use crate::{
+    api_keys::{API_KEY_HEADER, ApiKeys},
     log_task,
     logger::{Outcome, Task},
 };
@@ -34,10 +38,14 @@ const IDLE_TIMEOUT_ERR_SUBSTR: &str = "read header from client timeout";
 use crate::{
     errors::ServerError,
     price_stream_manager::GlobalPriceStreams,
+    token_remap::TokenRemapReloader,
     utils::{HttpRouter, PriceReporterConfig},
 };
 
-use self::routes::{HEALTH_CHECK_ROUTE, Handler, HealthCheckHandler, PRICE_ROUTE, PriceHandler};
+use self::routes::{
+    DEPTH_ROUTE, DepthHandler, HEALTH_CHECK_ROUTE, Handler, HealthCheckHandler, OHLC_ROUTE,
+    OhlcHandler, PRICE_AT_ROUTE, PRICE_ROUTE, PriceAtHandler, PriceHandler,
+};
 
 pub mod routes;
 
@@ -56,17 +64,27 @@ pub struct HttpServer {
     port: u16,
     /// The router for the HTTP server, used to match routes
     router: Arc<HttpRouter>,
+    /// The API keys accepted on authenticated routes, if any
+    api_keys: Option<ApiKeys>,
 }
 
 impl HttpServer {
     /// Create a new HTTP server with the given port and global price streams
-    pub fn new(config: &PriceReporterConfig, price_streams: GlobalPriceStreams) -> Self {
-        let router = Self::build_router(config, price_streams);
-        Self { port: config.http_port, router: Arc::new(router) }
+    pub fn new(
+        config: &PriceReporterConfig,
+        price_streams: GlobalPriceStreams,
+        reloader: TokenRemapReloader,
+    ) -> Self {
+        let router = Self::build_router(config, price_streams, reloader);
+        Self { port: config.http_port, router: Arc::new(router), api_keys: config.api_keys.clone() }
     }
 
     /// Build the router for the HTTP server
-    fn build_router(config: &PriceReporterConfig, price_streams: GlobalPriceStreams) -> HttpRouter {
+    fn build_router(
+        config: &PriceReporterConfig,
+        price_streams: GlobalPriceStreams,
+        reloader: TokenRemapReloader,
+    ) -> HttpRouter {
         let mut router: Router<Box<dyn Handler>> = Router::new();
 
         router.insert(HEALTH_CHECK_ROUTE, Box::new(HealthCheckHandler::new())).unwrap();
@@ -83,18 +101,32 @@ impl HttpServer {
 
         router
             .insert(
-                REFRESH_TOKEN_MAPPING_ROUTE,
-                Box::new(RefreshTokenMappingHandler::new(
-                    config.admin_key,
-                    config.token_remap_path.clone(),
-                    config.chains.clone(),
-                    price_streams,
+                DEPTH_ROUTE,
+                Box::new(DepthHandler::new(
                     config.exchange_conn_config.clone(),
-                    config.disabled_exchanges.clone(),
+                    price_streams.clone(),
                 )),
             )
             .unwrap();
 
+        router
+            .insert(PRICE_AT_ROUTE, Box::new(PriceAtHandler::new(price_streams.clone())))
+            .unwrap();
+        router.insert(OHLC_ROUTE, Box::new(OhlcHandler::new(price_streams))).unwrap();
+
+        router
+            .insert(
+                REFRESH_TOKEN_MAPPING_ROUTE,
+                Box::new(RefreshTokenMappingHandler::new(config.admin_key, reloader.clone())),
+            )
+            .unwrap();
+        router
+            .insert(
+                TOKEN_MAPPING_STATUS_ROUTE,
+                Box::new(TokenMappingStatusHandler::new(config.admin_key, reloader)),
+            )
+            .unwrap();
+
         router
     }
 
@@ -102,6 +134,11 @@ impl HttpServer {
     async fn serve_request(&self, req: Request<IncomingBody>) -> Response<ResponseBody> {
         if let Ok(matched_path) = self.router.at(req.uri().path()) {
             let handler = matched_path.value;
+            let auth = if handler.requires_api_key() { self.authorize(&req) } else { Ok(()) };
+            if let Err(e) = auth {
+                return Self::auth_error_response(&e);
+            }
+
             let url_params =
                 matched_path.params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
             handler.as_ref().handle(req, url_params).await
@@ -110,6 +147,31 @@ impl HttpServer {
         }
     }
 
+    /// Authorize a request against the configured API keys, consuming from
+    /// the client's rate limit
+    fn authorize(&self, req: &Request<IncomingBody>) -> Result<(), ServerError> {
+        let Some(api_keys) = &self.api_keys else {
+            return Ok(());
+        };
+
+        let key = req.headers().get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+        api_keys.authenticate(key)?.check_rate_limit()
+    }
+
+    /// Build the response for a request which failed authorization
+    fn auth_error_response(err: &ServerError) -> Response<ResponseBody> {
+        let status = match err {
+            ServerError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::UNAUTHORIZED,
+        };
+
+        Response::builder()
+            .status(status)
+            .header("Access-Control-Allow-Origin", "*")
+            .body(resp_body(err.to_string()))
+            .unwrap()
+    }
+
     /// The execution loop for the http server, accepts incoming connections,
     /// serves them, and awaits the next connection
     pub async fn execution_loop(self) -> Result<(), ServerError> {
@@ -1,22 +1,27 @@
 //! The routes for the HTTP server
 
+use std::str::FromStr;
+
 use async_trait::async_trait;
 use http_body_util::{BodyExt, Full};
 use hyper::{
-    HeaderMap, Request, Response, StatusCode,
+    Request, Response, StatusCode,
     body::{Bytes as BytesBody, Incoming as IncomingBody},
 };
 use renegade_api::auth::validate_expiring_auth;
-use renegade_types_core::{Chain, Exchange, HmacKey, Price};
-use renegade_util::err_str;
+use renegade_types_core::HmacKey;
+use renegade_util::{err_str, get_current_time_millis};
+use serde::Serialize;
+use url::form_urlencoded;
 
 use crate::{
     errors::ServerError,
     exchanges::ExchangeConnectionsConfig,
     http_server::{ResponseBody, resp_body},
-    init_default_price_streams,
+    price_history::{Candle, PriceTick},
     price_stream_manager::GlobalPriceStreams,
-    utils::{PairInfo, UrlParams, setup_all_token_remaps},
+    token_remap::TokenRemapReloader,
+    utils::{PairInfo, PriceMessage, PriceUpdate, UrlParams},
 };
 
 /// A handler is attached to a route and handles the process of translating an
@@ -29,6 +34,31 @@ pub trait Handler: Send + Sync {
         req: Request<IncomingBody>,
         url_params: UrlParams,
     ) -> Response<ResponseBody>;
+
+    /// Whether requests on the handler's route require an API key, when API
+    /// keys are configured
+    fn requires_api_key(&self) -> bool {
+        true
+    }
+}
+
+/// Build the response for an error serving a price or depth read
+///
+/// Requests for unknown or unsupported pairs are the client's fault, and are
+/// answered with a 400 so that clients do not fail over on them. Every other
+/// error is answered with a 500.
+fn error_response(e: &ServerError) -> Response<ResponseBody> {
+    let status = match e {
+        ServerError::InvalidPairInfo(_) | ServerError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    Response::builder()
+        .status(status)
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Content-Type", "text/plain")
+        .body(resp_body(e.to_string()))
+        .unwrap()
 }
 
 // ----------------------
@@ -54,6 +84,10 @@ impl Handler for HealthCheckHandler {
         let body = Full::new(BytesBody::from("OK"));
         Response::builder().status(StatusCode::OK).body(body).unwrap()
     }
+
+    fn requires_api_key(&self) -> bool {
+        false
+    }
 }
 
 // ---------------
@@ -61,8 +95,15 @@ impl Handler for HealthCheckHandler {
 // ---------------
 
 /// The route for the price endpoint
+///
+/// Returns the bare price as plain text by default. With `?format=json`, the
+/// price is returned as a JSON `PriceMessage` carrying its source, timestamps,
+/// and age.
 pub const PRICE_ROUTE: &str = "/price/:topic";
 
+/// The `format` query parameter value selecting a JSON price response
+const JSON_FORMAT: &str = "json";
+
 /// The handler for the price endpoint
 #[derive(Clone)]
 pub struct PriceHandler {
@@ -81,9 +122,27 @@ impl PriceHandler {
 
     /// Get the current price for the given topic by borrowing from the watch
     /// channel directly, returning the latest known price immediately.
-    pub async fn get_price(&self, topic: &str) -> Result<Price, ServerError> {
-        let pair_info = PairInfo::from_topic(topic)?;
-        self.price_streams.get_current_price(pair_info, self.config.clone()).await
+    ///
+    /// Composite topics are served from the composite stream for the pair.
+    pub async fn get_price(&self, topic: &str) -> Result<PriceUpdate, ServerError> {
+        self.price_streams.get_current_topic_price(topic, self.config.clone()).await
+    }
+
+    /// Build the response body for the given price update in the requested
+    /// format, returning the body and its content type
+    fn price_body(
+        req: &Request<IncomingBody>,
+        topic: &str,
+        update: &PriceUpdate,
+    ) -> Result<(&'static str, String), ServerError> {
+        let format: Option<String> = parse_query_param(req, "format")?;
+        if format.as_deref() != Some(JSON_FORMAT) {
+            return Ok(("text/plain", update.price.to_string()));
+        }
+
+        let message = PriceMessage::new(topic.to_string(), update);
+        let body = serde_json::to_string(&message).map_err(err_str!(ServerError::Serde))?;
+        Ok(("application/json", body))
     }
 }
 
@@ -91,28 +150,219 @@ impl PriceHandler {
 impl Handler for PriceHandler {
     async fn handle(
         &self,
-        _: Request<IncomingBody>,
+        req: Request<IncomingBody>,
         url_params: UrlParams,
     ) -> Response<ResponseBody> {
         let topic = url_params.get("topic").unwrap();
+        let res =
+            self.get_price(topic).await.and_then(|update| Self::price_body(&req, topic, &update));
 
-        match self.get_price(topic).await {
-            Ok(price) => Response::builder()
+        match res {
+            Ok((content_type, body)) => Response::builder()
                 .status(StatusCode::OK)
                 .header("Access-Control-Allow-Origin", "*")
-                .header("Content-Type", "text/plain")
-                .body(resp_body(price.to_string()))
+                .header("Content-Type", content_type)
+                .body(resp_body(body))
                 .unwrap(),
-            Err(e) => Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(e) => error_response(&e),
+        }
+    }
+}
+
+// ---------------
+// | DEPTH ROUTE |
+// ---------------
+
+/// The route for the order book depth endpoint
+pub const DEPTH_ROUTE: &str = "/depth/:topic";
+
+/// The handler for the order book depth endpoint
+#[derive(Clone)]
+pub struct DepthHandler {
+    /// The configuration for the exchange connections, used to potentially
+    /// instantiate new depth streams
+    config: ExchangeConnectionsConfig,
+    /// The global map of price streams, from which to read the depth
+    price_streams: GlobalPriceStreams,
+}
+
+impl DepthHandler {
+    /// Create a new depth handler with the given global price streams
+    pub fn new(config: ExchangeConnectionsConfig, price_streams: GlobalPriceStreams) -> Self {
+        Self { config, price_streams }
+    }
+
+    /// Get the current depth snapshot for the given `exchange-base-quote`
+    /// topic, serialized as JSON
+    pub async fn get_depth(&self, topic: &str) -> Result<String, ServerError> {
+        let pair_info = PairInfo::from_topic(topic)?;
+        let depth = self.price_streams.get_current_depth(pair_info, self.config.clone()).await?;
+        serde_json::to_string(&depth).map_err(err_str!(ServerError::Serde))
+    }
+}
+
+#[async_trait]
+impl Handler for DepthHandler {
+    async fn handle(
+        &self,
+        _: Request<IncomingBody>,
+        url_params: UrlParams,
+    ) -> Response<ResponseBody> {
+        let topic = url_params.get("topic").unwrap();
+
+        match self.get_depth(topic).await {
+            Ok(depth) => Response::builder()
+                .status(StatusCode::OK)
                 .header("Access-Control-Allow-Origin", "*")
-                .header("Content-Type", "text/plain")
-                .body(resp_body(e.to_string()))
+                .header("Content-Type", "application/json")
+                .body(resp_body(depth))
                 .unwrap(),
+            Err(e) => error_response(&e),
         }
     }
 }
 
+// ------------------------
+// | PRICE HISTORY ROUTES |
+// ------------------------
+
+/// The route for the historical price endpoint
+///
+/// Takes a `timestamp` query parameter, in milliseconds since the epoch
+pub const PRICE_AT_ROUTE: &str = "/price-history/:topic";
+
+/// The route for the OHLC candles endpoint
+///
+/// Takes `start`, `end`, and `interval` query parameters, in milliseconds.
+/// `end` defaults to the current time, and `interval` to one minute.
+pub const OHLC_ROUTE: &str = "/ohlc/:topic";
+
+/// The default interval of OHLC candles, in milliseconds
+const DEFAULT_CANDLE_INTERVAL_MS: u64 = 60_000; // 1 minute
+
+/// Parse a query parameter from the request, returning `None` if it is absent
+fn parse_query_param<T: FromStr>(
+    req: &Request<IncomingBody>,
+    name: &str,
+) -> Result<Option<T>, ServerError> {
+    let query = req.uri().query().unwrap_or_default();
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| {
+            value.parse().map_err(|_| {
+                ServerError::InvalidRequest(format!("invalid `{name}` parameter: {value}"))
+            })
+        })
+        .transpose()
+}
+
+/// Build a response for a price history query
+fn price_history_response<T: Serialize>(
+    res: Result<Option<T>, ServerError>,
+) -> Response<ResponseBody> {
+    let (status, content_type, body) = match res {
+        Ok(Some(res)) => match serde_json::to_string(&res) {
+            Ok(body) => (StatusCode::OK, "application/json", body),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, "text/plain", e.to_string()),
+        },
+        Ok(None) => (StatusCode::NOT_FOUND, "text/plain", "No price recorded".to_string()),
+        Err(e @ ServerError::PriceHistoryDisabled) => {
+            (StatusCode::NOT_FOUND, "text/plain", e.to_string())
+        },
+        Err(e @ ServerError::InvalidRequest(_)) => {
+            (StatusCode::BAD_REQUEST, "text/plain", e.to_string())
+        },
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, "text/plain", e.to_string()),
+    };
+
+    Response::builder()
+        .status(status)
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Content-Type", content_type)
+        .body(resp_body(body))
+        .unwrap()
+}
+
+/// The handler for the historical price endpoint
+#[derive(Clone)]
+pub struct PriceAtHandler {
+    /// The global map of price streams, from which to read the price history
+    price_streams: GlobalPriceStreams,
+}
+
+impl PriceAtHandler {
+    /// Create a new historical price handler with the given global price
+    /// streams
+    pub fn new(price_streams: GlobalPriceStreams) -> Self {
+        Self { price_streams }
+    }
+
+    /// Get the price in effect on the given topic at the requested time
+    async fn get_price_at(
+        &self,
+        req: &Request<IncomingBody>,
+        topic: &str,
+    ) -> Result<Option<PriceTick>, ServerError> {
+        let timestamp = parse_query_param(req, "timestamp")?.ok_or_else(|| {
+            ServerError::InvalidRequest("missing `timestamp` parameter".to_string())
+        })?;
+
+        self.price_streams.get_price_at(topic, timestamp).await
+    }
+}
+
+#[async_trait]
+impl Handler for PriceAtHandler {
+    async fn handle(
+        &self,
+        req: Request<IncomingBody>,
+        url_params: UrlParams,
+    ) -> Response<ResponseBody> {
+        let topic = url_params.get("topic").unwrap();
+        price_history_response(self.get_price_at(&req, topic).await)
+    }
+}
+
+/// The handler for the OHLC candles endpoint
+#[derive(Clone)]
+pub struct OhlcHandler {
+    /// The global map of price streams, from which to read the price history
+    price_streams: GlobalPriceStreams,
+}
+
+impl OhlcHandler {
+    /// Create a new OHLC handler with the given global price streams
+    pub fn new(price_streams: GlobalPriceStreams) -> Self {
+        Self { price_streams }
+    }
+
+    /// Get the candles on the given topic over the requested window
+    async fn get_candles(
+        &self,
+        req: &Request<IncomingBody>,
+        topic: &str,
+    ) -> Result<Vec<Candle>, ServerError> {
+        let start = parse_query_param(req, "start")?
+            .ok_or_else(|| ServerError::InvalidRequest("missing `start` parameter".to_string()))?;
+        let end = parse_query_param(req, "end")?.unwrap_or_else(get_current_time_millis);
+        let interval = parse_query_param(req, "interval")?.unwrap_or(DEFAULT_CANDLE_INTERVAL_MS);
+
+        self.price_streams.get_candles(topic, start, end, interval).await
+    }
+}
+
+#[async_trait]
+impl Handler for OhlcHandler {
+    async fn handle(
+        &self,
+        req: Request<IncomingBody>,
+        url_params: UrlParams,
+    ) -> Response<ResponseBody> {
+        let topic = url_params.get("topic").unwrap();
+        price_history_response(self.get_candles(&req, topic).await.map(Some))
+    }
+}
+
 // -------------------------------
 // | REFRESH TOKEN MAPPING ROUTE |
 // -------------------------------
@@ -125,85 +375,30 @@ pub const REFRESH_TOKEN_MAPPING_ROUTE: &str = "/refresh-token-mapping";
 pub struct RefreshTokenMappingHandler {
     /// The HMAC key for the admin API
     admin_key: Option<HmacKey>,
-    /// The path to the token remap file
-    token_remap_path: Option<String>,
-    /// The chains to use for the token remap
-    chains: Vec<Chain>,
-    /// The global price streams
-    price_streams: GlobalPriceStreams,
-    /// The configuration for the exchange connections
-    config: ExchangeConnectionsConfig,
-    /// The exchanges for which to disable price reporting
-    disabled_exchanges: Vec<Exchange>,
+    /// The reloader applying the refreshed token mapping
+    reloader: TokenRemapReloader,
 }
 
 impl RefreshTokenMappingHandler {
     /// Create a new token mapping refresh handler
-    pub fn new(
-        admin_key: Option<HmacKey>,
-        token_remap_path: Option<String>,
-        chains: Vec<Chain>,
-        price_streams: GlobalPriceStreams,
-        config: ExchangeConnectionsConfig,
-        disabled_exchanges: Vec<Exchange>,
-    ) -> Self {
-        Self { admin_key, token_remap_path, chains, price_streams, config, disabled_exchanges }
-    }
-
-    /// Authenticate a token mapping refresh request using the admin HMAC key.
-    fn authenticate_request(
-        &self,
-        path: &str,
-        headers: &HeaderMap,
-        body: &[u8],
-    ) -> Result<(), ServerError> {
-        if self.admin_key.is_none() {
-            return Err(ServerError::NoAdminKey);
-        }
-
-        validate_expiring_auth(path, headers, body, &self.admin_key.unwrap())
-            .map_err(err_str!(ServerError::Unauthorized))
+    pub fn new(admin_key: Option<HmacKey>, reloader: TokenRemapReloader) -> Self {
+        Self { admin_key, reloader }
     }
 
     /// Refresh the token mapping from the remote source
+    ///
+    /// The default price streams are re-initialized even if the mapping is
+    /// unchanged, restarting any streams which have exited.
     pub async fn refresh_token_mapping(&self) -> Result<(), ServerError> {
-        let token_remap_path = self.token_remap_path.clone();
-        let chains = self.chains.clone();
-        tokio::task::spawn_blocking(move || setup_all_token_remaps(token_remap_path, &chains))
-            .await
-            .map_err(err_str!(ServerError::TokenRemap))
-            .and_then(|res| res.map_err(err_str!(ServerError::TokenRemap)))?;
-
-        // Re-initialize the default price streams after refreshing the token mapping
-        init_default_price_streams(
-            &self.price_streams,
-            &self.config,
-            self.disabled_exchanges.clone(),
-        )
-        .await
+        self.reloader.reload(true /* force */).await.map(|_status| ())
     }
 }
 
 #[async_trait]
 impl Handler for RefreshTokenMappingHandler {
     async fn handle(&self, req: Request<IncomingBody>, _: UrlParams) -> Response<ResponseBody> {
-        if self.admin_key.is_none() {
-            return Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(resp_body("Admin API disabled"))
-                .unwrap();
-        }
-
-        // Destructure the request into its parts
-        let path = req.uri().path().to_string();
-        let headers = req.headers().clone();
-        let req_body = req.into_body().collect().await.unwrap_or_default();
-        let body_bytes = req_body.to_bytes().to_vec();
-        if let Err(e) = self.authenticate_request(&path, &headers, &body_bytes) {
-            return Response::builder()
-                .status(StatusCode::UNAUTHORIZED)
-                .body(resp_body(e.to_string()))
-                .unwrap();
+        if let Err(resp) = authenticate_admin_request(self.admin_key.as_ref(), req).await {
+            return resp;
         }
 
         match self.refresh_token_mapping().await {
@@ -217,4 +412,106 @@ impl Handler for RefreshTokenMappingHandler {
                 .unwrap(),
         }
     }
+
+    // The admin route is authenticated with the admin key instead
+    fn requires_api_key(&self) -> bool {
+        false
+    }
+}
+
+// ------------------------------
+// | TOKEN MAPPING STATUS ROUTE |
+// ------------------------------
+
+/// The route for the token mapping status endpoint
+///
+/// Returns the version of the token mapping and its last applied diff as JSON
+pub const TOKEN_MAPPING_STATUS_ROUTE: &str = "/token-mapping-status";
+
+/// The handler for the token mapping status endpoint
+#[derive(Clone)]
+pub struct TokenMappingStatusHandler {
+    /// The HMAC key for the admin API
+    admin_key: Option<HmacKey>,
+    /// The reloader tracking the applied token mapping
+    reloader: TokenRemapReloader,
+}
+
+impl TokenMappingStatusHandler {
+    /// Create a new token mapping status handler
+    pub fn new(admin_key: Option<HmacKey>, reloader: TokenRemapReloader) -> Self {
+        Self { admin_key, reloader }
+    }
+}
+
+#[async_trait]
+impl Handler for TokenMappingStatusHandler {
+    async fn handle(&self, req: Request<IncomingBody>, _: UrlParams) -> Response<ResponseBody> {
+        if let Err(resp) = authenticate_admin_request(self.admin_key.as_ref(), req).await {
+            return resp;
+        }
+
+        let status = self.reloader.status().await;
+        match serde_json::to_string(&status) {
+            Ok(body) => Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "application/json")
+                .body(resp_body(body))
+                .unwrap(),
+            Err(e) => Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(resp_body(e.to_string()))
+                .unwrap(),
+        }
+    }
+
+    // The admin route is authenticated with the admin key instead
+    fn requires_api_key(&self) -> bool {
+        false
+    }
+}
+
+/// Authenticate an admin request using the admin HMAC key, returning the
+/// error response if the admin API is disabled or authentication fails
+async fn authenticate_admin_request(
+    admin_key: Option<&HmacKey>,
+    req: Request<IncomingBody>,
+) -> Result<(), Response<ResponseBody>> {
+    let Some(admin_key) = admin_key else {
+        return Err(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(resp_body("Admin API disabled"))
+            .unwrap());
+    };
+
+    // Destructure the request into its parts
+    let path = req.uri().path().to_string();
+    let headers = req.headers().clone();
+    let req_body = req.into_body().collect().await.unwrap_or_default();
+    let body_bytes = req_body.to_bytes().to_vec();
+    validate_expiring_auth(&path, &headers, &body_bytes, admin_key).map_err(|e| {
+        Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(resp_body(ServerError::Unauthorized(e.to_string()).to_string()))
+            .unwrap()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_pairs_are_client_errors() {
+        let invalid_pair = ServerError::InvalidPairInfo("binance:UNKNOWN:USDT".to_string());
+        assert_eq!(error_response(&invalid_pair).status(), StatusCode::BAD_REQUEST);
+
+        let invalid_request = ServerError::InvalidRequest("invalid `format` parameter".to_string());
+        assert_eq!(error_response(&invalid_request).status(), StatusCode::BAD_REQUEST);
+
+        // A stream that is unavailable is the endpoint's fault, so clients fail
+        // over on it
+        let closed = error_response(&ServerError::PriceStreamClosed);
+        assert!(closed.status().is_server_error());
+    }
 }
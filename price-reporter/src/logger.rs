@@ -52,6 +52,13 @@ pub enum Task {
     /// External WebSocket server: accepting client connections and
     /// handling client subscribe / unsubscribe messages.
     WsServer,
+    /// Historical price recording, including writes to the on-disk sink.
+    PriceHistory,
+    /// Switching a price stream between its primary exchange and fallback
+    /// exchanges, including probes of the primary while failed over.
+    Failover,
+    /// Reloading the token remap and applying its diff to the price streams.
+    TokenRemap,
 }
 
 impl Task {
@@ -66,6 +73,9 @@ impl Task {
             Task::Heartbeat => "heartbeat",
             Task::HttpServer => "http-server",
             Task::WsServer => "ws-server",
+            Task::PriceHistory => "price-history",
+            Task::Failover => "failover",
+            Task::TokenRemap => "token-remap",
         }
     }
 }
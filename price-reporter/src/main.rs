@@ -23,15 +23,22 @@ use crate::{
     cli::Cli,
     exchanges::ExchangeConnectionsConfig,
     logger::{Outcome, Task},
+    price_history::PriceHistory,
     price_stream_manager::GlobalPriceStreams,
+    token_remap::TokenRemapReloader,
 };
 
+mod api_keys;
 mod cli;
+mod composite;
 mod errors;
 mod exchanges;
+mod failover;
 mod http_server;
 mod logger;
+mod price_history;
 mod price_stream_manager;
+mod token_remap;
 mod utils;
 mod ws_server;
 
@@ -55,7 +62,13 @@ async fn main() -> Result<(), ServerError> {
     .unwrap()?;
 
     let (closure_tx, mut closure_rx) = unbounded_channel();
-    let global_price_streams = GlobalPriceStreams::new(closure_tx);
+    let price_history =
+        price_reporter_config.price_history_config.as_ref().map(PriceHistory::new).transpose()?;
+    let global_price_streams = GlobalPriceStreams::new(
+        closure_tx,
+        price_reporter_config.composite_config.clone(),
+        price_history,
+    );
     init_default_price_streams(
         &global_price_streams,
         &price_reporter_config.exchange_conn_config,
@@ -71,7 +84,14 @@ async fn main() -> Result<(), ServerError> {
 
     log_task!(Task::ServiceLifecycle, Outcome::Ok, addr = %addr, "listening");
 
-    let http_server = HttpServer::new(&price_reporter_config, global_price_streams.clone());
+    // Watch the token remap for changes, if polling is enabled
+    let reloader = TokenRemapReloader::new(&price_reporter_config, global_price_streams.clone());
+    if let Some(interval) = price_reporter_config.token_remap_poll_interval {
+        tokio::spawn(reloader.clone().poll_loop(interval));
+    }
+
+    let http_server =
+        HttpServer::new(&price_reporter_config, global_price_streams.clone(), reloader);
     tokio::spawn(http_server.execution_loop());
     // TODO: Handle shutdown of the HTTP server
 
@@ -83,6 +103,8 @@ async fn main() -> Result<(), ServerError> {
                     stream,
                     global_price_streams.clone(),
                     price_reporter_config.exchange_conn_config.clone(),
+                    price_reporter_config.max_ws_subscriptions,
+                    price_reporter_config.api_keys.clone(),
                 ));
             }
             // Handle price stream closure
@@ -104,12 +126,13 @@ async fn main() -> Result<(), ServerError> {
 /// Initialize price streams for all default token mapped pairs
 ///
 /// Cancels any existing streams that are no longer in the desired set
-/// (e.g. tokens removed from mappings), then initializes new streams.
+/// (e.g. tokens removed from mappings), then initializes the streams that are
+/// not yet running. Returns the topics of the started and cancelled streams.
 pub(crate) async fn init_default_price_streams(
     global_price_streams: &GlobalPriceStreams,
     config: &ExchangeConnectionsConfig,
     disabled_exchanges: Vec<Exchange>,
-) -> Result<(), ServerError> {
+) -> Result<StreamChanges, ServerError> {
     log_task!(Task::ServiceLifecycle, Outcome::Started, "initializing default price streams");
 
     let disabled_exchanges_set: HashSet<Exchange> = disabled_exchanges.into_iter().collect();
@@ -154,13 +177,36 @@ pub(crate) async fn init_default_price_streams(
             PairInfo::new_default_stable(*exchange, &token.get_addr()).ok()
         })
         .collect();
-    global_price_streams.cancel_removed_streams(&desired_pairs).await;
+    let cancelled = global_price_streams.cancel_removed_streams(&desired_pairs).await;
 
-    // Initialize all streams
+    // Initialize the streams which are not already running
+    let active_pairs = global_price_streams.active_pairs().await;
+    let mut started = Vec::new();
     for (base_token, exchange) in streams {
+        let pair_info = PairInfo::new_default_stable(exchange, &base_token.get_addr())?;
+        // Streams on the `Renegade` exchange run on the canonical exchange's pair
+        let normalized_pair_info = global_price_streams
+            .normalize_pair_info(pair_info.clone())
+            .map_or(pair_info, |(normalized, _)| normalized);
+        if active_pairs.contains(&normalized_pair_info) {
+            continue;
+        }
+
+        started.push(normalized_pair_info.to_topic());
         init_price_stream(base_token, exchange, global_price_streams, config.clone())?;
     }
-    Ok(())
+
+    Ok(StreamChanges { started, cancelled: cancelled.iter().map(PairInfo::to_topic).collect() })
+}
+
+/// The price streams started and cancelled by a call to
+/// `init_default_price_streams`
+#[derive(Debug, Default)]
+pub(crate) struct StreamChanges {
+    /// The topics of the streams that were started
+    pub started: Vec<String>,
+    /// The topics of the streams that were cancelled
+    pub cancelled: Vec<String>,
 }
 
 /// Spawn a task to initialize a price stream for a given token pair
@@ -192,7 +238,7 @@ fn init_price_stream(
 }
 
 /// Get the listing exchanges for a given base token
-fn get_supported_exchanges(
+pub(crate) fn get_supported_exchanges(
     base_token: &Token,
     config: &ExchangeConnectionsConfig,
 ) -> HashSet<Exchange> {
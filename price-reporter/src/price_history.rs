@@ -0,0 +1,584 @@
+//! Historical price recording
+//!
+//! Price streams only hold their latest value, so the recorder keeps a
+//! bounded ring buffer of every published tick per topic. This allows the
+//! reporter to answer "what was the price at time T" when a fill or refund is
+//! disputed, and to serve OHLC candles over a window.
+//!
+//! Ticks may additionally be appended to an on-disk sink as JSON lines, which
+//! outlives the in-memory buffers across restarts. The sink is replayed into
+//! the buffers on startup, keeping the most recent ticks of each topic, and is
+//! compacted to the same retention window on startup and periodically while
+//! running.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Write},
+    sync::Arc,
+};
+
+use renegade_types_core::Price;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{
+    RwLock,
+    mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+};
+
+use crate::{
+    errors::ServerError,
+    log_task,
+    logger::{Outcome, Task},
+    utils::{PriceReceiver, PriceUpdate},
+};
+
+// -------------
+// | Constants |
+// -------------
+
+/// The maximum number of candles that may be requested in a single query
+pub const MAX_CANDLES: u64 = 1_000;
+
+// ---------
+// | Types |
+// ---------
+
+/// The configuration options for the price history recorder
+#[derive(Clone, Debug)]
+pub struct PriceHistoryConfig {
+    /// The number of ticks retained in memory per topic
+    pub capacity: usize,
+    /// The path of a file to which every tick is appended, if any
+    pub sink_path: Option<String>,
+}
+
+/// A single published price
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PriceTick {
+    /// The time at which the price was received from its source, in
+    /// milliseconds since the epoch
+    pub timestamp: u64,
+    /// The published price
+    pub price: Price,
+}
+
+/// An OHLC candle over a fixed interval
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    /// The start of the interval, inclusive, in milliseconds since the epoch
+    pub start: u64,
+    /// The end of the interval, exclusive, in milliseconds since the epoch
+    pub end: u64,
+    /// The price in effect at the start of the interval
+    pub open: Price,
+    /// The highest price in effect during the interval
+    pub high: Price,
+    /// The lowest price in effect during the interval
+    pub low: Price,
+    /// The price in effect at the end of the interval
+    pub close: Price,
+    /// The number of ticks published during the interval
+    pub num_ticks: usize,
+}
+
+/// A tick as written to the on-disk sink
+#[derive(Serialize, Deserialize)]
+struct SinkRecord {
+    /// The topic on which the tick was published
+    topic: String,
+    /// The tick
+    #[serde(flatten)]
+    tick: PriceTick,
+}
+
+/// A type alias for a map of tick buffers, indexed by topic
+type TickBuffers = HashMap<String, VecDeque<PriceTick>>;
+/// A type alias for a shareable map of tick buffers, indexed by topic
+type SharedTickBuffers = Arc<RwLock<TickBuffers>>;
+
+// ------------
+// | Recorder |
+// ------------
+
+/// Records the ticks published on each topic
+#[derive(Clone)]
+pub struct PriceHistory {
+    /// The ring buffer of recent ticks for each topic
+    buffers: SharedTickBuffers,
+    /// The number of ticks retained in memory per topic
+    capacity: usize,
+    /// The channel on which ticks are forwarded to the on-disk sink, if one is
+    /// configured
+    sink: Option<UnboundedSender<SinkRecord>>,
+}
+
+impl PriceHistory {
+    /// Create a new price history recorder, replaying the sink into memory
+    /// and spawning the sink writer if configured
+    pub fn new(config: &PriceHistoryConfig) -> Result<Self, ServerError> {
+        let buffers = match &config.sink_path {
+            Some(path) => read_sink(path, config.capacity)?,
+            None => HashMap::new(),
+        };
+
+        let sink = match &config.sink_path {
+            Some(path) => {
+                let file = compact_sink(path, &buffers)?;
+
+                let (sink_tx, sink_rx) = unbounded_channel();
+                let path = path.clone();
+                let capacity = config.capacity;
+                tokio::task::spawn_blocking(move || {
+                    Self::sink_task(&path, capacity, file, sink_rx)
+                });
+                Some(sink_tx)
+            },
+            None => None,
+        };
+
+        Ok(Self { buffers: Arc::new(RwLock::new(buffers)), capacity: config.capacity, sink })
+    }
+
+    /// Record every price published on the given receiver under the given
+    /// topic, until the sender is dropped
+    ///
+    /// Heartbeat replays re-send the last update unchanged, so an update equal
+    /// to the last one recorded is skipped rather than recorded as a new tick.
+    pub fn watch(&self, topic: String, mut price_rx: PriceReceiver) {
+        let history = self.clone();
+        tokio::spawn(async move {
+            let mut last_recorded: Option<PriceUpdate> = None;
+            while price_rx.changed().await.is_ok() {
+                let update = *price_rx.borrow_and_update();
+                // The zero price is only used to initialize the channel
+                if update.is_initial() || last_recorded == Some(update) {
+                    continue;
+                }
+
+                history.record(&topic, update).await;
+                last_recorded = Some(update);
+            }
+        });
+    }
+
+    /// Record an update on the given topic, stamped with the time at which it
+    /// was received from its source
+    pub async fn record(&self, topic: &str, update: PriceUpdate) {
+        let mut buffers = self.buffers.write().await;
+
+        // Composite updates carry the receive time of their oldest source, which
+        // may move backwards; clamp it so the buffer stays sorted
+        let last_timestamp = buffers.get(topic).and_then(|buffer| buffer.back());
+        let timestamp = last_timestamp
+            .map_or(update.received_at, |tick| tick.timestamp.max(update.received_at));
+        let tick = PriceTick { timestamp, price: update.price };
+
+        push_tick(&mut buffers, topic, tick, self.capacity);
+        drop(buffers);
+
+        if let Some(sink) = &self.sink {
+            let _ = sink.send(SinkRecord { topic: topic.to_string(), tick });
+        }
+    }
+
+    /// Get the tick in effect on the given topic at the given time
+    pub async fn price_at(&self, topic: &str, timestamp: u64) -> Option<PriceTick> {
+        let buffers = self.buffers.read().await;
+        let buffer = buffers.get(topic)?;
+        let idx = buffer.partition_point(|tick| tick.timestamp <= timestamp);
+        idx.checked_sub(1).and_then(|idx| buffer.get(idx).copied())
+    }
+
+    /// Get the ticks in effect on the given topic in the window
+    /// `[start, end)`
+    ///
+    /// Includes the last tick before `start`, if any, as it is the price in
+    /// effect at the start of the window.
+    pub async fn ticks(&self, topic: &str, start: u64, end: u64) -> Vec<PriceTick> {
+        let buffers = self.buffers.read().await;
+        let Some(buffer) = buffers.get(topic) else {
+            return Vec::new();
+        };
+
+        let first = buffer.partition_point(|tick| tick.timestamp < start).saturating_sub(1);
+        buffer.range(first..).take_while(|tick| tick.timestamp < end).copied().collect()
+    }
+
+    /// The task writing ticks to the on-disk sink as JSON lines
+    ///
+    /// Once as many records have been appended since the last compaction as
+    /// the in-memory buffers retain, the sink is compacted back down to the
+    /// retention window. This bounds the sink at roughly twice the retained
+    /// ticks.
+    ///
+    /// Runs on a blocking thread until the recorder is dropped.
+    fn sink_task(
+        path: &str,
+        capacity: usize,
+        file: File,
+        mut sink_rx: UnboundedReceiver<SinkRecord>,
+    ) {
+        let mut writer = BufWriter::new(file);
+        let mut topics = HashSet::new();
+        let mut num_appended = 0;
+        while let Some(record) = sink_rx.blocking_recv() {
+            // Flush once the backlog is drained
+            let flush = sink_rx.is_empty();
+            if let Err(e) = Self::write_record(&mut writer, &record, flush) {
+                log_task!(
+                    Task::PriceHistory,
+                    Outcome::Failed,
+                    subject = %path,
+                    error = %e,
+                    "error writing to price history sink"
+                );
+            }
+
+            topics.insert(record.topic);
+            num_appended += 1;
+            if num_appended < capacity.saturating_mul(topics.len()) {
+                continue;
+            }
+
+            num_appended = 0;
+            let compacted = writer
+                .flush()
+                .map_err(|e| ServerError::PriceHistory(format!("{path}: {e}")))
+                .and_then(|_| load_sink(path, capacity))
+                .and_then(|(buffers, _)| compact_sink(path, &buffers));
+            match compacted {
+                Ok(file) => writer = BufWriter::new(file),
+                Err(e) => log_task!(
+                    Task::PriceHistory,
+                    Outcome::Failed,
+                    subject = %path,
+                    error = %e,
+                    "error compacting price history sink"
+                ),
+            }
+        }
+    }
+
+    /// Write a record to the sink as a JSON line
+    fn write_record(
+        writer: &mut BufWriter<File>,
+        record: &SinkRecord,
+        flush: bool,
+    ) -> Result<(), String> {
+        serde_json::to_writer(&mut *writer, record).map_err(|e| e.to_string())?;
+        writeln!(writer).map_err(|e| e.to_string())?;
+        if flush {
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Append a tick to a topic's buffer, evicting the oldest tick if the buffer
+/// is at capacity
+fn push_tick(buffers: &mut TickBuffers, topic: &str, tick: PriceTick, capacity: usize) {
+    let buffer = buffers.entry(topic.to_string()).or_default();
+    if buffer.len() >= capacity {
+        buffer.pop_front();
+    }
+    buffer.push_back(tick);
+}
+
+/// Read the ticks recorded in the on-disk sink, keeping the most recent
+/// `capacity` ticks of each topic
+///
+/// A missing sink is treated as empty. Lines that fail to parse, e.g. one
+/// truncated by a crash mid-write, are skipped.
+fn read_sink(path: &str, capacity: usize) -> Result<TickBuffers, ServerError> {
+    let (buffers, num_skipped) = load_sink(path, capacity)?;
+    log_task!(
+        Task::PriceHistory,
+        if num_skipped == 0 { Outcome::Ok } else { Outcome::Partial },
+        subject = %path,
+        num_topics = buffers.len(),
+        num_skipped,
+        "replayed price history sink"
+    );
+
+    Ok(buffers)
+}
+
+/// Load the ticks recorded in the on-disk sink, returning the most recent
+/// `capacity` ticks of each topic and the number of lines skipped
+fn load_sink(path: &str, capacity: usize) -> Result<(TickBuffers, usize), ServerError> {
+    let mut buffers = HashMap::new();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((buffers, 0)),
+        Err(e) => return Err(ServerError::PriceHistory(format!("{path}: {e}"))),
+    };
+
+    let mut num_skipped = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| ServerError::PriceHistory(format!("{path}: {e}")))?;
+        match serde_json::from_str::<SinkRecord>(&line) {
+            Ok(record) => push_tick(&mut buffers, &record.topic, record.tick, capacity),
+            Err(_) => num_skipped += 1,
+        }
+    }
+
+    Ok((buffers, num_skipped))
+}
+
+/// Rewrite the on-disk sink to hold exactly the given ticks, returning the
+/// compacted sink opened for appending
+///
+/// The compacted sink is written to a temporary file and renamed over the
+/// sink, so a crash mid-compaction leaves the previous sink intact.
+fn compact_sink(path: &str, buffers: &TickBuffers) -> Result<File, ServerError> {
+    let to_err = |e: String| ServerError::PriceHistory(format!("{path}: {e}"));
+    let tmp_path = format!("{path}.tmp");
+
+    let tmp_file = File::create(&tmp_path).map_err(|e| to_err(e.to_string()))?;
+    let mut writer = BufWriter::new(tmp_file);
+    for (topic, buffer) in buffers {
+        for tick in buffer {
+            let record = SinkRecord { topic: topic.clone(), tick: *tick };
+            PriceHistory::write_record(&mut writer, &record, false /* flush */).map_err(to_err)?;
+        }
+    }
+    writer.flush().map_err(|e| to_err(e.to_string()))?;
+    drop(writer);
+
+    std::fs::rename(&tmp_path, path).map_err(|e| to_err(e.to_string()))?;
+    OpenOptions::new().append(true).open(path).map_err(|e| to_err(e.to_string()))
+}
+
+/// Get the tick in effect at the given time, i.e. the last tick published at
+/// or before it
+pub fn tick_at(ticks: &[PriceTick], timestamp: u64) -> Option<PriceTick> {
+    let idx = ticks.partition_point(|tick| tick.timestamp <= timestamp);
+    idx.checked_sub(1).map(|idx| ticks[idx])
+}
+
+/// Convert a series of ticks into a different quote by dividing by the
+/// conversion price in effect at each tick
+///
+/// A converted tick is emitted whenever either series ticks, once both have a
+/// price.
+pub fn convert_ticks(ticks: &[PriceTick], conversion_ticks: &[PriceTick]) -> Vec<PriceTick> {
+    let mut timestamps =
+        ticks.iter().chain(conversion_ticks.iter()).map(|tick| tick.timestamp).collect::<Vec<_>>();
+    timestamps.sort_unstable();
+    timestamps.dedup();
+
+    timestamps
+        .into_iter()
+        .filter_map(|timestamp| {
+            let price = tick_at(ticks, timestamp)?.price;
+            let conversion_price = tick_at(conversion_ticks, timestamp)?.price;
+            Some(PriceTick { timestamp, price: price / conversion_price })
+        })
+        .collect()
+}
+
+/// Build OHLC candles of the given interval over the window `[start, end)`
+///
+/// Expects `ticks` to be sorted by timestamp, and to include the last tick
+/// before `start` so that the first candle opens at the price in effect.
+/// Intervals without ticks produce a flat candle at the previous close;
+/// intervals before the first tick produce no candle.
+pub fn build_candles(ticks: &[PriceTick], start: u64, end: u64, interval: u64) -> Vec<Candle> {
+    let mut candles = Vec::new();
+    if interval == 0 {
+        return candles;
+    }
+
+    let mut next_tick = ticks.partition_point(|tick| tick.timestamp < start);
+    let mut prev_close = ticks[..next_tick].last().map(|tick| tick.price);
+
+    let mut candle_start = start;
+    while candle_start < end {
+        let candle_end = candle_start.saturating_add(interval).min(end);
+        let mut candle = prev_close.map(|open| Candle {
+            start: candle_start,
+            end: candle_end,
+            open,
+            high: open,
+            low: open,
+            close: open,
+            num_ticks: 0,
+        });
+
+        while let Some(tick) = ticks.get(next_tick).filter(|tick| tick.timestamp < candle_end) {
+            let candle = candle.get_or_insert(Candle {
+                start: candle_start,
+                end: candle_end,
+                open: tick.price,
+                high: tick.price,
+                low: tick.price,
+                close: tick.price,
+                num_ticks: 0,
+            });
+            candle.high = candle.high.max(tick.price);
+            candle.low = candle.low.min(tick.price);
+            candle.close = tick.price;
+            candle.num_ticks += 1;
+            next_tick += 1;
+        }
+
+        if let Some(candle) = candle {
+            prev_close = Some(candle.close);
+            candles.push(candle);
+        }
+        candle_start = candle_end;
+    }
+
+    candles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a series of ticks from `(timestamp, price)` pairs
+    fn ticks(series: &[(u64, Price)]) -> Vec<PriceTick> {
+        series
+            .iter()
+            .map(|(timestamp, price)| PriceTick { timestamp: *timestamp, price: *price })
+            .collect()
+    }
+
+    #[test]
+    fn tick_at_returns_price_in_effect() {
+        let series = ticks(&[(10, 1.), (20, 2.), (30, 3.)]);
+
+        assert_eq!(tick_at(&series, 5), None);
+        assert_eq!(tick_at(&series, 10).unwrap().price, 1.);
+        assert_eq!(tick_at(&series, 25).unwrap().price, 2.);
+        assert_eq!(tick_at(&series, 100).unwrap().price, 3.);
+    }
+
+    #[test]
+    fn candles_aggregate_ticks() {
+        let series = ticks(&[(0, 10.), (5, 12.), (7, 9.), (12, 11.)]);
+        let candles = build_candles(&series, 0, 20, 10);
+
+        assert_eq!(candles.len(), 2);
+        let first = &candles[0];
+        assert_eq!((first.open, first.high, first.low, first.close), (10., 12., 9., 9.));
+        assert_eq!(first.num_ticks, 3);
+
+        // The second candle opens at the previous close
+        let second = &candles[1];
+        assert_eq!((second.open, second.high, second.low, second.close), (9., 11., 9., 11.));
+        assert_eq!(second.num_ticks, 1);
+    }
+
+    #[test]
+    fn empty_intervals_carry_previous_close() {
+        let series = ticks(&[(0, 10.), (25, 12.)]);
+        let candles = build_candles(&series, 0, 30, 10);
+
+        assert_eq!(candles.len(), 3);
+        assert_eq!(candles[1].num_ticks, 0);
+        assert_eq!((candles[1].open, candles[1].close), (10., 10.));
+        assert_eq!(candles[2].close, 12.);
+    }
+
+    #[test]
+    fn candles_open_at_price_before_window() {
+        let series = ticks(&[(5, 10.), (15, 12.)]);
+        let candles = build_candles(&series, 10, 20, 10);
+
+        assert_eq!(candles.len(), 1);
+        assert_eq!((candles[0].open, candles[0].close), (10., 12.));
+        assert_eq!(candles[0].low, 10.);
+    }
+
+    #[test]
+    fn no_candles_before_first_tick() {
+        let series = ticks(&[(15, 10.)]);
+        let candles = build_candles(&series, 0, 20, 10);
+
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].start, 10);
+        assert_eq!(candles[0].open, 10.);
+    }
+
+    #[test]
+    fn sink_replays_most_recent_ticks() {
+        let path = std::env::temp_dir().join(format!("price-history-{}.jsonl", std::process::id()));
+        let records = [("a", 1, 1.), ("b", 2, 2.), ("a", 3, 3.), ("a", 4, 4.)];
+        let mut lines = records
+            .iter()
+            .map(|(topic, timestamp, price)| {
+                let tick = PriceTick { timestamp: *timestamp, price: *price };
+                serde_json::to_string(&SinkRecord { topic: topic.to_string(), tick }).unwrap()
+            })
+            .collect::<Vec<_>>();
+        // A line truncated by a crash mid-write
+        lines.push("{\"topic\":\"a\",\"timest".to_string());
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let buffers = read_sink(path.to_str().unwrap(), 2 /* capacity */).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Vec::from(buffers["a"].clone()), ticks(&[(3, 3.), (4, 4.)]));
+        assert_eq!(Vec::from(buffers["b"].clone()), ticks(&[(2, 2.)]));
+    }
+
+    #[test]
+    fn compaction_keeps_retention_window() {
+        let path = std::env::temp_dir()
+            .join(format!("price-history-compact-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let records = (0..10).map(|i| {
+            let tick = PriceTick { timestamp: i, price: i as Price };
+            serde_json::to_string(&SinkRecord { topic: "a".to_string(), tick }).unwrap()
+        });
+        std::fs::write(path, records.collect::<Vec<_>>().join("\n")).unwrap();
+
+        let buffers = read_sink(path, 3 /* capacity */).unwrap();
+        compact_sink(path, &buffers).unwrap();
+        let num_lines = std::fs::read_to_string(path).unwrap().lines().count();
+        let compacted = read_sink(path, 10 /* capacity */).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(num_lines, 3);
+        assert_eq!(Vec::from(compacted["a"].clone()), ticks(&[(7, 7.), (8, 8.), (9, 9.)]));
+    }
+
+    #[tokio::test]
+    async fn replayed_updates_are_not_recorded() {
+        let config = PriceHistoryConfig { capacity: 10, sink_path: None };
+        let history = PriceHistory::new(&config).unwrap();
+        let (price_tx, price_rx) = tokio::sync::watch::channel(PriceUpdate::default());
+        history.watch("a".to_string(), price_rx);
+
+        let update = PriceUpdate::new_composite(100., 1_000);
+        for _ in 0..3 {
+            price_tx.send(update).unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        price_tx.send(PriceUpdate::new_composite(101., 2_000)).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        assert_eq!(history.ticks("a", 0, 3_000).await, ticks(&[(1_000, 100.), (2_000, 101.)]));
+    }
+
+    #[test]
+    fn missing_sink_is_empty() {
+        let path = std::env::temp_dir().join("price-history-missing.jsonl");
+        assert!(read_sink(path.to_str().unwrap(), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn convert_ticks_divides_by_conversion_in_effect() {
+        let series = ticks(&[(0, 100.), (10, 110.)]);
+        let conversion = ticks(&[(5, 2.), (15, 1.)]);
+        let converted = convert_ticks(&series, &conversion);
+
+        assert_eq!(converted, ticks(&[(5, 50.), (10, 55.), (15, 110.)]));
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::{
     collections::{HashMap, HashSet},
+    hash::Hash,
     sync::{
         Arc,
         atomic::{AtomicU64, Ordering},
@@ -9,27 +10,43 @@ use std::{
     time::Duration,
 };
 
-use renegade_types_core::{Exchange, Price};
+use itertools::Itertools;
+use renegade_types_core::Exchange;
 use tokio::{
-    sync::{RwLock, watch::channel},
+    sync::{
+        RwLock, broadcast, oneshot,
+        watch::{Sender as WatchSender, channel},
+    },
     time::Instant,
 };
-use tokio_stream::StreamExt;
+use tokio_stream::{StreamExt, StreamMap, wrappers::WatchStream};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
+    composite::{
+        CompositeConfig, CompositeMethod, CompositePair, aggregate_prices, is_composite_topic,
+        retain_fresh_sources,
+    },
     errors::ServerError,
     exchanges::{
-        ExchangeConnectionsConfig, connect_exchange, connection::ExchangeConnection,
+        ExchangeConnectionsConfig, connect_exchange,
+        connection::{ExchangeConnection, ExchangePrice},
+        depth::{DepthConnection, DepthSnapshot, connect_depth},
         error::ExchangeConnectionError,
+        quote_conversion::QuoteConversionConnection,
+        venue::PriceSource,
     },
+    failover::{FAILBACK_PROBE_INTERVAL, fallback_pairs, quote_conversion_pair},
     log_task,
     logger::{Outcome, Task},
+    price_history::{Candle, MAX_CANDLES, PriceHistory, PriceTick, build_candles, convert_ticks},
     utils::{
-        CONN_RETRY_DELAY, ClosureSender, FEED_AGE_EMIT_INTERVAL, HEARTBEAT_INTERVAL,
-        HEARTBEAT_REPLAY_WARN_AGE, KEEPALIVE_INTERVAL, MAX_CONN_RETRIES, MAX_CONN_RETRY_WINDOW,
-        MAX_HEARTBEAT_AGE, PairInfo, PriceReceiver, PriceSender, PriceStream,
-        RATE_LIMIT_RETRY_DELAY, SUBSCRIBE_ACK_TIMEOUT, SharedPriceStreams,
+        CONN_RETRY_DELAY, ClosureSender, DepthReceiver, DepthSender, FEED_AGE_EMIT_INTERVAL,
+        FIRST_UPDATE_TIMEOUT, HEARTBEAT_INTERVAL, HEARTBEAT_REPLAY_WARN_AGE, KEEPALIVE_INTERVAL,
+        LastRead, MAX_CONN_RETRIES, MAX_CONN_RETRY_WINDOW, MAX_HEARTBEAT_AGE, PairInfo,
+        PriceReceiver, PriceSender, PriceStream, PriceUpdate, RATE_LIMIT_RETRY_DELAY,
+        STREAM_IDLE_TIMEOUT, SUBSCRIBE_ACK_TIMEOUT, SharedCompositeStreams, SharedDepthStreams,
+        SharedPriceStreams, get_price_topic_str,
     },
 };
 
@@ -40,6 +57,10 @@ use crate::{
 /// lock. A value of `0` means no real tick has been observed yet.
 type LastRealTick = Arc<AtomicU64>;
 
+/// The receiver for a failback probe's healthy primary connection and its
+/// first price
+type FailbackReceiver = oneshot::Receiver<(Box<dyn ExchangeConnection>, ExchangePrice)>;
+
 /// Current Unix time in milliseconds. Returns `0` if the system clock is
 /// somehow before the epoch (shouldn't happen, but `SystemTime` can fail).
 fn now_millis() -> u64 {
@@ -49,6 +70,49 @@ fn now_millis() -> u64 {
         .unwrap_or(0)
 }
 
+/// Whether a stream has no receivers beyond the retained ones, and has not
+/// been read over HTTP within the last `STREAM_IDLE_TIMEOUT`
+fn is_idle<T>(tx: &WatchSender<T>, retained_receivers: usize, last_read: &LastRead) -> bool {
+    let since_read = now_millis().saturating_sub(last_read.load(Ordering::Relaxed));
+    tx.receiver_count() <= retained_receivers
+        && since_read >= STREAM_IDLE_TIMEOUT.as_millis() as u64
+}
+
+/// Wait until the given stream has been idle for a full `STREAM_IDLE_TIMEOUT`
+async fn wait_for_idle<T>(tx: &WatchSender<T>, retained_receivers: usize, last_read: &LastRead) {
+    let mut was_idle = false;
+    loop {
+        tokio::time::sleep(STREAM_IDLE_TIMEOUT).await;
+        let is_idle = is_idle(tx, retained_receivers, last_read);
+        if is_idle && was_idle {
+            return;
+        }
+
+        was_idle = is_idle;
+    }
+}
+
+/// Remove a stream from the given map if it is still idle, returning whether
+/// it was removed
+///
+/// The check is made under the map's write lock, so that no receiver can be
+/// handed out for a stream that is being torn down.
+async fn remove_if_idle<K: Eq + Hash, V, T>(
+    streams: &RwLock<HashMap<K, V>>,
+    key: &K,
+    tx: &WatchSender<T>,
+    retained_receivers: usize,
+    last_read: &LastRead,
+) -> bool {
+    let mut streams = streams.write().await;
+    if !is_idle(tx, retained_receivers, last_read) {
+        return false;
+    }
+
+    streams.remove(key);
+    true
+}
+
 /// The price for a unit pair
 ///
 /// A unit pair is a pair in which the base and quote tokens are the same.
@@ -57,6 +121,8 @@ fn now_millis() -> u64 {
 const UNIT_PAIR_PRICE: f64 = 1.0;
 /// The interval at which to refresh the unit price
 const UNIT_PRICE_REFRESH_INTERVAL_MS: u64 = 1_000; // 1 second
+/// The capacity of the channel announcing restarted `Renegade` topics
+const RENEGADE_RESTART_CHANNEL_CAPACITY: usize = 16;
 
 /// A map of price streams from exchanges maintained by the server,
 /// shared across all connections
@@ -67,12 +133,59 @@ pub(crate) struct GlobalPriceStreams {
     pub price_streams: SharedPriceStreams,
     /// A channel to send closure signals from the price stream tasks
     pub closure_channel: ClosureSender,
+    /// A thread-safe map of composite price streams, indexed by the composite
+    /// pair
+    pub composite_streams: SharedCompositeStreams,
+    /// The configuration options for composite price streams
+    pub composite_config: CompositeConfig,
+    /// A thread-safe map of order book depth streams, indexed by the (source,
+    /// base, quote) tuple
+    pub depth_streams: SharedDepthStreams,
+    /// The recorder of published prices, if price history is enabled
+    pub price_history: Option<PriceHistory>,
+    /// A channel announcing the `Renegade` topics whose canonical exchange
+    /// changed, so that their subscribers re-resolve their streams
+    pub renegade_restarts: broadcast::Sender<Vec<String>>,
 }
 
 impl GlobalPriceStreams {
     /// Instantiate a new global price streams map
-    pub fn new(closure_channel: ClosureSender) -> Self {
-        Self { price_streams: Arc::new(RwLock::new(HashMap::new())), closure_channel }
+    pub fn new(
+        closure_channel: ClosureSender,
+        composite_config: CompositeConfig,
+        price_history: Option<PriceHistory>,
+    ) -> Self {
+        Self {
+            price_streams: Arc::new(RwLock::new(HashMap::new())),
+            closure_channel,
+            composite_streams: Arc::new(RwLock::new(HashMap::new())),
+            composite_config,
+            depth_streams: Arc::new(RwLock::new(HashMap::new())),
+            price_history,
+            renegade_restarts: broadcast::channel(RENEGADE_RESTART_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    /// Restart the `Renegade` streams of the given base mints on their current
+    /// canonical exchanges, returning the restarted topics
+    ///
+    /// `Renegade` streams are served from the canonical exchange's pair, which
+    /// is resolved at subscription time, so subscribers are told to re-resolve
+    /// their streams. The stream of the previous canonical pair is torn down
+    /// once it has no subscribers left.
+    pub fn restart_renegade_streams(&self, base_mints: &[String]) -> Vec<String> {
+        let topics: Vec<String> = base_mints
+            .iter()
+            .filter_map(|mint| PairInfo::new_default_stable(Exchange::Renegade, mint).ok())
+            .map(|pair_info| get_price_topic_str(&pair_info.into()))
+            .collect();
+
+        if !topics.is_empty() {
+            // Errors only if there are no subscribers to notify
+            let _ = self.renegade_restarts.send(topics.clone());
+        }
+
+        topics
     }
 
     /// Attempt to add a price stream to the global map, returning the price
@@ -101,8 +214,14 @@ impl GlobalPriceStreams {
         }
     }
 
-    /// Cancel all streams whose PairInfo is not in the desired set
-    pub async fn cancel_removed_streams(&self, desired: &HashSet<PairInfo>) {
+    /// The pairs for which a price stream is currently running
+    pub async fn active_pairs(&self) -> HashSet<PairInfo> {
+        self.price_streams.read().await.keys().cloned().collect()
+    }
+
+    /// Cancel all streams whose PairInfo is not in the desired set, returning
+    /// the cancelled pairs
+    pub async fn cancel_removed_streams(&self, desired: &HashSet<PairInfo>) -> Vec<PairInfo> {
         let to_cancel: Vec<PairInfo> = {
             let streams = self.price_streams.read().await;
             streams.keys().filter(|k| !desired.contains(k)).cloned().collect()
@@ -110,18 +229,20 @@ impl GlobalPriceStreams {
 
         if !to_cancel.is_empty() {
             let mut streams = self.price_streams.write().await;
-            for pair_info in to_cancel {
+            for pair_info in to_cancel.iter() {
                 log_task!(
                     Task::PriceStream,
                     Outcome::Ok,
                     subject = %pair_info.to_topic(),
                     "cancelling removed price stream"
                 );
-                if let Some((_, cancel_token)) = streams.remove(&pair_info) {
+                if let Some((_, cancel_token)) = streams.remove(pair_info) {
                     cancel_token.cancel();
                 }
             }
         }
+
+        to_cancel
     }
 
     /// Initialize a price stream for the given pair info
@@ -130,10 +251,14 @@ impl GlobalPriceStreams {
         pair_info: PairInfo,
         config: ExchangeConnectionsConfig,
     ) -> Result<PriceReceiver, ServerError> {
-        pair_info.validate_subscription().await?;
+        // Replayed streams are served from recordings, so they need not be
+        // supported by the live exchange
+        if !config.replays(&pair_info) {
+            pair_info.validate_subscription(&config).await?;
+        }
 
         // Create a shared channel into which we forward streamed prices
-        let (price_tx, price_rx) = channel(Price::default());
+        let (price_tx, price_rx) = channel(PriceUpdate::default());
         let cancel_token = CancellationToken::new();
 
         if let Some(stream_rx) = self
@@ -154,6 +279,10 @@ impl GlobalPriceStreams {
             "initializing price stream"
         );
 
+        if let Some(history) = &self.price_history {
+            history.watch(get_price_topic_str(&pair_info.clone().into()), price_rx.clone());
+        }
+
         // Spawn a task responsible for forwarding prices into the broadcast channel &
         // sending keepalive messages to the exchange
         let global_price_streams = self.clone();
@@ -177,7 +306,7 @@ impl GlobalPriceStreams {
         cancel_token: CancellationToken,
     ) -> Result<(), ServerError> {
         if pair_info.is_unit_pair() {
-            Self::stream_unit_pair_price(&price_tx).await;
+            Self::stream_unit_pair_price(&price_tx, pair_info.source()).await;
             return Ok(());
         }
 
@@ -196,12 +325,37 @@ impl GlobalPriceStreams {
             Self::emit_feed_age_loop(emitter_pair, emitter_last_tick, emitter_cancel).await;
         });
 
-        // Connect to the pair on the specified exchange
+        // The sources the stream may draw from: the pair itself, followed by
+        // its fallbacks if the pair is on the canonical exchange or converts
+        // canonical prices into USDC
+        let mut sources = vec![pair_info.clone()];
+        if pair_info.is_canonical() || pair_info.is_conversion_pair() {
+            sources.extend(fallback_pairs(&pair_info, &config.fallback_exchanges));
+        }
+
+        // Connect to the pair on the specified exchange, failing over if it
+        // cannot be reached
         let mut retry_timestamps = Vec::new();
-        let mut conn =
-            Self::connect_with_retries(&pair_info, &config, &mut retry_timestamps).await?;
+        let (mut active, mut conn) =
+            match Self::connect_with_retries(&pair_info, &config, &mut retry_timestamps).await {
+                Ok(conn) => (0, conn),
+                Err(e) => {
+                    let (next, conn) = Self::fail_over(&sources, 0, &config, e).await?;
+                    Self::record_switch(&pair_info, &pair_info, &sources[next]);
+                    (next, conn)
+                },
+            };
+
+        // While failed over, the primary is probed in the background and
+        // handed back along with its first price once it is healthy
+        let mut failback_rx = None;
+        let mut initial_price = None;
+        if active != 0 {
+            failback_rx = Some(Self::spawn_failback_probe(&pair_info, &config, &cancel_token));
+        }
 
         loop {
+            let source = &sources[active];
             tokio::select! {
                 _ = cancel_token.cancelled() => {
                     log_task!(
@@ -212,19 +366,229 @@ impl GlobalPriceStreams {
                     );
                     return Ok(());
                 }
-                res = Self::manage_connection(&mut conn, &price_tx, &pair_info, &last_real_tick) => {
-                    match res {
-                        Ok(()) => {},
-                        Err(e) => {
-                            conn = Self::exhaust_retries(e, &pair_info, &config, &mut retry_timestamps)
-                                .await?;
-                        },
+                Some((primary_conn, price)) = Self::recv_failback(&mut failback_rx), if failback_rx.is_some() => {
+                    Self::record_switch(&pair_info, source, &pair_info);
+                    failback_rx = None;
+                    retry_timestamps.clear();
+                    initial_price = Some(price);
+                    active = 0;
+                    conn = primary_conn;
+                }
+                res = Self::manage_connection(&mut conn, &price_tx, source, &last_real_tick, initial_price.take()) => {
+                    if let Err(e) = res {
+                        (active, conn) =
+                            Self::reconnect(e, &sources, active, &config, &mut retry_timestamps).await?;
+                        if active == 0 {
+                            failback_rx = None;
+                        } else if failback_rx.is_none() {
+                            failback_rx =
+                                Some(Self::spawn_failback_probe(&pair_info, &config, &cancel_token));
+                        }
                     }
                 }
             }
         }
     }
 
+    // ------------
+    // | Failover |
+    // ------------
+
+    /// Re-establish an erroring connection to the active source, failing over
+    /// to another source once its retries are exhausted
+    ///
+    /// Returns the index of the source connected to alongside the connection.
+    async fn reconnect(
+        err: ServerError,
+        sources: &[PairInfo],
+        active: usize,
+        config: &ExchangeConnectionsConfig,
+        retry_timestamps: &mut Vec<Instant>,
+    ) -> Result<(usize, Box<dyn ExchangeConnection>), ServerError> {
+        let source = &sources[active];
+        let err =
+            match Self::exhaust_retries(err, &sources[0], source, config, retry_timestamps).await {
+                Ok(conn) => return Ok((active, conn)),
+                Err(e) => e,
+            };
+
+        let (next, conn) = Self::fail_over(sources, active, config, err).await?;
+        Self::record_switch(&sources[0], source, &sources[next]);
+        retry_timestamps.clear();
+        Ok((next, conn))
+    }
+
+    /// Connect to the next healthy source after the failed one, in order of
+    /// preference and wrapping around to the primary
+    ///
+    /// Each candidate is tried once, so that prices resume as soon as any
+    /// source is reachable. Returns the index of the connected source, or the
+    /// failed source's error if no other source is reachable.
+    ///
+    /// The first source is the stream's own pair, whose quote every source's
+    /// prices are converted into.
+    async fn fail_over(
+        sources: &[PairInfo],
+        failed: usize,
+        config: &ExchangeConnectionsConfig,
+        err: ServerError,
+    ) -> Result<(usize, Box<dyn ExchangeConnection>), ServerError> {
+        let candidates = (1..sources.len()).map(|offset| (failed + offset) % sources.len());
+        for idx in candidates {
+            let candidate = &sources[idx];
+            if !Self::source_supported(&sources[0], candidate, config).await {
+                log_task!(
+                    Task::Failover,
+                    Outcome::Skipped,
+                    subject = %candidate.to_topic(),
+                    "fallback exchange does not support the pair"
+                );
+                continue;
+            }
+
+            match Self::connect_source(&sources[0], candidate, config).await {
+                Ok(conn) => return Ok((idx, conn)),
+                Err(e) => {
+                    log_task!(
+                        Task::Failover,
+                        Outcome::Failed,
+                        subject = %candidate.to_topic(),
+                        error = %e,
+                        "failed to connect to fallback exchange"
+                    );
+                },
+            }
+        }
+
+        Err(err)
+    }
+
+    /// Whether the given source and, if its quote differs from the stream's,
+    /// the pair converting it into the stream's quote are supported
+    async fn source_supported(
+        stream_pair: &PairInfo,
+        source: &PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> bool {
+        if let Some(conversion_pair) = quote_conversion_pair(source, &stream_pair.quote)
+            && !conversion_pair.is_supported(config).await.unwrap_or(false)
+        {
+            return false;
+        }
+
+        source.is_supported(config).await.unwrap_or(false)
+    }
+
+    /// Connect to a source of the given stream, converting the source's prices
+    /// into the stream's quote if the source is quoted in a different unit
+    async fn connect_source(
+        stream_pair: &PairInfo,
+        source: &PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<Box<dyn ExchangeConnection>, ExchangeConnectionError> {
+        if quote_conversion_pair(source, &stream_pair.quote).is_none() {
+            return connect_exchange(source.clone(), config).await;
+        }
+
+        let converted = PairInfo { quote: stream_pair.quote.clone(), ..source.clone() };
+        Ok(Box::new(QuoteConversionConnection::connect(converted, config).await?))
+    }
+
+    /// Log and record a metric for a switch of the given stream's source
+    fn record_switch(pair_info: &PairInfo, from: &PairInfo, to: &PairInfo) {
+        let is_failback = to == pair_info;
+        log_task!(
+            Task::Failover,
+            if is_failback { Outcome::Ok } else { Outcome::Partial },
+            subject = %pair_info.to_topic(),
+            from = %from.source(),
+            to = %to.source(),
+            "switched price stream source"
+        );
+        renegade_util::metrics::counter!(
+            "price_source_switches",
+            "pair" => pair_info.to_topic(),
+            "from" => from.source().to_string(),
+            "to" => to.source().to_string(),
+        )
+        .increment(1);
+    }
+
+    /// Spawn a task probing the primary exchange of a failed-over stream,
+    /// which sends back a connection and its first price once the primary is
+    /// healthy
+    ///
+    /// The probe stops when the stream is cancelled.
+    fn spawn_failback_probe(
+        pair_info: &PairInfo,
+        config: &ExchangeConnectionsConfig,
+        cancel_token: &CancellationToken,
+    ) -> FailbackReceiver {
+        let (tx, rx) = oneshot::channel();
+        let pair_info = pair_info.clone();
+        let config = config.clone();
+        let cancel_token = cancel_token.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = cancel_token.cancelled() => {},
+                primed = Self::probe_primary(&pair_info, &config) => {
+                    let _ = tx.send(primed);
+                }
+            }
+        });
+
+        rx
+    }
+
+    /// Probe the primary exchange until it connects and publishes a price
+    async fn probe_primary(
+        pair_info: &PairInfo,
+        config: &ExchangeConnectionsConfig,
+    ) -> (Box<dyn ExchangeConnection>, ExchangePrice) {
+        loop {
+            tokio::time::sleep(FAILBACK_PROBE_INTERVAL).await;
+            let mut conn = match connect_exchange(pair_info.clone(), config).await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log_task!(
+                        Task::Failover,
+                        Outcome::Retrying,
+                        subject = %pair_info.to_topic(),
+                        error = %e,
+                        "primary exchange still unreachable"
+                    );
+                    continue;
+                },
+            };
+
+            // Only hand back a primary that is actually publishing prices
+            match tokio::time::timeout(SUBSCRIBE_ACK_TIMEOUT, conn.next()).await {
+                Ok(Some(Ok(price))) => return (conn, price),
+                _ => {
+                    log_task!(
+                        Task::Failover,
+                        Outcome::Retrying,
+                        subject = %pair_info.to_topic(),
+                        "primary exchange connected but published no price"
+                    );
+                },
+            }
+        }
+    }
+
+    /// Await the result of a failback probe, clearing the receiver if the
+    /// probe has stopped
+    async fn recv_failback(
+        failback_rx: &mut Option<FailbackReceiver>,
+    ) -> Option<(Box<dyn ExchangeConnection>, ExchangePrice)> {
+        let res = failback_rx.as_mut()?.await.ok();
+        if res.is_none() {
+            *failback_rx = None;
+        }
+
+        res
+    }
+
     /// Periodically emit `exchange_last_update_age_seconds` for this stream
     /// until the parent task is cancelled. The gauge value is the wall-clock
     /// age of the most recent real exchange tick; while the connection is
@@ -262,21 +626,26 @@ impl GlobalPriceStreams {
     ///
     /// We simply send a price of 1.0 in a loop with a delay. This will keep the
     /// price "fresh" as measured by consumers in this service and via the API.
-    async fn stream_unit_pair_price(price_tx: &PriceSender) {
+    async fn stream_unit_pair_price(price_tx: &PriceSender, source: PriceSource) {
         let refresh_interval = Duration::from_millis(UNIT_PRICE_REFRESH_INTERVAL_MS);
         loop {
-            let _ = price_tx.send(UNIT_PAIR_PRICE);
+            let update = PriceUpdate::new(UNIT_PAIR_PRICE.into(), source, now_millis());
+            let _ = price_tx.send(update);
             tokio::time::sleep(refresh_interval).await;
         }
     }
 
     /// Manages an exchange connection, sending keepalive messages and
     /// forwarding prices to the price receiver
+    ///
+    /// If an initial price is given, it was read from the connection before
+    /// handing it over and is published immediately.
     async fn manage_connection(
         conn: &mut Box<dyn ExchangeConnection>,
         price_tx: &PriceSender,
         pair_info: &PairInfo,
         last_real_tick: &LastRealTick,
+        initial_price: Option<ExchangePrice>,
     ) -> Result<(), ServerError> {
         let keepalive_delay = tokio::time::sleep(KEEPALIVE_INTERVAL);
         let heartbeat_delay = tokio::time::sleep(HEARTBEAT_INTERVAL);
@@ -284,7 +653,7 @@ impl GlobalPriceStreams {
         tokio::pin!(keepalive_delay);
         tokio::pin!(heartbeat_delay);
         tokio::pin!(subscribe_ack_deadline);
-        let mut last_price: Option<Price> = None;
+        let mut last_update: Option<PriceUpdate> = None;
         let mut last_exchange_update = Instant::now();
         let mut received_first_tick = false;
         // Edge-triggered state for the heartbeat-replay warn. We log once
@@ -294,6 +663,15 @@ impl GlobalPriceStreams {
         // tick every 60–120s.
         let mut replay_stalled = false;
 
+        if let Some(price) = initial_price {
+            let received_at = now_millis();
+            let update = PriceUpdate::new(price, pair_info.source(), received_at);
+            let _ = price_tx.send(update);
+            last_update = Some(update);
+            received_first_tick = true;
+            last_real_tick.store(received_at, Ordering::Relaxed);
+        }
+
         loop {
             tokio::select! {
                 // Send a keepalive message to the exchange
@@ -328,7 +706,7 @@ impl GlobalPriceStreams {
                 // fresh, but only if we've received a real exchange update
                 // recently — otherwise treat the connection as dead.
                 _ = &mut heartbeat_delay => {
-                    if let Some(price) = last_price {
+                    if let Some(update) = last_update {
                         let age = last_exchange_update.elapsed();
                         if age < MAX_HEARTBEAT_AGE {
                             // Edge-trigger: log exactly once when the replay
@@ -345,7 +723,9 @@ impl GlobalPriceStreams {
                                 );
                                 replay_stalled = true;
                             }
-                            let _ = price_tx.send(price);
+                            // The replayed update keeps its original receive
+                            // time, so consumers see its age grow
+                            let _ = price_tx.send(update);
                         } else {
                             log_task!(
                                 Task::Heartbeat,
@@ -382,11 +762,13 @@ impl GlobalPriceStreams {
                             );
                             replay_stalled = false;
                         }
-                        let _ = price_tx.send(price);
-                        last_price = Some(price);
+                        let received_at = now_millis();
+                        let update = PriceUpdate::new(price, pair_info.source(), received_at);
+                        let _ = price_tx.send(update);
+                        last_update = Some(update);
                         last_exchange_update = Instant::now();
                         received_first_tick = true;
-                        last_real_tick.store(now_millis(), Ordering::Relaxed);
+                        last_real_tick.store(received_at, Ordering::Relaxed);
                         heartbeat_delay.as_mut().reset(Instant::now() + HEARTBEAT_INTERVAL);
                         renegade_util::metrics::counter!("exchange_updates", "pair" => pair_info.to_topic()).increment(1);
                     }
@@ -411,14 +793,17 @@ impl GlobalPriceStreams {
             .map_err(ServerError::ExchangeConnection)
         {
             Ok(conn) => Ok(conn),
-            Err(e) => Self::exhaust_retries(e, pair_info, config, retry_timestamps).await,
+            Err(e) => {
+                Self::exhaust_retries(e, pair_info, pair_info, config, retry_timestamps).await
+            },
         }
     }
 
-    /// Attempt to re-establish an erroring exchange connection, exhausting
-    /// retries if necessary
+    /// Attempt to re-establish an erroring exchange connection to a source of
+    /// the given stream, exhausting retries if necessary
     async fn exhaust_retries(
         mut prev_err: ServerError,
+        stream_pair: &PairInfo,
         pair_info: &PairInfo,
         config: &ExchangeConnectionsConfig,
         retry_timestamps: &mut Vec<Instant>,
@@ -450,7 +835,14 @@ impl GlobalPriceStreams {
                 tokio::time::sleep(CONN_RETRY_DELAY).await;
             }
 
-            prev_err = match Self::retry_connection(pair_info, config, retry_timestamps).await {
+            prev_err = match Self::retry_connection(
+                stream_pair,
+                pair_info,
+                config,
+                retry_timestamps,
+            )
+            .await
+            {
                 Ok(conn) => {
                     log_task!(
                         Task::ExchangeConnection,
@@ -494,6 +886,7 @@ impl GlobalPriceStreams {
     ///
     /// Mirrors https://github.com/renegade-fi/renegade/blob/main/workers/price-reporter/src/reporter.rs#L470
     async fn retry_connection(
+        stream_pair: &PairInfo,
         pair_info: &PairInfo,
         config: &ExchangeConnectionsConfig,
         retry_timestamps: &mut Vec<Instant>,
@@ -518,12 +911,17 @@ impl GlobalPriceStreams {
         }
 
         // Reconnect
-        connect_exchange(pair_info.clone(), config).await.map_err(ServerError::ExchangeConnection)
+        Self::connect_source(stream_pair, pair_info, config)
+            .await
+            .map_err(ServerError::ExchangeConnection)
     }
 
     /// Returns a tuple of (canonicalized pair info, requires quote conversion),
     /// if needed
-    fn normalize_pair_info(&self, pair_info: PairInfo) -> Result<(PairInfo, bool), ServerError> {
+    pub fn normalize_pair_info(
+        &self,
+        pair_info: PairInfo,
+    ) -> Result<(PairInfo, bool), ServerError> {
         if pair_info.exchange != Exchange::Renegade {
             return Ok((pair_info, false));
         }
@@ -575,26 +973,26 @@ impl GlobalPriceStreams {
         &self,
         pair_info: PairInfo,
         config: ExchangeConnectionsConfig,
-    ) -> Result<Price, ServerError> {
+    ) -> Result<PriceUpdate, ServerError> {
         let (normalized_pair_info, requires_conversion) =
             self.normalize_pair_info(pair_info.clone())?;
 
         let price_rx =
             self.get_or_create_price_receiver(normalized_pair_info.clone(), config.clone()).await?;
-        let price = *price_rx.borrow();
-        if price == 0.0 {
+        let update = *price_rx.borrow();
+        if update.is_initial() {
             return Err(ServerError::PriceStreamClosed);
         }
 
         if requires_conversion {
             let conversion_rx = self.quote_conversion_stream(normalized_pair_info, config).await?;
-            let conversion_price = *conversion_rx.borrow();
-            if conversion_price == 0.0 {
+            let conversion = *conversion_rx.borrow();
+            if conversion.is_initial() {
                 return Err(ServerError::PriceStreamClosed);
             }
-            Ok(price / conversion_price)
+            Ok(update.convert(&conversion))
         } else {
-            Ok(price)
+            Ok(update)
         }
     }
 
@@ -613,4 +1011,558 @@ impl GlobalPriceStreams {
 
         self.init_price_stream(pair_info, config).await
     }
+
+    // ---------------------
+    // | Composite Streams |
+    // ---------------------
+
+    /// Fetch a price stream for the given topic, returning the canonical topic
+    /// string alongside the stream
+    ///
+    /// Handles both single-exchange and composite topics.
+    pub async fn get_or_create_topic_stream(
+        &self,
+        topic: &str,
+        config: ExchangeConnectionsConfig,
+    ) -> Result<(String, PriceStream), ServerError> {
+        if is_composite_topic(topic) {
+            let pair = CompositePair::from_topic(topic)?;
+            let (price_rx, _) = self.get_or_create_composite_stream(pair.clone(), config).await?;
+            return Ok((pair.to_topic(), PriceStream::new(price_rx.into())));
+        }
+
+        let pair_info = PairInfo::from_topic(topic)?;
+        let stream = self.get_or_create_price_stream(pair_info.clone(), config).await?;
+        Ok((get_price_topic_str(&pair_info.into()), stream))
+    }
+
+    /// Get the current price for the given topic, handling both
+    /// single-exchange and composite topics
+    ///
+    /// Reading a composite topic keeps its stream alive, and waits for the
+    /// stream's first aggregate if it has not published one yet.
+    pub async fn get_current_topic_price(
+        &self,
+        topic: &str,
+        config: ExchangeConnectionsConfig,
+    ) -> Result<PriceUpdate, ServerError> {
+        if !is_composite_topic(topic) {
+            let pair_info = PairInfo::from_topic(topic)?;
+            return self.get_current_price(pair_info, config).await;
+        }
+
+        let pair = CompositePair::from_topic(topic)?;
+        let (mut price_rx, last_read) = self.get_or_create_composite_stream(pair, config).await?;
+        last_read.store(now_millis(), Ordering::Relaxed);
+
+        let first_aggregate = price_rx.wait_for(|update| !update.is_initial());
+        match tokio::time::timeout(FIRST_UPDATE_TIMEOUT, first_aggregate).await {
+            Ok(Ok(update)) => Ok(*update),
+            _ => Err(ServerError::PriceStreamClosed),
+        }
+    }
+
+    /// Get the price receiver and last HTTP read time of the composite stream
+    /// for the given pair, creating the stream if necessary
+    async fn get_or_create_composite_stream(
+        &self,
+        pair: CompositePair,
+        config: ExchangeConnectionsConfig,
+    ) -> Result<(PriceReceiver, LastRead), ServerError> {
+        if let Some(stream) = self.composite_streams.read().await.get(&pair).cloned() {
+            return Ok(stream);
+        }
+
+        // Subscribe to each source exchange, skipping those that fail to
+        // initialize; the composite is computed over whichever sources remain
+        let mut sources = StreamMap::new();
+        for source in pair.source_pairs(&self.composite_config, &config) {
+            let exchange = source.exchange;
+            match self.get_or_create_source_stream(source, config.clone()).await {
+                Ok(stream) => {
+                    sources.insert(exchange, stream);
+                },
+                Err(e) => {
+                    log_task!(
+                        Task::PriceStream,
+                        Outcome::Partial,
+                        subject = %pair.to_topic(),
+                        exchange = %exchange,
+                        error = %e,
+                        "skipping composite price source"
+                    );
+                },
+            }
+        }
+
+        if sources.is_empty() {
+            return Err(ServerError::InvalidPairInfo(format!(
+                "no price sources available for {}",
+                pair.to_topic()
+            )));
+        }
+
+        // The volume-weighted method weights each source by its depth feed,
+        // where one is available
+        let mut depths = StreamMap::new();
+        if self.composite_config.method == CompositeMethod::VolumeWeighted {
+            for source in pair.source_pairs(&self.composite_config, &config) {
+                let exchange = source.exchange;
+                if let Ok(depth_rx) =
+                    self.get_or_create_depth_receiver(source, config.clone()).await
+                {
+                    depths.insert(exchange, WatchStream::new(depth_rx));
+                }
+            }
+        }
+
+        let (price_tx, price_rx) = channel(PriceUpdate::default());
+        let last_read = LastRead::default();
+        {
+            // Check again under the write lock so that concurrent callers do
+            // not spawn duplicate composite tasks
+            let mut composite_streams = self.composite_streams.write().await;
+            if let Some(stream) = composite_streams.get(&pair).cloned() {
+                return Ok(stream);
+            }
+            composite_streams.insert(pair.clone(), (price_rx.clone(), last_read.clone()));
+        }
+
+        // The receivers held by the stream map and the history recorder do not
+        // count as subscribers, while HTTP reads keep the stream alive
+        let mut retained_receivers = 1;
+        if let Some(history) = &self.price_history {
+            history.watch(pair.to_topic(), price_rx.clone());
+            retained_receivers += 1;
+        }
+
+        log_task!(
+            Task::PriceStream,
+            Outcome::Started,
+            subject = %pair.to_topic(),
+            num_sources = sources.len(),
+            "initializing composite price stream"
+        );
+
+        let task_last_read = last_read.clone();
+        let global_price_streams = self.clone();
+        tokio::spawn(async move {
+            let task =
+                global_price_streams.composite_stream_task(&pair, sources, depths, &price_tx);
+            tokio::pin!(task);
+            loop {
+                tokio::select! {
+                    _ = &mut task => break,
+                    _ = wait_for_idle(&price_tx, retained_receivers, &task_last_read) => {
+                        let composite_streams = &global_price_streams.composite_streams;
+                        if remove_if_idle(composite_streams, &pair, &price_tx, retained_receivers, &task_last_read).await {
+                            log_task!(
+                                Task::PriceStream,
+                                Outcome::Ok,
+                                subject = %pair.to_topic(),
+                                "tearing down composite price stream without subscribers"
+                            );
+                            return;
+                        }
+                    }
+                }
+            }
+
+            global_price_streams.composite_streams.write().await.remove(&pair);
+        });
+
+        Ok((price_rx, last_read))
+    }
+
+    /// Get a price stream for a composite source, converting its quote into
+    /// USDC if necessary
+    async fn get_or_create_source_stream(
+        &self,
+        source: PairInfo,
+        config: ExchangeConnectionsConfig,
+    ) -> Result<PriceStream, ServerError> {
+        let price_rx = self.get_or_create_price_receiver(source.clone(), config.clone()).await?;
+        if !source.requires_usdc_conversion() {
+            return Ok(PriceStream::new(price_rx.into()));
+        }
+
+        let conversion_pair = source.get_conversion_pair()?;
+        let conversion_rx = self.get_or_create_price_receiver(conversion_pair, config).await?;
+        Ok(PriceStream::new_with_conversion(price_rx.into(), conversion_rx.into()))
+    }
+
+    /// The task responsible for aggregating source prices into a composite
+    /// price
+    ///
+    /// Exits once every source stream has closed.
+    async fn composite_stream_task(
+        &self,
+        pair: &CompositePair,
+        mut sources: StreamMap<Exchange, PriceStream>,
+        mut depths: StreamMap<Exchange, WatchStream<Option<DepthSnapshot>>>,
+        price_tx: &PriceSender,
+    ) {
+        let topic = pair.to_topic();
+        let mut latest_prices: HashMap<Exchange, PriceUpdate> = HashMap::new();
+        let mut volumes: HashMap<Exchange, f64> = HashMap::new();
+
+        loop {
+            let (exchange, update) = tokio::select! {
+                Some((exchange, depth)) = depths.next(), if !depths.is_empty() => {
+                    match depth.as_ref().map(DepthSnapshot::total_size) {
+                        Some(volume) => volumes.insert(exchange, volume),
+                        None => volumes.remove(&exchange),
+                    };
+                    continue;
+                }
+                maybe_update = sources.next() => match maybe_update {
+                    Some(source_update) => source_update,
+                    None => break,
+                }
+            };
+
+            // Sources are aged by their original receive time, so heartbeat
+            // replays of a stalled exchange do not keep it in the composite
+            latest_prices.insert(exchange, update);
+            retain_fresh_sources(&mut latest_prices, now_millis());
+
+            let source_prices = latest_prices
+                .iter()
+                .map(|(exchange, update)| (*exchange, update.price))
+                .collect_vec();
+            let composite = aggregate_prices(&source_prices, &volumes, &self.composite_config);
+
+            // The composite is only as fresh as its oldest in-band source
+            let received_at = latest_prices
+                .iter()
+                .filter(|(exchange, _)| !composite.rejected.contains(exchange))
+                .map(|(_, update)| update.received_at)
+                .min()
+                .unwrap_or_default();
+
+            for exchange in composite.rejected {
+                renegade_util::metrics::counter!(
+                    "composite_source_rejections",
+                    "pair" => topic.clone(),
+                    "exchange" => exchange.to_string(),
+                )
+                .increment(1);
+            }
+
+            if let Some(price) = composite.price {
+                let _ = price_tx.send(PriceUpdate::new_composite(price, received_at));
+            }
+        }
+
+        log_task!(
+            Task::PriceStream,
+            Outcome::Ok,
+            subject = %topic,
+            "all composite price sources closed"
+        );
+    }
+
+    // -----------------
+    // | Price History |
+    // -----------------
+
+    /// Get the price recorder, erroring if price history is disabled
+    fn price_history(&self) -> Result<&PriceHistory, ServerError> {
+        self.price_history.as_ref().ok_or(ServerError::PriceHistoryDisabled)
+    }
+
+    /// Resolve a topic into the topic under which its prices are recorded and,
+    /// if its price is converted into USDC, the recorded topic of the
+    /// conversion pair
+    fn resolve_history_topics(&self, topic: &str) -> Result<(String, Option<String>), ServerError> {
+        if is_composite_topic(topic) {
+            return Ok((CompositePair::from_topic(topic)?.to_topic(), None));
+        }
+
+        let pair_info = PairInfo::from_topic(topic)?;
+        let (normalized_pair_info, requires_conversion) = self.normalize_pair_info(pair_info)?;
+        let conversion_topic = if requires_conversion {
+            let conversion_pair = normalized_pair_info.get_conversion_pair()?;
+            Some(get_price_topic_str(&conversion_pair.into()))
+        } else {
+            None
+        };
+
+        Ok((get_price_topic_str(&normalized_pair_info.into()), conversion_topic))
+    }
+
+    /// Get the price in effect on the given topic at the given time, in
+    /// milliseconds since the epoch
+    ///
+    /// Returns `None` if no price was recorded at or before the given time.
+    pub async fn get_price_at(
+        &self,
+        topic: &str,
+        timestamp: u64,
+    ) -> Result<Option<PriceTick>, ServerError> {
+        let history = self.price_history()?;
+        let (topic, conversion_topic) = self.resolve_history_topics(topic)?;
+
+        let Some(tick) = history.price_at(&topic, timestamp).await else {
+            return Ok(None);
+        };
+        let Some(conversion_topic) = conversion_topic else {
+            return Ok(Some(tick));
+        };
+
+        Ok(history.price_at(&conversion_topic, timestamp).await.map(|conversion_tick| PriceTick {
+            timestamp: tick.timestamp.max(conversion_tick.timestamp),
+            price: tick.price / conversion_tick.price,
+        }))
+    }
+
+    /// Get OHLC candles of the given interval on the given topic over the
+    /// window `[start, end)`, all in milliseconds
+    pub async fn get_candles(
+        &self,
+        topic: &str,
+        start: u64,
+        end: u64,
+        interval: u64,
+    ) -> Result<Vec<Candle>, ServerError> {
+        if interval == 0 || start >= end {
+            return Err(ServerError::InvalidRequest(
+                "expected a non-empty window and a positive interval".to_string(),
+            ));
+        }
+        if (end - start).div_ceil(interval) > MAX_CANDLES {
+            return Err(ServerError::InvalidRequest(format!(
+                "at most {MAX_CANDLES} candles may be requested"
+            )));
+        }
+
+        let history = self.price_history()?;
+        let (topic, conversion_topic) = self.resolve_history_topics(topic)?;
+
+        let mut ticks = history.ticks(&topic, start, end).await;
+        if let Some(conversion_topic) = conversion_topic {
+            let conversion_ticks = history.ticks(&conversion_topic, start, end).await;
+            ticks = convert_ticks(&ticks, &conversion_ticks);
+        }
+
+        Ok(build_candles(&ticks, start, end, interval))
+    }
+
+    // -----------------
+    // | Depth Streams |
+    // -----------------
+
+    /// Get the current depth snapshot for the given pair
+    ///
+    /// The read keeps the depth stream alive, and waits for the stream's first
+    /// snapshot if it has not published one yet.
+    pub async fn get_current_depth(
+        &self,
+        pair_info: PairInfo,
+        config: ExchangeConnectionsConfig,
+    ) -> Result<DepthSnapshot, ServerError> {
+        let (mut depth_rx, last_read) = self.get_or_create_depth_stream(pair_info, config).await?;
+        last_read.store(now_millis(), Ordering::Relaxed);
+
+        let first_snapshot = depth_rx.wait_for(Option::is_some);
+        match tokio::time::timeout(FIRST_UPDATE_TIMEOUT, first_snapshot).await {
+            Ok(Ok(depth)) => depth.clone().ok_or(ServerError::PriceStreamClosed),
+            _ => Err(ServerError::PriceStreamClosed),
+        }
+    }
+
+    /// Get a depth receiver for the given pair or create a new depth stream
+    pub async fn get_or_create_depth_receiver(
+        &self,
+        pair_info: PairInfo,
+        config: ExchangeConnectionsConfig,
+    ) -> Result<DepthReceiver, ServerError> {
+        let (depth_rx, _) = self.get_or_create_depth_stream(pair_info, config).await?;
+        Ok(depth_rx)
+    }
+
+    /// Get the depth receiver and last HTTP read time of the depth stream for
+    /// the given pair, creating the stream if necessary
+    async fn get_or_create_depth_stream(
+        &self,
+        pair_info: PairInfo,
+        config: ExchangeConnectionsConfig,
+    ) -> Result<(DepthReceiver, LastRead), ServerError> {
+        if let Some(stream) = self.depth_streams.read().await.get(&pair_info).cloned() {
+            return Ok(stream);
+        }
+
+        pair_info.validate_depth_subscription(&config).await?;
+
+        let (depth_tx, depth_rx) = channel(None);
+        let last_read = LastRead::default();
+        {
+            // Check again under the write lock so that concurrent callers do
+            // not spawn duplicate depth tasks
+            let mut depth_streams = self.depth_streams.write().await;
+            if let Some(stream) = depth_streams.get(&pair_info).cloned() {
+                return Ok(stream);
+            }
+            depth_streams.insert(pair_info.clone(), (depth_rx.clone(), last_read.clone()));
+        }
+
+        log_task!(
+            Task::PriceStream,
+            Outcome::Started,
+            subject = %pair_info.to_topic(),
+            "initializing depth stream"
+        );
+
+        // Depth feeds are auxiliary to the price feeds, so a failed depth
+        // stream is torn down without signalling the closure channel. Depth
+        // streams are created on demand, so they are also torn down once they
+        // have no subscribers beyond the receiver held by the stream map, and
+        // have not been read over HTTP for a while
+        let retained_receivers = 1;
+        let task_last_read = last_read.clone();
+        let global_price_streams = self.clone();
+        tokio::spawn(async move {
+            let task = Self::depth_stream_task(&pair_info, &config, &depth_tx);
+            tokio::pin!(task);
+            let res = loop {
+                tokio::select! {
+                    res = &mut task => break res,
+                    _ = wait_for_idle(&depth_tx, retained_receivers, &task_last_read) => {
+                        let depth_streams = &global_price_streams.depth_streams;
+                        if remove_if_idle(depth_streams, &pair_info, &depth_tx, retained_receivers, &task_last_read).await {
+                            log_task!(
+                                Task::PriceStream,
+                                Outcome::Ok,
+                                subject = %pair_info.to_topic(),
+                                "tearing down depth stream without subscribers"
+                            );
+                            return;
+                        }
+                    }
+                }
+            };
+
+            if let Err(e) = res {
+                log_task!(
+                    Task::PriceStream,
+                    Outcome::Failed,
+                    subject = %pair_info.to_topic(),
+                    error = %e,
+                    "depth stream closed"
+                );
+            }
+            global_price_streams.depth_streams.write().await.remove(&pair_info);
+        });
+
+        Ok((depth_rx, last_read))
+    }
+
+    /// The task responsible for streaming depth snapshots from the exchange,
+    /// reconnecting until `MAX_CONN_RETRIES` is exhausted within the retry
+    /// window
+    async fn depth_stream_task(
+        pair_info: &PairInfo,
+        config: &ExchangeConnectionsConfig,
+        depth_tx: &DepthSender,
+    ) -> Result<(), ServerError> {
+        let mut retry_timestamps: Vec<Instant> = Vec::new();
+        loop {
+            let err = match connect_depth(pair_info.clone(), config).await {
+                Ok(mut conn) => Self::manage_depth_connection(&mut conn, depth_tx, pair_info).await,
+                Err(e) => ServerError::ExchangeConnection(e),
+            };
+
+            log_task!(
+                Task::ExchangeConnection,
+                Outcome::Failed,
+                subject = %pair_info.to_topic(),
+                error = %err,
+                "error in depth connection"
+            );
+
+            let now = Instant::now();
+            retry_timestamps.retain(|ts| now.duration_since(*ts) < MAX_CONN_RETRY_WINDOW);
+            retry_timestamps.push(now);
+            if retry_timestamps.len() >= MAX_CONN_RETRIES {
+                return Err(ServerError::ExchangeConnection(ExchangeConnectionError::MaxRetries(
+                    pair_info.exchange,
+                )));
+            }
+
+            // Clear the last snapshot so that consumers do not read a stale book
+            // while reconnecting
+            let _ = depth_tx.send(None);
+            let delay =
+                if err.is_rate_limit_error() { RATE_LIMIT_RETRY_DELAY } else { CONN_RETRY_DELAY };
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Manages a depth connection, sending keepalive messages and forwarding
+    /// snapshots to the depth receiver. Returns the error that ended the
+    /// connection.
+    async fn manage_depth_connection(
+        conn: &mut Box<dyn DepthConnection>,
+        depth_tx: &DepthSender,
+        pair_info: &PairInfo,
+    ) -> ServerError {
+        let keepalive_delay = tokio::time::sleep(KEEPALIVE_INTERVAL);
+        let stale_deadline = tokio::time::sleep(MAX_HEARTBEAT_AGE);
+        tokio::pin!(keepalive_delay);
+        tokio::pin!(stale_deadline);
+
+        loop {
+            tokio::select! {
+                _ = &mut keepalive_delay => {
+                    if let Err(e) = conn.send_keepalive().await {
+                        return ServerError::ExchangeConnection(e);
+                    }
+                    keepalive_delay.as_mut().reset(Instant::now() + KEEPALIVE_INTERVAL);
+                }
+
+                // Treat the connection as dead if the book has not updated
+                // within the max heartbeat age
+                _ = &mut stale_deadline => {
+                    return ServerError::ExchangeConnection(ExchangeConnectionError::ConnectionHangup(
+                        format!("no depth update for {:?}", MAX_HEARTBEAT_AGE),
+                    ));
+                }
+
+                maybe_depth = conn.next() => match maybe_depth {
+                    Some(Ok(depth)) => {
+                        let _ = depth_tx.send(Some(depth));
+                        stale_deadline.as_mut().reset(Instant::now() + MAX_HEARTBEAT_AGE);
+                        renegade_util::metrics::counter!("exchange_depth_updates", "pair" => pair_info.to_topic()).increment(1);
+                    }
+                    Some(Err(e)) => return ServerError::ExchangeConnection(e),
+                    None => {
+                        let msg = format!("Depth stream for {} has closed", pair_info.to_topic());
+                        return ServerError::ExchangeConnection(ExchangeConnectionError::ConnectionHangup(msg));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stream_read_over_http_is_not_idle() {
+        let streams = RwLock::new(HashMap::from([("topic", ())]));
+        let (tx, _rx) = channel(());
+        let last_read = LastRead::default();
+
+        // The stream map's receiver is retained, so the stream is only kept
+        // alive by its recent HTTP read
+        last_read.store(now_millis(), Ordering::Relaxed);
+        assert!(!remove_if_idle(&streams, &"topic", &tx, 1, &last_read).await);
+        assert!(streams.read().await.contains_key("topic"));
+
+        last_read.store(0, Ordering::Relaxed);
+        assert!(remove_if_idle(&streams, &"topic", &tx, 1, &last_read).await);
+        assert!(streams.read().await.is_empty());
+    }
 }
@@ -0,0 +1,406 @@
+//! Hot-reloading of the token remap
+//!
+//! The token remap and canonical exchange map are reloaded when the admin
+//! refresh route is hit, and, if a poll interval is configured, periodically
+//! in the background. Each reload diffs the loaded tokens and canonical
+//! exchanges against those of the last applied remap, and starts or cancels
+//! only the price streams affected by the change. `Renegade` streams of tokens
+//! whose canonical exchange changed are restarted on the new canonical
+//! exchange. The last applied diff and a version counter are exposed through
+//! an admin route.
+
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use renegade_types_core::{Chain, Exchange, get_all_base_tokens};
+use renegade_util::{err_str, get_current_time_millis};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::{
+    errors::ServerError,
+    exchanges::ExchangeConnectionsConfig,
+    init_default_price_streams, log_task,
+    logger::{Outcome, Task},
+    price_stream_manager::GlobalPriceStreams,
+    utils::{
+        CanonicalExchangeMap, PriceReporterConfig, get_canonical_exchange_map,
+        setup_all_token_remaps,
+    },
+};
+
+// ---------
+// | Types |
+// ---------
+
+/// The tokens and canonical exchanges loaded from a token remap
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct RemapSnapshot {
+    /// The tickers of the remapped base tokens, indexed by address
+    tokens: BTreeMap<String, String>,
+    /// The canonical exchange of each ticker
+    canonical_exchanges: BTreeMap<String, Exchange>,
+}
+
+impl RemapSnapshot {
+    /// Capture the currently loaded token remap
+    fn capture() -> Self {
+        let tokens = get_all_base_tokens()
+            .into_iter()
+            .filter_map(|token| Some((token.get_addr().to_lowercase(), token.get_ticker()?)))
+            .collect();
+        Self::new(tokens, get_canonical_exchange_map())
+    }
+
+    /// Create a snapshot from the given tokens and canonical exchanges
+    fn new(tokens: BTreeMap<String, String>, canonical_exchanges: CanonicalExchangeMap) -> Self {
+        Self { tokens, canonical_exchanges: canonical_exchanges.into_iter().collect() }
+    }
+}
+
+/// A token added to or removed from the remap
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TokenEntry {
+    /// The token's ticker
+    pub ticker: String,
+    /// The token's address
+    pub address: String,
+}
+
+/// A change to the canonical exchange of a ticker
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CanonicalExchangeChange {
+    /// The ticker whose canonical exchange changed
+    pub ticker: String,
+    /// The previous canonical exchange, if the ticker had one
+    pub previous: Option<String>,
+    /// The new canonical exchange, if the ticker has one
+    pub current: Option<String>,
+}
+
+/// The difference between two token remaps, along with the price streams
+/// started and cancelled to apply it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RemapDiff {
+    /// The tokens added to the remap
+    pub added_tokens: Vec<TokenEntry>,
+    /// The tokens removed from the remap
+    pub removed_tokens: Vec<TokenEntry>,
+    /// The tickers whose canonical exchange changed
+    pub canonical_exchange_changes: Vec<CanonicalExchangeChange>,
+    /// The topics of the price streams started to apply the diff
+    pub started_streams: Vec<String>,
+    /// The topics of the price streams cancelled to apply the diff
+    pub cancelled_streams: Vec<String>,
+    /// The topics of the `Renegade` price streams restarted on a new canonical
+    /// exchange to apply the diff
+    pub restarted_streams: Vec<String>,
+}
+
+impl RemapDiff {
+    /// Compute the difference in tokens and canonical exchanges between two
+    /// remaps
+    fn between(previous: &RemapSnapshot, current: &RemapSnapshot) -> Self {
+        let token_entries = |from: &RemapSnapshot, to: &RemapSnapshot| {
+            from.tokens
+                .iter()
+                .filter(|(address, _)| !to.tokens.contains_key(*address))
+                .map(|(address, ticker)| TokenEntry {
+                    ticker: ticker.clone(),
+                    address: address.clone(),
+                })
+                .collect()
+        };
+
+        let mut tickers: Vec<&String> =
+            previous.canonical_exchanges.keys().chain(current.canonical_exchanges.keys()).collect();
+        tickers.sort();
+        tickers.dedup();
+
+        let canonical_exchange_changes = tickers
+            .into_iter()
+            .filter_map(|ticker| {
+                let prev = previous.canonical_exchanges.get(ticker);
+                let curr = current.canonical_exchanges.get(ticker);
+                (prev != curr).then(|| CanonicalExchangeChange {
+                    ticker: ticker.clone(),
+                    previous: prev.map(ToString::to_string),
+                    current: curr.map(ToString::to_string),
+                })
+            })
+            .collect();
+
+        Self {
+            added_tokens: token_entries(current, previous),
+            removed_tokens: token_entries(previous, current),
+            canonical_exchange_changes,
+            ..Default::default()
+        }
+    }
+
+    /// The addresses of the tokens in the given remap whose canonical exchange
+    /// changed
+    fn canonical_exchange_changed_mints(&self, current: &RemapSnapshot) -> Vec<String> {
+        current
+            .tokens
+            .iter()
+            .filter(|(_, ticker)| {
+                self.canonical_exchange_changes.iter().any(|change| &change.ticker == *ticker)
+            })
+            .map(|(address, _)| address.clone())
+            .collect()
+    }
+
+    /// Whether the remap is unchanged
+    fn is_empty(&self) -> bool {
+        self.added_tokens.is_empty()
+            && self.removed_tokens.is_empty()
+            && self.canonical_exchange_changes.is_empty()
+    }
+}
+
+/// The state of the token remap, as reported by the admin status route
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RemapStatus {
+    /// The number of remap changes applied since startup
+    pub version: u64,
+    /// The time at which the remap was last checked for changes, in
+    /// milliseconds since the epoch
+    pub last_checked_at: Option<u64>,
+    /// The time at which a remap change was last applied, in milliseconds
+    /// since the epoch
+    pub last_applied_at: Option<u64>,
+    /// The last applied diff
+    pub last_diff: Option<RemapDiff>,
+}
+
+/// The mutable state of the reloader
+#[derive(Debug)]
+struct ReloaderState {
+    /// The last applied remap
+    snapshot: RemapSnapshot,
+    /// The modification time of the remap file when it was last loaded
+    file_modified: Option<SystemTime>,
+    /// The status reported to the admin route
+    status: RemapStatus,
+}
+
+// ------------
+// | Reloader |
+// ------------
+
+/// Reloads the token remap and applies the resulting diff to the price
+/// streams
+#[derive(Clone)]
+pub struct TokenRemapReloader {
+    /// The path to the token remap file, if the remap is not fetched from
+    /// Github
+    token_remap_path: Option<String>,
+    /// The chains to use for the token remap
+    chains: Vec<Chain>,
+    /// The global price streams
+    price_streams: GlobalPriceStreams,
+    /// The configuration for the exchange connections
+    config: ExchangeConnectionsConfig,
+    /// The exchanges for which to disable price reporting
+    disabled_exchanges: Vec<Exchange>,
+    /// The reloader state, locked for the duration of a reload so that
+    /// reloads do not interleave
+    state: Arc<Mutex<ReloaderState>>,
+}
+
+impl TokenRemapReloader {
+    /// Create a reloader, taking the currently loaded remap as the last
+    /// applied one
+    pub fn new(config: &PriceReporterConfig, price_streams: GlobalPriceStreams) -> Self {
+        let state = ReloaderState {
+            snapshot: RemapSnapshot::capture(),
+            file_modified: config.token_remap_path.as_deref().and_then(file_modified),
+            status: RemapStatus::default(),
+        };
+
+        Self {
+            token_remap_path: config.token_remap_path.clone(),
+            chains: config.chains.clone(),
+            price_streams,
+            config: config.exchange_conn_config.clone(),
+            disabled_exchanges: config.disabled_exchanges.clone(),
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// The current status of the token remap
+    pub async fn status(&self) -> RemapStatus {
+        self.state.lock().await.status.clone()
+    }
+
+    /// Reload the token remap and apply the diff to the price streams
+    ///
+    /// If `force` is set, the default price streams are re-initialized even
+    /// when the remap is unchanged, restarting any which have exited.
+    pub async fn reload(&self, force: bool) -> Result<RemapStatus, ServerError> {
+        let mut state = self.state.lock().await;
+        let file_modified = self.token_remap_path.as_deref().and_then(file_modified);
+        let token_remap_path = self.token_remap_path.clone();
+        let chains = self.chains.clone();
+        tokio::task::spawn_blocking(move || setup_all_token_remaps(token_remap_path, &chains))
+            .await
+            .map_err(err_str!(ServerError::TokenRemap))
+            .and_then(|res| res.map_err(err_str!(ServerError::TokenRemap)))?;
+
+        let snapshot = RemapSnapshot::capture();
+        let mut diff = RemapDiff::between(&state.snapshot, &snapshot);
+        let now = get_current_time_millis();
+        state.status.last_checked_at = Some(now);
+        state.file_modified = file_modified;
+        if diff.is_empty() && !force {
+            log_task!(Task::TokenRemap, Outcome::Skipped, "token remap unchanged");
+            return Ok(state.status.clone());
+        }
+
+        let changes = init_default_price_streams(
+            &self.price_streams,
+            &self.config,
+            self.disabled_exchanges.clone(),
+        )
+        .await?;
+        diff.started_streams = changes.started;
+        diff.cancelled_streams = changes.cancelled;
+
+        // Move `Renegade` streams onto their tokens' new canonical exchanges
+        let changed_mints = diff.canonical_exchange_changed_mints(&snapshot);
+        diff.restarted_streams = self.price_streams.restart_renegade_streams(&changed_mints);
+
+        log_task!(
+            Task::TokenRemap,
+            Outcome::Ok,
+            added_tokens = diff.added_tokens.len(),
+            removed_tokens = diff.removed_tokens.len(),
+            canonical_exchange_changes = diff.canonical_exchange_changes.len(),
+            started_streams = diff.started_streams.len(),
+            cancelled_streams = diff.cancelled_streams.len(),
+            restarted_streams = diff.restarted_streams.len(),
+            "applied token remap"
+        );
+
+        state.snapshot = snapshot;
+        state.status.version += 1;
+        state.status.last_applied_at = Some(now);
+        state.status.last_diff = Some(diff);
+        Ok(state.status.clone())
+    }
+
+    /// Poll the token remap for changes at the given interval
+    ///
+    /// A remap file is only reloaded once its modification time changes; a
+    /// remap fetched from Github is re-fetched on every poll.
+    pub async fn poll_loop(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately, and the remap was just loaded
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if !self.remap_file_changed().await {
+                continue;
+            }
+
+            if let Err(e) = self.reload(false /* force */).await {
+                log_task!(
+                    Task::TokenRemap,
+                    Outcome::Failed,
+                    error = %e,
+                    "error reloading token remap"
+                );
+            }
+        }
+    }
+
+    /// Whether the remap file has been modified since it was last loaded
+    ///
+    /// Always true when the remap is fetched from Github.
+    async fn remap_file_changed(&self) -> bool {
+        let Some(path) = self.token_remap_path.as_deref() else {
+            return true;
+        };
+
+        let modified = file_modified(path);
+        modified.is_some() && modified != self.state.lock().await.file_modified
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Get the modification time of the file at the given path
+fn file_modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a snapshot from (address, ticker) and (ticker, exchange) pairs
+    fn snapshot(tokens: &[(&str, &str)], canonical: &[(&str, Exchange)]) -> RemapSnapshot {
+        let tokens = tokens.iter().map(|(addr, ticker)| (addr.to_string(), ticker.to_string()));
+        let canonical = canonical.iter().map(|(ticker, exchange)| (ticker.to_string(), *exchange));
+        RemapSnapshot::new(tokens.collect(), canonical.collect())
+    }
+
+    #[test]
+    fn diff_tokens_and_canonical_exchanges() {
+        let previous = snapshot(
+            &[("0x1", "WETH"), ("0x2", "WBTC")],
+            &[("WETH", Exchange::Binance), ("WBTC", Exchange::Binance)],
+        );
+        let current = snapshot(
+            &[("0x1", "WETH"), ("0x3", "ARB")],
+            &[("WETH", Exchange::Okx), ("ARB", Exchange::Binance)],
+        );
+
+        let diff = RemapDiff::between(&previous, &current);
+        let entry = |ticker: &str, address: &str| TokenEntry {
+            ticker: ticker.to_string(),
+            address: address.to_string(),
+        };
+        assert_eq!(diff.added_tokens, vec![entry("ARB", "0x3")]);
+        assert_eq!(diff.removed_tokens, vec![entry("WBTC", "0x2")]);
+
+        let changed: Vec<_> = diff
+            .canonical_exchange_changes
+            .iter()
+            .map(|change| {
+                (change.ticker.as_str(), change.previous.is_some(), change.current.is_some())
+            })
+            .collect();
+        assert_eq!(
+            changed,
+            vec![("ARB", false, true), ("WBTC", true, false), ("WETH", true, true)]
+        );
+
+        // Only tokens still in the remap have `Renegade` streams to restart
+        assert_eq!(diff.canonical_exchange_changed_mints(&current), vec!["0x1", "0x3"]);
+    }
+
+    #[test]
+    fn only_changed_canonical_exchanges_restart_streams() {
+        let previous = snapshot(&[("0x1", "WETH")], &[("WETH", Exchange::Binance)]);
+        let current = snapshot(
+            &[("0x1", "WETH"), ("0x2", "WBTC")],
+            &[("WETH", Exchange::Binance), ("WBTC", Exchange::Okx)],
+        );
+
+        let diff = RemapDiff::between(&previous, &current);
+        assert_eq!(diff.canonical_exchange_changed_mints(&current), vec!["0x2"]);
+    }
+
+    #[test]
+    fn unchanged_remap_has_empty_diff() {
+        let remap = snapshot(&[("0x1", "WETH")], &[("WETH", Exchange::Binance)]);
+        assert!(RemapDiff::between(&remap, &remap.clone()).is_empty());
+    }
+}
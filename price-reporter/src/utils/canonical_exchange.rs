@@ -22,7 +22,7 @@ use crate::{errors::ServerError, utils::get_token_and_chain};
 
 /// A type alias representing the mapping from a token ticker to the canonical
 /// exchange to use as a price source
-type CanonicalExchangeMap = HashMap<String, Exchange>;
+pub type CanonicalExchangeMap = HashMap<String, Exchange>;
 
 /// The mapping from ERC-20 ticker to the canonical exchange to use as a price
 /// source
@@ -49,29 +49,38 @@ pub fn get_canonical_exchange(mint: &str) -> Result<Exchange, ServerError> {
 }
 
 /// Set the static mapping of token tickers to the canonical exchange to use as
-/// a price source
+/// a price source, from the remaps of the given chains
+///
+/// The new mapping replaces the existing one in a single write, so that a
+/// reload drops tickers removed from the remap without briefly exposing an
+/// empty mapping.
 pub fn set_canonical_exchange_map(
     remap_file: Option<String>,
-    chain: Chain,
+    chains: &[Chain],
 ) -> Result<(), ServerError> {
-    let map = if let Some(file) = remap_file {
-        parse_remap_from_file(file)
-    } else {
-        fetch_remap_from_repo(chain)
-    }
-    .map_err(err_str!(ServerError::TokenRemap))?;
-
-    let chain_canonical_exchange_map = map.get_canonical_exchange_map();
-
-    let mut global_canonical_exchange_map = write_canonical_exchange_map();
+    let mut canonical_exchange_map = CanonicalExchangeMap::new();
+    for chain in chains {
+        let map = if let Some(file) = remap_file.clone() {
+            parse_remap_from_file(file)
+        } else {
+            fetch_remap_from_repo(*chain)
+        }
+        .map_err(err_str!(ServerError::TokenRemap))?;
 
-    // We extend to effectively merge the two maps. This is safe because we
-    // assume each ticker has one canonical exchange.
-    global_canonical_exchange_map.extend(chain_canonical_exchange_map);
+        // We extend to effectively merge the chains' maps. This is safe because
+        // we assume each ticker has one canonical exchange.
+        canonical_exchange_map.extend(map.get_canonical_exchange_map());
+    }
 
+    *write_canonical_exchange_map() = canonical_exchange_map;
     Ok(())
 }
 
+/// Get a copy of the current canonical exchange mapping
+pub fn get_canonical_exchange_map() -> CanonicalExchangeMap {
+    read_canonical_exchange_map().clone()
+}
+
 /// Returns a read lock guard to the canonical exchange map
 fn read_canonical_exchange_map<'a>() -> RwLockReadGuard<'a, CanonicalExchangeMap> {
     CANONICAL_EXCHANGE_MAP.read().expect("Canonical exchange map lock poisoned")
@@ -3,7 +3,10 @@
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, atomic::AtomicU64},
+};
 
 use futures_util::StreamExt;
 use futures_util::{Stream, stream::SplitSink};
@@ -14,7 +17,7 @@ use renegade_types_core::{
     Chain, Exchange, HmacKey, Price, Token, USDC_TICKER,
     default_exchange_stable as _default_exchange_stable, read_token_remaps,
 };
-use renegade_util::err_str;
+use renegade_util::{err_str, get_current_time_millis};
 use serde::{Deserialize, Serialize};
 use tokio::{
     net::TcpStream,
@@ -26,14 +29,23 @@ use tokio_tungstenite::WebSocketStream;
 use tokio_util::sync::CancellationToken;
 use tungstenite::Message;
 
-use crate::exchanges::ExchangeConnectionsConfig;
+use crate::api_keys::ApiKeys;
+use crate::composite::{CompositeConfig, CompositePair};
+use crate::exchanges::{ExchangeConnectionsConfig, depth::DepthSnapshot, venue::PriceSource};
+use crate::price_history::PriceHistoryConfig;
 use crate::{errors::ServerError, http_server::routes::Handler};
 
 mod canonical_exchange;
 mod pair_info;
+mod price_update;
+mod throttle;
 
-pub use canonical_exchange::set_canonical_exchange_map;
+pub use canonical_exchange::{
+    CanonicalExchangeMap, get_canonical_exchange_map, set_canonical_exchange_map,
+};
 pub use pair_info::PairInfo;
+pub use price_update::PriceUpdate;
+pub use throttle::{SubscriptionOptions, ThrottledPriceStream};
 
 // ----------
 // | CONSTS |
@@ -77,28 +89,73 @@ pub const SUBSCRIBE_ACK_TIMEOUT: Duration = Duration::from_secs(10);
 pub const FEED_AGE_EMIT_INTERVAL: Duration = Duration::from_secs(5);
 /// The replay-age threshold above which `manage_connection` emits a per-tick
 /// warn log. Below this, replay is treated as normal slow-market behavior.
+///
+/// Published prices older than this are flagged as stale.
 pub const HEARTBEAT_REPLAY_WARN_AGE: Duration = Duration::from_secs(30);
 
+/// How long an on-demand stream may go without subscribers before it is torn
+/// down.
+///
+/// Streams are checked at this interval, so an idle stream is torn down
+/// between one and two intervals after its last subscriber leaves.
+pub const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long an HTTP read of an on-demand stream waits for the stream's first
+/// update, e.g. when the read itself created the stream
+pub const FIRST_UPDATE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The prefix identifying an order book depth topic
+pub const DEPTH_TOPIC_PREFIX: &str = "depth";
+
+/// The number of basis points in one
+pub const BPS_PER_ONE: f64 = 10_000.;
+
 // ---------
 // | TYPES |
 // ---------
 
-/// A type alias for a tuple of (exchange, base token, quote token)
-pub type PriceTopic = (Exchange, Token, Token);
+/// A type alias for a tuple of (price source, base token, quote token)
+pub type PriceTopic = (PriceSource, Token, Token);
 
 /// A type alias for the sender end of a price channel
-pub type PriceSender = WatchSender<Price>;
+pub type PriceSender = WatchSender<PriceUpdate>;
 
 /// A type alias for a price receiver
-pub type PriceReceiver = WatchReceiver<Price>;
+pub type PriceReceiver = WatchReceiver<PriceUpdate>;
 
 /// A type alias for a shareable map of price streams, indexed by the (source,
 /// base, quote) tuple
 // TODO: Replace w/ `DashMap`?
 pub type SharedPriceStreams = Arc<RwLock<HashMap<PairInfo, (PriceReceiver, CancellationToken)>>>;
 
+/// A type alias for a shareable map of composite price streams, indexed by the
+/// composite pair
+pub type SharedCompositeStreams = Arc<RwLock<HashMap<CompositePair, (PriceReceiver, LastRead)>>>;
+
+/// A type alias for the sender end of a depth channel
+pub type DepthSender = WatchSender<Option<DepthSnapshot>>;
+
+/// A type alias for a depth receiver
+pub type DepthReceiver = WatchReceiver<Option<DepthSnapshot>>;
+
+/// The wall-clock time, in milliseconds since the epoch, at which an
+/// on-demand stream was last read over HTTP
+///
+/// HTTP reads do not hold a receiver, so this keeps a stream that is only read
+/// over HTTP from being torn down as idle. A value of `0` means the stream has
+/// never been read over HTTP.
+pub type LastRead = Arc<AtomicU64>;
+
+/// A type alias for a shareable map of depth streams, indexed by the (source,
+/// base, quote) tuple
+pub type SharedDepthStreams = Arc<RwLock<HashMap<PairInfo, (DepthReceiver, LastRead)>>>;
+
+/// A type alias for a mapped stream of depth snapshots, indexed by the topic
+/// string
+pub type DepthStreamMap = StreamMap<String, WatchStream<Option<DepthSnapshot>>>;
+
 /// A type alias for a price stream
-pub type SinglePriceStream = WatchStream<Price>;
+pub type SinglePriceStream = WatchStream<PriceUpdate>;
 /// A price stream, containing the watch underlying the stream and an optional
 /// second watch for converting quote tokens
 pub struct PriceStream {
@@ -109,7 +166,7 @@ pub struct PriceStream {
 }
 
 impl Stream for PriceStream {
-    type Item = Price;
+    type Item = PriceUpdate;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.get_mut();
@@ -118,7 +175,7 @@ impl Stream for PriceStream {
         // initial value
         let main_price = loop {
             match this.stream.poll_next_unpin(cx) {
-                Poll::Ready(Some(0.0)) => continue,
+                Poll::Ready(Some(p)) if p.is_initial() => continue,
                 Poll::Ready(Some(p)) => break p,
                 Poll::Ready(None) => return Poll::Ready(None),
                 Poll::Pending => return Poll::Pending,
@@ -135,7 +192,7 @@ impl Stream for PriceStream {
         let conv = this.conversion_stream.as_mut().unwrap();
         let conversion_price = loop {
             match conv.poll_next_unpin(cx) {
-                Poll::Ready(Some(0.0)) => continue,
+                Poll::Ready(Some(p)) if p.is_initial() => continue,
                 Poll::Ready(Some(p)) => break p,
                 Poll::Ready(None) => return Poll::Ready(None),
                 Poll::Pending => return Poll::Pending,
@@ -144,7 +201,7 @@ impl Stream for PriceStream {
 
         // Divide main price by conversion price
         // Practically this will be [USDT / BASE] / [USDT / USDC] = USDC / BASE
-        let converted_price = main_price / conversion_price;
+        let converted_price = main_price.convert(&conversion_price);
 
         Poll::Ready(Some(converted_price))
     }
@@ -165,9 +222,9 @@ impl PriceStream {
     }
 }
 
-/// A type alias for a mapped stream prices, indexed by the (source, base,
-/// quote) tuple
-pub type PriceStreamMap = StreamMap<PriceTopic, PriceStream>;
+/// A type alias for a mapped stream of throttled prices, indexed by the topic
+/// string
+pub type PriceStreamMap = StreamMap<String, ThrottledPriceStream>;
 
 /// A type alias for a websocket write stream
 pub type WsWriteStream = SplitSink<WebSocketStream<TcpStream>, Message>;
@@ -189,6 +246,60 @@ pub struct PriceMessage {
     pub topic: String,
     /// The new price
     pub price: Price,
+    /// The exchange from which the price was sourced, or `composite` for
+    /// prices aggregated across exchanges
+    pub source: String,
+    /// The time at which the exchange reported the price, in milliseconds
+    /// since the epoch, if the exchange reports one
+    pub exchange_timestamp: Option<u64>,
+    /// The local time at which the price was received from the exchange, in
+    /// milliseconds since the epoch
+    pub received_at: u64,
+    /// The age of the price when the message was built, in milliseconds
+    pub age_ms: u64,
+    /// Whether the price is older than `HEARTBEAT_REPLAY_WARN_AGE`
+    pub stale: bool,
+}
+
+impl PriceMessage {
+    /// Build a price message for the given topic from a price update
+    pub fn new(topic: String, update: &PriceUpdate) -> Self {
+        let now = get_current_time_millis();
+        Self {
+            topic,
+            price: update.price,
+            source: update.source_name(),
+            exchange_timestamp: update.exchange_timestamp,
+            received_at: update.received_at,
+            age_ms: update.age_ms(now),
+            stale: update.is_stale(now),
+        }
+    }
+}
+
+/// A message that is sent by the price reporter to the client indicating
+/// an order book depth update for the given topic
+#[derive(Serialize, Deserialize)]
+pub struct DepthMessage {
+    /// The topic for which the depth update is being sent
+    pub topic: String,
+    /// The new depth snapshot
+    pub depth: DepthSnapshot,
+}
+
+/// A message that is sent by the price reporter to the client indicating that
+/// a request failed
+#[derive(Serialize, Deserialize)]
+pub struct ErrorMessage {
+    /// The error
+    pub error: String,
+}
+
+impl ErrorMessage {
+    /// Build an error message from a server error
+    pub fn new(error: &ServerError) -> Self {
+        Self { error: error.to_string() }
+    }
 }
 
 /// The configuration options for the price reporter server
@@ -201,6 +312,9 @@ pub struct PriceReporterConfig {
     pub token_remap_path: Option<String>,
     /// The chains to use for token remapping
     pub chains: Vec<Chain>,
+    /// The interval at which to poll the token remap for changes. If none is
+    /// provided, the remap is only reloaded through the admin API.
+    pub token_remap_poll_interval: Option<Duration>,
     /// The configuration options that may be used by exchange connections
     pub exchange_conn_config: ExchangeConnectionsConfig,
     /// The HMAC key for the admin API. If one is not provided, the admin API
@@ -208,6 +322,19 @@ pub struct PriceReporterConfig {
     pub admin_key: Option<HmacKey>,
     /// Exchanges for which to disable price reporting
     pub disabled_exchanges: Vec<Exchange>,
+    /// The configuration options for composite price streams
+    pub composite_config: CompositeConfig,
+    /// The configuration options for the price history recorder. If none is
+    /// provided, price history will not be recorded.
+    pub price_history_config: Option<PriceHistoryConfig>,
+    /// The maximum number of topics a websocket client may subscribe to,
+    /// counted across its connections for API key clients and per
+    /// connection otherwise. If none is provided, subscriptions are uncapped
+    /// except by per-key limits
+    pub max_ws_subscriptions: Option<usize>,
+    /// The API keys accepted from price consumers. If none are provided, the
+    /// websocket server and HTTP price routes are unauthenticated.
+    pub api_keys: Option<ApiKeys>,
 }
 
 // -----------
@@ -219,9 +346,23 @@ pub fn get_price_topic_str(topic: &PriceTopic) -> String {
     format!("{}-{}-{}", topic.0, topic.1, topic.2)
 }
 
-/// Get all the topics that are subscribed to in a `PriceStreamMap`
-pub fn get_subscribed_topics(subscriptions: &PriceStreamMap) -> Vec<String> {
-    subscriptions.keys().map(get_price_topic_str).collect_vec()
+/// Get all the topics that are subscribed to in a `PriceStreamMap` and
+/// `DepthStreamMap`
+pub fn get_subscribed_topics(
+    subscriptions: &PriceStreamMap,
+    depth_subscriptions: &DepthStreamMap,
+) -> Vec<String> {
+    subscriptions.keys().chain(depth_subscriptions.keys()).cloned().collect_vec()
+}
+
+/// Whether the given topic refers to an order book depth feed
+pub fn is_depth_topic(topic: &str) -> bool {
+    topic.split('-').next() == Some(DEPTH_TOPIC_PREFIX)
+}
+
+/// Get the depth topic name for a given pair info
+pub fn get_depth_topic_str(pair_info: &PairInfo) -> String {
+    format!("{DEPTH_TOPIC_PREFIX}-{}", get_price_topic_str(&pair_info.clone().into()))
 }
 
 /// Given an address, search through the token remaps to find the token and
@@ -271,18 +412,20 @@ pub fn setup_all_token_remaps(
         )),
         // If a token remap path is provided, use it
         Some(path) => {
-            set_canonical_exchange_map(Some(path.clone()), chains[0])?;
+            set_canonical_exchange_map(Some(path.clone()), &chains[..1])?;
             setup_token_remaps(Some(path), chains[0])
                 .map(|_loaded_tickers| ())
                 .map_err(err_str!(ServerError::TokenRemap))
         },
         // Otherwise, fetch remap from default location
-        None => chains.iter().try_for_each(|chain| {
-            set_canonical_exchange_map(None /* remap file */, *chain)?;
-            setup_token_remaps(None, *chain)
-                .map(|_loaded_tickers| ())
-                .map_err(err_str!(ServerError::TokenRemap))
-        }),
+        None => {
+            set_canonical_exchange_map(None /* remap file */, chains)?;
+            chains.iter().try_for_each(|chain| {
+                setup_token_remaps(None, *chain)
+                    .map(|_loaded_tickers| ())
+                    .map_err(err_str!(ServerError::TokenRemap))
+            })
+        },
     }
 }
 
@@ -1,7 +1,7 @@
 //! Types and utilities for PairInfo
 //!
 //! PairInfo is the ticker-based key we use to de-duplicate price streams. In
-//! contrast, `PriceTopic` is a tuple of (Exchange, Token, Token) that uses
+//! contrast, `PriceTopic` is a tuple of (PriceSource, Token, Token) that uses
 //! addresses for uniqueness. This is necessary in a multi-chain environment
 //! where multiple addresses can map to the same ticker.
 use std::str::FromStr;
@@ -13,12 +13,20 @@ use renegade_util::err_str;
 use crate::{
     errors::ServerError,
     exchanges::{
-        binance::BinanceConnection, coinbase::CoinbaseConnection, connection::ExchangeConnection,
-        kraken::KrakenConnection, okx::OkxConnection,
+        ExchangeConnectionsConfig,
+        binance::BinanceConnection,
+        bybit::BybitConnection,
+        coinbase::CoinbaseConnection,
+        connection::ExchangeConnection,
+        hyperliquid::HyperliquidConnection,
+        kraken::KrakenConnection,
+        okx::OkxConnection,
+        uniswap_v3::UniswapV3Connection,
+        venue::{PriceSource, Venue},
     },
     utils::{
-        PriceTopic, canonical_exchange::get_canonical_exchange, default_exchange_stable,
-        get_token_and_chain, resolve_tokens_and_chain,
+        DEPTH_TOPIC_PREFIX, PriceTopic, canonical_exchange::get_canonical_exchange,
+        default_exchange_stable, get_token_and_chain, resolve_tokens_and_chain,
     },
 };
 
@@ -27,7 +35,12 @@ use crate::{
 #[derivative(PartialEq, Eq, Hash)]
 pub struct PairInfo {
     /// The exchange
+    ///
+    /// For a pair on a venue, this is the exchange whose ticker mappings the
+    /// venue shares
     pub exchange: Exchange,
+    /// The venue, if the pair is on a venue without an upstream `Exchange`
+    pub venue: Option<Venue>,
     /// The base ticker
     pub base: String,
     /// The quote ticker
@@ -40,7 +53,7 @@ pub struct PairInfo {
 impl PairInfo {
     /// Create a new pair info
     pub fn new(exchange: Exchange, base: String, quote: String, chain: Option<Chain>) -> Self {
-        Self { exchange, base, quote, chain: chain.unwrap_or(default_chain()) }
+        Self { exchange, venue: None, base, quote, chain: chain.unwrap_or(default_chain()) }
     }
 
     /// Create a new pair info from two tokens
@@ -57,7 +70,28 @@ impl PairInfo {
             ServerError::InvalidPairInfo(format!("unable to get ticker for {}", quote))
         })?;
 
-        Ok(Self { exchange, base: base_ticker, quote: quote_ticker, chain: quote.get_chain() })
+        Ok(Self {
+            exchange,
+            venue: None,
+            base: base_ticker,
+            quote: quote_ticker,
+            chain: quote.get_chain(),
+        })
+    }
+
+    /// Place the pair on the given venue
+    pub fn with_venue(mut self, venue: Venue) -> Self {
+        self.exchange = venue.ticker_exchange();
+        self.venue = Some(venue);
+        self
+    }
+
+    /// Get the source of the pair's price
+    pub fn source(&self) -> PriceSource {
+        match self.venue {
+            Some(venue) => PriceSource::Venue(venue),
+            None => PriceSource::Exchange(self.exchange),
+        }
     }
 
     /// Create a new pair info with the default stable token of the given
@@ -96,9 +130,13 @@ impl PairInfo {
     /// Parse the pair info from a given topic
     pub fn from_topic(topic: &str) -> Result<Self, ServerError> {
         let parts: Vec<&str> = topic.split('-').collect();
-        let exchange =
-            Exchange::from_str(parts[0]).map_err(err_str!(ServerError::InvalidPairInfo))?;
+        let source =
+            PriceSource::from_str(parts[0]).map_err(err_str!(ServerError::InvalidPairInfo))?;
         let base_mint = parts[1];
+        let exchange = match source {
+            PriceSource::Exchange(exchange) => exchange,
+            PriceSource::Venue(venue) => venue.ticker_exchange(),
+        };
         // Renegade topics may omit the quote: use default stable (USDC) if so
         if exchange == Exchange::Renegade && parts.len() == 2 {
             return Self::new_default_stable(exchange, base_mint);
@@ -112,7 +150,11 @@ impl PairInfo {
             if exchange == Exchange::Renegade {
                 Self::enforce_usdc(&quote)?;
             }
-            return Self::new_from_tokens(exchange, &base, &quote);
+            let pair_info = Self::new_from_tokens(exchange, &base, &quote)?;
+            return Ok(match source {
+                PriceSource::Venue(venue) => pair_info.with_venue(venue),
+                PriceSource::Exchange(_) => pair_info,
+            });
         }
 
         Err(ServerError::InvalidPairInfo(format!(
@@ -121,6 +163,19 @@ impl PairInfo {
         )))
     }
 
+    /// Parse the pair info from a given depth topic, of the form
+    /// `depth-<exchange>-<base mint>-<quote mint>`
+    pub fn from_depth_topic(topic: &str) -> Result<Self, ServerError> {
+        let pair_topic = topic
+            .strip_prefix(DEPTH_TOPIC_PREFIX)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .ok_or_else(|| {
+                ServerError::InvalidPairInfo(format!("invalid depth topic `{topic}`"))
+            })?;
+
+        Self::from_topic(pair_topic)
+    }
+
     /// Ensure the given Token's ticker is USDC
     fn enforce_usdc(token: &Token) -> Result<(), ServerError> {
         let ticker = token.get_ticker().ok_or_else(|| {
@@ -134,7 +189,7 @@ impl PairInfo {
 
     /// Get the topic name for a given pair info as a string
     pub fn to_topic(&self) -> String {
-        format!("{}-{}-{}", self.exchange, self.base, self.quote)
+        format!("{}-{}-{}", self.source(), self.base, self.quote)
     }
 
     // --------------
@@ -143,24 +198,51 @@ impl PairInfo {
 
     /// Validate a pair info tuple, checking that the exchange supports the base
     /// and quote tokens
-    pub async fn validate_subscription(&self) -> Result<(), ServerError> {
-        let (exchange, base, quote) = (self.exchange, self.base_token(), self.quote_token());
-        if exchange == Exchange::UniswapV3 {
-            return Err(ServerError::InvalidPairInfo("UniswapV3 is not supported".to_string()));
-        }
-
-        if !self.is_supported().await? {
+    pub async fn validate_subscription(
+        &self,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<(), ServerError> {
+        let (base, quote) = (self.base_token(), self.quote_token());
+        if !self.is_supported(config).await? {
             return Err(ServerError::InvalidPairInfo(format!(
                 "{} does not support the pair ({}, {})",
-                self.exchange, base, quote
+                self.source(),
+                base,
+                quote
             )));
         }
 
         Ok(())
     }
 
+    /// Validate a depth subscription, checking that the exchange publishes a
+    /// depth feed and supports the base and quote tokens
+    pub async fn validate_depth_subscription(
+        &self,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<(), ServerError> {
+        // Venues publish prices only
+        let has_depth_feed = self.venue.is_none()
+            && match self.exchange {
+                Exchange::Binance | Exchange::Kraken | Exchange::Okx => true,
+                Exchange::Coinbase => config.coinbase_configured(),
+                _ => false,
+            };
+        if !has_depth_feed || self.is_unit_pair() {
+            return Err(ServerError::InvalidPairInfo(format!(
+                "no depth feed available for {}",
+                self.to_topic()
+            )));
+        }
+
+        self.validate_subscription(config).await
+    }
+
     /// Check if the given exchange supports the given pair
-    pub async fn is_supported(&self) -> Result<bool, ServerError> {
+    pub async fn is_supported(
+        &self,
+        config: &ExchangeConnectionsConfig,
+    ) -> Result<bool, ServerError> {
         // If the pair is a unit pair (e.g. USDT-USDT), we don't need to check
         // if the exchange supports it
         if self.is_unit_pair() {
@@ -170,6 +252,16 @@ impl PairInfo {
         let (exchange, base_token, quote_token) =
             (self.exchange, self.base_token(), self.quote_token());
 
+        match self.venue {
+            Some(Venue::Bybit) => {
+                return Ok(BybitConnection::supports_pair(&base_token, &quote_token).await?);
+            },
+            Some(Venue::Hyperliquid) => {
+                return Ok(HyperliquidConnection::supports_pair(&base_token, &quote_token).await?);
+            },
+            None => {},
+        }
+
         Ok(match exchange {
             Exchange::Binance => {
                 BinanceConnection::supports_pair(&base_token, &quote_token).await?
@@ -179,6 +271,9 @@ impl PairInfo {
             },
             Exchange::Kraken => KrakenConnection::supports_pair(&base_token, &quote_token).await?,
             Exchange::Okx => OkxConnection::supports_pair(&base_token, &quote_token).await?,
+            Exchange::UniswapV3 => {
+                UniswapV3Connection::has_pool(&base_token, &quote_token, config).await?
+            },
             Exchange::Renegade => {
                 BinanceConnection::supports_pair(&base_token, &quote_token).await?
             },
@@ -186,6 +281,18 @@ impl PairInfo {
         })
     }
 
+    /// Returns whether the pair is on its base token's canonical exchange
+    ///
+    /// Venues are never canonical
+    pub fn is_canonical(&self) -> bool {
+        if self.venue.is_some() {
+            return false;
+        }
+
+        let base_mint = self.base_token().get_addr();
+        get_canonical_exchange(&base_mint).is_ok_and(|exchange| exchange == self.exchange)
+    }
+
     /// Returns whether the pair is a unit pair
     pub fn is_unit_pair(&self) -> bool {
         self.base == self.quote
@@ -214,6 +321,23 @@ impl PairInfo {
         Ok(is_renegade && !is_canonical_coinbase && !is_canonical_stable_usdc)
     }
 
+    /// Whether the pair's quote must be converted to obtain a USDC price
+    ///
+    /// Coinbase quotes in USD, which we treat as equivalent to USDC.
+    pub fn requires_usdc_conversion(&self) -> bool {
+        self.exchange != Exchange::Coinbase && self.quote != USDC_TICKER
+    }
+
+    /// Whether the pair is the quote conversion pair of its exchange, i.e.
+    /// USDC priced in the exchange's default stable
+    pub fn is_conversion_pair(&self) -> bool {
+        let stable = default_exchange_stable(&self.exchange, self.chain).get_ticker();
+        self.venue.is_none()
+            && self.base == USDC_TICKER
+            && !self.is_unit_pair()
+            && stable.is_some_and(|stable| stable == self.quote)
+    }
+
     /// Get the pair info for the quote conversion pair
     ///
     /// This is just the price of USDC against the default stable of the
@@ -226,6 +350,6 @@ impl PairInfo {
 
 impl From<PairInfo> for PriceTopic {
     fn from(pair_info: PairInfo) -> Self {
-        (pair_info.exchange, pair_info.base_token(), pair_info.quote_token())
+        (pair_info.source(), pair_info.base_token(), pair_info.quote_token())
     }
 }
@@ -0,0 +1,125 @@
+//! The price update published on each price stream
+//!
+//! Alongside the price itself, an update carries the metadata consumers need
+//! to judge its freshness: the exchange or venue that sourced it, the
+//! exchange's own timestamp for the tick, and the local time at which it was
+//! received.
+
+use renegade_types_core::Price;
+
+use crate::{
+    exchanges::{connection::ExchangePrice, venue::PriceSource},
+    utils::HEARTBEAT_REPLAY_WARN_AGE,
+};
+
+/// The source name reported for prices that are not sourced from a single
+/// exchange
+pub const COMPOSITE_SOURCE: &str = "composite";
+
+/// A price published on a price stream, along with its provenance
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceUpdate {
+    /// The price
+    pub price: Price,
+    /// The exchange or venue from which the price was sourced, or `None` for
+    /// composite prices
+    pub source: Option<PriceSource>,
+    /// The time at which the exchange reported the price, in milliseconds
+    /// since the epoch, if the exchange reports one
+    pub exchange_timestamp: Option<u64>,
+    /// The local time at which the price was received from the exchange, in
+    /// milliseconds since the epoch
+    ///
+    /// Heartbeat replays of a cached price keep the original receive time, so
+    /// this reflects the last real exchange update.
+    pub received_at: u64,
+}
+
+impl PriceUpdate {
+    /// Construct a price update for a price received from the given source
+    pub fn new(price: ExchangePrice, source: PriceSource, received_at: u64) -> Self {
+        Self {
+            price: price.price,
+            source: Some(source),
+            exchange_timestamp: price.exchange_timestamp,
+            received_at,
+        }
+    }
+
+    /// Construct a price update for a composite price, received at the given
+    /// time
+    pub fn new_composite(price: Price, received_at: u64) -> Self {
+        Self { price, source: None, exchange_timestamp: None, received_at }
+    }
+
+    /// Whether this is the placeholder value used to initialize a price
+    /// channel, rather than a real price
+    pub fn is_initial(&self) -> bool {
+        self.price == 0.0
+    }
+
+    /// The name of the update's source
+    pub fn source_name(&self) -> String {
+        self.source.map(|source| source.to_string()).unwrap_or(COMPOSITE_SOURCE.to_string())
+    }
+
+    /// The age of the update at the given time, in milliseconds
+    pub fn age_ms(&self, now: u64) -> u64 {
+        now.saturating_sub(self.received_at)
+    }
+
+    /// Whether the update is stale at the given time
+    pub fn is_stale(&self, now: u64) -> bool {
+        self.age_ms(now) > HEARTBEAT_REPLAY_WARN_AGE.as_millis() as u64
+    }
+
+    /// Convert the update's price by the given conversion update
+    ///
+    /// The converted update keeps this update's source and exchange timestamp,
+    /// and is only as fresh as the older of the two updates.
+    pub fn convert(&self, conversion: &PriceUpdate) -> Self {
+        Self {
+            price: self.price / conversion.price,
+            received_at: self.received_at.min(conversion.received_at),
+            ..*self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use renegade_types_core::Exchange;
+
+    use super::*;
+
+    #[test]
+    fn age_and_staleness() {
+        let update = PriceUpdate::new_composite(100., 1_000);
+        let stale_after = HEARTBEAT_REPLAY_WARN_AGE.as_millis() as u64;
+
+        assert_eq!(update.age_ms(1_500), 500);
+        assert_eq!(update.age_ms(500), 0);
+        assert!(!update.is_stale(1_000 + stale_after));
+        assert!(update.is_stale(1_001 + stale_after));
+    }
+
+    #[test]
+    fn conversion_keeps_source_and_oldest_receive_time() {
+        let price = ExchangePrice::new_with_timestamp(200., 900);
+        let binance = PriceSource::Exchange(Exchange::Binance);
+        let update = PriceUpdate::new(price, binance, 1_000);
+        let conversion = PriceUpdate::new(2.0.into(), binance, 800);
+
+        let converted = update.convert(&conversion);
+        assert_eq!(converted.price, 100.);
+        assert_eq!(converted.source, Some(binance));
+        assert_eq!(converted.exchange_timestamp, Some(900));
+        assert_eq!(converted.received_at, 800);
+    }
+
+    #[test]
+    fn composite_source_name() {
+        assert_eq!(PriceUpdate::new_composite(1., 0).source_name(), COMPOSITE_SOURCE);
+        assert!(PriceUpdate::default().is_initial());
+    }
+}
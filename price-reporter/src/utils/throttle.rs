@@ -0,0 +1,231 @@
+//! Per-subscription throttling of price streams
+//!
+//! A websocket client may request, per subscription, a minimum interval
+//! between price messages and a minimum relative change from the last price
+//! sent. Updates which do not clear the change threshold are dropped, and
+//! updates arriving within the interval are conflated to the latest value,
+//! which is sent once the interval elapses.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_util::{Stream, StreamExt};
+use renegade_types_core::Price;
+use serde::Deserialize;
+use tokio::time::{Instant, Sleep, sleep_until};
+
+use crate::{
+    errors::ServerError,
+    utils::{BPS_PER_ONE, PriceStream, PriceUpdate},
+};
+
+/// The throttling options a client may attach to a price subscription
+///
+/// The options are read from the subscribe message alongside its topic, and
+/// are all optional; a subscription without options receives every update.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub struct SubscriptionOptions {
+    /// The minimum interval between price messages, in milliseconds
+    #[serde(default)]
+    pub min_interval_ms: Option<u64>,
+    /// The minimum change from the last price sent, in basis points
+    #[serde(default)]
+    pub min_change_bps: Option<f64>,
+}
+
+impl SubscriptionOptions {
+    /// Validate the options
+    pub fn validate(&self) -> Result<(), ServerError> {
+        match self.min_change_bps {
+            Some(bps) if !(bps.is_finite() && bps >= 0.) => Err(ServerError::InvalidRequest(
+                format!("min_change_bps must be a non-negative number, got {bps}"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The minimum interval between price messages, if any
+    fn min_interval(&self) -> Option<Duration> {
+        self.min_interval_ms.map(Duration::from_millis)
+    }
+}
+
+/// The throttling state of a single subscription
+#[derive(Clone, Debug, Default)]
+struct Throttle {
+    /// The subscription's options
+    options: SubscriptionOptions,
+    /// The time at which the last price was sent, and the price
+    last_sent: Option<(Instant, Price)>,
+}
+
+impl Throttle {
+    /// Whether the update moves far enough from the last price sent to be
+    /// forwarded
+    fn admits(&self, update: &PriceUpdate) -> bool {
+        match (self.options.min_change_bps, self.last_sent) {
+            (Some(min_bps), Some((_, last_price))) => {
+                let change_bps = (update.price - last_price).abs() / last_price * BPS_PER_ONE;
+                change_bps >= min_bps
+            },
+            _ => true,
+        }
+    }
+
+    /// The earliest time at which the next price may be sent, if limited
+    fn next_send_at(&self) -> Option<Instant> {
+        let interval = self.options.min_interval()?;
+        self.last_sent.map(|(sent_at, _)| sent_at + interval)
+    }
+
+    /// Record that a price was sent at the given time
+    fn record_sent(&mut self, now: Instant, price: Price) {
+        self.last_sent = Some((now, price));
+    }
+}
+
+/// A price stream throttled and conflated according to a subscription's
+/// options
+pub struct ThrottledPriceStream {
+    /// The underlying price stream
+    inner: PriceStream,
+    /// The throttling state
+    throttle: Throttle,
+    /// The latest admitted update which has not yet been sent
+    pending: Option<PriceUpdate>,
+    /// Whether the underlying stream has ended
+    ///
+    /// The pending update, if any, is still sent before the throttled stream
+    /// ends.
+    inner_closed: bool,
+    /// The timer waking the stream once the pending update may be sent
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl ThrottledPriceStream {
+    /// Throttle the given price stream with the given options
+    pub fn new(inner: PriceStream, options: SubscriptionOptions) -> Self {
+        let throttle = Throttle { options, last_sent: None };
+        Self { inner, throttle, pending: None, inner_closed: false, delay: None }
+    }
+
+    /// Replace the underlying price stream, keeping the throttling state
+    pub fn replace_inner(&mut self, inner: PriceStream) {
+        self.inner = inner;
+        self.inner_closed = false;
+    }
+}
+
+impl Stream for ThrottledPriceStream {
+    type Item = PriceUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // Drain the underlying stream, conflating to the latest update. An update
+        // which falls back within the change threshold supersedes an admitted one
+        while !this.inner_closed {
+            match this.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(update)) => {
+                    this.pending = this.throttle.admits(&update).then_some(update);
+                },
+                Poll::Ready(None) => this.inner_closed = true,
+                Poll::Pending => break,
+            }
+        }
+
+        // The stream only ends once the pending update has been flushed
+        let Some(update) = this.pending else {
+            return if this.inner_closed { Poll::Ready(None) } else { Poll::Pending };
+        };
+
+        // Wait out the minimum interval since the last price sent
+        let now = Instant::now();
+        let send_at = this.throttle.next_send_at().filter(|send_at| now < *send_at);
+        if let Some(send_at) = send_at {
+            let delay = this.delay.get_or_insert_with(|| Box::pin(sleep_until(send_at)));
+            if delay.deadline() != send_at {
+                delay.as_mut().reset(send_at);
+            }
+
+            if delay.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        this.delay = None;
+        this.pending = None;
+        this.throttle.record_sent(now, update.price);
+        Poll::Ready(Some(update))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a throttle which last sent the given price at the given time
+    fn throttle_after(options: SubscriptionOptions, sent_at: Instant, price: Price) -> Throttle {
+        Throttle { options, last_sent: Some((sent_at, price)) }
+    }
+
+    #[test]
+    fn change_threshold_filters_small_moves() {
+        let options = SubscriptionOptions { min_change_bps: Some(10.), ..Default::default() };
+        let throttle = throttle_after(options, Instant::now(), 1_000.);
+
+        assert!(!throttle.admits(&PriceUpdate::new_composite(1_000.5, 0)));
+        assert!(throttle.admits(&PriceUpdate::new_composite(1_001., 0)));
+        assert!(throttle.admits(&PriceUpdate::new_composite(998., 0)));
+
+        // The first update is always admitted
+        let fresh = Throttle { options, last_sent: None };
+        assert!(fresh.admits(&PriceUpdate::new_composite(1., 0)));
+    }
+
+    #[test]
+    fn interval_delays_next_send() {
+        let now = Instant::now();
+        let options = SubscriptionOptions { min_interval_ms: Some(500), ..Default::default() };
+
+        assert_eq!(Throttle { options, last_sent: None }.next_send_at(), None);
+        let throttle = throttle_after(options, now, 1.);
+        assert_eq!(throttle.next_send_at(), Some(now + Duration::from_millis(500)));
+
+        let unlimited = throttle_after(SubscriptionOptions::default(), now, 1.);
+        assert_eq!(unlimited.next_send_at(), None);
+    }
+
+    #[tokio::test]
+    async fn pending_update_is_flushed_when_inner_ends() {
+        let (price_tx, price_rx) = tokio::sync::watch::channel(PriceUpdate::default());
+        let options = SubscriptionOptions { min_interval_ms: Some(20), ..Default::default() };
+        let mut stream = ThrottledPriceStream::new(PriceStream::new(price_rx.into()), options);
+
+        price_tx.send(PriceUpdate::new_composite(1., 0)).unwrap();
+        assert_eq!(stream.next().await.map(|update| update.price), Some(1.));
+
+        // The second update arrives within the interval, so it is still pending
+        // when the underlying stream ends
+        price_tx.send(PriceUpdate::new_composite(2., 0)).unwrap();
+        drop(price_tx);
+        assert_eq!(stream.next().await.map(|update| update.price), Some(2.));
+        assert_eq!(stream.next().await, None);
+    }
+
+    #[test]
+    fn options_parse_from_subscribe_message() {
+        let msg = r#"{"method":"subscribe","topic":"binance-WETH-USDT","min_interval_ms":250}"#;
+        let options: SubscriptionOptions = serde_json::from_str(msg).unwrap();
+        assert_eq!(options.min_interval_ms, Some(250));
+        assert_eq!(options.min_change_bps, None);
+
+        let invalid = SubscriptionOptions { min_change_bps: Some(-1.), ..Default::default() };
+        assert!(invalid.validate().is_err());
+        assert!(SubscriptionOptions::default().validate().is_ok());
+    }
+}
@@ -5,24 +5,122 @@ use std::net::SocketAddr;
 
 use futures_util::{SinkExt, StreamExt};
 use renegade_api::websocket::{SubscriptionResponse, WebsocketMessage};
+use renegade_types_core::Exchange;
 use renegade_util::err_str;
-use tokio::net::TcpStream;
+use tokio::{net::TcpStream, sync::broadcast::error::RecvError};
 use tokio_stream::StreamMap;
-use tokio_tungstenite::accept_async;
-use tungstenite::Message;
+use tokio_tungstenite::accept_hdr_async;
+use tungstenite::{
+    Message,
+    handshake::server::{
+        ErrorResponse, Request as HandshakeRequest, Response as HandshakeResponse,
+    },
+    http::StatusCode,
+};
 
 use crate::{
+    api_keys::{API_KEY_HEADER, ApiClient, ApiKeys},
+    composite::{CompositePair, is_composite_topic},
     errors::ServerError,
     exchanges::ExchangeConnectionsConfig,
     log_task,
     logger::{Outcome, Task},
     price_stream_manager::GlobalPriceStreams,
     utils::{
-        PairInfo, PriceMessage, PriceStreamMap, WsWriteStream, get_price_topic_str,
-        get_subscribed_topics,
+        DepthMessage, DepthStreamMap, ErrorMessage, PairInfo, PriceMessage, PriceStreamMap,
+        SubscriptionOptions, ThrottledPriceStream, WsWriteStream, get_depth_topic_str,
+        get_price_topic_str, get_subscribed_topics, is_depth_topic,
     },
 };
 
+/// The subscriptions of a single websocket connection
+///
+/// When a cap is configured, an authenticated client's subscriptions are
+/// capped across all of its connections, an anonymous connection's are capped
+/// per connection.
+struct Subscriptions {
+    /// The price subscriptions, indexed by topic
+    prices: PriceStreamMap,
+    /// The depth subscriptions, indexed by topic
+    depth: DepthStreamMap,
+    /// The maximum number of topics the client may subscribe to, if capped
+    max_topics: Option<usize>,
+    /// The authenticated client holding the connection, if any
+    client: Option<ApiClient>,
+}
+
+impl Subscriptions {
+    /// Create an empty set of subscriptions with the given cap, if any
+    fn new(max_topics: Option<usize>, client: Option<ApiClient>) -> Self {
+        Self { prices: StreamMap::new(), depth: StreamMap::new(), max_topics, client }
+    }
+
+    /// The number of subscribed topics
+    fn len(&self) -> usize {
+        self.prices.len() + self.depth.len()
+    }
+
+    /// Whether the connection is subscribed to the given topic
+    fn contains(&self, topic: &str) -> bool {
+        self.prices.contains_key(topic) || self.depth.contains_key(topic)
+    }
+
+    /// Reserve capacity for a subscription to the given topic, returning
+    /// whether a new slot was taken
+    ///
+    /// Resubscribing to a topic takes no new slot.
+    fn reserve(&self, topic: &str) -> Result<bool, ServerError> {
+        if self.contains(topic) {
+            return Ok(false);
+        }
+
+        match &self.client {
+            Some(client) => client.acquire_subscription(self.max_topics)?,
+            None => match self.max_topics {
+                Some(max) if self.len() >= max => {
+                    return Err(ServerError::TooManySubscriptions(max));
+                },
+                _ => {},
+            },
+        }
+
+        Ok(true)
+    }
+
+    /// Release `n` reserved slots
+    fn release(&self, n: usize) {
+        if let Some(client) = &self.client {
+            client.release_subscriptions(n);
+        }
+    }
+
+    /// Remove the subscription to the given topic, releasing its slot
+    fn remove(&mut self, topic: &str) {
+        let removed = self.prices.remove(topic).is_some() || self.depth.remove(topic).is_some();
+        if removed {
+            self.release(1);
+        }
+    }
+
+    /// Get all the subscribed topics
+    fn topics(&self) -> Vec<String> {
+        get_subscribed_topics(&self.prices, &self.depth)
+    }
+
+    /// Get the subscribed `Renegade` price topics
+    fn renegade_topics(&self) -> Vec<String> {
+        let prefix = format!("{}-", Exchange::Renegade);
+        self.prices.keys().filter(|topic| topic.starts_with(&prefix)).cloned().collect()
+    }
+}
+
+impl Drop for Subscriptions {
+    /// Release the connection's slots when it closes
+    fn drop(&mut self) {
+        self.release(self.len());
+    }
+}
+
 // ----------
 // | SERVER |
 // ----------
@@ -33,6 +131,8 @@ pub async fn handle_connection(
     stream: TcpStream,
     global_price_streams: GlobalPriceStreams,
     config: ExchangeConnectionsConfig,
+    max_subscriptions: Option<usize>,
+    api_keys: Option<ApiKeys>,
 ) -> Result<(), ServerError> {
     let peer_addr = stream.peer_addr().map_err(ServerError::GetPeerAddr)?;
 
@@ -43,20 +143,39 @@ pub async fn handle_connection(
         "accepting websocket connection"
     );
 
-    let websocket_stream =
-        accept_async(stream).await.map_err(err_str!(ServerError::WebsocketConnection))?;
+    // Authenticate the client during the handshake, if API keys are configured
+    let mut client = None;
+    let websocket_stream = accept_hdr_async(stream, |req: &HandshakeRequest, resp| {
+        client = authenticate_handshake(req, api_keys.as_ref(), peer_addr)?;
+        Ok(resp)
+    })
+    .await
+    .map_err(err_str!(ServerError::WebsocketConnection))?;
     let (mut write_stream, mut read_stream) = websocket_stream.split();
 
-    let mut subscriptions = StreamMap::new();
+    let max_subscriptions =
+        client.as_ref().map_or(max_subscriptions, |c| c.subscription_limit(max_subscriptions));
+    let mut subscriptions = Subscriptions::new(max_subscriptions, client.clone());
+    let mut renegade_restarts = global_price_streams.renegade_restarts.subscribe();
 
     loop {
         tokio::select! {
             // Send the next price to the client
-            Some((topic, price)) = subscriptions.next() => {
+            Some((topic, price)) = subscriptions.prices.next() => {
                 // The potential error in `price_res` here is a `BroadcastStreamRecvError::Lagged`,
                 // meaning the stream lagged receiving price updates. We can safely ignore this.
-                let topic = get_price_topic_str(&topic);
-                let message = PriceMessage { topic, price };
+                let message = PriceMessage::new(topic, &price);
+                let message_ser = serde_json::to_string(&message).map_err(err_str!(ServerError::Serde))?;
+                write_stream
+                    .send(Message::Text(message_ser))
+                    .await
+                    .map_err(err_str!(ServerError::WebsocketSend))?;
+            }
+
+            // Send the next depth snapshot to the client. Empty snapshots are
+            // published while a depth feed reconnects, and are not forwarded
+            Some((topic, Some(depth))) = subscriptions.depth.next() => {
+                let message = DepthMessage { topic, depth };
                 let message_ser = serde_json::to_string(&message).map_err(err_str!(ServerError::Serde))?;
                 write_stream
                     .send(Message::Text(message_ser))
@@ -64,6 +183,20 @@ pub async fn handle_connection(
                     .map_err(err_str!(ServerError::WebsocketSend))?;
             }
 
+            // Re-resolve the `Renegade` streams whose canonical exchange changed.
+            // If restarts were missed, every `Renegade` stream is re-resolved
+            restart = renegade_restarts.recv() => {
+                let topics = match restart {
+                    Ok(topics) => topics,
+                    Err(RecvError::Lagged(_)) => subscriptions.renegade_topics(),
+                    // The sender is held by the global streams, so the channel
+                    // never closes while the connection is open
+                    Err(RecvError::Closed) => continue,
+                };
+                restart_price_streams(&topics, &mut subscriptions, &global_price_streams, &config)
+                    .await;
+            }
+
             // Handle incoming websocket messages
             message = read_stream.next() => {
                 match message {
@@ -80,6 +213,7 @@ pub async fn handle_connection(
                                     global_price_streams.clone(),
                                     config.clone(),
                                     peer_addr,
+                                    client.as_ref(),
                                 ).await?;
                             }
                         }
@@ -106,19 +240,30 @@ pub async fn handle_connection(
 /// Handles an incoming websocket message
 async fn handle_ws_message(
     message: Message,
-    subscriptions: &mut PriceStreamMap,
+    subscriptions: &mut Subscriptions,
     write_stream: &mut WsWriteStream,
     global_price_streams: GlobalPriceStreams,
     config: ExchangeConnectionsConfig,
     peer_addr: SocketAddr,
+    client: Option<&ApiClient>,
 ) -> Result<(), ServerError> {
     if let Message::Text(msg_text) = message {
-        let msg_deser: Result<WebsocketMessage, _> = serde_json::from_str(&msg_text);
+        // Each message consumes from the client's rate limit
+        if let Err(e) = client.map(ApiClient::check_rate_limit).transpose() {
+            let resp = Message::Text(error_message(&e)?);
+            return write_stream.send(resp).await.map_err(err_str!(ServerError::WebsocketSend));
+        }
+
+        // Throttling options are carried alongside the topic in subscribe messages
+        let msg_deser: Result<(WebsocketMessage, SubscriptionOptions), _> =
+            serde_json::from_str(&msg_text)
+                .and_then(|msg| Ok((msg, serde_json::from_str(&msg_text)?)));
         let resp = match msg_deser {
             // Valid message body
-            Ok(msg) => {
+            Ok((msg, options)) => {
                 let response = match handle_subscription_message(
                     msg,
+                    options,
                     subscriptions,
                     global_price_streams,
                     config,
@@ -127,14 +272,14 @@ async fn handle_ws_message(
                 .await
                 {
                     Ok(res) => serde_json::to_string(&res).map_err(err_str!(ServerError::Serde))?,
-                    Err(e) => e.to_string(),
+                    Err(e) => error_message(&e)?,
                 };
 
                 Message::Text(response)
             },
 
             // Respond with an error if deserialization fails
-            Err(e) => Message::Text(format!("Invalid request: {}", e)),
+            Err(e) => Message::Text(error_message(&ServerError::InvalidRequest(e.to_string()))?),
         };
 
         // Write out the response over the websocket
@@ -147,7 +292,8 @@ async fn handle_ws_message(
 /// Handles an incoming un/subscribe message
 async fn handle_subscription_message(
     message: WebsocketMessage,
-    subscriptions: &mut PriceStreamMap,
+    options: SubscriptionOptions,
+    subscriptions: &mut Subscriptions,
     global_price_streams: GlobalPriceStreams,
     config: ExchangeConnectionsConfig,
     peer_addr: SocketAddr,
@@ -161,12 +307,14 @@ async fn handle_subscription_message(
                 subject = %topic,
                 "client subscribed"
             );
-            let pair_info = PairInfo::from_topic(&topic)?;
-            let stream = global_price_streams
-                .get_or_create_price_stream(pair_info.clone(), config.clone())
-                .await?;
+            let reserved = subscriptions.reserve(&canonical_topic(&topic)?)?;
+            let res = subscribe(&topic, options, subscriptions, global_price_streams, config).await;
 
-            subscriptions.insert(pair_info.into(), stream);
+            // Free the reserved slot if the subscription failed
+            if res.is_err() && reserved {
+                subscriptions.release(1);
+            }
+            res?;
         },
         WebsocketMessage::Unsubscribe { topic } => {
             log_task!(
@@ -176,10 +324,167 @@ async fn handle_subscription_message(
                 subject = %topic,
                 "client unsubscribed"
             );
-            let pair_info = PairInfo::from_topic(&topic)?;
-            subscriptions.remove(&pair_info.into());
+            subscriptions.remove(&canonical_topic(&topic)?);
         },
     };
 
-    Ok(SubscriptionResponse { subscriptions: get_subscribed_topics(subscriptions) })
+    Ok(SubscriptionResponse { subscriptions: subscriptions.topics() })
+}
+
+/// Subscribe the connection to the given topic
+async fn subscribe(
+    topic: &str,
+    options: SubscriptionOptions,
+    subscriptions: &mut Subscriptions,
+    global_price_streams: GlobalPriceStreams,
+    config: ExchangeConnectionsConfig,
+) -> Result<(), ServerError> {
+    if is_depth_topic(topic) {
+        let pair_info = PairInfo::from_depth_topic(topic)?;
+        let depth_rx =
+            global_price_streams.get_or_create_depth_receiver(pair_info.clone(), config).await?;
+        subscriptions.depth.insert(get_depth_topic_str(&pair_info), depth_rx.into());
+    } else {
+        options.validate()?;
+        let (topic, stream) =
+            global_price_streams.get_or_create_topic_stream(topic, config).await?;
+        subscriptions.prices.insert(topic, ThrottledPriceStream::new(stream, options));
+    }
+
+    Ok(())
+}
+
+/// Replace the connection's streams on the given price topics with freshly
+/// resolved ones, keeping their throttling
+///
+/// A topic whose stream cannot be resolved is left on its previous stream.
+async fn restart_price_streams(
+    topics: &[String],
+    subscriptions: &mut Subscriptions,
+    global_price_streams: &GlobalPriceStreams,
+    config: &ExchangeConnectionsConfig,
+) {
+    for topic in topics {
+        let Some(subscription) = subscriptions.prices.iter_mut().find(|(t, _)| t == topic) else {
+            continue;
+        };
+
+        match global_price_streams.get_or_create_topic_stream(topic, config.clone()).await {
+            Ok((_, stream)) => subscription.1.replace_inner(stream),
+            Err(e) => log_task!(
+                Task::WsServer,
+                Outcome::Failed,
+                subject = %topic,
+                error = %e,
+                "error restarting price stream"
+            ),
+        }
+    }
+}
+
+/// Serialize an error into the JSON error message sent to clients
+fn error_message(error: &ServerError) -> Result<String, ServerError> {
+    serde_json::to_string(&ErrorMessage::new(error)).map_err(err_str!(ServerError::Serde))
+}
+
+/// Get the canonical form of a topic, as used to key subscriptions
+fn canonical_topic(topic: &str) -> Result<String, ServerError> {
+    if is_depth_topic(topic) {
+        return Ok(get_depth_topic_str(&PairInfo::from_depth_topic(topic)?));
+    }
+
+    if is_composite_topic(topic) {
+        return Ok(CompositePair::from_topic(topic)?.to_topic());
+    }
+
+    let pair_info = PairInfo::from_topic(topic)?;
+    Ok(get_price_topic_str(&pair_info.into()))
+}
+
+/// Authenticate a websocket handshake against the configured API keys, if
+/// any, rejecting the upgrade for unknown or rate limited clients
+fn authenticate_handshake(
+    req: &HandshakeRequest,
+    api_keys: Option<&ApiKeys>,
+    peer_addr: SocketAddr,
+) -> Result<Option<ApiClient>, ErrorResponse> {
+    let Some(api_keys) = api_keys else {
+        return Ok(None);
+    };
+
+    let key = req.headers().get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+    let res =
+        api_keys.authenticate(key).and_then(|client| client.check_rate_limit().map(|_| client));
+
+    match res {
+        Ok(client) => {
+            log_task!(
+                Task::WsServer,
+                Outcome::Ok,
+                subject = %peer_addr,
+                client = %client.name,
+                "authenticated websocket client"
+            );
+            Ok(Some(client))
+        },
+        Err(e) => {
+            log_task!(
+                Task::WsServer,
+                Outcome::Failed,
+                subject = %peer_addr,
+                error = %e,
+                "rejected websocket client"
+            );
+
+            let status = match e {
+                ServerError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+                _ => StatusCode::UNAUTHORIZED,
+            };
+            let mut resp = ErrorResponse::new(Some(e.to_string()));
+            *resp.status_mut() = status;
+            Err(resp)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api_keys::ApiKeyEntry;
+
+    use super::*;
+
+    #[test]
+    fn keyed_limits_span_connections_and_anonymous_limits_do_not() {
+        let keys = ApiKeys::new(vec![ApiKeyEntry {
+            key: "secret".to_string(),
+            name: "partner".to_string(),
+            max_subscriptions: Some(1),
+            requests_per_minute: None,
+        }]);
+        let client = keys.authenticate(Some("secret")).unwrap();
+
+        let keyed1 = Subscriptions::new(Some(1), Some(client.clone()));
+        let keyed2 = Subscriptions::new(Some(1), Some(client));
+        assert!(keyed1.reserve("binance-WETH-USDT").unwrap());
+        assert!(matches!(
+            keyed2.reserve("binance-WBTC-USDT"),
+            Err(ServerError::TooManySubscriptions(1))
+        ));
+
+        let anonymous1 = Subscriptions::new(Some(1), None);
+        let anonymous2 = Subscriptions::new(Some(1), None);
+        assert!(anonymous1.reserve("binance-WETH-USDT").unwrap());
+        assert!(anonymous2.reserve("binance-WBTC-USDT").unwrap());
+
+        // Without a configured cap, subscriptions are uncapped
+        let uncapped = Subscriptions::new(None, None);
+        assert!(uncapped.reserve("binance-WETH-USDT").unwrap());
+    }
+
+    #[test]
+    fn errors_are_sent_as_json() {
+        let message = error_message(&ServerError::RateLimited("partner".to_string())).unwrap();
+        let parsed: ErrorMessage = serde_json::from_str(&message).unwrap();
+        assert_eq!(parsed.error, "Rate limit exceeded for client partner");
+    }
 }